@@ -0,0 +1,873 @@
+//! ACL users, with the commands, keys and channels they may access, and the log of the
+//! accesses they were refused.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs,
+    time::SystemTime,
+};
+
+use crate::{
+    cmd::table::{self, Access},
+    error::RedisError,
+    util::{glob, hex, sha256::sha256, time::to_unix_millis},
+};
+
+pub(crate) const DEFAULT_USER: &str = "default";
+
+/// Entries kept in the ACL log, the newest first.
+const LOG_MAX_LEN: usize = 128;
+/// Refusals within this long of a logged one with the same details are counted in it.
+const LOG_GROUPING_MILLIS: u64 = 60_000;
+
+/// What an allow or deny rule for commands covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CommandRule {
+    Category(&'static str, u32),
+    /// A command, or a subcommand as `command|subcommand`.
+    Command(String),
+}
+
+impl CommandRule {
+    fn matches(&self, access: &Access) -> bool {
+        match self {
+            CommandRule::Category(_, categories) => access.categories & categories != 0,
+            CommandRule::Command(name) => {
+                *name == access.name
+                    || access
+                        .name
+                        .split_once('|')
+                        .is_some_and(|(command, _)| name == command)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeyPattern {
+    pattern: String,
+    read: bool,
+    write: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct User {
+    enabled: bool,
+    nopass: bool,
+    /// SHA-256 digests of the passwords, in hex.
+    passwords: BTreeSet<String>,
+    /// Allow and deny rules in the order they were given, the last matching one deciding.
+    commands: Vec<(bool, CommandRule)>,
+    keys: Vec<KeyPattern>,
+    channels: Vec<String>,
+}
+
+impl User {
+    /// A user that is disabled and can't do anything until rules are added.
+    fn new() -> Self {
+        User {
+            enabled: false,
+            nopass: false,
+            passwords: BTreeSet::new(),
+            commands: vec![(false, CommandRule::Category("all", table::ALL))],
+            keys: Vec::new(),
+            channels: Vec::new(),
+        }
+    }
+
+    /// The default user, which can do anything without a password.
+    fn default_user() -> Self {
+        let mut user = User::new();
+        for rule in ["on", "nopass", "~*", "&*", "+@all"] {
+            user.apply(rule).expect("valid default rule");
+        }
+        user
+    }
+
+    /// Applies one ACL rule, such as `+@read` or `~cache:*`, returning why it is invalid if
+    /// it is. Command names are checked by [`Acl::set_user`], which knows those of modules.
+    fn apply(&mut self, rule: &str) -> Result<(), &'static str> {
+        match rule.to_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => return self.apply("~*"),
+            "resetkeys" => self.keys.clear(),
+            "allchannels" => return self.apply("&*"),
+            "resetchannels" => self.channels.clear(),
+            "allcommands" => return self.apply("+@all"),
+            "nocommands" => return self.apply("-@all"),
+            "reset" => *self = User::new(),
+            _ => return self.apply_pattern(rule),
+        }
+
+        Ok(())
+    }
+
+    fn apply_pattern(&mut self, rule: &str) -> Result<(), &'static str> {
+        let (op, rest) = rule.split_at(rule.chars().next().map_or(0, char::len_utf8));
+
+        match op {
+            ">" => {
+                self.passwords.insert(hash(rest));
+                self.nopass = false;
+            }
+            "<" if !self.passwords.remove(&hash(rest)) => {
+                return Err("The password you are trying to remove from the user does not exist")
+            }
+            "<" => {}
+            "#" => {
+                let valid = rest.len() == 64
+                    && rest.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'));
+                if !valid {
+                    return Err(
+                        "The password hash must be exactly 64 characters and contain \
+                                only lowercase hexadecimal characters",
+                    );
+                }
+                self.passwords.insert(rest.to_owned());
+                self.nopass = false;
+            }
+            "!" if !self.passwords.remove(rest) => {
+                return Err("The password you are trying to remove from the user does not exist")
+            }
+            "!" => {}
+            "~" | "%" => {
+                let (perms, pattern) = match op {
+                    "~" => ("RW", rest),
+                    _ => rest.split_once('~').ok_or("Syntax error")?,
+                };
+                let read = perms.contains(['R', 'r']);
+                let write = perms.contains(['W', 'w']);
+                if perms.is_empty() || perms.chars().any(|c| !"RWrw".contains(c)) {
+                    return Err("Syntax error");
+                }
+
+                if pattern == "*" && read && write {
+                    self.keys.clear();
+                }
+                let pattern = KeyPattern {
+                    pattern: pattern.to_owned(),
+                    read,
+                    write,
+                };
+                if !self.keys.contains(&pattern) {
+                    self.keys.push(pattern);
+                }
+            }
+            "&" => {
+                if rest == "*" {
+                    self.channels.clear();
+                }
+                if !self.channels.iter().any(|c| c == rest) {
+                    self.channels.push(rest.to_owned());
+                }
+            }
+            "+" | "-" => {
+                let allow = op == "+";
+                let rule = match rest.strip_prefix('@') {
+                    Some(category) => {
+                        let category = category.to_lowercase();
+                        let (name, bits) = match category.as_str() {
+                            "all" => ("all", table::ALL),
+                            _ => *table::CATEGORIES
+                                .iter()
+                                .find(|(name, _)| *name == category)
+                                .ok_or("Unknown command or category name in ACL")?,
+                        };
+                        CommandRule::Category(name, bits)
+                    }
+                    None if rest.is_empty() => {
+                        return Err("Unknown command or category name in ACL")
+                    }
+                    None => CommandRule::Command(rest.to_lowercase()),
+                };
+
+                // Allowing or denying everything overrides what came before.
+                if rule == CommandRule::Category("all", table::ALL) {
+                    self.commands.clear();
+                }
+                self.commands.push((allow, rule));
+            }
+            _ => return Err("Syntax error"),
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn flags(&self) -> Vec<String> {
+        let mut flags = vec![String::from(if self.enabled { "on" } else { "off" })];
+        if self.nopass {
+            flags.push(String::from("nopass"));
+        }
+        flags
+    }
+
+    pub(crate) fn passwords(&self) -> Vec<String> {
+        self.passwords.iter().cloned().collect()
+    }
+
+    pub(crate) fn describe_commands(&self) -> String {
+        self.commands
+            .iter()
+            .map(|(allow, rule)| {
+                let op = if *allow { '+' } else { '-' };
+                match rule {
+                    CommandRule::Category(name, _) => format!("{op}@{name}"),
+                    CommandRule::Command(name) => format!("{op}{name}"),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub(crate) fn describe_keys(&self) -> String {
+        self.keys
+            .iter()
+            .map(|k| match (k.read, k.write) {
+                (true, true) => format!("~{}", k.pattern),
+                (true, false) => format!("%R~{}", k.pattern),
+                _ => format!("%W~{}", k.pattern),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub(crate) fn describe_channels(&self) -> String {
+        self.channels
+            .iter()
+            .map(|c| format!("&{c}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The user as a list of rules, as in ACL LIST and ACL files.
+    fn describe(&self) -> String {
+        let mut rules = self.flags();
+        rules.extend(self.passwords.iter().map(|p| format!("#{p}")));
+        if !self.keys.is_empty() {
+            rules.push(self.describe_keys());
+        }
+        rules.push(match self.channels.is_empty() {
+            true => String::from("resetchannels"),
+            false => self.describe_channels(),
+        });
+        rules.push(self.describe_commands());
+        rules.join(" ")
+    }
+
+    fn check(&self, access: &Access) -> Result<(), Denial> {
+        let allowed = self
+            .commands
+            .iter()
+            .rev()
+            .find(|(_, rule)| rule.matches(access))
+            .is_some_and(|(allow, _)| *allow);
+        if !allowed {
+            return Err(Denial::new(Reason::Command, &access.name));
+        }
+
+        // Commands such as FT.SEARCH reach keys through index prefixes or labels, which key
+        // patterns can't be checked against.
+        let all_keys = self
+            .keys
+            .iter()
+            .any(|k| k.read && k.write && k.pattern == "*");
+        if access.any_key && !all_keys {
+            return Err(Denial::new(Reason::Key, "*"));
+        }
+
+        for (key, write) in &access.keys {
+            let allowed = self.keys.iter().any(|k| {
+                let permitted = if *write { k.write } else { k.read };
+                permitted && glob::matches(&k.pattern, key)
+            });
+            if !allowed {
+                return Err(Denial::new(Reason::Key, key));
+            }
+        }
+
+        for (channel, is_pattern) in &access.channels {
+            if !self.allows_channel(channel, *is_pattern) {
+                return Err(Denial::new(Reason::Channel, channel));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn allows_channel(&self, channel: &str, is_pattern: bool) -> bool {
+        // Patterns are only allowed if the user may use the very same pattern.
+        self.channels.iter().any(|c| match is_pattern {
+            true => c == "*" || c == channel,
+            false => glob::matches(c, channel),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reason {
+    Command,
+    Key,
+    Channel,
+    Auth,
+}
+
+impl Reason {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Reason::Command => "command",
+            Reason::Key => "key",
+            Reason::Channel => "channel",
+            Reason::Auth => "auth",
+        }
+    }
+}
+
+/// Why a user was refused access, along with what it was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Denial {
+    pub(crate) reason: Reason,
+    pub(crate) object: String,
+}
+
+impl Denial {
+    pub(crate) fn new(reason: Reason, object: &str) -> Self {
+        Denial {
+            reason,
+            object: object.to_owned(),
+        }
+    }
+
+    pub(crate) fn error(&self, username: &str) -> RedisError {
+        match self.reason {
+            Reason::Command => RedisError::NoPerm(format!(
+                "User {username} has no permissions to run the '{}' command",
+                self.object
+            )),
+            Reason::Key => RedisError::NoPerm(String::from("No permissions to access a key")),
+            Reason::Channel => {
+                RedisError::NoPerm(String::from("No permissions to access a channel"))
+            }
+            Reason::Auth => RedisError::WrongPass,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct LogEntry {
+    pub(crate) count: u64,
+    pub(crate) reason: Reason,
+    /// Whether the command was run on its own or by EXEC.
+    pub(crate) context: &'static str,
+    pub(crate) object: String,
+    pub(crate) username: String,
+    pub(crate) client_info: String,
+    pub(crate) entry_id: u64,
+    pub(crate) created: u64,
+    pub(crate) updated: u64,
+}
+
+/// The users by name, and the log of refused accesses.
+#[derive(Debug)]
+pub(crate) struct Acl {
+    users: BTreeMap<String, User>,
+    /// The password of the default user as last set with `requirepass`.
+    requirepass: String,
+    /// The commands registered by modules, which rules can name like built-in ones.
+    module_commands: BTreeSet<String>,
+    log: VecDeque<LogEntry>,
+    next_entry_id: u64,
+}
+
+impl Acl {
+    pub(crate) fn new(requirepass: Option<&str>) -> Self {
+        let mut acl = Acl {
+            users: BTreeMap::from([(DEFAULT_USER.to_owned(), User::default_user())]),
+            requirepass: String::new(),
+            module_commands: BTreeSet::new(),
+            log: VecDeque::new(),
+            next_entry_id: 0,
+        };
+        if let Some(password) = requirepass {
+            acl.set_requirepass(password);
+        }
+        acl
+    }
+
+    /// The password set with `requirepass`, or an empty one once the default user no longer
+    /// has it as its only password, as after ACL LOAD or ACL SETUSER redefine it.
+    pub(crate) fn requirepass(&self) -> &str {
+        let current = self.users.get(DEFAULT_USER).is_some_and(|user| {
+            !user.nopass
+                && user.passwords.len() == 1
+                && user.passwords.contains(&hash(&self.requirepass))
+        });
+
+        match current {
+            true => &self.requirepass,
+            false => "",
+        }
+    }
+
+    /// Sets the password of the default user, where an empty one lets anyone in.
+    pub(crate) fn set_requirepass(&mut self, password: &str) {
+        let user = self
+            .users
+            .entry(DEFAULT_USER.to_owned())
+            .or_insert_with(User::default_user);
+        let rule = match password {
+            "" => String::from("nopass"),
+            password => format!(">{password}"),
+        };
+        user.apply("resetpass").expect("valid rule");
+        user.apply(&rule).expect("valid rule");
+
+        self.requirepass = password.to_owned();
+    }
+
+    /// Whether new connections are authenticated as the default user without AUTH.
+    pub(crate) fn is_open(&self) -> bool {
+        self.users
+            .get(DEFAULT_USER)
+            .is_some_and(|user| user.enabled && user.nopass)
+    }
+
+    pub(crate) fn authenticate(&self, username: &str, password: &str) -> bool {
+        self.users.get(username).is_some_and(|user| {
+            user.enabled && (user.nopass || user.passwords.contains(&hash(password)))
+        })
+    }
+
+    pub(crate) fn user(&self, username: &str) -> Option<&User> {
+        self.users.get(username)
+    }
+
+    pub(crate) fn usernames(&self) -> Vec<String> {
+        self.users.keys().cloned().collect()
+    }
+
+    pub(crate) fn add_module_command(&mut self, name: &str) {
+        self.module_commands.insert(name.to_lowercase());
+    }
+
+    /// Whether `username` may stay subscribed to `channel`, a pattern if `is_pattern`.
+    pub(crate) fn allows_channel(&self, username: &str, channel: &str, is_pattern: bool) -> bool {
+        self.users
+            .get(username)
+            .is_some_and(|user| user.allows_channel(channel, is_pattern))
+    }
+
+    /// Checks that `username` may access what a command accesses.
+    pub(crate) fn check(&self, username: &str, access: &Access) -> Result<(), Denial> {
+        match self.users.get(username) {
+            Some(user) => user.check(access),
+            None => Err(Denial::new(Reason::Command, &access.name)),
+        }
+    }
+
+    /// Creates or changes a user, applying either all of the rules or none of them.
+    pub(crate) fn set_user(&mut self, username: &str, rules: &[String]) -> Result<(), RedisError> {
+        let mut user = self.users.get(username).cloned().unwrap_or_else(User::new);
+        for rule in rules {
+            self.check_command_name(rule)
+                .and_then(|()| user.apply(rule))
+                .map_err(|err| {
+                    RedisError::Custom(format!("Error in ACL SETUSER modifier '{rule}': {err}"))
+                })?;
+        }
+
+        self.users.insert(username.to_owned(), user);
+        Ok(())
+    }
+
+    /// Checks that the command allowed or denied by `rule`, if it names one, exists.
+    fn check_command_name(&self, rule: &str) -> Result<(), &'static str> {
+        let Some(name) = rule.strip_prefix(['+', '-']) else {
+            return Ok(());
+        };
+        let name = name.to_lowercase();
+
+        let known =
+            name.starts_with('@') || table::exists(&name) || self.module_commands.contains(&name);
+        match known {
+            true => Ok(()),
+            false => Err("Unknown command or category name in ACL"),
+        }
+    }
+
+    pub(crate) fn delete_user(&mut self, username: &str) -> Result<bool, RedisError> {
+        if username == DEFAULT_USER {
+            return Err(RedisError::Custom(String::from(
+                "The 'default' user cannot be removed",
+            )));
+        }
+
+        Ok(self.users.remove(username).is_some())
+    }
+
+    /// Every user as its rules, as in ACL LIST.
+    pub(crate) fn list(&self) -> Vec<String> {
+        self.users
+            .iter()
+            .map(|(name, user)| format!("user {name} {}", user.describe()))
+            .collect()
+    }
+
+    /// Records a refused access, counting it in a recent entry with the same details if
+    /// there is one.
+    pub(crate) fn log(
+        &mut self,
+        denial: Denial,
+        context: &'static str,
+        username: &str,
+        client_info: &str,
+    ) {
+        let now = to_unix_millis(SystemTime::now());
+
+        let recent = self.log.iter().position(|entry| {
+            entry.reason == denial.reason
+                && entry.context == context
+                && entry.object == denial.object
+                && entry.username == username
+                && now.saturating_sub(entry.created) < LOG_GROUPING_MILLIS
+        });
+
+        let entry = match recent.and_then(|i| self.log.remove(i)) {
+            Some(mut entry) => {
+                entry.count += 1;
+                entry.updated = now;
+                entry.client_info = client_info.to_owned();
+                entry
+            }
+            None => {
+                self.next_entry_id += 1;
+                LogEntry {
+                    count: 1,
+                    reason: denial.reason,
+                    context,
+                    object: denial.object,
+                    username: username.to_owned(),
+                    client_info: client_info.to_owned(),
+                    entry_id: self.next_entry_id - 1,
+                    created: now,
+                    updated: now,
+                }
+            }
+        };
+
+        self.log.push_front(entry);
+        self.log.truncate(LOG_MAX_LEN);
+    }
+
+    /// The latest `count` entries of the log, the newest first.
+    pub(crate) fn log_entries(&self, count: usize) -> impl Iterator<Item = &LogEntry> {
+        self.log.iter().take(count)
+    }
+
+    pub(crate) fn reset_log(&mut self) {
+        self.log.clear();
+    }
+
+    /// Replaces the users with those of an ACL file, made of `user <name> <rules...>` lines.
+    /// Nothing changes if any line is invalid. The default user stays as it is if the file
+    /// doesn't define it.
+    pub(crate) fn load(&mut self, path: &str) -> Result<(), RedisError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| RedisError::Custom(format!("Error loading ACLs: {err}")))?;
+
+        let mut acl = Acl::new(None);
+        acl.users.clear();
+        acl.module_commands = self.module_commands.clone();
+
+        for (n, line) in contents.lines().enumerate() {
+            let words: Vec<String> = line.split_whitespace().map(String::from).collect();
+            let error = |err: &str| RedisError::Custom(format!("{path}:{}: {err}", n + 1));
+
+            match words.as_slice() {
+                [] => continue,
+                [user, name, rules @ ..] if user == "user" => {
+                    if acl.users.contains_key(name) {
+                        return Err(error("Duplicate user found"));
+                    }
+                    acl.set_user(name, rules).map_err(|err| match err {
+                        RedisError::Custom(msg) => error(&msg),
+                        err => error(&err.to_string()),
+                    })?;
+                }
+                _ => return Err(error("should start with user keyword")),
+            }
+        }
+
+        if !acl.users.contains_key(DEFAULT_USER) {
+            let user = self
+                .users
+                .remove(DEFAULT_USER)
+                .unwrap_or_else(User::default_user);
+            acl.users.insert(DEFAULT_USER.to_owned(), user);
+        }
+        self.users = acl.users;
+        Ok(())
+    }
+
+    pub(crate) fn save(&self, path: &str) -> Result<(), RedisError> {
+        let mut contents = self.list().join("\n");
+        contents.push('\n');
+
+        fs::write(path, contents)
+            .map_err(|err| RedisError::Custom(format!("There was an error trying to save the ACLs. Please check the server logs for more information ({err})")))
+    }
+}
+
+fn hash(password: &str) -> String {
+    hex::encode(&sha256(password.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Acl, Denial, Reason, DEFAULT_USER};
+    use crate::{cmd::table::Access, error::RedisError};
+
+    fn check(acl: &Acl, username: &str, args: &[&str]) -> Result<(), Denial> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        acl.check(username, &Access::of(&args).unwrap())
+    }
+
+    fn set_user(acl: &mut Acl, username: &str, rules: &[&str]) {
+        let rules: Vec<String> = rules.iter().map(|s| s.to_string()).collect();
+        acl.set_user(username, &rules).unwrap();
+    }
+
+    #[test]
+    fn test_default_user() {
+        let mut acl = Acl::new(None);
+        assert!(acl.is_open());
+        assert!(check(&acl, DEFAULT_USER, &["set", "k", "v"]).is_ok());
+        assert_eq!(vec!["user default on nopass ~* &* +@all"], acl.list());
+
+        acl.set_requirepass("secret");
+        assert!(!acl.is_open());
+        assert!(acl.authenticate(DEFAULT_USER, "secret"));
+        assert!(!acl.authenticate(DEFAULT_USER, "wrong"));
+        assert!(acl.delete_user(DEFAULT_USER).is_err());
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut acl = Acl::new(None);
+        set_user(
+            &mut acl,
+            "alice",
+            &[
+                "on", ">pw", "~cache:*", "%R~ro:*", "&news.*", "+@read", "-hgetall", "+set",
+            ],
+        );
+
+        assert!(acl.authenticate("alice", "pw"));
+        assert!(check(&acl, "alice", &["get", "cache:1"]).is_ok());
+        assert!(check(&acl, "alice", &["set", "cache:1", "v"]).is_ok());
+        assert!(check(&acl, "alice", &["get", "ro:1"]).is_ok());
+        assert_eq!(
+            Err(Denial::new(Reason::Key, "ro:1")),
+            check(&acl, "alice", &["set", "ro:1", "v"])
+        );
+        assert_eq!(
+            Err(Denial::new(Reason::Command, "hgetall")),
+            check(&acl, "alice", &["hgetall", "cache:1"])
+        );
+        assert_eq!(
+            Err(Denial::new(Reason::Command, "hset")),
+            check(&acl, "alice", &["hset", "cache:1", "f", "v"])
+        );
+
+        // Destinations are written while sources are only read.
+        set_user(&mut acl, "alice", &["+sunionstore"]);
+        assert!(check(&acl, "alice", &["sunionstore", "cache:d", "ro:a"]).is_ok());
+        assert!(check(&acl, "alice", &["sunionstore", "ro:d", "cache:a"]).is_err());
+
+        // Channel patterns must be allowed as they are.
+        set_user(&mut acl, "alice", &["+@pubsub"]);
+        assert!(check(&acl, "alice", &["publish", "news.tech", "hi"]).is_ok());
+        assert!(check(&acl, "alice", &["psubscribe", "news.*"]).is_ok());
+        assert_eq!(
+            Err(Denial::new(Reason::Channel, "news.t*")),
+            check(&acl, "alice", &["psubscribe", "news.t*"])
+        );
+
+        assert_eq!(
+            "user alice on #30c952fab122c3f9759f02a6d95c3758b246b4fee239957b2d4fee46e26170c4 \
+             ~cache:* %R~ro:* &news.* -@all +@read -hgetall +set +sunionstore +@pubsub",
+            acl.list()[0]
+        );
+
+        // Subcommands can be allowed on their own.
+        set_user(&mut acl, "bob", &["on", "nopass", "+config|get"]);
+        assert!(check(&acl, "bob", &["config", "get", "x"]).is_ok());
+        assert!(check(&acl, "bob", &["config", "set", "x", "y"]).is_err());
+
+        // A failing rule leaves the user as it was.
+        let rules = vec![String::from("off"), String::from("+@nope")];
+        assert!(acl.set_user("bob", &rules).is_err());
+        assert!(acl.authenticate("bob", ""));
+
+        set_user(&mut acl, "bob", &["reset"]);
+        assert_eq!("user bob off resetchannels -@all", acl.list()[1]);
+        assert_eq!(Ok(true), acl.delete_user("bob"));
+        assert!(check(&acl, "bob", &["ping"]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_commands() {
+        let mut acl = Acl::new(None);
+        for rule in [
+            "+nosuchcmd",
+            "-nosuchcmd",
+            "+get|sub",
+            "+config|",
+            "+",
+            "-@nope",
+        ] {
+            assert_eq!(
+                Err(RedisError::Custom(format!(
+                    "Error in ACL SETUSER modifier '{rule}': Unknown command or category \
+                     name in ACL"
+                ))),
+                acl.set_user("alice", &[String::from("on"), rule.to_string()])
+            );
+        }
+        assert!(acl.user("alice").is_none());
+
+        set_user(
+            &mut acl,
+            "alice",
+            &["+GET", "-config|set", "+xgroup|create"],
+        );
+
+        // Commands of modules can be named once they are registered, even by ACL files.
+        let rules = vec![String::from("+mod.cmd")];
+        assert!(acl.set_user("alice", &rules).is_err());
+        acl.add_module_command("MOD.CMD");
+        set_user(&mut acl, "alice", &["+mod.cmd"]);
+
+        let path = std::env::temp_dir().join(format!("acl-mod-{}.acl", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "user bob on nopass -mod.cmd\n").unwrap();
+        acl.load(path).unwrap();
+        assert_eq!(
+            "user bob on nopass resetchannels -@all -mod.cmd",
+            acl.list()[0]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_allows_channel() {
+        let mut acl = Acl::new(None);
+        set_user(&mut acl, "alice", &["&news.*"]);
+
+        assert!(acl.allows_channel("alice", "news.tech", false));
+        assert!(acl.allows_channel("alice", "news.*", true));
+        assert!(!acl.allows_channel("alice", "news.t*", true));
+        assert!(!acl.allows_channel("alice", "weather", false));
+        assert!(!acl.allows_channel("bob", "news.tech", false));
+    }
+
+    #[test]
+    fn test_commands_on_any_key() {
+        let mut acl = Acl::new(None);
+        set_user(
+            &mut acl,
+            "alice",
+            &["on", "nopass", "~doc:*", "+@search", "+ts.mrange"],
+        );
+        assert_eq!(
+            Err(Denial::new(Reason::Key, "*")),
+            check(&acl, "alice", &["ft.search", "idx", "*"])
+        );
+        assert_eq!(
+            Err(Denial::new(Reason::Key, "*")),
+            check(&acl, "alice", &["ts.mrange", "-", "+", "FILTER", "a=b"])
+        );
+
+        // Read access to every key is not enough.
+        set_user(&mut acl, "alice", &["%R~*"]);
+        assert!(check(&acl, "alice", &["ft.search", "idx", "*"]).is_err());
+
+        set_user(&mut acl, "alice", &["allkeys"]);
+        assert!(check(&acl, "alice", &["ft.search", "idx", "*"]).is_ok());
+        assert!(check(&acl, "alice", &["ft.dropindex", "idx"]).is_ok());
+        assert!(check(&acl, "alice", &["ts.mrange", "-", "+", "FILTER", "a=b"]).is_ok());
+    }
+
+    #[test]
+    fn test_log() {
+        let mut acl = Acl::new(None);
+        acl.log(Denial::new(Reason::Key, "k"), "toplevel", "alice", "");
+        acl.log(Denial::new(Reason::Command, "get"), "toplevel", "alice", "");
+        acl.log(Denial::new(Reason::Key, "k"), "toplevel", "alice", "");
+
+        let entries: Vec<_> = acl.log_entries(10).collect();
+        assert_eq!(2, entries.len());
+        assert_eq!(
+            (2, "k", 0),
+            (
+                entries[0].count,
+                entries[0].object.as_str(),
+                entries[0].entry_id
+            )
+        );
+        assert_eq!((1, 1), (entries[1].count, entries[1].entry_id));
+
+        acl.reset_log();
+        assert_eq!(0, acl.log_entries(10).count());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("acl-{}.acl", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut acl = Acl::new(None);
+        set_user(&mut acl, "alice", &["on", ">pw", "%W~log:*", "+@write"]);
+        acl.save(path).unwrap();
+        let saved = acl.list();
+
+        // The default user stays as it is when the file doesn't define it.
+        std::fs::write(path, "user alice on nopass\n\nuser bob off\n").unwrap();
+        acl.set_requirepass("secret");
+        acl.load(path).unwrap();
+        assert_eq!(vec!["alice", "bob", "default"], acl.usernames());
+        assert!(!acl.is_open());
+        assert_eq!("secret", acl.requirepass());
+
+        // Redefining the default user replaces the password set with requirepass.
+        std::fs::write(path, "user default on >other ~* &* +@all\n").unwrap();
+        acl.load(path).unwrap();
+        assert!(acl.authenticate(DEFAULT_USER, "other"));
+        assert!(!acl.authenticate(DEFAULT_USER, "secret"));
+        assert_eq!("", acl.requirepass());
+
+        std::fs::write(path, saved.join("\n")).unwrap();
+        acl.load(path).unwrap();
+        assert_eq!(saved, acl.list());
+
+        std::fs::write(path, "user carol on\nuser dave +@nope\n").unwrap();
+        assert_eq!(
+            Err(RedisError::Custom(format!(
+                "{path}:2: Error in ACL SETUSER modifier '+@nope': Unknown command or \
+                 category name in ACL"
+            ))),
+            acl.load(path)
+        );
+        assert_eq!(saved, acl.list());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
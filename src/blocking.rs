@@ -0,0 +1,114 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use tokio::sync::oneshot;
+
+use crate::{cmd::Block, db::Database, frame::Frame};
+
+struct Waiter {
+    cmd: Arc<dyn Block>,
+    reply: oneshot::Sender<Frame>,
+}
+
+/// Clients blocked by commands such as BZPOPMIN. Each key keeps its clients in the order they
+/// blocked, so the client that has waited the longest is served first.
+#[derive(Default)]
+pub(crate) struct BlockedClients {
+    next_id: u64,
+    waiters: HashMap<u64, Waiter>,
+    queues: HashMap<String, VecDeque<u64>>,
+}
+
+impl BlockedClients {
+    /// Blocks `cmd` on its keys. The reply is delivered through the returned receiver once one
+    /// of the keys can serve it.
+    pub(crate) fn block(
+        &mut self,
+        db: &mut dyn Database,
+        cmd: Arc<dyn Block>,
+    ) -> (u64, oneshot::Receiver<Frame>) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        for key in cmd.keys() {
+            self.queues.entry(key.clone()).or_default().push_back(id);
+            db.block(key);
+        }
+
+        let (reply, rx) = oneshot::channel();
+        self.waiters.insert(id, Waiter { cmd, reply });
+
+        (id, rx)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.waiters.is_empty()
+    }
+
+    /// Removes a blocked client, returning `false` if it was already served.
+    pub(crate) fn unblock(&mut self, db: &mut dyn Database, id: u64) -> bool {
+        self.remove(db, id).is_some()
+    }
+
+    /// Serves the clients blocked on keys that were written, returning the frames to propagate
+    /// to replicas for what they consumed.
+    pub(crate) fn serve(&mut self, db: &mut dyn Database) -> Vec<Frame> {
+        let mut propagated = Vec::new();
+
+        // Serving a client writes to the key, which may make keys ready again.
+        loop {
+            let ready = db.drain_ready();
+            if ready.is_empty() {
+                break;
+            }
+
+            for key in ready {
+                let ids: Vec<u64> = self
+                    .queues
+                    .get(&key)
+                    .map(|queue| queue.iter().copied().collect())
+                    .unwrap_or_default();
+
+                for id in ids {
+                    let Some(waiter) = self.waiters.get(&id) else {
+                        continue;
+                    };
+
+                    // An error such as WRONGTYPE keeps the client waiting, as the key may hold
+                    // the right type again later.
+                    let served = waiter.cmd.serve(db, &key);
+
+                    propagated.extend(db.drain_propagated());
+                    propagated.extend(db.drain_rewritten().unwrap_or_default());
+
+                    if let Ok(Some(reply)) = served {
+                        if let Some(waiter) = self.remove(db, id) {
+                            let _ = waiter.reply.send(reply);
+                        }
+                    }
+                }
+            }
+        }
+
+        propagated
+    }
+
+    fn remove(&mut self, db: &mut dyn Database, id: u64) -> Option<Waiter> {
+        let waiter = self.waiters.remove(&id)?;
+
+        for key in waiter.cmd.keys() {
+            if let Some(queue) = self.queues.get_mut(key) {
+                queue.retain(|i| *i != id);
+                if queue.is_empty() {
+                    self.queues.remove(key);
+                }
+            }
+            db.unblock(key);
+        }
+
+        Some(waiter)
+    }
+}
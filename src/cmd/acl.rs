@@ -0,0 +1,297 @@
+use std::{fmt, time::SystemTime};
+
+use crate::{
+    acl::Acl,
+    cmd::{check_arity, table},
+    error::RedisError,
+    frame::Frame,
+    util::{
+        num::{format_float, parse_int},
+        time::to_unix_millis,
+    },
+};
+
+/// Entries ACL LOG replies with when not given a count.
+const DEFAULT_LOG_COUNT: usize = 10;
+
+/// `AUTH [username] password`, where the username defaults to `default`.
+pub(crate) struct Auth {
+    pub(crate) username: Option<String>,
+    pub(crate) password: String,
+}
+
+impl Auth {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        match args.as_slice() {
+            [_, password] => Ok(Auth {
+                username: None,
+                password: password.clone(),
+            }),
+            [_, username, password] => Ok(Auth {
+                username: Some(username.clone()),
+                password: password.clone(),
+            }),
+            _ => Err(RedisError::WrongArity(String::from("auth"))),
+        }
+    }
+}
+
+/// Leaves the password out, so that it doesn't end up in logs.
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Auth")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The `ACL` subcommands, which work on the users and the log of refused accesses.
+pub(crate) enum AclCommand {
+    SetUser(String, Vec<String>),
+    GetUser(String),
+    DelUser(Vec<String>),
+    List,
+    Users,
+    Whoami,
+    Cat(Option<String>),
+    Log(Option<usize>),
+    LogReset,
+    Save,
+    Load,
+}
+
+/// Hides the passwords and password hashes among the rules of ACL SETUSER.
+impl fmt::Debug for AclCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AclCommand::SetUser(name, rules) => {
+                let rules: Vec<&str> = rules
+                    .iter()
+                    .map(|rule| match rule.chars().next() {
+                        Some('>') => "><redacted>",
+                        Some('<') => "<<redacted>",
+                        Some('#') => "#<redacted>",
+                        Some('!') => "!<redacted>",
+                        _ => rule.as_str(),
+                    })
+                    .collect();
+                f.debug_tuple("SetUser").field(name).field(&rules).finish()
+            }
+            AclCommand::GetUser(name) => f.debug_tuple("GetUser").field(name).finish(),
+            AclCommand::DelUser(names) => f.debug_tuple("DelUser").field(names).finish(),
+            AclCommand::List => f.write_str("List"),
+            AclCommand::Users => f.write_str("Users"),
+            AclCommand::Whoami => f.write_str("Whoami"),
+            AclCommand::Cat(category) => f.debug_tuple("Cat").field(category).finish(),
+            AclCommand::Log(count) => f.debug_tuple("Log").field(count).finish(),
+            AclCommand::LogReset => f.write_str("LogReset"),
+            AclCommand::Save => f.write_str("Save"),
+            AclCommand::Load => f.write_str("Load"),
+        }
+    }
+}
+
+impl AclCommand {
+    pub(crate) fn parse(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let subcommand = args[1].to_lowercase();
+        let arity = |valid: bool| match valid {
+            true => Ok(()),
+            false => Err(RedisError::WrongArity(format!("acl|{subcommand}"))),
+        };
+
+        match subcommand.as_str() {
+            "setuser" => {
+                arity(args.len() >= 3)?;
+                Ok(AclCommand::SetUser(args[2].clone(), args[3..].to_vec()))
+            }
+            "getuser" => {
+                arity(args.len() == 3)?;
+                Ok(AclCommand::GetUser(args[2].clone()))
+            }
+            "deluser" => {
+                arity(args.len() >= 3)?;
+                Ok(AclCommand::DelUser(args[2..].to_vec()))
+            }
+            "list" | "users" | "whoami" | "save" | "load" => {
+                arity(args.len() == 2)?;
+                Ok(match subcommand.as_str() {
+                    "list" => AclCommand::List,
+                    "users" => AclCommand::Users,
+                    "whoami" => AclCommand::Whoami,
+                    "save" => AclCommand::Save,
+                    _ => AclCommand::Load,
+                })
+            }
+            "cat" => {
+                arity(args.len() <= 3)?;
+                Ok(AclCommand::Cat(args.get(2).map(|c| c.to_lowercase())))
+            }
+            "log" => {
+                arity(args.len() <= 3)?;
+                match args.get(2) {
+                    None => Ok(AclCommand::Log(None)),
+                    Some(arg) if arg.eq_ignore_ascii_case("reset") => Ok(AclCommand::LogReset),
+                    Some(count) => {
+                        let count = usize::try_from(parse_int(count)?)
+                            .map_err(|_| RedisError::NotInteger)?;
+                        Ok(AclCommand::Log(Some(count)))
+                    }
+                }
+            }
+            _ => Err(RedisError::Custom(format!(
+                "unknown subcommand '{}'. Try ACL HELP.",
+                args[1]
+            ))),
+        }
+    }
+
+    /// Runs the subcommand for the client authenticated as `username`. `aclfile` is where
+    /// ACL SAVE and ACL LOAD keep the users, if the server was given one.
+    pub(crate) fn execute(
+        &self,
+        acl: &mut Acl,
+        username: &str,
+        aclfile: Option<&str>,
+    ) -> Result<Frame, RedisError> {
+        let ok = || Frame::SimpleString(String::from("OK"));
+
+        match self {
+            AclCommand::SetUser(name, rules) => {
+                acl.set_user(name, rules)?;
+                Ok(ok())
+            }
+            AclCommand::GetUser(name) => {
+                let Some(user) = acl.user(name) else {
+                    return Ok(Frame::Null);
+                };
+
+                Ok(Frame::Array(vec![
+                    Frame::BulkString(String::from("flags")),
+                    Frame::Arrays(user.flags()),
+                    Frame::BulkString(String::from("passwords")),
+                    Frame::Arrays(user.passwords()),
+                    Frame::BulkString(String::from("commands")),
+                    Frame::BulkString(user.describe_commands()),
+                    Frame::BulkString(String::from("keys")),
+                    Frame::BulkString(user.describe_keys()),
+                    Frame::BulkString(String::from("channels")),
+                    Frame::BulkString(user.describe_channels()),
+                    Frame::BulkString(String::from("selectors")),
+                    Frame::Array(Vec::new()),
+                ]))
+            }
+            AclCommand::DelUser(names) => {
+                let mut deleted = 0;
+                for name in names {
+                    if acl.delete_user(name)? {
+                        deleted += 1;
+                    }
+                }
+
+                Ok(Frame::Integer(deleted))
+            }
+            AclCommand::List => Ok(Frame::Arrays(acl.list())),
+            AclCommand::Users => Ok(Frame::Arrays(acl.usernames())),
+            AclCommand::Whoami => Ok(Frame::BulkString(username.to_owned())),
+            AclCommand::Cat(None) => Ok(Frame::Arrays(
+                table::CATEGORIES
+                    .iter()
+                    .map(|(name, _)| name.to_string())
+                    .collect(),
+            )),
+            AclCommand::Cat(Some(category)) => {
+                let (_, categories) = table::CATEGORIES
+                    .iter()
+                    .find(|(name, _)| name == category)
+                    .ok_or(RedisError::Custom(format!("Unknown category '{category}'")))?;
+
+                Ok(Frame::Arrays(
+                    table::commands_in(*categories)
+                        .into_iter()
+                        .map(String::from)
+                        .collect(),
+                ))
+            }
+            AclCommand::Log(count) => {
+                let now = to_unix_millis(SystemTime::now());
+                let entries = acl
+                    .log_entries(count.unwrap_or(DEFAULT_LOG_COUNT))
+                    .map(|entry| {
+                        let age = now.saturating_sub(entry.created) as f64 / 1000.0;
+                        Frame::Array(vec![
+                            Frame::BulkString(String::from("count")),
+                            Frame::Integer(entry.count as i64),
+                            Frame::BulkString(String::from("reason")),
+                            Frame::BulkString(entry.reason.name().to_owned()),
+                            Frame::BulkString(String::from("context")),
+                            Frame::BulkString(entry.context.to_owned()),
+                            Frame::BulkString(String::from("object")),
+                            Frame::BulkString(entry.object.clone()),
+                            Frame::BulkString(String::from("username")),
+                            Frame::BulkString(entry.username.clone()),
+                            Frame::BulkString(String::from("age-seconds")),
+                            Frame::BulkString(format_float(age)),
+                            Frame::BulkString(String::from("client-info")),
+                            Frame::BulkString(entry.client_info.clone()),
+                            Frame::BulkString(String::from("entry-id")),
+                            Frame::Integer(entry.entry_id as i64),
+                            Frame::BulkString(String::from("timestamp-created")),
+                            Frame::Integer(entry.created as i64),
+                            Frame::BulkString(String::from("timestamp-last-updated")),
+                            Frame::Integer(entry.updated as i64),
+                        ])
+                    })
+                    .collect();
+
+                Ok(Frame::Array(entries))
+            }
+            AclCommand::LogReset => {
+                acl.reset_log();
+                Ok(ok())
+            }
+            AclCommand::Save | AclCommand::Load => {
+                let path = aclfile.ok_or(RedisError::Custom(String::from(
+                    "This Redis instance is not configured to use an ACL file. You may want to \
+                     specify users via the ACL SETUSER command and then issue a CONFIG REWRITE \
+                     (assuming you have a Redis configuration file set) in order to store users \
+                     in the Redis configuration.",
+                )))?;
+
+                match self {
+                    AclCommand::Save => acl.save(path)?,
+                    _ => acl.load(path)?,
+                }
+                Ok(ok())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AclCommand, Auth};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_debug_hides_passwords() {
+        let auth = Auth::new(args(&["AUTH", "alice", "secret"])).unwrap();
+        let debug = format!("{auth:?}");
+        assert!(debug.contains("alice"));
+        assert!(!debug.contains("secret"));
+
+        let setuser = AclCommand::parse(args(&[
+            "ACL", "SETUSER", "alice", "on", ">secret", "<old", "#abc123", "!def456", "~*",
+        ]))
+        .unwrap();
+        let debug = format!("{setuser:?}");
+        assert!(debug.contains("alice") && debug.contains("on") && debug.contains("~*"));
+        for secret in ["secret", "old", "abc123", "def456"] {
+            assert!(!debug.contains(secret), "{debug} shows {secret}");
+        }
+    }
+}
@@ -0,0 +1,789 @@
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{
+        bitmap::{self, BitOp, FieldType, Overflow},
+        notify,
+        string::Str,
+        Data, Database, Value,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::num::parse_int,
+};
+
+/// Bitmaps are limited to 512MB like any other string, so offsets stay below 2^32.
+const MAX_BIT_OFFSET: u64 = (512 << 20) * 8;
+
+#[derive(Debug)]
+pub(crate) enum BitmapCommand {
+    Setbit(Setbit),
+    Getbit(Getbit),
+    Bitcount(Bitcount),
+    Bitpos(Bitpos),
+    Bitop(Bitop),
+    Bitfield(Bitfield),
+}
+
+impl BitmapCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "setbit" => Setbit::new(args).map(BitmapCommand::Setbit),
+            "getbit" => Getbit::new(args).map(BitmapCommand::Getbit),
+            "bitcount" => Bitcount::new(args).map(BitmapCommand::Bitcount),
+            "bitpos" => Bitpos::new(args).map(BitmapCommand::Bitpos),
+            "bitop" => Bitop::new(args).map(BitmapCommand::Bitop),
+            "bitfield" => Bitfield::new(args, false).map(BitmapCommand::Bitfield),
+            "bitfield_ro" => Bitfield::new(args, true).map(BitmapCommand::Bitfield),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for BitmapCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            BitmapCommand::Setbit(cmd) => cmd.execute(db),
+            BitmapCommand::Getbit(cmd) => cmd.execute(db),
+            BitmapCommand::Bitcount(cmd) => cmd.execute(db),
+            BitmapCommand::Bitpos(cmd) => cmd.execute(db),
+            BitmapCommand::Bitop(cmd) => cmd.execute(db),
+            BitmapCommand::Bitfield(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn get_string<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut Vec<u8>>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_string_mut())
+        .transpose()
+}
+
+fn read_string<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut Vec<u8>>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_string_mut())
+        .transpose()
+}
+
+fn get_or_create_string<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<&'a mut Vec<u8>, RedisError> {
+    db.get_or_insert_with(key, &|| Data::String(Str::Raw(Vec::new())))
+        .as_string_mut()
+}
+
+fn offset_error() -> RedisError {
+    RedisError::Custom(String::from("bit offset is not an integer or out of range"))
+}
+
+fn parse_offset(s: &str) -> Result<u64, RedisError> {
+    s.parse::<u64>()
+        .ok()
+        .filter(|offset| *offset < MAX_BIT_OFFSET)
+        .ok_or_else(offset_error)
+}
+
+/// Whether `BYTE` or `BIT` was given as the unit of a range, defaulting to bytes.
+fn parse_bit_unit(unit: Option<&String>) -> Result<bool, RedisError> {
+    match unit.map(|s| s.to_lowercase()).as_deref() {
+        None | Some("byte") => Ok(false),
+        Some("bit") => Ok(true),
+        Some(_) => Err(RedisError::Syntax),
+    }
+}
+
+/// Turns a `start`/`end` range over `len` bytes or bits, where negative indexes count from
+/// the end, into an inclusive range of bit offsets.
+fn resolve_range(start: i64, end: i64, len: usize, bit: bool) -> Option<(u64, u64)> {
+    let len = if bit { len as i64 * 8 } else { len as i64 };
+    let start = if start < 0 { start + len } else { start }.max(0);
+    let end = if end < 0 { end + len } else { end }.max(0).min(len - 1);
+
+    if start > end {
+        return None;
+    }
+
+    match bit {
+        true => Some((start as u64, end as u64)),
+        false => Some((start as u64 * 8, end as u64 * 8 + 7)),
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Setbit {
+    key: String,
+    offset: u64,
+    on: bool,
+}
+
+impl Setbit {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let on = match args[3].as_str() {
+            "0" => false,
+            "1" => true,
+            _ => {
+                return Err(RedisError::Custom(String::from(
+                    "bit is not an integer or out of range",
+                )))
+            }
+        };
+
+        Ok(Setbit {
+            key: args[1].clone(),
+            offset: parse_offset(&args[2])?,
+            on,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let bytes = get_or_create_string(db, &self.key)?;
+
+        let len = bytes.len();
+        let old = bitmap::set_bit(bytes, self.offset, self.on);
+
+        if bytes.len() > len || old != self.on {
+            db.touch(&self.key);
+            db.notify(notify::STRING, "setbit", &self.key);
+        }
+
+        Ok(Frame::Integer(old as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Getbit {
+    key: String,
+    offset: u64,
+}
+
+impl Getbit {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Getbit {
+            key: args[1].clone(),
+            offset: parse_offset(&args[2])?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let on =
+            read_string(db, &self.key)?.is_some_and(|bytes| bitmap::get_bit(bytes, self.offset));
+
+        Ok(Frame::Integer(on as i64))
+    }
+}
+
+/// `BITCOUNT key [start end [BYTE | BIT]]`
+#[derive(Debug)]
+pub(crate) struct Bitcount {
+    key: String,
+    range: Option<(i64, i64, bool)>,
+}
+
+impl Bitcount {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let range = match args.len() {
+            2 => None,
+            4 | 5 => Some((
+                parse_int(&args[2])?,
+                parse_int(&args[3])?,
+                parse_bit_unit(args.get(4))?,
+            )),
+            _ => return Err(RedisError::Syntax),
+        };
+
+        Ok(Bitcount {
+            key: args[1].clone(),
+            range,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(bytes) = read_string(db, &self.key)? else {
+            return Ok(Frame::Integer(0));
+        };
+
+        let (start, end, bit) = self.range.unwrap_or((0, -1, false));
+        let count = resolve_range(start, end, bytes.len(), bit)
+            .map_or(0, |(start, end)| bitmap::count(bytes, start, end));
+
+        Ok(Frame::Integer(count as i64))
+    }
+}
+
+/// `BITPOS key bit [start [end [BYTE | BIT]]]`
+#[derive(Debug)]
+pub(crate) struct Bitpos {
+    key: String,
+    bit: bool,
+    start: i64,
+    end: Option<i64>,
+    unit_bit: bool,
+}
+
+impl Bitpos {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        if args.len() > 6 {
+            return Err(RedisError::Syntax);
+        }
+
+        let bit = match args[2].as_str() {
+            "0" => false,
+            "1" => true,
+            _ => {
+                return Err(RedisError::Custom(String::from(
+                    "The bit argument must be 1 or 0.",
+                )))
+            }
+        };
+
+        Ok(Bitpos {
+            key: args[1].clone(),
+            bit,
+            start: args.get(3).map(|s| parse_int(s)).transpose()?.unwrap_or(0),
+            end: args.get(4).map(|s| parse_int(s)).transpose()?,
+            unit_bit: parse_bit_unit(args.get(5))?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(bytes) = read_string(db, &self.key)? else {
+            // A missing key is an empty string padded with zeros.
+            return Ok(Frame::Integer(if self.bit { -1 } else { 0 }));
+        };
+
+        let range = resolve_range(
+            self.start,
+            self.end.unwrap_or(-1),
+            bytes.len(),
+            self.unit_bit,
+        );
+        let Some((start, end)) = range else {
+            return Ok(Frame::Integer(-1));
+        };
+
+        let pos = match bitmap::position(bytes, self.bit, start, end) {
+            Some(pos) => pos as i64,
+            // Without an explicit end, the string is considered to be padded with zeros, so
+            // the first clear bit is right after it.
+            None if !self.bit && self.end.is_none() => end as i64 + 1,
+            None => -1,
+        };
+
+        Ok(Frame::Integer(pos))
+    }
+}
+
+/// `BITOP <AND | OR | XOR | NOT | DIFF> destkey key [key ...]`
+#[derive(Debug)]
+pub(crate) struct Bitop {
+    op: BitOp,
+    dest: String,
+    keys: Vec<String>,
+}
+
+impl Bitop {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let op = match args[1].to_lowercase().as_str() {
+            "and" => BitOp::And,
+            "or" => BitOp::Or,
+            "xor" => BitOp::Xor,
+            "not" => BitOp::Not,
+            "diff" => BitOp::Diff,
+            _ => return Err(RedisError::Syntax),
+        };
+
+        let keys = args[3..].to_vec();
+        match op {
+            BitOp::Not if keys.len() != 1 => {
+                return Err(RedisError::Custom(String::from(
+                    "BITOP NOT must be called with a single source key.",
+                )))
+            }
+            BitOp::Diff if keys.len() < 2 => {
+                return Err(RedisError::Custom(String::from(
+                    "BITOP DIFF must be called with at least two source keys.",
+                )))
+            }
+            _ => {}
+        }
+
+        Ok(Bitop {
+            op,
+            dest: args[2].clone(),
+            keys,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let mut sources = Vec::with_capacity(self.keys.len());
+        for key in &self.keys {
+            sources.push(read_string(db, key)?.cloned().unwrap_or_default());
+        }
+
+        let sources: Vec<&[u8]> = sources.iter().map(|s| s.as_slice()).collect();
+        let result = bitmap::bitop(self.op, &sources);
+        let len = result.len();
+
+        // Like any string write, the destination loses its expiration, and an empty result
+        // deletes it.
+        if result.is_empty() {
+            if db.remove(&self.dest).is_some() {
+                db.notify(notify::GENERIC, "del", &self.dest);
+            }
+        } else {
+            db.insert(&self.dest, Value::from(Data::String(Str::Raw(result))));
+            db.notify(notify::STRING, "set", &self.dest);
+        }
+        db.touch(&self.dest);
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+#[derive(Debug)]
+enum FieldOp {
+    Get,
+    Set(i64),
+    Incrby(i64),
+}
+
+#[derive(Debug)]
+struct Field {
+    op: FieldOp,
+    ty: FieldType,
+    offset: u64,
+    overflow: Overflow,
+}
+
+/// `BITFIELD key [GET type offset | SET type offset value | INCRBY type offset increment |
+/// OVERFLOW <WRAP | SAT | FAIL>] ...`, where offsets prefixed with `#` are multiplied by the
+/// width of the type. BITFIELD_RO only accepts GET.
+#[derive(Debug)]
+pub(crate) struct Bitfield {
+    key: String,
+    fields: Vec<Field>,
+}
+
+impl Bitfield {
+    pub(crate) fn new(args: Vec<String>, read_only: bool) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let mut fields = Vec::new();
+        let mut overflow = Overflow::Wrap;
+
+        let mut rest = args[2..].iter();
+        while let Some(arg) = rest.next() {
+            let sub = arg.to_lowercase();
+
+            if sub == "overflow" {
+                overflow = match rest.next().map(|s| s.to_lowercase()).as_deref() {
+                    Some("wrap") => Overflow::Wrap,
+                    Some("sat") => Overflow::Sat,
+                    Some("fail") => Overflow::Fail,
+                    Some(_) => {
+                        return Err(RedisError::Custom(String::from(
+                            "Invalid OVERFLOW type specified",
+                        )))
+                    }
+                    None => return Err(RedisError::Syntax),
+                };
+                continue;
+            }
+
+            let (ty, offset) = match (rest.next(), rest.next()) {
+                (Some(ty), Some(offset)) => (ty, offset),
+                _ => return Err(RedisError::Syntax),
+            };
+            let ty = FieldType::parse(ty).ok_or(RedisError::Custom(String::from(
+                "Invalid bitfield type. Use something like i16 u8. Note that u64 is not \
+                 supported but i64 is.",
+            )))?;
+            let offset = parse_field_offset(offset, ty.bits)?;
+
+            let op = match sub.as_str() {
+                "get" => FieldOp::Get,
+                "set" | "incrby" => {
+                    let value = parse_int(rest.next().ok_or(RedisError::Syntax)?)?;
+                    match sub.as_str() {
+                        "set" => FieldOp::Set(value),
+                        _ => FieldOp::Incrby(value),
+                    }
+                }
+                _ => return Err(RedisError::Syntax),
+            };
+
+            if read_only && !matches!(op, FieldOp::Get) {
+                return Err(RedisError::Custom(String::from(
+                    "BITFIELD_RO only supports the GET subcommand",
+                )));
+            }
+
+            fields.push(Field {
+                op,
+                ty,
+                offset,
+                overflow,
+            });
+        }
+
+        Ok(Bitfield {
+            key: args[1].clone(),
+            fields,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let writes = self.fields.iter().any(|f| !matches!(f.op, FieldOp::Get));
+
+        if !writes {
+            let bytes = read_string(db, &self.key)?.map(|b| b.as_slice());
+            let replies = self
+                .fields
+                .iter()
+                .map(|f| {
+                    let raw = bitmap::get_field(bytes.unwrap_or_default(), f.offset, f.ty.bits);
+                    Frame::Integer(f.ty.decode(raw))
+                })
+                .collect();
+
+            return Ok(Frame::Array(replies));
+        }
+
+        let existed = get_string(db, &self.key)?.is_some();
+        let bytes = get_or_create_string(db, &self.key)?;
+
+        let mut changed = false;
+        let mut replies = Vec::with_capacity(self.fields.len());
+        for f in &self.fields {
+            let old = f.ty.decode(bitmap::get_field(bytes, f.offset, f.ty.bits));
+
+            let (new, reply) = match f.op {
+                FieldOp::Get => {
+                    replies.push(Frame::Integer(old));
+                    continue;
+                }
+                FieldOp::Set(value) => {
+                    let new = f.ty.fit(value as i128, f.overflow);
+                    (new, new.map(|_| old))
+                }
+                FieldOp::Incrby(incr) => {
+                    let new = f.ty.fit(old as i128 + incr as i128, f.overflow);
+                    (new, new)
+                }
+            };
+
+            // With FAIL, a value that doesn't fit is not written and the reply is nil.
+            if let Some(new) = new {
+                bitmap::set_field(bytes, f.offset, f.ty.bits, f.ty.encode(new));
+                changed = true;
+            }
+            replies.push(reply.map_or(Frame::Null, Frame::Integer));
+        }
+
+        if changed {
+            db.touch(&self.key);
+            db.notify(notify::STRING, "setbit", &self.key);
+        } else if !existed {
+            db.remove(&self.key);
+        }
+
+        Ok(Frame::Array(replies))
+    }
+}
+
+/// Parses a BITFIELD offset, either in bits or, when prefixed with `#`, in multiples of the
+/// field width.
+fn parse_field_offset(s: &str, bits: u32) -> Result<u64, RedisError> {
+    let offset = match s.strip_prefix('#') {
+        Some(index) => index
+            .parse::<u64>()
+            .ok()
+            .and_then(|i| i.checked_mul(bits as u64))
+            .ok_or_else(offset_error)?,
+        None => s.parse::<u64>().map_err(|_| offset_error())?,
+    };
+
+    offset
+        .checked_add(bits as u64)
+        .filter(|end| *end <= MAX_BIT_OFFSET)
+        .map(|_| offset)
+        .ok_or_else(offset_error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitmapCommand;
+    use crate::{
+        cmd::Execute,
+        db::{Database, KeyValueDb},
+        error::RedisError,
+        frame::Frame,
+    };
+
+    fn run(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        BitmapCommand::parse(&args[0], args.clone())
+            .and_then(|cmd| cmd.execute(db))
+            .unwrap_or_else(Frame::from)
+    }
+
+    fn bitfield(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        run(db, &[&["bitfield", "b"], args].concat())
+    }
+
+    /// The reply to a BITFIELD, where `None` stands for nil.
+    fn replies(values: &[Option<i64>]) -> Frame {
+        Frame::Array(
+            values
+                .iter()
+                .map(|v| v.map_or(Frame::Null, Frame::Integer))
+                .collect(),
+        )
+    }
+
+    fn error(msg: &str) -> Frame {
+        Frame::from(RedisError::Custom(msg.to_owned()))
+    }
+
+    #[test]
+    fn test_bitfield_overflow() {
+        let mut db = KeyValueDb::new();
+
+        // WRAP is the default, and OVERFLOW only applies to the operations after it.
+        let reply = bitfield(
+            &mut db,
+            &[
+                "SET", "i8", "0", "100", "INCRBY", "i8", "0", "100", "OVERFLOW", "SAT", "INCRBY",
+                "i8", "0", "-200", "OVERFLOW", "FAIL", "INCRBY", "i8", "0", "-1", "GET", "i8", "0",
+            ],
+        );
+        assert_eq!(
+            replies(&[Some(0), Some(-56), Some(-128), None, Some(-128)]),
+            reply
+        );
+
+        let reply = bitfield(
+            &mut db,
+            &[
+                "SET", "u4", "8", "15", "INCRBY", "u4", "8", "1", "OVERFLOW", "SAT", "INCRBY",
+                "u4", "8", "-5", "INCRBY", "u4", "8", "100", "OVERFLOW", "FAIL", "INCRBY", "u4",
+                "8", "1",
+            ],
+        );
+        assert_eq!(replies(&[Some(0), Some(0), Some(0), Some(15), None]), reply);
+
+        // A SET that doesn't fit replies with nil and leaves the field alone.
+        let reply = bitfield(
+            &mut db,
+            &["OVERFLOW", "FAIL", "SET", "u4", "8", "16", "GET", "u4", "8"],
+        );
+        assert_eq!(replies(&[None, Some(15)]), reply);
+        let reply = bitfield(
+            &mut db,
+            &["OVERFLOW", "SAT", "SET", "u4", "8", "-16", "GET", "u4", "8"],
+        );
+        assert_eq!(replies(&[Some(15), Some(0)]), reply);
+        let reply = bitfield(&mut db, &["SET", "u4", "8", "-1", "GET", "u4", "8"]);
+        assert_eq!(replies(&[Some(0), Some(15)]), reply);
+
+        // Nothing is written when every operation fails.
+        let reply = run(
+            &mut db,
+            &["bitfield", "new", "OVERFLOW", "FAIL", "SET", "u1", "0", "2"],
+        );
+        assert_eq!(replies(&[None]), reply);
+        assert!(db.get_value("new").is_none());
+    }
+
+    #[test]
+    fn test_bitfield_signed_and_unsigned() {
+        let mut db = KeyValueDb::new();
+
+        // The same bits read differently depending on the type.
+        bitfield(&mut db, &["SET", "u8", "0", "200"]);
+        let reply = bitfield(
+            &mut db,
+            &[
+                "GET", "u8", "0", "GET", "i8", "0", "GET", "u4", "0", "GET", "i4", "0", "GET",
+                "i1", "0", "GET", "u1", "0",
+            ],
+        );
+        assert_eq!(
+            replies(&[Some(200), Some(-56), Some(12), Some(-4), Some(-1), Some(1)]),
+            reply
+        );
+
+        // Fields don't need to be aligned to bytes.
+        let reply = bitfield(
+            &mut db,
+            &["SET", "u5", "3", "31", "GET", "u8", "0", "GET", "u3", "3"],
+        );
+        assert_eq!(replies(&[Some(8), Some(223), Some(7)]), reply);
+
+        // Reading past the end of the string gives zeros.
+        let reply = bitfield(&mut db, &["GET", "i16", "100", "GET", "u8", "4"]);
+        assert_eq!(replies(&[Some(0), Some(240)]), reply);
+        let reply = run(&mut db, &["bitfield_ro", "missing", "GET", "i8", "0"]);
+        assert_eq!(replies(&[Some(0)]), reply);
+    }
+
+    #[test]
+    fn test_bitfield_hash_offsets() {
+        let mut db = KeyValueDb::new();
+
+        // `#n` is the n-th field of that width.
+        let reply = bitfield(
+            &mut db,
+            &[
+                "SET", "u8", "#1", "255", "SET", "i4", "#5", "-1", "GET", "u8", "8", "GET", "u16",
+                "0", "GET", "u8", "#2",
+            ],
+        );
+        assert_eq!(
+            replies(&[Some(0), Some(0), Some(255), Some(255), Some(15)]),
+            reply
+        );
+        let reply = bitfield(&mut db, &["INCRBY", "u8", "#2", "1", "GET", "u24", "#0"]);
+        assert_eq!(replies(&[Some(16), Some(0x00ff10)]), reply);
+
+        let offset_error = error("bit offset is not an integer or out of range");
+        for offset in [
+            "#-1",
+            "#x",
+            "-1",
+            "#536870912",
+            "4294967288",
+            "#18446744073709551615",
+        ] {
+            assert_eq!(
+                offset_error,
+                bitfield(&mut db, &["GET", "u16", offset]),
+                "{offset}"
+            );
+        }
+        assert_eq!(
+            replies(&[Some(0)]),
+            bitfield(&mut db, &["GET", "u8", "#536870911"])
+        );
+    }
+
+    #[test]
+    fn test_bitfield_64_bit_limits() {
+        let mut db = KeyValueDb::new();
+        let min = i64::MIN.to_string();
+        let max = i64::MAX.to_string();
+
+        let reply = bitfield(
+            &mut db,
+            &[
+                "SET", "i64", "0", &max, "INCRBY", "i64", "0", "1", "INCRBY", "i64", "0", "-1",
+            ],
+        );
+        assert_eq!(replies(&[Some(0), Some(i64::MIN), Some(i64::MAX)]), reply);
+
+        let reply = bitfield(
+            &mut db,
+            &[
+                "OVERFLOW", "SAT", "INCRBY", "i64", "0", "1", "SET", "i64", "0", &min, "INCRBY",
+                "i64", "0", "-1",
+            ],
+        );
+        assert_eq!(
+            replies(&[Some(i64::MAX), Some(i64::MAX), Some(i64::MIN)]),
+            reply
+        );
+
+        let reply = bitfield(
+            &mut db,
+            &[
+                "OVERFLOW", "FAIL", "INCRBY", "i64", "0", "-1", "INCRBY", "i64", "0", &max, "GET",
+                "i64", "0",
+            ],
+        );
+        assert_eq!(replies(&[None, Some(-1), Some(-1)]), reply);
+
+        // u63 is the widest unsigned type.
+        let reply = bitfield(
+            &mut db,
+            &[
+                "SET", "u63", "64", &max, "INCRBY", "u63", "64", "1", "INCRBY", "u63", "64", "-1",
+            ],
+        );
+        assert_eq!(replies(&[Some(0), Some(0), Some(i64::MAX)]), reply);
+
+        let reply = bitfield(
+            &mut db,
+            &[
+                "OVERFLOW", "SAT", "INCRBY", "u63", "64", "1", "SET", "u63", "64", &min, "INCRBY",
+                "u63", "64", "-1",
+            ],
+        );
+        assert_eq!(replies(&[Some(i64::MAX), Some(i64::MAX), Some(0)]), reply);
+
+        let reply = bitfield(
+            &mut db,
+            &[
+                "OVERFLOW", "FAIL", "SET", "u63", "64", "-1", "INCRBY", "u63", "64", &min, "GET",
+                "u63", "64",
+            ],
+        );
+        assert_eq!(replies(&[None, None, Some(0)]), reply);
+
+        let type_error = error(
+            "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.",
+        );
+        for ty in ["u64", "i65", "i0", "x8", "8"] {
+            assert_eq!(type_error, bitfield(&mut db, &["GET", ty, "0"]), "{ty}");
+        }
+    }
+
+    #[test]
+    fn test_bitfield_errors() {
+        let mut db = KeyValueDb::new();
+
+        assert_eq!(
+            error("Invalid OVERFLOW type specified"),
+            bitfield(&mut db, &["OVERFLOW", "BOUNCE", "GET", "u8", "0"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::Syntax),
+            bitfield(&mut db, &["OVERFLOW"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::Syntax),
+            bitfield(&mut db, &["SET", "u8", "0"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::Syntax),
+            bitfield(&mut db, &["DEL", "u8", "0"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::NotInteger),
+            bitfield(&mut db, &["INCRBY", "u8", "0", "x"])
+        );
+        assert_eq!(
+            error("BITFIELD_RO only supports the GET subcommand"),
+            run(
+                &mut db,
+                &["bitfield_ro", "b", "GET", "u8", "0", "SET", "u8", "0", "1"]
+            )
+        );
+        assert!(db.get_value("b").is_none());
+    }
+}
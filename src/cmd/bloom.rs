@@ -0,0 +1,305 @@
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{
+        bloom::{self, Bloom},
+        notify, Data, Database, Value,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::num::{parse_float, parse_int},
+};
+
+#[derive(Debug)]
+pub(crate) enum BloomCommand {
+    Reserve(BfReserve),
+    Add(BfAdd),
+    Exists(BfExists),
+    Info(BfInfo),
+}
+
+impl BloomCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "bf.reserve" => BfReserve::new(args).map(BloomCommand::Reserve),
+            "bf.add" => BfAdd::new(args, false).map(BloomCommand::Add),
+            "bf.madd" => BfAdd::new(args, true).map(BloomCommand::Add),
+            "bf.exists" => BfExists::new(args).map(BloomCommand::Exists),
+            "bf.info" => BfInfo::new(args).map(BloomCommand::Info),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for BloomCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            BloomCommand::Reserve(cmd) => cmd.execute(db),
+            BloomCommand::Add(cmd) => cmd.execute(db),
+            BloomCommand::Exists(cmd) => cmd.execute(db),
+            BloomCommand::Info(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn get_bloom<'a>(db: &'a mut dyn Database, key: &str) -> Result<Option<&'a mut Bloom>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_bloom_mut())
+        .transpose()
+}
+
+fn read_bloom<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut Bloom>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_bloom_mut())
+        .transpose()
+}
+
+/// `BF.RESERVE key error_rate capacity [EXPANSION expansion] [NONSCALING]`
+#[derive(Debug)]
+pub(crate) struct BfReserve {
+    key: String,
+    error: f64,
+    capacity: u64,
+    expansion: Option<u32>,
+}
+
+impl BfReserve {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let error = parse_float(&args[2])
+            .ok()
+            .filter(|e| *e > 0.0 && *e < 1.0)
+            .ok_or(RedisError::Custom(String::from(
+                "(0 < error rate range < 1)",
+            )))?;
+        let capacity = parse_int(&args[3])
+            .ok()
+            .filter(|c| *c > 0)
+            .ok_or(RedisError::Custom(String::from(
+                "(capacity should be larger than 0)",
+            )))?;
+
+        let mut expansion = None;
+        let mut nonscaling = false;
+        let mut rest = args[4..].iter();
+        while let Some(opt) = rest.next() {
+            match opt.to_lowercase().as_str() {
+                "expansion" => {
+                    let value = rest.next().ok_or(RedisError::Syntax)?;
+                    let n = parse_int(value)
+                        .map_err(|_| RedisError::Custom(String::from("bad expansion")))?;
+                    match u32::try_from(n) {
+                        Ok(n) if n >= 1 => expansion = Some(n),
+                        _ => {
+                            return Err(RedisError::Custom(String::from(
+                                "expansion should be greater or equal to 1",
+                            )))
+                        }
+                    }
+                }
+                "nonscaling" => nonscaling = true,
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        if nonscaling && expansion.is_some() {
+            return Err(RedisError::Custom(String::from(
+                "Nonscaling filters cannot expand",
+            )));
+        }
+
+        Ok(BfReserve {
+            key: args[1].clone(),
+            error,
+            capacity: capacity as u64,
+            expansion: match nonscaling {
+                true => None,
+                false => Some(expansion.unwrap_or(bloom::DEFAULT_EXPANSION)),
+            },
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        if db.get_value(&self.key).is_some() {
+            return Err(RedisError::Custom(String::from("item exists")));
+        }
+
+        let bloom =
+            Bloom::new(self.error, self.capacity, self.expansion).ok_or(RedisError::Custom(
+                String::from("(capacity and error rate need a filter that is too large)"),
+            ))?;
+        db.insert(&self.key, Value::from(Data::Bloom(Box::new(bloom))));
+        db.touch(&self.key);
+        db.notify(notify::MODULE, "bf.reserve", &self.key);
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
+
+/// `BF.ADD key item` and `BF.MADD key item [item ...]`, which create the filter with the
+/// default error rate and capacity if needed.
+#[derive(Debug)]
+pub(crate) struct BfAdd {
+    key: String,
+    items: Vec<String>,
+    multi: bool,
+}
+
+impl BfAdd {
+    pub(crate) fn new(args: Vec<String>, multi: bool) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+        if !multi && args.len() != 3 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(BfAdd {
+            key: args[1].clone(),
+            items: args[2..].to_vec(),
+            multi,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let created = get_bloom(db, &self.key)?.is_none();
+        let bloom = db
+            .get_or_insert_with(&self.key, &|| {
+                Data::Bloom(Box::new(
+                    Bloom::new(
+                        bloom::DEFAULT_ERROR_RATE,
+                        bloom::DEFAULT_CAPACITY,
+                        Some(bloom::DEFAULT_EXPANSION),
+                    )
+                    .expect("the default filter is small"),
+                ))
+            })
+            .as_bloom_mut()?;
+
+        // Each item gets its own reply, so a full filter only fails the items that did not
+        // fit.
+        let mut replies: Vec<_> = self
+            .items
+            .iter()
+            .map(|item| {
+                bloom
+                    .add(item.as_bytes())
+                    .map_err(|_| RedisError::Custom(String::from("non scaling filter is full")))
+            })
+            .collect();
+
+        if created || replies.iter().any(|reply| reply == &Ok(true)) {
+            db.touch(&self.key);
+            let event = if self.multi { "bf.madd" } else { "bf.add" };
+            db.notify(notify::MODULE, event, &self.key);
+        }
+
+        match self.multi {
+            true => Ok(Frame::Array(
+                replies
+                    .into_iter()
+                    .map(|reply| {
+                        reply.map_or_else(Frame::from, |added| Frame::Integer(added as i64))
+                    })
+                    .collect(),
+            )),
+            false => replies.remove(0).map(|added| Frame::Integer(added as i64)),
+        }
+    }
+}
+
+/// `BF.EXISTS key item`
+#[derive(Debug)]
+pub(crate) struct BfExists {
+    key: String,
+    item: String,
+}
+
+impl BfExists {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+        if args.len() != 3 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(BfExists {
+            key: args[1].clone(),
+            item: args[2].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let exists =
+            read_bloom(db, &self.key)?.is_some_and(|bloom| bloom.contains(self.item.as_bytes()));
+        Ok(Frame::Integer(exists as i64))
+    }
+}
+
+/// `BF.INFO key [CAPACITY | SIZE | FILTERS | ITEMS | EXPANSION]`
+#[derive(Debug)]
+pub(crate) struct BfInfo {
+    key: String,
+    field: Option<String>,
+}
+
+impl BfInfo {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+        if args.len() > 3 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(BfInfo {
+            key: args[1].clone(),
+            field: args.get(2).map(|s| s.to_lowercase()),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let bloom =
+            read_bloom(db, &self.key)?.ok_or(RedisError::Custom(String::from("not found")))?;
+
+        let expansion = match bloom.expansion() {
+            Some(expansion) => Frame::Integer(expansion as i64),
+            None => Frame::Null,
+        };
+        let fields = [
+            ("Capacity", Frame::Integer(bloom.capacity() as i64)),
+            ("Size", Frame::Integer(bloom.size() as i64)),
+            (
+                "Number of filters",
+                Frame::Integer(bloom.layers().len() as i64),
+            ),
+            (
+                "Number of items inserted",
+                Frame::Integer(bloom.items() as i64),
+            ),
+            ("Expansion rate", expansion),
+        ];
+
+        let Some(field) = &self.field else {
+            return Ok(Frame::Array(
+                fields
+                    .into_iter()
+                    .flat_map(|(name, value)| [Frame::SimpleString(name.to_owned()), value])
+                    .collect(),
+            ));
+        };
+
+        let index = match field.as_str() {
+            "capacity" => 0,
+            "size" => 1,
+            "filters" => 2,
+            "items" => 3,
+            "expansion" => 4,
+            _ => {
+                return Err(RedisError::Custom(String::from(
+                    "Invalid information value",
+                )))
+            }
+        };
+        let (_, value) = fields.into_iter().nth(index).expect("known field");
+        Ok(Frame::Array(vec![value]))
+    }
+}
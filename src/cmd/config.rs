@@ -0,0 +1,157 @@
+use crate::{
+    acl::Acl,
+    cmd::check_arity,
+    db::{encoding, notify, Database},
+    error::RedisError,
+    frame::Frame,
+    util::glob,
+};
+
+#[derive(Debug)]
+pub(crate) enum ConfigCommand {
+    Get(ConfigGet),
+    Set(ConfigSet),
+}
+
+impl ConfigCommand {
+    pub(crate) fn parse(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        match args[1].to_lowercase().as_str() {
+            "get" => ConfigGet::new(args).map(ConfigCommand::Get),
+            "set" => ConfigSet::new(args).map(ConfigCommand::Set),
+            _ => Err(RedisError::Custom(format!(
+                "unknown subcommand '{}'. Try CONFIG HELP.",
+                args[1]
+            ))),
+        }
+    }
+
+    /// Runs the command, with the ACL users holding the password of the default user.
+    pub(crate) fn execute(
+        &self,
+        db: &mut dyn Database,
+        acl: &mut Acl,
+    ) -> Result<Frame, RedisError> {
+        match self {
+            ConfigCommand::Get(cmd) => cmd.execute(db, acl),
+            ConfigCommand::Set(cmd) => cmd.execute(db, acl),
+        }
+    }
+}
+
+/// The parameter enabling keyspace notifications.
+const NOTIFY_KEYSPACE_EVENTS: &str = "notify-keyspace-events";
+/// The password of the default user, where an empty one lets clients in without AUTH.
+const REQUIREPASS: &str = "requirepass";
+
+/// `CONFIG GET parameter [parameter ...]`, where parameters are glob patterns. Only the
+/// encoding thresholds, `notify-keyspace-events` and `requirepass` can be read.
+#[derive(Debug)]
+pub(crate) struct ConfigGet {
+    patterns: Vec<String>,
+}
+
+impl ConfigGet {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        if args.len() < 3 {
+            return Err(RedisError::WrongArity(String::from("config|get")));
+        }
+
+        Ok(ConfigGet {
+            patterns: args[2..].iter().map(|p| p.to_lowercase()).collect(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database, acl: &mut Acl) -> Result<Frame, RedisError> {
+        let matches = |name: &str| self.patterns.iter().any(|p| glob::matches(p, name));
+
+        let thresholds = db.thresholds();
+        let mut items: Vec<String> = encoding::NAMES
+            .iter()
+            .filter(|name| matches(name))
+            .flat_map(|name| {
+                let value = thresholds.get(name).expect("every name has a threshold");
+                [name.to_string(), value.to_string()]
+            })
+            .collect();
+
+        if matches(NOTIFY_KEYSPACE_EVENTS) {
+            items.push(NOTIFY_KEYSPACE_EVENTS.to_owned());
+            items.push(notify::format(db.notify_flags()));
+        }
+        if matches(REQUIREPASS) {
+            items.push(REQUIREPASS.to_owned());
+            items.push(acl.requirepass().to_owned());
+        }
+
+        Ok(Frame::Arrays(items))
+    }
+}
+
+/// `CONFIG SET parameter value [parameter value ...]`, which changes nothing unless every
+/// value is valid. The `e` class of `notify-keyspace-events` is accepted for compatibility,
+/// but no event is ever published for it since keys are never evicted.
+#[derive(Debug)]
+pub(crate) struct ConfigSet {
+    values: Vec<ConfigValue>,
+}
+
+#[derive(Debug)]
+enum ConfigValue {
+    Threshold(&'static str, usize),
+    NotifyKeyspaceEvents(u32),
+    RequirePass(String),
+}
+
+impl ConfigSet {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        if args.len() < 4 || !args.len().is_multiple_of(2) {
+            return Err(RedisError::WrongArity(String::from("config|set")));
+        }
+
+        let values = args[2..]
+            .chunks_exact(2)
+            .map(|pair| {
+                if pair[0].eq_ignore_ascii_case(NOTIFY_KEYSPACE_EVENTS) {
+                    let flags = notify::parse(&pair[1]).ok_or(RedisError::Custom(format!(
+                        "CONFIG SET failed (possibly related to argument '{}') - Invalid event class character. Use 'Ag$lshzxeKEtmdn'.",
+                        pair[0]
+                    )))?;
+                    return Ok(ConfigValue::NotifyKeyspaceEvents(flags));
+                }
+                if pair[0].eq_ignore_ascii_case(REQUIREPASS) {
+                    return Ok(ConfigValue::RequirePass(pair[1].clone()));
+                }
+
+                let name = encoding::find(&pair[0]).ok_or(RedisError::Custom(format!(
+                    "Unknown option or number of arguments for CONFIG SET - '{}'",
+                    pair[0]
+                )))?;
+                let value = pair[1].parse::<usize>().map_err(|_| {
+                    RedisError::Custom(format!(
+                        "CONFIG SET failed (possibly related to argument '{}') - argument couldn't be parsed into an integer",
+                        pair[0]
+                    ))
+                })?;
+                Ok(ConfigValue::Threshold(name, value))
+            })
+            .collect::<Result<_, RedisError>>()?;
+
+        Ok(ConfigSet { values })
+    }
+
+    fn execute(&self, db: &mut dyn Database, acl: &mut Acl) -> Result<Frame, RedisError> {
+        for value in &self.values {
+            match value {
+                ConfigValue::Threshold(name, value) => {
+                    db.thresholds_mut().set(name, *value);
+                }
+                ConfigValue::NotifyKeyspaceEvents(flags) => db.set_notify_flags(*flags),
+                ConfigValue::RequirePass(password) => acl.set_requirepass(password),
+            }
+        }
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
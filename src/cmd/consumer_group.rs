@@ -0,0 +1,1351 @@
+use std::{
+    ops::Bound,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    cmd::{
+        check_arity,
+        stream::{
+            entries_frame, entry_frame, get_stream, parse_block_timeout, parse_end, parse_id,
+            parse_start, read_stream,
+        },
+        Block, Command, Execute,
+    },
+    db::{
+        notify,
+        stream::{ConsumerGroup, Stream, StreamId},
+        Data, Database,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::{num::parse_int, time::to_unix_millis},
+};
+
+/// XAUTOCLAIM scans at most this many pending entries for every entry it may claim.
+const AUTOCLAIM_ATTEMPTS_FACTOR: usize = 10;
+
+#[derive(Debug)]
+pub(crate) enum ConsumerGroupCommand {
+    Xgroup(Xgroup),
+    Xreadgroup(Xreadgroup),
+    Xack(Xack),
+    Xpending(Xpending),
+    Xclaim(Xclaim),
+    Xautoclaim(Xautoclaim),
+}
+
+impl ConsumerGroupCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "xgroup" => Xgroup::new(args).map(ConsumerGroupCommand::Xgroup),
+            "xack" => Xack::new(args).map(ConsumerGroupCommand::Xack),
+            "xpending" => Xpending::new(args).map(ConsumerGroupCommand::Xpending),
+            "xclaim" => Xclaim::new(args).map(ConsumerGroupCommand::Xclaim),
+            "xautoclaim" => Xautoclaim::new(args).map(ConsumerGroupCommand::Xautoclaim),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for ConsumerGroupCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            ConsumerGroupCommand::Xgroup(cmd) => cmd.execute(db),
+            ConsumerGroupCommand::Xreadgroup(cmd) => {
+                cmd.execute(db).map(|read| read.unwrap_or(Frame::NullArray))
+            }
+            ConsumerGroupCommand::Xack(cmd) => cmd.execute(db),
+            ConsumerGroupCommand::Xpending(cmd) => cmd.execute(db),
+            ConsumerGroupCommand::Xclaim(cmd) => cmd.execute(db),
+            ConsumerGroupCommand::Xautoclaim(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn now() -> u64 {
+    to_unix_millis(SystemTime::now())
+}
+
+fn no_such_key_or_group(key: &str, group: &str) -> RedisError {
+    RedisError::NoGroup(format!("No such key '{key}' or consumer group '{group}'"))
+}
+
+pub(crate) fn no_such_group(key: &str, group: &str) -> RedisError {
+    RedisError::NoGroup(format!(
+        "No such consumer group '{group}' for key name '{key}'"
+    ))
+}
+
+/// Returns the stream at `key`, or `no_group` unless it has a group named `group`.
+fn get_group_stream<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+    group: &str,
+    no_group: impl FnOnce() -> RedisError,
+) -> Result<&'a mut Stream, RedisError> {
+    match get_stream(db, key)? {
+        Some(stream) if stream.group(group).is_some() => Ok(stream),
+        _ => Err(no_group()),
+    }
+}
+
+fn command(args: &[&str]) -> Frame {
+    Frame::Arrays(args.iter().map(|s| s.to_string()).collect())
+}
+
+/// Sets the pending entry `id` of a replica to the exact state it has here.
+fn claim_frame(key: &str, name: &str, group: &ConsumerGroup, id: StreamId) -> Frame {
+    let pending = &group.pending()[&id];
+
+    command(&[
+        "XCLAIM",
+        key,
+        name,
+        &pending.consumer,
+        "0",
+        &id.to_string(),
+        "TIME",
+        &pending.delivery_time.to_string(),
+        "RETRYCOUNT",
+        &pending.delivery_count.to_string(),
+        "FORCE",
+        "JUSTID",
+        "LASTID",
+        &group.last_id.to_string(),
+    ])
+}
+
+/// Sets the last delivered ID and read counter of a group on replicas.
+fn setid_frame(key: &str, name: &str, group: &ConsumerGroup) -> Frame {
+    let entries_read = group
+        .entries_read
+        .map_or(String::from("-1"), |read| read.to_string());
+
+    command(&[
+        "XGROUP",
+        "SETID",
+        key,
+        name,
+        &group.last_id.to_string(),
+        "ENTRIESREAD",
+        &entries_read,
+    ])
+}
+
+fn create_consumer_frame(key: &str, group: &str, consumer: &str) -> Frame {
+    command(&["XGROUP", "CREATECONSUMER", key, group, consumer])
+}
+
+fn ack_frame(key: &str, group: &str, id: StreamId) -> Frame {
+    command(&["XACK", key, group, &id.to_string()])
+}
+
+/// Replaces the propagation of the command with `frames`, if it changed anything.
+fn rewrite_all(db: &mut dyn Database, key: &str, frames: Vec<Frame>) {
+    if frames.is_empty() {
+        return;
+    }
+
+    for frame in frames {
+        db.rewrite(frame);
+    }
+    db.touch(key);
+}
+
+/// Parses the ID a group starts delivering after, where `$` is the last ID of the stream.
+fn parse_group_id(s: &str) -> Result<Option<StreamId>, RedisError> {
+    match s {
+        "$" => Ok(None),
+        _ => parse_id(s, 0).map(Some),
+    }
+}
+
+fn parse_entries_read(s: &str) -> Result<Option<u64>, RedisError> {
+    match parse_int(s)? {
+        -1 => Ok(None),
+        n if n < 0 => Err(RedisError::Custom(String::from(
+            "value for ENTRIESREAD must be positive or -1",
+        ))),
+        n => Ok(Some(n as u64)),
+    }
+}
+
+#[derive(Debug)]
+enum XgroupSubcommand {
+    /// `CREATE key group id|$ [MKSTREAM] [ENTRIESREAD entries-read]`
+    Create {
+        id: Option<StreamId>,
+        mkstream: bool,
+        entries_read: Option<u64>,
+    },
+    /// `SETID key group id|$ [ENTRIESREAD entries-read]`
+    Setid {
+        id: Option<StreamId>,
+        entries_read: Option<u64>,
+    },
+    Destroy,
+    CreateConsumer(String),
+    DelConsumer(String),
+}
+
+#[derive(Debug)]
+pub(crate) struct Xgroup {
+    key: String,
+    group: String,
+    subcommand: XgroupSubcommand,
+}
+
+impl Xgroup {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let name = args[1].to_lowercase();
+        let arity = |min: usize, max: usize| {
+            if args.len() < min || args.len() > max {
+                return Err(RedisError::WrongArity(format!("xgroup|{name}")));
+            }
+            Ok(())
+        };
+
+        let subcommand = match name.as_str() {
+            "create" | "setid" => {
+                arity(5, 8)?;
+
+                let create = name == "create";
+                let mut mkstream = false;
+                let mut entries_read = None;
+
+                let mut rest = args[5..].iter();
+                while let Some(opt) = rest.next() {
+                    match opt.to_lowercase().as_str() {
+                        "mkstream" if create => mkstream = true,
+                        "entriesread" => {
+                            let value = rest.next().ok_or(RedisError::Syntax)?;
+                            entries_read = parse_entries_read(value)?;
+                        }
+                        _ => return Err(RedisError::Syntax),
+                    }
+                }
+
+                let id = parse_group_id(&args[4])?;
+                if create {
+                    XgroupSubcommand::Create {
+                        id,
+                        mkstream,
+                        entries_read,
+                    }
+                } else {
+                    XgroupSubcommand::Setid { id, entries_read }
+                }
+            }
+            "destroy" => {
+                arity(4, 4)?;
+                XgroupSubcommand::Destroy
+            }
+            "createconsumer" => {
+                arity(5, 5)?;
+                XgroupSubcommand::CreateConsumer(args[4].clone())
+            }
+            "delconsumer" => {
+                arity(5, 5)?;
+                XgroupSubcommand::DelConsumer(args[4].clone())
+            }
+            _ => {
+                return Err(RedisError::Custom(format!(
+                    "unknown subcommand '{}'. Try XGROUP HELP.",
+                    args[1]
+                )))
+            }
+        };
+
+        Ok(Xgroup {
+            key: args[2].clone(),
+            group: args[3].clone(),
+            subcommand,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        if let XgroupSubcommand::Create { mkstream: true, .. } = self.subcommand {
+            if get_stream(db, &self.key)?.is_none() {
+                db.get_or_insert_with(&self.key, &|| Data::Stream(Box::new(Stream::new())));
+            }
+        }
+
+        let Some(stream) = get_stream(db, &self.key)? else {
+            return Err(RedisError::Custom(String::from(
+                "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.",
+            )));
+        };
+
+        let reply = match &self.subcommand {
+            XgroupSubcommand::Create {
+                id, entries_read, ..
+            } => {
+                let id = id.unwrap_or(stream.last_id());
+                if !stream.create_group(&self.group, id, *entries_read) {
+                    return Err(RedisError::BusyGroup);
+                }
+                Frame::SimpleString(String::from("OK"))
+            }
+            XgroupSubcommand::Setid { id, entries_read } => {
+                let id = id.unwrap_or(stream.last_id());
+                let group = stream
+                    .group_mut(&self.group)
+                    .ok_or_else(|| no_such_group(&self.key, &self.group))?;
+                group.last_id = id;
+                group.entries_read = *entries_read;
+                Frame::SimpleString(String::from("OK"))
+            }
+            XgroupSubcommand::Destroy => {
+                if !stream.destroy_group(&self.group) {
+                    return Ok(Frame::Integer(0));
+                }
+                Frame::Integer(1)
+            }
+            XgroupSubcommand::CreateConsumer(consumer) => {
+                let group = stream
+                    .group_mut(&self.group)
+                    .ok_or_else(|| no_such_group(&self.key, &self.group))?;
+                if !group.create_consumer(consumer, now()) {
+                    return Ok(Frame::Integer(0));
+                }
+                Frame::Integer(1)
+            }
+            XgroupSubcommand::DelConsumer(consumer) => {
+                let group = stream
+                    .group_mut(&self.group)
+                    .ok_or_else(|| no_such_group(&self.key, &self.group))?;
+                match group.delete_consumer(consumer) {
+                    Some(pending) => Frame::Integer(pending as i64),
+                    None => return Ok(Frame::Integer(0)),
+                }
+            }
+        };
+
+        db.touch(&self.key);
+
+        let event = match self.subcommand {
+            XgroupSubcommand::Create { .. } => "xgroup-create",
+            XgroupSubcommand::Setid { .. } => "xgroup-setid",
+            XgroupSubcommand::Destroy => "xgroup-destroy",
+            XgroupSubcommand::CreateConsumer(_) => "xgroup-createconsumer",
+            XgroupSubcommand::DelConsumer(_) => "xgroup-delconsumer",
+        };
+        db.notify(notify::STREAM, event, &self.key);
+
+        Ok(reply)
+    }
+}
+
+/// `XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK]
+/// STREAMS key [key ...] id [id ...]`.
+#[derive(Debug, Clone)]
+pub(crate) struct Xreadgroup {
+    group: String,
+    consumer: String,
+    keys: Vec<String>,
+    /// `None` for `>`, which delivers entries never delivered to the group. An ID reads the
+    /// history of the consumer: its pending entries after that ID.
+    after: Vec<Option<StreamId>>,
+    count: Option<usize>,
+    noack: bool,
+}
+
+/// Parses XREADGROUP, which only blocks when it has a BLOCK option.
+pub(crate) fn parse_xreadgroup(args: Vec<String>) -> Result<Command, RedisError> {
+    check_arity(&args, 7)?;
+
+    if !args[1].eq_ignore_ascii_case("group") {
+        return Err(RedisError::Syntax);
+    }
+
+    let mut count = None;
+    let mut block = None;
+    let mut noack = false;
+    let mut index = 4;
+
+    loop {
+        let opt = args.get(index).ok_or(RedisError::Syntax)?.to_lowercase();
+        let value = args.get(index + 1);
+        match (opt.as_str(), value) {
+            ("count", Some(value)) => count = Some(parse_int(value)?.max(0) as usize),
+            ("block", Some(value)) => block = Some(parse_block_timeout(value)?),
+            ("noack", _) => {
+                noack = true;
+                index += 1;
+                continue;
+            }
+            ("streams", _) => break,
+            _ => return Err(RedisError::Syntax),
+        }
+        index += 2;
+    }
+
+    let streams = &args[index + 1..];
+    if streams.is_empty() || streams.len() % 2 == 1 {
+        return Err(RedisError::Custom(String::from(
+            "Unbalanced 'xreadgroup' list of streams: for each stream key an ID or '>' must be specified.",
+        )));
+    }
+
+    let (keys, ids) = streams.split_at(streams.len() / 2);
+    let after = ids
+        .iter()
+        .map(|id| match id.as_str() {
+            ">" => Ok(None),
+            "$" => Err(RedisError::Custom(String::from(
+                "The $ ID is meaningless in the context of XREADGROUP: you want to read the history of this consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID would just return an empty result set.",
+            ))),
+            _ => parse_id(id, 0).map(Some),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let read = Xreadgroup {
+        group: args[2].clone(),
+        consumer: args[3].clone(),
+        keys: keys.to_vec(),
+        after,
+        // A count of zero means no limit.
+        count: count.filter(|c| *c > 0),
+        noack,
+    };
+
+    Ok(match block {
+        Some(timeout) => Command::Blocking(Arc::new(XreadgroupBlock { read, timeout })),
+        None => Command::ConsumerGroup(ConsumerGroupCommand::Xreadgroup(read)),
+    })
+}
+
+impl Xreadgroup {
+    fn no_group(&self, key: &str) -> RedisError {
+        RedisError::NoGroup(format!(
+            "No such key '{key}' or consumer group '{}' in XREADGROUP with GROUP option",
+            self.group
+        ))
+    }
+
+    /// Delivers new entries of the stream at `keys[index]`, or reads the history of the
+    /// consumer, returning `None` if there is nothing new.
+    fn read_stream(
+        &self,
+        db: &mut dyn Database,
+        index: usize,
+        now: u64,
+    ) -> Result<Option<Frame>, RedisError> {
+        let key = &self.keys[index];
+        let stream = get_group_stream(db, key, &self.group, || self.no_group(key))?;
+
+        let mut propagated = Vec::new();
+        let group = stream.group_mut(&self.group).expect("group exists");
+        if group.create_consumer(&self.consumer, now) {
+            propagated.push(create_consumer_frame(key, &self.group, &self.consumer));
+        }
+        group.touch_consumer(&self.consumer, now, false);
+
+        let entries = match self.after[index] {
+            None => {
+                let entries = match group.last_id.next() {
+                    Some(start) => stream.range(start, StreamId::MAX, false, self.count),
+                    None => vec![],
+                };
+
+                for (id, _) in &entries {
+                    stream.advance_group(&self.group, *id);
+
+                    if !self.noack {
+                        let group = stream.group_mut(&self.group).expect("group exists");
+                        group.assign(*id, &self.consumer, now, 1);
+                        propagated.push(claim_frame(key, &self.group, group, *id));
+                    }
+                }
+
+                if !entries.is_empty() {
+                    let group = stream.group_mut(&self.group).expect("group exists");
+                    group.touch_consumer(&self.consumer, now, true);
+                    propagated.push(setid_frame(key, &self.group, group));
+                }
+
+                (!entries.is_empty()).then(|| entries_frame(entries))
+            }
+            Some(after) => {
+                let ids: Vec<StreamId> = group.consumers()[&self.consumer]
+                    .pending()
+                    .range((Bound::Excluded(after), Bound::Unbounded))
+                    .take(self.count.unwrap_or(usize::MAX))
+                    .copied()
+                    .collect();
+
+                let mut entries = Vec::new();
+                for id in ids {
+                    // Entries deleted from the stream are still pending, but have no fields.
+                    let Some(fields) = stream.get(id) else {
+                        entries.push(Frame::Array(vec![
+                            Frame::BulkString(id.to_string()),
+                            Frame::NullArray,
+                        ]));
+                        continue;
+                    };
+
+                    let group = stream.group_mut(&self.group).expect("group exists");
+                    let count = group.pending()[&id].delivery_count + 1;
+                    group.assign(id, &self.consumer, now, count);
+                    propagated.push(claim_frame(key, &self.group, group, id));
+
+                    entries.push(entry_frame((id, fields)));
+                }
+
+                Some(Frame::Array(entries))
+            }
+        };
+
+        rewrite_all(db, key, propagated);
+
+        Ok(entries.map(|entries| Frame::Array(vec![Frame::BulkString(key.clone()), entries])))
+    }
+
+    /// Reads every stream, returning `None` if none of them has anything to offer.
+    fn execute(&self, db: &mut dyn Database) -> Result<Option<Frame>, RedisError> {
+        for key in &self.keys {
+            get_group_stream(db, key, &self.group, || self.no_group(key))?;
+        }
+
+        let now = now();
+        let mut streams = Vec::new();
+        for index in 0..self.keys.len() {
+            streams.extend(self.read_stream(db, index, now)?);
+        }
+
+        Ok((!streams.is_empty()).then_some(Frame::Array(streams)))
+    }
+}
+
+/// XREADGROUP with the BLOCK option. Reading history never blocks, as it always replies.
+#[derive(Debug)]
+struct XreadgroupBlock {
+    read: Xreadgroup,
+    timeout: Option<Duration>,
+}
+
+impl Block for XreadgroupBlock {
+    fn keys(&self) -> &[String] {
+        &self.read.keys
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn serve(&self, db: &mut dyn Database, key: &str) -> Result<Option<Frame>, RedisError> {
+        let Some(index) = self.read.keys.iter().position(|k| k == key) else {
+            return Ok(None);
+        };
+
+        // A group destroyed while the client waits makes it give up with an error.
+        match self.read.read_stream(db, index, now()) {
+            Ok(stream) => Ok(stream.map(|stream| Frame::Array(vec![stream]))),
+            Err(err @ RedisError::NoGroup(_)) => Ok(Some(Frame::from(err))),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Option<Frame>, RedisError> {
+        self.read.execute(db)
+    }
+}
+
+/// `XACK key group id [id ...]`
+#[derive(Debug)]
+pub(crate) struct Xack {
+    key: String,
+    group: String,
+    ids: Vec<StreamId>,
+}
+
+impl Xack {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Ok(Xack {
+            key: args[1].clone(),
+            group: args[2].clone(),
+            ids: args[3..]
+                .iter()
+                .map(|id| parse_id(id, 0))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(group) = get_stream(db, &self.key)?.and_then(|s| s.group_mut(&self.group)) else {
+            return Ok(Frame::Integer(0));
+        };
+
+        let acked = self.ids.iter().filter(|id| group.ack(**id)).count();
+
+        if acked > 0 {
+            db.touch(&self.key);
+        }
+
+        Ok(Frame::Integer(acked as i64))
+    }
+}
+
+/// `[IDLE min-idle-time] start end count [consumer]`
+#[derive(Debug)]
+struct PendingRange {
+    min_idle: u64,
+    start: StreamId,
+    end: StreamId,
+    count: usize,
+    consumer: Option<String>,
+}
+
+/// `XPENDING key group [[IDLE min-idle-time] start end count [consumer]]`, which summarizes
+/// the PEL of a group unless given a range.
+#[derive(Debug)]
+pub(crate) struct Xpending {
+    key: String,
+    group: String,
+    range: Option<PendingRange>,
+}
+
+impl Xpending {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        let range = match &args[3..] {
+            [] => None,
+            rest => {
+                let (min_idle, rest) = match rest {
+                    [opt, idle, rest @ ..] if opt.eq_ignore_ascii_case("idle") => {
+                        (parse_int(idle)?.max(0) as u64, rest)
+                    }
+                    _ => (0, rest),
+                };
+
+                match rest {
+                    [start, end, count, consumer @ ..] if consumer.len() <= 1 => {
+                        Some(PendingRange {
+                            min_idle,
+                            start: parse_start(start)?,
+                            end: parse_end(end)?,
+                            count: parse_int(count)?.max(0) as usize,
+                            consumer: consumer.first().cloned(),
+                        })
+                    }
+                    _ => return Err(RedisError::Syntax),
+                }
+            }
+        };
+
+        Ok(Xpending {
+            key: args[1].clone(),
+            group: args[2].clone(),
+            range,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(group) = read_stream(db, &self.key)?.and_then(|s| s.group(&self.group)) else {
+            return Err(no_such_key_or_group(&self.key, &self.group));
+        };
+
+        match &self.range {
+            None => Ok(pending_summary(group)),
+            Some(range) => Ok(pending_range(group, range, now())),
+        }
+    }
+}
+
+/// `[count, smallest ID, greatest ID, [[consumer, count], ...]]`
+fn pending_summary(group: &ConsumerGroup) -> Frame {
+    let pending = group.pending();
+    let (Some((first, _)), Some((last, _))) = (pending.first_key_value(), pending.last_key_value())
+    else {
+        return Frame::Array(vec![
+            Frame::Integer(0),
+            Frame::Null,
+            Frame::Null,
+            Frame::NullArray,
+        ]);
+    };
+
+    let consumers = group
+        .consumers()
+        .iter()
+        .filter(|(_, consumer)| !consumer.pending().is_empty())
+        .map(|(name, consumer)| {
+            Frame::Arrays(vec![name.clone(), consumer.pending().len().to_string()])
+        })
+        .collect();
+
+    Frame::Array(vec![
+        Frame::Integer(pending.len() as i64),
+        Frame::BulkString(first.to_string()),
+        Frame::BulkString(last.to_string()),
+        Frame::Array(consumers),
+    ])
+}
+
+/// `[[id, consumer, idle, delivery count], ...]`
+fn pending_range(group: &ConsumerGroup, range: &PendingRange, now: u64) -> Frame {
+    if range.start > range.end {
+        return Frame::Array(vec![]);
+    }
+
+    let bounds = range.start..=range.end;
+    let ids: Box<dyn Iterator<Item = &StreamId>> = match &range.consumer {
+        Some(name) => match group.consumers().get(name) {
+            Some(consumer) => Box::new(consumer.pending().range(bounds)),
+            None => return Frame::Array(vec![]),
+        },
+        None => Box::new(group.pending().range(bounds).map(|(id, _)| id)),
+    };
+
+    let entries = ids
+        .map(|id| (id, &group.pending()[id]))
+        .filter(|(_, pending)| now.saturating_sub(pending.delivery_time) >= range.min_idle)
+        .take(range.count)
+        .map(|(id, pending)| {
+            Frame::Array(vec![
+                Frame::BulkString(id.to_string()),
+                Frame::BulkString(pending.consumer.clone()),
+                Frame::Integer(now.saturating_sub(pending.delivery_time) as i64),
+                Frame::Integer(pending.delivery_count as i64),
+            ])
+        })
+        .collect();
+
+    Frame::Array(entries)
+}
+
+/// When a claimed entry counts as delivered.
+#[derive(Debug)]
+enum DeliveryTime {
+    /// `IDLE ms`: that long ago.
+    Idle(u64),
+    /// `TIME unix-time-milliseconds`
+    At(u64),
+}
+
+/// `XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms]
+/// [TIME unix-time-milliseconds] [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID lastid]`
+#[derive(Debug)]
+pub(crate) struct Xclaim {
+    key: String,
+    group: String,
+    consumer: String,
+    min_idle: u64,
+    ids: Vec<StreamId>,
+    delivery_time: Option<DeliveryTime>,
+    retry_count: Option<u64>,
+    /// Creates pending entries for IDs that exist in the stream but are not pending.
+    force: bool,
+    /// Replies with IDs only, and leaves the delivery counts alone.
+    justid: bool,
+    last_id: Option<StreamId>,
+}
+
+impl Xclaim {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 6)?;
+
+        let parse_millis = |s: &str, what: &str| {
+            parse_int(s)
+                .map(|n| n.max(0) as u64)
+                .map_err(|_| RedisError::Custom(format!("Invalid {what} argument for XCLAIM")))
+        };
+
+        let min_idle = parse_millis(&args[4], "min-idle-time")?;
+
+        // IDs go on until the first argument that is not one.
+        let mut rest = args[5..].iter().peekable();
+        let mut ids = Vec::new();
+        while let Some(id) = rest.peek().and_then(|s| StreamId::parse(s, 0)) {
+            ids.push(id);
+            rest.next();
+        }
+
+        let mut claim = Xclaim {
+            key: args[1].clone(),
+            group: args[2].clone(),
+            consumer: args[3].clone(),
+            min_idle,
+            ids,
+            delivery_time: None,
+            retry_count: None,
+            force: false,
+            justid: false,
+            last_id: None,
+        };
+
+        while let Some(opt) = rest.next() {
+            match opt.to_lowercase().as_str() {
+                "force" => claim.force = true,
+                "justid" => claim.justid = true,
+                name @ ("idle" | "time" | "retrycount" | "lastid") => {
+                    let value = rest.next().ok_or(RedisError::Syntax)?;
+                    match name {
+                        "idle" => {
+                            let idle = parse_millis(value, "IDLE option")?;
+                            claim.delivery_time = Some(DeliveryTime::Idle(idle));
+                        }
+                        "time" => {
+                            let time = parse_millis(value, "TIME option")?;
+                            claim.delivery_time = Some(DeliveryTime::At(time));
+                        }
+                        "retrycount" => {
+                            claim.retry_count = Some(parse_millis(value, "RETRYCOUNT option")?)
+                        }
+                        _ => claim.last_id = Some(parse_id(value, 0)?),
+                    }
+                }
+                _ => {
+                    return Err(RedisError::Custom(format!(
+                        "Unrecognized XCLAIM option '{opt}'"
+                    )))
+                }
+            }
+        }
+
+        Ok(claim)
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let now = now();
+        // Deliveries can't happen in the future.
+        let delivery_time = match self.delivery_time {
+            Some(DeliveryTime::Idle(idle)) => now.saturating_sub(idle),
+            Some(DeliveryTime::At(time)) => time.min(now),
+            None => now,
+        };
+
+        let stream = get_group_stream(db, &self.key, &self.group, || {
+            no_such_key_or_group(&self.key, &self.group)
+        })?;
+
+        let mut propagated = Vec::new();
+        let group = stream.group_mut(&self.group).expect("group exists");
+
+        let last_id = self.last_id.filter(|id| *id > group.last_id);
+        if let Some(id) = last_id {
+            group.last_id = id;
+        }
+        if group.create_consumer(&self.consumer, now) {
+            propagated.push(create_consumer_frame(
+                &self.key,
+                &self.group,
+                &self.consumer,
+            ));
+        }
+
+        let mut claimed = Vec::new();
+        for &id in &self.ids {
+            let fields = stream.get(id);
+            let group = stream.group_mut(&self.group).expect("group exists");
+
+            let count = match (group.pending().get(&id), &fields) {
+                // The entry was deleted from the stream, so there is nothing left to claim.
+                (Some(_), None) => {
+                    group.ack(id);
+                    propagated.push(ack_frame(&self.key, &self.group, id));
+                    continue;
+                }
+                (Some(pending), Some(_)) => {
+                    if now.saturating_sub(pending.delivery_time) < self.min_idle {
+                        continue;
+                    }
+                    pending.delivery_count
+                }
+                (None, Some(_)) if self.force => 0,
+                (None, _) => continue,
+            };
+
+            let count = match self.retry_count {
+                Some(count) => count,
+                None if self.justid => count,
+                None => count + 1,
+            };
+            group.assign(id, &self.consumer, delivery_time, count);
+            propagated.push(claim_frame(&self.key, &self.group, group, id));
+
+            claimed.push(match fields {
+                Some(fields) if !self.justid => entry_frame((id, fields)),
+                _ => Frame::BulkString(id.to_string()),
+            });
+        }
+
+        let group = stream.group_mut(&self.group).expect("group exists");
+        group.touch_consumer(&self.consumer, now, !claimed.is_empty());
+        if last_id.is_some() {
+            propagated.push(setid_frame(&self.key, &self.group, group));
+        }
+
+        rewrite_all(db, &self.key, propagated);
+
+        Ok(Frame::Array(claimed))
+    }
+}
+
+/// `XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]`
+#[derive(Debug)]
+pub(crate) struct Xautoclaim {
+    key: String,
+    group: String,
+    consumer: String,
+    min_idle: u64,
+    start: StreamId,
+    count: usize,
+    justid: bool,
+}
+
+impl Xautoclaim {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 6)?;
+
+        let min_idle = parse_int(&args[4])
+            .map_err(|_| {
+                RedisError::Custom(String::from(
+                    "Invalid min-idle-time argument for XAUTOCLAIM",
+                ))
+            })?
+            .max(0) as u64;
+
+        let mut count = 100;
+        let mut justid = false;
+
+        let mut rest = args[6..].iter();
+        while let Some(opt) = rest.next() {
+            match opt.to_lowercase().as_str() {
+                "count" => {
+                    let value = rest.next().ok_or(RedisError::Syntax)?;
+                    count = match parse_int(value)? {
+                        n if n < 1 || n > i64::MAX / AUTOCLAIM_ATTEMPTS_FACTOR as i64 => {
+                            return Err(RedisError::Custom(String::from("COUNT must be > 0")))
+                        }
+                        n => n as usize,
+                    };
+                }
+                "justid" => justid = true,
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(Xautoclaim {
+            key: args[1].clone(),
+            group: args[2].clone(),
+            consumer: args[3].clone(),
+            min_idle,
+            start: parse_start(&args[5])?,
+            count,
+            justid,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let now = now();
+        let stream = get_group_stream(db, &self.key, &self.group, || {
+            no_such_key_or_group(&self.key, &self.group)
+        })?;
+
+        let mut propagated = Vec::new();
+        let group = stream.group_mut(&self.group).expect("group exists");
+        if group.create_consumer(&self.consumer, now) {
+            propagated.push(create_consumer_frame(
+                &self.key,
+                &self.group,
+                &self.consumer,
+            ));
+        }
+
+        // One more ID than can be scanned, which becomes the cursor of the next call.
+        let attempts = self.count * AUTOCLAIM_ATTEMPTS_FACTOR;
+        let ids: Vec<StreamId> = group
+            .pending()
+            .range(self.start..)
+            .map(|(id, _)| *id)
+            .take(attempts + 1)
+            .collect();
+
+        let mut cursor = StreamId::MIN;
+        let mut claimed = Vec::new();
+        let mut deleted = Vec::new();
+
+        for (scanned, id) in ids.into_iter().enumerate() {
+            if scanned == attempts || claimed.len() == self.count {
+                cursor = id;
+                break;
+            }
+
+            let fields = stream.get(id);
+            let group = stream.group_mut(&self.group).expect("group exists");
+
+            let Some(fields) = fields else {
+                group.ack(id);
+                propagated.push(ack_frame(&self.key, &self.group, id));
+                deleted.push(id.to_string());
+                continue;
+            };
+
+            let pending = &group.pending()[&id];
+            if now.saturating_sub(pending.delivery_time) < self.min_idle {
+                continue;
+            }
+
+            let count = pending.delivery_count + u64::from(!self.justid);
+            group.assign(id, &self.consumer, now, count);
+            propagated.push(claim_frame(&self.key, &self.group, group, id));
+
+            claimed.push(if self.justid {
+                Frame::BulkString(id.to_string())
+            } else {
+                entry_frame((id, fields))
+            });
+        }
+
+        let group = stream.group_mut(&self.group).expect("group exists");
+        group.touch_consumer(&self.consumer, now, !claimed.is_empty());
+
+        rewrite_all(db, &self.key, propagated);
+
+        Ok(Frame::Array(vec![
+            Frame::BulkString(cursor.to_string()),
+            Frame::Array(claimed),
+            Frame::Arrays(deleted),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::now;
+    use crate::{cmd::Command, db::KeyValueDb, error::RedisError, frame::Frame, module::Registry};
+
+    fn run(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        let frame = Frame::Arrays(args.iter().map(|arg| arg.to_string()).collect());
+        match Command::parse(&frame, &Registry::default()) {
+            Command::Error(err) => Frame::from(err),
+            cmd => cmd
+                .as_execute()
+                .unwrap()
+                .execute(db)
+                .unwrap_or_else(Frame::from),
+        }
+    }
+
+    fn entry(id: &str, value: &str) -> Frame {
+        Frame::Array(vec![
+            Frame::BulkString(id.to_owned()),
+            Frame::Arrays(vec![String::from("f"), value.to_owned()]),
+        ])
+    }
+
+    fn ids(ids: &[&str]) -> Frame {
+        Frame::Array(
+            ids.iter()
+                .map(|id| Frame::BulkString(id.to_string()))
+                .collect(),
+        )
+    }
+
+    /// A stream `s` with entries `1-0` to `n-0`, all delivered to `alice` of group `g`.
+    fn delivered(n: usize) -> KeyValueDb {
+        let mut db = KeyValueDb::new();
+        for i in 1..=n {
+            run(
+                &mut db,
+                &["xadd", "s", &format!("{i}-0"), "f", &format!("v{i}")],
+            );
+        }
+        run(&mut db, &["xgroup", "create", "s", "g", "0"]);
+        run(
+            &mut db,
+            &["xreadgroup", "group", "g", "alice", "streams", "s", ">"],
+        );
+        db
+    }
+
+    /// The pending entries of `g` as `(id, consumer, idle, delivery count)`.
+    fn pending(db: &mut KeyValueDb) -> Vec<(String, String, i64, i64)> {
+        let Frame::Array(entries) = run(db, &["xpending", "s", "g", "-", "+", "100"]) else {
+            panic!("XPENDING should reply with an array");
+        };
+        entries
+            .into_iter()
+            .map(|entry| match &entry {
+                Frame::Array(fields) => match &fields[..] {
+                    [Frame::BulkString(id), Frame::BulkString(consumer), Frame::Integer(idle), Frame::Integer(count)] => {
+                        (id.clone(), consumer.clone(), *idle, *count)
+                    }
+                    _ => panic!("unexpected {entry:?}"),
+                },
+                _ => panic!("unexpected {entry:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_xclaim_idle_time_and_retrycount() {
+        let mut db = delivered(2);
+
+        assert_eq!(
+            ids(&[]),
+            run(&mut db, &["xclaim", "s", "g", "bob", "60000", "1-0"])
+        );
+
+        let reply = run(
+            &mut db,
+            &[
+                "xclaim",
+                "s",
+                "g",
+                "bob",
+                "0",
+                "1-0",
+                "IDLE",
+                "5000",
+                "RETRYCOUNT",
+                "7",
+            ],
+        );
+        assert_eq!(Frame::Array(vec![entry("1-0", "v1")]), reply);
+        let (_, consumer, idle, count) = pending(&mut db).remove(0);
+        assert_eq!(("bob", 7), (consumer.as_str(), count));
+        assert!((5000..6000).contains(&idle), "{idle}");
+
+        let time = (now() - 10_000).to_string();
+        let reply = run(
+            &mut db,
+            &["xclaim", "s", "g", "bob", "0", "2-0", "TIME", &time],
+        );
+        assert_eq!(Frame::Array(vec![entry("2-0", "v2")]), reply);
+        let (_, _, idle, count) = pending(&mut db).remove(1);
+        assert_eq!(2, count);
+        assert!((10_000..11_000).contains(&idle), "{idle}");
+
+        // Deliveries in the future count as happening now.
+        let time = (now() + 60_000).to_string();
+        run(
+            &mut db,
+            &["xclaim", "s", "g", "carol", "0", "2-0", "TIME", &time],
+        );
+        let (_, consumer, idle, count) = pending(&mut db).remove(1);
+        assert_eq!(("carol", 3), (consumer.as_str(), count));
+        assert!((0..1000).contains(&idle), "{idle}");
+    }
+
+    #[test]
+    fn test_xclaim_justid_and_force() {
+        let mut db = delivered(2);
+        run(&mut db, &["xadd", "s", "3-0", "f", "v3"]);
+
+        // JUSTID replies with IDs and doesn't count as a delivery.
+        let reply = run(
+            &mut db,
+            &["xclaim", "s", "g", "bob", "0", "1-0", "2-0", "JUSTID"],
+        );
+        assert_eq!(ids(&["1-0", "2-0"]), reply);
+        let counts: Vec<_> = pending(&mut db).into_iter().map(|p| (p.1, p.3)).collect();
+        assert_eq!(
+            vec![(String::from("bob"), 1), (String::from("bob"), 1)],
+            counts
+        );
+
+        // FORCE claims entries that aren't pending, as long as they are in the stream.
+        assert_eq!(
+            ids(&[]),
+            run(&mut db, &["xclaim", "s", "g", "bob", "0", "3-0"])
+        );
+        let reply = run(
+            &mut db,
+            &["xclaim", "s", "g", "bob", "0", "3-0", "9-0", "FORCE"],
+        );
+        assert_eq!(Frame::Array(vec![entry("3-0", "v3")]), reply);
+        let pel: Vec<_> = pending(&mut db).into_iter().map(|p| (p.0, p.3)).collect();
+        assert_eq!(
+            vec![
+                (String::from("1-0"), 1),
+                (String::from("2-0"), 1),
+                (String::from("3-0"), 1)
+            ],
+            pel
+        );
+
+        // Entries deleted from the stream are dropped from the PEL instead.
+        run(&mut db, &["xdel", "s", "2-0"]);
+        let reply = run(
+            &mut db,
+            &["xclaim", "s", "g", "carol", "0", "1-0", "2-0", "JUSTID"],
+        );
+        assert_eq!(ids(&["1-0"]), reply);
+        let pel: Vec<_> = pending(&mut db).into_iter().map(|p| p.0).collect();
+        assert_eq!(vec!["1-0", "3-0"], pel);
+    }
+
+    #[test]
+    fn test_xclaim_errors() {
+        let mut db = delivered(1);
+        let error = |msg: &str| Frame::from(RedisError::Custom(msg.to_owned()));
+
+        assert_eq!(
+            error("Invalid min-idle-time argument for XCLAIM"),
+            run(&mut db, &["xclaim", "s", "g", "bob", "x", "1-0"])
+        );
+        assert_eq!(
+            error("Invalid IDLE option argument for XCLAIM"),
+            run(
+                &mut db,
+                &["xclaim", "s", "g", "bob", "0", "1-0", "IDLE", "x"]
+            )
+        );
+        assert_eq!(
+            error("Invalid RETRYCOUNT option argument for XCLAIM"),
+            run(
+                &mut db,
+                &["xclaim", "s", "g", "bob", "0", "1-0", "RETRYCOUNT", "x"]
+            )
+        );
+        assert_eq!(
+            error("Unrecognized XCLAIM option 'foo'"),
+            run(&mut db, &["xclaim", "s", "g", "bob", "0", "1-0", "foo"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::Syntax),
+            run(&mut db, &["xclaim", "s", "g", "bob", "0", "1-0", "TIME"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::NoGroup(String::from(
+                "No such key 's' or consumer group 'nogroup'"
+            ))),
+            run(&mut db, &["xclaim", "s", "nogroup", "bob", "0", "1-0"])
+        );
+    }
+
+    #[test]
+    fn test_xautoclaim_cursor_and_count() {
+        let mut db = delivered(5);
+
+        let reply = run(
+            &mut db,
+            &["xautoclaim", "s", "g", "bob", "0", "0", "COUNT", "2"],
+        );
+        assert_eq!(
+            Frame::Array(vec![
+                Frame::BulkString(String::from("3-0")),
+                Frame::Array(vec![entry("1-0", "v1"), entry("2-0", "v2")]),
+                Frame::Arrays(vec![]),
+            ]),
+            reply
+        );
+
+        let reply = run(
+            &mut db,
+            &[
+                "xautoclaim",
+                "s",
+                "g",
+                "bob",
+                "0",
+                "3-0",
+                "COUNT",
+                "2",
+                "JUSTID",
+            ],
+        );
+        assert_eq!(
+            Frame::Array(vec![
+                Frame::BulkString(String::from("5-0")),
+                ids(&["3-0", "4-0"]),
+                Frame::Arrays(vec![]),
+            ]),
+            reply
+        );
+
+        // The cursor goes back to 0-0 once the whole PEL has been scanned.
+        let reply = run(&mut db, &["xautoclaim", "s", "g", "bob", "0", "5-0"]);
+        assert_eq!(
+            Frame::Array(vec![
+                Frame::BulkString(String::from("0-0")),
+                Frame::Array(vec![entry("5-0", "v5")]),
+                Frame::Arrays(vec![]),
+            ]),
+            reply
+        );
+
+        // JUSTID leaves the delivery counts alone.
+        let counts: Vec<_> = pending(&mut db).into_iter().map(|p| (p.1, p.3)).collect();
+        let bob = |count| (String::from("bob"), count);
+        assert_eq!(vec![bob(2), bob(2), bob(1), bob(1), bob(2)], counts);
+
+        let reply = run(&mut db, &["xautoclaim", "s", "g", "carol", "60000", "0"]);
+        assert_eq!(
+            Frame::Array(vec![
+                Frame::BulkString(String::from("0-0")),
+                ids(&[]),
+                Frame::Arrays(vec![]),
+            ]),
+            reply
+        );
+
+        assert_eq!(
+            Frame::from(RedisError::Custom(String::from("COUNT must be > 0"))),
+            run(
+                &mut db,
+                &["xautoclaim", "s", "g", "bob", "0", "0", "COUNT", "0"]
+            )
+        );
+    }
+
+    #[test]
+    fn test_xautoclaim_scan_limit() {
+        let mut db = delivered(12);
+
+        // Each call scans at most 10 entries for every one it may claim.
+        let reply = run(
+            &mut db,
+            &["xautoclaim", "s", "g", "bob", "60000", "0", "COUNT", "1"],
+        );
+        assert_eq!(
+            Frame::Array(vec![
+                Frame::BulkString(String::from("11-0")),
+                ids(&[]),
+                Frame::Arrays(vec![]),
+            ]),
+            reply
+        );
+    }
+
+    #[test]
+    fn test_xautoclaim_deleted_ids() {
+        let mut db = delivered(5);
+        run(&mut db, &["xdel", "s", "2-0", "4-0"]);
+
+        let reply = run(
+            &mut db,
+            &["xautoclaim", "s", "g", "bob", "0", "0", "JUSTID"],
+        );
+        assert_eq!(
+            Frame::Array(vec![
+                Frame::BulkString(String::from("0-0")),
+                ids(&["1-0", "3-0", "5-0"]),
+                Frame::Arrays(vec![String::from("2-0"), String::from("4-0")]),
+            ]),
+            reply
+        );
+
+        // Deleted entries are dropped from the PEL, so they are only reported once.
+        let pel: Vec<_> = pending(&mut db).into_iter().map(|p| p.0).collect();
+        assert_eq!(vec!["1-0", "3-0", "5-0"], pel);
+        let reply = run(
+            &mut db,
+            &["xautoclaim", "s", "g", "bob", "0", "0", "JUSTID"],
+        );
+        let Frame::Array(reply) = reply else {
+            panic!("XAUTOCLAIM should reply with an array");
+        };
+        assert_eq!(Frame::Arrays(vec![]), reply[2]);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{
+        countmin::{self, CountMin},
+        notify, Data, Database, Value,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::num::parse_int,
+};
+
+#[derive(Debug)]
+pub(crate) enum CountMinCommand {
+    InitByDim(CmsInitByDim),
+    IncrBy(CmsIncrBy),
+    Query(CmsQuery),
+    Merge(CmsMerge),
+}
+
+impl CountMinCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "cms.initbydim" => CmsInitByDim::new(args).map(CountMinCommand::InitByDim),
+            "cms.incrby" => CmsIncrBy::new(args).map(CountMinCommand::IncrBy),
+            "cms.query" => CmsQuery::new(args).map(CountMinCommand::Query),
+            "cms.merge" => CmsMerge::new(args).map(CountMinCommand::Merge),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for CountMinCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            CountMinCommand::InitByDim(cmd) => cmd.execute(db),
+            CountMinCommand::IncrBy(cmd) => cmd.execute(db),
+            CountMinCommand::Query(cmd) => cmd.execute(db),
+            CountMinCommand::Merge(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn cms_error(msg: &str) -> RedisError {
+    RedisError::Custom(format!("CMS: {msg}"))
+}
+
+/// Returns the sketch at `key`, which the commands other than CMS.INITBYDIM never create.
+fn get_count_min<'a>(db: &'a mut dyn Database, key: &str) -> Result<&'a mut CountMin, RedisError> {
+    db.get_value(key)
+        .ok_or(cms_error("key does not exist"))?
+        .as_count_min_mut()
+}
+
+fn read_count_min<'a>(db: &'a mut dyn Database, key: &str) -> Result<&'a mut CountMin, RedisError> {
+    db.read_value(key)
+        .ok_or(cms_error("key does not exist"))?
+        .as_count_min_mut()
+}
+
+fn parse_dimension(s: &str, name: &str) -> Result<u32, RedisError> {
+    parse_int(s)
+        .ok()
+        .and_then(|n| u32::try_from(n).ok())
+        .filter(|n| *n > 0)
+        .ok_or(cms_error(&format!("invalid {name}")))
+}
+
+/// `CMS.INITBYDIM key width depth`
+#[derive(Debug)]
+pub(crate) struct CmsInitByDim {
+    key: String,
+    width: u32,
+    depth: u32,
+}
+
+impl CmsInitByDim {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+        if args.len() != 4 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(CmsInitByDim {
+            key: args[1].clone(),
+            width: parse_dimension(&args[2], "width")?,
+            depth: parse_dimension(&args[3], "depth")?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        if db.get_value(&self.key).is_some() {
+            return Err(cms_error("key already exists"));
+        }
+
+        let cms = CountMin::new(self.width, self.depth)
+            .ok_or(cms_error("width and depth are too large"))?;
+        db.insert(&self.key, Value::from(Data::CountMin(Box::new(cms))));
+        db.touch(&self.key);
+        db.notify(notify::MODULE, "cms.initbydim", &self.key);
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
+
+/// `CMS.INCRBY key item increment [item increment ...]`, replying with the new estimate of
+/// each item.
+#[derive(Debug)]
+pub(crate) struct CmsIncrBy {
+    key: String,
+    increments: Vec<(String, u32)>,
+}
+
+impl CmsIncrBy {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+        if !(args.len() - 2).is_multiple_of(2) {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        let increments = args[2..]
+            .chunks_exact(2)
+            .map(|pair| {
+                let increment = parse_int(&pair[1])
+                    .ok()
+                    .and_then(|n| u32::try_from(n).ok())
+                    .ok_or(cms_error("Cannot parse number"))?;
+                Ok((pair[0].clone(), increment))
+            })
+            .collect::<Result<_, RedisError>>()?;
+
+        Ok(CmsIncrBy {
+            key: args[1].clone(),
+            increments,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let cms = get_count_min(db, &self.key)?;
+
+        // Check every counter first so that an overflow leaves the sketch unchanged.
+        let mut updated = cms.clone();
+        let counts = self
+            .increments
+            .iter()
+            .map(|(item, increment)| {
+                updated
+                    .increment(item.as_bytes(), *increment)
+                    .map_err(|_| cms_error("INCRBY overflow"))
+            })
+            .collect::<Result<Vec<_>, RedisError>>()?;
+        *cms = updated;
+        db.touch(&self.key);
+        db.notify(notify::MODULE, "cms.incrby", &self.key);
+
+        Ok(Frame::Array(
+            counts
+                .into_iter()
+                .map(|count| Frame::Integer(count as i64))
+                .collect(),
+        ))
+    }
+}
+
+/// `CMS.QUERY key item [item ...]`
+#[derive(Debug)]
+pub(crate) struct CmsQuery {
+    key: String,
+    items: Vec<String>,
+}
+
+impl CmsQuery {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(CmsQuery {
+            key: args[1].clone(),
+            items: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let cms = read_count_min(db, &self.key)?;
+
+        Ok(Frame::Array(
+            self.items
+                .iter()
+                .map(|item| Frame::Integer(cms.query(item.as_bytes()) as i64))
+                .collect(),
+        ))
+    }
+}
+
+/// `CMS.MERGE destination numkeys source [source ...] [WEIGHTS weight [weight ...]]`, where
+/// the destination must already exist with the dimensions of the sources.
+#[derive(Debug)]
+pub(crate) struct CmsMerge {
+    destination: String,
+    sources: Vec<(String, i64)>,
+}
+
+impl CmsMerge {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let numkeys = parse_int(&args[2])
+            .ok()
+            .and_then(|n| usize::try_from(n).ok())
+            .filter(|n| *n > 0)
+            .ok_or(cms_error("invalid numkeys"))?;
+        let keys = args
+            .get(3..3 + numkeys)
+            .ok_or(RedisError::WrongArity(args[0].to_lowercase()))?;
+
+        let weights = match args.get(3 + numkeys) {
+            None => vec![1; numkeys],
+            Some(opt) if opt.eq_ignore_ascii_case("weights") => {
+                let weights = &args[4 + numkeys..];
+                if weights.len() != numkeys {
+                    return Err(RedisError::WrongArity(args[0].to_lowercase()));
+                }
+                weights
+                    .iter()
+                    .map(|w| parse_int(w).map_err(|_| cms_error("invalid weight value")))
+                    .collect::<Result<_, RedisError>>()?
+            }
+            Some(_) => return Err(RedisError::Syntax),
+        };
+
+        Ok(CmsMerge {
+            destination: args[1].clone(),
+            sources: keys.iter().cloned().zip(weights).collect(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        get_count_min(db, &self.destination)?;
+
+        // The destination may be one of the sources, so they are copied first.
+        let sources = self
+            .sources
+            .iter()
+            .map(|(key, weight)| Ok((read_count_min(db, key)?.clone(), *weight)))
+            .collect::<Result<Vec<_>, RedisError>>()?;
+        let sources: Vec<_> = sources.iter().map(|(cms, weight)| (cms, *weight)).collect();
+
+        get_count_min(db, &self.destination)?
+            .merge(&sources)
+            .map_err(|err| match err {
+                countmin::Error::Dimensions => cms_error("width/depth is not equal"),
+                countmin::Error::Overflow => cms_error("MERGE overflow"),
+            })?;
+        db.touch(&self.destination);
+        db.notify(notify::MODULE, "cms.merge", &self.destination);
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
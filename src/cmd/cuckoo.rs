@@ -0,0 +1,148 @@
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{
+        cuckoo::{self, Cuckoo},
+        notify, Data, Database,
+    },
+    error::RedisError,
+    frame::Frame,
+};
+
+#[derive(Debug)]
+pub(crate) enum CuckooCommand {
+    Add(CfAdd),
+    Del(CfDel),
+    Exists(CfExists),
+}
+
+impl CuckooCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "cf.add" => CfAdd::new(args).map(CuckooCommand::Add),
+            "cf.del" => CfDel::new(args).map(CuckooCommand::Del),
+            "cf.exists" => CfExists::new(args).map(CuckooCommand::Exists),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for CuckooCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            CuckooCommand::Add(cmd) => cmd.execute(db),
+            CuckooCommand::Del(cmd) => cmd.execute(db),
+            CuckooCommand::Exists(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn get_cuckoo<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut Cuckoo>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_cuckoo_mut())
+        .transpose()
+}
+
+fn read_cuckoo<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut Cuckoo>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_cuckoo_mut())
+        .transpose()
+}
+
+/// Parses the `key item` arguments shared by all the commands.
+fn parse_key_item(args: &[String]) -> Result<(String, String), RedisError> {
+    check_arity(args, 3)?;
+    if args.len() != 3 {
+        return Err(RedisError::WrongArity(args[0].to_lowercase()));
+    }
+
+    Ok((args[1].clone(), args[2].clone()))
+}
+
+/// `CF.ADD key item`, which creates the filter with the default capacity if needed. Items
+/// can be added several times.
+#[derive(Debug)]
+pub(crate) struct CfAdd {
+    key: String,
+    item: String,
+}
+
+impl CfAdd {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        let (key, item) = parse_key_item(&args)?;
+        Ok(CfAdd { key, item })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let cuckoo = db
+            .get_or_insert_with(&self.key, &|| {
+                Data::Cuckoo(Box::new(Cuckoo::new(
+                    cuckoo::DEFAULT_CAPACITY,
+                    cuckoo::DEFAULT_BUCKET_SIZE,
+                    cuckoo::DEFAULT_MAX_ITERATIONS,
+                    cuckoo::DEFAULT_EXPANSION,
+                )))
+            })
+            .as_cuckoo_mut()?;
+
+        cuckoo
+            .add(self.item.as_bytes())
+            .map_err(|_| RedisError::Custom(String::from("Filter is full")))?;
+        db.touch(&self.key);
+        db.notify(notify::MODULE, "cf.add", &self.key);
+
+        Ok(Frame::Integer(1))
+    }
+}
+
+/// `CF.DEL key item` deletes one occurrence of the item.
+#[derive(Debug)]
+pub(crate) struct CfDel {
+    key: String,
+    item: String,
+}
+
+impl CfDel {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        let (key, item) = parse_key_item(&args)?;
+        Ok(CfDel { key, item })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let cuckoo =
+            get_cuckoo(db, &self.key)?.ok_or(RedisError::Custom(String::from("Not found")))?;
+
+        let deleted = cuckoo.delete(self.item.as_bytes());
+        if deleted {
+            db.touch(&self.key);
+            db.notify(notify::MODULE, "cf.del", &self.key);
+        }
+
+        Ok(Frame::Integer(deleted as i64))
+    }
+}
+
+/// `CF.EXISTS key item`
+#[derive(Debug)]
+pub(crate) struct CfExists {
+    key: String,
+    item: String,
+}
+
+impl CfExists {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        let (key, item) = parse_key_item(&args)?;
+        Ok(CfExists { key, item })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let exists =
+            read_cuckoo(db, &self.key)?.is_some_and(|cuckoo| cuckoo.contains(self.item.as_bytes()));
+        Ok(Frame::Integer(exists as i64))
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{notify, Database},
+    error::RedisError,
+    frame::Frame,
+};
+
+/// `DEL key [key ...]`, replying with the number of keys that were deleted. Masters also
+/// send it to replicas when keys expire.
+#[derive(Debug)]
+pub(crate) struct Del {
+    keys: Vec<String>,
+}
+
+impl Del {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Del {
+            keys: args[1..].to_vec(),
+        })
+    }
+}
+
+impl Execute for Del {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let mut deleted = 0;
+        for key in &self.keys {
+            if db.remove(key).is_some() {
+                db.touch(key);
+                db.notify(notify::GENERIC, "del", key);
+                deleted += 1;
+            }
+        }
+
+        Ok(Frame::Integer(deleted))
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Error;
 
-use crate::{connection::Connection, frame::Frame};
+use crate::{cmd::Execute, connection::Connection, db::Database, error::RedisError, frame::Frame};
 
 #[derive(Debug)]
 pub(crate) struct Echo {
@@ -22,3 +22,9 @@ impl Echo {
         Ok(())
     }
 }
+
+impl Execute for Echo {
+    fn execute(&self, _db: &mut dyn Database) -> Result<Frame, RedisError> {
+        Ok(Frame::BulkString(self.msg.clone()))
+    }
+}
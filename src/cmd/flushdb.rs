@@ -0,0 +1,22 @@
+use crate::{cmd::Execute, db::Database, error::RedisError, frame::Frame};
+
+/// `FLUSHDB [ASYNC | SYNC]`, where both modes delete every key right away.
+#[derive(Debug)]
+pub(crate) struct Flushdb;
+
+impl Flushdb {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        match args.get(1).map(|s| s.to_lowercase()).as_deref() {
+            None | Some("async" | "sync") if args.len() <= 2 => Ok(Flushdb),
+            _ => Err(RedisError::Syntax),
+        }
+    }
+}
+
+impl Execute for Flushdb {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        db.flush();
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
@@ -0,0 +1,559 @@
+use crate::{
+    cmd::{
+        check_arity, remove_if_empty,
+        zset::{get_or_create_sorted_set, get_sorted_set, read_sorted_set},
+        Execute,
+    },
+    db::{
+        geo::{self, Shape},
+        notify,
+        skiplist::ScoreRange,
+        zset::SortedSet,
+        Data, Database, Value,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::num::{format_float, parse_float, parse_int},
+};
+
+#[derive(Debug)]
+pub(crate) enum GeoCommand {
+    Geoadd(Geoadd),
+    Geopos(Geopos),
+    Geodist(Geodist),
+    Geohash(Geohash),
+    Geosearch(Geosearch),
+}
+
+impl GeoCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "geoadd" => Geoadd::new(args).map(GeoCommand::Geoadd),
+            "geopos" => Geopos::new(args).map(GeoCommand::Geopos),
+            "geodist" => Geodist::new(args).map(GeoCommand::Geodist),
+            "geohash" => Geohash::new(args).map(GeoCommand::Geohash),
+            "geosearch" | "geosearchstore" => Geosearch::new(args).map(GeoCommand::Geosearch),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for GeoCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            GeoCommand::Geoadd(cmd) => cmd.execute(db),
+            GeoCommand::Geopos(cmd) => cmd.execute(db),
+            GeoCommand::Geodist(cmd) => cmd.execute(db),
+            GeoCommand::Geohash(cmd) => cmd.execute(db),
+            GeoCommand::Geosearch(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+/// Returns how many meters make one `unit`.
+fn parse_unit(unit: &str) -> Result<f64, RedisError> {
+    match unit.to_lowercase().as_str() {
+        "m" => Ok(1.0),
+        "km" => Ok(1000.0),
+        "ft" => Ok(0.3048),
+        "mi" => Ok(1609.34),
+        _ => Err(RedisError::Custom(String::from(
+            "unsupported unit provided. please use M, KM, FT, MI",
+        ))),
+    }
+}
+
+fn parse_lon_lat(lon: &str, lat: &str) -> Result<(f64, f64), RedisError> {
+    let (lon, lat) = (parse_float(lon)?, parse_float(lat)?);
+
+    match geo::is_valid(lon, lat) {
+        true => Ok((lon, lat)),
+        false => Err(RedisError::Custom(format!(
+            "invalid longitude,latitude pair {lon:.6},{lat:.6}"
+        ))),
+    }
+}
+
+/// Returns the position of `member` from its score, the center of its geohash cell.
+fn position(zset: &SortedSet, member: &str) -> Option<(f64, f64)> {
+    zset.score(member).map(|score| geo::decode(score as u64))
+}
+
+fn format_distance(meters: f64, unit: f64) -> String {
+    format!("{:.4}", meters / unit)
+}
+
+fn coord_reply((lon, lat): (f64, f64)) -> Frame {
+    Frame::Array(vec![
+        Frame::BulkString(format_float(lon)),
+        Frame::BulkString(format_float(lat)),
+    ])
+}
+
+/// `GEOADD key [NX | XX] [CH] longitude latitude member [longitude latitude member ...]`
+#[derive(Debug)]
+pub(crate) struct Geoadd {
+    key: String,
+    nx: bool,
+    xx: bool,
+    ch: bool,
+    points: Vec<(u64, String)>,
+}
+
+impl Geoadd {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+
+        let (mut nx, mut xx, mut ch) = (false, false, false);
+        let mut index = 2;
+        while let Some(arg) = args.get(index) {
+            match arg.to_lowercase().as_str() {
+                "nx" => nx = true,
+                "xx" => xx = true,
+                "ch" => ch = true,
+                _ => break,
+            }
+            index += 1;
+        }
+
+        let triples = &args[index..];
+        if triples.is_empty() || !triples.len().is_multiple_of(3) {
+            return Err(RedisError::Syntax);
+        }
+
+        if nx && xx {
+            return Err(RedisError::Custom(String::from(
+                "XX and NX options at the same time are not compatible",
+            )));
+        }
+
+        let points = triples
+            .chunks_exact(3)
+            .map(|t| {
+                let (lon, lat) = parse_lon_lat(&t[0], &t[1])?;
+                Ok((geo::encode(lon, lat), t[2].clone()))
+            })
+            .collect::<Result<_, RedisError>>()?;
+
+        Ok(Geoadd {
+            key: args[1].clone(),
+            nx,
+            xx,
+            ch,
+            points,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        if self.xx && get_sorted_set(db, &self.key)?.is_none() {
+            return Ok(Frame::Integer(0));
+        }
+
+        let zset = get_or_create_sorted_set(db, &self.key)?;
+        let (mut added, mut changed) = (0, 0);
+
+        for (bits, member) in self.points.iter() {
+            let score = *bits as f64;
+            match zset.score(member) {
+                Some(current) if !self.nx && current != score => {
+                    zset.insert(member, score);
+                    changed += 1;
+                }
+                None if !self.xx => {
+                    zset.insert(member, score);
+                    added += 1;
+                }
+                _ => {}
+            }
+        }
+
+        remove_if_empty(db, &self.key);
+
+        if added + changed > 0 {
+            db.touch(&self.key);
+            db.notify(notify::ZSET, "zadd", &self.key);
+        }
+
+        Ok(Frame::Integer(if self.ch {
+            added + changed
+        } else {
+            added
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Geopos {
+    key: String,
+    members: Vec<String>,
+}
+
+impl Geopos {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Geopos {
+            key: args[1].clone(),
+            members: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let zset = read_sorted_set(db, &self.key)?;
+
+        let positions = self
+            .members
+            .iter()
+            .map(|member| {
+                zset.as_ref()
+                    .and_then(|zset| position(zset, member))
+                    .map_or(Frame::Null, coord_reply)
+            })
+            .collect();
+
+        Ok(Frame::Array(positions))
+    }
+}
+
+/// `GEODIST key member1 member2 [M | KM | FT | MI]`
+#[derive(Debug)]
+pub(crate) struct Geodist {
+    key: String,
+    members: (String, String),
+    unit: f64,
+}
+
+impl Geodist {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let unit = match args.len() {
+            4 => 1.0,
+            5 => parse_unit(&args[4])?,
+            _ => return Err(RedisError::Syntax),
+        };
+
+        Ok(Geodist {
+            key: args[1].clone(),
+            members: (args[2].clone(), args[3].clone()),
+            unit,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(zset) = read_sorted_set(db, &self.key)? else {
+            return Ok(Frame::Null);
+        };
+
+        let (Some(a), Some(b)) = (
+            position(zset, &self.members.0),
+            position(zset, &self.members.1),
+        ) else {
+            return Ok(Frame::Null);
+        };
+
+        let meters = geo::distance(a.0, a.1, b.0, b.1);
+        Ok(Frame::BulkString(format_distance(meters, self.unit)))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Geohash {
+    key: String,
+    members: Vec<String>,
+}
+
+impl Geohash {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Geohash {
+            key: args[1].clone(),
+            members: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let zset = read_sorted_set(db, &self.key)?;
+
+        let hashes = self
+            .members
+            .iter()
+            .map(|member| {
+                zset.as_ref()
+                    .and_then(|zset| zset.score(member))
+                    .map_or(Frame::Null, |score| {
+                        Frame::BulkString(geo::to_base32(score as u64))
+                    })
+            })
+            .collect();
+
+        Ok(Frame::Array(hashes))
+    }
+}
+
+#[derive(Debug)]
+enum Origin {
+    Member(String),
+    LonLat(f64, f64),
+}
+
+#[derive(Debug)]
+struct Match {
+    member: String,
+    score: f64,
+    distance: f64,
+    position: (f64, f64),
+}
+
+/// `GEOSEARCH key <FROMMEMBER member | FROMLONLAT longitude latitude>
+/// <BYRADIUS radius unit | BYBOX width height unit> [ASC | DESC] [COUNT count [ANY]]
+/// [WITHCOORD] [WITHDIST] [WITHHASH]`, and GEOSEARCHSTORE, which takes a destination before
+/// the key and accepts STOREDIST instead of the WITH options.
+#[derive(Debug)]
+pub(crate) struct Geosearch {
+    dest: Option<String>,
+    key: String,
+    origin: Origin,
+    shape: Shape,
+    unit: f64,
+    /// `Some(true)` for descending distances.
+    desc: Option<bool>,
+    count: Option<usize>,
+    any: bool,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+    store_dist: bool,
+}
+
+impl Geosearch {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        let cmd = args[0].to_lowercase();
+        let store = cmd == "geosearchstore";
+        check_arity(&args, if store { 8 } else { 7 })?;
+
+        let (dest, key) = match store {
+            true => (Some(args[1].clone()), args[2].clone()),
+            false => (None, args[1].clone()),
+        };
+
+        let mut origin = None;
+        let mut shape = None;
+        let mut search = Geosearch {
+            dest,
+            key,
+            origin: Origin::LonLat(0.0, 0.0),
+            shape: Shape::Radius(0.0),
+            unit: 1.0,
+            desc: None,
+            count: None,
+            any: false,
+            with_coord: false,
+            with_dist: false,
+            with_hash: false,
+            store_dist: false,
+        };
+
+        let from_error = || {
+            RedisError::Custom(format!(
+                "exactly one of FROMMEMBER or FROMLONLAT can be specified for {}",
+                args[0]
+            ))
+        };
+        let by_error = || {
+            RedisError::Custom(format!(
+                "exactly one of BYRADIUS and BYBOX can be specified for {}",
+                args[0]
+            ))
+        };
+
+        let mut rest = args[if store { 3 } else { 2 }..].iter();
+        let mut next = || rest.next().ok_or(RedisError::Syntax);
+        while let Ok(opt) = next() {
+            match opt.to_lowercase().as_str() {
+                "frommember" => {
+                    let member = Origin::Member(next()?.clone());
+                    if origin.replace(member).is_some() {
+                        return Err(from_error());
+                    }
+                }
+                "fromlonlat" => {
+                    let (lon, lat) = parse_lon_lat(next()?, next()?)?;
+                    if origin.replace(Origin::LonLat(lon, lat)).is_some() {
+                        return Err(from_error());
+                    }
+                }
+                "byradius" => {
+                    let radius = parse_float(next()?)?;
+                    if radius < 0.0 {
+                        return Err(RedisError::Custom(String::from(
+                            "radius cannot be negative",
+                        )));
+                    }
+                    search.unit = parse_unit(next()?)?;
+                    if shape.replace(Shape::Radius(radius * search.unit)).is_some() {
+                        return Err(by_error());
+                    }
+                }
+                "bybox" => {
+                    let (width, height) = (parse_float(next()?)?, parse_float(next()?)?);
+                    if width < 0.0 || height < 0.0 {
+                        return Err(RedisError::Custom(String::from(
+                            "height or width cannot be negative",
+                        )));
+                    }
+                    search.unit = parse_unit(next()?)?;
+                    let bbox = Shape::Box {
+                        width: width * search.unit,
+                        height: height * search.unit,
+                    };
+                    if shape.replace(bbox).is_some() {
+                        return Err(by_error());
+                    }
+                }
+                "asc" => search.desc = Some(false),
+                "desc" => search.desc = Some(true),
+                "count" => {
+                    search.count = match parse_int(next()?)? {
+                        n if n <= 0 => {
+                            return Err(RedisError::Custom(String::from("COUNT must be > 0")))
+                        }
+                        n => Some(n as usize),
+                    };
+                }
+                "any" => search.any = true,
+                "withcoord" if !store => search.with_coord = true,
+                "withdist" if !store => search.with_dist = true,
+                "withhash" if !store => search.with_hash = true,
+                "storedist" if store => search.store_dist = true,
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        search.origin = origin.ok_or_else(from_error)?;
+        search.shape = shape.ok_or_else(by_error)?;
+
+        if search.any && search.count.is_none() {
+            return Err(RedisError::Custom(String::from(
+                "the ANY argument requires COUNT argument",
+            )));
+        }
+
+        // Without ANY, the closest matches are the ones counted.
+        if search.count.is_some() && !search.any && search.desc.is_none() {
+            search.desc = Some(false);
+        }
+
+        Ok(search)
+    }
+
+    /// Scans the cells around the center and keeps the members within the shape. With ANY,
+    /// the scan stops as soon as enough members are found.
+    fn search(&self, zset: &SortedSet) -> Result<Vec<Match>, RedisError> {
+        let center = match &self.origin {
+            Origin::LonLat(lon, lat) => (*lon, *lat),
+            Origin::Member(member) => position(zset, member).ok_or(RedisError::Custom(
+                String::from("could not decode requested zset member"),
+            ))?,
+        };
+
+        let limit = self.count.filter(|_| self.any);
+        let mut matches = Vec::new();
+
+        'cells: for (min, max) in geo::search_ranges(center.0, center.1, &self.shape) {
+            let range = ScoreRange {
+                min: min as f64,
+                max: max as f64,
+                min_exclusive: false,
+                max_exclusive: true,
+            };
+
+            for (member, score) in zset.range_by_score(&range, false, 0, None) {
+                let position = geo::decode(score as u64);
+                if let Some(distance) = self.shape.distance(center, position) {
+                    matches.push(Match {
+                        member,
+                        score,
+                        distance,
+                        position,
+                    });
+
+                    if limit.is_some_and(|n| matches.len() >= n) {
+                        break 'cells;
+                    }
+                }
+            }
+        }
+
+        if let Some(desc) = self.desc {
+            matches.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+            if desc {
+                matches.reverse();
+            }
+        }
+
+        if let Some(count) = self.count {
+            matches.truncate(count);
+        }
+
+        Ok(matches)
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let matches = match read_sorted_set(db, &self.key)? {
+            Some(zset) => self.search(zset)?,
+            None => Vec::new(),
+        };
+
+        let Some(dest) = &self.dest else {
+            return Ok(self.reply(matches));
+        };
+
+        let len = matches.len();
+        if matches.is_empty() {
+            if db.remove(dest).is_some() {
+                db.notify(notify::GENERIC, "del", dest);
+            }
+        } else {
+            let mut zset = SortedSet::new();
+            for m in matches {
+                let score = match self.store_dist {
+                    true => m.distance / self.unit,
+                    false => m.score,
+                };
+                zset.insert(&m.member, score);
+            }
+            db.insert(dest, Value::from(Data::SortedSet(Box::new(zset))));
+            db.notify(notify::ZSET, "geosearchstore", dest);
+        }
+        db.touch(dest);
+
+        Ok(Frame::Integer(len as i64))
+    }
+
+    fn reply(&self, matches: Vec<Match>) -> Frame {
+        if !(self.with_coord || self.with_dist || self.with_hash) {
+            return Frame::Arrays(matches.into_iter().map(|m| m.member).collect());
+        }
+
+        let items = matches
+            .into_iter()
+            .map(|m| {
+                let mut item = vec![Frame::BulkString(m.member)];
+                if self.with_dist {
+                    item.push(Frame::BulkString(format_distance(m.distance, self.unit)));
+                }
+                if self.with_hash {
+                    item.push(Frame::Integer(m.score as i64));
+                }
+                if self.with_coord {
+                    item.push(coord_reply(m.position));
+                }
+                Frame::Array(item)
+            })
+            .collect();
+
+        Frame::Array(items)
+    }
+}
@@ -1,9 +1,4 @@
-use std::sync::Arc;
-
-use anyhow::Error;
-use tokio::sync::Mutex;
-
-use crate::{connection::Connection, db::Database, frame::Frame};
+use crate::{cmd::Execute, db::Database, error::RedisError, frame::Frame};
 
 #[derive(Debug)]
 pub(crate) struct Get {
@@ -14,23 +9,13 @@ impl Get {
     pub(crate) fn new(args: Vec<String>) -> Option<Self> {
         args.get(1).cloned().map(|key| Get { key })
     }
+}
 
-    pub(crate) async fn apply<D>(
-        &self,
-        conn: &mut Connection,
-        db: Arc<Mutex<D>>,
-    ) -> Result<(), Error>
-    where
-        D: Database,
-    {
-        let frame = if let Some(value) = db.lock().await.get(&self.key) {
-            Frame::BulkString(value)
-        } else {
-            Frame::Null
-        };
-
-        conn.write_frame(&frame).await?;
-
-        Ok(())
+impl Execute for Get {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match db.get(&self.key)? {
+            Some(value) => Ok(Frame::BulkBinary(value)),
+            None => Ok(Frame::Null),
+        }
     }
 }
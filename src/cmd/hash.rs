@@ -0,0 +1,1308 @@
+use std::time::SystemTime;
+
+use crate::{
+    cmd::{check_arity, remove_if_empty, scan_reply, Execute, ScanOptions},
+    db::{hash::Hash, notify, Data, Database},
+    error::RedisError,
+    frame::Frame,
+    util::{
+        num::{format_float, parse_float, parse_int},
+        rand::{random_range, sample},
+        time::{from_unix_millis, millis_until, to_unix_millis},
+    },
+};
+
+#[derive(Debug)]
+pub(crate) enum HashCommand {
+    Hset(Hset),
+    Hsetnx(Hsetnx),
+    Hget(Hget),
+    Hmget(Hmget),
+    Hgetall(Hgetall),
+    Hdel(Hdel),
+    Hexists(Hexists),
+    Hincrby(Hincrby),
+    Hincrbyfloat(Hincrbyfloat),
+    Hkeys(Hkeys),
+    Hvals(Hvals),
+    Hlen(Hlen),
+    Hstrlen(Hstrlen),
+    Hrandfield(Hrandfield),
+    Hscan(Hscan),
+    Hexpire(Hexpire),
+    Httl(Httl),
+    Hpersist(Hpersist),
+    Hgetex(Hgetex),
+}
+
+impl HashCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "hset" | "hmset" => Hset::new(args).map(HashCommand::Hset),
+            "hsetnx" => Hsetnx::new(args).map(HashCommand::Hsetnx),
+            "hget" => Hget::new(args).map(HashCommand::Hget),
+            "hmget" => Hmget::new(args).map(HashCommand::Hmget),
+            "hgetall" => Hgetall::new(args).map(HashCommand::Hgetall),
+            "hdel" => Hdel::new(args).map(HashCommand::Hdel),
+            "hexists" => Hexists::new(args).map(HashCommand::Hexists),
+            "hincrby" => Hincrby::new(args).map(HashCommand::Hincrby),
+            "hincrbyfloat" => Hincrbyfloat::new(args).map(HashCommand::Hincrbyfloat),
+            "hkeys" => Hkeys::new(args).map(HashCommand::Hkeys),
+            "hvals" => Hvals::new(args).map(HashCommand::Hvals),
+            "hlen" => Hlen::new(args).map(HashCommand::Hlen),
+            "hstrlen" => Hstrlen::new(args).map(HashCommand::Hstrlen),
+            "hrandfield" => Hrandfield::new(args).map(HashCommand::Hrandfield),
+            "hscan" => Hscan::new(args).map(HashCommand::Hscan),
+            "hexpire" | "hpexpire" | "hexpireat" | "hpexpireat" => {
+                Hexpire::new(args).map(HashCommand::Hexpire)
+            }
+            "httl" | "hpttl" | "hexpiretime" | "hpexpiretime" => {
+                Httl::new(args).map(HashCommand::Httl)
+            }
+            "hpersist" => Hpersist::new(args).map(HashCommand::Hpersist),
+            "hgetex" => Hgetex::new(args).map(HashCommand::Hgetex),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for HashCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            HashCommand::Hset(cmd) => cmd.execute(db),
+            HashCommand::Hsetnx(cmd) => cmd.execute(db),
+            HashCommand::Hget(cmd) => cmd.execute(db),
+            HashCommand::Hmget(cmd) => cmd.execute(db),
+            HashCommand::Hgetall(cmd) => cmd.execute(db),
+            HashCommand::Hdel(cmd) => cmd.execute(db),
+            HashCommand::Hexists(cmd) => cmd.execute(db),
+            HashCommand::Hincrby(cmd) => cmd.execute(db),
+            HashCommand::Hincrbyfloat(cmd) => cmd.execute(db),
+            HashCommand::Hkeys(cmd) => cmd.execute(db),
+            HashCommand::Hvals(cmd) => cmd.execute(db),
+            HashCommand::Hlen(cmd) => cmd.execute(db),
+            HashCommand::Hstrlen(cmd) => cmd.execute(db),
+            HashCommand::Hrandfield(cmd) => cmd.execute(db),
+            HashCommand::Hscan(cmd) => cmd.execute(db),
+            HashCommand::Hexpire(cmd) => cmd.execute(db),
+            HashCommand::Httl(cmd) => cmd.execute(db),
+            HashCommand::Hpersist(cmd) => cmd.execute(db),
+            HashCommand::Hgetex(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn get_hash<'a>(db: &'a mut dyn Database, key: &str) -> Result<Option<&'a mut Hash>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_hash_mut())
+        .transpose()
+}
+
+fn read_hash<'a>(db: &'a mut dyn Database, key: &str) -> Result<Option<&'a mut Hash>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_hash_mut())
+        .transpose()
+}
+
+fn get_or_create_hash<'a>(db: &'a mut dyn Database, key: &str) -> Result<&'a mut Hash, RedisError> {
+    db.get_or_insert_with(key, &|| Data::Hash(Box::new(Hash::new())))
+        .as_hash_mut()
+}
+
+#[derive(Debug)]
+pub(crate) struct Hset {
+    key: String,
+    pairs: Vec<(String, String)>,
+    legacy: bool,
+}
+
+impl Hset {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        if args.len() % 2 == 1 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        let pairs = args[2..]
+            .chunks_exact(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+
+        Ok(Hset {
+            key: args[1].clone(),
+            pairs,
+            legacy: args[0].eq_ignore_ascii_case("hmset"),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let thresholds = db.thresholds();
+        let hash = get_or_create_hash(db, &self.key)?;
+
+        let added = self
+            .pairs
+            .iter()
+            .filter(|(field, value)| hash.insert(field, value, &thresholds))
+            .count();
+
+        db.touch(&self.key);
+        db.notify(notify::HASH, "hset", &self.key);
+
+        if self.legacy {
+            Ok(Frame::SimpleString(String::from("OK")))
+        } else {
+            Ok(Frame::Integer(added as i64))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hsetnx {
+    key: String,
+    field: String,
+    value: String,
+}
+
+impl Hsetnx {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Ok(Hsetnx {
+            key: args[1].clone(),
+            field: args[2].clone(),
+            value: args[3].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        if get_hash(db, &self.key)?.is_some_and(|hash| hash.contains(&self.field)) {
+            return Ok(Frame::Integer(0));
+        }
+
+        let thresholds = db.thresholds();
+        get_or_create_hash(db, &self.key)?.insert(&self.field, &self.value, &thresholds);
+        db.touch(&self.key);
+        db.notify(notify::HASH, "hset", &self.key);
+
+        Ok(Frame::Integer(1))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hget {
+    key: String,
+    field: String,
+}
+
+impl Hget {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Hget {
+            key: args[1].clone(),
+            field: args[2].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let value = read_hash(db, &self.key)?.and_then(|hash| hash.get(&self.field));
+
+        Ok(value.map_or(Frame::Null, |v| Frame::BulkString(v.into_owned())))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hmget {
+    key: String,
+    fields: Vec<String>,
+}
+
+impl Hmget {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Hmget {
+            key: args[1].clone(),
+            fields: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let hash = read_hash(db, &self.key)?;
+
+        let values = self
+            .fields
+            .iter()
+            .map(|field| {
+                hash.as_ref()
+                    .and_then(|hash| hash.get(field))
+                    .map_or(Frame::Null, |v| Frame::BulkString(v.into_owned()))
+            })
+            .collect();
+
+        Ok(Frame::Array(values))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hgetall {
+    key: String,
+}
+
+impl Hgetall {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Hgetall {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let items = read_hash(db, &self.key)?
+            .map(|hash| {
+                hash.iter()
+                    .flat_map(|(f, v)| [f.into_owned(), v.into_owned()])
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Frame::Arrays(items))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hdel {
+    key: String,
+    fields: Vec<String>,
+}
+
+impl Hdel {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Hdel {
+            key: args[1].clone(),
+            fields: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(hash) = get_hash(db, &self.key)? else {
+            return Ok(Frame::Integer(0));
+        };
+
+        let removed = self.fields.iter().filter(|f| hash.remove(f)).count();
+
+        if removed > 0 {
+            db.notify(notify::HASH, "hdel", &self.key);
+            remove_if_empty(db, &self.key);
+            db.touch(&self.key);
+        }
+
+        Ok(Frame::Integer(removed as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hexists {
+    key: String,
+    field: String,
+}
+
+impl Hexists {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Hexists {
+            key: args[1].clone(),
+            field: args[2].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let exists = read_hash(db, &self.key)?.is_some_and(|hash| hash.contains(&self.field));
+
+        Ok(Frame::Integer(exists as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hincrby {
+    key: String,
+    field: String,
+    increment: i64,
+}
+
+impl Hincrby {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Ok(Hincrby {
+            key: args[1].clone(),
+            field: args[2].clone(),
+            increment: parse_int(&args[3])?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let current = match get_hash(db, &self.key)?.and_then(|hash| hash.get(&self.field)) {
+            Some(v) => v
+                .parse::<i64>()
+                .map_err(|_| RedisError::Custom(String::from("hash value is not an integer")))?,
+            None => 0,
+        };
+
+        let value = current
+            .checked_add(self.increment)
+            .ok_or(RedisError::Custom(String::from(
+                "increment or decrement would overflow",
+            )))?;
+
+        let thresholds = db.thresholds();
+        get_or_create_hash(db, &self.key)?.insert(&self.field, &value.to_string(), &thresholds);
+        db.touch(&self.key);
+        db.notify(notify::HASH, "hincrby", &self.key);
+
+        Ok(Frame::Integer(value))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hincrbyfloat {
+    key: String,
+    field: String,
+    increment: f64,
+}
+
+impl Hincrbyfloat {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Ok(Hincrbyfloat {
+            key: args[1].clone(),
+            field: args[2].clone(),
+            increment: parse_float(&args[3])?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let current = match get_hash(db, &self.key)?.and_then(|hash| hash.get(&self.field)) {
+            Some(v) => parse_float(&v)
+                .map_err(|_| RedisError::Custom(String::from("hash value is not a float")))?,
+            None => 0.0,
+        };
+
+        let value = current + self.increment;
+        if !value.is_finite() {
+            return Err(RedisError::Custom(String::from(
+                "increment would produce NaN or Infinity",
+            )));
+        }
+
+        let value = format_float(value);
+
+        let thresholds = db.thresholds();
+        get_or_create_hash(db, &self.key)?.insert(&self.field, &value, &thresholds);
+        db.touch(&self.key);
+        db.notify(notify::HASH, "hincrbyfloat", &self.key);
+
+        // The float is propagated as a plain HSET so replicas don't accumulate rounding errors.
+        db.rewrite(Frame::Arrays(vec![
+            String::from("HSET"),
+            self.key.clone(),
+            self.field.clone(),
+            value.clone(),
+        ]));
+
+        Ok(Frame::BulkString(value))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hkeys {
+    key: String,
+}
+
+impl Hkeys {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Hkeys {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let keys = read_hash(db, &self.key)?
+            .map(|hash| hash.iter().map(|(f, _)| f.into_owned()).collect())
+            .unwrap_or_default();
+
+        Ok(Frame::Arrays(keys))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hvals {
+    key: String,
+}
+
+impl Hvals {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Hvals {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let values = read_hash(db, &self.key)?
+            .map(|hash| hash.iter().map(|(_, v)| v.into_owned()).collect())
+            .unwrap_or_default();
+
+        Ok(Frame::Arrays(values))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hlen {
+    key: String,
+}
+
+impl Hlen {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Hlen {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let len = read_hash(db, &self.key)?.map_or(0, |hash| hash.len());
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hstrlen {
+    key: String,
+    field: String,
+}
+
+impl Hstrlen {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Hstrlen {
+            key: args[1].clone(),
+            field: args[2].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let len = read_hash(db, &self.key)?
+            .and_then(|hash| hash.get(&self.field))
+            .map_or(0, |v| v.len());
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hrandfield {
+    key: String,
+    count: Option<i64>,
+    with_values: bool,
+}
+
+impl Hrandfield {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let count = args.get(2).map(|c| parse_int(c)).transpose()?;
+        let with_values = match args.get(3) {
+            Some(opt) if opt.eq_ignore_ascii_case("withvalues") && args.len() == 4 => true,
+            Some(_) => return Err(RedisError::Syntax),
+            None => false,
+        };
+
+        Ok(Hrandfield {
+            key: args[1].clone(),
+            count,
+            with_values,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let hash = read_hash(db, &self.key)?;
+
+        let Some(count) = self.count else {
+            let field = hash
+                .filter(|hash| !hash.is_empty())
+                .and_then(|hash| hash.iter().nth(random_range(hash.len())))
+                .map(|(f, _)| f.into_owned());
+
+            return Ok(field.map_or(Frame::Null, Frame::BulkString));
+        };
+
+        let Some(hash) = hash.filter(|hash| !hash.is_empty()) else {
+            return Ok(Frame::Arrays(vec![]));
+        };
+
+        let entries: Vec<_> = hash.iter().collect();
+
+        let items = sample(entries, count)
+            .into_iter()
+            .flat_map(|(f, v)| {
+                let mut item = vec![f.into_owned()];
+                if self.with_values {
+                    item.push(v.into_owned());
+                }
+                item
+            })
+            .collect();
+
+        Ok(Frame::Arrays(items))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hscan {
+    key: String,
+    options: ScanOptions,
+    no_values: bool,
+}
+
+impl Hscan {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        let no_values = args[3..].iter().any(|a| a.eq_ignore_ascii_case("novalues"));
+        let options: Vec<String> = args[2..]
+            .iter()
+            .filter(|a| !a.eq_ignore_ascii_case("novalues"))
+            .cloned()
+            .collect();
+
+        Ok(Hscan {
+            key: args[1].clone(),
+            options: ScanOptions::parse(&options)?,
+            no_values,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(hash) = read_hash(db, &self.key)? else {
+            return Ok(scan_reply(0, vec![]));
+        };
+
+        let (cursor, batch) = hash.scan(self.options.cursor, self.options.count);
+
+        let items = batch
+            .into_iter()
+            .filter(|(f, _)| self.options.matches(f))
+            .flat_map(|(f, v)| {
+                let mut item = vec![f];
+                if !self.no_values {
+                    item.push(v);
+                }
+                item
+            })
+            .collect();
+
+        Ok(scan_reply(cursor, items))
+    }
+}
+
+/// Field expirations are limited to 48 bits of milliseconds, like in Redis.
+const MAX_FIELD_EXPIRE_MILLIS: u64 = (1 << 48) - 1;
+
+/// Parses `FIELDS numfields field [field ...]`, which must make up the rest of `args`.
+fn parse_fields(args: &[String]) -> Result<Vec<String>, RedisError> {
+    match args.first() {
+        Some(keyword) if keyword.eq_ignore_ascii_case("fields") => {}
+        _ => {
+            return Err(RedisError::Custom(String::from(
+                "Mandatory argument FIELDS is missing or not at the right position",
+            )))
+        }
+    }
+
+    let count = args.get(1).map(|n| parse_int(n)).transpose()?;
+    match count {
+        Some(n) if n > 0 && n as usize == args.len() - 2 => Ok(args[2..].to_vec()),
+        Some(n) if n > 0 => Err(RedisError::Custom(String::from(
+            "The `numfields` parameter must match the number of arguments",
+        ))),
+        _ => Err(RedisError::Custom(String::from(
+            "Parameter `numFields` should be greater than 0",
+        ))),
+    }
+}
+
+fn field_codes(codes: Vec<i64>) -> Frame {
+    Frame::Array(codes.into_iter().map(Frame::Integer).collect())
+}
+
+/// An expiration given as a relative or absolute time, in seconds or milliseconds.
+#[derive(Debug, Clone, Copy)]
+struct FieldExpiry {
+    value: u64,
+    millis: bool,
+    absolute: bool,
+}
+
+impl FieldExpiry {
+    fn parse(value: &str, millis: bool, absolute: bool) -> Result<Self, RedisError> {
+        let value = parse_int(value)?;
+        if value < 0 {
+            return Err(RedisError::Custom(String::from("invalid expire time")));
+        }
+
+        Ok(FieldExpiry {
+            value: value as u64,
+            millis,
+            absolute,
+        })
+    }
+
+    /// Resolves the expiration to an absolute time, relative to the moment the command runs.
+    fn resolve(&self) -> Result<SystemTime, RedisError> {
+        let ms = if self.millis {
+            Some(self.value)
+        } else {
+            self.value.checked_mul(1000)
+        };
+
+        let ms = if self.absolute {
+            ms
+        } else {
+            ms.and_then(|ms| ms.checked_add(to_unix_millis(SystemTime::now())))
+        };
+
+        match ms {
+            Some(ms) if ms <= MAX_FIELD_EXPIRE_MILLIS => Ok(from_unix_millis(ms)),
+            _ => Err(RedisError::Custom(format!(
+                "invalid expire time, must be >= 0 and <= {MAX_FIELD_EXPIRE_MILLIS}"
+            ))),
+        }
+    }
+}
+
+/// Sets the expiration of `fields` to `at`, deleting the ones whose time has already passed,
+/// and rewrites the command so that replicas apply the same absolute time.
+fn expire_fields<F>(
+    db: &mut dyn Database,
+    key: &str,
+    fields: &[String],
+    at: SystemTime,
+    allow: F,
+) -> Result<Vec<i64>, RedisError>
+where
+    F: Fn(Option<SystemTime>) -> bool,
+{
+    let Some(hash) = get_hash(db, key)? else {
+        return Ok(vec![-2; fields.len()]);
+    };
+
+    let now = SystemTime::now();
+    let mut updated = Vec::new();
+    let mut deleted = Vec::new();
+
+    let codes = fields
+        .iter()
+        .map(|field| {
+            if !hash.contains(field) {
+                -2
+            } else if !allow(hash.expire_time(field)) {
+                0
+            } else if at <= now {
+                hash.remove(field);
+                deleted.push(field.clone());
+                2
+            } else {
+                hash.set_expire_time(field, at);
+                updated.push(field.clone());
+                1
+            }
+        })
+        .collect();
+
+    if !updated.is_empty() {
+        db.track_field_expiry(key);
+        db.rewrite(Frame::Arrays(
+            [
+                vec![
+                    String::from("HPEXPIREAT"),
+                    key.to_owned(),
+                    to_unix_millis(at).to_string(),
+                    String::from("FIELDS"),
+                    updated.len().to_string(),
+                ],
+                updated.clone(),
+            ]
+            .concat(),
+        ));
+    }
+
+    if !updated.is_empty() || !deleted.is_empty() {
+        db.notify(notify::HASH, "hexpire", key);
+    }
+
+    if !deleted.is_empty() {
+        remove_if_empty(db, key);
+        db.rewrite(Frame::Arrays(
+            [vec![String::from("HDEL"), key.to_owned()], deleted.clone()].concat(),
+        ));
+    }
+
+    if !updated.is_empty() || !deleted.is_empty() {
+        db.touch(key);
+    }
+
+    Ok(codes)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ExpireCondition {
+    Always,
+    Nx,
+    Xx,
+    Gt,
+    Lt,
+}
+
+impl ExpireCondition {
+    /// Whether a field whose current expiration is `current` may be given expiration `at`.
+    /// A field without an expiration behaves as if it never expires.
+    fn allows(&self, current: Option<SystemTime>, at: SystemTime) -> bool {
+        match (self, current) {
+            (ExpireCondition::Always, _) => true,
+            (ExpireCondition::Nx, current) => current.is_none(),
+            (ExpireCondition::Xx, current) => current.is_some(),
+            (ExpireCondition::Gt, Some(current)) => at > current,
+            (ExpireCondition::Gt, None) => false,
+            (ExpireCondition::Lt, Some(current)) => at < current,
+            (ExpireCondition::Lt, None) => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hexpire {
+    key: String,
+    expiry: FieldExpiry,
+    condition: ExpireCondition,
+    fields: Vec<String>,
+}
+
+impl Hexpire {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 6)?;
+
+        let cmd = args[0].to_lowercase();
+        let expiry = FieldExpiry::parse(&args[2], cmd.starts_with("hp"), cmd.ends_with("at"))?;
+
+        let (condition, rest) = match args[3].to_lowercase().as_str() {
+            "nx" => (ExpireCondition::Nx, &args[4..]),
+            "xx" => (ExpireCondition::Xx, &args[4..]),
+            "gt" => (ExpireCondition::Gt, &args[4..]),
+            "lt" => (ExpireCondition::Lt, &args[4..]),
+            _ => (ExpireCondition::Always, &args[3..]),
+        };
+
+        Ok(Hexpire {
+            key: args[1].clone(),
+            expiry,
+            condition,
+            fields: parse_fields(rest)?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let at = self.expiry.resolve()?;
+
+        let codes = expire_fields(db, &self.key, &self.fields, at, |current| {
+            self.condition.allows(current, at)
+        })?;
+
+        Ok(field_codes(codes))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Httl {
+    key: String,
+    millis: bool,
+    absolute: bool,
+    fields: Vec<String>,
+}
+
+impl Httl {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+
+        let cmd = args[0].to_lowercase();
+
+        Ok(Httl {
+            key: args[1].clone(),
+            millis: cmd.starts_with("hp"),
+            absolute: cmd.ends_with("time"),
+            fields: parse_fields(&args[2..])?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(hash) = read_hash(db, &self.key)? else {
+            return Ok(field_codes(vec![-2; self.fields.len()]));
+        };
+
+        let codes = self
+            .fields
+            .iter()
+            .map(
+                |field| match (hash.contains(field), hash.expire_time(field)) {
+                    (false, _) => -2,
+                    (true, None) => -1,
+                    (true, Some(at)) => {
+                        let ms = if self.absolute {
+                            to_unix_millis(at)
+                        } else {
+                            millis_until(at)
+                        };
+
+                        match (self.millis, self.absolute) {
+                            (true, _) => ms as i64,
+                            (false, true) => (ms / 1000) as i64,
+                            (false, false) => ((ms + 500) / 1000) as i64,
+                        }
+                    }
+                },
+            )
+            .collect();
+
+        Ok(field_codes(codes))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Hpersist {
+    key: String,
+    fields: Vec<String>,
+}
+
+impl Hpersist {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+
+        Ok(Hpersist {
+            key: args[1].clone(),
+            fields: parse_fields(&args[2..])?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(hash) = get_hash(db, &self.key)? else {
+            return Ok(field_codes(vec![-2; self.fields.len()]));
+        };
+
+        let codes: Vec<i64> = self
+            .fields
+            .iter()
+            .map(|field| match (hash.contains(field), hash.persist(field)) {
+                (false, _) => -2,
+                (true, false) => -1,
+                (true, true) => 1,
+            })
+            .collect();
+
+        if codes.contains(&1) {
+            db.touch(&self.key);
+            db.notify(notify::HASH, "hpersist", &self.key);
+        }
+
+        Ok(field_codes(codes))
+    }
+}
+
+#[derive(Debug)]
+enum HgetexExpiry {
+    Keep,
+    Persist,
+    At(FieldExpiry),
+}
+
+#[derive(Debug)]
+pub(crate) struct Hgetex {
+    key: String,
+    expiry: HgetexExpiry,
+    fields: Vec<String>,
+}
+
+impl Hgetex {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+
+        let (expiry, rest) = match args[2].to_lowercase().as_str() {
+            "persist" => (HgetexExpiry::Persist, &args[3..]),
+            opt @ ("ex" | "px" | "exat" | "pxat") => {
+                let value = args.get(3).ok_or(RedisError::Syntax)?;
+                let expiry = FieldExpiry::parse(value, opt.starts_with('p'), opt.ends_with("at"))?;
+                (HgetexExpiry::At(expiry), &args[4..])
+            }
+            _ => (HgetexExpiry::Keep, &args[2..]),
+        };
+
+        Ok(Hgetex {
+            key: args[1].clone(),
+            expiry,
+            fields: parse_fields(rest)?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let values: Vec<Frame> = match get_hash(db, &self.key)? {
+            Some(hash) => self
+                .fields
+                .iter()
+                .map(|field| {
+                    hash.get(field)
+                        .map_or(Frame::Null, |v| Frame::BulkString(v.into_owned()))
+                })
+                .collect(),
+            None => return Ok(Frame::Array(vec![Frame::Null; self.fields.len()])),
+        };
+
+        match &self.expiry {
+            HgetexExpiry::Keep => {}
+            HgetexExpiry::Persist => {
+                let Some(hash) = get_hash(db, &self.key)? else {
+                    return Ok(Frame::Array(values));
+                };
+
+                let persisted: Vec<String> = self
+                    .fields
+                    .iter()
+                    .filter(|field| hash.persist(field))
+                    .cloned()
+                    .collect();
+
+                if !persisted.is_empty() {
+                    db.touch(&self.key);
+                    db.notify(notify::HASH, "hpersist", &self.key);
+                    db.rewrite(Frame::Arrays(
+                        [
+                            vec![
+                                String::from("HPERSIST"),
+                                self.key.clone(),
+                                String::from("FIELDS"),
+                                persisted.len().to_string(),
+                            ],
+                            persisted,
+                        ]
+                        .concat(),
+                    ));
+                }
+            }
+            HgetexExpiry::At(expiry) => {
+                let at = expiry.resolve()?;
+                expire_fields(db, &self.key, &self.fields, at, |_| true)?;
+            }
+        }
+
+        Ok(Frame::Array(values))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::SystemTime;
+
+    use super::HashCommand;
+    use crate::{
+        cmd::Execute,
+        db::{notify, Database, KeyValueDb},
+        frame::Frame,
+        util::time::to_unix_millis,
+    };
+
+    fn run(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let cmd = HashCommand::parse(&args[0], args.clone()).unwrap();
+        cmd.execute(db).unwrap_or_else(Frame::from)
+    }
+
+    fn codes(codes: &[i64]) -> Frame {
+        Frame::Array(codes.iter().map(|&c| Frame::Integer(c)).collect())
+    }
+
+    fn arrays(items: &[&str]) -> Frame {
+        Frame::Arrays(items.iter().map(|item| item.to_string()).collect())
+    }
+
+    /// Runs an expiry command and returns the time it was rewritten to, checking that it
+    /// lies `ms` from when the command ran.
+    fn rewritten_at(db: &mut KeyValueDb, args: &[&str], ms: u64, fields: &[&str]) -> u64 {
+        db.drain_rewritten();
+        let before = to_unix_millis(SystemTime::now());
+        run(db, args);
+        let after = to_unix_millis(SystemTime::now());
+
+        let rewritten = db.drain_rewritten();
+        let Some([Frame::Arrays(rewritten)]) = rewritten.as_deref() else {
+            panic!("{args:?} wasn't rewritten");
+        };
+        let count = fields.len().to_string();
+        let expected: Vec<&str> = ["HPEXPIREAT", "h", "", "FIELDS", count.as_str()]
+            .into_iter()
+            .chain(fields.iter().copied())
+            .collect();
+        assert_eq!(expected.len(), rewritten.len());
+        for (i, (expected, actual)) in expected.iter().zip(rewritten).enumerate() {
+            if i != 2 {
+                assert_eq!(expected, actual);
+            }
+        }
+
+        let at: u64 = rewritten[2].parse().unwrap();
+        assert!(
+            (before + ms..=after + ms).contains(&at),
+            "{at} for {args:?}"
+        );
+        at
+    }
+
+    #[test]
+    fn test_keymiss_only_on_reads() {
+        let mut db = KeyValueDb::new();
+        db.set_notify_flags(notify::parse("Km").unwrap());
+
+        run(&mut db, &["hdel", "missing", "f"]);
+        run(&mut db, &["hexpire", "missing", "10", "FIELDS", "1", "f"]);
+        assert!(db.drain_notifications().is_empty());
+
+        run(&mut db, &["hget", "missing", "f"]);
+        let events: Vec<_> = db
+            .drain_notifications()
+            .into_iter()
+            .map(|n| (n.event, n.key))
+            .collect();
+        assert_eq!(vec![("keymiss", String::from("missing"))], events);
+    }
+
+    #[test]
+    fn test_expire_rewrites_absolute_millis() {
+        let mut db = KeyValueDb::new();
+        run(&mut db, &["hset", "h", "a", "1", "b", "2", "c", "3"]);
+
+        rewritten_at(
+            &mut db,
+            &["hexpire", "h", "100", "FIELDS", "1", "a"],
+            100_000,
+            &["a"],
+        );
+        rewritten_at(
+            &mut db,
+            &["hpexpire", "h", "2500", "FIELDS", "2", "a", "b"],
+            2500,
+            &["a", "b"],
+        );
+        rewritten_at(
+            &mut db,
+            &["hgetex", "h", "EX", "30", "FIELDS", "1", "c"],
+            30_000,
+            &["c"],
+        );
+        rewritten_at(
+            &mut db,
+            &["hgetex", "h", "PX", "700", "FIELDS", "1", "c"],
+            700,
+            &["c"],
+        );
+
+        // Absolute times are kept as they are, in milliseconds.
+        let at = to_unix_millis(SystemTime::now()) / 1000 + 60;
+        run(
+            &mut db,
+            &["hexpireat", "h", &at.to_string(), "FIELDS", "1", "a"],
+        );
+        assert_eq!(
+            Some(vec![arrays(&[
+                "HPEXPIREAT",
+                "h",
+                &(at * 1000).to_string(),
+                "FIELDS",
+                "1",
+                "a"
+            ])]),
+            db.drain_rewritten()
+        );
+
+        // Fields that are missing or not updated are left out.
+        rewritten_at(
+            &mut db,
+            &["hpexpire", "h", "900", "FIELDS", "2", "x", "b"],
+            900,
+            &["b"],
+        );
+        run(&mut db, &["hpexpire", "h", "900", "NX", "FIELDS", "1", "b"]);
+        assert_eq!(None, db.drain_rewritten());
+    }
+
+    #[test]
+    fn test_expire_in_the_past_deletes() {
+        let mut db = KeyValueDb::new();
+        run(&mut db, &["hset", "h", "a", "1", "b", "2", "c", "3"]);
+
+        let reply = run(&mut db, &["hpexpireat", "h", "1", "FIELDS", "2", "a", "x"]);
+        assert_eq!(codes(&[2, -2]), reply);
+        assert_eq!(
+            Some(vec![arrays(&["HDEL", "h", "a"])]),
+            db.drain_rewritten()
+        );
+
+        let reply = run(&mut db, &["hexpire", "h", "0", "FIELDS", "1", "b"]);
+        assert_eq!(codes(&[2]), reply);
+        assert_eq!(
+            Some(vec![arrays(&["HDEL", "h", "b"])]),
+            db.drain_rewritten()
+        );
+
+        let reply = run(&mut db, &["hgetex", "h", "PXAT", "1", "FIELDS", "1", "c"]);
+        assert_eq!(
+            Frame::Array(vec![Frame::BulkString(String::from("3"))]),
+            reply
+        );
+        assert_eq!(
+            Some(vec![arrays(&["HDEL", "h", "c"])]),
+            db.drain_rewritten()
+        );
+
+        // Deleting the last field deletes the hash.
+        assert!(db.get_value("h").is_none());
+        let reply = run(&mut db, &["hexpire", "h", "0", "FIELDS", "1", "c"]);
+        assert_eq!(codes(&[-2]), reply);
+    }
+
+    #[test]
+    fn test_expire_conditions_on_non_volatile_fields() {
+        let mut db = KeyValueDb::new();
+        run(
+            &mut db,
+            &["hset", "h", "nx", "1", "xx", "2", "gt", "3", "lt", "4"],
+        );
+
+        // A field without an expiration counts as never expiring.
+        for (condition, code) in [("NX", 1), ("XX", 0), ("GT", 0), ("LT", 1)] {
+            let field = condition.to_lowercase();
+            let args = ["hexpire", "h", "100", condition, "FIELDS", "1", &field];
+            assert_eq!(codes(&[code]), run(&mut db, &args), "{condition}");
+        }
+
+        assert_eq!(
+            codes(&[100, -1, -1, 100]),
+            run(
+                &mut db,
+                &["httl", "h", "FIELDS", "4", "nx", "xx", "gt", "lt"]
+            )
+        );
+
+        // Once volatile, they follow the current expiration.
+        for (condition, ttl, code) in [
+            ("NX", 200, 0),
+            ("XX", 200, 1),
+            ("GT", 50, 0),
+            ("GT", 300, 1),
+            ("LT", 500, 0),
+            ("LT", 10, 1),
+        ] {
+            let args = [
+                "hexpire",
+                "h",
+                &ttl.to_string(),
+                condition,
+                "FIELDS",
+                "1",
+                "nx",
+            ];
+            assert_eq!(codes(&[code]), run(&mut db, &args), "{condition} {ttl}");
+        }
+        assert_eq!(
+            codes(&[10]),
+            run(&mut db, &["httl", "h", "FIELDS", "1", "nx"])
+        );
+    }
+
+    #[test]
+    fn test_ttl_replies() {
+        let mut db = KeyValueDb::new();
+        run(&mut db, &["hset", "h", "a", "1", "b", "2"]);
+
+        for cmd in ["httl", "hpttl", "hexpiretime", "hpexpiretime"] {
+            let reply = run(&mut db, &[cmd, "missing", "FIELDS", "2", "a", "b"]);
+            assert_eq!(codes(&[-2, -2]), reply, "{cmd}");
+            let reply = run(&mut db, &[cmd, "h", "FIELDS", "2", "a", "x"]);
+            assert_eq!(codes(&[-1, -2]), reply, "{cmd}");
+        }
+
+        let at = rewritten_at(
+            &mut db,
+            &["hpexpire", "h", "10000", "FIELDS", "1", "a"],
+            10_000,
+            &["a"],
+        );
+
+        let reply = run(&mut db, &["httl", "h", "FIELDS", "2", "a", "b"]);
+        assert_eq!(codes(&[10, -1]), reply);
+        let Frame::Array(reply) = run(&mut db, &["hpttl", "h", "FIELDS", "1", "a"]) else {
+            panic!("HPTTL should reply with an array");
+        };
+        assert!(matches!(reply[..], [Frame::Integer(ms)] if (9000..=10_000).contains(&ms)));
+        let reply = run(&mut db, &["hexpiretime", "h", "FIELDS", "1", "a"]);
+        assert_eq!(codes(&[(at / 1000) as i64]), reply);
+        let reply = run(&mut db, &["hpexpiretime", "h", "FIELDS", "1", "a"]);
+        assert_eq!(codes(&[at as i64]), reply);
+
+        let reply = run(&mut db, &["hpersist", "h", "FIELDS", "3", "a", "b", "x"]);
+        assert_eq!(codes(&[1, -1, -2]), reply);
+        assert_eq!(
+            codes(&[-1]),
+            run(&mut db, &["httl", "h", "FIELDS", "1", "a"])
+        );
+    }
+
+    #[test]
+    fn test_hrandfield_negative_count() {
+        let mut db = KeyValueDb::new();
+        run(&mut db, &["hset", "h", "a", "1", "b", "2"]);
+
+        // A negative count may repeat fields, and always returns that many.
+        let Frame::Arrays(fields) = run(&mut db, &["hrandfield", "h", "-10"]) else {
+            panic!("HRANDFIELD should reply with an array");
+        };
+        assert_eq!(10, fields.len());
+        assert!(fields.iter().all(|f| f == "a" || f == "b"));
+
+        let Frame::Arrays(items) = run(&mut db, &["hrandfield", "h", "-5", "WITHVALUES"]) else {
+            panic!("HRANDFIELD should reply with an array");
+        };
+        assert_eq!(10, items.len());
+        for pair in items.chunks(2) {
+            assert!(pair == ["a", "1"] || pair == ["b", "2"], "{pair:?}");
+        }
+
+        // A positive count never repeats fields.
+        let Frame::Arrays(mut fields) = run(&mut db, &["hrandfield", "h", "10"]) else {
+            panic!("HRANDFIELD should reply with an array");
+        };
+        fields.sort();
+        assert_eq!(vec!["a", "b"], fields);
+
+        assert_eq!(arrays(&[]), run(&mut db, &["hrandfield", "missing", "-3"]));
+        assert_eq!(arrays(&[]), run(&mut db, &["hrandfield", "h", "0"]));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{
+        hyperloglog::{self, Invalid, REGISTERS},
+        notify,
+        string::Str,
+        Data, Database,
+    },
+    error::RedisError,
+    frame::Frame,
+};
+
+#[derive(Debug)]
+pub(crate) enum HyperLogLogCommand {
+    Pfadd(Pfadd),
+    Pfcount(Pfcount),
+    Pfmerge(Pfmerge),
+}
+
+impl HyperLogLogCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "pfadd" => Pfadd::new(args).map(HyperLogLogCommand::Pfadd),
+            "pfcount" => Pfcount::new(args).map(HyperLogLogCommand::Pfcount),
+            "pfmerge" => Pfmerge::new(args).map(HyperLogLogCommand::Pfmerge),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for HyperLogLogCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            HyperLogLogCommand::Pfadd(cmd) => cmd.execute(db),
+            HyperLogLogCommand::Pfcount(cmd) => cmd.execute(db),
+            HyperLogLogCommand::Pfmerge(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn invalid(err: Invalid) -> RedisError {
+    match err {
+        Invalid::NotHll => RedisError::InvalidHll,
+        Invalid::Corrupted => RedisError::CorruptedHll,
+    }
+}
+
+fn get_string<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut Vec<u8>>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_string_mut())
+        .transpose()
+}
+
+fn read_string<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut Vec<u8>>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_string_mut())
+        .transpose()
+}
+
+/// Returns the registers of the HyperLogLog at `key`, and whether it uses the dense encoding.
+fn read_registers(db: &mut dyn Database, key: &str) -> Result<Option<(Vec<u8>, bool)>, RedisError> {
+    let Some(bytes) = read_string(db, key)? else {
+        return Ok(None);
+    };
+
+    let registers = hyperloglog::registers(bytes).map_err(invalid)?;
+    Ok(Some((registers, hyperloglog::is_dense(bytes))))
+}
+
+#[derive(Debug)]
+pub(crate) struct Pfadd {
+    key: String,
+    elements: Vec<String>,
+}
+
+impl Pfadd {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Pfadd {
+            key: args[1].clone(),
+            elements: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let created = get_string(db, &self.key)?.is_none();
+        let bytes = db
+            .get_or_insert_with(&self.key, &|| Data::String(Str::Raw(hyperloglog::new())))
+            .as_string_mut()?;
+
+        let elements = self.elements.iter().map(|e| e.as_bytes());
+        let changed = hyperloglog::add(bytes, elements).map_err(invalid)?;
+
+        if created || changed {
+            db.touch(&self.key);
+            db.notify(notify::STRING, "pfadd", &self.key);
+        }
+
+        Ok(Frame::Integer((created || changed) as i64))
+    }
+}
+
+/// `PFCOUNT key [key ...]`, where several keys give the cardinality of their union without
+/// storing it.
+#[derive(Debug)]
+pub(crate) struct Pfcount {
+    keys: Vec<String>,
+}
+
+impl Pfcount {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Pfcount {
+            keys: args[1..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        if let [key] = self.keys.as_slice() {
+            // A single key caches its cardinality in the header until the next write.
+            let count = match read_string(db, key)? {
+                Some(bytes) => hyperloglog::count(bytes).map_err(invalid)?,
+                None => 0,
+            };
+
+            return Ok(Frame::Integer(count as i64));
+        }
+
+        let mut union = vec![0; REGISTERS];
+        for key in &self.keys {
+            if let Some((registers, _)) = read_registers(db, key)? {
+                hyperloglog::merge(&mut union, &registers);
+            }
+        }
+
+        Ok(Frame::Integer(hyperloglog::estimate(&union) as i64))
+    }
+}
+
+/// `PFMERGE destkey [sourcekey ...]`, where the destination is part of the union.
+#[derive(Debug)]
+pub(crate) struct Pfmerge {
+    dest: String,
+    sources: Vec<String>,
+}
+
+impl Pfmerge {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Pfmerge {
+            dest: args[1].clone(),
+            sources: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let mut union = vec![0; REGISTERS];
+        let mut dense = false;
+
+        for key in std::iter::once(&self.dest).chain(&self.sources) {
+            if let Some((registers, is_dense)) = read_registers(db, key)? {
+                hyperloglog::merge(&mut union, &registers);
+                dense |= is_dense;
+            }
+        }
+
+        // The result stays sparse unless one of the inputs was dense. Writing it in place
+        // keeps the expiration of the destination.
+        let bytes = db
+            .get_or_insert_with(&self.dest, &|| Data::String(Str::Raw(Vec::new())))
+            .as_string_mut()?;
+        *bytes = hyperloglog::encode(&union, !dense);
+        db.touch(&self.dest);
+        db.notify(notify::STRING, "pfadd", &self.dest);
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
@@ -21,6 +21,12 @@ impl Info {
         _config: &Config,
         repl: &Replication,
     ) -> Result<(), Error> {
+        conn.write_frame(&self.reply(repl)).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn reply(&self, repl: &Replication) -> Frame {
         let s = match repl.role {
             Role::Master => {
                 let info = [
@@ -34,10 +40,6 @@ impl Info {
             Role::Slave => format!("role:{}", repl.role),
         };
 
-        let frame = Frame::BulkString(s);
-
-        conn.write_frame(&frame).await?;
-
-        Ok(())
+        Frame::BulkString(s)
     }
 }
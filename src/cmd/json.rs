@@ -0,0 +1,935 @@
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{
+        json::{self, Format, Json, Location, Path},
+        notify, Data, Database, Value,
+    },
+    error::RedisError,
+    frame::Frame,
+};
+
+#[derive(Debug)]
+pub(crate) enum JsonCommand {
+    Set(JsonSet),
+    Get(JsonGet),
+    Del(JsonDel),
+    Type(JsonType),
+    Numincrby(JsonNumincrby),
+    Arrappend(JsonArrappend),
+    Strappend(JsonStrappend),
+    Mget(JsonMget),
+}
+
+impl JsonCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "json.set" => JsonSet::new(args).map(JsonCommand::Set),
+            "json.get" => JsonGet::new(args).map(JsonCommand::Get),
+            "json.del" | "json.forget" => JsonDel::new(args).map(JsonCommand::Del),
+            "json.type" => JsonType::new(args).map(JsonCommand::Type),
+            "json.numincrby" => JsonNumincrby::new(args).map(JsonCommand::Numincrby),
+            "json.arrappend" => JsonArrappend::new(args).map(JsonCommand::Arrappend),
+            "json.strappend" => JsonStrappend::new(args).map(JsonCommand::Strappend),
+            "json.mget" => JsonMget::new(args).map(JsonCommand::Mget),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for JsonCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            JsonCommand::Set(cmd) => cmd.execute(db),
+            JsonCommand::Get(cmd) => cmd.execute(db),
+            JsonCommand::Del(cmd) => cmd.execute(db),
+            JsonCommand::Type(cmd) => cmd.execute(db),
+            JsonCommand::Numincrby(cmd) => cmd.execute(db),
+            JsonCommand::Arrappend(cmd) => cmd.execute(db),
+            JsonCommand::Strappend(cmd) => cmd.execute(db),
+            JsonCommand::Mget(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn get_json<'a>(db: &'a mut dyn Database, key: &str) -> Result<Option<&'a mut Json>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_json_mut())
+        .transpose()
+}
+
+fn read_json<'a>(db: &'a mut dyn Database, key: &str) -> Result<Option<&'a mut Json>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_json_mut())
+        .transpose()
+}
+
+/// Like `get_json`, for commands that update part of an existing document.
+fn get_existing_json<'a>(db: &'a mut dyn Database, key: &str) -> Result<&'a mut Json, RedisError> {
+    get_json(db, key)?.ok_or(RedisError::Custom(String::from(
+        "could not perform this operation on a key that doesn't exist",
+    )))
+}
+
+fn parse_json(s: &str) -> Result<Json, RedisError> {
+    Json::parse(s).map_err(|e| RedisError::Custom(format!("invalid JSON, {e}")))
+}
+
+/// A path along with how it was written, for error messages and JSON.GET replies.
+#[derive(Debug)]
+struct PathArg {
+    path: Path,
+    raw: String,
+}
+
+impl PathArg {
+    fn parse(s: &str) -> Result<Self, RedisError> {
+        Ok(PathArg {
+            path: Path::parse(s).map_err(RedisError::Custom)?,
+            raw: s.to_owned(),
+        })
+    }
+
+    fn root() -> Self {
+        PathArg {
+            path: Path::root(),
+            raw: String::from("."),
+        }
+    }
+
+    /// A legacy path refers to a single value, the first one it matches.
+    fn locations(&self, root: &Json) -> Vec<Location> {
+        let mut locations = self.path.select(root);
+        if self.path.is_legacy() {
+            locations.truncate(1);
+        }
+        locations
+    }
+
+    fn missing(&self) -> RedisError {
+        RedisError::Custom(format!("Path '{}' does not exist", self.raw))
+    }
+
+    /// Applies `update` to every value the path matches. It returns the type of the value
+    /// when the value doesn't support the operation.
+    fn update<T>(
+        &self,
+        root: &mut Json,
+        mut update: impl FnMut(&mut Json) -> Result<T, &'static str>,
+    ) -> Vec<Result<T, &'static str>> {
+        self.locations(root)
+            .iter()
+            .map(|location| update(json::get_mut(root, location)))
+            .collect()
+    }
+
+    /// Replies with the result for a legacy path, failing if it didn't match or the value has
+    /// the wrong type, or with all results otherwise, nil for values of the wrong type.
+    fn reply<T>(
+        &self,
+        expected: &str,
+        results: Vec<Result<T, &'static str>>,
+        frame: impl Fn(T) -> Frame,
+    ) -> Result<Frame, RedisError> {
+        if !self.path.is_legacy() {
+            let frames = results
+                .into_iter()
+                .map(|r| r.map_or(Frame::Null, &frame))
+                .collect();
+            return Ok(Frame::Array(frames));
+        }
+
+        match results.into_iter().next() {
+            Some(Ok(result)) => Ok(frame(result)),
+            Some(Err(found)) => Err(RedisError::Custom(format!(
+                "wrong type of path value - expected {expected} but found {found}"
+            ))),
+            None => Err(self.missing()),
+        }
+    }
+}
+
+/// `JSON.SET key path value [NX | XX]`
+#[derive(Debug)]
+pub(crate) struct JsonSet {
+    key: String,
+    path: PathArg,
+    value: Json,
+    nx: bool,
+    xx: bool,
+}
+
+impl JsonSet {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let (nx, xx) = match args.get(4).map(|s| s.to_lowercase()).as_deref() {
+            None => (false, false),
+            Some("nx") => (true, false),
+            Some("xx") => (false, true),
+            Some(_) => return Err(RedisError::Syntax),
+        };
+
+        if args.len() > 5 {
+            return Err(RedisError::Syntax);
+        }
+
+        Ok(JsonSet {
+            key: args[1].clone(),
+            path: PathArg::parse(&args[2])?,
+            value: parse_json(&args[3])?,
+            nx,
+            xx,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(root) = get_json(db, &self.key)? else {
+            if !self.path.path.is_root() {
+                return Err(RedisError::Custom(String::from(
+                    "new objects must be created at the root",
+                )));
+            }
+            if self.xx {
+                return Ok(Frame::Null);
+            }
+
+            db.insert(
+                &self.key,
+                Value::from(Data::Json(Box::new(self.value.clone()))),
+            );
+            db.touch(&self.key);
+            db.notify(notify::MODULE, "json.set", &self.key);
+            return Ok(Frame::SimpleString(String::from("OK")));
+        };
+
+        let locations = self.path.locations(root);
+        if !locations.is_empty() {
+            if self.nx {
+                return Ok(Frame::Null);
+            }
+
+            for location in locations {
+                *json::get_mut(root, &location) = self.value.clone();
+            }
+        } else {
+            if self.xx {
+                return Ok(Frame::Null);
+            }
+
+            // A missing member is added to the objects matched by the parent path.
+            let Some((parent, key)) = self.path.path.parent() else {
+                return Ok(Frame::Null);
+            };
+
+            let mut created = false;
+            for location in parent.select(root) {
+                if let Json::Object(members) = json::get_mut(root, &location) {
+                    members.push((key.to_owned(), self.value.clone()));
+                    created = true;
+                }
+            }
+
+            if !created {
+                return Ok(Frame::Null);
+            }
+        }
+
+        db.touch(&self.key);
+        db.notify(notify::MODULE, "json.set", &self.key);
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
+
+/// `JSON.GET key [INDENT indent] [NEWLINE newline] [SPACE space] [path ...]`
+#[derive(Debug)]
+pub(crate) struct JsonGet {
+    key: String,
+    indent: String,
+    newline: String,
+    space: String,
+    paths: Vec<PathArg>,
+}
+
+impl JsonGet {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let mut get = JsonGet {
+            key: args[1].clone(),
+            indent: String::new(),
+            newline: String::new(),
+            space: String::new(),
+            paths: Vec::new(),
+        };
+
+        let mut rest = args[2..].iter();
+        while let Some(arg) = rest.next() {
+            let option = match arg.to_lowercase().as_str() {
+                "indent" => &mut get.indent,
+                "newline" => &mut get.newline,
+                "space" => &mut get.space,
+                _ => {
+                    get.paths.push(PathArg::parse(arg)?);
+                    continue;
+                }
+            };
+            *option = rest.next().ok_or(RedisError::Syntax)?.clone();
+        }
+
+        if get.paths.is_empty() {
+            get.paths.push(PathArg::root());
+        }
+
+        Ok(get)
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(root) = read_json(db, &self.key)? else {
+            return Ok(Frame::Null);
+        };
+
+        // Legacy paths return their value, unless they are mixed with JSONPath ones, which
+        // return arrays of matches.
+        let legacy = self.paths.iter().all(|p| p.path.is_legacy());
+        let select = |path: &PathArg| -> Result<Json, RedisError> {
+            let locations = path.path.select(root);
+            match (legacy, locations.first()) {
+                (true, Some(location)) => Ok(json::get(root, location).clone()),
+                (true, None) => Err(path.missing()),
+                (false, _) => Ok(Json::Array(
+                    locations
+                        .iter()
+                        .map(|l| json::get(root, l).clone())
+                        .collect(),
+                )),
+            }
+        };
+
+        let result = match self.paths.as_slice() {
+            [path] => select(path)?,
+            paths => Json::Object(
+                paths
+                    .iter()
+                    .map(|path| Ok((path.raw.clone(), select(path)?)))
+                    .collect::<Result<_, RedisError>>()?,
+            ),
+        };
+
+        let format = Format {
+            indent: &self.indent,
+            newline: &self.newline,
+            space: &self.space,
+        };
+        Ok(Frame::BulkString(result.to_formatted(&format)))
+    }
+}
+
+/// `JSON.DEL key [path]`, where deleting the root deletes the key.
+#[derive(Debug)]
+pub(crate) struct JsonDel {
+    key: String,
+    path: PathArg,
+}
+
+impl JsonDel {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let path = match args.len() {
+            2 => PathArg::root(),
+            3 => PathArg::parse(&args[2])?,
+            _ => return Err(RedisError::Syntax),
+        };
+
+        Ok(JsonDel {
+            key: args[1].clone(),
+            path,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(root) = get_json(db, &self.key)? else {
+            return Ok(Frame::Integer(0));
+        };
+
+        let deleted = if self.path.path.is_root() {
+            db.remove(&self.key);
+            1
+        } else {
+            let locations = self.path.locations(root);
+            json::delete(root, locations)
+        };
+
+        if deleted > 0 {
+            db.touch(&self.key);
+            db.notify(notify::MODULE, "json.del", &self.key);
+        }
+
+        Ok(Frame::Integer(deleted as i64))
+    }
+}
+
+/// `JSON.TYPE key [path]`
+#[derive(Debug)]
+pub(crate) struct JsonType {
+    key: String,
+    path: PathArg,
+}
+
+impl JsonType {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let path = match args.len() {
+            2 => PathArg::root(),
+            3 => PathArg::parse(&args[2])?,
+            _ => return Err(RedisError::Syntax),
+        };
+
+        Ok(JsonType {
+            key: args[1].clone(),
+            path,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(root) = read_json(db, &self.key)? else {
+            return Ok(Frame::Null);
+        };
+
+        let types: Vec<String> = self
+            .path
+            .locations(root)
+            .iter()
+            .map(|l| json::get(root, l).type_name().to_owned())
+            .collect();
+
+        match self.path.path.is_legacy() {
+            true => Ok(types
+                .into_iter()
+                .next()
+                .map_or(Frame::Null, Frame::BulkString)),
+            false => Ok(Frame::Arrays(types)),
+        }
+    }
+}
+
+/// `JSON.NUMINCRBY key path value`, which replies with the new values serialized as JSON.
+#[derive(Debug)]
+pub(crate) struct JsonNumincrby {
+    key: String,
+    path: PathArg,
+    incr: Json,
+}
+
+impl JsonNumincrby {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        if args.len() > 4 {
+            return Err(RedisError::Syntax);
+        }
+
+        let incr = match parse_json(&args[3])? {
+            n @ (Json::Int(_) | Json::Float(_)) => n,
+            other => {
+                return Err(RedisError::Custom(format!(
+                    "expected a number but found {}",
+                    other.type_name()
+                )))
+            }
+        };
+
+        Ok(JsonNumincrby {
+            key: args[1].clone(),
+            path: PathArg::parse(&args[2])?,
+            incr,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let root = get_existing_json(db, &self.key)?;
+
+        let as_float = |n: &Json| match *n {
+            Json::Int(i) => i as f64,
+            Json::Float(f) => f,
+            _ => unreachable!("only numbers are incremented"),
+        };
+
+        let mut overflow = false;
+        let results = self.path.update(root, |value| {
+            let sum = match (&*value, &self.incr) {
+                (Json::Int(a), Json::Int(b)) => match a.checked_add(*b) {
+                    Some(sum) => Json::Int(sum),
+                    None => Json::Float(*a as f64 + *b as f64),
+                },
+                (Json::Int(_) | Json::Float(_), incr) => {
+                    Json::Float(as_float(value) + as_float(incr))
+                }
+                (other, _) => return Err(other.type_name()),
+            };
+
+            if let Json::Float(f) = sum {
+                if !f.is_finite() {
+                    overflow = true;
+                    return Err("number");
+                }
+            }
+
+            *value = sum.clone();
+            Ok(sum)
+        });
+
+        if overflow {
+            return Err(RedisError::Custom(String::from(
+                "result is not a finite number",
+            )));
+        }
+
+        if results.iter().any(|r| r.is_ok()) {
+            db.touch(&self.key);
+            db.notify(notify::MODULE, "json.numincrby", &self.key);
+        }
+
+        match self.path.path.is_legacy() {
+            true => self
+                .path
+                .reply("a number", results, |n| Frame::BulkString(n.to_compact())),
+            false => {
+                let values = results.into_iter().map(|r| r.unwrap_or(Json::Null));
+                Ok(Frame::BulkString(
+                    Json::Array(values.collect()).to_compact(),
+                ))
+            }
+        }
+    }
+}
+
+/// `JSON.ARRAPPEND key path value [value ...]`, which replies with the new lengths.
+#[derive(Debug)]
+pub(crate) struct JsonArrappend {
+    key: String,
+    path: PathArg,
+    values: Vec<Json>,
+}
+
+impl JsonArrappend {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Ok(JsonArrappend {
+            key: args[1].clone(),
+            path: PathArg::parse(&args[2])?,
+            values: args[3..]
+                .iter()
+                .map(|s| parse_json(s))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let root = get_existing_json(db, &self.key)?;
+
+        let results = self.path.update(root, |value| match value {
+            Json::Array(items) => {
+                items.extend(self.values.iter().cloned());
+                Ok(items.len())
+            }
+            other => Err(other.type_name()),
+        });
+
+        if results.iter().any(|r| r.is_ok()) {
+            db.touch(&self.key);
+            db.notify(notify::MODULE, "json.arrappend", &self.key);
+        }
+
+        self.path
+            .reply("an array", results, |len| Frame::Integer(len as i64))
+    }
+}
+
+/// `JSON.STRAPPEND key [path] value`, where the value is a JSON string, which replies with
+/// the new lengths.
+#[derive(Debug)]
+pub(crate) struct JsonStrappend {
+    key: String,
+    path: PathArg,
+    suffix: String,
+}
+
+impl JsonStrappend {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        let (path, value) = match args.len() {
+            3 => (PathArg::root(), &args[2]),
+            4 => (PathArg::parse(&args[2])?, &args[3]),
+            _ => return Err(RedisError::Syntax),
+        };
+
+        let Json::String(suffix) = parse_json(value)? else {
+            return Err(RedisError::Custom(String::from(
+                "expected a JSON string to append",
+            )));
+        };
+
+        Ok(JsonStrappend {
+            key: args[1].clone(),
+            path,
+            suffix,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let root = get_existing_json(db, &self.key)?;
+
+        let results = self.path.update(root, |value| match value {
+            Json::String(s) => {
+                s.push_str(&self.suffix);
+                Ok(s.len())
+            }
+            other => Err(other.type_name()),
+        });
+
+        if results.iter().any(|r| r.is_ok()) {
+            db.touch(&self.key);
+            db.notify(notify::MODULE, "json.strappend", &self.key);
+        }
+
+        self.path
+            .reply("a string", results, |len| Frame::Integer(len as i64))
+    }
+}
+
+/// `JSON.MGET key [key ...] path`, with nil for missing keys and keys that don't hold JSON.
+#[derive(Debug)]
+pub(crate) struct JsonMget {
+    keys: Vec<String>,
+    path: PathArg,
+}
+
+impl JsonMget {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(JsonMget {
+            keys: args[1..args.len() - 1].to_vec(),
+            path: PathArg::parse(&args[args.len() - 1])?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let replies = self
+            .keys
+            .iter()
+            .map(|key| {
+                let Some(root) = db.read_value(key).and_then(|v| v.as_json_mut().ok()) else {
+                    return Frame::Null;
+                };
+
+                let locations = self.path.locations(root);
+                let values = locations.iter().map(|l| json::get(root, l).clone());
+                match self.path.path.is_legacy() {
+                    true => values
+                        .map(|v| Frame::BulkString(v.to_compact()))
+                        .next()
+                        .unwrap_or(Frame::Null),
+                    false => Frame::BulkString(Json::Array(values.collect()).to_compact()),
+                }
+            })
+            .collect();
+
+        Ok(Frame::Array(replies))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::JsonCommand;
+    use crate::{
+        cmd::Execute,
+        db::{Database, KeyValueDb},
+        error::RedisError,
+        frame::Frame,
+    };
+
+    fn run(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        JsonCommand::parse(&args[0], args.clone())
+            .and_then(|cmd| cmd.execute(db))
+            .unwrap_or_else(Frame::from)
+    }
+
+    fn ok() -> Frame {
+        Frame::SimpleString(String::from("OK"))
+    }
+
+    fn bulk(s: &str) -> Frame {
+        Frame::BulkString(s.to_owned())
+    }
+
+    fn error(msg: &str) -> Frame {
+        Frame::from(RedisError::Custom(msg.to_owned()))
+    }
+
+    fn doc(db: &mut KeyValueDb) -> Frame {
+        run(db, &["json.get", "j"])
+    }
+
+    #[test]
+    fn test_set_paths() {
+        let mut db = KeyValueDb::new();
+
+        assert_eq!(
+            error("new objects must be created at the root"),
+            run(&mut db, &["json.set", "j", "$.a", "1"])
+        );
+        let reply = run(
+            &mut db,
+            &["json.set", "j", "$", r#"{"a":1,"b":{"c":[1,2]},"e":{}}"#],
+        );
+        assert_eq!(ok(), reply);
+
+        // Legacy paths and JSONPath both update existing values.
+        assert_eq!(ok(), run(&mut db, &["json.set", "j", ".a", "2"]));
+        assert_eq!(ok(), run(&mut db, &["json.set", "j", "$.b.c[*]", "0"]));
+        assert_eq!(ok(), run(&mut db, &["json.set", "j", "b.c[-1]", "\"x\""]));
+
+        // A missing member is added to every object its parent matches.
+        assert_eq!(ok(), run(&mut db, &["json.set", "j", "$.*.f", "true"]));
+        assert_eq!(Frame::Null, run(&mut db, &["json.set", "j", "$.x.y", "1"]));
+        assert_eq!(
+            Frame::Null,
+            run(&mut db, &["json.set", "j", "$.b.c.z", "1"])
+        );
+        assert_eq!(Frame::Null, run(&mut db, &["json.set", "j", "$.a[5]", "1"]));
+        assert_eq!(
+            bulk(r#"{"a":2,"b":{"c":[0,"x"],"f":true},"e":{"f":true}}"#),
+            doc(&mut db)
+        );
+
+        let invalid_path = run(&mut db, &["json.set", "j", "$[", "1"]);
+        assert!(matches!(invalid_path, Frame::Error(_)), "{invalid_path:?}");
+        let Frame::Error(invalid_json) = run(&mut db, &["json.set", "j", "$", "{"]) else {
+            panic!("invalid JSON should be rejected");
+        };
+        assert!(
+            invalid_json.starts_with("ERR invalid JSON, "),
+            "{invalid_json}"
+        );
+
+        db.set("s", b"x", None);
+        assert_eq!(
+            Frame::from(RedisError::WrongType),
+            run(&mut db, &["json.set", "s", "$", "1"])
+        );
+    }
+
+    #[test]
+    fn test_set_nx_xx() {
+        let mut db = KeyValueDb::new();
+
+        assert_eq!(
+            Frame::Null,
+            run(&mut db, &["json.set", "j", "$", "{}", "XX"])
+        );
+        assert!(db.get_value("j").is_none());
+        assert_eq!(
+            ok(),
+            run(&mut db, &["json.set", "j", "$", r#"{"a":1}"#, "NX"])
+        );
+        assert_eq!(
+            Frame::Null,
+            run(&mut db, &["json.set", "j", "$", "{}", "NX"])
+        );
+
+        assert_eq!(
+            Frame::Null,
+            run(&mut db, &["json.set", "j", "$.a", "2", "nx"])
+        );
+        assert_eq!(ok(), run(&mut db, &["json.set", "j", "$.a", "2", "xx"]));
+        assert_eq!(
+            Frame::Null,
+            run(&mut db, &["json.set", "j", ".b", "3", "XX"])
+        );
+        assert_eq!(ok(), run(&mut db, &["json.set", "j", ".b", "3", "NX"]));
+        assert_eq!(bulk(r#"{"a":2,"b":3}"#), doc(&mut db));
+
+        for args in [&["NX", "XX"][..], &["FOO"], &["NX", "NX"]] {
+            let reply = run(&mut db, &[&["json.set", "j", "$", "1"], args].concat());
+            assert_eq!(Frame::from(RedisError::Syntax), reply, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_get_paths() {
+        let mut db = KeyValueDb::new();
+        run(
+            &mut db,
+            &[
+                "json.set",
+                "j",
+                "$",
+                r#"{"a":{"n":1},"b":{"n":2},"c":[1,2,3]}"#,
+            ],
+        );
+
+        // Legacy paths return the first match, JSONPath returns them all.
+        assert_eq!(bulk("1"), run(&mut db, &["json.get", "j", ".a.n"]));
+        assert_eq!(bulk("[1,2]"), run(&mut db, &["json.get", "j", "$.*.n"]));
+        assert_eq!(bulk("1"), run(&mut db, &["json.get", "j", "*.n"]));
+        assert_eq!(bulk("[]"), run(&mut db, &["json.get", "j", "$.x"]));
+        assert_eq!(bulk("[2,3]"), run(&mut db, &["json.get", "j", "$.c[1:]"]));
+        assert_eq!(
+            error("Path '.x' does not exist"),
+            run(&mut db, &["json.get", "j", ".x"])
+        );
+
+        // Several paths reply with an object, of arrays when any of them is JSONPath.
+        assert_eq!(
+            bulk(r#"{".a.n":1,"c[0]":1}"#),
+            run(&mut db, &["json.get", "j", ".a.n", "c[0]"])
+        );
+        assert_eq!(
+            bulk(r#"{".a.n":[1],"$.c[-1]":[3]}"#),
+            run(&mut db, &["json.get", "j", ".a.n", "$.c[-1]"])
+        );
+        assert_eq!(
+            bulk("{\n\t\"n\":_1\n}"),
+            run(
+                &mut db,
+                &["json.get", "j", "INDENT", "\t", "NEWLINE", "\n", "SPACE", "_", ".a"]
+            )
+        );
+        assert_eq!(Frame::Null, run(&mut db, &["json.get", "missing"]));
+
+        assert_eq!(
+            Frame::Arrays(vec![String::from("integer"), String::from("integer")]),
+            run(&mut db, &["json.type", "j", "$.*.n"])
+        );
+        assert_eq!(bulk("array"), run(&mut db, &["json.type", "j", ".c"]));
+        assert_eq!(Frame::Null, run(&mut db, &["json.type", "j", ".x"]));
+
+        db.set("s", b"x", None);
+        assert_eq!(
+            Frame::Array(vec![bulk("1"), Frame::Null, Frame::Null]),
+            run(&mut db, &["json.mget", "j", "s", "missing", ".a.n"])
+        );
+        assert_eq!(
+            Frame::Array(vec![bulk("[1,2]"), Frame::Null]),
+            run(&mut db, &["json.mget", "j", "missing", "$.*.n"])
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let mut db = KeyValueDb::new();
+        run(
+            &mut db,
+            &["json.set", "j", "$", r#"{"n":1,"s":"ab","a":[]}"#],
+        );
+
+        assert_eq!(
+            error("wrong type of path value - expected a number but found string"),
+            run(&mut db, &["json.numincrby", "j", ".s", "1"])
+        );
+        assert_eq!(
+            error("wrong type of path value - expected an array but found integer"),
+            run(&mut db, &["json.arrappend", "j", ".n", "1"])
+        );
+        assert_eq!(
+            error("wrong type of path value - expected a string but found array"),
+            run(&mut db, &["json.strappend", "j", ".a", "\"x\""])
+        );
+
+        // JSONPath replies with nil for values of the wrong type and updates the rest.
+        assert_eq!(
+            bulk("[3,null,null]"),
+            run(&mut db, &["json.numincrby", "j", "$.*", "2"])
+        );
+        assert_eq!(
+            Frame::Array(vec![Frame::Null, Frame::Null, Frame::Integer(1)]),
+            run(&mut db, &["json.arrappend", "j", "$.*", "true"])
+        );
+        assert_eq!(
+            Frame::Array(vec![Frame::Null, Frame::Integer(4), Frame::Null]),
+            run(&mut db, &["json.strappend", "j", "$.*", "\"cd\""])
+        );
+        assert_eq!(bulk(r#"{"n":3,"s":"abcd","a":[true]}"#), doc(&mut db));
+
+        assert_eq!(
+            error("expected a number but found string"),
+            run(&mut db, &["json.numincrby", "j", ".n", "\"1\""])
+        );
+        assert_eq!(
+            error("expected a JSON string to append"),
+            run(&mut db, &["json.strappend", "j", ".s", "1"])
+        );
+        assert_eq!(
+            error("Path '.x' does not exist"),
+            run(&mut db, &["json.numincrby", "j", ".x", "1"])
+        );
+        assert_eq!(
+            error("could not perform this operation on a key that doesn't exist"),
+            run(&mut db, &["json.arrappend", "missing", "$", "1"])
+        );
+    }
+
+    #[test]
+    fn test_numincrby() {
+        let mut db = KeyValueDb::new();
+        run(
+            &mut db,
+            &[
+                "json.set",
+                "j",
+                "$",
+                r#"{"i":1,"f":1.5,"big":9223372036854775807}"#,
+            ],
+        );
+
+        assert_eq!(bulk("3"), run(&mut db, &["json.numincrby", "j", ".i", "2"]));
+        assert_eq!(
+            bulk("3.5"),
+            run(&mut db, &["json.numincrby", "j", ".i", "0.5"])
+        );
+        assert_eq!(
+            bulk("[2.0]"),
+            run(&mut db, &["json.numincrby", "j", "$.f", "0.5"])
+        );
+
+        // Integers that overflow become floats, but infinities are rejected.
+        run(&mut db, &["json.numincrby", "j", ".big", "1"]);
+        assert_eq!(bulk("number"), run(&mut db, &["json.type", "j", ".big"]));
+        run(&mut db, &["json.set", "j", ".f", "1e308"]);
+        assert_eq!(
+            error("result is not a finite number"),
+            run(&mut db, &["json.numincrby", "j", ".f", "1e308"])
+        );
+        assert_eq!(bulk("1e308"), run(&mut db, &["json.get", "j", ".f"]));
+    }
+
+    #[test]
+    fn test_del() {
+        let mut db = KeyValueDb::new();
+        run(
+            &mut db,
+            &["json.set", "j", "$", r#"{"a":[1,2,3],"b":{"a":1}}"#],
+        );
+
+        assert_eq!(
+            Frame::Integer(2),
+            run(&mut db, &["json.del", "j", "$.a[0:2]"])
+        );
+        assert_eq!(Frame::Integer(0), run(&mut db, &["json.del", "j", "$.x"]));
+        assert_eq!(
+            Frame::Integer(1),
+            run(&mut db, &["json.forget", "j", ".b.a"])
+        );
+        assert_eq!(bulk(r#"{"a":[3],"b":{}}"#), doc(&mut db));
+
+        assert_eq!(Frame::Integer(1), run(&mut db, &["json.del", "j"]));
+        assert!(db.get_value("j").is_none());
+        assert_eq!(Frame::Integer(0), run(&mut db, &["json.del", "j", "$"]));
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::SystemTime;
+
+use crate::{
+    cmd::{check_arity, Execute},
+    db::Database,
+    error::RedisError,
+    frame::Frame,
+    util::num::parse_int,
+};
+
+#[derive(Debug)]
+enum MemorySubcommand {
+    Usage,
+}
+
+/// `MEMORY USAGE key [SAMPLES count]`, replying with an estimate of the bytes taken by the
+/// key, its value and its expiration. Every element is counted, so the sample count is only
+/// validated.
+#[derive(Debug)]
+pub(crate) struct Memory {
+    key: String,
+    subcommand: MemorySubcommand,
+}
+
+impl Memory {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let subcommand = match args[1].to_lowercase().as_str() {
+            "usage" if args.len() == 3 => MemorySubcommand::Usage,
+            "usage" if args.len() == 5 && args[3].eq_ignore_ascii_case("samples") => {
+                if parse_int(&args[4])? < 0 {
+                    return Err(RedisError::Syntax);
+                }
+                MemorySubcommand::Usage
+            }
+            "usage" if args.len() < 3 => {
+                return Err(RedisError::WrongArity(String::from("memory|usage")))
+            }
+            "usage" => return Err(RedisError::Syntax),
+            _ => {
+                return Err(RedisError::Custom(format!(
+                    "unknown subcommand '{}'. Try MEMORY HELP.",
+                    args[1]
+                )))
+            }
+        };
+
+        Ok(Memory {
+            key: args[2].clone(),
+            subcommand,
+        })
+    }
+}
+
+impl Execute for Memory {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self.subcommand {
+            MemorySubcommand::Usage => {
+                let Some(value) = db.get_value(&self.key) else {
+                    return Ok(Frame::Null);
+                };
+
+                let key = size_of::<String>() + self.key.len();
+                let value = value.memory_usage();
+                let expiry = match db.expire_time(&self.key) {
+                    Some(_) => key + size_of::<SystemTime>(),
+                    None => 0,
+                };
+
+                Ok(Frame::Integer((key + value + expiry) as i64))
+            }
+        }
+    }
+}
@@ -1,20 +1,113 @@
+use acl::{AclCommand, Auth};
+use bitmap::BitmapCommand;
+use bloom::BloomCommand;
+use config::ConfigCommand;
+use consumer_group::ConsumerGroupCommand;
+use countmin::CountMinCommand;
+use cuckoo::CuckooCommand;
+use del::Del;
 use echo::Echo;
+use flushdb::Flushdb;
+use geo::GeoCommand;
 use get::Get;
+use hash::HashCommand;
+use hyperloglog::HyperLogLogCommand;
 use info::Info;
+use json::JsonCommand;
+use memory::Memory;
+use module::ModuleCall;
+use object::Object;
 use ping::Ping;
 use psync::Psync;
+use pubsub::{PubSubCommand, Subscription};
 use replconf::Replconf;
+use search::SearchCommand;
 use set::Set;
+use sets::SetCommand;
+use stream::StreamCommand;
+use throttle::Throttle;
+use timeseries::TimeSeriesCommand;
+use vectorset::VectorSetCommand;
+use zset::SortedSetCommand;
 
-use crate::frame::Frame;
+use std::{fmt::Debug, sync::Arc, time::Duration};
+use table::Access;
 
+use crate::{
+    db::{notify, Database},
+    error::RedisError,
+    frame::Frame,
+    module::Registry,
+    util::{
+        glob,
+        num::{parse_float, parse_int},
+    },
+};
+
+pub mod acl;
+pub mod bitmap;
+pub mod bloom;
+pub mod config;
+pub mod consumer_group;
+pub mod countmin;
+pub mod cuckoo;
+pub mod del;
 pub mod echo;
+pub mod flushdb;
+pub mod geo;
 pub mod get;
+pub mod hash;
+pub mod hyperloglog;
 pub mod info;
+pub mod json;
+pub mod memory;
+pub mod module;
+pub mod object;
 pub mod ping;
 pub mod psync;
+pub mod pubsub;
 pub mod replconf;
+pub mod search;
 pub mod set;
+pub mod sets;
+pub mod stream;
+pub mod table;
+pub mod throttle;
+pub mod timeseries;
+pub mod vectorset;
+pub mod zset;
+
+/// A command that runs entirely under the database lock and produces a single reply.
+pub(crate) trait Execute {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError>;
+}
+
+/// A command that waits for one of its keys to become ready when it cannot be served right
+/// away, such as BZPOPMIN.
+pub(crate) trait Block: Debug + Send + Sync {
+    fn keys(&self) -> &[String];
+    /// How long to wait before giving up, or `None` to wait forever.
+    fn timeout(&self) -> Option<Duration>;
+    /// Serves the command from `key`, returning `None` if `key` has nothing to offer yet.
+    fn serve(&self, db: &mut dyn Database, key: &str) -> Result<Option<Frame>, RedisError>;
+
+    /// Runs the command as soon as it is received, returning `None` if it has to block.
+    fn execute(&self, db: &mut dyn Database) -> Result<Option<Frame>, RedisError> {
+        for key in self.keys() {
+            if let Some(reply) = self.serve(db, key)? {
+                return Ok(Some(reply));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the command to block with when it depends on the state at the time it blocks,
+    /// such as XREAD with `$`.
+    fn resolve(&self, _db: &mut dyn Database) -> Option<Arc<dyn Block>> {
+        None
+    }
+}
 
 #[derive(Debug)]
 pub(crate) enum Command {
@@ -25,23 +118,334 @@ pub(crate) enum Command {
     Info(Info),
     Replconf(Replconf),
     Psync(Psync),
-    Unknown,
+    Hash(HashCommand),
+    Sets(SetCommand),
+    SortedSet(SortedSetCommand),
+    Stream(StreamCommand),
+    ConsumerGroup(ConsumerGroupCommand),
+    Bitmap(BitmapCommand),
+    HyperLogLog(HyperLogLogCommand),
+    Geo(GeoCommand),
+    Json(JsonCommand),
+    Bloom(BloomCommand),
+    Cuckoo(CuckooCommand),
+    CountMin(CountMinCommand),
+    TimeSeries(TimeSeriesCommand),
+    VectorSet(VectorSetCommand),
+    Search(SearchCommand),
+    Throttle(Throttle),
+    Object(Object),
+    Memory(Memory),
+    Config(ConfigCommand),
+    Auth(Auth),
+    Acl(AclCommand),
+    Flushdb(Flushdb),
+    Del(Del),
+    Module(ModuleCall),
+    Subscription(Subscription),
+    PubSub(PubSubCommand),
+    Blocking(Arc<dyn Block>),
+    Multi,
+    Exec,
+    Discard,
+    Watch(Vec<String>),
+    Unwatch,
+    Quit,
+    Error(RedisError),
 }
 
 impl Command {
-    pub(crate) fn parse(frame: &Frame) -> Self {
+    /// Parses a built-in command, or else one of the commands registered by `modules`.
+    pub(crate) fn parse(frame: &Frame, modules: &Registry) -> Self {
         let args = frame.to_vec();
         let cmd = args.first().map(|s| s.to_lowercase()).unwrap_or_default();
 
         match cmd.as_str() {
-            "ping" => Command::Ping(Ping::new(None)),
+            "ping" if args.len() > 2 => Command::Error(RedisError::WrongArity(cmd)),
+            "ping" => Command::Ping(Ping::new(args.get(1).map(String::as_str))),
             "echo" => Command::Echo(Echo::new(args)),
-            "get" => Get::new(args).map_or(Command::Unknown, Command::Get),
-            "set" => Set::new(args).map_or(Command::Unknown, Command::Set),
+            "get" => {
+                Get::new(args).map_or(Command::Error(RedisError::WrongArity(cmd)), Command::Get)
+            }
+            "set" => Set::new(args, frame.to_raw_vec())
+                .map_or(Command::Error(RedisError::WrongArity(cmd)), Command::Set),
             "info" => Command::Info(Info::new()),
             "replconf" => Command::Replconf(Replconf::new(args)),
             "psync" => Command::Psync(Psync::new(args)),
-            _ => Command::Unknown,
+            "hset" | "hmset" | "hsetnx" | "hget" | "hmget" | "hgetall" | "hdel" | "hexists"
+            | "hincrby" | "hincrbyfloat" | "hkeys" | "hvals" | "hlen" | "hstrlen"
+            | "hrandfield" | "hscan" | "hexpire" | "hpexpire" | "hexpireat" | "hpexpireat"
+            | "httl" | "hpttl" | "hexpiretime" | "hpexpiretime" | "hpersist" | "hgetex" => {
+                HashCommand::parse(&cmd, args).map_or_else(Command::Error, Command::Hash)
+            }
+            "sadd" | "srem" | "smembers" | "sismember" | "smismember" | "scard" | "spop"
+            | "srandmember" | "smove" | "sinter" | "sunion" | "sdiff" | "sinterstore"
+            | "sunionstore" | "sdiffstore" | "sintercard" | "sscan" => {
+                SetCommand::parse(&cmd, args).map_or_else(Command::Error, Command::Sets)
+            }
+            "zadd" | "zincrby" | "zrem" | "zcard" | "zscore" | "zmscore" | "zrank" | "zrevrank"
+            | "zcount" | "zrange" | "zpopmin" | "zpopmax" | "zrandmember" | "zscan"
+            | "zunionstore" | "zinterstore" | "zdiffstore" | "zmpop" => {
+                SortedSetCommand::parse(&cmd, args).map_or_else(Command::Error, Command::SortedSet)
+            }
+            "xadd" | "xrange" | "xrevrange" | "xlen" | "xdel" | "xtrim" | "xinfo" | "xsetid" => {
+                StreamCommand::parse(&cmd, args).map_or_else(Command::Error, Command::Stream)
+            }
+            "xread" => stream::parse_xread(args).unwrap_or_else(Command::Error),
+            "xgroup" | "xack" | "xpending" | "xclaim" | "xautoclaim" => {
+                ConsumerGroupCommand::parse(&cmd, args)
+                    .map_or_else(Command::Error, Command::ConsumerGroup)
+            }
+            "xreadgroup" => consumer_group::parse_xreadgroup(args).unwrap_or_else(Command::Error),
+            "setbit" | "getbit" | "bitcount" | "bitpos" | "bitop" | "bitfield" | "bitfield_ro" => {
+                BitmapCommand::parse(&cmd, args).map_or_else(Command::Error, Command::Bitmap)
+            }
+            "pfadd" | "pfcount" | "pfmerge" => HyperLogLogCommand::parse(&cmd, args)
+                .map_or_else(Command::Error, Command::HyperLogLog),
+            "geoadd" | "geopos" | "geodist" | "geohash" | "geosearch" | "geosearchstore" => {
+                GeoCommand::parse(&cmd, args).map_or_else(Command::Error, Command::Geo)
+            }
+            "json.set" | "json.get" | "json.del" | "json.forget" | "json.type"
+            | "json.numincrby" | "json.arrappend" | "json.strappend" | "json.mget" => {
+                JsonCommand::parse(&cmd, args).map_or_else(Command::Error, Command::Json)
+            }
+            "bf.reserve" | "bf.add" | "bf.madd" | "bf.exists" | "bf.info" => {
+                BloomCommand::parse(&cmd, args).map_or_else(Command::Error, Command::Bloom)
+            }
+            "cf.add" | "cf.del" | "cf.exists" => {
+                CuckooCommand::parse(&cmd, args).map_or_else(Command::Error, Command::Cuckoo)
+            }
+            "cms.initbydim" | "cms.incrby" | "cms.query" | "cms.merge" => {
+                CountMinCommand::parse(&cmd, args).map_or_else(Command::Error, Command::CountMin)
+            }
+            "ts.create" | "ts.add" | "ts.madd" | "ts.range" | "ts.revrange" | "ts.mrange"
+            | "ts.createrule" | "ts.deleterule" => TimeSeriesCommand::parse(&cmd, args)
+                .map_or_else(Command::Error, Command::TimeSeries),
+            "vadd" | "vsim" | "vrem" | "vcard" | "vdim" | "vemb" => {
+                VectorSetCommand::parse(&cmd, args, frame.to_raw_vec())
+                    .map_or_else(Command::Error, Command::VectorSet)
+            }
+            "ft.create" | "ft.search" | "ft.dropindex" => {
+                SearchCommand::parse(&cmd, args).map_or_else(Command::Error, Command::Search)
+            }
+            "object" => Object::new(args).map_or_else(Command::Error, Command::Object),
+            "memory" => Memory::new(args).map_or_else(Command::Error, Command::Memory),
+            "config" => ConfigCommand::parse(args).map_or_else(Command::Error, Command::Config),
+            "auth" => Auth::new(args).map_or_else(Command::Error, Command::Auth),
+            "acl" => AclCommand::parse(args).map_or_else(Command::Error, Command::Acl),
+            "subscribe" | "psubscribe" | "unsubscribe" | "punsubscribe" => {
+                Subscription::new(&cmd, args).map_or_else(Command::Error, Command::Subscription)
+            }
+            "publish" | "pubsub" => {
+                PubSubCommand::parse(&cmd, args).map_or_else(Command::Error, Command::PubSub)
+            }
+            "flushdb" => Flushdb::new(args).map_or_else(Command::Error, Command::Flushdb),
+            "del" => Del::new(args).map_or_else(Command::Error, Command::Del),
+            "cl.throttle" => Throttle::new(args).map_or_else(Command::Error, Command::Throttle),
+            "bzpopmin" | "bzpopmax" | "bzmpop" => {
+                zset::parse_blocking(&cmd, args).map_or_else(Command::Error, Command::Blocking)
+            }
+            "multi" | "exec" | "discard" if args.len() != 1 => {
+                Command::Error(RedisError::WrongArity(cmd))
+            }
+            "multi" => Command::Multi,
+            "exec" => Command::Exec,
+            "discard" => Command::Discard,
+            "watch" => match check_arity(&args, 2) {
+                Ok(()) => Command::Watch(args[1..].to_vec()),
+                Err(err) => Command::Error(err),
+            },
+            "unwatch" if args.len() != 1 => Command::Error(RedisError::WrongArity(cmd)),
+            "unwatch" => Command::Unwatch,
+            "quit" => Command::Quit,
+            _ => match modules.command(&cmd) {
+                Some(command) => {
+                    ModuleCall::new(command, args).map_or_else(Command::Error, Command::Module)
+                }
+                None => Command::Error(RedisError::UnknownCommand(cmd)),
+            },
+        }
+    }
+
+    /// Returns the command as one that runs entirely under the database lock, or `None` for
+    /// commands that need more than the database, such as INFO, PUBLISH, ACL or blocking
+    /// commands.
+    pub(crate) fn as_execute(&self) -> Option<&dyn Execute> {
+        let cmd: &dyn Execute = match self {
+            Command::Ping(cmd) => cmd,
+            Command::Echo(cmd) => cmd,
+            Command::Get(cmd) => cmd,
+            Command::Set(cmd) => cmd,
+            Command::Hash(cmd) => cmd,
+            Command::Sets(cmd) => cmd,
+            Command::SortedSet(cmd) => cmd,
+            Command::Stream(cmd) => cmd,
+            Command::ConsumerGroup(cmd) => cmd,
+            Command::Bitmap(cmd) => cmd,
+            Command::HyperLogLog(cmd) => cmd,
+            Command::Geo(cmd) => cmd,
+            Command::Json(cmd) => cmd,
+            Command::Bloom(cmd) => cmd,
+            Command::Cuckoo(cmd) => cmd,
+            Command::CountMin(cmd) => cmd,
+            Command::TimeSeries(cmd) => cmd,
+            Command::VectorSet(cmd) => cmd,
+            Command::Search(cmd) => cmd,
+            Command::Throttle(cmd) => cmd,
+            Command::Object(cmd) => cmd,
+            Command::Memory(cmd) => cmd,
+            Command::Flushdb(cmd) => cmd,
+            Command::Del(cmd) => cmd,
+            Command::Module(cmd) => cmd,
+            Command::Info(_)
+            | Command::Config(_)
+            | Command::Auth(_)
+            | Command::Acl(_)
+            | Command::Subscription(_)
+            | Command::PubSub(_)
+            | Command::Replconf(_)
+            | Command::Psync(_)
+            | Command::Blocking(_)
+            | Command::Multi
+            | Command::Exec
+            | Command::Discard
+            | Command::Watch(_)
+            | Command::Unwatch
+            | Command::Quit
+            | Command::Error(_) => return None,
+        };
+
+        Some(cmd)
+    }
+
+    /// What the command accesses given its arguments, which ACL rules are checked against,
+    /// or `None` for AUTH, commands that failed to parse and commands missing from the table.
+    pub(crate) fn access<'a>(&self, args: &'a [String]) -> Option<Access<'a>> {
+        match self {
+            Command::Auth(_) | Command::Error(_) => None,
+            Command::Module(call) => Some(call.access(args)),
+            _ => Access::of(args),
+        }
+    }
+}
+
+/// Returns an arity error unless `args`, including the command name, has at least `min`
+/// elements.
+pub(crate) fn check_arity(args: &[String], min: usize) -> Result<(), RedisError> {
+    if args.len() < min {
+        let name = args.first().map(|s| s.to_lowercase()).unwrap_or_default();
+        return Err(RedisError::WrongArity(name));
+    }
+
+    Ok(())
+}
+
+/// Parses a blocking timeout given in seconds, where zero means waiting forever.
+pub(crate) fn parse_timeout(s: &str) -> Result<Option<Duration>, RedisError> {
+    let timeout = parse_float(s)
+        .ok()
+        .filter(|t| t.is_finite())
+        .ok_or(RedisError::Custom(String::from(
+            "timeout is not a float or out of range",
+        )))?;
+
+    match timeout {
+        t if t < 0.0 => Err(RedisError::Custom(String::from("timeout is negative"))),
+        0.0 => Ok(None),
+        t => Ok(Some(Duration::from_secs_f64(t))),
+    }
+}
+
+/// Deletes `key` if the collection stored there has become empty.
+pub(crate) fn remove_if_empty(db: &mut dyn Database, key: &str) {
+    if db.get_value(key).is_some_and(|value| value.is_empty()) {
+        db.remove(key);
+        db.notify(notify::GENERIC, "del", key);
+    }
+}
+
+/// Options shared by the `*SCAN` family: `cursor [MATCH pattern] [COUNT count]`.
+#[derive(Debug)]
+pub(crate) struct ScanOptions {
+    pub(crate) cursor: u64,
+    pub(crate) pattern: Option<String>,
+    pub(crate) count: usize,
+}
+
+impl ScanOptions {
+    pub(crate) fn parse(args: &[String]) -> Result<Self, RedisError> {
+        let cursor = args
+            .first()
+            .and_then(|s| s.parse::<u64>().ok())
+            .ok_or(RedisError::Custom(String::from("invalid cursor")))?;
+
+        let mut options = ScanOptions {
+            cursor,
+            pattern: None,
+            count: 10,
+        };
+
+        let mut rest = args[1..].iter();
+        while let Some(opt) = rest.next() {
+            let value = rest.next().ok_or(RedisError::Syntax)?;
+            match opt.to_lowercase().as_str() {
+                "match" => options.pattern = Some(value.clone()),
+                "count" => {
+                    options.count = match parse_int(value)? {
+                        n if n < 1 => return Err(RedisError::Syntax),
+                        n => n as usize,
+                    }
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(options)
+    }
+
+    pub(crate) fn matches(&self, item: &str) -> bool {
+        match &self.pattern {
+            Some(pattern) => glob::matches(pattern, item),
+            None => true,
+        }
+    }
+}
+
+pub(crate) fn scan_reply(cursor: u64, items: Vec<String>) -> Frame {
+    Frame::Array(vec![
+        Frame::BulkString(cursor.to_string()),
+        Frame::Arrays(items),
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::{table::Access, Command};
+    use crate::{error::RedisError, frame::Frame, module::Registry};
+
+    /// Every command `Command::parse` knows must have a table entry, or ACL rules couldn't be
+    /// checked against it.
+    #[test]
+    fn test_every_command_has_a_table_entry() {
+        let source = include_str!("mod.rs");
+        let start = source.find("fn parse(frame").unwrap();
+        let end = start + source[start..].find("fn as_execute").unwrap();
+        let names: Vec<&str> = source[start..end].split('"').skip(1).step_by(2).collect();
+        assert!(names.contains(&"get") && names.contains(&"vsim"));
+
+        for name in names {
+            let frame = Frame::Arrays(vec![name.to_owned()]);
+            assert!(
+                !matches!(
+                    Command::parse(&frame, &Registry::default()),
+                    Command::Error(RedisError::UnknownCommand(_))
+                ),
+                "{name} is not a command"
+            );
+            assert!(
+                Access::of(&[name.to_owned()]).is_some(),
+                "{name} has no table entry"
+            );
         }
     }
 }
@@ -0,0 +1,56 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    cmd::{table::Access, Execute},
+    db::Database,
+    error::RedisError,
+    frame::Frame,
+    module::ModuleCommand,
+};
+
+/// A call to a command registered by a module, with its arguments.
+pub(crate) struct ModuleCall {
+    command: Arc<dyn ModuleCommand>,
+    args: Vec<String>,
+}
+
+impl ModuleCall {
+    pub(crate) fn new(
+        command: Arc<dyn ModuleCommand>,
+        args: Vec<String>,
+    ) -> Result<Self, RedisError> {
+        let arity = command.arity();
+        let valid = match arity {
+            arity if arity > 0 => args.len() == arity as usize,
+            arity => args.len() >= arity.unsigned_abs() as usize,
+        };
+        if !valid {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(ModuleCall { command, args })
+    }
+
+    pub(crate) fn flags(&self) -> u32 {
+        self.command.flags()
+    }
+
+    pub(crate) fn access<'a>(&self, args: &'a [String]) -> Access<'a> {
+        Access::of_module(&*self.command, args)
+    }
+}
+
+impl fmt::Debug for ModuleCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModuleCall")
+            .field("command", &self.command.name())
+            .field("args", &self.args)
+            .finish()
+    }
+}
+
+impl Execute for ModuleCall {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        self.command.execute(db, &self.args)
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{
+    cmd::{check_arity, Execute},
+    db::Database,
+    error::RedisError,
+    frame::Frame,
+};
+
+#[derive(Debug)]
+enum ObjectSubcommand {
+    Encoding,
+}
+
+/// `OBJECT ENCODING key`
+#[derive(Debug)]
+pub(crate) struct Object {
+    key: String,
+    subcommand: ObjectSubcommand,
+}
+
+impl Object {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let subcommand = match args[1].to_lowercase().as_str() {
+            "encoding" if args.len() == 3 => ObjectSubcommand::Encoding,
+            "encoding" => return Err(RedisError::WrongArity(String::from("object|encoding"))),
+            _ => {
+                return Err(RedisError::Custom(format!(
+                    "unknown subcommand '{}'. Try OBJECT HELP.",
+                    args[1]
+                )))
+            }
+        };
+
+        Ok(Object {
+            key: args[2].clone(),
+            subcommand,
+        })
+    }
+}
+
+impl Execute for Object {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(value) = db.get_value(&self.key) else {
+            return Ok(Frame::Null);
+        };
+
+        match self.subcommand {
+            ObjectSubcommand::Encoding => Ok(Frame::BulkString(value.encoding().to_owned())),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Error;
 
-use crate::{connection::Connection, frame::Frame};
+use crate::{cmd::Execute, connection::Connection, db::Database, error::RedisError, frame::Frame};
 
 #[derive(Debug)]
 pub struct Ping {
@@ -15,13 +15,28 @@ impl Ping {
     }
 
     pub(crate) async fn apply(&self, conn: &mut Connection) -> Result<(), Error> {
-        let frame = Frame::SimpleString(String::from("PONG"));
-
-        conn.write_frame(&frame).await?;
+        conn.write_frame(&self.reply()).await?;
 
         Ok(())
     }
 
+    /// PONG, or the message when there is one.
+    fn reply(&self) -> Frame {
+        match &self.msg {
+            Some(msg) => Frame::BulkString(msg.clone()),
+            None => Frame::SimpleString(String::from("PONG")),
+        }
+    }
+
+    /// The reply to a subscribed client, which is always `pong` and the possibly empty
+    /// message.
+    pub(crate) fn subscribed_reply(&self) -> Frame {
+        Frame::Array(vec![
+            Frame::BulkString(String::from("pong")),
+            Frame::BulkString(self.msg.clone().unwrap_or_default()),
+        ])
+    }
+
     pub(crate) async fn send(&self, conn: &mut Connection) -> Result<(), Error> {
         if let Some(msg) = self.msg.clone() {
             let frame = Frame::Arrays(vec![msg]);
@@ -32,3 +47,9 @@ impl Ping {
         Ok(())
     }
 }
+
+impl Execute for Ping {
+    fn execute(&self, _db: &mut dyn Database) -> Result<Frame, RedisError> {
+        Ok(self.reply())
+    }
+}
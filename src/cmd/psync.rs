@@ -1,6 +1,6 @@
 use anyhow::Error;
 
-use crate::{connection::Connection, frame::Frame, replication::Replication, util::hex};
+use crate::{connection::Connection, frame::Frame, replication::Replication};
 
 #[derive(Debug)]
 pub(crate) struct Psync {
@@ -16,6 +16,7 @@ impl Psync {
         &self,
         conn: &mut Connection,
         repl: &Replication,
+        snapshot: Vec<u8>,
     ) -> Result<(), Error> {
         match (self.args.get(1), self.args.get(2)) {
             (Some(a), Some(b)) if a == "?" && b == "-1" => {
@@ -23,7 +24,7 @@ impl Psync {
 
                 conn.write_frame(&frame).await?;
 
-                let frame = Frame::BulkBytes(hex::decode("524544495330303131fa0972656469732d76657205372e322e30fa0a72656469732d62697473c040fa056374696d65c26d08bc65fa08757365642d6d656dc2b0c41000fa08616f662d62617365c000fff06e3bfec0ff5aa2"));
+                let frame = Frame::BulkBytes(snapshot);
 
                 conn.write_frame(&frame).await?;
             }
@@ -0,0 +1,137 @@
+use crate::{
+    cmd::check_arity,
+    error::RedisError,
+    frame::Frame,
+    pubsub::{Kind, PubSub},
+};
+
+/// `SUBSCRIBE`, `PSUBSCRIBE`, `UNSUBSCRIBE` and `PUNSUBSCRIBE`, which change what the
+/// connection is subscribed to.
+#[derive(Debug)]
+pub(crate) struct Subscription {
+    kind: Kind,
+    subscribe: bool,
+    names: Vec<String>,
+}
+
+impl Subscription {
+    pub(crate) fn new(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        let (kind, subscribe) = match cmd {
+            "subscribe" => (Kind::Channel, true),
+            "psubscribe" => (Kind::Pattern, true),
+            "unsubscribe" => (Kind::Channel, false),
+            "punsubscribe" => (Kind::Pattern, false),
+            _ => return Err(RedisError::UnknownCommand(cmd.to_owned())),
+        };
+
+        if subscribe {
+            check_arity(&args, 2)?;
+        }
+
+        Ok(Subscription {
+            kind,
+            subscribe,
+            names: args[1..].to_vec(),
+        })
+    }
+
+    /// Applies the command for client `id`, returning one reply per channel or pattern.
+    /// Unsubscribing without names removes every subscription of the kind.
+    pub(crate) fn apply(&self, pubsub: &mut PubSub, id: u64) -> Vec<Frame> {
+        let action = match (self.kind, self.subscribe) {
+            (Kind::Channel, true) => "subscribe",
+            (Kind::Pattern, true) => "psubscribe",
+            (Kind::Channel, false) => "unsubscribe",
+            (Kind::Pattern, false) => "punsubscribe",
+        };
+
+        let names = match self.names.is_empty() {
+            true => pubsub.subscriptions(id, self.kind),
+            false => self.names.clone(),
+        };
+
+        if names.is_empty() {
+            let count = pubsub.count(id) as i64;
+            return vec![reply(action, Frame::Null, count)];
+        }
+
+        names
+            .into_iter()
+            .map(|name| {
+                let count = match self.subscribe {
+                    true => pubsub.subscribe(id, self.kind, &name),
+                    false => pubsub.unsubscribe(id, self.kind, &name),
+                };
+
+                reply(action, Frame::BulkString(name), count as i64)
+            })
+            .collect()
+    }
+}
+
+fn reply(action: &str, name: Frame, count: i64) -> Frame {
+    Frame::Array(vec![
+        Frame::BulkString(action.to_owned()),
+        name,
+        Frame::Integer(count),
+    ])
+}
+
+/// `PUBLISH` and the `PUBSUB` introspection subcommands, which only need the registry.
+#[derive(Debug)]
+pub(crate) enum PubSubCommand {
+    Publish { channel: String, message: String },
+    Channels(Option<String>),
+    Numsub(Vec<String>),
+    Numpat,
+}
+
+impl PubSubCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        if cmd == "publish" {
+            if args.len() != 3 {
+                return Err(RedisError::WrongArity(cmd.to_owned()));
+            }
+
+            return Ok(PubSubCommand::Publish {
+                channel: args[1].clone(),
+                message: args[2].clone(),
+            });
+        }
+
+        check_arity(&args, 2)?;
+
+        let subcommand = args[1].to_lowercase();
+        match subcommand.as_str() {
+            "channels" if args.len() <= 3 => Ok(PubSubCommand::Channels(args.get(2).cloned())),
+            "numsub" => Ok(PubSubCommand::Numsub(args[2..].to_vec())),
+            "numpat" if args.len() == 2 => Ok(PubSubCommand::Numpat),
+            "channels" | "numpat" => Err(RedisError::WrongArity(format!("pubsub|{subcommand}"))),
+            _ => Err(RedisError::Custom(format!(
+                "unknown subcommand '{}'. Try PUBSUB HELP.",
+                args[1]
+            ))),
+        }
+    }
+
+    pub(crate) fn execute(&self, pubsub: &mut PubSub) -> Frame {
+        match self {
+            PubSubCommand::Publish { channel, message } => {
+                Frame::Integer(pubsub.publish(channel, message) as i64)
+            }
+            PubSubCommand::Channels(pattern) => Frame::Arrays(pubsub.channels(pattern.as_deref())),
+            PubSubCommand::Numsub(channels) => Frame::Array(
+                channels
+                    .iter()
+                    .flat_map(|channel| {
+                        [
+                            Frame::BulkString(channel.clone()),
+                            Frame::Integer(pubsub.numsub(channel) as i64),
+                        ]
+                    })
+                    .collect(),
+            ),
+            PubSubCommand::Numpat => Frame::Integer(pubsub.numpat() as i64),
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use std::cmp::Ordering;
+
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{
+        search::{self, Field, FieldType, Index, Query},
+        Database,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::num::parse_int,
+};
+
+#[derive(Debug)]
+pub(crate) enum SearchCommand {
+    Create(FtCreate),
+    Search(FtSearch),
+    DropIndex(FtDropIndex),
+}
+
+impl SearchCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "ft.create" => FtCreate::new(args).map(SearchCommand::Create),
+            "ft.search" => FtSearch::new(args).map(SearchCommand::Search),
+            "ft.dropindex" => FtDropIndex::new(args).map(SearchCommand::DropIndex),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for SearchCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            SearchCommand::Create(cmd) => cmd.execute(db),
+            SearchCommand::Search(cmd) => cmd.execute(db),
+            SearchCommand::DropIndex(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn no_such_index(name: &str) -> RedisError {
+    RedisError::Custom(format!("{name}: no such index"))
+}
+
+/// Parses a count followed by as many arguments, as in `PREFIX count prefix ...`.
+fn parse_counted<'a>(args: &'a [String], index: &mut usize) -> Result<&'a [String], RedisError> {
+    let count = args
+        .get(*index)
+        .map(|s| parse_int(s))
+        .transpose()?
+        .and_then(|n| usize::try_from(n).ok())
+        .ok_or(RedisError::Syntax)?;
+    let items = args
+        .get(*index + 1..*index + 1 + count)
+        .ok_or(RedisError::Syntax)?;
+    *index += 1 + count;
+    Ok(items)
+}
+
+/// `FT.CREATE index [ON HASH] [PREFIX count prefix ...] SCHEMA field [AS alias]
+/// TEXT | TAG [SEPARATOR sep] [CASESENSITIVE] | NUMERIC [SORTABLE] ...`
+#[derive(Debug)]
+pub(crate) struct FtCreate {
+    name: String,
+    prefixes: Vec<String>,
+    fields: Vec<Field>,
+}
+
+impl FtCreate {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+
+        let mut prefixes = Vec::new();
+        let mut index = 2;
+        loop {
+            let opt = args.get(index).ok_or(RedisError::Syntax)?;
+            match opt.to_lowercase().as_str() {
+                "on" => {
+                    let kind = args.get(index + 1).ok_or(RedisError::Syntax)?;
+                    if !kind.eq_ignore_ascii_case("hash") {
+                        return Err(RedisError::Custom(String::from(
+                            "Only HASH indexes are supported",
+                        )));
+                    }
+                    index += 2;
+                }
+                "prefix" => {
+                    index += 1;
+                    prefixes = parse_counted(&args, &mut index)?.to_vec();
+                }
+                "schema" => {
+                    index += 1;
+                    break;
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        let mut fields: Vec<Field> = Vec::new();
+        while let Some(name) = args.get(index) {
+            let mut alias = name.clone();
+            index += 1;
+            if args
+                .get(index)
+                .is_some_and(|s| s.eq_ignore_ascii_case("as"))
+            {
+                alias = args.get(index + 1).ok_or(RedisError::Syntax)?.clone();
+                index += 2;
+            }
+
+            let invalid = || RedisError::Custom(format!("Invalid field type for field `{name}`"));
+            let mut kind = match args.get(index).map(|s| s.to_lowercase()).as_deref() {
+                Some("text") => FieldType::Text,
+                Some("tag") => FieldType::Tag {
+                    separator: ',',
+                    case_sensitive: false,
+                },
+                Some("numeric") => FieldType::Numeric,
+                _ => return Err(invalid()),
+            };
+            index += 1;
+
+            // Every field can be sorted on, so SORTABLE and the scoring options are accepted
+            // and ignored.
+            while let Some(opt) = args.get(index) {
+                match (opt.to_lowercase().as_str(), &mut kind) {
+                    ("sortable" | "nostem", _) => index += 1,
+                    ("weight", FieldType::Text) => index += 2,
+                    ("separator", FieldType::Tag { separator, .. }) => {
+                        let sep = args.get(index + 1).ok_or(RedisError::Syntax)?;
+                        let mut chars = sep.chars();
+                        *separator = match (chars.next(), chars.next()) {
+                            (Some(c), None) => c,
+                            _ => return Err(RedisError::Syntax),
+                        };
+                        index += 2;
+                    }
+                    ("casesensitive", FieldType::Tag { case_sensitive, .. }) => {
+                        *case_sensitive = true;
+                        index += 1;
+                    }
+                    _ => break,
+                }
+            }
+
+            if fields.iter().any(|f| f.alias == alias) {
+                return Err(RedisError::Custom(format!(
+                    "Duplicate field in schema - {alias}"
+                )));
+            }
+            fields.push(Field {
+                name: name.clone(),
+                alias,
+                kind,
+            });
+        }
+
+        if fields.is_empty() {
+            return Err(RedisError::Custom(String::from(
+                "Fields arguments are missing",
+            )));
+        }
+
+        Ok(FtCreate {
+            name: args[1].clone(),
+            prefixes,
+            fields,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let index = Index::new(self.prefixes.clone(), self.fields.clone());
+        if !db.create_index(&self.name, index) {
+            return Err(RedisError::Custom(String::from("Index already exists")));
+        }
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
+
+/// The value of a document that SORTBY orders it by.
+enum SortKey {
+    Number(f64),
+    Text(String),
+}
+
+impl SortKey {
+    fn compare(&self, other: &SortKey) -> Ordering {
+        match (self, other) {
+            (SortKey::Number(a), SortKey::Number(b)) => a.total_cmp(b),
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+            // The values of a field are all of the same kind.
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// `FT.SEARCH index query [NOCONTENT] [RETURN count field ...] [SORTBY field [ASC | DESC]]
+/// [LIMIT offset num]`, replying with the number of matches followed by each key and its
+/// fields. Matches are sorted by key unless SORTBY is given.
+#[derive(Debug)]
+pub(crate) struct FtSearch {
+    name: String,
+    query: Query,
+    content: bool,
+    fields: Option<Vec<String>>,
+    sort_by: Option<(String, bool)>,
+    offset: usize,
+    limit: usize,
+}
+
+impl FtSearch {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        let query = Query::parse(&args[2]).map_err(RedisError::Custom)?;
+        let mut search = FtSearch {
+            name: args[1].clone(),
+            query,
+            content: true,
+            fields: None,
+            sort_by: None,
+            offset: 0,
+            limit: 10,
+        };
+
+        let mut index = 3;
+        while let Some(opt) = args.get(index) {
+            match opt.to_lowercase().as_str() {
+                "nocontent" => {
+                    search.content = false;
+                    index += 1;
+                }
+                "return" => {
+                    index += 1;
+                    search.fields = Some(parse_counted(&args, &mut index)?.to_vec());
+                }
+                "sortby" => {
+                    let field = args.get(index + 1).ok_or(RedisError::Syntax)?;
+                    index += 2;
+                    let descending = match args.get(index).map(|s| s.to_lowercase()).as_deref() {
+                        Some("asc") => false,
+                        Some("desc") => true,
+                        _ => {
+                            index -= 1;
+                            false
+                        }
+                    };
+                    index += 1;
+                    search.sort_by = Some((field.trim_start_matches('@').to_owned(), descending));
+                }
+                "limit" => {
+                    let bound = |i: usize| -> Result<usize, RedisError> {
+                        let n = parse_int(args.get(i).ok_or(RedisError::Syntax)?)?;
+                        usize::try_from(n).map_err(|_| RedisError::Syntax)
+                    };
+                    search.offset = bound(index + 1)?;
+                    search.limit = bound(index + 2)?;
+                    index += 3;
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(search)
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let index = db.index(&self.name).ok_or(no_such_index(&self.name))?;
+        let mut keys: Vec<_> = index
+            .search(&self.query)
+            .map_err(RedisError::Custom)?
+            .into_iter()
+            .collect();
+
+        if let Some((field, descending)) = &self.sort_by {
+            let kind = &index
+                .field(field)
+                .ok_or(RedisError::Custom(format!(
+                    "Property `{field}` not loaded nor in schema"
+                )))?
+                .kind;
+
+            // Documents without the field, or without a number in a numeric field, come last
+            // in both directions.
+            let sort_key = |key: &String| {
+                let value = index.value(key, field)?;
+                match kind {
+                    FieldType::Numeric => search::parse_number(value).map(SortKey::Number),
+                    _ => Some(SortKey::Text(value.to_owned())),
+                }
+            };
+            let mut sorted: Vec<_> = keys.into_iter().map(|k| (sort_key(&k), k)).collect();
+            sorted.sort_by(|(a, _), (b, _)| {
+                let ordering = match (a, b) {
+                    (Some(a), Some(b)) => a.compare(b),
+                    (Some(_), None) => return Ordering::Less,
+                    (None, Some(_)) => return Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                };
+                match descending {
+                    true => ordering.reverse(),
+                    false => ordering,
+                }
+            });
+            keys = sorted.into_iter().map(|(_, k)| k).collect();
+        }
+
+        // Expired keys stay indexed until they are looked up, which removes them.
+        keys.retain(|key| db.get_value(key).is_some());
+
+        let mut reply = vec![Frame::Integer(keys.len() as i64)];
+        for key in keys.into_iter().skip(self.offset).take(self.limit) {
+            let content = match self.content {
+                true => {
+                    let hash = db
+                        .get_value(&key)
+                        .map(|value| value.as_hash_mut())
+                        .transpose()?;
+                    let items: Vec<_> = match (hash, &self.fields) {
+                        (Some(hash), Some(fields)) => fields
+                            .iter()
+                            .filter_map(|f| hash.get(f).map(|v| [f.clone(), v.into_owned()]))
+                            .flatten()
+                            .collect(),
+                        (Some(hash), None) => hash
+                            .iter()
+                            .flat_map(|(f, v)| [f.into_owned(), v.into_owned()])
+                            .collect(),
+                        (None, _) => Vec::new(),
+                    };
+                    Some(Frame::Arrays(items))
+                }
+                false => None,
+            };
+
+            reply.push(Frame::BulkString(key));
+            reply.extend(content);
+        }
+
+        Ok(Frame::Array(reply))
+    }
+}
+
+/// `FT.DROPINDEX index`, which keeps the indexed hashes.
+#[derive(Debug)]
+pub(crate) struct FtDropIndex {
+    name: String,
+}
+
+impl FtDropIndex {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+        if args.len() != 2 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(FtDropIndex {
+            name: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        if !db.drop_index(&self.name) {
+            return Err(no_such_index(&self.name));
+        }
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{cmd::Command, db::KeyValueDb, frame::Frame, module::Registry};
+
+    fn run(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        let frame = Frame::Arrays(args.iter().map(|arg| arg.to_string()).collect());
+        let cmd = Command::parse(&frame, &Registry::default());
+        cmd.as_execute()
+            .unwrap()
+            .execute(db)
+            .unwrap_or_else(Frame::from)
+    }
+
+    fn keys(reply: Frame) -> Vec<String> {
+        let Frame::Array(items) = reply else {
+            panic!("unexpected {reply:?}");
+        };
+        items[1..]
+            .iter()
+            .map(|item| match item {
+                Frame::BulkString(key) => key.clone(),
+                item => panic!("unexpected {item:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sortby_numeric_skips_non_numbers() {
+        let mut db = KeyValueDb::new();
+        run(
+            &mut db,
+            &[
+                "FT.CREATE",
+                "idx",
+                "ON",
+                "HASH",
+                "PREFIX",
+                "1",
+                "doc:",
+                "SCHEMA",
+                "n",
+                "NUMERIC",
+            ],
+        );
+        run(&mut db, &["HSET", "doc:1", "n", "2"]);
+        run(&mut db, &["HSET", "doc:2", "n", "abc"]);
+        run(&mut db, &["HSET", "doc:3", "n", "10"]);
+        run(&mut db, &["HSET", "doc:4", "n", "nan"]);
+        run(&mut db, &["HSET", "doc:5", "n", "-1.5"]);
+
+        let search = |db: &mut KeyValueDb, order: &str| {
+            keys(run(
+                db,
+                &["FT.SEARCH", "idx", "*", "NOCONTENT", "SORTBY", "n", order],
+            ))
+        };
+        let (asc, desc) = (search(&mut db, "ASC"), search(&mut db, "DESC"));
+        assert_eq!(vec!["doc:5", "doc:1", "doc:3"], asc[..3]);
+        assert_eq!(vec!["doc:3", "doc:1", "doc:5"], desc[..3]);
+        // Values that are not numbers come last both ways.
+        for keys in [asc, desc] {
+            let mut last = keys[3..].to_vec();
+            last.sort();
+            assert_eq!(vec!["doc:2", "doc:4"], last);
+        }
+
+        // Nor are they in the numeric index.
+        assert_eq!(
+            vec!["doc:5", "doc:1", "doc:3"],
+            keys(run(
+                &mut db,
+                &[
+                    "FT.SEARCH",
+                    "idx",
+                    "@n:[-inf +inf]",
+                    "NOCONTENT",
+                    "SORTBY",
+                    "n"
+                ]
+            ))
+        );
+    }
+}
@@ -1,11 +1,9 @@
-use std::{sync::Arc, time::SystemTime};
-
-use anyhow::Error;
-use tokio::sync::Mutex;
+use std::time::SystemTime;
 
 use crate::{
-    connection::Connection,
-    db::Database,
+    cmd::Execute,
+    db::{notify, Database},
+    error::RedisError,
     frame::Frame,
     util::time::{current_time_with_milliseconds, current_time_with_seconds},
 };
@@ -13,12 +11,14 @@ use crate::{
 #[derive(Debug)]
 pub(crate) struct Set {
     key: String,
-    value: String,
+    value: Vec<u8>,
     exp: Option<SystemTime>,
 }
 
 impl Set {
-    pub(crate) fn new(args: Vec<String>) -> Option<Self> {
+    /// The value is taken from the `raw` arguments, so that binary values such as dense
+    /// HyperLogLogs can be written back as GET returned them.
+    pub(crate) fn new(args: Vec<String>, raw: Vec<Vec<u8>>) -> Option<Self> {
         let exp = {
             let unit = args.get(3).cloned().unwrap_or_default();
             let value = args.get(4).map(|s| s.parse::<u64>().unwrap_or(0));
@@ -30,26 +30,21 @@ impl Set {
             }
         };
 
-        match (args.get(1).cloned(), args.get(2).cloned()) {
+        match (args.get(1).cloned(), raw.into_iter().nth(2)) {
             (Some(key), Some(value)) => Some(Set { key, value, exp }),
             (_, _) => None,
         }
     }
+}
 
-    pub(crate) async fn apply<D>(
-        &self,
-        conn: &mut Connection,
-        db: Arc<Mutex<D>>,
-    ) -> Result<(), Error>
-    where
-        D: Database,
-    {
-        db.lock().await.set(&self.key, &self.value, self.exp);
-
-        let frame = Frame::SimpleString(String::from("OK"));
-
-        conn.write_frame(&frame).await?;
+impl Execute for Set {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        db.set(&self.key, &self.value, self.exp);
+        db.notify(notify::STRING, "set", &self.key);
+        if self.exp.is_some() {
+            db.notify(notify::GENERIC, "expire", &self.key);
+        }
 
-        Ok(())
+        Ok(Frame::SimpleString(String::from("OK")))
     }
 }
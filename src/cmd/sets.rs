@@ -0,0 +1,622 @@
+use std::collections::HashSet;
+
+use crate::{
+    cmd::{check_arity, remove_if_empty, scan_reply, Execute, ScanOptions},
+    db::{notify, set::Set, Data, Database, Value},
+    error::RedisError,
+    frame::Frame,
+    util::{num::parse_int, rand::sample},
+};
+
+#[derive(Debug)]
+pub(crate) enum SetCommand {
+    Sadd(Sadd),
+    Srem(Srem),
+    Smembers(Smembers),
+    Sismember(Sismember),
+    Smismember(Smismember),
+    Scard(Scard),
+    Spop(Spop),
+    Srandmember(Srandmember),
+    Smove(Smove),
+    Algebra(Algebra),
+    Sintercard(Sintercard),
+    Sscan(Sscan),
+}
+
+impl SetCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "sadd" => Sadd::new(args).map(SetCommand::Sadd),
+            "srem" => Srem::new(args).map(SetCommand::Srem),
+            "smembers" => Smembers::new(args).map(SetCommand::Smembers),
+            "sismember" => Sismember::new(args).map(SetCommand::Sismember),
+            "smismember" => Smismember::new(args).map(SetCommand::Smismember),
+            "scard" => Scard::new(args).map(SetCommand::Scard),
+            "spop" => Spop::new(args).map(SetCommand::Spop),
+            "srandmember" => Srandmember::new(args).map(SetCommand::Srandmember),
+            "smove" => Smove::new(args).map(SetCommand::Smove),
+            "sinter" | "sunion" | "sdiff" | "sinterstore" | "sunionstore" | "sdiffstore" => {
+                Algebra::new(args).map(SetCommand::Algebra)
+            }
+            "sintercard" => Sintercard::new(args).map(SetCommand::Sintercard),
+            "sscan" => Sscan::new(args).map(SetCommand::Sscan),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for SetCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            SetCommand::Sadd(cmd) => cmd.execute(db),
+            SetCommand::Srem(cmd) => cmd.execute(db),
+            SetCommand::Smembers(cmd) => cmd.execute(db),
+            SetCommand::Sismember(cmd) => cmd.execute(db),
+            SetCommand::Smismember(cmd) => cmd.execute(db),
+            SetCommand::Scard(cmd) => cmd.execute(db),
+            SetCommand::Spop(cmd) => cmd.execute(db),
+            SetCommand::Srandmember(cmd) => cmd.execute(db),
+            SetCommand::Smove(cmd) => cmd.execute(db),
+            SetCommand::Algebra(cmd) => cmd.execute(db),
+            SetCommand::Sintercard(cmd) => cmd.execute(db),
+            SetCommand::Sscan(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn get_set<'a>(db: &'a mut dyn Database, key: &str) -> Result<Option<&'a mut Set>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_set_mut())
+        .transpose()
+}
+
+fn read_set<'a>(db: &'a mut dyn Database, key: &str) -> Result<Option<&'a mut Set>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_set_mut())
+        .transpose()
+}
+
+fn get_or_create_set<'a>(db: &'a mut dyn Database, key: &str) -> Result<&'a mut Set, RedisError> {
+    db.get_or_insert_with(key, &|| Data::Set(Box::new(Set::new())))
+        .as_set_mut()
+}
+
+#[derive(Debug)]
+pub(crate) struct Sadd {
+    key: String,
+    members: Vec<String>,
+}
+
+impl Sadd {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Sadd {
+            key: args[1].clone(),
+            members: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let thresholds = db.thresholds();
+        let set = get_or_create_set(db, &self.key)?;
+
+        let added = self
+            .members
+            .iter()
+            .filter(|m| set.insert(m, &thresholds))
+            .count();
+
+        if added > 0 {
+            db.touch(&self.key);
+            db.notify(notify::SET, "sadd", &self.key);
+        }
+
+        Ok(Frame::Integer(added as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Srem {
+    key: String,
+    members: Vec<String>,
+}
+
+impl Srem {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Srem {
+            key: args[1].clone(),
+            members: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(set) = get_set(db, &self.key)? else {
+            return Ok(Frame::Integer(0));
+        };
+
+        let removed = self.members.iter().filter(|m| set.remove(m)).count();
+
+        if removed > 0 {
+            db.notify(notify::SET, "srem", &self.key);
+            remove_if_empty(db, &self.key);
+            db.touch(&self.key);
+        }
+
+        Ok(Frame::Integer(removed as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Smembers {
+    key: String,
+}
+
+impl Smembers {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Smembers {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let members = read_set(db, &self.key)?
+            .map(|set| set.members())
+            .unwrap_or_default();
+
+        Ok(Frame::Arrays(members))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Sismember {
+    key: String,
+    member: String,
+}
+
+impl Sismember {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Sismember {
+            key: args[1].clone(),
+            member: args[2].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let exists = read_set(db, &self.key)?.is_some_and(|set| set.contains(&self.member));
+
+        Ok(Frame::Integer(exists as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Smismember {
+    key: String,
+    members: Vec<String>,
+}
+
+impl Smismember {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Smismember {
+            key: args[1].clone(),
+            members: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let set = read_set(db, &self.key)?;
+
+        let exists = self
+            .members
+            .iter()
+            .map(|m| {
+                let exists = set.as_ref().is_some_and(|set| set.contains(m));
+                Frame::Integer(exists as i64)
+            })
+            .collect();
+
+        Ok(Frame::Array(exists))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Scard {
+    key: String,
+}
+
+impl Scard {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Scard {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let len = read_set(db, &self.key)?.map_or(0, |set| set.len());
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Spop {
+    key: String,
+    count: Option<usize>,
+}
+
+impl Spop {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let count = match args.get(2).map(|c| parse_int(c)).transpose()? {
+            Some(c) if c < 0 => {
+                return Err(RedisError::Custom(String::from(
+                    "value is out of range, must be positive",
+                )))
+            }
+            c => c.map(|c| c as usize),
+        };
+
+        if args.len() > 3 {
+            return Err(RedisError::Syntax);
+        }
+
+        Ok(Spop {
+            key: args[1].clone(),
+            count,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(set) = get_set(db, &self.key)? else {
+            return Ok(match self.count {
+                Some(_) => Frame::Arrays(vec![]),
+                None => Frame::Null,
+            });
+        };
+
+        let popped = match self.count {
+            Some(count) => sample(set.members(), count as i64),
+            None => set.random_member().into_iter().collect(),
+        };
+
+        for member in popped.iter() {
+            set.remove(member);
+        }
+
+        if !popped.is_empty() {
+            db.notify(notify::SET, "spop", &self.key);
+            remove_if_empty(db, &self.key);
+            db.touch(&self.key);
+
+            // Replicas remove the same members rather than picking their own at random.
+            db.rewrite(Frame::Arrays(
+                [vec![String::from("SREM"), self.key.clone()], popped.clone()].concat(),
+            ));
+        }
+
+        match self.count {
+            Some(_) => Ok(Frame::Arrays(popped)),
+            None => Ok(popped
+                .into_iter()
+                .next()
+                .map_or(Frame::Null, Frame::BulkString)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Srandmember {
+    key: String,
+    count: Option<i64>,
+}
+
+impl Srandmember {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        if args.len() > 3 {
+            return Err(RedisError::Syntax);
+        }
+
+        Ok(Srandmember {
+            key: args[1].clone(),
+            count: args.get(2).map(|c| parse_int(c)).transpose()?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let set = read_set(db, &self.key)?;
+
+        match self.count {
+            Some(count) => {
+                let members = set
+                    .map(|set| sample(set.members(), count))
+                    .unwrap_or_default();
+
+                Ok(Frame::Arrays(members))
+            }
+            None => Ok(set
+                .and_then(|set| set.random_member())
+                .map_or(Frame::Null, Frame::BulkString)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Smove {
+    source: String,
+    destination: String,
+    member: String,
+}
+
+impl Smove {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Ok(Smove {
+            source: args[1].clone(),
+            destination: args[2].clone(),
+            member: args[3].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        // Check the destination type first so a failed move never loses the member.
+        get_set(db, &self.destination)?;
+
+        let Some(source) = get_set(db, &self.source)? else {
+            return Ok(Frame::Integer(0));
+        };
+
+        if self.source == self.destination {
+            return Ok(Frame::Integer(source.contains(&self.member) as i64));
+        }
+
+        if !source.remove(&self.member) {
+            return Ok(Frame::Integer(0));
+        }
+
+        db.notify(notify::SET, "srem", &self.source);
+        remove_if_empty(db, &self.source);
+        let thresholds = db.thresholds();
+        get_or_create_set(db, &self.destination)?.insert(&self.member, &thresholds);
+        db.notify(notify::SET, "sadd", &self.destination);
+
+        db.touch(&self.source);
+        db.touch(&self.destination);
+
+        Ok(Frame::Integer(1))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+/// Computes `op` over the sets stored at `keys`, treating missing keys as empty sets.
+fn combine(db: &mut dyn Database, op: SetOp, keys: &[String]) -> Result<Vec<String>, RedisError> {
+    let mut sizes = Vec::with_capacity(keys.len());
+    for key in keys {
+        sizes.push(read_set(db, key)?.map_or(0, |set| set.len()));
+    }
+
+    match op {
+        SetOp::Inter => {
+            if sizes.contains(&0) {
+                return Ok(vec![]);
+            }
+
+            // Start from the smallest set so every later step only shrinks the result.
+            let smallest = sizes
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, size)| **size)
+                .map_or(0, |(i, _)| i);
+
+            let mut result = get_set(db, &keys[smallest])?
+                .map(|set| set.members())
+                .unwrap_or_default();
+
+            for (i, key) in keys.iter().enumerate() {
+                if i != smallest {
+                    if let Some(set) = get_set(db, key)? {
+                        result.retain(|m| set.contains(m));
+                    }
+                }
+            }
+
+            Ok(result)
+        }
+        SetOp::Union => {
+            let mut result = HashSet::new();
+            for key in keys {
+                if let Some(set) = get_set(db, key)? {
+                    result.extend(set.members());
+                }
+            }
+
+            Ok(result.into_iter().collect())
+        }
+        SetOp::Diff => {
+            let mut result = get_set(db, &keys[0])?
+                .map(|set| set.members())
+                .unwrap_or_default();
+
+            for key in &keys[1..] {
+                if result.is_empty() {
+                    break;
+                }
+                if let Some(set) = get_set(db, key)? {
+                    result.retain(|m| !set.contains(m));
+                }
+            }
+
+            Ok(result)
+        }
+    }
+}
+
+/// SINTER, SUNION, SDIFF and their `STORE` variants.
+#[derive(Debug)]
+pub(crate) struct Algebra {
+    op: SetOp,
+    destination: Option<String>,
+    keys: Vec<String>,
+}
+
+impl Algebra {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        let cmd = args[0].to_lowercase();
+        let store = cmd.ends_with("store");
+
+        check_arity(&args, if store { 3 } else { 2 })?;
+
+        let op = match &cmd[..5] {
+            "sinte" => SetOp::Inter,
+            "sunio" => SetOp::Union,
+            _ => SetOp::Diff,
+        };
+
+        let (destination, keys) = if store {
+            (Some(args[1].clone()), args[2..].to_vec())
+        } else {
+            (None, args[1..].to_vec())
+        };
+
+        Ok(Algebra {
+            op,
+            destination,
+            keys,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let result = combine(db, self.op, &self.keys)?;
+
+        let Some(destination) = &self.destination else {
+            return Ok(Frame::Arrays(result));
+        };
+
+        let len = result.len();
+
+        if result.is_empty() {
+            if db.remove(destination).is_some() {
+                db.notify(notify::GENERIC, "del", destination);
+            }
+        } else {
+            let set = Set::from_members(result, &db.thresholds());
+            db.insert(destination, Value::from(Data::Set(Box::new(set))));
+
+            let event = match self.op {
+                SetOp::Inter => "sinterstore",
+                SetOp::Union => "sunionstore",
+                SetOp::Diff => "sdiffstore",
+            };
+            db.notify(notify::SET, event, destination);
+        }
+
+        db.touch(destination);
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Sintercard {
+    keys: Vec<String>,
+    limit: usize,
+}
+
+impl Sintercard {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        let numkeys = match parse_int(&args[1])? {
+            n if n <= 0 => {
+                return Err(RedisError::Custom(String::from(
+                    "numkeys should be greater than 0",
+                )))
+            }
+            n => n as usize,
+        };
+
+        let Some(keys) = args.get(2..2 + numkeys) else {
+            return Err(RedisError::Custom(String::from(
+                "Number of keys can't be greater than number of args",
+            )));
+        };
+
+        let limit = match &args[2 + numkeys..] {
+            [] => 0,
+            [opt, limit] if opt.eq_ignore_ascii_case("limit") => match parse_int(limit)? {
+                l if l < 0 => {
+                    return Err(RedisError::Custom(String::from("LIMIT can't be negative")))
+                }
+                l => l as usize,
+            },
+            _ => return Err(RedisError::Syntax),
+        };
+
+        Ok(Sintercard {
+            keys: keys.to_vec(),
+            limit,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let len = combine(db, SetOp::Inter, &self.keys)?.len();
+
+        let len = match self.limit {
+            0 => len,
+            limit => len.min(limit),
+        };
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Sscan {
+    key: String,
+    options: ScanOptions,
+}
+
+impl Sscan {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Sscan {
+            key: args[1].clone(),
+            options: ScanOptions::parse(&args[2..])?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(set) = read_set(db, &self.key)? else {
+            return Ok(scan_reply(0, vec![]));
+        };
+
+        let (cursor, batch) = set.scan(self.options.cursor, self.options.count);
+        let items = batch
+            .into_iter()
+            .filter(|m| self.options.matches(m))
+            .collect();
+
+        Ok(scan_reply(cursor, items))
+    }
+}
@@ -0,0 +1,1274 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    cmd::{check_arity, consumer_group::no_such_group, Block, Command, Execute},
+    db::{
+        notify,
+        stream::{Consumer, ConsumerGroup, Fields, Stream, StreamEntry, StreamId, TrimStrategy},
+        Data, Database,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::{num::parse_int, time::to_unix_millis},
+};
+
+/// Approximate trimming evicts at most this many entries unless LIMIT says otherwise.
+const DEFAULT_TRIM_LIMIT: usize = 100 * 100;
+
+#[derive(Debug)]
+pub(crate) enum StreamCommand {
+    Xadd(Xadd),
+    Xrange(Xrange),
+    Xlen(Xlen),
+    Xdel(Xdel),
+    Xtrim(Xtrim),
+    Xinfo(Xinfo),
+    Xsetid(Xsetid),
+    Xread(Xread),
+}
+
+impl StreamCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "xadd" => Xadd::new(args).map(StreamCommand::Xadd),
+            "xrange" | "xrevrange" => Xrange::new(args).map(StreamCommand::Xrange),
+            "xlen" => Xlen::new(args).map(StreamCommand::Xlen),
+            "xdel" => Xdel::new(args).map(StreamCommand::Xdel),
+            "xtrim" => Xtrim::new(args).map(StreamCommand::Xtrim),
+            "xinfo" => Xinfo::new(args).map(StreamCommand::Xinfo),
+            "xsetid" => Xsetid::new(args).map(StreamCommand::Xsetid),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for StreamCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            StreamCommand::Xadd(cmd) => cmd.execute(db),
+            StreamCommand::Xrange(cmd) => cmd.execute(db),
+            StreamCommand::Xlen(cmd) => cmd.execute(db),
+            StreamCommand::Xdel(cmd) => cmd.execute(db),
+            StreamCommand::Xtrim(cmd) => cmd.execute(db),
+            StreamCommand::Xinfo(cmd) => cmd.execute(db),
+            StreamCommand::Xsetid(cmd) => cmd.execute(db),
+            StreamCommand::Xread(cmd) => {
+                cmd.execute(db).map(|read| read.unwrap_or(Frame::NullArray))
+            }
+        }
+    }
+}
+
+pub(crate) fn get_stream<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut Stream>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_stream_mut())
+        .transpose()
+}
+
+pub(crate) fn read_stream<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut Stream>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_stream_mut())
+        .transpose()
+}
+
+fn get_existing_stream<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<&'a mut Stream, RedisError> {
+    get_stream(db, key)?.ok_or(RedisError::Custom(String::from("no such key")))
+}
+
+fn invalid_id() -> RedisError {
+    RedisError::Custom(String::from(
+        "Invalid stream ID specified as stream command argument",
+    ))
+}
+
+/// Parses a complete ID, where a missing sequence defaults to `default_seq`.
+pub(crate) fn parse_id(s: &str, default_seq: u64) -> Result<StreamId, RedisError> {
+    StreamId::parse(s, default_seq).ok_or_else(invalid_id)
+}
+
+/// Parses the start of an interval: `-`, an ID, or an exclusive `(ID`.
+pub(crate) fn parse_start(s: &str) -> Result<StreamId, RedisError> {
+    match s {
+        "-" => Ok(StreamId::MIN),
+        "+" => Ok(StreamId::MAX),
+        _ => match s.strip_prefix('(') {
+            Some(id) => parse_id(id, 0)?
+                .next()
+                .ok_or(RedisError::Custom(String::from(
+                    "invalid start ID for the interval",
+                ))),
+            None => parse_id(s, 0),
+        },
+    }
+}
+
+/// Parses the end of an interval: `+`, an ID, or an exclusive `(ID`.
+pub(crate) fn parse_end(s: &str) -> Result<StreamId, RedisError> {
+    match s {
+        "-" => Ok(StreamId::MIN),
+        "+" => Ok(StreamId::MAX),
+        _ => match s.strip_prefix('(') {
+            Some(id) => parse_id(id, u64::MAX)?
+                .prev()
+                .ok_or(RedisError::Custom(String::from(
+                    "invalid end ID for the interval",
+                ))),
+            None => parse_id(s, u64::MAX),
+        },
+    }
+}
+
+/// `[id, [field, value, ...]]`.
+pub(crate) fn entry_frame((id, fields): StreamEntry) -> Frame {
+    let fields = fields
+        .into_iter()
+        .flat_map(|(field, value)| [field, value])
+        .collect();
+
+    Frame::Array(vec![
+        Frame::BulkString(id.to_string()),
+        Frame::Arrays(fields),
+    ])
+}
+
+pub(crate) fn entries_frame(entries: Vec<StreamEntry>) -> Frame {
+    Frame::Array(entries.into_iter().map(entry_frame).collect())
+}
+
+/// `MAXLEN|MINID [=|~] threshold [LIMIT count]`, shared by XADD and XTRIM.
+#[derive(Debug)]
+struct Trim {
+    strategy: TrimStrategy,
+    approx: bool,
+    limit: Option<usize>,
+}
+
+impl Trim {
+    /// Parses the trimming options starting at `args[0]`, returning how many arguments were
+    /// consumed, or `None` if `args[0]` isn't a trimming option.
+    fn parse(args: &[String]) -> Result<Option<(Self, usize)>, RedisError> {
+        let Some(kind) = args.first().map(|s| s.to_lowercase()) else {
+            return Ok(None);
+        };
+
+        if kind != "maxlen" && kind != "minid" {
+            return Ok(None);
+        }
+
+        let mut index = 1;
+        let approx = match args.get(index).map(String::as_str) {
+            Some("~") => {
+                index += 1;
+                true
+            }
+            Some("=") => {
+                index += 1;
+                false
+            }
+            _ => false,
+        };
+
+        let threshold = args.get(index).ok_or(RedisError::Syntax)?;
+        index += 1;
+
+        let strategy = if kind == "maxlen" {
+            match parse_int(threshold)? {
+                n if n < 0 => {
+                    return Err(RedisError::Custom(String::from(
+                        "The MAXLEN argument must be >= 0.",
+                    )))
+                }
+                n => TrimStrategy::MaxLen(n as usize),
+            }
+        } else {
+            TrimStrategy::MinId(parse_id(threshold, 0)?)
+        };
+
+        let mut limit = approx.then_some(DEFAULT_TRIM_LIMIT);
+        if args
+            .get(index)
+            .is_some_and(|s| s.eq_ignore_ascii_case("limit"))
+        {
+            let count = args.get(index + 1).ok_or(RedisError::Syntax)?;
+            index += 2;
+
+            if !approx {
+                return Err(RedisError::Custom(String::from(
+                    "syntax error, LIMIT cannot be used without the special ~ option",
+                )));
+            }
+
+            limit = match parse_int(count)? {
+                n if n < 0 => {
+                    return Err(RedisError::Custom(String::from(
+                        "The LIMIT argument must be >= 0.",
+                    )))
+                }
+                0 => None,
+                n => Some(n as usize),
+            };
+        }
+
+        Ok(Some((
+            Trim {
+                strategy,
+                approx,
+                limit,
+            },
+            index,
+        )))
+    }
+
+    fn apply(&self, stream: &mut Stream) -> usize {
+        stream.trim(self.strategy, self.approx, self.limit)
+    }
+
+    /// Exact trimming arguments that leave a replica with the same entries as `stream`, even
+    /// when approximate trimming stopped at a block boundary.
+    fn propagated_args(&self, stream: &Stream) -> Vec<String> {
+        match self.strategy {
+            TrimStrategy::MinId(_) if !stream.is_empty() => {
+                vec![
+                    String::from("MINID"),
+                    String::from("="),
+                    stream.first_id().to_string(),
+                ]
+            }
+            _ => vec![
+                String::from("MAXLEN"),
+                String::from("="),
+                stream.len().to_string(),
+            ],
+        }
+    }
+}
+
+#[derive(Debug)]
+enum IdSpec {
+    /// `*`
+    Auto,
+    /// `ms-*`
+    Millis(u64),
+    Explicit(StreamId),
+}
+
+impl IdSpec {
+    fn parse(s: &str) -> Result<Self, RedisError> {
+        if s == "*" {
+            return Ok(IdSpec::Auto);
+        }
+
+        match s.strip_suffix("-*") {
+            Some(ms) => ms.parse().map(IdSpec::Millis).map_err(|_| invalid_id()),
+            None => parse_id(s, 0).map(IdSpec::Explicit),
+        }
+    }
+
+    /// Resolves the ID of a new entry, which must be greater than `last`.
+    fn resolve(&self, last: StreamId) -> Result<StreamId, RedisError> {
+        let smaller = || {
+            RedisError::Custom(String::from(
+                "The ID specified in XADD is equal or smaller than the target stream top item",
+            ))
+        };
+
+        match self {
+            IdSpec::Auto => {
+                let now = to_unix_millis(SystemTime::now());
+                if now > last.ms {
+                    return Ok(StreamId::new(now, 0));
+                }
+
+                last.next().ok_or(RedisError::Custom(String::from(
+                    "The stream has exhausted the last possible ID, unable to add more items",
+                )))
+            }
+            IdSpec::Millis(ms) if *ms == last.ms => last
+                .seq
+                .checked_add(1)
+                .map(|seq| StreamId::new(*ms, seq))
+                .ok_or_else(smaller),
+            IdSpec::Millis(ms) if *ms > last.ms => Ok(StreamId::new(*ms, 0)),
+            IdSpec::Millis(_) => Err(smaller()),
+            IdSpec::Explicit(id) if *id == StreamId::MIN => Err(RedisError::Custom(String::from(
+                "The ID specified in XADD must be greater than 0-0",
+            ))),
+            IdSpec::Explicit(id) if *id <= last => Err(smaller()),
+            IdSpec::Explicit(id) => Ok(*id),
+        }
+    }
+}
+
+/// `XADD key [NOMKSTREAM] [MAXLEN|MINID [=|~] threshold [LIMIT count]] *|id field value ...`
+#[derive(Debug)]
+pub(crate) struct Xadd {
+    key: String,
+    no_mkstream: bool,
+    trim: Option<Trim>,
+    id: IdSpec,
+    fields: Fields,
+}
+
+impl Xadd {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+
+        let mut no_mkstream = false;
+        let mut trim = None;
+        let mut index = 2;
+
+        loop {
+            if args
+                .get(index)
+                .is_some_and(|s| s.eq_ignore_ascii_case("nomkstream"))
+            {
+                no_mkstream = true;
+                index += 1;
+                continue;
+            }
+
+            match Trim::parse(&args[index..])? {
+                Some((t, consumed)) => {
+                    trim = Some(t);
+                    index += consumed;
+                }
+                None => break,
+            }
+        }
+
+        let id = args.get(index).ok_or(RedisError::Syntax)?;
+        let pairs = &args[index + 1..];
+        if pairs.is_empty() || pairs.len() % 2 == 1 {
+            return Err(RedisError::WrongArity(String::from("xadd")));
+        }
+
+        Ok(Xadd {
+            key: args[1].clone(),
+            no_mkstream,
+            trim,
+            id: IdSpec::parse(id)?,
+            fields: pairs
+                .chunks_exact(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let last = match get_stream(db, &self.key)? {
+            Some(stream) => stream.last_id(),
+            None if self.no_mkstream => return Ok(Frame::Null),
+            None => StreamId::MIN,
+        };
+
+        let id = self.id.resolve(last)?;
+
+        let stream = db
+            .get_or_insert_with(&self.key, &|| Data::Stream(Box::new(Stream::new())))
+            .as_stream_mut()?;
+        stream.append(id, &self.fields);
+
+        // Replicas get the resolved ID, and an exact trim that matches the outcome here.
+        let mut frame = vec![String::from("XADD"), self.key.clone()];
+        let mut trimmed = 0;
+        if let Some(trim) = &self.trim {
+            trimmed = trim.apply(stream);
+            frame.extend(trim.propagated_args(stream));
+        }
+        frame.push(id.to_string());
+        for (field, value) in self.fields.iter() {
+            frame.extend([field.clone(), value.clone()]);
+        }
+
+        db.rewrite(Frame::Arrays(frame));
+        db.touch(&self.key);
+        db.notify(notify::STREAM, "xadd", &self.key);
+        if trimmed > 0 {
+            db.notify(notify::STREAM, "xtrim", &self.key);
+        }
+
+        Ok(Frame::BulkString(id.to_string()))
+    }
+}
+
+/// XRANGE and XREVRANGE.
+#[derive(Debug)]
+pub(crate) struct Xrange {
+    key: String,
+    start: StreamId,
+    end: StreamId,
+    reverse: bool,
+    count: Option<usize>,
+}
+
+impl Xrange {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let reverse = args[0].eq_ignore_ascii_case("xrevrange");
+        let (start, end) = if reverse {
+            (&args[3], &args[2])
+        } else {
+            (&args[2], &args[3])
+        };
+
+        let count = match &args[4..] {
+            [] => None,
+            [opt, count] if opt.eq_ignore_ascii_case("count") => {
+                Some(parse_int(count)?.max(0) as usize)
+            }
+            _ => return Err(RedisError::Syntax),
+        };
+
+        Ok(Xrange {
+            key: args[1].clone(),
+            start: parse_start(start)?,
+            end: parse_end(end)?,
+            reverse,
+            count,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let entries = match read_stream(db, &self.key)? {
+            Some(_) if self.count == Some(0) => vec![],
+            Some(stream) => stream.range(self.start, self.end, self.reverse, self.count),
+            None => vec![],
+        };
+
+        Ok(entries_frame(entries))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Xlen {
+    key: String,
+}
+
+impl Xlen {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Xlen {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let len = read_stream(db, &self.key)?.map_or(0, |stream| stream.len());
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Xdel {
+    key: String,
+    ids: Vec<StreamId>,
+}
+
+impl Xdel {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Xdel {
+            key: args[1].clone(),
+            ids: args[2..]
+                .iter()
+                .map(|id| parse_id(id, 0))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(stream) = get_stream(db, &self.key)? else {
+            return Ok(Frame::Integer(0));
+        };
+
+        let deleted = self.ids.iter().filter(|id| stream.remove(**id)).count();
+
+        if deleted > 0 {
+            db.touch(&self.key);
+            db.notify(notify::STREAM, "xdel", &self.key);
+        }
+
+        Ok(Frame::Integer(deleted as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Xtrim {
+    key: String,
+    trim: Trim,
+}
+
+impl Xtrim {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        match Trim::parse(&args[2..])? {
+            Some((trim, consumed)) if 2 + consumed == args.len() => Ok(Xtrim {
+                key: args[1].clone(),
+                trim,
+            }),
+            _ => Err(RedisError::Syntax),
+        }
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(stream) = get_stream(db, &self.key)? else {
+            return Ok(Frame::Integer(0));
+        };
+
+        let trimmed = self.trim.apply(stream);
+
+        if trimmed > 0 {
+            let frame = [
+                vec![String::from("XTRIM"), self.key.clone()],
+                self.trim.propagated_args(stream),
+            ]
+            .concat();
+
+            db.rewrite(Frame::Arrays(frame));
+            db.touch(&self.key);
+            db.notify(notify::STREAM, "xtrim", &self.key);
+        }
+
+        Ok(Frame::Integer(trimmed as i64))
+    }
+}
+
+#[derive(Debug)]
+enum XinfoSubcommand {
+    /// `STREAM key [FULL [COUNT count]]`, where the count limits the entries of a full reply.
+    Stream {
+        full: Option<usize>,
+    },
+    Groups,
+    Consumers {
+        group: String,
+    },
+}
+
+#[derive(Debug)]
+pub(crate) struct Xinfo {
+    key: String,
+    subcommand: XinfoSubcommand,
+}
+
+impl Xinfo {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        let subcommand = match args[1].to_lowercase().as_str() {
+            "stream" => {
+                let full = match &args[3..] {
+                    [] => None,
+                    [full] if full.eq_ignore_ascii_case("full") => Some(10),
+                    [full, opt, count]
+                        if full.eq_ignore_ascii_case("full")
+                            && opt.eq_ignore_ascii_case("count") =>
+                    {
+                        Some(parse_int(count)?.max(0) as usize)
+                    }
+                    _ => return Err(RedisError::Syntax),
+                };
+                XinfoSubcommand::Stream { full }
+            }
+            "groups" if args.len() == 3 => XinfoSubcommand::Groups,
+            "groups" => return Err(RedisError::WrongArity(String::from("xinfo|groups"))),
+            "consumers" if args.len() == 4 => XinfoSubcommand::Consumers {
+                group: args[3].clone(),
+            },
+            "consumers" => return Err(RedisError::WrongArity(String::from("xinfo|consumers"))),
+            _ => {
+                return Err(RedisError::Custom(format!(
+                    "unknown subcommand '{}'. Try XINFO HELP.",
+                    args[1]
+                )))
+            }
+        };
+
+        Ok(Xinfo {
+            key: args[2].clone(),
+            subcommand,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let stream =
+            read_stream(db, &self.key)?.ok_or(RedisError::Custom(String::from("no such key")))?;
+
+        match &self.subcommand {
+            XinfoSubcommand::Stream { full } => Ok(stream_info(stream, *full)),
+            XinfoSubcommand::Groups => Ok(Frame::Array(
+                stream
+                    .groups()
+                    .iter()
+                    .map(|(name, group)| group_info(stream, name, group))
+                    .collect(),
+            )),
+            XinfoSubcommand::Consumers { group } => {
+                let now = to_unix_millis(SystemTime::now());
+                let consumers = stream
+                    .group(group)
+                    .ok_or_else(|| no_such_group(&self.key, group))?
+                    .consumers()
+                    .iter()
+                    .map(|(name, consumer)| consumer_info(name, consumer, now))
+                    .collect();
+
+                Ok(Frame::Array(consumers))
+            }
+        }
+    }
+}
+
+fn optional_int(n: Option<u64>) -> Frame {
+    n.map_or(Frame::Null, |n| Frame::Integer(n as i64))
+}
+
+fn group_info(stream: &Stream, name: &str, group: &ConsumerGroup) -> Frame {
+    let bulk = |s: &str| Frame::BulkString(s.to_owned());
+
+    Frame::Array(vec![
+        bulk("name"),
+        bulk(name),
+        bulk("consumers"),
+        Frame::Integer(group.consumers().len() as i64),
+        bulk("pending"),
+        Frame::Integer(group.pending().len() as i64),
+        bulk("last-delivered-id"),
+        bulk(&group.last_id.to_string()),
+        bulk("entries-read"),
+        optional_int(group.entries_read),
+        bulk("lag"),
+        optional_int(stream.lag(group)),
+    ])
+}
+
+fn consumer_info(name: &str, consumer: &Consumer, now: u64) -> Frame {
+    let bulk = |s: &str| Frame::BulkString(s.to_owned());
+    let inactive = consumer
+        .active_time
+        .map_or(-1, |active| now.saturating_sub(active) as i64);
+
+    Frame::Array(vec![
+        bulk("name"),
+        bulk(name),
+        bulk("pending"),
+        Frame::Integer(consumer.pending().len() as i64),
+        bulk("idle"),
+        Frame::Integer(now.saturating_sub(consumer.seen_time) as i64),
+        bulk("inactive"),
+        Frame::Integer(inactive),
+    ])
+}
+
+/// The groups of `XINFO STREAM FULL`, where `count` limits the pending entries listed.
+fn full_groups_info(stream: &Stream, count: Option<usize>) -> Frame {
+    let bulk = |s: &str| Frame::BulkString(s.to_owned());
+    let limit = count.unwrap_or(usize::MAX);
+
+    let groups = stream.groups().iter().map(|(name, group)| {
+        let pending = group
+            .pending()
+            .iter()
+            .take(limit)
+            .map(|(id, pending)| {
+                Frame::Array(vec![
+                    bulk(&id.to_string()),
+                    bulk(&pending.consumer),
+                    Frame::Integer(pending.delivery_time as i64),
+                    Frame::Integer(pending.delivery_count as i64),
+                ])
+            })
+            .collect();
+
+        let consumers = group.consumers().iter().map(|(name, consumer)| {
+            let pending = consumer
+                .pending()
+                .iter()
+                .take(limit)
+                .map(|id| {
+                    let entry = &group.pending()[id];
+                    Frame::Array(vec![
+                        bulk(&id.to_string()),
+                        Frame::Integer(entry.delivery_time as i64),
+                        Frame::Integer(entry.delivery_count as i64),
+                    ])
+                })
+                .collect();
+
+            Frame::Array(vec![
+                bulk("name"),
+                bulk(name),
+                bulk("seen-time"),
+                Frame::Integer(consumer.seen_time as i64),
+                bulk("active-time"),
+                Frame::Integer(consumer.active_time.map_or(-1, |t| t as i64)),
+                bulk("pel-count"),
+                Frame::Integer(consumer.pending().len() as i64),
+                bulk("pending"),
+                Frame::Array(pending),
+            ])
+        });
+
+        Frame::Array(vec![
+            bulk("name"),
+            bulk(name),
+            bulk("last-delivered-id"),
+            bulk(&group.last_id.to_string()),
+            bulk("entries-read"),
+            optional_int(group.entries_read),
+            bulk("lag"),
+            optional_int(stream.lag(group)),
+            bulk("pel-count"),
+            Frame::Integer(group.pending().len() as i64),
+            bulk("pending"),
+            Frame::Array(pending),
+            bulk("consumers"),
+            Frame::Array(consumers.collect()),
+        ])
+    });
+
+    Frame::Array(groups.collect())
+}
+
+fn stream_info(stream: &Stream, full: Option<usize>) -> Frame {
+    let bulk = |s: &str| Frame::BulkString(s.to_owned());
+    let entry = |entry: Option<StreamEntry>| entry.map_or(Frame::Null, entry_frame);
+
+    let mut info = vec![
+        bulk("length"),
+        Frame::Integer(stream.len() as i64),
+        bulk("radix-tree-keys"),
+        Frame::Integer(stream.radix_tree_keys() as i64),
+        bulk("radix-tree-nodes"),
+        Frame::Integer(stream.radix_tree_nodes() as i64),
+        bulk("last-generated-id"),
+        bulk(&stream.last_id().to_string()),
+        bulk("max-deleted-entry-id"),
+        bulk(&stream.max_deleted_id().to_string()),
+        bulk("entries-added"),
+        Frame::Integer(stream.entries_added() as i64),
+        bulk("recorded-first-entry-id"),
+        bulk(&stream.first_id().to_string()),
+    ];
+
+    match full {
+        Some(count) => {
+            let count = (count > 0).then_some(count);
+            let entries = stream.range(StreamId::MIN, StreamId::MAX, false, count);
+            info.extend([
+                bulk("entries"),
+                entries_frame(entries),
+                bulk("groups"),
+                full_groups_info(stream, count),
+            ]);
+        }
+        None => info.extend([
+            bulk("groups"),
+            Frame::Integer(stream.groups().len() as i64),
+            bulk("first-entry"),
+            entry(stream.first_entry()),
+            bulk("last-entry"),
+            entry(stream.last_entry()),
+        ]),
+    }
+
+    Frame::Array(info)
+}
+
+/// `XSETID key last-id [ENTRIESADDED entries-added] [MAXDELETEDID max-deleted-id]`
+#[derive(Debug)]
+pub(crate) struct Xsetid {
+    key: String,
+    id: StreamId,
+    entries_added: Option<u64>,
+    max_deleted_id: Option<StreamId>,
+}
+
+impl Xsetid {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        let mut entries_added = None;
+        let mut max_deleted_id = None;
+
+        let mut rest = args[3..].iter();
+        while let Some(opt) = rest.next() {
+            let value = rest.next().ok_or(RedisError::Syntax)?;
+            match opt.to_lowercase().as_str() {
+                "entriesadded" => {
+                    entries_added = match parse_int(value)? {
+                        n if n < 0 => {
+                            return Err(RedisError::Custom(String::from(
+                                "entries_added must be positive",
+                            )))
+                        }
+                        n => Some(n as u64),
+                    }
+                }
+                "maxdeletedid" => max_deleted_id = Some(parse_id(value, 0)?),
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        let id = parse_id(&args[2], 0)?;
+        if max_deleted_id.is_some_and(|max| id < max) {
+            return Err(RedisError::Custom(String::from(
+                "The ID specified in XSETID is smaller than the provided max_deleted_entry_id",
+            )));
+        }
+
+        Ok(Xsetid {
+            key: args[1].clone(),
+            id,
+            entries_added,
+            max_deleted_id,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let stream = get_existing_stream(db, &self.key)?;
+
+        if stream.last_entry().is_some_and(|(last, _)| self.id < last) {
+            return Err(RedisError::Custom(String::from(
+                "The ID specified in XSETID is smaller than the target stream top item",
+            )));
+        }
+
+        let entries_added = self.entries_added.unwrap_or(stream.entries_added());
+        if (entries_added as usize) < stream.len() {
+            return Err(RedisError::Custom(String::from(
+                "The entries_added specified in XSETID is smaller than the target stream length",
+            )));
+        }
+
+        let max_deleted_id = self.max_deleted_id.unwrap_or(stream.max_deleted_id());
+        stream.set_last_id(self.id, entries_added, max_deleted_id);
+        db.touch(&self.key);
+        db.notify(notify::STREAM, "xsetid", &self.key);
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
+
+/// Where XREAD starts reading a stream from.
+#[derive(Debug, Clone, Copy)]
+enum ReadFrom {
+    /// `$`: only entries added after the command blocks.
+    New,
+    /// `+`: the last entry.
+    LastEntry,
+    After(StreamId),
+}
+
+/// `XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]`.
+#[derive(Debug, Clone)]
+pub(crate) struct Xread {
+    keys: Vec<String>,
+    from: Vec<ReadFrom>,
+    count: Option<usize>,
+}
+
+/// Parses a BLOCK timeout given in milliseconds, where zero means waiting forever.
+pub(crate) fn parse_block_timeout(s: &str) -> Result<Option<Duration>, RedisError> {
+    match s.parse::<i64>() {
+        Ok(ms) if ms < 0 => Err(RedisError::Custom(String::from("timeout is negative"))),
+        Ok(0) => Ok(None),
+        Ok(ms) => Ok(Some(Duration::from_millis(ms as u64))),
+        Err(_) => Err(RedisError::Custom(String::from(
+            "timeout is not an integer or out of range",
+        ))),
+    }
+}
+
+/// Parses XREAD, which only blocks when it has a BLOCK option.
+pub(crate) fn parse_xread(args: Vec<String>) -> Result<Command, RedisError> {
+    check_arity(&args, 4)?;
+
+    let mut count = None;
+    let mut block = None;
+    let mut index = 1;
+
+    loop {
+        let opt = args.get(index).ok_or(RedisError::Syntax)?.to_lowercase();
+        let value = args.get(index + 1);
+        match (opt.as_str(), value) {
+            ("count", Some(value)) => count = Some(parse_int(value)?.max(0) as usize),
+            ("block", Some(value)) => block = Some(parse_block_timeout(value)?),
+            ("streams", _) => break,
+            _ => return Err(RedisError::Syntax),
+        }
+        index += 2;
+    }
+
+    let streams = &args[index + 1..];
+    if streams.is_empty() || streams.len() % 2 == 1 {
+        return Err(RedisError::Custom(String::from(
+            "Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.",
+        )));
+    }
+
+    let (keys, ids) = streams.split_at(streams.len() / 2);
+    let from = ids
+        .iter()
+        .map(|id| match id.as_str() {
+            "$" => Ok(ReadFrom::New),
+            "+" => Ok(ReadFrom::LastEntry),
+            _ => parse_id(id, 0).map(ReadFrom::After),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let read = Xread {
+        keys: keys.to_vec(),
+        from,
+        // A count of zero means no limit.
+        count: count.filter(|c| *c > 0),
+    };
+
+    Ok(match block {
+        Some(timeout) => Command::Blocking(Arc::new(XreadBlock { read, timeout })),
+        None => Command::Stream(StreamCommand::Xread(read)),
+    })
+}
+
+impl Xread {
+    fn read_stream(
+        &self,
+        db: &mut dyn Database,
+        index: usize,
+    ) -> Result<Option<Frame>, RedisError> {
+        let key = &self.keys[index];
+        let Some(stream) = read_stream(db, key)? else {
+            return Ok(None);
+        };
+
+        let entries = match self.from[index] {
+            ReadFrom::New => vec![],
+            ReadFrom::LastEntry => stream.last_entry().into_iter().collect(),
+            ReadFrom::After(id) => match id.next() {
+                Some(start) => stream.range(start, StreamId::MAX, false, self.count),
+                None => vec![],
+            },
+        };
+
+        if entries.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Frame::Array(vec![
+            Frame::BulkString(key.clone()),
+            entries_frame(entries),
+        ])))
+    }
+
+    /// Reads every stream, returning `None` if none of them has anything to offer.
+    fn execute(&self, db: &mut dyn Database) -> Result<Option<Frame>, RedisError> {
+        let mut streams = Vec::new();
+        for index in 0..self.keys.len() {
+            streams.extend(self.read_stream(db, index)?);
+        }
+
+        Ok((!streams.is_empty()).then_some(Frame::Array(streams)))
+    }
+}
+
+/// XREAD with the BLOCK option.
+#[derive(Debug)]
+struct XreadBlock {
+    read: Xread,
+    timeout: Option<Duration>,
+}
+
+impl Block for XreadBlock {
+    fn keys(&self) -> &[String] {
+        &self.read.keys
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn serve(&self, db: &mut dyn Database, key: &str) -> Result<Option<Frame>, RedisError> {
+        let Some(index) = self.read.keys.iter().position(|k| k == key) else {
+            return Ok(None);
+        };
+
+        let stream = self.read.read_stream(db, index)?;
+
+        Ok(stream.map(|stream| Frame::Array(vec![stream])))
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Option<Frame>, RedisError> {
+        self.read.execute(db)
+    }
+
+    /// Pins `$` and `+` to the last ID of each stream, so that only entries added while the
+    /// client is blocked are returned.
+    fn resolve(&self, db: &mut dyn Database) -> Option<Arc<dyn Block>> {
+        let mut read = self.read.clone();
+
+        for (key, from) in read.keys.iter().zip(read.from.iter_mut()) {
+            if let ReadFrom::New | ReadFrom::LastEntry = from {
+                let last = match get_stream(db, key) {
+                    Ok(Some(stream)) => stream.last_id(),
+                    _ => StreamId::MIN,
+                };
+                *from = ReadFrom::After(last);
+            }
+        }
+
+        Some(Arc::new(XreadBlock {
+            read,
+            timeout: self.timeout,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::SystemTime;
+
+    use super::StreamCommand;
+    use crate::{
+        cmd::Execute,
+        db::{Database, KeyValueDb},
+        error::RedisError,
+        frame::Frame,
+        util::time::to_unix_millis,
+    };
+
+    fn run(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        StreamCommand::parse(&args[0], args.clone())
+            .and_then(|cmd| cmd.execute(db))
+            .unwrap_or_else(Frame::from)
+    }
+
+    fn xadd(db: &mut KeyValueDb, key: &str, args: &[&str]) -> Frame {
+        run(db, &[&["xadd", key], args, &["f", "v"]].concat())
+    }
+
+    fn bulk(s: &str) -> Frame {
+        Frame::BulkString(s.to_owned())
+    }
+
+    fn error(msg: &str) -> Frame {
+        Frame::from(RedisError::Custom(msg.to_owned()))
+    }
+
+    /// The IDs in the stream at `key`, oldest first.
+    fn ids(db: &mut KeyValueDb, key: &str) -> Vec<String> {
+        let Frame::Array(entries) = run(db, &["xrange", key, "-", "+"]) else {
+            panic!("XRANGE should reply with an array");
+        };
+        entries
+            .into_iter()
+            .map(|entry| match entry {
+                Frame::Array(entry) => match &entry[0] {
+                    Frame::BulkString(id) => id.clone(),
+                    id => panic!("unexpected {id:?}"),
+                },
+                entry => panic!("unexpected {entry:?}"),
+            })
+            .collect()
+    }
+
+    /// A stream at `key` with entries `1-0` to `n-0`.
+    fn filled(db: &mut KeyValueDb, key: &str, n: usize) {
+        for i in 1..=n {
+            xadd(db, key, &[&format!("{i}-0")]);
+        }
+        db.drain_rewritten();
+    }
+
+    #[test]
+    fn test_xadd_explicit_ids() {
+        let mut db = KeyValueDb::new();
+        let smaller =
+            error("The ID specified in XADD is equal or smaller than the target stream top item");
+
+        assert_eq!(
+            error("The ID specified in XADD must be greater than 0-0"),
+            xadd(&mut db, "s", &["0-0"])
+        );
+        assert_eq!(
+            error("The ID specified in XADD must be greater than 0-0"),
+            xadd(&mut db, "s", &["0"])
+        );
+        assert!(db.get_value("s").is_none());
+
+        assert_eq!(bulk("1-1"), xadd(&mut db, "s", &["1-1"]));
+        assert_eq!(smaller, xadd(&mut db, "s", &["1-1"]));
+        assert_eq!(smaller, xadd(&mut db, "s", &["1-0"]));
+        assert_eq!(smaller, xadd(&mut db, "s", &["0-5"]));
+        assert_eq!(smaller, xadd(&mut db, "s", &["1"]));
+        assert_eq!(bulk("1-2"), xadd(&mut db, "s", &["1-2"]));
+        assert_eq!(bulk("2-0"), xadd(&mut db, "s", &["2"]));
+
+        let invalid = error("Invalid stream ID specified as stream command argument");
+        for id in ["abc", "1-x", "-1", "1-2-3", "x-*"] {
+            assert_eq!(invalid, xadd(&mut db, "s", &[id]), "{id}");
+        }
+
+        assert_eq!(vec!["1-1", "1-2", "2-0"], ids(&mut db, "s"));
+    }
+
+    #[test]
+    fn test_xadd_generated_ids() {
+        let mut db = KeyValueDb::new();
+        let smaller =
+            error("The ID specified in XADD is equal or smaller than the target stream top item");
+
+        // `ms-*` picks the next sequence number for that millisecond.
+        assert_eq!(bulk("0-1"), xadd(&mut db, "s", &["0-*"]));
+        assert_eq!(bulk("0-2"), xadd(&mut db, "s", &["0-*"]));
+        assert_eq!(bulk("5-0"), xadd(&mut db, "s", &["5-*"]));
+        assert_eq!(bulk("5-1"), xadd(&mut db, "s", &["5-*"]));
+        assert_eq!(smaller, xadd(&mut db, "s", &["4-*"]));
+
+        // Replicas get the resolved ID.
+        db.drain_rewritten();
+        xadd(&mut db, "s", &["7-*"]);
+        let rewritten = Frame::Arrays(["XADD", "s", "7-0", "f", "v"].map(String::from).to_vec());
+        assert_eq!(Some(vec![rewritten]), db.drain_rewritten());
+
+        // `*` uses the current time, or follows the top item when that is ahead of it.
+        let before = to_unix_millis(SystemTime::now());
+        let Frame::BulkString(id) = xadd(&mut db, "s", &["*"]) else {
+            panic!("XADD should reply with the ID");
+        };
+        let (ms, seq) = id.split_once('-').unwrap();
+        assert!(ms.parse::<u64>().unwrap() >= before);
+        assert_eq!("0", seq);
+
+        let max = u64::MAX.to_string();
+        assert_eq!(
+            bulk(&format!("{max}-0")),
+            xadd(&mut db, "s", &[&format!("{max}-*")])
+        );
+        assert_eq!(bulk(&format!("{max}-1")), xadd(&mut db, "s", &["*"]));
+        xadd(&mut db, "s", &[&format!("{max}-{max}")]);
+        assert_eq!(
+            error("The stream has exhausted the last possible ID, unable to add more items"),
+            xadd(&mut db, "s", &["*"])
+        );
+        assert_eq!(smaller, xadd(&mut db, "s", &[&format!("{max}-*")]));
+    }
+
+    #[test]
+    fn test_xadd_exact_trims() {
+        let mut db = KeyValueDb::new();
+        let rewritten = |args: &[&str]| {
+            Some(vec![Frame::Arrays(
+                args.iter().map(|a| a.to_string()).collect(),
+            )])
+        };
+
+        filled(&mut db, "s", 5);
+        assert_eq!(bulk("6-0"), xadd(&mut db, "s", &["MAXLEN", "3", "6-0"]));
+        assert_eq!(vec!["4-0", "5-0", "6-0"], ids(&mut db, "s"));
+        assert_eq!(
+            rewritten(&["XADD", "s", "MAXLEN", "=", "3", "6-0", "f", "v"]),
+            db.drain_rewritten()
+        );
+
+        assert_eq!(
+            bulk("7-0"),
+            xadd(&mut db, "s", &["MAXLEN", "=", "0", "7-0"])
+        );
+        assert!(ids(&mut db, "s").is_empty());
+        assert_eq!(Frame::Integer(0), run(&mut db, &["xlen", "s"]));
+
+        filled(&mut db, "m", 5);
+        xadd(&mut db, "m", &["MINID", "3", "6-0"]);
+        assert_eq!(vec!["3-0", "4-0", "5-0", "6-0"], ids(&mut db, "m"));
+        assert_eq!(
+            rewritten(&["XADD", "m", "MINID", "=", "3-0", "6-0", "f", "v"]),
+            db.drain_rewritten()
+        );
+
+        // The new entry is trimmed as well when it is below the threshold.
+        xadd(&mut db, "m", &["MINID", "=", "10", "7-0"]);
+        assert!(ids(&mut db, "m").is_empty());
+        assert_eq!(
+            rewritten(&["XADD", "m", "MAXLEN", "=", "0", "7-0", "f", "v"]),
+            db.drain_rewritten()
+        );
+    }
+
+    #[test]
+    fn test_xadd_approximate_trims() {
+        let mut db = KeyValueDb::new();
+
+        // Approximate trims only free whole blocks of 100 entries.
+        filled(&mut db, "s", 5);
+        xadd(&mut db, "s", &["MAXLEN", "~", "3", "6-0"]);
+        assert_eq!(Frame::Integer(6), run(&mut db, &["xlen", "s"]));
+
+        filled(&mut db, "t", 250);
+        xadd(&mut db, "t", &["MAXLEN", "~", "120", "251-0"]);
+        assert_eq!(Frame::Integer(151), run(&mut db, &["xlen", "t"]));
+        assert_eq!("101-0", ids(&mut db, "t")[0]);
+        let rewritten = ["XADD", "t", "MAXLEN", "=", "151", "251-0", "f", "v"];
+        assert_eq!(
+            Some(vec![Frame::Arrays(rewritten.map(String::from).to_vec())]),
+            db.drain_rewritten()
+        );
+
+        filled(&mut db, "u", 300);
+        xadd(
+            &mut db,
+            "u",
+            &["MINID", "~", "1000", "LIMIT", "150", "301-0"],
+        );
+        assert_eq!(Frame::Integer(201), run(&mut db, &["xlen", "u"]));
+    }
+
+    #[test]
+    fn test_xadd_errors() {
+        let mut db = KeyValueDb::new();
+
+        assert_eq!(
+            error("The MAXLEN argument must be >= 0."),
+            xadd(&mut db, "s", &["MAXLEN", "-1", "*"])
+        );
+        assert_eq!(
+            error("syntax error, LIMIT cannot be used without the special ~ option"),
+            xadd(&mut db, "s", &["MAXLEN", "3", "LIMIT", "10", "*"])
+        );
+        assert_eq!(
+            error("The LIMIT argument must be >= 0."),
+            xadd(&mut db, "s", &["MAXLEN", "~", "3", "LIMIT", "-1", "*"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::NotInteger),
+            xadd(&mut db, "s", &["MAXLEN", "x", "*"])
+        );
+        assert_eq!(
+            error("Invalid stream ID specified as stream command argument"),
+            xadd(&mut db, "s", &["MINID", "x", "*"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::WrongArity(String::from("xadd"))),
+            run(&mut db, &["xadd", "s", "*", "f", "v", "g"])
+        );
+        assert_eq!(Frame::Null, xadd(&mut db, "s", &["NOMKSTREAM", "*"]));
+        assert!(db.get_value("s").is_none());
+    }
+}
@@ -0,0 +1,588 @@
+//! What every command accesses, so that ACL rules can be checked before running it: its
+//! categories, its keys and whether it writes them, and its channels.
+
+use crate::module::{self, ModuleCommand};
+
+pub(crate) const KEYSPACE: u32 = 1 << 0;
+pub(crate) const READ: u32 = 1 << 1;
+pub(crate) const WRITE: u32 = 1 << 2;
+pub(crate) const SET: u32 = 1 << 3;
+pub(crate) const SORTEDSET: u32 = 1 << 4;
+pub(crate) const LIST: u32 = 1 << 5;
+pub(crate) const HASH: u32 = 1 << 6;
+pub(crate) const STRING: u32 = 1 << 7;
+pub(crate) const BITMAP: u32 = 1 << 8;
+pub(crate) const HYPERLOGLOG: u32 = 1 << 9;
+pub(crate) const GEO: u32 = 1 << 10;
+pub(crate) const STREAM: u32 = 1 << 11;
+pub(crate) const PUBSUB: u32 = 1 << 12;
+pub(crate) const ADMIN: u32 = 1 << 13;
+pub(crate) const FAST: u32 = 1 << 14;
+pub(crate) const SLOW: u32 = 1 << 15;
+pub(crate) const BLOCKING: u32 = 1 << 16;
+pub(crate) const DANGEROUS: u32 = 1 << 17;
+pub(crate) const CONNECTION: u32 = 1 << 18;
+pub(crate) const TRANSACTION: u32 = 1 << 19;
+pub(crate) const SCRIPTING: u32 = 1 << 20;
+pub(crate) const JSON: u32 = 1 << 21;
+pub(crate) const BLOOM: u32 = 1 << 22;
+pub(crate) const CUCKOO: u32 = 1 << 23;
+pub(crate) const CMS: u32 = 1 << 24;
+pub(crate) const TIMESERIES: u32 = 1 << 25;
+pub(crate) const SEARCH: u32 = 1 << 26;
+pub(crate) const ALL: u32 = u32::MAX;
+
+/// The ACL categories in the order ACL CAT lists them.
+pub(crate) const CATEGORIES: [(&str, u32); 27] = [
+    ("keyspace", KEYSPACE),
+    ("read", READ),
+    ("write", WRITE),
+    ("set", SET),
+    ("sortedset", SORTEDSET),
+    ("list", LIST),
+    ("hash", HASH),
+    ("string", STRING),
+    ("bitmap", BITMAP),
+    ("hyperloglog", HYPERLOGLOG),
+    ("geo", GEO),
+    ("stream", STREAM),
+    ("pubsub", PUBSUB),
+    ("admin", ADMIN),
+    ("fast", FAST),
+    ("slow", SLOW),
+    ("blocking", BLOCKING),
+    ("dangerous", DANGEROUS),
+    ("connection", CONNECTION),
+    ("transaction", TRANSACTION),
+    ("scripting", SCRIPTING),
+    ("json", JSON),
+    ("bloom", BLOOM),
+    ("cuckoo", CUCKOO),
+    ("cms", CMS),
+    ("timeseries", TIMESERIES),
+    ("search", SEARCH),
+];
+
+/// Where the keys of a command are among its arguments.
+#[derive(Debug, Clone, Copy)]
+enum Keys {
+    None,
+    /// Every `step` arguments from `first` to `last`, where a negative `last` counts from the
+    /// end.
+    Range(usize, isize, usize),
+    /// The number of keys at the given index, followed by the keys.
+    Counted(usize),
+    /// The keys after the STREAMS keyword, which are followed by as many IDs.
+    Streams,
+    /// Keys that can't be told from the arguments, so that only users allowed to read and
+    /// write every key may run the command.
+    Any,
+}
+
+/// Where the channels of a command are among its arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channels {
+    None,
+    /// Every argument after the command name is a channel.
+    Names,
+    /// Every argument after the command name is a pattern, which ACL rules must allow as is.
+    Patterns,
+    /// The first argument is the channel.
+    First,
+}
+
+#[derive(Debug)]
+struct Spec {
+    /// The command name, or `command|subcommand` for subcommands with their own categories.
+    name: &'static str,
+    categories: u32,
+    keys: Keys,
+    /// A key the command writes to while only reading the others, such as the destination
+    /// of SUNIONSTORE.
+    dest: Option<usize>,
+    channels: Channels,
+}
+
+const fn spec(name: &'static str, categories: u32, keys: Keys) -> Spec {
+    Spec {
+        name,
+        categories,
+        keys,
+        dest: None,
+        channels: Channels::None,
+    }
+}
+
+/// A command storing into `dest` what it computes from `keys`.
+const fn store(name: &'static str, categories: u32, keys: Keys, dest: usize) -> Spec {
+    Spec {
+        name,
+        categories,
+        keys,
+        dest: Some(dest),
+        channels: Channels::None,
+    }
+}
+
+const fn pubsub(name: &'static str, categories: u32, channels: Channels) -> Spec {
+    Spec {
+        name,
+        categories,
+        keys: Keys::None,
+        dest: None,
+        channels,
+    }
+}
+
+/// Commands that take a subcommand, which ACL rules can allow on their own as
+/// `command|subcommand`.
+const CONTAINERS: [&str; 7] = [
+    "acl", "config", "object", "memory", "xinfo", "xgroup", "pubsub",
+];
+
+const NONE: Keys = Keys::None;
+const ANY: Keys = Keys::Any;
+const KEY: Keys = Keys::Range(1, 1, 1);
+const ALL_KEYS: Keys = Keys::Range(1, -1, 1);
+/// Keys followed by a timeout, as in BZPOPMIN.
+const KEYS_THEN_ONE: Keys = Keys::Range(1, -2, 1);
+/// The key after a subcommand, as in XINFO STREAM.
+const SUBCOMMAND_KEY: Keys = Keys::Range(2, 2, 1);
+
+const TABLE: &[Spec] = &[
+    // Connection and server.
+    spec("ping", CONNECTION | FAST, NONE),
+    spec("echo", CONNECTION | FAST, NONE),
+    spec("auth", CONNECTION | FAST, NONE),
+    spec("quit", CONNECTION | FAST, NONE),
+    spec("info", SLOW | DANGEROUS, NONE),
+    spec("replconf", ADMIN | SLOW | DANGEROUS, NONE),
+    spec("psync", ADMIN | SLOW | DANGEROUS, NONE),
+    spec("config", ADMIN | SLOW | DANGEROUS, NONE),
+    spec("acl", ADMIN | SLOW | DANGEROUS, NONE),
+    spec("acl|whoami", SLOW, NONE),
+    spec("acl|cat", SLOW, NONE),
+    spec("flushdb", KEYSPACE | WRITE | SLOW | DANGEROUS, NONE),
+    spec("del", KEYSPACE | WRITE | SLOW, ALL_KEYS),
+    spec("object", KEYSPACE | READ | SLOW, SUBCOMMAND_KEY),
+    spec("memory", READ | SLOW, SUBCOMMAND_KEY),
+    // Transactions.
+    spec("multi", TRANSACTION | FAST, NONE),
+    spec("exec", TRANSACTION | SLOW, NONE),
+    spec("discard", TRANSACTION | FAST, NONE),
+    spec("watch", TRANSACTION | FAST, ALL_KEYS),
+    spec("unwatch", TRANSACTION | FAST, NONE),
+    // Pub/sub.
+    pubsub("subscribe", PUBSUB | SLOW, Channels::Names),
+    pubsub("psubscribe", PUBSUB | SLOW, Channels::Patterns),
+    pubsub("unsubscribe", PUBSUB | SLOW, Channels::None),
+    pubsub("punsubscribe", PUBSUB | SLOW, Channels::None),
+    pubsub("publish", PUBSUB | FAST, Channels::First),
+    pubsub("pubsub", PUBSUB | SLOW, Channels::None),
+    // Strings.
+    spec("get", READ | STRING | FAST, KEY),
+    spec("set", WRITE | STRING | SLOW, KEY),
+    spec("cl.throttle", WRITE | STRING | FAST, KEY),
+    // Hashes.
+    spec("hset", WRITE | HASH | FAST, KEY),
+    spec("hmset", WRITE | HASH | FAST, KEY),
+    spec("hsetnx", WRITE | HASH | FAST, KEY),
+    spec("hget", READ | HASH | FAST, KEY),
+    spec("hmget", READ | HASH | FAST, KEY),
+    spec("hgetall", READ | HASH | SLOW, KEY),
+    spec("hdel", WRITE | HASH | FAST, KEY),
+    spec("hexists", READ | HASH | FAST, KEY),
+    spec("hincrby", WRITE | HASH | FAST, KEY),
+    spec("hincrbyfloat", WRITE | HASH | FAST, KEY),
+    spec("hkeys", READ | HASH | SLOW, KEY),
+    spec("hvals", READ | HASH | SLOW, KEY),
+    spec("hlen", READ | HASH | FAST, KEY),
+    spec("hstrlen", READ | HASH | FAST, KEY),
+    spec("hrandfield", READ | HASH | SLOW, KEY),
+    spec("hscan", READ | HASH | SLOW, KEY),
+    spec("hexpire", WRITE | HASH | FAST, KEY),
+    spec("hpexpire", WRITE | HASH | FAST, KEY),
+    spec("hexpireat", WRITE | HASH | FAST, KEY),
+    spec("hpexpireat", WRITE | HASH | FAST, KEY),
+    spec("httl", READ | HASH | FAST, KEY),
+    spec("hpttl", READ | HASH | FAST, KEY),
+    spec("hexpiretime", READ | HASH | FAST, KEY),
+    spec("hpexpiretime", READ | HASH | FAST, KEY),
+    spec("hpersist", WRITE | HASH | FAST, KEY),
+    spec("hgetex", WRITE | HASH | FAST, KEY),
+    // Sets.
+    spec("sadd", WRITE | SET | FAST, KEY),
+    spec("srem", WRITE | SET | FAST, KEY),
+    spec("smembers", READ | SET | SLOW, KEY),
+    spec("sismember", READ | SET | FAST, KEY),
+    spec("smismember", READ | SET | FAST, KEY),
+    spec("scard", READ | SET | FAST, KEY),
+    spec("spop", WRITE | SET | FAST, KEY),
+    spec("srandmember", READ | SET | SLOW, KEY),
+    spec("smove", WRITE | SET | FAST, Keys::Range(1, 2, 1)),
+    spec("sinter", READ | SET | SLOW, ALL_KEYS),
+    spec("sunion", READ | SET | SLOW, ALL_KEYS),
+    spec("sdiff", READ | SET | SLOW, ALL_KEYS),
+    store("sinterstore", WRITE | SET | SLOW, Keys::Range(2, -1, 1), 1),
+    store("sunionstore", WRITE | SET | SLOW, Keys::Range(2, -1, 1), 1),
+    store("sdiffstore", WRITE | SET | SLOW, Keys::Range(2, -1, 1), 1),
+    spec("sintercard", READ | SET | SLOW, Keys::Counted(1)),
+    spec("sscan", READ | SET | SLOW, KEY),
+    // Sorted sets.
+    spec("zadd", WRITE | SORTEDSET | FAST, KEY),
+    spec("zincrby", WRITE | SORTEDSET | FAST, KEY),
+    spec("zrem", WRITE | SORTEDSET | FAST, KEY),
+    spec("zcard", READ | SORTEDSET | FAST, KEY),
+    spec("zscore", READ | SORTEDSET | FAST, KEY),
+    spec("zmscore", READ | SORTEDSET | FAST, KEY),
+    spec("zrank", READ | SORTEDSET | FAST, KEY),
+    spec("zrevrank", READ | SORTEDSET | FAST, KEY),
+    spec("zcount", READ | SORTEDSET | FAST, KEY),
+    spec("zrange", READ | SORTEDSET | SLOW, KEY),
+    spec("zpopmin", WRITE | SORTEDSET | FAST, KEY),
+    spec("zpopmax", WRITE | SORTEDSET | FAST, KEY),
+    spec("zrandmember", READ | SORTEDSET | SLOW, KEY),
+    spec("zscan", READ | SORTEDSET | SLOW, KEY),
+    store("zunionstore", WRITE | SORTEDSET | SLOW, Keys::Counted(2), 1),
+    store("zinterstore", WRITE | SORTEDSET | SLOW, Keys::Counted(2), 1),
+    store("zdiffstore", WRITE | SORTEDSET | SLOW, Keys::Counted(2), 1),
+    spec("zmpop", WRITE | SORTEDSET | SLOW, Keys::Counted(1)),
+    spec(
+        "bzpopmin",
+        WRITE | SORTEDSET | FAST | BLOCKING,
+        KEYS_THEN_ONE,
+    ),
+    spec(
+        "bzpopmax",
+        WRITE | SORTEDSET | FAST | BLOCKING,
+        KEYS_THEN_ONE,
+    ),
+    spec(
+        "bzmpop",
+        WRITE | SORTEDSET | SLOW | BLOCKING,
+        Keys::Counted(2),
+    ),
+    // Streams.
+    spec("xadd", WRITE | STREAM | FAST, KEY),
+    spec("xrange", READ | STREAM | SLOW, KEY),
+    spec("xrevrange", READ | STREAM | SLOW, KEY),
+    spec("xlen", READ | STREAM | FAST, KEY),
+    spec("xdel", WRITE | STREAM | FAST, KEY),
+    spec("xtrim", WRITE | STREAM | SLOW, KEY),
+    spec("xinfo", READ | STREAM | SLOW, SUBCOMMAND_KEY),
+    spec("xsetid", WRITE | STREAM | FAST, KEY),
+    spec("xread", READ | STREAM | SLOW | BLOCKING, Keys::Streams),
+    spec("xgroup", WRITE | STREAM | SLOW, SUBCOMMAND_KEY),
+    spec("xack", WRITE | STREAM | FAST, KEY),
+    spec("xpending", READ | STREAM | SLOW, KEY),
+    spec("xclaim", WRITE | STREAM | FAST, KEY),
+    spec("xautoclaim", WRITE | STREAM | FAST, KEY),
+    spec(
+        "xreadgroup",
+        WRITE | STREAM | SLOW | BLOCKING,
+        Keys::Streams,
+    ),
+    // Bitmaps and HyperLogLogs.
+    spec("setbit", WRITE | BITMAP | SLOW, KEY),
+    spec("getbit", READ | BITMAP | FAST, KEY),
+    spec("bitcount", READ | BITMAP | SLOW, KEY),
+    spec("bitpos", READ | BITMAP | SLOW, KEY),
+    store("bitop", WRITE | BITMAP | SLOW, Keys::Range(3, -1, 1), 2),
+    spec("bitfield", WRITE | BITMAP | SLOW, KEY),
+    spec("bitfield_ro", READ | BITMAP | FAST, KEY),
+    spec("pfadd", WRITE | HYPERLOGLOG | FAST, KEY),
+    spec("pfcount", READ | HYPERLOGLOG | SLOW, ALL_KEYS),
+    store(
+        "pfmerge",
+        WRITE | HYPERLOGLOG | SLOW,
+        Keys::Range(2, -1, 1),
+        1,
+    ),
+    // Geospatial indexes.
+    spec("geoadd", WRITE | GEO | SLOW, KEY),
+    spec("geopos", READ | GEO | SLOW, KEY),
+    spec("geodist", READ | GEO | SLOW, KEY),
+    spec("geohash", READ | GEO | SLOW, KEY),
+    spec("geosearch", READ | GEO | SLOW, KEY),
+    store(
+        "geosearchstore",
+        WRITE | GEO | SLOW,
+        Keys::Range(2, 2, 1),
+        1,
+    ),
+    // Module types.
+    spec("json.set", WRITE | JSON | SLOW, KEY),
+    spec("json.get", READ | JSON | SLOW, KEY),
+    spec("json.del", WRITE | JSON | SLOW, KEY),
+    spec("json.forget", WRITE | JSON | SLOW, KEY),
+    spec("json.type", READ | JSON | SLOW, KEY),
+    spec("json.numincrby", WRITE | JSON | SLOW, KEY),
+    spec("json.arrappend", WRITE | JSON | SLOW, KEY),
+    spec("json.strappend", WRITE | JSON | SLOW, KEY),
+    spec("json.mget", READ | JSON | SLOW, KEYS_THEN_ONE),
+    spec("bf.reserve", WRITE | BLOOM | FAST, KEY),
+    spec("bf.add", WRITE | BLOOM | FAST, KEY),
+    spec("bf.madd", WRITE | BLOOM | FAST, KEY),
+    spec("bf.exists", READ | BLOOM | FAST, KEY),
+    spec("bf.info", READ | BLOOM | FAST, KEY),
+    spec("cf.add", WRITE | CUCKOO | FAST, KEY),
+    spec("cf.del", WRITE | CUCKOO | FAST, KEY),
+    spec("cf.exists", READ | CUCKOO | FAST, KEY),
+    spec("cms.initbydim", WRITE | CMS | FAST, KEY),
+    spec("cms.incrby", WRITE | CMS | FAST, KEY),
+    spec("cms.query", READ | CMS | FAST, KEY),
+    store("cms.merge", WRITE | CMS | SLOW, Keys::Counted(2), 1),
+    spec("ts.create", WRITE | TIMESERIES | FAST, KEY),
+    spec("ts.add", WRITE | TIMESERIES | FAST, KEY),
+    spec("ts.madd", WRITE | TIMESERIES | SLOW, Keys::Range(1, -1, 3)),
+    spec("ts.range", READ | TIMESERIES | SLOW, KEY),
+    spec("ts.revrange", READ | TIMESERIES | SLOW, KEY),
+    // Series are selected by their labels.
+    spec("ts.mrange", READ | TIMESERIES | SLOW, ANY),
+    spec(
+        "ts.createrule",
+        WRITE | TIMESERIES | FAST,
+        Keys::Range(1, 2, 1),
+    ),
+    spec(
+        "ts.deleterule",
+        WRITE | TIMESERIES | FAST,
+        Keys::Range(1, 2, 1),
+    ),
+    spec("vadd", WRITE | SLOW, KEY),
+    spec("vsim", READ | SLOW, KEY),
+    spec("vrem", WRITE | SLOW, KEY),
+    spec("vcard", READ | FAST, KEY),
+    spec("vdim", READ | FAST, KEY),
+    spec("vemb", READ | FAST, KEY),
+    // Indexes cover the keys with their prefixes, like in RediSearch.
+    spec("ft.create", WRITE | SEARCH | SLOW, ANY),
+    spec("ft.search", READ | SEARCH | SLOW, ANY),
+    spec("ft.dropindex", WRITE | SEARCH | SLOW, ANY),
+];
+
+/// What a call to a command accesses.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Access<'a> {
+    /// The command name, or `command|subcommand` for commands with subcommands.
+    pub(crate) name: String,
+    pub(crate) categories: u32,
+    /// The keys with whether they are written.
+    pub(crate) keys: Vec<(&'a str, bool)>,
+    /// Whether the command may access any key, as with keys selected by their prefix.
+    pub(crate) any_key: bool,
+    /// The channels with whether they are patterns.
+    pub(crate) channels: Vec<(&'a str, bool)>,
+}
+
+impl<'a> Access<'a> {
+    /// The access of a built-in command, given its arguments including its name.
+    pub(crate) fn of(args: &'a [String]) -> Option<Self> {
+        let command = args.first()?.to_lowercase();
+        let name = match args.get(1) {
+            Some(sub) if CONTAINERS.contains(&command.as_str()) => {
+                format!("{command}|{}", sub.to_lowercase())
+            }
+            _ => command.clone(),
+        };
+        let spec = TABLE
+            .iter()
+            .find(|s| s.name == name)
+            .or_else(|| TABLE.iter().find(|s| s.name == command))?;
+
+        let write = spec.categories & WRITE != 0;
+        let mut keys: Vec<(&str, bool)> = positions(spec.keys, args)
+            .into_iter()
+            .map(|i| (args[i].as_str(), write && spec.dest.is_none()))
+            .collect();
+        if let Some(dest) = spec.dest.filter(|&i| i < args.len()) {
+            keys.insert(0, (args[dest].as_str(), true));
+        }
+
+        let channels = match spec.channels {
+            Channels::None => Vec::new(),
+            Channels::Names => args[1..].iter().map(|c| (c.as_str(), false)).collect(),
+            Channels::Patterns => args[1..].iter().map(|c| (c.as_str(), true)).collect(),
+            Channels::First => args
+                .get(1)
+                .map(|c| (c.as_str(), false))
+                .into_iter()
+                .collect(),
+        };
+
+        Some(Access {
+            name,
+            categories: spec.categories,
+            keys,
+            any_key: matches!(spec.keys, Keys::Any),
+            channels,
+        })
+    }
+
+    /// The access of a command registered by a module, with categories following its flags.
+    pub(crate) fn of_module(command: &dyn ModuleCommand, args: &'a [String]) -> Self {
+        let flags = command.flags();
+        let mut categories = if flags & module::FAST != 0 {
+            FAST
+        } else {
+            SLOW
+        };
+        if flags & module::WRITE != 0 {
+            categories |= WRITE;
+        }
+        if flags & module::READONLY != 0 {
+            categories |= READ;
+        }
+        if flags & module::ADMIN != 0 {
+            categories |= ADMIN | DANGEROUS;
+        }
+
+        let write = flags & module::WRITE != 0;
+        let keys = command
+            .keys(args)
+            .into_iter()
+            .filter_map(|i| args.get(i))
+            .map(|key| (key.as_str(), write))
+            .collect();
+
+        Access {
+            name: command.name().to_lowercase(),
+            categories,
+            keys,
+            any_key: false,
+            channels: Vec::new(),
+        }
+    }
+}
+
+/// The commands in the categories `categories`, as listed by ACL CAT.
+pub(crate) fn commands_in(categories: u32) -> Vec<&'static str> {
+    TABLE
+        .iter()
+        .filter(|s| s.categories & categories != 0)
+        .map(|s| s.name)
+        .collect()
+}
+
+/// Whether `name` is a built-in command, or `command|subcommand` for a command taking
+/// subcommands, as named by ACL rules.
+pub(crate) fn exists(name: &str) -> bool {
+    match name.split_once('|') {
+        Some((command, sub)) => !sub.is_empty() && CONTAINERS.contains(&command),
+        None => TABLE.iter().any(|s| s.name == name),
+    }
+}
+
+/// The positions of the keys among `args`, leaving out those past the end.
+fn positions(keys: Keys, args: &[String]) -> Vec<usize> {
+    let positions: Vec<usize> = match keys {
+        Keys::None | Keys::Any => Vec::new(),
+        Keys::Range(first, last, step) => {
+            let last = match last {
+                last if last < 0 => args.len() as isize + last,
+                last => last,
+            };
+            (first..=last.max(0) as usize).step_by(step).collect()
+        }
+        Keys::Counted(index) => {
+            let count = args
+                .get(index)
+                .and_then(|n| n.parse::<usize>().ok())
+                .unwrap_or(0);
+            (index + 1..index + 1 + count).collect()
+        }
+        Keys::Streams => {
+            let start = args
+                .iter()
+                .position(|arg| arg.eq_ignore_ascii_case("streams"))
+                .map_or(args.len(), |i| i + 1);
+            let count = (args.len() - start) / 2;
+            (start..start + count).collect()
+        }
+    };
+
+    positions.into_iter().filter(|&i| i < args.len()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Access, ADMIN, READ, WRITE};
+
+    fn access(args: &[&str]) -> Option<(String, Vec<(String, bool)>)> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let access = Access::of(&args)?;
+        let keys = access
+            .keys
+            .into_iter()
+            .map(|(key, write)| (key.to_owned(), write))
+            .collect();
+        Some((access.name, keys))
+    }
+
+    fn keys(keys: &[(&str, bool)]) -> Vec<(String, bool)> {
+        keys.iter().map(|(k, w)| (k.to_string(), *w)).collect()
+    }
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(
+            Some((String::from("get"), keys(&[("k", false)]))),
+            access(&["GET", "k"])
+        );
+        assert_eq!(
+            keys(&[("d", true), ("a", false), ("b", false)]),
+            access(&["zunionstore", "d", "2", "a", "b", "WEIGHTS", "1", "2"])
+                .unwrap()
+                .1
+        );
+        assert_eq!(
+            keys(&[("a", true), ("b", true)]),
+            access(&["bzpopmin", "a", "b", "0"]).unwrap().1
+        );
+        assert_eq!(
+            keys(&[("a", false), ("b", false)]),
+            access(&["xread", "COUNT", "1", "STREAMS", "a", "b", "0", "0"])
+                .unwrap()
+                .1
+        );
+        assert_eq!(
+            keys(&[("d", true), ("a", false)]),
+            access(&["bitop", "NOT", "d", "a"]).unwrap().1
+        );
+        assert_eq!(
+            keys(&[("a", true), ("b", true)]),
+            access(&["ts.madd", "a", "1", "1", "b", "1", "1"])
+                .unwrap()
+                .1
+        );
+        // Numbers of keys past the end are cut short.
+        assert_eq!(
+            keys(&[("a", false)]),
+            access(&["sintercard", "5", "a"]).unwrap().1
+        );
+        assert_eq!(None, access(&["nope"]));
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let args: Vec<String> = ["ACL", "whoami"].iter().map(|s| s.to_string()).collect();
+        let access = Access::of(&args).unwrap();
+        assert_eq!("acl|whoami", access.name);
+        assert_eq!(0, access.categories & ADMIN);
+
+        let args: Vec<String> = ["acl", "setuser", "a"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_ne!(0, Access::of(&args).unwrap().categories & ADMIN);
+
+        let args: Vec<String> = ["object", "encoding", "k"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let access = Access::of(&args).unwrap();
+        assert_eq!("object|encoding", access.name);
+        assert_eq!(vec![("k", false)], access.keys);
+        assert_eq!(READ, access.categories & (READ | WRITE));
+    }
+}
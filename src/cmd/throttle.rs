@@ -0,0 +1,204 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{notify, Database},
+    error::RedisError,
+    frame::Frame,
+    util::{
+        gcra::{self, Rate},
+        num::parse_int,
+    },
+};
+
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+/// `CL.THROTTLE key max_burst count period [quantity]`, rate limiting `key` to `count`
+/// requests every `period` seconds with bursts of `max_burst` more.
+///
+/// Replies with whether the request was denied, the limit, the remaining requests, the
+/// seconds to wait before retrying (-1 if allowed) and the seconds until the limit is fully
+/// reset. The limiter is stored as a string holding its TAT, which expires once the limiter
+/// is back to its full limit.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    key: String,
+    rate: Rate,
+    quantity: i64,
+}
+
+impl Throttle {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+        if args.len() > 6 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        let parse = |s: &str, name: &str, min: i64| {
+            parse_int(s)
+                .ok()
+                .filter(|n| *n >= min)
+                .ok_or(RedisError::Custom(format!(
+                    "invalid {name}, must be an integer of at least {min}"
+                )))
+        };
+
+        let period = parse(&args[4], "period", 1)?;
+        Ok(Throttle {
+            key: args[1].clone(),
+            rate: Rate {
+                max_burst: parse(&args[2], "max_burst", 0)?,
+                count: parse(&args[3], "count", 1)?,
+                period: period.saturating_mul(NANOS_PER_SECOND),
+            },
+            quantity: args.get(5).map_or(Ok(1), |s| parse(s, "quantity", 0))?,
+        })
+    }
+}
+
+impl Execute for Throttle {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let state = db
+            .get_value(&self.key)
+            .map(|value| value.as_string_mut().cloned())
+            .transpose()?;
+        let tat = match state {
+            Some(state) => {
+                let tat = std::str::from_utf8(&state)
+                    .ok()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .ok_or(RedisError::Custom(String::from(
+                        "value is not a rate limiter state",
+                    )))?;
+                Some(tat)
+            }
+            None => None,
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as i64);
+        let decision = gcra::throttle(self.rate, self.quantity, tat, now);
+
+        // Replicas would take the decision at a different time, so they get the state.
+        match decision.tat {
+            Some(tat) if tat > now => {
+                let exp = UNIX_EPOCH + Duration::from_nanos(tat as u64);
+                db.set(&self.key, tat.to_string().as_bytes(), Some(exp));
+                db.notify(notify::STRING, "set", &self.key);
+                db.notify(notify::GENERIC, "expire", &self.key);
+
+                let ttl = ((tat - now) as u64).div_ceil(1_000_000);
+                db.rewrite(Frame::Arrays(vec![
+                    String::from("SET"),
+                    self.key.clone(),
+                    tat.to_string(),
+                    String::from("PX"),
+                    ttl.to_string(),
+                ]));
+            }
+            // A stored TAT is always in the future, so there is nothing to store when the
+            // request consumed nothing.
+            _ => {}
+        }
+
+        // Round up, so that retrying after that many seconds succeeds.
+        let seconds = |nanos: i64| (nanos as u64).div_ceil(NANOS_PER_SECOND as u64) as i64;
+        Ok(Frame::Array(vec![
+            Frame::Integer(decision.limited as i64),
+            Frame::Integer(decision.limit),
+            Frame::Integer(decision.remaining),
+            Frame::Integer(decision.retry_after.map_or(-1, seconds)),
+            Frame::Integer(seconds(decision.reset_after)),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Throttle;
+    use crate::{
+        cmd::Execute,
+        db::{Database, KeyValueDb},
+        frame::Frame,
+    };
+
+    fn run(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        Throttle::new(args)
+            .and_then(|cmd| cmd.execute(db))
+            .unwrap_or_else(Frame::from)
+    }
+
+    fn reply(values: [i64; 5]) -> Frame {
+        Frame::Array(values.into_iter().map(Frame::Integer).collect())
+    }
+
+    #[test]
+    fn test_reply_shape() {
+        let mut db = KeyValueDb::default();
+
+        // Allowed requests retry after -1 and push the reset further out.
+        let throttle = ["cl.throttle", "k", "4", "1", "60"];
+        assert_eq!(run(&mut db, &throttle), reply([0, 5, 4, -1, 60]));
+        assert_eq!(run(&mut db, &throttle), reply([0, 5, 3, -1, 120]));
+        assert!(matches!(db.get("k"), Ok(Some(_))));
+
+        let take = ["cl.throttle", "k", "4", "1", "60", "3"];
+        assert_eq!(run(&mut db, &take), reply([0, 5, 0, -1, 300]));
+
+        // Once exhausted, the retry is one emission interval away.
+        assert_eq!(run(&mut db, &throttle), reply([1, 5, 0, 60, 300]));
+
+        // A zero quantity only reads the state.
+        let peek = ["cl.throttle", "k", "4", "1", "60", "0"];
+        assert_eq!(run(&mut db, &peek), reply([0, 5, 0, -1, 300]));
+    }
+
+    #[test]
+    fn test_quantity_over_burst() {
+        let mut db = KeyValueDb::default();
+
+        // Asking for more than the limit never succeeds, so there is no retry time.
+        let throttle = ["cl.throttle", "k", "4", "1", "60", "6"];
+        assert_eq!(run(&mut db, &throttle), reply([1, 5, 5, -1, 0]));
+        assert!(matches!(db.get("k"), Ok(None)));
+
+        // Exactly the limit is allowed on a fresh limiter.
+        let throttle = ["cl.throttle", "k", "4", "1", "60", "5"];
+        assert_eq!(run(&mut db, &throttle), reply([0, 5, 0, -1, 300]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut db = KeyValueDb::default();
+        let error = |msg: &str| Frame::Error(msg.to_string());
+
+        assert_eq!(
+            run(&mut db, &["cl.throttle", "k", "4", "1"]),
+            error("ERR wrong number of arguments for 'cl.throttle' command")
+        );
+        assert_eq!(
+            run(&mut db, &["cl.throttle", "k", "4", "1", "60", "1", "1"]),
+            error("ERR wrong number of arguments for 'cl.throttle' command")
+        );
+        assert_eq!(
+            run(&mut db, &["cl.throttle", "k", "-1", "1", "60"]),
+            error("ERR invalid max_burst, must be an integer of at least 0")
+        );
+        assert_eq!(
+            run(&mut db, &["cl.throttle", "k", "4", "0", "60"]),
+            error("ERR invalid count, must be an integer of at least 1")
+        );
+        assert_eq!(
+            run(&mut db, &["cl.throttle", "k", "4", "1", "x"]),
+            error("ERR invalid period, must be an integer of at least 1")
+        );
+
+        db.set("k", b"x", None);
+        assert_eq!(
+            run(&mut db, &["cl.throttle", "k", "4", "1", "60"]),
+            error("ERR value is not a rate limiter state")
+        );
+    }
+}
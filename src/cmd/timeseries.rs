@@ -0,0 +1,1197 @@
+use std::{collections::BTreeMap, time::SystemTime};
+
+use crate::{
+    cmd::{check_arity, Execute},
+    db::{
+        notify,
+        timeseries::{self, Aggregation, DuplicatePolicy, Rule, TimeSeries},
+        Data, Database, Value,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::{
+        num::{format_float, parse_float, parse_int},
+        time::to_unix_millis,
+    },
+};
+
+#[derive(Debug)]
+pub(crate) enum TimeSeriesCommand {
+    Create(TsCreate),
+    Add(TsAdd),
+    Madd(TsMadd),
+    Range(TsRange),
+    Mrange(TsMrange),
+    CreateRule(TsCreateRule),
+    DeleteRule(TsDeleteRule),
+}
+
+impl TimeSeriesCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "ts.create" => TsCreate::new(args).map(TimeSeriesCommand::Create),
+            "ts.add" => TsAdd::new(args).map(TimeSeriesCommand::Add),
+            "ts.madd" => TsMadd::new(args).map(TimeSeriesCommand::Madd),
+            "ts.range" => TsRange::new(args, false).map(TimeSeriesCommand::Range),
+            "ts.revrange" => TsRange::new(args, true).map(TimeSeriesCommand::Range),
+            "ts.mrange" => TsMrange::new(args).map(TimeSeriesCommand::Mrange),
+            "ts.createrule" => TsCreateRule::new(args).map(TimeSeriesCommand::CreateRule),
+            "ts.deleterule" => TsDeleteRule::new(args).map(TimeSeriesCommand::DeleteRule),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for TimeSeriesCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            TimeSeriesCommand::Create(cmd) => cmd.execute(db),
+            TimeSeriesCommand::Add(cmd) => cmd.execute(db),
+            TimeSeriesCommand::Madd(cmd) => cmd.execute(db),
+            TimeSeriesCommand::Range(cmd) => cmd.execute(db),
+            TimeSeriesCommand::Mrange(cmd) => cmd.execute(db),
+            TimeSeriesCommand::CreateRule(cmd) => cmd.execute(db),
+            TimeSeriesCommand::DeleteRule(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn tsdb_error(msg: &str) -> RedisError {
+    RedisError::Custom(format!("TSDB: {msg}"))
+}
+
+fn get_series<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut TimeSeries>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_timeseries_mut())
+        .transpose()
+}
+
+fn get_existing_series<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<&'a mut TimeSeries, RedisError> {
+    get_series(db, key)?.ok_or(tsdb_error("the key does not exist"))
+}
+
+fn read_existing_series<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<&'a mut TimeSeries, RedisError> {
+    db.read_value(key)
+        .ok_or(tsdb_error("the key does not exist"))?
+        .as_timeseries_mut()
+}
+
+fn parse_timestamp(s: &str) -> Result<i64, RedisError> {
+    s.parse::<i64>()
+        .ok()
+        .filter(|ts| *ts >= 0)
+        .ok_or(tsdb_error("invalid timestamp"))
+}
+
+/// Parses the timestamp of a new sample, where `*` is the current time.
+fn parse_sample_timestamp(s: &str) -> Result<Option<i64>, RedisError> {
+    match s {
+        "*" => Ok(None),
+        _ => parse_timestamp(s).map(Some),
+    }
+}
+
+fn parse_value(s: &str) -> Result<f64, RedisError> {
+    parse_float(s).map_err(|_| tsdb_error("invalid value"))
+}
+
+fn now() -> i64 {
+    to_unix_millis(SystemTime::now()) as i64
+}
+
+/// Parses `AGGREGATION aggregator bucketDuration`, starting after the keyword.
+fn parse_aggregation(aggregator: &str, duration: &str) -> Result<(Aggregation, i64), RedisError> {
+    let aggregation =
+        Aggregation::parse(aggregator).ok_or(tsdb_error("Unknown aggregation type"))?;
+    let duration = parse_int(duration)
+        .ok()
+        .filter(|d| *d > 0)
+        .ok_or(tsdb_error("bucketDuration must be greater than zero"))?;
+    Ok((aggregation, duration))
+}
+
+fn sample_reply((ts, value): (i64, f64)) -> Frame {
+    Frame::Array(vec![
+        Frame::Integer(ts),
+        Frame::SimpleString(format_float(value)),
+    ])
+}
+
+/// Options of TS.CREATE, also accepted by TS.ADD to create the series.
+#[derive(Debug, Default)]
+struct SeriesOptions {
+    retention: Option<i64>,
+    chunk_size: Option<usize>,
+    duplicate_policy: Option<DuplicatePolicy>,
+    on_duplicate: Option<DuplicatePolicy>,
+    labels: Vec<(String, String)>,
+}
+
+impl SeriesOptions {
+    /// Parses the options in `args`. `ON_DUPLICATE` only applies to adding a sample.
+    fn parse(args: &[String], on_duplicate: bool) -> Result<Self, RedisError> {
+        let mut options = SeriesOptions::default();
+
+        let mut rest = args.iter();
+        while let Some(opt) = rest.next() {
+            let opt = opt.to_lowercase();
+            if opt == "labels" {
+                let labels: Vec<_> = rest.by_ref().collect();
+                if labels.is_empty() || !labels.len().is_multiple_of(2) {
+                    return Err(RedisError::Syntax);
+                }
+                options.labels = labels
+                    .chunks_exact(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+                    .collect();
+                break;
+            }
+
+            let value = rest.next().ok_or(RedisError::Syntax)?;
+            match opt.as_str() {
+                "retention" => {
+                    let retention = parse_int(value)
+                        .ok()
+                        .filter(|r| *r >= 0)
+                        .ok_or(tsdb_error("Couldn't parse RETENTION"))?;
+                    options.retention = Some(retention);
+                }
+                "chunk_size" => {
+                    let size = parse_int(value)
+                        .ok()
+                        .filter(|s| (48..=1_048_576).contains(s) && (*s as u64).is_multiple_of(8))
+                        .ok_or(tsdb_error(
+                            "CHUNK_SIZE value must be a multiple of 8 in the range [48 .. 1048576]",
+                        ))?;
+                    options.chunk_size = Some(size as usize);
+                }
+                "duplicate_policy" | "on_duplicate" => {
+                    let policy = DuplicatePolicy::parse(value)
+                        .ok_or(tsdb_error("Unknown DUPLICATE_POLICY"))?;
+                    match opt.as_str() {
+                        "on_duplicate" if on_duplicate => options.on_duplicate = Some(policy),
+                        "duplicate_policy" => options.duplicate_policy = Some(policy),
+                        _ => return Err(RedisError::Syntax),
+                    }
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn create(&self) -> TimeSeries {
+        let mut series = TimeSeries::new(
+            self.retention.unwrap_or(0),
+            self.duplicate_policy.unwrap_or(DuplicatePolicy::Block),
+        );
+        series.chunk_size = self.chunk_size.unwrap_or(timeseries::DEFAULT_CHUNK_SIZE);
+        series.labels = self.labels.clone();
+        series
+    }
+}
+
+/// `TS.CREATE key [RETENTION retention] [CHUNK_SIZE size] [DUPLICATE_POLICY policy]
+/// [LABELS label value ...]`
+#[derive(Debug)]
+pub(crate) struct TsCreate {
+    key: String,
+    options: SeriesOptions,
+}
+
+impl TsCreate {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(TsCreate {
+            key: args[1].clone(),
+            options: SeriesOptions::parse(&args[2..], false)?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        if db.get_value(&self.key).is_some() {
+            return Err(tsdb_error("key already exists"));
+        }
+
+        let series = self.options.create();
+        db.insert(&self.key, Value::from(Data::TimeSeries(Box::new(series))));
+        db.touch(&self.key);
+        db.notify(notify::MODULE, "ts.create", &self.key);
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
+
+/// Adds a sample to the series at `key` and writes the buckets it closes into the
+/// destinations of the compaction rules.
+fn add_sample(
+    db: &mut dyn Database,
+    key: &str,
+    ts: i64,
+    value: f64,
+    policy: Option<DuplicatePolicy>,
+) -> Result<(), RedisError> {
+    let series = get_existing_series(db, key)?;
+    series.add(ts, value, policy).map_err(|err| match err {
+        timeseries::Error::Duplicate => tsdb_error(
+            "Error at upsert, update is not supported when DUPLICATE_POLICY is set to BLOCK mode",
+        ),
+        timeseries::Error::TooOld => tsdb_error("Timestamp is older than retention"),
+    })?;
+    let writes = series.compact(ts);
+    db.touch(key);
+    db.notify(notify::MODULE, "ts.add", key);
+
+    for (destination, start, value) in writes {
+        // Rules whose destination was deleted are kept, but have nowhere to write.
+        if let Ok(Some(series)) = get_series(db, &destination) {
+            if series
+                .add(start, value, Some(DuplicatePolicy::Last))
+                .is_ok()
+            {
+                db.touch(&destination);
+                db.notify(notify::MODULE, "ts.add", &destination);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `TS.ADD key timestamp value [RETENTION retention] [CHUNK_SIZE size]
+/// [DUPLICATE_POLICY policy] [ON_DUPLICATE policy] [LABELS label value ...]`, which creates
+/// the series with the given options if it does not exist.
+#[derive(Debug)]
+pub(crate) struct TsAdd {
+    args: Vec<String>,
+    timestamp: Option<i64>,
+    value: f64,
+    options: SeriesOptions,
+}
+
+impl TsAdd {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Ok(TsAdd {
+            timestamp: parse_sample_timestamp(&args[2])?,
+            value: parse_value(&args[3])?,
+            options: SeriesOptions::parse(&args[4..], true)?,
+            args,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let key = &self.args[1];
+        if get_series(db, key)?.is_none() {
+            let series = self.options.create();
+            db.insert(key, Value::from(Data::TimeSeries(Box::new(series))));
+        }
+
+        let ts = self.timestamp.unwrap_or_else(now);
+        add_sample(db, key, ts, self.value, self.options.on_duplicate)?;
+
+        // Replicas must store the sample at the same time.
+        if self.timestamp.is_none() {
+            let mut frame = self.args.clone();
+            frame[2] = ts.to_string();
+            db.rewrite(Frame::Arrays(frame));
+        }
+
+        Ok(Frame::Integer(ts))
+    }
+}
+
+/// `TS.MADD key timestamp value [key timestamp value ...]`, replying with the timestamp of
+/// each sample or the error that prevented adding it.
+#[derive(Debug)]
+pub(crate) struct TsMadd {
+    samples: Vec<(String, Option<i64>, f64)>,
+}
+
+impl TsMadd {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+        if !(args.len() - 1).is_multiple_of(3) {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        let samples = args[1..]
+            .chunks_exact(3)
+            .map(|s| {
+                Ok((
+                    s[0].clone(),
+                    parse_sample_timestamp(&s[1])?,
+                    parse_value(&s[2])?,
+                ))
+            })
+            .collect::<Result<_, RedisError>>()?;
+
+        Ok(TsMadd { samples })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let now = now();
+        let mut replies = Vec::new();
+        let mut frame = vec![String::from("TS.MADD")];
+
+        for (key, timestamp, value) in &self.samples {
+            let ts = timestamp.unwrap_or(now);
+            replies.push(match add_sample(db, key, ts, *value, None) {
+                Ok(()) => Frame::Integer(ts),
+                Err(err) => Frame::from(err),
+            });
+            frame.extend([key.clone(), ts.to_string(), format_float(*value)]);
+        }
+
+        if self.samples.iter().any(|(_, ts, _)| ts.is_none()) {
+            db.rewrite(Frame::Arrays(frame));
+        }
+
+        Ok(Frame::Array(replies))
+    }
+}
+
+/// Where buckets start when aggregating a range.
+#[derive(Debug, Clone, Copy)]
+enum Align {
+    Start,
+    End,
+    At(i64),
+}
+
+/// Options of TS.RANGE, TS.REVRANGE and TS.MRANGE that select and aggregate samples.
+#[derive(Debug)]
+struct RangeOptions {
+    from: i64,
+    to: i64,
+    count: Option<usize>,
+    align: Option<Align>,
+    aggregation: Option<(Aggregation, i64)>,
+    filter_by_ts: Option<Vec<i64>>,
+    filter_by_value: Option<(f64, f64)>,
+}
+
+impl RangeOptions {
+    fn new(from: &str, to: &str) -> Result<Self, RedisError> {
+        let from = match from {
+            "-" => 0,
+            _ => parse_timestamp(from).map_err(|_| tsdb_error("wrong fromTimestamp"))?,
+        };
+        let to = match to {
+            "+" => i64::MAX,
+            _ => parse_timestamp(to).map_err(|_| tsdb_error("wrong toTimestamp"))?,
+        };
+
+        Ok(RangeOptions {
+            from,
+            to,
+            count: None,
+            align: None,
+            aggregation: None,
+            filter_by_ts: None,
+            filter_by_value: None,
+        })
+    }
+
+    /// Parses the option at `args[*index]` if it is a range option, advancing past it.
+    fn parse_option(&mut self, args: &[String], index: &mut usize) -> Result<bool, RedisError> {
+        let arg = |i: usize| args.get(i).ok_or(RedisError::Syntax);
+
+        match args[*index].to_lowercase().as_str() {
+            "count" => {
+                let count = parse_int(arg(*index + 1)?)
+                    .ok()
+                    .filter(|c| *c > 0)
+                    .ok_or(tsdb_error("Invalid COUNT value"))?;
+                self.count = Some(count as usize);
+                *index += 2;
+            }
+            "align" => {
+                self.align = Some(match arg(*index + 1)?.to_lowercase().as_str() {
+                    "start" | "-" => Align::Start,
+                    "end" | "+" => Align::End,
+                    s => {
+                        Align::At(parse_int(s).map_err(|_| tsdb_error("unknown ALIGN parameter"))?)
+                    }
+                });
+                *index += 2;
+            }
+            "aggregation" => {
+                self.aggregation = Some(parse_aggregation(arg(*index + 1)?, arg(*index + 2)?)?);
+                *index += 3;
+            }
+            "filter_by_ts" => {
+                let timestamps: Vec<_> = args[*index + 1..]
+                    .iter()
+                    .map_while(|s| parse_timestamp(s).ok())
+                    .collect();
+                if timestamps.is_empty() {
+                    return Err(RedisError::Syntax);
+                }
+                *index += 1 + timestamps.len();
+                self.filter_by_ts = Some(timestamps);
+            }
+            "filter_by_value" => {
+                let min = parse_value(arg(*index + 1)?)?;
+                let max = parse_value(arg(*index + 2)?)?;
+                self.filter_by_value = Some((min, max));
+                *index += 3;
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    fn validate(&self) -> Result<(), RedisError> {
+        if self.align.is_some() && self.aggregation.is_none() {
+            return Err(tsdb_error(
+                "ALIGN parameter can only be used with AGGREGATION",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Selects the samples of `series`, newest first if `reverse`.
+    fn query(&self, series: &TimeSeries, reverse: bool) -> Vec<(i64, f64)> {
+        let mut samples = series.range(self.from..=self.to);
+
+        if let Some(timestamps) = &self.filter_by_ts {
+            samples.retain(|(ts, _)| timestamps.contains(ts));
+        }
+        if let Some((min, max)) = self.filter_by_value {
+            samples.retain(|(_, v)| (min..=max).contains(v));
+        }
+
+        if let Some((aggregation, duration)) = self.aggregation {
+            let align = match self.align {
+                None => 0,
+                Some(Align::Start) => self.from,
+                Some(Align::End) => self.to,
+                Some(Align::At(ts)) => ts,
+            };
+            samples = timeseries::aggregate(&samples, aggregation, duration, align);
+        }
+
+        if reverse {
+            samples.reverse();
+        }
+        if let Some(count) = self.count {
+            samples.truncate(count);
+        }
+
+        samples
+    }
+}
+
+/// `TS.RANGE key fromTimestamp toTimestamp [FILTER_BY_TS ts ...] [FILTER_BY_VALUE min max]
+/// [COUNT count] [ALIGN align] [AGGREGATION aggregator bucketDuration]`, and `TS.REVRANGE`
+/// which replies with the newest samples first.
+#[derive(Debug)]
+pub(crate) struct TsRange {
+    key: String,
+    options: RangeOptions,
+    reverse: bool,
+}
+
+impl TsRange {
+    pub(crate) fn new(args: Vec<String>, reverse: bool) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let mut options = RangeOptions::new(&args[2], &args[3])?;
+        let mut index = 4;
+        while index < args.len() {
+            if !options.parse_option(&args, &mut index)? {
+                return Err(RedisError::Syntax);
+            }
+        }
+        options.validate()?;
+
+        Ok(TsRange {
+            key: args[1].clone(),
+            options,
+            reverse,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let series = read_existing_series(db, &self.key)?;
+        let samples = self.options.query(series, self.reverse);
+
+        Ok(Frame::Array(
+            samples.into_iter().map(sample_reply).collect(),
+        ))
+    }
+}
+
+/// A `label=value` expression of TS.MRANGE. Missing labels compare as empty, so `label=`
+/// matches series without the label and `label!=` those with it.
+#[derive(Debug)]
+struct Matcher {
+    label: String,
+    values: Vec<String>,
+    negated: bool,
+}
+
+impl Matcher {
+    fn parse(s: &str) -> Result<Self, RedisError> {
+        let (label, values, negated) = match s.split_once("!=") {
+            Some((label, values)) => (label, values, true),
+            None => match s.split_once('=') {
+                Some((label, values)) => (label, values, false),
+                None => return Err(tsdb_error("failed parsing labels")),
+            },
+        };
+
+        let values = match values.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+            Some(list) => list.split(',').map(|v| v.trim().to_owned()).collect(),
+            None => vec![values.to_owned()],
+        };
+
+        Ok(Matcher {
+            label: label.to_owned(),
+            values,
+            negated,
+        })
+    }
+
+    /// Whether the matcher selects series by a value of their label.
+    fn is_positive(&self) -> bool {
+        !self.negated && self.values.iter().any(|v| !v.is_empty())
+    }
+
+    fn matches(&self, series: &TimeSeries) -> bool {
+        let value = series.label(&self.label).unwrap_or_default();
+        self.values.iter().any(|v| v == value) != self.negated
+    }
+}
+
+/// `GROUPBY label REDUCE reducer`, which combines the series that share a value of `label`.
+#[derive(Debug)]
+struct GroupBy {
+    label: String,
+    reducer: Aggregation,
+}
+
+impl GroupBy {
+    fn parse(args: &[String]) -> Result<Self, RedisError> {
+        let [label, keyword, reducer] = args else {
+            return Err(RedisError::Syntax);
+        };
+        if !keyword.eq_ignore_ascii_case("reduce") {
+            return Err(RedisError::Syntax);
+        }
+
+        let reducer = Aggregation::parse(reducer)
+            .filter(|r| !matches!(r, Aggregation::First | Aggregation::Last))
+            .ok_or(tsdb_error("invalid reducer"))?;
+
+        Ok(GroupBy {
+            label: label.clone(),
+            reducer,
+        })
+    }
+}
+
+/// `TS.MRANGE fromTimestamp toTimestamp [range options] [WITHLABELS] FILTER filterExpr ...
+/// [GROUPBY label REDUCE reducer]`, which queries every series whose labels match all the
+/// filters.
+#[derive(Debug)]
+pub(crate) struct TsMrange {
+    options: RangeOptions,
+    with_labels: bool,
+    matchers: Vec<Matcher>,
+    group_by: Option<GroupBy>,
+}
+
+impl TsMrange {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+
+        let mut options = RangeOptions::new(&args[1], &args[2])?;
+        let mut with_labels = false;
+        let mut index = 3;
+        while index < args.len() && !args[index].eq_ignore_ascii_case("filter") {
+            if args[index].eq_ignore_ascii_case("withlabels") {
+                with_labels = true;
+                index += 1;
+            } else if !options.parse_option(&args, &mut index)? {
+                return Err(RedisError::Syntax);
+            }
+        }
+        options.validate()?;
+
+        let filters = args.get(index + 1..).unwrap_or_default();
+        let (filters, group_by) = match filters
+            .iter()
+            .position(|s| s.eq_ignore_ascii_case("groupby"))
+        {
+            Some(i) => (&filters[..i], Some(GroupBy::parse(&filters[i + 1..])?)),
+            None => (filters, None),
+        };
+        if filters.is_empty() {
+            return Err(RedisError::Syntax);
+        }
+
+        let matchers = filters
+            .iter()
+            .map(|s| Matcher::parse(s))
+            .collect::<Result<Vec<_>, RedisError>>()?;
+        if !matchers.iter().any(Matcher::is_positive) {
+            return Err(tsdb_error("please provide at least one matcher"));
+        }
+
+        Ok(TsMrange {
+            options,
+            with_labels,
+            matchers,
+            group_by,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let mut series: Vec<_> = db
+            .iter()
+            .filter_map(|(key, value)| match value.data() {
+                Data::TimeSeries(series) => Some((key, series)),
+                _ => None,
+            })
+            .filter(|(_, series)| self.matchers.iter().all(|m| m.matches(series)))
+            .collect();
+        series.sort_by_key(|(key, _)| *key);
+
+        let reply = |name: String, labels: &[(String, String)], samples: Vec<(i64, f64)>| {
+            let labels = match self.with_labels {
+                true => labels
+                    .iter()
+                    .map(|(name, value)| Frame::Arrays(vec![name.clone(), value.clone()]))
+                    .collect(),
+                false => Vec::new(),
+            };
+
+            Frame::Array(vec![
+                Frame::BulkString(name),
+                Frame::Array(labels),
+                Frame::Array(samples.into_iter().map(sample_reply).collect()),
+            ])
+        };
+
+        let Some(group_by) = &self.group_by else {
+            let replies = series
+                .into_iter()
+                .map(|(key, series)| {
+                    let samples = self.options.query(series, false);
+                    reply(key.to_owned(), &series.labels, samples)
+                })
+                .collect();
+
+            return Ok(Frame::Array(replies));
+        };
+
+        // Series without the label are left out of every group.
+        let mut groups: BTreeMap<&str, Vec<(&str, &TimeSeries)>> = BTreeMap::new();
+        for (key, series) in series {
+            if let Some(value) = series.label(&group_by.label) {
+                groups.entry(value).or_default().push((key, series));
+            }
+        }
+
+        let replies = groups
+            .into_iter()
+            .map(|(value, members)| {
+                let samples: Vec<_> = members
+                    .iter()
+                    .map(|(_, series)| self.options.query(series, false))
+                    .collect();
+                let sources: Vec<&str> = members.iter().map(|(key, _)| *key).collect();
+                let labels = [
+                    (group_by.label.clone(), value.to_owned()),
+                    (
+                        String::from("__reducer__"),
+                        group_by.reducer.name().to_owned(),
+                    ),
+                    (String::from("__source__"), sources.join(",")),
+                ];
+
+                reply(
+                    format!("{}={value}", group_by.label),
+                    &labels,
+                    timeseries::reduce(&samples, group_by.reducer),
+                )
+            })
+            .collect();
+
+        Ok(Frame::Array(replies))
+    }
+}
+
+/// `TS.CREATERULE sourceKey destKey AGGREGATION aggregator bucketDuration [alignTimestamp]`.
+/// Buckets are written to the destination once a sample arrives in a later bucket, and
+/// compactions cannot be chained.
+#[derive(Debug)]
+pub(crate) struct TsCreateRule {
+    source: String,
+    destination: String,
+    aggregation: Aggregation,
+    duration: i64,
+    align: i64,
+}
+
+impl TsCreateRule {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 6)?;
+        if args.len() > 7 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+        if !args[3].eq_ignore_ascii_case("aggregation") {
+            return Err(RedisError::Syntax);
+        }
+
+        let (aggregation, duration) = parse_aggregation(&args[4], &args[5])?;
+        let align = match args.get(6) {
+            Some(s) => parse_timestamp(s)?,
+            None => 0,
+        };
+
+        Ok(TsCreateRule {
+            source: args[1].clone(),
+            destination: args[2].clone(),
+            aggregation,
+            duration,
+            align,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        if self.source == self.destination {
+            return Err(tsdb_error(
+                "the source key and destination key should be different",
+            ));
+        }
+
+        let destination = get_existing_series(db, &self.destination)?;
+        if destination.source.is_some() {
+            return Err(tsdb_error("the destination key already has a src rule"));
+        }
+        if !destination.rules.is_empty() {
+            return Err(tsdb_error("the destination key already has a dst rule"));
+        }
+
+        let source = get_existing_series(db, &self.source)?;
+        if source.source.is_some() {
+            return Err(tsdb_error("the source key already has a source rule"));
+        }
+        source.rules.push(Rule {
+            destination: self.destination.clone(),
+            aggregation: self.aggregation,
+            duration: self.duration,
+            align: self.align,
+            open: None,
+        });
+
+        get_existing_series(db, &self.destination)?.source = Some(self.source.clone());
+        db.touch(&self.source);
+        db.touch(&self.destination);
+        db.notify(notify::MODULE, "ts.createrule", &self.source);
+        db.notify(notify::MODULE, "ts.createrule", &self.destination);
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
+
+/// `TS.DELETERULE sourceKey destKey`
+#[derive(Debug)]
+pub(crate) struct TsDeleteRule {
+    source: String,
+    destination: String,
+}
+
+impl TsDeleteRule {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+        if args.len() != 3 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(TsDeleteRule {
+            source: args[1].clone(),
+            destination: args[2].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let source = get_existing_series(db, &self.source)?;
+        let index = source
+            .rules
+            .iter()
+            .position(|rule| rule.destination == self.destination)
+            .ok_or(tsdb_error("compaction rule does not exist"))?;
+        source.rules.remove(index);
+        db.touch(&self.source);
+        db.notify(notify::MODULE, "ts.deleterule", &self.source);
+
+        if let Ok(Some(destination)) = get_series(db, &self.destination) {
+            destination.source = None;
+            db.touch(&self.destination);
+            db.notify(notify::MODULE, "ts.deleterule", &self.destination);
+        }
+
+        Ok(Frame::SimpleString(String::from("OK")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TimeSeriesCommand;
+    use crate::{cmd::Execute, db::KeyValueDb, error::RedisError, frame::Frame};
+
+    fn run(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        TimeSeriesCommand::parse(&args[0], args.clone())
+            .and_then(|cmd| cmd.execute(db))
+            .unwrap_or_else(Frame::from)
+    }
+
+    type Series = (String, Vec<(String, String)>, Vec<(i64, String)>);
+
+    /// Runs TS.MRANGE and flattens its reply into names, labels and samples.
+    fn mrange(db: &mut KeyValueDb, args: &[&str]) -> Vec<Series> {
+        let reply = run(db, &[&["ts.mrange", "-", "+"], args].concat());
+        let Frame::Array(replies) = reply else {
+            panic!("unexpected {reply:?}");
+        };
+
+        replies
+            .into_iter()
+            .map(|reply| match reply {
+                Frame::Array(reply) => match &reply[..] {
+                    [Frame::BulkString(name), Frame::Array(labels), Frame::Array(samples)] => {
+                        let labels = labels
+                            .iter()
+                            .map(|label| match label {
+                                Frame::Arrays(pair) => (pair[0].clone(), pair[1].clone()),
+                                label => panic!("unexpected {label:?}"),
+                            })
+                            .collect();
+                        let samples = samples
+                            .iter()
+                            .map(|sample| match sample {
+                                Frame::Array(sample) => match &sample[..] {
+                                    [Frame::Integer(ts), Frame::SimpleString(v)] => {
+                                        (*ts, v.clone())
+                                    }
+                                    sample => panic!("unexpected {sample:?}"),
+                                },
+                                sample => panic!("unexpected {sample:?}"),
+                            })
+                            .collect();
+                        (name.clone(), labels, samples)
+                    }
+                    reply => panic!("unexpected {reply:?}"),
+                },
+                reply => panic!("unexpected {reply:?}"),
+            })
+            .collect()
+    }
+
+    fn names(db: &mut KeyValueDb, filters: &[&str]) -> Vec<String> {
+        let args = [&["FILTER"], filters].concat();
+        mrange(db, &args).into_iter().map(|s| s.0).collect()
+    }
+
+    fn samples(samples: &[(i64, &str)]) -> Vec<(i64, String)> {
+        samples.iter().map(|(ts, v)| (*ts, v.to_string())).collect()
+    }
+
+    fn labels(labels: &[(&str, &str)]) -> Vec<(String, String)> {
+        labels
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn create(db: &mut KeyValueDb, key: &str, labels: &[&str], samples: &[(&str, &str)]) {
+        run(db, &[&["ts.create", key, "LABELS"], labels].concat());
+        for (ts, value) in samples {
+            run(db, &["ts.add", key, ts, value]);
+        }
+    }
+
+    /// `t1` and `t2` are temperatures in rooms a and b, `h1` a humidity in room a, and `t3`
+    /// a temperature without a room.
+    fn populated() -> KeyValueDb {
+        let mut db = KeyValueDb::new();
+        let temp = |room| ["type", "temp", "room", room];
+        create(
+            &mut db,
+            "t1",
+            &temp("a"),
+            &[("10", "1"), ("20", "2"), ("30", "3")],
+        );
+        create(
+            &mut db,
+            "t2",
+            &temp("b"),
+            &[("10", "5"), ("30", "7"), ("40", "9")],
+        );
+        create(
+            &mut db,
+            "h1",
+            &["type", "hum", "room", "a"],
+            &[("10", "50")],
+        );
+        create(
+            &mut db,
+            "t3",
+            &["type", "temp", "other", "x"],
+            &[("10", "100")],
+        );
+        db
+    }
+
+    #[test]
+    fn test_mrange_filters() {
+        let mut db = populated();
+
+        assert_eq!(vec!["t1", "t2", "t3"], names(&mut db, &["type=temp"]));
+        assert_eq!(vec!["h1", "t1"], names(&mut db, &["room=a"]));
+        assert_eq!(vec!["h1", "t1", "t2"], names(&mut db, &["room=(a,b)"]));
+        assert_eq!(vec!["h1", "t1", "t2"], names(&mut db, &["room=(a, b)"]));
+        assert_eq!(vec!["h1"], names(&mut db, &["type!=temp", "room=a"]));
+        assert_eq!(
+            vec!["t2", "t3"],
+            names(&mut db, &["type=temp", "room!=(a,c)"])
+        );
+
+        // `label=` matches series without the label, and `label!=` series with it.
+        assert_eq!(vec!["t3"], names(&mut db, &["type=temp", "room="]));
+        assert_eq!(vec!["t1", "t2"], names(&mut db, &["type=temp", "room!="]));
+        assert!(names(&mut db, &["type=temp", "room=c"]).is_empty());
+        assert!(names(&mut db, &["kind=temp"]).is_empty());
+
+        let tsdb_error = |msg: &str| Frame::from(RedisError::Custom(format!("TSDB: {msg}")));
+        let please = tsdb_error("please provide at least one matcher");
+        assert_eq!(
+            please,
+            run(&mut db, &["ts.mrange", "-", "+", "FILTER", "type!=temp"])
+        );
+        assert_eq!(
+            please,
+            run(
+                &mut db,
+                &["ts.mrange", "-", "+", "FILTER", "room=", "type!="]
+            )
+        );
+        assert_eq!(
+            tsdb_error("failed parsing labels"),
+            run(&mut db, &["ts.mrange", "-", "+", "FILTER", "type"])
+        );
+        let syntax = Frame::from(RedisError::Syntax);
+        assert_eq!(
+            syntax,
+            run(&mut db, &["ts.mrange", "-", "+", "COUNT", "1", "FILTER"])
+        );
+        assert_eq!(
+            syntax,
+            run(&mut db, &["ts.mrange", "-", "+", "COUNT", "1", "type=temp"])
+        );
+    }
+
+    #[test]
+    fn test_mrange_options() {
+        let mut db = populated();
+
+        let reply = mrange(
+            &mut db,
+            &["AGGREGATION", "sum", "20", "FILTER", "type=temp", "room!="],
+        );
+        assert_eq!(
+            vec![
+                (String::from("t1"), vec![], samples(&[(0, "1"), (20, "5")])),
+                (
+                    String::from("t2"),
+                    vec![],
+                    samples(&[(0, "5"), (20, "7"), (40, "9")])
+                ),
+            ],
+            reply
+        );
+
+        let reply = mrange(&mut db, &["COUNT", "1", "WITHLABELS", "FILTER", "room=b"]);
+        assert_eq!(
+            vec![(
+                String::from("t2"),
+                labels(&[("type", "temp"), ("room", "b")]),
+                samples(&[(10, "5")])
+            )],
+            reply
+        );
+
+        let reply = mrange(
+            &mut db,
+            &["FILTER_BY_VALUE", "2", "7", "FILTER", "room=(a,b)"],
+        );
+        let values: Vec<_> = reply.into_iter().map(|s| (s.0, s.2)).collect();
+        assert_eq!(
+            vec![
+                (String::from("h1"), vec![]),
+                (String::from("t1"), samples(&[(20, "2"), (30, "3")])),
+                (String::from("t2"), samples(&[(10, "5"), (30, "7")])),
+            ],
+            values
+        );
+
+        let reply = run(
+            &mut db,
+            &[
+                "ts.mrange",
+                "20",
+                "30",
+                "AGGREGATION",
+                "avg",
+                "100",
+                "ALIGN",
+                "start",
+                "FILTER",
+                "room=a",
+            ],
+        );
+        assert_eq!(
+            Frame::Array(vec![
+                Frame::Array(vec![
+                    Frame::BulkString(String::from("h1")),
+                    Frame::Array(vec![]),
+                    Frame::Array(vec![]),
+                ]),
+                Frame::Array(vec![
+                    Frame::BulkString(String::from("t1")),
+                    Frame::Array(vec![]),
+                    Frame::Array(vec![Frame::Array(vec![
+                        Frame::Integer(20),
+                        Frame::SimpleString(String::from("2.5")),
+                    ])]),
+                ]),
+            ]),
+            reply
+        );
+    }
+
+    #[test]
+    fn test_mrange_groupby() {
+        let mut db = populated();
+
+        // Series without the label are left out.
+        let reply = mrange(
+            &mut db,
+            &[
+                "WITHLABELS",
+                "FILTER",
+                "type=(temp,hum)",
+                "GROUPBY",
+                "room",
+                "REDUCE",
+                "sum",
+            ],
+        );
+        assert_eq!(
+            vec![
+                (
+                    String::from("room=a"),
+                    labels(&[
+                        ("room", "a"),
+                        ("__reducer__", "sum"),
+                        ("__source__", "h1,t1")
+                    ]),
+                    samples(&[(10, "51"), (20, "2"), (30, "3")])
+                ),
+                (
+                    String::from("room=b"),
+                    labels(&[("room", "b"), ("__reducer__", "sum"), ("__source__", "t2")]),
+                    samples(&[(10, "5"), (30, "7"), (40, "9")])
+                ),
+            ],
+            reply
+        );
+
+        // Each series is aggregated before the group is reduced.
+        let reply = mrange(
+            &mut db,
+            &[
+                "AGGREGATION",
+                "max",
+                "100",
+                "FILTER",
+                "type=temp",
+                "groupby",
+                "type",
+                "reduce",
+                "avg",
+            ],
+        );
+        assert_eq!(
+            vec![(
+                String::from("type=temp"),
+                vec![],
+                samples(&[(0, "37.333333333333336")])
+            )],
+            reply
+        );
+
+        let reply = mrange(
+            &mut db,
+            &["FILTER", "room=(a,b)", "GROUPBY", "type", "REDUCE", "count"],
+        );
+        let counts: Vec<_> = reply.into_iter().map(|s| (s.0, s.2)).collect();
+        assert_eq!(
+            vec![
+                (String::from("type=hum"), samples(&[(10, "1")])),
+                (
+                    String::from("type=temp"),
+                    samples(&[(10, "2"), (20, "1"), (30, "2"), (40, "1")])
+                ),
+            ],
+            counts
+        );
+
+        let syntax = Frame::from(RedisError::Syntax);
+        for groupby in [
+            &["GROUPBY"][..],
+            &["GROUPBY", "room"],
+            &["GROUPBY", "room", "REDUCER", "sum"],
+            &["GROUPBY", "room", "REDUCE", "sum", "x"],
+        ] {
+            let args = [&["ts.mrange", "-", "+", "FILTER", "type=temp"], groupby].concat();
+            assert_eq!(syntax, run(&mut db, &args), "{groupby:?}");
+        }
+        assert_eq!(
+            Frame::from(RedisError::Custom(String::from("TSDB: invalid reducer"))),
+            run(
+                &mut db,
+                &[
+                    "ts.mrange",
+                    "-",
+                    "+",
+                    "FILTER",
+                    "type=temp",
+                    "GROUPBY",
+                    "room",
+                    "REDUCE",
+                    "first"
+                ]
+            )
+        );
+    }
+}
@@ -0,0 +1,560 @@
+use crate::{
+    cmd::{check_arity, remove_if_empty, Execute},
+    db::{
+        expr::Expr,
+        json::Json,
+        notify,
+        vectorset::{
+            Filter, Metric, Quantization, SearchOptions, VectorSet, DEFAULT_EF_CONSTRUCTION,
+            DEFAULT_M,
+        },
+        Data, Database, Value,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::num::{format_float, parse_float, parse_int},
+};
+
+#[derive(Debug)]
+pub(crate) enum VectorSetCommand {
+    Add(VAdd),
+    Sim(VSim),
+    Rem(VRem),
+    Card(VCard),
+    Dim(VDim),
+    Emb(VEmb),
+}
+
+impl VectorSetCommand {
+    /// Parses the command from its arguments, where `raw` holds their exact bytes for the
+    /// vectors given as blobs.
+    pub(crate) fn parse(
+        cmd: &str,
+        args: Vec<String>,
+        raw: Vec<Vec<u8>>,
+    ) -> Result<Self, RedisError> {
+        match cmd {
+            "vadd" => VAdd::new(args, &raw).map(VectorSetCommand::Add),
+            "vsim" => VSim::new(args, &raw).map(VectorSetCommand::Sim),
+            "vrem" => VRem::new(args).map(VectorSetCommand::Rem),
+            "vcard" => VCard::new(args).map(VectorSetCommand::Card),
+            "vdim" => VDim::new(args).map(VectorSetCommand::Dim),
+            "vemb" => VEmb::new(args).map(VectorSetCommand::Emb),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for VectorSetCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            VectorSetCommand::Add(cmd) => cmd.execute(db),
+            VectorSetCommand::Sim(cmd) => cmd.execute(db),
+            VectorSetCommand::Rem(cmd) => cmd.execute(db),
+            VectorSetCommand::Card(cmd) => cmd.execute(db),
+            VectorSetCommand::Dim(cmd) => cmd.execute(db),
+            VectorSetCommand::Emb(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+fn get_vector_set<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut VectorSet>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_vector_set_mut())
+        .transpose()
+}
+
+fn read_vector_set<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut VectorSet>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_vector_set_mut())
+        .transpose()
+}
+
+fn parse_count(s: &str, name: &str) -> Result<usize, RedisError> {
+    parse_int(s)
+        .ok()
+        .and_then(|n| usize::try_from(n).ok())
+        .filter(|n| *n > 0)
+        .ok_or(RedisError::Custom(format!("invalid {name}")))
+}
+
+/// Parses a vector given as `FP32 blob` or `VALUES count value ...` at `args[*i]`, moving
+/// `i` past it. The blob is read from `raw`, the arguments as they were received.
+fn parse_vector(args: &[String], raw: &[Vec<u8>], i: &mut usize) -> Result<Vec<f32>, RedisError> {
+    match args.get(*i).map(|s| s.to_lowercase()).as_deref() {
+        Some("fp32") => {
+            let blob = raw.get(*i + 1).ok_or(RedisError::Syntax)?;
+            if blob.is_empty() || !blob.len().is_multiple_of(4) {
+                return Err(RedisError::Custom(String::from(
+                    "invalid vector specification",
+                )));
+            }
+            *i += 2;
+            Ok(blob
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect())
+        }
+        Some("values") => {
+            let count = args
+                .get(*i + 1)
+                .ok_or(RedisError::Syntax)
+                .and_then(|s| parse_count(s, "vector dimension"))?;
+            let values = args
+                .get(*i + 2..*i + 2 + count)
+                .ok_or(RedisError::Syntax)?
+                .iter()
+                .map(|s| {
+                    parse_float(s)
+                        .ok()
+                        .filter(|f| f.is_finite())
+                        .map(|f| f as f32)
+                        .ok_or(RedisError::Custom(String::from("invalid vector value")))
+                })
+                .collect::<Result<_, _>>()?;
+            *i += 2 + count;
+            Ok(values)
+        }
+        _ => Err(RedisError::Syntax),
+    }
+}
+
+/// `VADD key (FP32 blob | VALUES count value ...) element [NOQUANT | Q8] [EF
+/// build-exploration-factor] [M links] [SETATTR attributes] [METRIC (COSINE | L2)]`
+///
+/// The quantization, metric and number of links are those of the set once it exists.
+#[derive(Debug)]
+pub(crate) struct VAdd {
+    args: Vec<String>,
+    key: String,
+    vector: Vec<f32>,
+    element: String,
+    quantization: Option<Quantization>,
+    metric: Option<Metric>,
+    ef: usize,
+    m: usize,
+    attributes: Option<String>,
+    /// Whether the vector was given as a blob, which is replicated as values instead so
+    /// that it goes through the text protocol unchanged.
+    blob: bool,
+}
+
+impl VAdd {
+    pub(crate) fn new(args: Vec<String>, raw: &[Vec<u8>]) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+
+        let mut i = 2;
+        let blob = args[i].eq_ignore_ascii_case("fp32");
+        let vector = parse_vector(&args, raw, &mut i)?;
+        let element = args.get(i).ok_or(RedisError::Syntax)?.clone();
+        i += 1;
+
+        let mut cmd = VAdd {
+            args: args.clone(),
+            key: args[1].clone(),
+            vector,
+            element,
+            quantization: None,
+            metric: None,
+            ef: DEFAULT_EF_CONSTRUCTION,
+            m: DEFAULT_M,
+            attributes: None,
+            blob,
+        };
+
+        while i < args.len() {
+            let value = args.get(i + 1);
+            match args[i].to_lowercase().as_str() {
+                "noquant" => cmd.quantization = Some(Quantization::None),
+                "q8" => cmd.quantization = Some(Quantization::Q8),
+                // Every write is applied before the next command, so there is nothing to
+                // check and set.
+                "cas" => {}
+                option => {
+                    let value = value.ok_or(RedisError::Syntax)?;
+                    match option {
+                        "ef" => cmd.ef = parse_count(value, "EF")?,
+                        "m" => cmd.m = parse_count(value, "M")?,
+                        "setattr" => {
+                            if !value.is_empty() {
+                                Json::parse(value).map_err(|_| {
+                                    RedisError::Custom(String::from("invalid JSON in SETATTR"))
+                                })?;
+                            }
+                            cmd.attributes = Some(value.clone());
+                        }
+                        "metric" => {
+                            cmd.metric = Some(match value.to_lowercase().as_str() {
+                                "cosine" => Metric::Cosine,
+                                "l2" => Metric::L2,
+                                _ => return Err(RedisError::Syntax),
+                            })
+                        }
+                        _ => return Err(RedisError::Syntax),
+                    }
+                    i += 1;
+                }
+            }
+            i += 1;
+        }
+
+        Ok(cmd)
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let set = match get_vector_set(db, &self.key)? {
+            Some(set) => set,
+            None => {
+                let mut set = VectorSet::new(
+                    self.vector.len(),
+                    self.metric.unwrap_or(Metric::Cosine),
+                    self.quantization.unwrap_or(Quantization::None),
+                );
+                set.set_graph_options(self.m, self.ef);
+                db.insert(&self.key, Value::from(Data::VectorSet(Box::new(set))));
+                get_vector_set(db, &self.key)?.expect("set was just inserted")
+            }
+        };
+
+        if set.dim() != self.vector.len() {
+            return Err(RedisError::Custom(format!(
+                "Vector dimension mismatch - got {} but set has {}",
+                self.vector.len(),
+                set.dim()
+            )));
+        }
+        if self.quantization.is_some_and(|q| q != set.quantization()) {
+            return Err(RedisError::Custom(String::from(
+                "asked quantization mismatch with existing vector set",
+            )));
+        }
+        if self.metric.is_some_and(|m| m != set.metric()) {
+            return Err(RedisError::Custom(String::from(
+                "asked metric mismatch with existing vector set",
+            )));
+        }
+
+        // An empty SETATTR removes the attributes.
+        let attributes = self.attributes.clone().filter(|a| !a.is_empty());
+        let added = set.add(&self.element, &self.vector, attributes);
+        if self.attributes.as_deref() == Some("") {
+            set.set_attributes(&self.element, None);
+        }
+        db.touch(&self.key);
+        db.notify(notify::MODULE, "vadd", &self.key);
+
+        if self.blob {
+            let mut frame = self.args[..2].to_vec();
+            frame.push(String::from("VALUES"));
+            frame.push(self.vector.len().to_string());
+            frame.extend(self.vector.iter().map(|x| x.to_string()));
+            frame.extend_from_slice(&self.args[4..]);
+            db.rewrite(Frame::Arrays(frame));
+        }
+
+        Ok(Frame::Integer(added as i64))
+    }
+}
+
+/// The query of `VSIM`, by vector or by the vector of an element of the set.
+#[derive(Debug)]
+enum Query {
+    Vector(Vec<f32>),
+    Element(String),
+}
+
+/// `VSIM key (ELE element | FP32 blob | VALUES count value ...) [WITHSCORES] [WITHATTRIBS]
+/// [COUNT count] [EF search-exploration-factor] [FILTER expression] [FILTER-EF
+/// max-filtering-effort] [TRUTH]`
+///
+/// Scores are the cosine similarity scaled from 0 to 1, or the Euclidean distance with the
+/// L2 metric.
+#[derive(Debug)]
+pub(crate) struct VSim {
+    key: String,
+    query: Query,
+    with_scores: bool,
+    with_attributes: bool,
+    count: usize,
+    ef: usize,
+    filter: Option<Expr>,
+    filter_ef: Option<usize>,
+    truth: bool,
+}
+
+impl VSim {
+    pub(crate) fn new(args: Vec<String>, raw: &[Vec<u8>]) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let mut i = 2;
+        let query = match args[i].eq_ignore_ascii_case("ele") {
+            true => {
+                i += 2;
+                Query::Element(args[3].clone())
+            }
+            false => Query::Vector(parse_vector(&args, raw, &mut i)?),
+        };
+
+        let mut cmd = VSim {
+            key: args[1].clone(),
+            query,
+            with_scores: false,
+            with_attributes: false,
+            count: 10,
+            ef: DEFAULT_EF_CONSTRUCTION,
+            filter: None,
+            filter_ef: None,
+            truth: false,
+        };
+
+        while i < args.len() {
+            let value = args.get(i + 1);
+            match args[i].to_lowercase().as_str() {
+                "withscores" => cmd.with_scores = true,
+                "withattribs" => cmd.with_attributes = true,
+                "truth" => cmd.truth = true,
+                "nothread" => {}
+                option => {
+                    let value = value.ok_or(RedisError::Syntax)?;
+                    match option {
+                        "count" => cmd.count = parse_count(value, "COUNT")?,
+                        "ef" => cmd.ef = parse_count(value, "EF")?,
+                        "filter-ef" => cmd.filter_ef = Some(parse_count(value, "FILTER-EF")?),
+                        "filter" => {
+                            let expr = Expr::parse(value).map_err(|e| {
+                                RedisError::Custom(format!(
+                                    "syntax error in FILTER expression: {e}"
+                                ))
+                            })?;
+                            cmd.filter = Some(expr);
+                        }
+                        _ => return Err(RedisError::Syntax),
+                    }
+                    i += 1;
+                }
+            }
+            i += 1;
+        }
+
+        Ok(cmd)
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(set) = read_vector_set(db, &self.key)? else {
+            return Ok(Frame::Array(Vec::new()));
+        };
+
+        let query = match &self.query {
+            Query::Vector(vector) if vector.len() != set.dim() => {
+                return Err(RedisError::Custom(format!(
+                    "Vector dimension mismatch - got {} but set has {}",
+                    vector.len(),
+                    set.dim()
+                )))
+            }
+            Query::Vector(vector) => vector.clone(),
+            Query::Element(element) => set
+                .embedding(element)
+                .ok_or(RedisError::Custom(String::from("element not found in set")))?,
+        };
+
+        let options = SearchOptions {
+            count: self.count,
+            ef: self.ef,
+            filter_ef: self.filter_ef.unwrap_or(self.count * 100),
+            exact: self.truth,
+        };
+        let matches = |attributes: Option<&str>| {
+            let json = attributes.and_then(|a| Json::parse(a).ok());
+            self.filter
+                .as_ref()
+                .is_some_and(|filter| filter.matches(json.as_ref()))
+        };
+        let filter = self.filter.as_ref().map(|_| &matches as Filter);
+
+        let set = &*set;
+        let mut reply = Vec::new();
+        for (element, distance) in set.search(&query, options, filter) {
+            reply.push(Frame::BulkString(element.to_owned()));
+            if self.with_scores {
+                reply.push(Frame::BulkString(format_float(set.score(distance))));
+            }
+            if self.with_attributes {
+                reply.push(match set.attributes(element) {
+                    Some(attributes) => Frame::BulkString(attributes.to_owned()),
+                    None => Frame::Null,
+                });
+            }
+        }
+
+        Ok(Frame::Array(reply))
+    }
+}
+
+/// `VREM key element`
+#[derive(Debug)]
+pub(crate) struct VRem {
+    key: String,
+    element: String,
+}
+
+impl VRem {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+        if args.len() != 3 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(VRem {
+            key: args[1].clone(),
+            element: args[2].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(set) = get_vector_set(db, &self.key)? else {
+            return Ok(Frame::Integer(0));
+        };
+
+        let removed = set.remove(&self.element);
+        if removed {
+            db.touch(&self.key);
+            db.notify(notify::MODULE, "vrem", &self.key);
+            remove_if_empty(db, &self.key);
+        }
+
+        Ok(Frame::Integer(removed as i64))
+    }
+}
+
+/// `VCARD key`
+#[derive(Debug)]
+pub(crate) struct VCard {
+    key: String,
+}
+
+impl VCard {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+        if args.len() != 2 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(VCard {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let len = read_vector_set(db, &self.key)?.map_or(0, |set| set.len());
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+/// `VDIM key`
+#[derive(Debug)]
+pub(crate) struct VDim {
+    key: String,
+}
+
+impl VDim {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+        if args.len() != 2 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(VDim {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let set = read_vector_set(db, &self.key)?
+            .ok_or(RedisError::Custom(String::from("key does not exist")))?;
+
+        Ok(Frame::Integer(set.dim() as i64))
+    }
+}
+
+/// `VEMB key element`, replying with the vector of the element as it was added, up to the
+/// loss of quantization.
+#[derive(Debug)]
+pub(crate) struct VEmb {
+    key: String,
+    element: String,
+}
+
+impl VEmb {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+        if args.len() != 3 {
+            return Err(RedisError::WrongArity(args[0].to_lowercase()));
+        }
+
+        Ok(VEmb {
+            key: args[1].clone(),
+            element: args[2].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let vector = read_vector_set(db, &self.key)?.and_then(|set| set.embedding(&self.element));
+
+        Ok(match vector {
+            Some(vector) => Frame::Array(
+                vector
+                    .into_iter()
+                    .map(|x| Frame::BulkString(format_float(x as f64)))
+                    .collect(),
+            ),
+            None => Frame::NullArray,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        cmd::Command, db::KeyValueDb, frame::Frame, module::Registry, util::num::format_float,
+    };
+
+    fn run(db: &mut KeyValueDb, args: &[&[u8]]) -> Frame {
+        let mut request = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            request.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            request.extend_from_slice(arg);
+            request.extend_from_slice(b"\r\n");
+        }
+
+        let frame = Frame::parse(&request).unwrap();
+        let cmd = Command::parse(&frame, &Registry::default());
+        cmd.as_execute().unwrap().execute(db).unwrap()
+    }
+
+    #[test]
+    fn test_fp32_round_trip() {
+        let vector = [-1.5f32, 0.25, 1e-40, 3.0];
+        let blob: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+        assert!(std::str::from_utf8(&blob).is_err());
+
+        let mut db = KeyValueDb::new();
+        let args: [&[u8]; 8] = [
+            b"VADD", b"v", b"FP32", &blob, b"a", b"NOQUANT", b"METRIC", b"L2",
+        ];
+        assert_eq!(Frame::Integer(1), run(&mut db, &args));
+
+        let expected = vector
+            .iter()
+            .map(|x| Frame::BulkString(format_float(*x as f64)))
+            .collect();
+        assert_eq!(Frame::Array(expected), run(&mut db, &[b"VEMB", b"v", b"a"]));
+    }
+}
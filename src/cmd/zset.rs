@@ -0,0 +1,1477 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use crate::{
+    cmd::{check_arity, parse_timeout, remove_if_empty, scan_reply, Block, Execute, ScanOptions},
+    db::{
+        notify,
+        skiplist::{LexBound, LexRange, ScoreRange},
+        zset::SortedSet,
+        Data, Database, Value,
+    },
+    error::RedisError,
+    frame::Frame,
+    util::{
+        num::{format_float, parse_float, parse_int},
+        rand::sample,
+    },
+};
+
+#[derive(Debug)]
+pub(crate) enum SortedSetCommand {
+    Zadd(Zadd),
+    Zincrby(Zincrby),
+    Zrem(Zrem),
+    Zcard(Zcard),
+    Zscore(Zscore),
+    Zmscore(Zmscore),
+    Zrank(Zrank),
+    Zcount(Zcount),
+    Zrange(Zrange),
+    Zpop(Zpop),
+    Zrandmember(Zrandmember),
+    Zscan(Zscan),
+    Zstore(Zstore),
+    Zmpop(Zmpop),
+}
+
+impl SortedSetCommand {
+    pub(crate) fn parse(cmd: &str, args: Vec<String>) -> Result<Self, RedisError> {
+        match cmd {
+            "zadd" => Zadd::new(args).map(SortedSetCommand::Zadd),
+            "zincrby" => Zincrby::new(args).map(SortedSetCommand::Zincrby),
+            "zrem" => Zrem::new(args).map(SortedSetCommand::Zrem),
+            "zcard" => Zcard::new(args).map(SortedSetCommand::Zcard),
+            "zscore" => Zscore::new(args).map(SortedSetCommand::Zscore),
+            "zmscore" => Zmscore::new(args).map(SortedSetCommand::Zmscore),
+            "zrank" | "zrevrank" => Zrank::new(args).map(SortedSetCommand::Zrank),
+            "zcount" => Zcount::new(args).map(SortedSetCommand::Zcount),
+            "zrange" => Zrange::new(args).map(SortedSetCommand::Zrange),
+            "zpopmin" | "zpopmax" => Zpop::new(args).map(SortedSetCommand::Zpop),
+            "zrandmember" => Zrandmember::new(args).map(SortedSetCommand::Zrandmember),
+            "zscan" => Zscan::new(args).map(SortedSetCommand::Zscan),
+            "zunionstore" | "zinterstore" | "zdiffstore" => {
+                Zstore::new(args).map(SortedSetCommand::Zstore)
+            }
+            "zmpop" => Zmpop::new(args).map(SortedSetCommand::Zmpop),
+            _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+        }
+    }
+}
+
+impl Execute for SortedSetCommand {
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        match self {
+            SortedSetCommand::Zadd(cmd) => cmd.execute(db),
+            SortedSetCommand::Zincrby(cmd) => cmd.execute(db),
+            SortedSetCommand::Zrem(cmd) => cmd.execute(db),
+            SortedSetCommand::Zcard(cmd) => cmd.execute(db),
+            SortedSetCommand::Zscore(cmd) => cmd.execute(db),
+            SortedSetCommand::Zmscore(cmd) => cmd.execute(db),
+            SortedSetCommand::Zrank(cmd) => cmd.execute(db),
+            SortedSetCommand::Zcount(cmd) => cmd.execute(db),
+            SortedSetCommand::Zrange(cmd) => cmd.execute(db),
+            SortedSetCommand::Zpop(cmd) => cmd.execute(db),
+            SortedSetCommand::Zrandmember(cmd) => cmd.execute(db),
+            SortedSetCommand::Zscan(cmd) => cmd.execute(db),
+            SortedSetCommand::Zstore(cmd) => cmd.execute(db),
+            SortedSetCommand::Zmpop(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+pub(crate) fn parse_blocking(cmd: &str, args: Vec<String>) -> Result<Arc<dyn Block>, RedisError> {
+    match cmd {
+        "bzpopmin" | "bzpopmax" => Ok(Arc::new(Bzpop::new(args)?)),
+        "bzmpop" => Ok(Arc::new(Bzmpop::new(args)?)),
+        _ => Err(RedisError::UnknownCommand(cmd.to_owned())),
+    }
+}
+
+pub(crate) fn get_sorted_set<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut SortedSet>, RedisError> {
+    db.get_value(key)
+        .map(|value| value.as_sorted_set_mut())
+        .transpose()
+}
+
+pub(crate) fn read_sorted_set<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<Option<&'a mut SortedSet>, RedisError> {
+    db.read_value(key)
+        .map(|value| value.as_sorted_set_mut())
+        .transpose()
+}
+
+pub(crate) fn get_or_create_sorted_set<'a>(
+    db: &'a mut dyn Database,
+    key: &str,
+) -> Result<&'a mut SortedSet, RedisError> {
+    db.get_or_insert_with(key, &|| Data::SortedSet(Box::new(SortedSet::new())))
+        .as_sorted_set_mut()
+}
+
+/// Flattens elements into `member score member score ...`, or only members.
+pub(crate) fn elements_reply(elements: Vec<(String, f64)>, with_scores: bool) -> Frame {
+    let items = elements
+        .into_iter()
+        .flat_map(|(member, score)| {
+            let mut item = vec![member];
+            if with_scores {
+                item.push(format_float(score));
+            }
+            item
+        })
+        .collect();
+
+    Frame::Arrays(items)
+}
+
+fn parse_score_bound(s: &str) -> Result<(f64, bool), RedisError> {
+    let err = || RedisError::Custom(String::from("min or max is not a float"));
+
+    match s.strip_prefix('(') {
+        Some(rest) => parse_float(rest).map(|f| (f, true)).map_err(|_| err()),
+        None => parse_float(s).map(|f| (f, false)).map_err(|_| err()),
+    }
+}
+
+fn parse_score_range(min: &str, max: &str) -> Result<ScoreRange, RedisError> {
+    let (min, min_exclusive) = parse_score_bound(min)?;
+    let (max, max_exclusive) = parse_score_bound(max)?;
+
+    Ok(ScoreRange {
+        min,
+        max,
+        min_exclusive,
+        max_exclusive,
+    })
+}
+
+fn parse_lex_bound(s: &str) -> Result<LexBound, RedisError> {
+    match (s, s.chars().next()) {
+        ("-", _) => Ok(LexBound::NegInf),
+        ("+", _) => Ok(LexBound::PosInf),
+        (_, Some('[')) => Ok(LexBound::Inclusive(s[1..].to_owned())),
+        (_, Some('(')) => Ok(LexBound::Exclusive(s[1..].to_owned())),
+        _ => Err(RedisError::Custom(String::from(
+            "min or max not valid string range item",
+        ))),
+    }
+}
+
+#[derive(Debug, Default)]
+struct ZaddFlags {
+    nx: bool,
+    xx: bool,
+    gt: bool,
+    lt: bool,
+    ch: bool,
+    incr: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct Zadd {
+    key: String,
+    flags: ZaddFlags,
+    elements: Vec<(f64, String)>,
+}
+
+impl Zadd {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let mut flags = ZaddFlags::default();
+        let mut index = 2;
+        while let Some(arg) = args.get(index) {
+            match arg.to_lowercase().as_str() {
+                "nx" => flags.nx = true,
+                "xx" => flags.xx = true,
+                "gt" => flags.gt = true,
+                "lt" => flags.lt = true,
+                "ch" => flags.ch = true,
+                "incr" => flags.incr = true,
+                _ => break,
+            }
+            index += 1;
+        }
+
+        let pairs = &args[index..];
+        if pairs.is_empty() || pairs.len() % 2 == 1 {
+            return Err(RedisError::Syntax);
+        }
+
+        if flags.nx && flags.xx {
+            return Err(RedisError::Custom(String::from(
+                "XX and NX options at the same time are not compatible",
+            )));
+        }
+
+        if [flags.gt, flags.lt, flags.nx]
+            .iter()
+            .filter(|f| **f)
+            .count()
+            > 1
+        {
+            return Err(RedisError::Custom(String::from(
+                "GT, LT, and/or NX options at the same time are not compatible",
+            )));
+        }
+
+        if flags.incr && pairs.len() > 2 {
+            return Err(RedisError::Custom(String::from(
+                "INCR option supports a single increment-element pair",
+            )));
+        }
+
+        let elements = pairs
+            .chunks_exact(2)
+            .map(|pair| parse_float(&pair[0]).map(|score| (score, pair[1].clone())))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Zadd {
+            key: args[1].clone(),
+            flags,
+            elements,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let flags = &self.flags;
+
+        if flags.xx && get_sorted_set(db, &self.key)?.is_none() {
+            return Ok(if flags.incr {
+                Frame::Null
+            } else {
+                Frame::Integer(0)
+            });
+        }
+
+        let zset = get_or_create_sorted_set(db, &self.key)?;
+        let mut added = 0;
+        let mut changed = 0;
+        let mut result = None;
+
+        for (score, member) in self.elements.iter() {
+            match zset.score(member) {
+                Some(current) => {
+                    if flags.nx {
+                        continue;
+                    }
+
+                    let score = if flags.incr { current + score } else { *score };
+                    if score.is_nan() {
+                        remove_if_empty(db, &self.key);
+                        return Err(RedisError::Custom(String::from(
+                            "resulting score is not a number (NaN)",
+                        )));
+                    }
+
+                    if (flags.gt && score <= current) || (flags.lt && score >= current) {
+                        continue;
+                    }
+
+                    if score != current {
+                        zset.insert(member, score);
+                        changed += 1;
+                    }
+                    result = Some(score);
+                }
+                None => {
+                    if flags.xx {
+                        continue;
+                    }
+
+                    zset.insert(member, *score);
+                    added += 1;
+                    result = Some(*score);
+                }
+            }
+        }
+
+        remove_if_empty(db, &self.key);
+
+        if added + changed > 0 {
+            db.touch(&self.key);
+
+            let event = if flags.incr { "zincr" } else { "zadd" };
+            db.notify(notify::ZSET, event, &self.key);
+        }
+
+        if flags.incr {
+            return Ok(result.map_or(Frame::Null, |score| Frame::BulkString(format_float(score))));
+        }
+
+        Ok(Frame::Integer(if flags.ch {
+            added + changed
+        } else {
+            added
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zincrby {
+    key: String,
+    increment: f64,
+    member: String,
+}
+
+impl Zincrby {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Ok(Zincrby {
+            key: args[1].clone(),
+            increment: parse_float(&args[2])?,
+            member: args[3].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let zset = get_or_create_sorted_set(db, &self.key)?;
+
+        let score = zset.score(&self.member).unwrap_or(0.0) + self.increment;
+        if score.is_nan() {
+            remove_if_empty(db, &self.key);
+            return Err(RedisError::Custom(String::from(
+                "resulting score is not a number (NaN)",
+            )));
+        }
+
+        zset.insert(&self.member, score);
+        db.touch(&self.key);
+        db.notify(notify::ZSET, "zincr", &self.key);
+
+        Ok(Frame::BulkString(format_float(score)))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zrem {
+    key: String,
+    members: Vec<String>,
+}
+
+impl Zrem {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Zrem {
+            key: args[1].clone(),
+            members: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(zset) = get_sorted_set(db, &self.key)? else {
+            return Ok(Frame::Integer(0));
+        };
+
+        let removed = self.members.iter().filter(|m| zset.remove(m)).count();
+
+        if removed > 0 {
+            db.notify(notify::ZSET, "zrem", &self.key);
+            remove_if_empty(db, &self.key);
+            db.touch(&self.key);
+        }
+
+        Ok(Frame::Integer(removed as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zcard {
+    key: String,
+}
+
+impl Zcard {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        Ok(Zcard {
+            key: args[1].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let len = read_sorted_set(db, &self.key)?.map_or(0, |zset| zset.len());
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zscore {
+    key: String,
+    member: String,
+}
+
+impl Zscore {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Zscore {
+            key: args[1].clone(),
+            member: args[2].clone(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let score = read_sorted_set(db, &self.key)?.and_then(|zset| zset.score(&self.member));
+
+        Ok(score.map_or(Frame::Null, |s| Frame::BulkString(format_float(s))))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zmscore {
+    key: String,
+    members: Vec<String>,
+}
+
+impl Zmscore {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Zmscore {
+            key: args[1].clone(),
+            members: args[2..].to_vec(),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let zset = read_sorted_set(db, &self.key)?;
+
+        let scores = self
+            .members
+            .iter()
+            .map(|m| {
+                zset.as_ref()
+                    .and_then(|zset| zset.score(m))
+                    .map_or(Frame::Null, |s| Frame::BulkString(format_float(s)))
+            })
+            .collect();
+
+        Ok(Frame::Array(scores))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zrank {
+    key: String,
+    member: String,
+    reverse: bool,
+    with_score: bool,
+}
+
+impl Zrank {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        let with_score = match args.get(3) {
+            Some(opt) if opt.eq_ignore_ascii_case("withscore") && args.len() == 4 => true,
+            Some(_) => return Err(RedisError::Syntax),
+            None => false,
+        };
+
+        Ok(Zrank {
+            key: args[1].clone(),
+            member: args[2].clone(),
+            reverse: args[0].eq_ignore_ascii_case("zrevrank"),
+            with_score,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let found = read_sorted_set(db, &self.key)?.and_then(|zset| {
+            let rank = zset.rank(&self.member, self.reverse)?;
+            Some((rank, zset.score(&self.member)?))
+        });
+
+        Ok(match (found, self.with_score) {
+            (Some((rank, _)), false) => Frame::Integer(rank as i64),
+            (Some((rank, score)), true) => Frame::Array(vec![
+                Frame::Integer(rank as i64),
+                Frame::BulkString(format_float(score)),
+            ]),
+            (None, false) => Frame::Null,
+            (None, true) => Frame::NullArray,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zcount {
+    key: String,
+    range: ScoreRange,
+}
+
+impl Zcount {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Ok(Zcount {
+            key: args[1].clone(),
+            range: parse_score_range(&args[2], &args[3])?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let count = read_sorted_set(db, &self.key)?.map_or(0, |zset| zset.count(&self.range));
+
+        Ok(Frame::Integer(count as i64))
+    }
+}
+
+#[derive(Debug)]
+enum RangeSpec {
+    Rank(i64, i64),
+    Score(ScoreRange),
+    Lex(LexRange),
+}
+
+/// The unified `ZRANGE key start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count]
+/// [WITHSCORES]`.
+#[derive(Debug)]
+pub(crate) struct Zrange {
+    key: String,
+    spec: RangeSpec,
+    reverse: bool,
+    limit: Option<(i64, i64)>,
+    with_scores: bool,
+}
+
+impl Zrange {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let mut by_score = false;
+        let mut by_lex = false;
+        let mut reverse = false;
+        let mut limit = None;
+        let mut with_scores = false;
+
+        let mut rest = args[4..].iter();
+        while let Some(opt) = rest.next() {
+            match opt.to_lowercase().as_str() {
+                "byscore" => by_score = true,
+                "bylex" => by_lex = true,
+                "rev" => reverse = true,
+                "withscores" => with_scores = true,
+                "limit" => {
+                    let (Some(offset), Some(count)) = (rest.next(), rest.next()) else {
+                        return Err(RedisError::Syntax);
+                    };
+                    limit = Some((parse_int(offset)?, parse_int(count)?));
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        if by_score && by_lex {
+            return Err(RedisError::Syntax);
+        }
+
+        if limit.is_some() && !by_score && !by_lex {
+            return Err(RedisError::Custom(String::from(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX",
+            )));
+        }
+
+        if with_scores && by_lex {
+            return Err(RedisError::Custom(String::from(
+                "syntax error, WITHSCORES not supported in combination with BYLEX",
+            )));
+        }
+
+        // With REV, score and lex ranges are given from the highest bound to the lowest.
+        let (min, max) = if reverse && (by_score || by_lex) {
+            (&args[3], &args[2])
+        } else {
+            (&args[2], &args[3])
+        };
+
+        let spec = if by_score {
+            RangeSpec::Score(parse_score_range(min, max)?)
+        } else if by_lex {
+            RangeSpec::Lex(LexRange {
+                min: parse_lex_bound(min)?,
+                max: parse_lex_bound(max)?,
+            })
+        } else {
+            RangeSpec::Rank(parse_int(min)?, parse_int(max)?)
+        };
+
+        Ok(Zrange {
+            key: args[1].clone(),
+            spec,
+            reverse,
+            limit,
+            with_scores,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(zset) = read_sorted_set(db, &self.key)? else {
+            return Ok(Frame::Arrays(vec![]));
+        };
+
+        let (offset, limit) = match self.limit {
+            Some((offset, _)) if offset < 0 => return Ok(Frame::Arrays(vec![])),
+            Some((offset, count)) if count >= 0 => (offset as usize, Some(count as usize)),
+            Some((offset, _)) => (offset as usize, None),
+            None => (0, None),
+        };
+
+        let elements = match &self.spec {
+            RangeSpec::Rank(start, stop) => {
+                let len = zset.len() as i64;
+                let start = if *start < 0 { len + start } else { *start }.max(0);
+                let stop = if *stop < 0 { len + stop } else { *stop };
+
+                if stop < 0 {
+                    vec![]
+                } else {
+                    zset.range_by_rank(start as usize, stop as usize, self.reverse)
+                }
+            }
+            RangeSpec::Score(range) => zset.range_by_score(range, self.reverse, offset, limit),
+            RangeSpec::Lex(range) => zset.range_by_lex(range, self.reverse, offset, limit),
+        };
+
+        Ok(elements_reply(elements, self.with_scores))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zpop {
+    key: String,
+    count: Option<usize>,
+    max: bool,
+}
+
+impl Zpop {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        if args.len() > 3 {
+            return Err(RedisError::Syntax);
+        }
+
+        let count = match args.get(2).map(|c| parse_int(c)).transpose()? {
+            Some(c) if c < 0 => {
+                return Err(RedisError::Custom(String::from(
+                    "value is out of range, must be positive",
+                )))
+            }
+            c => c.map(|c| c as usize),
+        };
+
+        Ok(Zpop {
+            key: args[1].clone(),
+            count,
+            max: args[0].eq_ignore_ascii_case("zpopmax"),
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let popped = pop(db, &self.key, self.count.unwrap_or(1), self.max)?;
+
+        Ok(elements_reply(popped, true))
+    }
+}
+
+/// Pops up to `count` elements from the sorted set at `key`, deleting the key once it is empty.
+pub(crate) fn pop(
+    db: &mut dyn Database,
+    key: &str,
+    count: usize,
+    max: bool,
+) -> Result<Vec<(String, f64)>, RedisError> {
+    let Some(zset) = get_sorted_set(db, key)? else {
+        return Ok(vec![]);
+    };
+
+    let popped = zset.pop(count, max);
+
+    if !popped.is_empty() {
+        let event = if max { "zpopmax" } else { "zpopmin" };
+        db.notify(notify::ZSET, event, key);
+        remove_if_empty(db, key);
+        db.touch(key);
+    }
+
+    Ok(popped)
+}
+
+type Elements = Vec<(String, f64)>;
+
+/// Pops from the first of `keys` that is not empty, replying with the key and its elements.
+/// The pop is propagated as ZPOPMIN or ZPOPMAX on that key.
+fn pop_first(
+    db: &mut dyn Database,
+    keys: &[String],
+    count: usize,
+    max: bool,
+) -> Result<Option<(String, Elements)>, RedisError> {
+    for key in keys {
+        let popped = pop(db, key, count, max)?;
+        if popped.is_empty() {
+            continue;
+        }
+
+        let cmd = if max { "ZPOPMAX" } else { "ZPOPMIN" };
+        db.rewrite(Frame::Arrays(vec![
+            cmd.to_owned(),
+            key.clone(),
+            popped.len().to_string(),
+        ]));
+
+        return Ok(Some((key.clone(), popped)));
+    }
+
+    Ok(None)
+}
+
+fn mpop_reply(key: String, popped: Vec<(String, f64)>) -> Frame {
+    let elements = popped
+        .into_iter()
+        .map(|(member, score)| Frame::Arrays(vec![member, format_float(score)]))
+        .collect();
+
+    Frame::Array(vec![Frame::BulkString(key), Frame::Array(elements)])
+}
+
+/// `ZMPOP numkeys key [key ...] MIN|MAX [COUNT count]`.
+#[derive(Debug)]
+pub(crate) struct Zmpop {
+    keys: Vec<String>,
+    max: bool,
+    count: usize,
+}
+
+impl Zmpop {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        Zmpop::parse(&args[1..])
+    }
+
+    /// Parses `numkeys key [key ...] MIN|MAX [COUNT count]`, which BZMPOP shares.
+    fn parse(args: &[String]) -> Result<Self, RedisError> {
+        let numkeys = match parse_int(&args[0])? {
+            n if n < 1 => {
+                return Err(RedisError::Custom(String::from(
+                    "numkeys should be greater than 0",
+                )))
+            }
+            n => n as usize,
+        };
+
+        let Some(keys) = args.get(1..1 + numkeys) else {
+            return Err(RedisError::Syntax);
+        };
+
+        let mut rest = args[1 + numkeys..].iter();
+        let max = match rest.next().map(|s| s.to_lowercase()).as_deref() {
+            Some("min") => false,
+            Some("max") => true,
+            _ => return Err(RedisError::Syntax),
+        };
+
+        let count = match (rest.next(), rest.next(), rest.next()) {
+            (None, _, _) => 1,
+            (Some(opt), Some(count), None) if opt.eq_ignore_ascii_case("count") => {
+                match parse_int(count)? {
+                    n if n < 1 => {
+                        return Err(RedisError::Custom(String::from(
+                            "count should be greater than 0",
+                        )))
+                    }
+                    n => n as usize,
+                }
+            }
+            _ => return Err(RedisError::Syntax),
+        };
+
+        Ok(Zmpop {
+            keys: keys.to_vec(),
+            max,
+            count,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let popped = pop_first(db, &self.keys, self.count, self.max)?;
+
+        Ok(popped.map_or(Frame::NullArray, |(key, popped)| mpop_reply(key, popped)))
+    }
+}
+
+/// `BZPOPMIN key [key ...] timeout` and `BZPOPMAX`.
+#[derive(Debug)]
+pub(crate) struct Bzpop {
+    keys: Vec<String>,
+    timeout: Option<Duration>,
+    max: bool,
+}
+
+impl Bzpop {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        let last = args.len() - 1;
+
+        Ok(Bzpop {
+            keys: args[1..last].to_vec(),
+            timeout: parse_timeout(&args[last])?,
+            max: args[0].eq_ignore_ascii_case("bzpopmax"),
+        })
+    }
+}
+
+impl Block for Bzpop {
+    fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn serve(&self, db: &mut dyn Database, key: &str) -> Result<Option<Frame>, RedisError> {
+        let popped = pop_first(db, &[key.to_owned()], 1, self.max)?;
+
+        Ok(popped.map(|(key, mut popped)| {
+            let (member, score) = popped.remove(0);
+            Frame::Arrays(vec![key, member, format_float(score)])
+        }))
+    }
+}
+
+/// `BZMPOP timeout numkeys key [key ...] MIN|MAX [COUNT count]`.
+#[derive(Debug)]
+pub(crate) struct Bzmpop {
+    timeout: Option<Duration>,
+    pop: Zmpop,
+}
+
+impl Bzmpop {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 5)?;
+
+        Ok(Bzmpop {
+            timeout: parse_timeout(&args[1])?,
+            pop: Zmpop::parse(&args[2..])?,
+        })
+    }
+}
+
+impl Block for Bzmpop {
+    fn keys(&self) -> &[String] {
+        &self.pop.keys
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn serve(&self, db: &mut dyn Database, key: &str) -> Result<Option<Frame>, RedisError> {
+        let popped = pop_first(db, &[key.to_owned()], self.pop.count, self.pop.max)?;
+
+        Ok(popped.map(|(key, popped)| mpop_reply(key, popped)))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zrandmember {
+    key: String,
+    count: Option<i64>,
+    with_scores: bool,
+}
+
+impl Zrandmember {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 2)?;
+
+        let count = args.get(2).map(|c| parse_int(c)).transpose()?;
+        let with_scores = match args.get(3) {
+            Some(opt) if opt.eq_ignore_ascii_case("withscores") && args.len() == 4 => true,
+            Some(_) => return Err(RedisError::Syntax),
+            None => false,
+        };
+
+        Ok(Zrandmember {
+            key: args[1].clone(),
+            count,
+            with_scores,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let elements: Vec<(String, f64)> = read_sorted_set(db, &self.key)?
+            .map(|zset| zset.iter().map(|(m, s)| (m.to_owned(), s)).collect())
+            .unwrap_or_default();
+
+        match self.count {
+            Some(count) => Ok(elements_reply(sample(elements, count), self.with_scores)),
+            None => Ok(sample(elements, 1)
+                .into_iter()
+                .next()
+                .map_or(Frame::Null, |(m, _)| Frame::BulkString(m))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Zscan {
+    key: String,
+    options: ScanOptions,
+}
+
+impl Zscan {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 3)?;
+
+        Ok(Zscan {
+            key: args[1].clone(),
+            options: ScanOptions::parse(&args[2..])?,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let Some(zset) = read_sorted_set(db, &self.key)? else {
+            return Ok(scan_reply(0, vec![]));
+        };
+
+        let (cursor, batch) = zset.scan(self.options.cursor, self.options.count);
+
+        let items = batch
+            .into_iter()
+            .filter(|(m, _)| self.options.matches(m))
+            .flat_map(|(m, score)| [m.to_owned(), format_float(score)])
+            .collect();
+
+        Ok(scan_reply(cursor, items))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StoreOp {
+    Union,
+    Inter,
+    Diff,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            // inf + -inf is NaN, which Redis turns into zero.
+            Aggregate::Sum => Some(a + b).filter(|s| !s.is_nan()).unwrap_or(0.0),
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+/// Reads the elements of a sorted set, or of a plain set whose members all score 1.
+fn read_elements(
+    db: &mut dyn Database,
+    key: &str,
+) -> Result<Option<Vec<(String, f64)>>, RedisError> {
+    let Some(value) = db.read_value(key) else {
+        return Ok(None);
+    };
+
+    match value.data() {
+        Data::SortedSet(zset) => Ok(Some(zset.iter().map(|(m, s)| (m.to_owned(), s)).collect())),
+        Data::Set(set) => Ok(Some(set.members().into_iter().map(|m| (m, 1.0)).collect())),
+        _ => Err(RedisError::WrongType),
+    }
+}
+
+/// ZUNIONSTORE, ZINTERSTORE and ZDIFFSTORE.
+#[derive(Debug)]
+pub(crate) struct Zstore {
+    op: StoreOp,
+    destination: String,
+    keys: Vec<String>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+}
+
+impl Zstore {
+    pub(crate) fn new(args: Vec<String>) -> Result<Self, RedisError> {
+        check_arity(&args, 4)?;
+
+        let cmd = args[0].to_lowercase();
+        let op = match cmd.as_str() {
+            "zunionstore" => StoreOp::Union,
+            "zinterstore" => StoreOp::Inter,
+            _ => StoreOp::Diff,
+        };
+
+        let numkeys = match parse_int(&args[2])? {
+            n if n < 1 => {
+                return Err(RedisError::Custom(format!(
+                    "at least 1 input key is needed for '{cmd}' command"
+                )))
+            }
+            n => n as usize,
+        };
+
+        let Some(keys) = args.get(3..3 + numkeys) else {
+            return Err(RedisError::Syntax);
+        };
+
+        let mut weights = vec![1.0; numkeys];
+        let mut aggregate = Aggregate::Sum;
+
+        let mut rest = args[3 + numkeys..].iter();
+        while let Some(opt) = rest.next() {
+            match (opt.to_lowercase().as_str(), op) {
+                ("weights", StoreOp::Union | StoreOp::Inter) => {
+                    for weight in weights.iter_mut() {
+                        let value = rest.next().ok_or(RedisError::Syntax)?;
+                        *weight = parse_float(value).map_err(|_| {
+                            RedisError::Custom(String::from("weight value is not a float"))
+                        })?;
+                    }
+                }
+                ("aggregate", StoreOp::Union | StoreOp::Inter) => {
+                    aggregate = match rest.next().map(|a| a.to_lowercase()).as_deref() {
+                        Some("sum") => Aggregate::Sum,
+                        Some("min") => Aggregate::Min,
+                        Some("max") => Aggregate::Max,
+                        _ => return Err(RedisError::Syntax),
+                    };
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(Zstore {
+            op,
+            destination: args[1].clone(),
+            keys: keys.to_vec(),
+            weights,
+            aggregate,
+        })
+    }
+
+    fn execute(&self, db: &mut dyn Database) -> Result<Frame, RedisError> {
+        let mut sources = Vec::with_capacity(self.keys.len());
+        for key in self.keys.iter() {
+            sources.push(read_elements(db, key)?);
+        }
+
+        let weighted = |(i, elements): (usize, Option<Vec<(String, f64)>>)| {
+            let weight = self.weights[i];
+            elements.unwrap_or_default().into_iter().map(move |(m, s)| {
+                let score = s * weight;
+                (m, if score.is_nan() { 0.0 } else { score })
+            })
+        };
+
+        let mut sources = sources.into_iter().enumerate();
+        let mut result: HashMap<String, f64> = sources
+            .next()
+            .map(|first| weighted(first).collect())
+            .unwrap_or_default();
+
+        for source in sources {
+            match self.op {
+                StoreOp::Union => {
+                    for (member, score) in weighted(source) {
+                        result
+                            .entry(member)
+                            .and_modify(|s| *s = self.aggregate.apply(*s, score))
+                            .or_insert(score);
+                    }
+                }
+                StoreOp::Inter => {
+                    let other: HashMap<String, f64> = weighted(source).collect();
+                    result.retain(|member, score| match other.get(member) {
+                        Some(s) => {
+                            *score = self.aggregate.apply(*score, *s);
+                            true
+                        }
+                        None => false,
+                    });
+                }
+                StoreOp::Diff => {
+                    for (member, _) in weighted(source) {
+                        result.remove(&member);
+                    }
+                }
+            }
+        }
+
+        let len = result.len();
+
+        if result.is_empty() {
+            if db.remove(&self.destination).is_some() {
+                db.notify(notify::GENERIC, "del", &self.destination);
+            }
+        } else {
+            let mut zset = SortedSet::new();
+            for (member, score) in result {
+                zset.insert(&member, score);
+            }
+            db.insert(
+                &self.destination,
+                Value::from(Data::SortedSet(Box::new(zset))),
+            );
+
+            let event = match self.op {
+                StoreOp::Union => "zunionstore",
+                StoreOp::Inter => "zinterstore",
+                StoreOp::Diff => "zdiffstore",
+            };
+            db.notify(notify::ZSET, event, &self.destination);
+        }
+
+        db.touch(&self.destination);
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SortedSetCommand;
+    use crate::{
+        cmd::Execute,
+        db::{Database, KeyValueDb},
+        error::RedisError,
+        frame::Frame,
+    };
+
+    fn run(db: &mut KeyValueDb, args: &[&str]) -> Frame {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        SortedSetCommand::parse(&args[0], args.clone())
+            .and_then(|cmd| cmd.execute(db))
+            .unwrap_or_else(Frame::from)
+    }
+
+    fn arrays(items: &[&str]) -> Frame {
+        Frame::Arrays(items.iter().map(|item| item.to_string()).collect())
+    }
+
+    fn error(msg: &str) -> Frame {
+        Frame::from(RedisError::Custom(msg.to_owned()))
+    }
+
+    fn bulk(s: &str) -> Frame {
+        Frame::BulkString(s.to_owned())
+    }
+
+    #[test]
+    fn test_zadd_flags() {
+        let mut db = KeyValueDb::new();
+        let mut zadd = |args: &[&str]| run(&mut db, &[&["zadd", "z"], args].concat());
+
+        assert_eq!(Frame::Integer(0), zadd(&["XX", "1", "a"]));
+        assert_eq!(Frame::Integer(2), zadd(&["1", "a", "2", "b"]));
+        assert_eq!(Frame::Integer(1), zadd(&["NX", "5", "a", "3", "c"]));
+        assert_eq!(Frame::Integer(0), zadd(&["XX", "10", "a", "4", "d"]));
+        assert_eq!(Frame::Integer(1), zadd(&["XX", "CH", "11", "a", "4", "d"]));
+        assert_eq!(Frame::Integer(0), zadd(&["CH", "11", "a"]));
+
+        // GT and LT only hold back updates; new members are still added.
+        assert_eq!(Frame::Integer(1), zadd(&["GT", "CH", "5", "a", "20", "b"]));
+        assert_eq!(Frame::Integer(1), zadd(&["LT", "CH", "5", "a", "30", "b"]));
+        assert_eq!(Frame::Integer(1), zadd(&["GT", "7", "e"]));
+        assert_eq!(Frame::Integer(2), zadd(&["LT", "CH", "1", "c", "8", "f"]));
+
+        assert_eq!(
+            arrays(&["c", "1", "a", "5", "e", "7", "f", "8", "b", "20"]),
+            run(&mut db, &["zrange", "z", "0", "-1", "WITHSCORES"])
+        );
+    }
+
+    #[test]
+    fn test_zadd_incr() {
+        let mut db = KeyValueDb::new();
+        let mut zadd = |args: &[&str]| run(&mut db, &[&["zadd", "z"], args].concat());
+
+        assert_eq!(Frame::Null, zadd(&["XX", "INCR", "1", "a"]));
+        assert_eq!(bulk("1.5"), zadd(&["INCR", "1.5", "a"]));
+        assert_eq!(bulk("3.5"), zadd(&["INCR", "2", "a"]));
+        assert_eq!(Frame::Null, zadd(&["NX", "INCR", "1", "a"]));
+        assert_eq!(bulk("1"), zadd(&["NX", "INCR", "1", "b"]));
+        assert_eq!(Frame::Null, zadd(&["XX", "INCR", "1", "c"]));
+        assert_eq!(Frame::Null, zadd(&["GT", "INCR", "-1", "a"]));
+        assert_eq!(bulk("4.5"), zadd(&["GT", "INCR", "1", "a"]));
+        assert_eq!(Frame::Null, zadd(&["LT", "INCR", "1", "a"]));
+        assert_eq!(bulk("2.5"), zadd(&["LT", "INCR", "-2", "a"]));
+
+        assert_eq!(bulk("inf"), zadd(&["INCR", "+inf", "a"]));
+        assert_eq!(
+            error("resulting score is not a number (NaN)"),
+            zadd(&["INCR", "-inf", "a"])
+        );
+        assert_eq!(bulk("inf"), run(&mut db, &["zscore", "z", "a"]));
+    }
+
+    #[test]
+    fn test_zadd_errors() {
+        let mut db = KeyValueDb::new();
+        let mut zadd = |args: &[&str]| run(&mut db, &[&["zadd", "z"], args].concat());
+
+        assert_eq!(
+            error("XX and NX options at the same time are not compatible"),
+            zadd(&["NX", "XX", "1", "a"])
+        );
+        for flags in [["GT", "LT"], ["NX", "GT"], ["LT", "NX"]] {
+            assert_eq!(
+                error("GT, LT, and/or NX options at the same time are not compatible"),
+                zadd(&[&flags[..], &["1", "a"]].concat())
+            );
+        }
+        assert_eq!(
+            error("INCR option supports a single increment-element pair"),
+            zadd(&["INCR", "1", "a", "2", "b"])
+        );
+        assert_eq!(Frame::from(RedisError::Syntax), zadd(&["1", "a", "2"]));
+        assert_eq!(Frame::from(RedisError::Syntax), zadd(&["CH", "1"]));
+        assert_eq!(
+            Frame::from(RedisError::NotFloat),
+            zadd(&["1", "a", "x", "b"])
+        );
+        assert_eq!(Frame::from(RedisError::NotFloat), zadd(&["nan", "a"]));
+
+        // Nothing is added when a command fails.
+        assert!(db.get_value("z").is_none());
+
+        db.set("s", b"x", None);
+        let reply = run(&mut db, &["zadd", "s", "1", "a"]);
+        assert_eq!(Frame::from(RedisError::WrongType), reply);
+    }
+
+    #[test]
+    fn test_zrange_by_score() {
+        let mut db = KeyValueDb::new();
+        run(
+            &mut db,
+            &[
+                "zadd", "z", "1", "a", "2", "b", "3", "c", "4", "d", "5", "e",
+            ],
+        );
+        let mut zrange = |args: &[&str]| run(&mut db, &[&["zrange", "z"], args].concat());
+
+        assert_eq!(arrays(&["b", "c"]), zrange(&["(1", "3", "BYSCORE"]));
+        assert_eq!(arrays(&["b"]), zrange(&["(1", "(3", "BYSCORE"]));
+        assert_eq!(arrays(&[]), zrange(&["(1", "(2", "BYSCORE"]));
+        assert_eq!(arrays(&[]), zrange(&["5", "1", "BYSCORE"]));
+        assert_eq!(
+            arrays(&["b", "2", "c", "3"]),
+            zrange(&["-inf", "+inf", "BYSCORE", "LIMIT", "1", "2", "WITHSCORES"])
+        );
+        assert_eq!(
+            arrays(&["d", "e"]),
+            zrange(&["-inf", "+inf", "BYSCORE", "LIMIT", "3", "-1"])
+        );
+        assert_eq!(
+            arrays(&[]),
+            zrange(&["-inf", "+inf", "BYSCORE", "LIMIT", "-1", "2"])
+        );
+
+        // With REV, the bounds go from the highest to the lowest.
+        assert_eq!(
+            arrays(&["d", "c", "b"]),
+            zrange(&["(5", "(1", "REV", "BYSCORE"])
+        );
+        assert_eq!(arrays(&[]), zrange(&["(1", "(5", "REV", "BYSCORE"]));
+        assert_eq!(
+            arrays(&["c"]),
+            zrange(&["+inf", "-inf", "BYSCORE", "REV", "LIMIT", "2", "1"])
+        );
+
+        assert_eq!(
+            error("min or max is not a float"),
+            zrange(&["(1", "x", "BYSCORE"])
+        );
+        assert_eq!(
+            error("min or max is not a float"),
+            zrange(&["((1", "2", "BYSCORE"])
+        );
+    }
+
+    #[test]
+    fn test_zrange_by_lex() {
+        let mut db = KeyValueDb::new();
+        run(
+            &mut db,
+            &["zadd", "z", "0", "a", "0", "b", "0", "c", "0", "d"],
+        );
+        let mut zrange = |args: &[&str]| run(&mut db, &[&["zrange", "z"], args].concat());
+
+        assert_eq!(arrays(&["b", "c"]), zrange(&["[b", "(d", "BYLEX"]));
+        assert_eq!(arrays(&["b", "c", "d"]), zrange(&["(a", "+", "BYLEX"]));
+        assert_eq!(arrays(&["a", "b"]), zrange(&["-", "[b", "BYLEX"]));
+        assert_eq!(arrays(&[]), zrange(&["(b", "(c", "BYLEX"]));
+        assert_eq!(
+            arrays(&["b", "c"]),
+            zrange(&["-", "+", "BYLEX", "LIMIT", "1", "2"])
+        );
+        assert_eq!(
+            arrays(&["c", "b", "a"]),
+            zrange(&["(d", "[a", "BYLEX", "REV"])
+        );
+        assert_eq!(
+            arrays(&["b"]),
+            zrange(&["+", "-", "BYLEX", "REV", "LIMIT", "2", "1"])
+        );
+
+        assert_eq!(
+            error("min or max not valid string range item"),
+            zrange(&["b", "[d", "BYLEX"])
+        );
+        assert_eq!(
+            error("syntax error, WITHSCORES not supported in combination with BYLEX"),
+            zrange(&["-", "+", "BYLEX", "WITHSCORES"])
+        );
+    }
+
+    #[test]
+    fn test_zrange_options() {
+        let mut db = KeyValueDb::new();
+        run(&mut db, &["zadd", "z", "1", "a", "2", "b", "3", "c"]);
+        let mut zrange = |args: &[&str]| run(&mut db, &[&["zrange", "z"], args].concat());
+
+        assert_eq!(arrays(&["c", "b"]), zrange(&["0", "1", "REV"]));
+        assert_eq!(arrays(&["b", "c"]), zrange(&["-2", "-1"]));
+        assert_eq!(arrays(&[]), zrange(&["2", "1"]));
+        assert_eq!(
+            error(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+            ),
+            zrange(&["0", "-1", "LIMIT", "0", "1"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::Syntax),
+            zrange(&["0", "1", "BYSCORE", "BYLEX"])
+        );
+        assert_eq!(
+            Frame::from(RedisError::Syntax),
+            zrange(&["0", "1", "BYSCORE", "LIMIT", "0"])
+        );
+    }
+
+    #[test]
+    fn test_zstore_weights_and_aggregate() {
+        let mut db = KeyValueDb::new();
+        run(&mut db, &["zadd", "z1", "1", "a", "2", "b"]);
+        run(&mut db, &["zadd", "z2", "3", "b", "4", "c"]);
+        run(&mut db, &["zadd", "zi", "+inf", "x"]);
+
+        let mut store = |args: &[&str]| {
+            let reply = run(&mut db, args);
+            let stored = run(&mut db, &["zrange", args[1], "0", "-1", "WITHSCORES"]);
+            (reply, stored)
+        };
+
+        assert_eq!(
+            (Frame::Integer(3), arrays(&["a", "1", "c", "4", "b", "5"])),
+            store(&["zunionstore", "out", "2", "z1", "z2"])
+        );
+        assert_eq!(
+            (Frame::Integer(3), arrays(&["a", "2", "c", "12", "b", "13"])),
+            store(&["zunionstore", "out", "2", "z1", "z2", "WEIGHTS", "2", "3"])
+        );
+        assert_eq!(
+            (Frame::Integer(3), arrays(&["a", "1", "b", "2", "c", "4"])),
+            store(&["zunionstore", "out", "2", "z1", "z2", "AGGREGATE", "MIN"])
+        );
+        assert_eq!(
+            (Frame::Integer(3), arrays(&["a", "-1", "b", "3", "c", "4"])),
+            store(&[
+                "zunionstore",
+                "out",
+                "2",
+                "z1",
+                "z2",
+                "weights",
+                "-1",
+                "1",
+                "aggregate",
+                "max"
+            ])
+        );
+        assert_eq!(
+            (Frame::Integer(1), arrays(&["b", "-1"])),
+            store(&["zinterstore", "out", "2", "z1", "z2", "WEIGHTS", "1", "-1"])
+        );
+        assert_eq!(
+            (Frame::Integer(1), arrays(&["b", "6"])),
+            store(&[
+                "zinterstore",
+                "out",
+                "2",
+                "z1",
+                "z2",
+                "WEIGHTS",
+                "3",
+                "2",
+                "AGGREGATE",
+                "MAX"
+            ])
+        );
+        assert_eq!(
+            (Frame::Integer(1), arrays(&["a", "1"])),
+            store(&["zdiffstore", "out", "2", "z1", "z2"])
+        );
+
+        // Infinite scores times a zero weight count as 0 rather than NaN.
+        assert_eq!(
+            (Frame::Integer(1), arrays(&["x", "0"])),
+            store(&["zunionstore", "out", "1", "zi", "WEIGHTS", "0"])
+        );
+
+        // An empty result deletes the destination.
+        assert_eq!(
+            (Frame::Integer(0), arrays(&[])),
+            store(&["zinterstore", "out", "2", "z1", "missing"])
+        );
+        assert!(db.get_value("out").is_none());
+    }
+
+    #[test]
+    fn test_zstore_errors() {
+        let mut db = KeyValueDb::new();
+        run(&mut db, &["zadd", "z1", "1", "a"]);
+
+        let syntax = Frame::from(RedisError::Syntax);
+        for args in [
+            &["zunionstore", "out", "3", "z1", "z2"][..],
+            &["zunionstore", "out", "2", "z1", "z2", "WEIGHTS", "1"],
+            &["zunionstore", "out", "1", "z1", "WEIGHTS", "1", "2"],
+            &["zunionstore", "out", "1", "z1", "AGGREGATE", "AVG"],
+            &["zinterstore", "out", "1", "z1", "AGGREGATE"],
+            &["zdiffstore", "out", "1", "z1", "WEIGHTS", "1"],
+        ] {
+            assert_eq!(syntax, run(&mut db, args), "{args:?}");
+        }
+
+        assert_eq!(
+            error("weight value is not a float"),
+            run(&mut db, &["zinterstore", "out", "1", "z1", "WEIGHTS", "x"])
+        );
+        assert_eq!(
+            error("at least 1 input key is needed for 'zunionstore' command"),
+            run(&mut db, &["zunionstore", "out", "0", "z1"])
+        );
+        assert!(db.get_value("out").is_none());
+    }
+}
@@ -3,6 +3,10 @@ use std::env;
 pub struct Config {
     pub port: String,
     pub replicaof: Option<String>,
+    /// The password of the default user, which clients then have to AUTH with.
+    pub requirepass: Option<String>,
+    /// The file ACL users are loaded from at startup and by ACL LOAD, and saved to by ACL SAVE.
+    pub aclfile: Option<String>,
 }
 
 impl Config {
@@ -11,6 +15,8 @@ impl Config {
         let mut config = Config {
             port: String::from("6379"),
             replicaof: None,
+            requirepass: None,
+            aclfile: None,
         };
 
         for (index, arg) in args.iter().enumerate() {
@@ -25,6 +31,14 @@ impl Config {
                     config.replicaof = Some(format!("{}:{}", host, port));
                 }
             }
+
+            if arg == "--requirepass" {
+                config.requirepass = args.get(index + 1).cloned();
+            }
+
+            if arg == "--aclfile" {
+                config.aclfile = args.get(index + 1).cloned();
+            }
         }
 
         config
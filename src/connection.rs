@@ -1,5 +1,7 @@
+use std::net::SocketAddr;
+
 use anyhow::Error;
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt, BufWriter},
     net::TcpStream,
@@ -20,62 +22,42 @@ impl Connection {
         }
     }
 
-    pub async fn read_frame(&mut self) -> Result<Frame, Error> {
-        self.buffer.clear();
-        self.stream.read_buf(&mut self.buffer).await?;
-
-        Frame::parse(&self.buffer[..])
+    /// The address of the peer, as reported in the ACL log.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.stream.get_ref().peer_addr().ok()
     }
 
-    pub async fn write_frame(&mut self, frame: &Frame) -> Result<(), Error> {
-        match frame {
-            Frame::SimpleString(s) => {
-                self.stream.write_u8(b'+').await?;
-                self.stream.write_all(s.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Null => {
-                self.stream.write_all(b"$-1\r\n").await?;
-            }
-            Frame::BulkString(s) => {
-                self.write_bulk(s).await?;
-            }
-            Frame::Arrays(a) => {
-                let l = a.len().to_string();
-                self.stream.write_u8(b'*').await?;
-                self.stream.write_all(l.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-                for s in a {
-                    self.write_bulk(s).await?;
-                }
+    /// Reads the next frame, waiting for more data until it has fully arrived. Whatever
+    /// follows it, such as pipelined commands, is kept for the next call.
+    pub async fn read_frame(&mut self) -> Result<Frame, Error> {
+        loop {
+            // Data may already have arrived, as when commands are pipelined or sent while
+            // the client was blocked.
+            if let Some((frame, len)) = Frame::parse_partial(&self.buffer[..])? {
+                self.buffer.advance(len);
+                return Ok(frame);
             }
-            Frame::BulkBytes(b) => {
-                self.write_bytes(b).await?;
+
+            if self.stream.read_buf(&mut self.buffer).await? == 0 {
+                return Err(Error::msg("Connection closed"));
             }
-            Frame::Error => {}
         }
-
-        self.stream.flush().await?;
-
-        Ok(())
     }
 
-    async fn write_bulk(&mut self, s: &str) -> Result<(), Error> {
-        let l = s.len().to_string();
-        self.stream.write_u8(b'$').await?;
-        self.stream.write_all(l.as_bytes()).await?;
-        self.stream.write_all(b"\r\n").await?;
-        self.stream.write_all(s.as_bytes()).await?;
-        self.stream.write_all(b"\r\n").await?;
-        Ok(())
+    /// Resolves once the peer closes the connection. Anything the peer sends meanwhile is
+    /// kept for the next `read_frame`.
+    pub async fn closed(&mut self) {
+        while let Ok(n) = self.stream.read_buf(&mut self.buffer).await {
+            if n == 0 {
+                return;
+            }
+        }
     }
 
-    async fn write_bytes(&mut self, b: &[u8]) -> Result<(), Error> {
-        let l = b.len().to_string();
-        self.stream.write_u8(b'$').await?;
-        self.stream.write_all(l.as_bytes()).await?;
-        self.stream.write_all(b"\r\n").await?;
-        self.stream.write_all(b).await?;
+    pub async fn write_frame(&mut self, frame: &Frame) -> Result<(), Error> {
+        self.stream.write_all(&frame.to_bytes()).await?;
+        self.stream.flush().await?;
+
         Ok(())
     }
 }
@@ -0,0 +1,259 @@
+/// Bits are numbered from the most significant bit of the first byte, and bits past the end
+/// of the string read as zero.
+pub fn get_bit(bytes: &[u8], offset: u64) -> bool {
+    bytes
+        .get((offset / 8) as usize)
+        .is_some_and(|b| b & (0x80 >> (offset % 8)) != 0)
+}
+
+/// Sets the bit at `offset`, growing the string with zeros if needed. Returns the old bit.
+pub fn set_bit(bytes: &mut Vec<u8>, offset: u64, on: bool) -> bool {
+    let index = (offset / 8) as usize;
+    if index >= bytes.len() {
+        bytes.resize(index + 1, 0);
+    }
+
+    let mask = 0x80 >> (offset % 8);
+    let old = bytes[index] & mask != 0;
+    if on {
+        bytes[index] |= mask;
+    } else {
+        bytes[index] &= !mask;
+    }
+    old
+}
+
+/// Counts the set bits between the bit offsets `start` and `end`, both inclusive and within
+/// the string.
+pub fn count(bytes: &[u8], start: u64, end: u64) -> u64 {
+    if start > end {
+        return 0;
+    }
+
+    let (first, last) = ((start / 8) as usize, (end / 8) as usize);
+    let total: u64 = bytes[first..=last]
+        .iter()
+        .map(|b| b.count_ones() as u64)
+        .sum();
+
+    // Leave out the bits of the first and last bytes that are outside of the range.
+    let before = bytes[first] & !(0xFF >> (start % 8));
+    let after = bytes[last] & (0xFFu16 >> (end % 8 + 1)) as u8;
+
+    total - before.count_ones() as u64 - after.count_ones() as u64
+}
+
+/// Finds the first bit equal to `bit` between the bit offsets `start` and `end`, both
+/// inclusive and within the string.
+pub fn position(bytes: &[u8], bit: bool, start: u64, end: u64) -> Option<u64> {
+    let skip = if bit { 0x00 } else { 0xFF };
+    let mut pos = start;
+
+    while pos <= end {
+        // Whole bytes without the bit are skipped at once.
+        if pos & 7 == 0 && pos + 7 <= end && bytes[(pos / 8) as usize] == skip {
+            pos += 8;
+            continue;
+        }
+
+        if get_bit(bytes, pos) == bit {
+            return Some(pos);
+        }
+        pos += 1;
+    }
+
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Not,
+    /// Bits set in the first source and in none of the others.
+    Diff,
+}
+
+/// Combines `sources`, the shorter ones padded with zeros to the length of the longest one.
+pub fn bitop(op: BitOp, sources: &[&[u8]]) -> Vec<u8> {
+    let len = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+
+    (0..len)
+        .map(|i| {
+            let byte = |s: &[u8]| s.get(i).copied().unwrap_or(0);
+            let first = byte(sources[0]);
+            let rest = sources[1..].iter().map(|s| byte(s));
+
+            match op {
+                BitOp::And => rest.fold(first, |acc, b| acc & b),
+                BitOp::Or => rest.fold(first, |acc, b| acc | b),
+                BitOp::Xor => rest.fold(first, |acc, b| acc ^ b),
+                BitOp::Not => !first,
+                BitOp::Diff => first & !rest.fold(0, |acc, b| acc | b),
+            }
+        })
+        .collect()
+}
+
+/// How BITFIELD handles values that don't fit in their field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+/// A BITFIELD integer type: `i1` to `i64`, or `u1` to `u63`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+impl FieldType {
+    pub fn parse(s: &str) -> Option<Self> {
+        let signed = match s.as_bytes().first()? {
+            b'i' | b'I' => true,
+            b'u' | b'U' => false,
+            _ => return None,
+        };
+
+        let bits: u32 = s[1..].parse().ok()?;
+        let max = if signed { 64 } else { 63 };
+        (1..=max)
+            .contains(&bits)
+            .then_some(FieldType { signed, bits })
+    }
+
+    fn min(&self) -> i128 {
+        if self.signed {
+            -(1 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    fn max(&self) -> i128 {
+        if self.signed {
+            (1 << (self.bits - 1)) - 1
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.bits)
+    }
+
+    /// Interprets the raw bits of a field.
+    pub fn decode(&self, raw: u64) -> i64 {
+        if self.signed {
+            let shift = 64 - self.bits;
+            ((raw << shift) as i64) >> shift
+        } else {
+            raw as i64
+        }
+    }
+
+    pub fn encode(&self, value: i64) -> u64 {
+        value as u64 & self.mask()
+    }
+
+    /// Fits `value` in the field according to `overflow`, returning `None` if it fails.
+    pub fn fit(&self, value: i128, overflow: Overflow) -> Option<i64> {
+        if (self.min()..=self.max()).contains(&value) {
+            return Some(value as i64);
+        }
+
+        match overflow {
+            Overflow::Wrap => Some(self.decode(value as u64 & self.mask())),
+            Overflow::Sat => Some(value.clamp(self.min(), self.max()) as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+/// Reads the raw bits of the `bits` wide field at the bit offset `offset`.
+pub fn get_field(bytes: &[u8], offset: u64, bits: u32) -> u64 {
+    (0..bits as u64).fold(0, |acc, i| (acc << 1) | get_bit(bytes, offset + i) as u64)
+}
+
+/// Writes the low `bits` bits of `raw` at the bit offset `offset`, growing the string with
+/// zeros if needed.
+pub fn set_field(bytes: &mut Vec<u8>, offset: u64, bits: u32, raw: u64) {
+    for i in 0..bits as u64 {
+        let on = (raw >> (bits as u64 - 1 - i)) & 1 == 1;
+        set_bit(bytes, offset + i, on);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        bitop, count, get_bit, get_field, position, set_bit, set_field, BitOp, FieldType, Overflow,
+    };
+
+    #[test]
+    fn test_set_and_count_bits() {
+        let mut bytes = Vec::new();
+        assert!(!set_bit(&mut bytes, 7, true));
+        assert!(set_bit(&mut bytes, 7, true));
+        set_bit(&mut bytes, 9, true);
+        set_bit(&mut bytes, 23, true);
+
+        assert_eq!(vec![0x01, 0x40, 0x01], bytes);
+        assert!(get_bit(&bytes, 9));
+        assert!(!get_bit(&bytes, 1000));
+
+        assert_eq!(3, count(&bytes, 0, 23));
+        assert_eq!(1, count(&bytes, 8, 22));
+        assert_eq!(1, count(&bytes, 9, 9));
+        assert_eq!(0, count(&bytes, 10, 22));
+    }
+
+    #[test]
+    fn test_position() {
+        let bytes = [0xFF, 0xFF, 0xF0, 0x00, 0x01];
+
+        assert_eq!(Some(20), position(&bytes, false, 0, 39));
+        assert_eq!(Some(39), position(&bytes, true, 24, 39));
+        assert_eq!(None, position(&bytes, false, 0, 19));
+        assert_eq!(Some(3), position(&bytes, true, 3, 5));
+    }
+
+    #[test]
+    fn test_bitop() {
+        let a: &[u8] = &[0b1100, 0xFF];
+        let b: &[u8] = &[0b1010];
+
+        assert_eq!(vec![0b1000, 0], bitop(BitOp::And, &[a, b]));
+        assert_eq!(vec![0b1110, 0xFF], bitop(BitOp::Or, &[a, b]));
+        assert_eq!(vec![0b0110, 0xFF], bitop(BitOp::Xor, &[a, b]));
+        assert_eq!(vec![!0b1100, 0], bitop(BitOp::Not, &[a]));
+        assert_eq!(vec![0b0100, 0xFF], bitop(BitOp::Diff, &[a, b]));
+    }
+
+    #[test]
+    fn test_fields() {
+        let i8 = FieldType::parse("i8").unwrap();
+        let u4 = FieldType::parse("u4").unwrap();
+        assert!(FieldType::parse("u64").is_none());
+        assert!(FieldType::parse("i0").is_none());
+
+        let mut bytes = Vec::new();
+        set_field(&mut bytes, 4, 8, i8.encode(-2));
+        assert_eq!(vec![0x0F, 0xE0], bytes);
+        assert_eq!(-2, i8.decode(get_field(&bytes, 4, 8)));
+        assert_eq!(15, u4.decode(get_field(&bytes, 4, 4)));
+
+        assert_eq!(Some(-128), i8.fit(128, Overflow::Wrap));
+        assert_eq!(Some(127), i8.fit(1000, Overflow::Sat));
+        assert_eq!(Some(-128), i8.fit(-1000, Overflow::Sat));
+        assert_eq!(None, i8.fit(128, Overflow::Fail));
+        assert_eq!(Some(1), u4.fit(17, Overflow::Wrap));
+        assert_eq!(Some(15), u4.fit(-3 + 100, Overflow::Sat));
+        assert_eq!(Some(0), u4.fit(-3, Overflow::Sat));
+        assert_eq!(Some(13), u4.fit(-3, Overflow::Wrap));
+    }
+}
@@ -0,0 +1,235 @@
+//! Scalable Bloom filters: a chain of filters where each one is sized for a number of items
+//! at a target error rate. Once the last filter is full, a larger one with a tighter error
+//! rate is appended, so the overall error rate stays bounded as the filter grows.
+
+use crate::util::hash::murmurhash64a;
+
+/// Each filter added to the chain halves the error rate of the previous one.
+const ERROR_TIGHTENING_RATIO: f64 = 0.5;
+const HASH_SEED: u64 = 0xc6a4_a793_5bd1_e995;
+
+/// Defaults of filters created by BF.ADD and BF.MADD.
+pub const DEFAULT_ERROR_RATE: f64 = 0.01;
+pub const DEFAULT_CAPACITY: u64 = 100;
+pub const DEFAULT_EXPANSION: u32 = 2;
+
+/// The most bytes a single filter of the chain may take, so that a single command can't take
+/// all the memory.
+pub const MAX_LAYER_SIZE: u64 = 128 * 1024 * 1024;
+
+/// A single Bloom filter of the chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    capacity: u64,
+    error: f64,
+    hashes: u32,
+    /// Bits per entry.
+    bpe: f64,
+    bits: Vec<u8>,
+    items: u64,
+}
+
+impl Layer {
+    /// A filter for `capacity` items at the `error` rate, or `None` if it would take more
+    /// than `MAX_LAYER_SIZE` bytes.
+    fn new(capacity: u64, error: f64) -> Option<Self> {
+        let bpe = -error.ln() / std::f64::consts::LN_2.powi(2);
+        // Bits are allocated in whole 64-bit words. The float to integer cast saturates.
+        let bits = ((capacity as f64 * bpe) as u64).checked_next_multiple_of(64)?;
+        if bits / 8 > MAX_LAYER_SIZE {
+            return None;
+        }
+
+        Some(Layer {
+            capacity,
+            error,
+            hashes: (std::f64::consts::LN_2 * bpe).ceil() as u32,
+            bpe,
+            bits: vec![0; (bits / 8) as usize],
+            items: 0,
+        })
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn error(&self) -> f64 {
+        self.error
+    }
+
+    pub fn hashes(&self) -> u32 {
+        self.hashes
+    }
+
+    pub fn bits_per_entry(&self) -> f64 {
+        self.bpe
+    }
+
+    pub fn bits(&self) -> &[u8] {
+        &self.bits
+    }
+
+    pub fn items(&self) -> u64 {
+        self.items
+    }
+
+    /// Positions of the bits of an item, with double hashing.
+    fn positions(&self, (a, b): (u64, u64)) -> impl Iterator<Item = usize> {
+        let len = self.bits.len() as u64 * 8;
+        (0..self.hashes as u64).map(move |i| (a.wrapping_add(i.wrapping_mul(b)) % len) as usize)
+    }
+
+    fn contains(&self, hash: (u64, u64)) -> bool {
+        self.positions(hash)
+            .all(|x| self.bits[x / 8] & (1 << (x % 8)) != 0)
+    }
+
+    fn add(&mut self, hash: (u64, u64)) {
+        let positions: Vec<_> = self.positions(hash).collect();
+        for x in positions {
+            self.bits[x / 8] |= 1 << (x % 8);
+        }
+        self.items += 1;
+    }
+}
+
+fn hash(item: &[u8]) -> (u64, u64) {
+    let a = murmurhash64a(item, HASH_SEED);
+    (a, murmurhash64a(item, a))
+}
+
+/// Returned when adding to a non-scaling filter that reached its capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Full;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bloom {
+    layers: Vec<Layer>,
+    /// Growth factor of the capacity of each new filter, or `None` if the filter does not
+    /// scale.
+    expansion: Option<u32>,
+}
+
+impl Bloom {
+    /// A filter whose first layer holds `capacity` items, or `None` if that layer would be
+    /// too large.
+    pub fn new(error: f64, capacity: u64, expansion: Option<u32>) -> Option<Self> {
+        Some(Bloom {
+            layers: vec![Layer::new(capacity, error)?],
+            expansion,
+        })
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn expansion(&self) -> Option<u32> {
+        self.expansion
+    }
+
+    /// Total number of items the filters can hold.
+    pub fn capacity(&self) -> u64 {
+        self.layers.iter().map(|layer| layer.capacity).sum()
+    }
+
+    /// Number of items added, not counting those that were reported as already present.
+    pub fn items(&self) -> u64 {
+        self.layers.iter().map(|layer| layer.items).sum()
+    }
+
+    /// Memory used by the bits of the filters.
+    pub fn size(&self) -> usize {
+        self.layers.iter().map(|layer| layer.bits.len()).sum()
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        let hash = hash(item);
+        self.layers.iter().any(|layer| layer.contains(hash))
+    }
+
+    /// Adds `item`, returning `false` if it may have been added before. A filter that can't
+    /// grow, either because it doesn't scale or because its next layer would be too large,
+    /// is full.
+    pub fn add(&mut self, item: &[u8]) -> Result<bool, Full> {
+        let hash = hash(item);
+        if self.layers.iter().any(|layer| layer.contains(hash)) {
+            return Ok(false);
+        }
+
+        let last = self.layers.last().expect("at least one filter");
+        if last.items >= last.capacity {
+            let expansion = self.expansion.ok_or(Full)?;
+            let layer = Layer::new(
+                last.capacity.saturating_mul(expansion as u64),
+                last.error * ERROR_TIGHTENING_RATIO,
+            )
+            .ok_or(Full)?;
+            self.layers.push(layer);
+        }
+
+        self.layers
+            .last_mut()
+            .expect("at least one filter")
+            .add(hash);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Bloom, Full, Layer};
+
+    #[test]
+    fn test_layer_size() {
+        // Matches the sizing of RedisBloom: 9.585 bits and 7 hashes per entry at 1%.
+        let layer = Layer::new(100, 0.01).unwrap();
+        assert_eq!(7, layer.hashes());
+        assert_eq!(960 / 8, layer.bits().len());
+    }
+
+    #[test]
+    fn test_add_contains() {
+        let mut bloom = Bloom::new(0.01, 100, Some(2)).unwrap();
+        assert_eq!(Ok(true), bloom.add(b"a"));
+        assert_eq!(Ok(false), bloom.add(b"a"));
+        assert!(bloom.contains(b"a"));
+        assert!(!bloom.contains(b"b"));
+
+        let false_positives = (0..1000)
+            .filter(|i| bloom.contains(format!("missing:{i}").as_bytes()))
+            .count();
+        assert!(false_positives < 30);
+    }
+
+    #[test]
+    fn test_scaling() {
+        let mut bloom = Bloom::new(0.01, 10, Some(2)).unwrap();
+        for i in 0..100 {
+            bloom.add(i.to_string().as_bytes()).unwrap();
+        }
+
+        assert!(bloom.layers().len() > 1);
+        assert_eq!(20, bloom.layers()[1].capacity());
+        assert_eq!(0.005, bloom.layers()[1].error());
+        assert!((0..100).all(|i| bloom.contains(i.to_string().as_bytes())));
+
+        let mut bloom = Bloom::new(0.01, 2, None).unwrap();
+        bloom.add(b"a").unwrap();
+        bloom.add(b"b").unwrap();
+        assert_eq!(Err(Full), bloom.add(b"c"));
+    }
+
+    #[test]
+    fn test_too_large() {
+        assert_eq!(None, Bloom::new(1e-300, i64::MAX as u64, Some(2)));
+        assert_eq!(None, Bloom::new(0.01, 1 << 30, Some(2)));
+        assert!(Bloom::new(0.01, 1 << 20, Some(2)).is_some());
+
+        // A filter that would grow past the limit is full instead.
+        let mut bloom = Bloom::new(0.01, 1, Some(u32::MAX)).unwrap();
+        bloom.add(b"a").unwrap();
+        assert_eq!(Err(Full), bloom.add(b"b"));
+    }
+}
@@ -0,0 +1,173 @@
+//! Count-Min sketches: `depth` rows of `width` counters, where each row hashes an item to one
+//! counter. The smallest of its counters over-estimates the count of an item by at most the
+//! collisions it shares with others.
+
+use crate::util::hash::murmurhash2;
+
+/// The most counters a sketch may have, so that a single command can't take all the memory.
+pub const MAX_COUNTERS: usize = 1 << 25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A counter would exceed `u32::MAX`.
+    Overflow,
+    /// Sketches of different dimensions cannot be merged.
+    Dimensions,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountMin {
+    width: u32,
+    depth: u32,
+    /// Sum of all increments.
+    count: u64,
+    counters: Vec<u32>,
+}
+
+impl CountMin {
+    /// A sketch of `depth` rows of `width` counters, or `None` if that is more than
+    /// `MAX_COUNTERS`.
+    pub fn new(width: u32, depth: u32) -> Option<Self> {
+        let counters = (width as usize)
+            .checked_mul(depth as usize)
+            .filter(|n| *n <= MAX_COUNTERS)?;
+
+        Some(CountMin {
+            width,
+            depth,
+            count: 0,
+            counters: vec![0; counters],
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn counters(&self) -> &[u32] {
+        &self.counters
+    }
+
+    /// Index of the counter of `item` in each row.
+    fn indexes<'a>(&self, item: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let width = self.width;
+        (0..self.depth).map(move |row| {
+            row as usize * width as usize + (murmurhash2(item, row) % width) as usize
+        })
+    }
+
+    pub fn query(&self, item: &[u8]) -> u32 {
+        self.indexes(item)
+            .map(|i| self.counters[i])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Adds `increment` to the count of `item` and returns its new estimate. Nothing is
+    /// changed if a counter would overflow.
+    pub fn increment(&mut self, item: &[u8], increment: u32) -> Result<u32, Error> {
+        let indexes: Vec<_> = self.indexes(item).collect();
+        if indexes
+            .iter()
+            .any(|&i| self.counters[i].checked_add(increment).is_none())
+        {
+            return Err(Error::Overflow);
+        }
+
+        for &i in &indexes {
+            self.counters[i] += increment;
+        }
+        self.count += increment as u64;
+        Ok(self.query(item))
+    }
+
+    /// Replaces the counters with the weighted sum of those of `sources`, which must all have
+    /// the dimensions of this sketch.
+    pub fn merge(&mut self, sources: &[(&CountMin, i64)]) -> Result<(), Error> {
+        if sources
+            .iter()
+            .any(|(s, _)| (s.width, s.depth) != (self.width, self.depth))
+        {
+            return Err(Error::Dimensions);
+        }
+
+        let mut counters = vec![0; self.counters.len()];
+        for (i, counter) in counters.iter_mut().enumerate() {
+            let sum = sources
+                .iter()
+                .try_fold(0i64, |sum, (s, weight)| {
+                    (s.counters[i] as i64)
+                        .checked_mul(*weight)
+                        .and_then(|n| sum.checked_add(n))
+                })
+                .ok_or(Error::Overflow)?;
+            *counter = u32::try_from(sum).map_err(|_| Error::Overflow)?;
+        }
+
+        let count = sources
+            .iter()
+            .try_fold(0i64, |sum, (s, weight)| {
+                (s.count as i64)
+                    .checked_mul(*weight)
+                    .and_then(|n| sum.checked_add(n))
+            })
+            .and_then(|n| u64::try_from(n).ok())
+            .ok_or(Error::Overflow)?;
+
+        self.counters = counters;
+        self.count = count;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CountMin, Error};
+
+    #[test]
+    fn test_increment_query() {
+        let mut cms = CountMin::new(2000, 5).unwrap();
+        assert_eq!(Ok(3), cms.increment(b"a", 3));
+        assert_eq!(Ok(5), cms.increment(b"a", 2));
+        assert_eq!(Ok(1), cms.increment(b"b", 1));
+        assert_eq!(5, cms.query(b"a"));
+        assert_eq!(0, cms.query(b"c"));
+        assert_eq!(6, cms.count());
+
+        assert_eq!(Err(Error::Overflow), cms.increment(b"a", u32::MAX));
+        assert_eq!(5, cms.query(b"a"));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = CountMin::new(100, 3).unwrap();
+        let mut b = CountMin::new(100, 3).unwrap();
+        a.increment(b"x", 2).unwrap();
+        b.increment(b"x", 3).unwrap();
+        b.increment(b"y", 1).unwrap();
+
+        let mut dest = CountMin::new(100, 3).unwrap();
+        dest.merge(&[(&a, 1), (&b, 2)]).unwrap();
+        assert_eq!(8, dest.query(b"x"));
+        assert_eq!(2, dest.query(b"y"));
+        assert_eq!(10, dest.count());
+
+        let other = CountMin::new(10, 3).unwrap();
+        assert_eq!(Err(Error::Dimensions), dest.merge(&[(&other, 1)]));
+    }
+
+    #[test]
+    fn test_too_large() {
+        assert_eq!(None, CountMin::new(u32::MAX, u32::MAX));
+        assert_eq!(None, CountMin::new(1 << 20, 64));
+        assert!(CountMin::new(1 << 20, 32).is_some());
+    }
+}
@@ -0,0 +1,261 @@
+//! Cuckoo filters: an 8-bit fingerprint of each item is stored in one of two buckets, the
+//! second derived from the first and the fingerprint, so items can be moved between their
+//! buckets to make room and deleted later. When no room can be made, another filter is
+//! appended.
+
+use std::ops::Range;
+
+use crate::util::hash::murmurhash64a;
+
+pub const DEFAULT_CAPACITY: u64 = 1024;
+pub const DEFAULT_BUCKET_SIZE: usize = 2;
+pub const DEFAULT_MAX_ITERATIONS: u32 = 20;
+pub const DEFAULT_EXPANSION: u32 = 1;
+
+const EMPTY: u8 = 0;
+
+/// Returned when an item cannot be inserted and the filter cannot grow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Full;
+
+/// The fingerprint of an item and the hashes of its two buckets.
+#[derive(Debug, Clone, Copy)]
+struct Lookup {
+    fp: u8,
+    h1: u64,
+    h2: u64,
+}
+
+impl Lookup {
+    fn new(item: &[u8]) -> Self {
+        let hash = murmurhash64a(item, 0);
+        let fp = (hash % 255 + 1) as u8;
+        Lookup {
+            fp,
+            h1: hash,
+            h2: alt_hash(fp, hash),
+        }
+    }
+}
+
+/// The other bucket of a fingerprint. Applying it twice gives back the first bucket, as
+/// the number of buckets is a power of two.
+fn alt_hash(fp: u8, hash: u64) -> u64 {
+    hash ^ (fp as u64).wrapping_mul(0x5bd1_e995)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubFilter {
+    buckets: u64,
+    data: Vec<u8>,
+}
+
+impl SubFilter {
+    fn new(buckets: u64, bucket_size: usize) -> Self {
+        SubFilter {
+            buckets,
+            data: vec![EMPTY; buckets as usize * bucket_size],
+        }
+    }
+
+    pub fn buckets(&self) -> u64 {
+        self.buckets
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cuckoo {
+    filters: Vec<SubFilter>,
+    bucket_size: usize,
+    max_iterations: u32,
+    /// Growth factor of the number of buckets of each new filter, where 0 means the filter
+    /// does not grow.
+    expansion: u32,
+    items: u64,
+    deletes: u64,
+}
+
+impl Cuckoo {
+    pub fn new(capacity: u64, bucket_size: usize, max_iterations: u32, expansion: u32) -> Self {
+        let buckets = (capacity / bucket_size as u64).max(1).next_power_of_two();
+        Cuckoo {
+            filters: vec![SubFilter::new(buckets, bucket_size)],
+            bucket_size,
+            max_iterations,
+            expansion,
+            items: 0,
+            deletes: 0,
+        }
+    }
+
+    pub fn filters(&self) -> &[SubFilter] {
+        &self.filters
+    }
+
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    pub fn max_iterations(&self) -> u32 {
+        self.max_iterations
+    }
+
+    pub fn expansion(&self) -> u32 {
+        self.expansion
+    }
+
+    pub fn items(&self) -> u64 {
+        self.items
+    }
+
+    pub fn deletes(&self) -> u64 {
+        self.deletes
+    }
+
+    fn bucket(&self, filter: usize, hash: u64) -> Range<usize> {
+        let index = (hash % self.filters[filter].buckets) as usize;
+        index * self.bucket_size..(index + 1) * self.bucket_size
+    }
+
+    /// Returns the slot holding `fp` in either bucket of the lookup in `filter`.
+    fn find(&self, filter: usize, lookup: &Lookup, fp: u8) -> Option<usize> {
+        [lookup.h1, lookup.h2].into_iter().find_map(|hash| {
+            let data = &self.filters[filter].data;
+            self.bucket(filter, hash).find(|&i| data[i] == fp)
+        })
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        let lookup = Lookup::new(item);
+        (0..self.filters.len()).any(|f| self.find(f, &lookup, lookup.fp).is_some())
+    }
+
+    /// Inserts `item`. The same item can be inserted several times, and must then be deleted
+    /// as many times.
+    pub fn add(&mut self, item: &[u8]) -> Result<(), Full> {
+        let lookup = Lookup::new(item);
+
+        // Take a free slot in any filter, starting with the newest one.
+        for f in (0..self.filters.len()).rev() {
+            if let Some(i) = self.find(f, &lookup, EMPTY) {
+                self.filters[f].data[i] = lookup.fp;
+                self.items += 1;
+                return Ok(());
+            }
+        }
+
+        let last = self.filters.len() - 1;
+        if !self.kick_out(last, &lookup) {
+            if self.expansion == 0 {
+                return Err(Full);
+            }
+
+            let buckets = self.filters[last]
+                .buckets
+                .saturating_mul(self.expansion as u64);
+            self.filters.push(SubFilter::new(buckets, self.bucket_size));
+            let i = self
+                .find(last + 1, &lookup, EMPTY)
+                .expect("a new filter has room");
+            self.filters[last + 1].data[i] = lookup.fp;
+        }
+
+        self.items += 1;
+        Ok(())
+    }
+
+    /// Makes room for the fingerprint in `filter` by moving others to their other bucket,
+    /// undoing the moves if none of them finds a free slot.
+    fn kick_out(&mut self, filter: usize, lookup: &Lookup) -> bool {
+        let size = self.bucket_size;
+        let buckets = self.filters[filter].buckets;
+        let data = &mut self.filters[filter].data;
+
+        let mut fp = lookup.fp;
+        let mut victim = 0;
+        let mut index = lookup.h1 % buckets;
+        for _ in 0..self.max_iterations {
+            std::mem::swap(&mut data[index as usize * size + victim], &mut fp);
+            index = alt_hash(fp, index) % buckets;
+
+            let bucket = index as usize * size..(index as usize + 1) * size;
+            if let Some(i) = bucket.into_iter().find(|&i| data[i] == EMPTY) {
+                data[i] = fp;
+                return true;
+            }
+            victim = (victim + 1) % size;
+        }
+
+        // Walk the moves back so that every fingerprint is where it was.
+        for _ in 0..self.max_iterations {
+            victim = (victim + size - 1) % size;
+            index = alt_hash(fp, index) % buckets;
+            std::mem::swap(&mut data[index as usize * size + victim], &mut fp);
+        }
+
+        false
+    }
+
+    /// Deletes one occurrence of `item`, returning whether it was found.
+    pub fn delete(&mut self, item: &[u8]) -> bool {
+        let lookup = Lookup::new(item);
+        for f in (0..self.filters.len()).rev() {
+            if let Some(i) = self.find(f, &lookup, lookup.fp) {
+                self.filters[f].data[i] = EMPTY;
+                self.items -= 1;
+                self.deletes += 1;
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cuckoo, Full};
+
+    #[test]
+    fn test_add_delete() {
+        let mut cuckoo = Cuckoo::new(1024, 2, 20, 1);
+        assert_eq!(512, cuckoo.filters()[0].buckets());
+
+        cuckoo.add(b"a").unwrap();
+        cuckoo.add(b"a").unwrap();
+        assert!(cuckoo.contains(b"a"));
+        assert!(!cuckoo.contains(b"b"));
+        assert_eq!(2, cuckoo.items());
+
+        assert!(cuckoo.delete(b"a"));
+        assert!(cuckoo.contains(b"a"));
+        assert!(cuckoo.delete(b"a"));
+        assert!(!cuckoo.contains(b"a"));
+        assert!(!cuckoo.delete(b"a"));
+        assert_eq!((0, 2), (cuckoo.items(), cuckoo.deletes()));
+    }
+
+    #[test]
+    fn test_growth() {
+        let mut cuckoo = Cuckoo::new(64, 2, 20, 1);
+        for i in 0..500 {
+            cuckoo.add(i.to_string().as_bytes()).unwrap();
+        }
+
+        assert!(cuckoo.filters().len() > 1);
+        assert!((0..500).all(|i| cuckoo.contains(i.to_string().as_bytes())));
+
+        let mut cuckoo = Cuckoo::new(4, 2, 20, 0);
+        let results: Vec<_> = (0..100)
+            .map(|i| cuckoo.add(i.to_string().as_bytes()))
+            .collect();
+        let inserted = results.iter().filter(|r| r.is_ok()).count();
+        assert!(inserted <= 4);
+        assert!(results.contains(&Err(Full)));
+        assert_eq!(inserted as u64, cuckoo.items());
+    }
+}
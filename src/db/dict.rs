@@ -0,0 +1,244 @@
+//! Hash table with string keys that can be scanned incrementally, like the dictionaries of
+//! Redis. Tables have a power of two number of buckets, and the scan cursor walks them in
+//! reverse binary order so that every element present for a whole scan is returned at least
+//! once even if the table grows or shrinks between calls.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    mem,
+};
+
+const MIN_BUCKETS: usize = 4;
+/// Buckets a scan call may visit per requested element, so that calls on a sparse table
+/// stay short.
+const MAX_VISITS_PER_ELEMENT: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct Dict<V> {
+    buckets: Vec<Vec<(String, V)>>,
+    len: usize,
+}
+
+impl<V> Default for Dict<V> {
+    fn default() -> Self {
+        Dict {
+            buckets: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+/// Deterministic for the lifetime of the process, so cursors stay valid between calls.
+fn hash(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<V> Dict<V> {
+    pub fn new() -> Self {
+        Dict::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn bucket(&self, key: &str) -> usize {
+        hash(key) as usize & (self.buckets.len() - 1)
+    }
+
+    fn position(&self, key: &str) -> Option<(usize, usize)> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+
+        let bucket = self.bucket(key);
+        let index = self.buckets[bucket].iter().position(|(k, _)| k == key)?;
+        Some((bucket, index))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&V> {
+        let (bucket, index) = self.position(key)?;
+        Some(&self.buckets[bucket][index].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut V> {
+        let (bucket, index) = self.position(key)?;
+        Some(&mut self.buckets[bucket][index].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Sets the value of `key`, returning the previous one.
+    pub fn insert(&mut self, key: String, value: V) -> Option<V> {
+        if let Some(current) = self.get_mut(&key) {
+            return Some(mem::replace(current, value));
+        }
+
+        if self.len >= self.buckets.len() {
+            self.resize((self.buckets.len() * 2).max(MIN_BUCKETS));
+        }
+
+        let bucket = self.bucket(&key);
+        self.buckets[bucket].push((key, value));
+        self.len += 1;
+
+        None
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        let (bucket, index) = self.position(key)?;
+        let (_, value) = self.buckets[bucket].swap_remove(index);
+        self.len -= 1;
+
+        if self.buckets.len() > MIN_BUCKETS && self.len * 8 < self.buckets.len() {
+            self.resize(self.buckets.len() / 2);
+        }
+
+        Some(value)
+    }
+
+    fn resize(&mut self, size: usize) {
+        let buckets = (0..size).map(|_| Vec::new()).collect();
+        let old = mem::replace(&mut self.buckets, buckets);
+
+        for (key, value) in old.into_iter().flatten() {
+            let bucket = self.bucket(&key);
+            self.buckets[bucket].push((key, value));
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &V)> {
+        self.buckets
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Returns the elements of the buckets from `cursor` on, stopping once about `count`
+    /// were found, together with the cursor to resume from, which is zero once the scan is
+    /// complete. Elements may be returned more than once if the table shrinks meanwhile.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&str, &V)>) {
+        let mut items = Vec::new();
+        if self.buckets.is_empty() {
+            return (0, items);
+        }
+
+        let mask = (self.buckets.len() - 1) as u64;
+        let mut cursor = cursor;
+        let mut visits = count.max(1) * MAX_VISITS_PER_ELEMENT;
+
+        loop {
+            let bucket = &self.buckets[(cursor & mask) as usize];
+            items.extend(bucket.iter().map(|(key, value)| (key.as_str(), value)));
+
+            // Increments the reversed cursor, so that the buckets a bucket splits into when
+            // the table grows come right after it.
+            cursor = (cursor | !mask)
+                .reverse_bits()
+                .wrapping_add(1)
+                .reverse_bits();
+
+            visits -= 1;
+            if cursor == 0 || items.len() >= count || visits == 0 {
+                return (cursor, items);
+            }
+        }
+    }
+}
+
+impl<V> FromIterator<(String, V)> for Dict<V> {
+    fn from_iter<I: IntoIterator<Item = (String, V)>>(iter: I) -> Self {
+        let mut dict = Dict::new();
+        for (key, value) in iter {
+            dict.insert(key, value);
+        }
+        dict
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::Dict;
+
+    /// Scans `dict` to the end, calling `between` on it between calls.
+    fn scan_all(dict: &mut Dict<()>, mut between: impl FnMut(&mut Dict<()>)) -> HashSet<String> {
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+
+        loop {
+            let (next, batch) = dict.scan(cursor, 5);
+            seen.extend(batch.into_iter().map(|(key, _)| key.to_owned()));
+            if next == 0 {
+                return seen;
+            }
+            cursor = next;
+            between(dict);
+        }
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut dict: Dict<usize> = (0..100).map(|i| (format!("k{i}"), i)).collect();
+        assert_eq!(100, dict.len());
+        assert_eq!(Some(&7), dict.get("k7"));
+        assert_eq!(Some(7), dict.insert(String::from("k7"), 70));
+        assert_eq!(Some(&70), dict.get("k7"));
+
+        for i in 0..100 {
+            assert!(dict.remove(&format!("k{i}")).is_some());
+        }
+        assert!(dict.is_empty());
+        assert_eq!(None, dict.remove("k7"));
+        assert_eq!(0, dict.iter().count());
+    }
+
+    #[test]
+    fn test_scan_returns_every_item() {
+        let mut dict: Dict<()> = (0..100).map(|i| (format!("item:{i}"), ())).collect();
+        assert_eq!(100, scan_all(&mut dict, |_| {}).len());
+    }
+
+    #[test]
+    fn test_scan_while_resizing() {
+        // Elements present for the whole scan are returned even as the table grows...
+        let mut dict: Dict<()> = (0..50).map(|i| (format!("a{i}"), ())).collect();
+        let mut added = 0;
+        let seen = scan_all(&mut dict, |dict| {
+            for _ in 0..20 {
+                if added < 1000 {
+                    dict.insert(format!("b{added}"), ());
+                    added += 1;
+                }
+            }
+        });
+        assert!((0..50).all(|i| seen.contains(&format!("a{i}"))));
+
+        // ...or shrinks.
+        let mut dict: Dict<()> = (0..500).map(|i| (format!("c{i}"), ())).collect();
+        let mut removed = 0;
+        let seen = scan_all(&mut dict, |dict| {
+            for _ in 0..20 {
+                if removed < 450 {
+                    dict.remove(&format!("c{removed}"));
+                    removed += 1;
+                }
+            }
+        });
+        assert!((450..500).all(|i| seen.contains(&format!("c{i}"))));
+    }
+}
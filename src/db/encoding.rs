@@ -0,0 +1,93 @@
+//! Thresholds past which small collections leave their compact encoding for a hash table.
+//!
+//! Each database holds its own, which are read on every write and can be changed at runtime
+//! with CONFIG SET. Collections never convert back to a compact encoding.
+
+/// The names of the thresholds, as CONFIG GET and CONFIG SET know them.
+pub const NAMES: [&str; 5] = [
+    "hash-max-listpack-entries",
+    "hash-max-listpack-value",
+    "set-max-intset-entries",
+    "set-max-listpack-entries",
+    "set-max-listpack-value",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    /// Maximum number of fields of a listpack hash.
+    pub hash_max_listpack_entries: usize,
+    /// Maximum length in bytes of the fields and values of a listpack hash.
+    pub hash_max_listpack_value: usize,
+    /// Maximum number of members of an intset.
+    pub set_max_intset_entries: usize,
+    /// Maximum number of members of a listpack set.
+    pub set_max_listpack_entries: usize,
+    /// Maximum length in bytes of the members of a listpack set.
+    pub set_max_listpack_value: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            hash_max_listpack_entries: 128,
+            hash_max_listpack_value: 64,
+            set_max_intset_entries: 512,
+            set_max_listpack_entries: 128,
+            set_max_listpack_value: 64,
+        }
+    }
+}
+
+/// The name of the threshold called `name`, ignoring case.
+pub fn find(name: &str) -> Option<&'static str> {
+    NAMES
+        .iter()
+        .copied()
+        .find(|known| known.eq_ignore_ascii_case(name))
+}
+
+impl Thresholds {
+    fn field_mut(&mut self, name: &str) -> Option<&mut usize> {
+        match find(name)? {
+            "hash-max-listpack-entries" => Some(&mut self.hash_max_listpack_entries),
+            "hash-max-listpack-value" => Some(&mut self.hash_max_listpack_value),
+            "set-max-intset-entries" => Some(&mut self.set_max_intset_entries),
+            "set-max-listpack-entries" => Some(&mut self.set_max_listpack_entries),
+            _ => Some(&mut self.set_max_listpack_value),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        let mut thresholds = *self;
+        thresholds.field_mut(name).copied()
+    }
+
+    /// Changes the threshold called `name`, returning `false` if there is none.
+    pub fn set(&mut self, name: &str, value: usize) -> bool {
+        match self.field_mut(name) {
+            Some(field) => {
+                *field = value;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Thresholds;
+
+    #[test]
+    fn test_get_and_set() {
+        let mut thresholds = Thresholds::default();
+        assert_eq!(Some(512), thresholds.get("SET-MAX-INTSET-ENTRIES"));
+        assert_eq!(None, thresholds.get("list-max-listpack-size"));
+
+        assert!(thresholds.set("hash-max-listpack-value", 8));
+        assert_eq!(8, thresholds.hash_max_listpack_value);
+        assert!(!thresholds.set("unknown", 8));
+        // Changing one database's thresholds leaves the defaults alone.
+        assert_eq!(64, Thresholds::default().hash_max_listpack_value);
+    }
+}
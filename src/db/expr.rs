@@ -0,0 +1,372 @@
+//! Filter expressions over the JSON attributes of vector set elements, such as
+//! `.year >= 1980 and (.genre == "drama" or .genre in ["action", "thriller"])`.
+//!
+//! `.field` selects a member of the attributes, and an expression that reads a missing member
+//! does not match.
+
+use super::json::Json;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn from_json(json: &Json) -> Option<Value> {
+        Some(match json {
+            Json::Null => Value::Null,
+            Json::Bool(b) => Value::Bool(*b),
+            Json::Int(n) => Value::Number(*n as f64),
+            Json::Float(f) => Value::Number(*f),
+            Json::String(s) => Value::String(s.clone()),
+            Json::Array(items) => {
+                Value::Array(items.iter().map(Value::from_json).collect::<Option<_>>()?)
+            }
+            Json::Object(_) => return None,
+        })
+    }
+
+    fn is_true(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::Array(items) => !items.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(Value),
+    Selector(Vec<String>),
+    List(Vec<Node>),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Compare(Box<Node>, Op, Box<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Selector(Vec<String>),
+    Number(f64),
+    String(String),
+    Word(String),
+    Op(Op),
+    Punct(char),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    let take_while = |i: &mut usize, f: &dyn Fn(char) -> bool| {
+        let start = *i;
+        while *i < chars.len() && f(chars[*i]) {
+            *i += 1;
+        }
+        chars[start..*i].iter().collect::<String>()
+    };
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '.' if next.is_some_and(is_ident) => {
+                let mut path = Vec::new();
+                while chars.get(i) == Some(&'.') {
+                    i += 1;
+                    path.push(take_while(&mut i, &is_ident));
+                }
+                tokens.push(Token::Selector(path));
+            }
+            '"' | '\'' => {
+                i += 1;
+                let mut s = String::new();
+                loop {
+                    match chars.get(i) {
+                        None => return Err(String::from("unterminated string")),
+                        Some('\\') => {
+                            s.extend(chars.get(i + 1));
+                            i += 2;
+                        }
+                        Some(&q) if q == c => break,
+                        Some(&ch) => {
+                            s.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push(Token::String(s));
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                i += 1;
+                let rest = take_while(&mut i, &|c| c.is_ascii_digit() || ".eE".contains(c));
+                let number = format!("{c}{rest}");
+                let n = number
+                    .parse()
+                    .map_err(|_| format!("invalid number '{number}'"))?;
+                tokens.push(Token::Number(n));
+            }
+            c if is_ident(c) => tokens.push(Token::Word(take_while(&mut i, &is_ident))),
+            '(' | ')' | '[' | ']' | ',' => {
+                tokens.push(Token::Punct(c));
+                i += 1;
+            }
+            _ => {
+                let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+                let (token, len) = match two.as_str() {
+                    "==" => (Token::Op(Op::Eq), 2),
+                    "!=" => (Token::Op(Op::Ne), 2),
+                    "<=" => (Token::Op(Op::Le), 2),
+                    ">=" => (Token::Op(Op::Ge), 2),
+                    "&&" => (Token::Word(String::from("and")), 2),
+                    "||" => (Token::Word(String::from("or")), 2),
+                    _ => match c {
+                        '<' => (Token::Op(Op::Lt), 1),
+                        '>' => (Token::Op(Op::Gt), 1),
+                        '!' => (Token::Word(String::from("not")), 1),
+                        _ => return Err(format!("unexpected character '{c}'")),
+                    },
+                };
+                tokens.push(token);
+                i += len;
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(word))
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.peek() {
+            Some(Token::Punct(p)) if *p == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(format!("expected '{c}'")),
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut expr = self.and()?;
+        while self.is_word("or") {
+            self.pos += 1;
+            expr = Node::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut expr = self.not()?;
+        while self.is_word("and") {
+            self.pos += 1;
+            expr = Node::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Node, String> {
+        if self.is_word("not") {
+            self.pos += 1;
+            return Ok(Node::Not(Box::new(self.not()?)));
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<Node, String> {
+        let left = self.primary()?;
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("in") => Op::In,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.primary()?;
+        Ok(Node::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or(String::from("unexpected end of expression"))?;
+        self.pos += 1;
+
+        match token {
+            Token::Selector(path) => Ok(Node::Selector(path)),
+            Token::Number(n) => Ok(Node::Literal(Value::Number(n))),
+            Token::String(s) => Ok(Node::Literal(Value::String(s))),
+            Token::Word(w) => match w.to_lowercase().as_str() {
+                "true" => Ok(Node::Literal(Value::Bool(true))),
+                "false" => Ok(Node::Literal(Value::Bool(false))),
+                "null" => Ok(Node::Literal(Value::Null)),
+                _ => Err(format!("unexpected '{w}'")),
+            },
+            Token::Punct('(') => {
+                let expr = self.or()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Token::Punct('[') => {
+                let mut items = Vec::new();
+                if self.peek() != Some(&Token::Punct(']')) {
+                    items.push(self.or()?);
+                    while self.peek() == Some(&Token::Punct(',')) {
+                        self.pos += 1;
+                        items.push(self.or()?);
+                    }
+                }
+                self.expect(']')?;
+                Ok(Node::List(items))
+            }
+            token => Err(format!("unexpected {token:?}")),
+        }
+    }
+}
+
+/// A parsed filter expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr(Node);
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let node = parser.or()?;
+        match parser.pos == parser.tokens.len() {
+            true => Ok(Expr(node)),
+            false => Err(String::from("trailing tokens in expression")),
+        }
+    }
+
+    /// Whether the attributes of an element match. Elements without attributes never do.
+    pub fn matches(&self, attributes: Option<&Json>) -> bool {
+        attributes
+            .and_then(|attributes| self.0.eval(attributes))
+            .is_some_and(|value| value.is_true())
+    }
+}
+
+impl Node {
+    /// Evaluates the expression, or returns `None` if it reads a missing member.
+    fn eval(&self, attributes: &Json) -> Option<Value> {
+        Some(match self {
+            Node::Literal(value) => value.clone(),
+            Node::Selector(path) => {
+                let json = path
+                    .iter()
+                    .try_fold(attributes, |json, key| json.get(key))?;
+                Value::from_json(json)?
+            }
+            Node::List(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| item.eval(attributes))
+                    .collect::<Option<_>>()?,
+            ),
+            Node::Not(expr) => Value::Bool(!expr.eval(attributes).is_some_and(|v| v.is_true())),
+            Node::And(a, b) => {
+                Value::Bool(a.eval(attributes)?.is_true() && b.eval(attributes)?.is_true())
+            }
+            Node::Or(a, b) => {
+                let a = a.eval(attributes).is_some_and(|v| v.is_true());
+                Value::Bool(a || b.eval(attributes).is_some_and(|v| v.is_true()))
+            }
+            Node::Compare(a, op, b) => {
+                let (a, b) = (a.eval(attributes)?, b.eval(attributes)?);
+                Value::Bool(compare(&a, *op, &b))
+            }
+        })
+    }
+}
+
+fn compare(a: &Value, op: Op, b: &Value) -> bool {
+    let ordering = match (a, b) {
+        (_, Value::Array(items)) if op == Op::In => return items.contains(a),
+        // A string is in another if it is a substring of it.
+        (Value::String(a), Value::String(b)) if op == Op::In => return b.contains(a.as_str()),
+        (_, _) if op == Op::In => return false,
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Null, Value::Null) => Some(std::cmp::Ordering::Equal),
+        _ => None,
+    };
+
+    match (op, ordering) {
+        (Op::Ne, None) => true,
+        (_, None) => false,
+        (Op::Eq, Some(o)) => o.is_eq(),
+        (Op::Ne, Some(o)) => o.is_ne(),
+        (Op::Lt, Some(o)) => o.is_lt(),
+        (Op::Le, Some(o)) => o.is_le(),
+        (Op::Gt, Some(o)) => o.is_gt(),
+        (Op::Ge, Some(o)) => o.is_ge(),
+        (Op::In, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Expr;
+    use crate::db::json::Json;
+
+    #[test]
+    fn test_matches() {
+        let movie =
+            Json::parse(r#"{"year": 1994, "genre": "drama", "meta": {"rated": true}}"#).unwrap();
+        let matches = |s: &str| Expr::parse(s).unwrap().matches(Some(&movie));
+
+        assert!(matches(".year > 1990 and .genre == 'drama'"));
+        assert!(matches(
+            ".year >= 2000 || .genre in [\"action\", \"drama\"]"
+        ));
+        assert!(matches("!(.year < 1990) && .meta.rated"));
+        assert!(matches("'ram' in .genre"));
+        assert!(!matches(".missing == 1 or .year == 1"));
+        assert!(!matches(".year != 1994"));
+        assert!(!Expr::parse(".year > 1").unwrap().matches(None));
+
+        assert!(Expr::parse(".year >").is_err());
+        assert!(Expr::parse("(.year > 1").is_err());
+        assert!(Expr::parse(".year @ 1").is_err());
+    }
+}
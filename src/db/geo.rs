@@ -0,0 +1,293 @@
+//! Geohashes as used by the geo commands: longitude and latitude are quantized to 26 bits
+//! each and interleaved into a 52-bit integer, which is exact as a sorted set score. Nearby
+//! points share prefixes, so an area maps to a few score ranges.
+
+pub const LON_MIN: f64 = -180.0;
+pub const LON_MAX: f64 = 180.0;
+/// Latitudes are limited to what the Web Mercator projection covers.
+pub const LAT_MIN: f64 = -85.051_128_78;
+pub const LAT_MAX: f64 = 85.051_128_78;
+
+const STEP_MAX: u32 = 26;
+const EARTH_RADIUS_IN_METERS: f64 = 6_372_797.560_856;
+const MERCATOR_MAX: f64 = 20_037_726.37;
+
+const BASE32: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Spreads the 32 bits of `v` over the even bits of a 64-bit integer.
+fn spread(v: u32) -> u64 {
+    let mut x = v as u64;
+    x = (x | (x << 16)) & 0x0000_FFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    (x | (x << 1)) & 0x5555_5555_5555_5555
+}
+
+/// Gathers the even bits of `x`, undoing `spread`.
+fn squash(x: u64) -> u32 {
+    let mut x = x & 0x5555_5555_5555_5555;
+    x = (x | (x >> 1)) & 0x3333_3333_3333_3333;
+    x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x >> 8)) & 0x0000_FFFF_0000_FFFF;
+    ((x | (x >> 16)) & 0x0000_0000_FFFF_FFFF) as u32
+}
+
+/// Latitude bits go in the even positions and longitude bits in the odd ones.
+fn interleave(lat: u32, lon: u32) -> u64 {
+    spread(lat) | (spread(lon) << 1)
+}
+
+fn deinterleave(bits: u64) -> (u32, u32) {
+    (squash(bits), squash(bits >> 1))
+}
+
+fn quantize(value: f64, min: f64, max: f64, step: u32) -> u32 {
+    let offset = (value - min) / (max - min) * (1u64 << step) as f64;
+    (offset as u64).min((1 << step) - 1) as u32
+}
+
+fn encode_in(lon: f64, lat: f64, lat_min: f64, lat_max: f64, step: u32) -> u64 {
+    interleave(
+        quantize(lat, lat_min, lat_max, step),
+        quantize(lon, LON_MIN, LON_MAX, step),
+    )
+}
+
+pub fn is_valid(lon: f64, lat: f64) -> bool {
+    (LON_MIN..=LON_MAX).contains(&lon) && (LAT_MIN..=LAT_MAX).contains(&lat)
+}
+
+/// Returns the 52-bit geohash of a point, used as its sorted set score.
+pub fn encode(lon: f64, lat: f64) -> u64 {
+    encode_in(lon, lat, LAT_MIN, LAT_MAX, STEP_MAX)
+}
+
+/// The cell of a geohash of `step` bits per coordinate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Area {
+    lon: (f64, f64),
+    lat: (f64, f64),
+}
+
+fn area(bits: u64, step: u32) -> Area {
+    let (lat, lon) = deinterleave(bits);
+    let cells = (1u64 << step) as f64;
+    let bounds = |i: u32, min: f64, max: f64| {
+        let scale = max - min;
+        (
+            min + i as f64 / cells * scale,
+            min + (i as f64 + 1.0) / cells * scale,
+        )
+    };
+
+    Area {
+        lon: bounds(lon, LON_MIN, LON_MAX),
+        lat: bounds(lat, LAT_MIN, LAT_MAX),
+    }
+}
+
+/// Returns the center of the cell of a 52-bit geohash as `(longitude, latitude)`.
+pub fn decode(bits: u64) -> (f64, f64) {
+    let area = area(bits, STEP_MAX);
+    let lon = (area.lon.0 + area.lon.1) / 2.0;
+    let lat = (area.lat.0 + area.lat.1) / 2.0;
+    (lon.clamp(LON_MIN, LON_MAX), lat.clamp(LAT_MIN, LAT_MAX))
+}
+
+/// Returns the standard 11 character geohash of a point stored with `bits`. Standard
+/// geohashes cover latitudes from -90 to 90, so the point is encoded again.
+pub fn to_base32(bits: u64) -> String {
+    let (lon, lat) = decode(bits);
+    let bits = encode_in(lon, lat, -90.0, 90.0, STEP_MAX);
+
+    (0..11)
+        .map(|i| {
+            // The 52 bits fill 10 characters and a half, and the last one is left as zero.
+            let index = match i {
+                10 => 0,
+                _ => (bits >> (52 - (i + 1) * 5)) & 0x1F,
+            };
+            BASE32[index as usize] as char
+        })
+        .collect()
+}
+
+/// Great-circle distance in meters with the haversine formula.
+pub fn distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let u = ((lat2 - lat1) / 2.0).sin();
+    let v = ((lon2 - lon1).to_radians() / 2.0).sin();
+    let a = u * u + lat1.cos() * lat2.cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+/// An area to search around a center, in meters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+impl Shape {
+    /// Radius of the circle that contains the shape.
+    fn radius(&self) -> f64 {
+        match *self {
+            Shape::Radius(r) => r,
+            Shape::Box { width, height } => (width / 2.0).hypot(height / 2.0),
+        }
+    }
+
+    /// Returns the distance from `center` to `point` if the point is within the shape.
+    pub fn distance(&self, center: (f64, f64), point: (f64, f64)) -> Option<f64> {
+        let ((lon1, lat1), (lon2, lat2)) = (center, point);
+
+        match *self {
+            Shape::Radius(radius) => {
+                Some(distance(lon1, lat1, lon2, lat2)).filter(|d| *d <= radius)
+            }
+            Shape::Box { width, height } => {
+                let lat_distance =
+                    EARTH_RADIUS_IN_METERS * (lat2.to_radians() - lat1.to_radians()).abs();
+                if lat_distance > height / 2.0 {
+                    return None;
+                }
+
+                // The width is measured along the latitude of the point.
+                if distance(lon1, lat2, lon2, lat2) > width / 2.0 {
+                    return None;
+                }
+
+                Some(distance(lon1, lat1, lon2, lat2))
+            }
+        }
+    }
+}
+
+/// Returns the number of bits per coordinate of the cells to look at, so that the cell of
+/// the center and its neighbors cover `radius` meters.
+fn estimate_step(radius: f64, lat: f64) -> u32 {
+    if radius == 0.0 {
+        return STEP_MAX;
+    }
+
+    let mut step: i32 = 1;
+    let mut range = radius;
+    while range < MERCATOR_MAX {
+        range *= 2.0;
+        step += 1;
+    }
+    // Make sure the range is included in most of the base cases.
+    step -= 2;
+
+    // Cells get narrower towards the poles.
+    if !(-66.0..=66.0).contains(&lat) {
+        step -= 1;
+        if !(-80.0..=80.0).contains(&lat) {
+            step -= 1;
+        }
+    }
+
+    step.clamp(1, STEP_MAX as i32) as u32
+}
+
+/// Returns the cells around the one of `(lon, lat)` at `step`, the center cell first.
+fn neighbors(lon: f64, lat: f64, step: u32) -> Vec<u64> {
+    let center = encode_in(lon, lat, LAT_MIN, LAT_MAX, step);
+    let (lat_i, lon_i) = deinterleave(center);
+    let mask = (1u64 << step) - 1;
+
+    let mut cells = vec![center];
+    for dlat in [-1i64, 0, 1] {
+        for dlon in [-1i64, 0, 1] {
+            // Cells wrap around, so the east of the last meridian is the first one.
+            let lat = (lat_i as i64 + dlat) as u64 & mask;
+            let lon = (lon_i as i64 + dlon) as u64 & mask;
+            let cell = interleave(lat as u32, lon as u32);
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+    }
+
+    cells
+}
+
+/// Returns the score ranges, as `[min, max)`, of the cells to scan for points within `shape`
+/// of `(lon, lat)`. Points in these ranges still have to be checked against the shape.
+pub fn search_ranges(lon: f64, lat: f64, shape: &Shape) -> Vec<(u64, u64)> {
+    let radius = shape.radius();
+    let mut step = estimate_step(radius, lat);
+
+    // When the center is near the edge of its cell, the neighbors at this step may not reach
+    // far enough, so use larger cells.
+    let cell = area(encode_in(lon, lat, LAT_MIN, LAT_MAX, step), step);
+    let height = cell.lat.1 - cell.lat.0;
+    let width = cell.lon.1 - cell.lon.0;
+    let too_small = [
+        distance(lon, lat, lon, cell.lat.1 + height),
+        distance(lon, lat, lon, cell.lat.0 - height),
+        distance(lon, lat, cell.lon.1 + width, lat),
+        distance(lon, lat, cell.lon.0 - width, lat),
+    ]
+    .iter()
+    .any(|d| *d < radius);
+    if step > 1 && too_small {
+        step -= 1;
+    }
+
+    let shift = 2 * (STEP_MAX - step);
+    neighbors(lon, lat, step)
+        .into_iter()
+        .map(|cell| (cell << shift, (cell + 1) << shift))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, distance, encode, is_valid, search_ranges, to_base32, Shape};
+
+    #[test]
+    fn test_encode_decode() {
+        // Palermo, as in the Redis documentation.
+        let bits = encode(13.361389, 38.115556);
+        assert_eq!(3479099956230698, bits);
+
+        let (lon, lat) = decode(bits);
+        assert!((lon - 13.361389).abs() < 1e-5);
+        assert!((lat - 38.115556).abs() < 1e-5);
+
+        assert_eq!("sqc8b49rny0", to_base32(bits));
+        assert!(!is_valid(0.0, 86.0));
+    }
+
+    #[test]
+    fn test_distance() {
+        let palermo = decode(encode(13.361389, 38.115556));
+        let catania = decode(encode(15.087269, 37.502669));
+
+        let d = distance(palermo.0, palermo.1, catania.0, catania.1);
+        assert!((d - 166274.1516).abs() < 0.01);
+
+        let shape = Shape::Radius(200_000.0);
+        assert!(shape.distance(palermo, catania).is_some());
+        let shape = Shape::Box {
+            width: 100_000.0,
+            height: 400_000.0,
+        };
+        assert!(shape.distance(palermo, catania).is_none());
+    }
+
+    #[test]
+    fn test_search_ranges() {
+        let center = (15.0, 37.0);
+        let catania = encode(15.087269, 37.502669);
+
+        let ranges = search_ranges(center.0, center.1, &Shape::Radius(200_000.0));
+        assert!(ranges.len() <= 9);
+        assert!(ranges
+            .iter()
+            .any(|(min, max)| (*min..*max).contains(&catania)));
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    time::SystemTime,
+};
+
+use crate::db::{
+    dict::Dict,
+    encoding::Thresholds,
+    listpack::{Entry, Listpack},
+};
+
+#[derive(Debug)]
+enum Fields {
+    /// Fields and values alternating in a listpack, for small hashes.
+    Listpack(Listpack),
+    Table(Dict<String>),
+}
+
+#[derive(Debug)]
+pub struct Hash {
+    fields: Fields,
+    /// Expiration time of fields that have one, mirrored in `expiry_order` so the next field
+    /// to expire can be found without scanning.
+    expires: HashMap<String, SystemTime>,
+    expiry_order: BTreeSet<(SystemTime, String)>,
+}
+
+impl Default for Hash {
+    fn default() -> Self {
+        Hash {
+            fields: Fields::Listpack(Listpack::new()),
+            expires: HashMap::new(),
+            expiry_order: BTreeSet::new(),
+        }
+    }
+}
+
+impl Hash {
+    pub fn new() -> Self {
+        Hash::default()
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.fields {
+            Fields::Listpack(lp) => lp.len() / 2,
+            Fields::Table(fields) => fields.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, field: &str) -> Option<Cow<'_, str>> {
+        match &self.fields {
+            Fields::Listpack(lp) => {
+                let off = lp.find(Entry::Str(field), 1)?;
+                lp.next(off).map(|off| lp.get(off).into())
+            }
+            Fields::Table(fields) => fields.get(field).map(|v| Cow::Borrowed(v.as_str())),
+        }
+    }
+
+    pub fn contains(&self, field: &str) -> bool {
+        match &self.fields {
+            Fields::Listpack(lp) => lp.find(Entry::Str(field), 1).is_some(),
+            Fields::Table(fields) => fields.contains_key(field),
+        }
+    }
+
+    /// Sets `field` to `value`, returning `true` if the field is new. Overwriting a field
+    /// clears its expiration.
+    pub fn insert(&mut self, field: &str, value: &str, thresholds: &Thresholds) -> bool {
+        self.persist(field);
+
+        let max_value = thresholds.hash_max_listpack_value;
+        if field.len() > max_value || value.len() > max_value {
+            self.convert_to_table();
+        }
+
+        let added = match &mut self.fields {
+            Fields::Listpack(lp) => match lp.find(Entry::Str(field), 1) {
+                Some(off) => {
+                    let off = lp.next(off).expect("fields have a value");
+                    lp.replace(off, Entry::Str(value));
+                    false
+                }
+                None => {
+                    lp.push(Entry::Str(field));
+                    lp.push(Entry::Str(value));
+                    true
+                }
+            },
+            Fields::Table(fields) => fields.insert(field.to_owned(), value.to_owned()).is_none(),
+        };
+
+        if self.len() > thresholds.hash_max_listpack_entries {
+            self.convert_to_table();
+        }
+
+        added
+    }
+
+    pub fn remove(&mut self, field: &str) -> bool {
+        self.persist(field);
+        self.remove_field(field)
+    }
+
+    fn remove_field(&mut self, field: &str) -> bool {
+        match &mut self.fields {
+            Fields::Listpack(lp) => match lp.find(Entry::Str(field), 1) {
+                Some(off) => {
+                    // Removing the field moves its value to the same offset.
+                    lp.remove(off);
+                    lp.remove(off);
+                    true
+                }
+                None => false,
+            },
+            Fields::Table(fields) => fields.remove(field).is_some(),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (Cow<'_, str>, Cow<'_, str>)> + '_> {
+        match &self.fields {
+            Fields::Listpack(lp) => {
+                let mut entries = lp.iter();
+                Box::new(std::iter::from_fn(move || {
+                    Some((entries.next()?.into(), entries.next()?.into()))
+                }))
+            }
+            Fields::Table(fields) => Box::new(
+                fields
+                    .iter()
+                    .map(|(f, v)| (Cow::Borrowed(f), Cow::Borrowed(v.as_str()))),
+            ),
+        }
+    }
+
+    /// Returns about `count` fields with their values from `cursor` on, and the cursor to
+    /// resume from, which is zero once the scan is complete. Small hashes are returned whole.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(String, String)>) {
+        match &self.fields {
+            Fields::Listpack(_) => {
+                let fields = self.iter().map(|(f, v)| (f.into_owned(), v.into_owned()));
+                (0, fields.collect())
+            }
+            Fields::Table(fields) => {
+                let (cursor, batch) = fields.scan(cursor, count);
+                let batch = batch
+                    .into_iter()
+                    .map(|(f, v)| (f.to_owned(), v.clone()))
+                    .collect();
+                (cursor, batch)
+            }
+        }
+    }
+
+    /// The listpack holding the fields and values, if the hash is small enough to use one.
+    pub fn listpack(&self) -> Option<&Listpack> {
+        match &self.fields {
+            Fields::Listpack(lp) => Some(lp),
+            Fields::Table(_) => None,
+        }
+    }
+
+    /// Estimate of the bytes allocated for the fields and their expiration, ignoring the spare
+    /// capacity of tables.
+    pub fn heap_size(&self) -> usize {
+        let fields = match &self.fields {
+            Fields::Listpack(lp) => lp.bytes(),
+            Fields::Table(fields) => fields
+                .iter()
+                .map(|(f, v)| 2 * size_of::<String>() + f.len() + v.len())
+                .sum(),
+        };
+        // Expiring fields are named in both the map and the ordered set.
+        let expires: usize = self
+            .expires
+            .keys()
+            .map(|f| 2 * (size_of::<String>() + f.len() + size_of::<SystemTime>()))
+            .sum();
+
+        fields + expires
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match &self.fields {
+            Fields::Listpack(_) => "listpack",
+            Fields::Table(_) => "hashtable",
+        }
+    }
+
+    fn convert_to_table(&mut self) {
+        if let Fields::Listpack(_) = self.fields {
+            let fields = self
+                .iter()
+                .map(|(f, v)| (f.into_owned(), v.into_owned()))
+                .collect();
+            self.fields = Fields::Table(fields);
+        }
+    }
+
+    pub fn expire_time(&self, field: &str) -> Option<SystemTime> {
+        self.expires.get(field).copied()
+    }
+
+    /// Sets the expiration of `field`. Hashes with expiring fields use a hash table, as their
+    /// listpack has no room for expiration times.
+    pub fn set_expire_time(&mut self, field: &str, at: SystemTime) {
+        self.convert_to_table();
+        self.persist(field);
+        self.expires.insert(field.to_owned(), at);
+        self.expiry_order.insert((at, field.to_owned()));
+    }
+
+    /// Removes the expiration of `field`, returning `true` if it had one.
+    pub fn persist(&mut self, field: &str) -> bool {
+        match self.expires.remove(field) {
+            Some(at) => self.expiry_order.remove(&(at, field.to_owned())),
+            None => false,
+        }
+    }
+
+    pub fn has_volatile_fields(&self) -> bool {
+        !self.expires.is_empty()
+    }
+
+    /// Deletes every field whose expiration is at or before `now`, returning their names.
+    pub fn remove_expired(&mut self, now: SystemTime) -> Vec<String> {
+        let mut expired = Vec::new();
+
+        while let Some((at, _)) = self.expiry_order.first() {
+            if *at > now {
+                break;
+            }
+
+            if let Some((_, field)) = self.expiry_order.pop_first() {
+                self.expires.remove(&field);
+                self.remove_field(&field);
+                expired.push(field);
+            }
+        }
+
+        expired
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use super::Hash;
+    use crate::db::encoding::Thresholds;
+
+    #[test]
+    fn test_remove_expired() {
+        let now = SystemTime::now();
+        let thresholds = Thresholds::default();
+        let mut hash = Hash::new();
+        hash.insert("a", "1", &thresholds);
+        hash.insert("b", "2", &thresholds);
+        hash.insert("c", "3", &thresholds);
+        hash.set_expire_time("a", now - Duration::from_secs(1));
+        hash.set_expire_time("b", now + Duration::from_secs(60));
+
+        assert_eq!(vec![String::from("a")], hash.remove_expired(now));
+        assert_eq!(2, hash.len());
+        assert!(hash.has_volatile_fields());
+    }
+
+    #[test]
+    fn test_insert_clears_expiration() {
+        let thresholds = Thresholds::default();
+        let mut hash = Hash::new();
+        hash.insert("a", "1", &thresholds);
+        hash.set_expire_time("a", SystemTime::now());
+
+        hash.insert("a", "2", &thresholds);
+
+        assert_eq!(None, hash.expire_time("a"));
+        assert!(!hash.has_volatile_fields());
+    }
+
+    #[test]
+    fn test_listpack_encoding() {
+        let thresholds = Thresholds::default();
+        let mut hash = Hash::new();
+        hash.insert("a", "1", &thresholds);
+        hash.insert("b", "x", &thresholds);
+        hash.insert("a", "2", &thresholds);
+        hash.remove("b");
+
+        assert_eq!("listpack", hash.encoding());
+        assert_eq!(1, hash.len());
+        assert_eq!(Some("2"), hash.get("a").as_deref());
+        assert!(!hash.contains("b"));
+    }
+
+    #[test]
+    fn test_convert_to_table() {
+        let thresholds = Thresholds::default();
+        let mut hash = Hash::new();
+        for i in 0..thresholds.hash_max_listpack_entries {
+            hash.insert(&i.to_string(), "v", &thresholds);
+        }
+        assert_eq!("listpack", hash.encoding());
+
+        hash.insert("last", "v", &thresholds);
+        assert_eq!("hashtable", hash.encoding());
+        assert_eq!(thresholds.hash_max_listpack_entries + 1, hash.len());
+        assert_eq!(Some("v"), hash.get("0").as_deref());
+
+        let mut hash = Hash::new();
+        hash.insert(
+            "a",
+            &"x".repeat(thresholds.hash_max_listpack_value + 1),
+            &thresholds,
+        );
+        assert_eq!("hashtable", hash.encoding());
+    }
+}
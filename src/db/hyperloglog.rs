@@ -0,0 +1,398 @@
+//! HyperLogLogs are stored as strings in the Redis layout, so they can be read and written
+//! with GET and SET and stay compatible with RDB files.
+//!
+//! A 16 byte header holds the `HYLL` magic, the encoding, three unused bytes, and the last
+//! computed cardinality as a little endian integer whose most significant bit flags it as
+//! stale. The 16384 registers of 6 bits follow, either packed (dense) or run-length encoded
+//! (sparse).
+
+use crate::util::hash::murmurhash64a;
+
+/// Bits of the hash used to select a register.
+const P: u32 = 14;
+/// Bits of the hash left for counting leading zeros.
+const Q: u32 = 64 - P;
+pub const REGISTERS: usize = 1 << P;
+const REGISTER_BITS: usize = 6;
+const REGISTER_MAX: u8 = (1 << REGISTER_BITS) - 1;
+
+const MAGIC: &[u8] = b"HYLL";
+const HEADER_LEN: usize = 16;
+const DENSE_LEN: usize = HEADER_LEN + (REGISTERS * REGISTER_BITS).div_ceil(8);
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+
+/// Sparse HyperLogLogs larger than this, header included, are converted to the dense
+/// encoding.
+const SPARSE_MAX_BYTES: usize = 3000;
+/// Largest register value the sparse encoding can hold.
+const SPARSE_VAL_MAX: u8 = 32;
+const SPARSE_VAL_MAX_LEN: usize = 4;
+const SPARSE_ZERO_MAX_LEN: usize = 64;
+const SPARSE_XZERO_MAX_LEN: usize = REGISTERS;
+
+/// Flags the cached cardinality as stale.
+const CACHE_STALE: u8 = 0x80;
+
+const HASH_SEED: u64 = 0xadc8_3b19;
+
+/// Why a string could not be used as a HyperLogLog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invalid {
+    /// The string is not a HyperLogLog at all.
+    NotHll,
+    /// The header is right but the registers are not.
+    Corrupted,
+}
+
+/// Returns an empty HyperLogLog, a single sparse run of zeros with a cached cardinality of
+/// zero.
+pub fn new() -> Vec<u8> {
+    let mut bytes = header(SPARSE);
+    bytes[15] = 0;
+    push_xzero(&mut bytes, REGISTERS);
+    bytes
+}
+
+fn header(encoding: u8) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    bytes.extend(MAGIC);
+    bytes.extend([encoding, 0, 0, 0]);
+    bytes.extend([0; 7]);
+    bytes.push(CACHE_STALE);
+    bytes
+}
+
+pub fn is_dense(bytes: &[u8]) -> bool {
+    bytes[4] == DENSE
+}
+
+fn check_header(bytes: &[u8]) -> Result<(), Invalid> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC || bytes[4] > SPARSE {
+        return Err(Invalid::NotHll);
+    }
+
+    if bytes[4] == DENSE && bytes.len() != DENSE_LEN {
+        return Err(Invalid::NotHll);
+    }
+
+    Ok(())
+}
+
+/// Decodes all registers of a dense or sparse HyperLogLog.
+pub fn registers(bytes: &[u8]) -> Result<Vec<u8>, Invalid> {
+    check_header(bytes)?;
+
+    let data = &bytes[HEADER_LEN..];
+    if is_dense(bytes) {
+        return Ok((0..REGISTERS).map(|i| get_register(data, i)).collect());
+    }
+
+    let mut registers = Vec::with_capacity(REGISTERS);
+    let mut ops = data.iter();
+    while let Some(&op) = ops.next() {
+        let (value, len) = match op >> 6 {
+            0b00 => (0, (op & 0x3F) as usize + 1),
+            0b01 => {
+                let low = *ops.next().ok_or(Invalid::Corrupted)?;
+                (0, (((op & 0x3F) as usize) << 8 | low as usize) + 1)
+            }
+            _ => (((op >> 2) & 0x1F) + 1, (op & 0x03) as usize + 1),
+        };
+
+        if registers.len() + len > REGISTERS {
+            return Err(Invalid::Corrupted);
+        }
+        registers.extend(std::iter::repeat_n(value, len));
+    }
+
+    match registers.len() {
+        REGISTERS => Ok(registers),
+        _ => Err(Invalid::Corrupted),
+    }
+}
+
+/// Encodes `registers` as a sparse HyperLogLog when `sparse` is set and they fit, or as a
+/// dense one otherwise.
+pub fn encode(registers: &[u8], sparse: bool) -> Vec<u8> {
+    if sparse {
+        if let Some(bytes) = encode_sparse(registers) {
+            return bytes;
+        }
+    }
+
+    let mut bytes = header(DENSE);
+    bytes.resize(DENSE_LEN, 0);
+    for (i, &value) in registers.iter().enumerate() {
+        set_register(&mut bytes[HEADER_LEN..], i, value);
+    }
+    bytes
+}
+
+fn encode_sparse(registers: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = header(SPARSE);
+
+    let mut i = 0;
+    while i < registers.len() {
+        let value = registers[i];
+        let run = registers[i..].iter().take_while(|&&v| v == value).count();
+        i += run;
+
+        match value {
+            0 => push_xzero(&mut bytes, run),
+            v if v > SPARSE_VAL_MAX => return None,
+            v => {
+                let mut left = run;
+                while left > 0 {
+                    let len = left.min(SPARSE_VAL_MAX_LEN);
+                    bytes.push(0x80 | (v - 1) << 2 | (len - 1) as u8);
+                    left -= len;
+                }
+            }
+        }
+
+        if bytes.len() > SPARSE_MAX_BYTES {
+            return None;
+        }
+    }
+
+    Some(bytes)
+}
+
+/// Appends a run of `len` zero registers, as ZERO opcodes for short runs and XZERO ones for
+/// long runs.
+fn push_xzero(bytes: &mut Vec<u8>, mut len: usize) {
+    while len > 0 {
+        let run = len.min(SPARSE_XZERO_MAX_LEN);
+        if run > SPARSE_ZERO_MAX_LEN {
+            let n = run - 1;
+            bytes.extend([0x40 | (n >> 8) as u8, n as u8]);
+        } else {
+            bytes.push((run - 1) as u8);
+        }
+        len -= run;
+    }
+}
+
+/// Registers are packed little endian: register `i` starts at bit `6 * i`, counting from
+/// the least significant bit of each byte.
+fn get_register(data: &[u8], i: usize) -> u8 {
+    let bit = i * REGISTER_BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let low = data[byte] as u16 >> shift;
+    let high = data.get(byte + 1).map_or(0, |&b| (b as u16) << (8 - shift));
+    (low | high) as u8 & REGISTER_MAX
+}
+
+fn set_register(data: &mut [u8], i: usize, value: u8) {
+    let bit = i * REGISTER_BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let value = value as u16;
+
+    data[byte] &= !((REGISTER_MAX as u16) << shift) as u8;
+    data[byte] |= (value << shift) as u8;
+    if let Some(next) = data.get_mut(byte + 1) {
+        *next &= !((REGISTER_MAX as u16) >> (8 - shift)) as u8;
+        *next |= (value >> (8 - shift)) as u8;
+    }
+}
+
+/// Returns the register an element falls into, and the value it proposes for it: the
+/// position of the first set bit in the rest of the hash.
+fn register_of(element: &[u8]) -> (usize, u8) {
+    let hash = murmurhash64a(element, HASH_SEED);
+    let index = (hash & (REGISTERS as u64 - 1)) as usize;
+    // Setting the bit past the hash bounds the count to Q + 1.
+    let rest = (hash >> P) | (1 << Q);
+    (index, rest.trailing_zeros() as u8 + 1)
+}
+
+/// Adds `elements`, returning whether any register changed. Dense HyperLogLogs are updated
+/// in place, while sparse ones are rewritten and switch to the dense encoding once they
+/// grow too large.
+pub fn add<'a>(
+    bytes: &mut Vec<u8>,
+    elements: impl IntoIterator<Item = &'a [u8]>,
+) -> Result<bool, Invalid> {
+    check_header(bytes)?;
+
+    let mut changed = false;
+    if is_dense(bytes) {
+        let data = &mut bytes[HEADER_LEN..];
+        for element in elements {
+            let (index, count) = register_of(element);
+            if count > get_register(data, index) {
+                set_register(data, index, count);
+                changed = true;
+            }
+        }
+    } else {
+        let mut registers = registers(bytes)?;
+        for element in elements {
+            let (index, count) = register_of(element);
+            if count > registers[index] {
+                registers[index] = count;
+                changed = true;
+            }
+        }
+
+        if changed {
+            *bytes = encode(&registers, true);
+        }
+    }
+
+    if changed {
+        bytes[15] |= CACHE_STALE;
+    }
+
+    Ok(changed)
+}
+
+/// Returns the estimated cardinality, computing it only if the cached one is stale.
+pub fn count(bytes: &mut [u8]) -> Result<u64, Invalid> {
+    check_header(bytes)?;
+
+    if bytes[15] & CACHE_STALE == 0 {
+        let cached = u64::from_le_bytes(bytes[8..16].try_into().expect("8 byte cache"));
+        return Ok(cached);
+    }
+
+    let card = estimate(&registers(bytes)?);
+    bytes[8..16].copy_from_slice(&card.to_le_bytes());
+    Ok(card)
+}
+
+/// Keeps the largest value of each register, which gives the HyperLogLog of the union.
+pub fn merge(max: &mut [u8], registers: &[u8]) {
+    for (m, &r) in max.iter_mut().zip(registers) {
+        *m = (*m).max(r);
+    }
+}
+
+/// Estimates the cardinality from the histogram of the registers, with the improved
+/// estimator from Otmar Ertl's "New cardinality estimation algorithms for HyperLogLog
+/// sketches", which needs no bias correction.
+pub fn estimate(registers: &[u8]) -> u64 {
+    let m = REGISTERS as f64;
+    let mut histogram = [0u32; Q as usize + 2];
+    for &r in registers {
+        histogram[r as usize] += 1;
+    }
+
+    let mut z = m * tau((m - histogram[Q as usize + 1] as f64) / m);
+    for &n in histogram[1..=Q as usize].iter().rev() {
+        z += n as f64;
+        z *= 0.5;
+    }
+    z += m * sigma(histogram[0] as f64 / m);
+
+    let alpha = 0.5 / std::f64::consts::LN_2;
+    (alpha * m * m / z).round() as u64
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let prev = z;
+        z += x * y;
+        y += y;
+        if z == prev {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let prev = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if z == prev {
+            return z / 3.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        add, count, encode, is_dense, merge, new, registers, Invalid, DENSE_LEN, REGISTERS,
+    };
+
+    fn elements(range: std::ops::Range<u32>) -> Vec<Vec<u8>> {
+        range.map(|i| format!("element:{i}").into_bytes()).collect()
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut hll = new();
+        assert_eq!(b"HYLL\x01\0\0\0\0\0\0\0\0\0\0\0\x7f\xff", hll.as_slice());
+        assert_eq!(Ok(0), count(&mut hll));
+        assert_eq!(vec![0; REGISTERS], registers(&hll).unwrap());
+
+        let letters = [b"a", b"b", b"c", b"d", b"e", b"f", b"g"];
+        add(&mut hll, letters.iter().map(|e| e.as_slice())).unwrap();
+        assert_eq!(Ok(7), count(&mut hll));
+    }
+
+    #[test]
+    fn test_sparse_to_dense() {
+        let mut hll = new();
+        let few = elements(0..100);
+        assert_eq!(Ok(true), add(&mut hll, few.iter().map(|e| e.as_slice())));
+        assert_eq!(Ok(false), add(&mut hll, few.iter().map(|e| e.as_slice())));
+        assert!(!is_dense(&hll));
+        assert!((99..=101).contains(&count(&mut hll).unwrap()));
+
+        let many = elements(0..5000);
+        add(&mut hll, many.iter().map(|e| e.as_slice())).unwrap();
+        assert!(is_dense(&hll));
+        assert_eq!(DENSE_LEN, hll.len());
+
+        // Both encodings hold the same registers.
+        let regs = registers(&hll).unwrap();
+        assert_eq!(regs, registers(&encode(&regs, false)).unwrap());
+        let mut sparse = new();
+        add(&mut sparse, many.iter().map(|e| e.as_slice())).unwrap();
+        assert_eq!(regs, registers(&sparse).unwrap());
+    }
+
+    #[test]
+    fn test_error_rate() {
+        let mut hll = new();
+        let mut union = vec![0; REGISTERS];
+        for chunk in elements(0..200_000).chunks(50_000) {
+            let mut part = new();
+            add(&mut part, chunk.iter().map(|e| e.as_slice())).unwrap();
+            add(&mut hll, chunk.iter().map(|e| e.as_slice())).unwrap();
+            merge(&mut union, &registers(&part).unwrap());
+        }
+
+        let card = count(&mut hll).unwrap();
+        let error = (card as f64 - 200_000.0).abs() / 200_000.0;
+        assert!(error < 0.0081 * 3.0, "estimate {card} is too far off");
+        assert_eq!(registers(&hll).unwrap(), union);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Err(Invalid::NotHll), count(&mut b"hello".to_vec()));
+
+        let mut truncated = new();
+        truncated.pop();
+        assert_eq!(Err(Invalid::Corrupted), registers(&truncated));
+    }
+}
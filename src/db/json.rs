@@ -0,0 +1,743 @@
+//! JSON documents, parsed once and then changed in place through paths.
+//!
+//! Paths starting with `$` are JSONPath and may match any number of values: `$.a`, `$['a']`,
+//! `$.*`, `$[1]`, `$[-1]` and slices like `$[1:5:2]`. Other paths use the legacy syntax, such
+//! as `.a[0]` or `a.b`, with `.` for the root, and refer to a single value.
+
+use std::fmt::Write;
+
+/// Deeper documents are rejected, so that parsing and printing them can't exhaust the stack.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members keep their insertion order.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(s: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            bytes: s.as_bytes(),
+            src: s,
+            pos: 0,
+            depth: 0,
+        };
+
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.pos == s.len() {
+            true => Ok(value),
+            false => Err(format!("trailing characters at offset {}", parser.pos)),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool(_) => "boolean",
+            Json::Int(_) => "integer",
+            Json::Float(_) => "number",
+            Json::String(_) => "string",
+            Json::Array(_) => "array",
+            Json::Object(_) => "object",
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Json> {
+        match self {
+            Json::Object(members) => members.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Serializes the value without any whitespace.
+    pub fn to_compact(&self) -> String {
+        self.to_formatted(&Format::default())
+    }
+
+    pub fn to_formatted(&self, format: &Format) -> String {
+        let mut out = String::new();
+        self.write(&mut out, format, 0);
+        out
+    }
+
+    fn write(&self, out: &mut String, format: &Format, depth: usize) {
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Json::Int(n) => out.push_str(&n.to_string()),
+            Json::Float(f) => out.push_str(&format_float(*f)),
+            Json::String(s) => write_string(out, s),
+            Json::Array(items) => {
+                format.write_container(out, depth, ('[', ']'), items, |out, item| {
+                    item.write(out, format, depth + 1)
+                });
+            }
+            Json::Object(members) => {
+                format.write_container(out, depth, ('{', '}'), members, |out, (key, value)| {
+                    write_string(out, key);
+                    out.push(':');
+                    out.push_str(format.space);
+                    value.write(out, format, depth + 1);
+                });
+            }
+        }
+    }
+}
+
+/// Floats always show a fraction or an exponent, so they read back as floats.
+fn format_float(f: f64) -> String {
+    let s = format!("{f:?}");
+    match s.contains(['.', 'e', 'E']) {
+        true => s,
+        false => format!("{s}.0"),
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// The whitespace JSON.GET puts around values: `indent` once per nesting level, `newline`
+/// after each element and `space` after each key.
+#[derive(Debug, Default)]
+pub struct Format<'a> {
+    pub indent: &'a str,
+    pub newline: &'a str,
+    pub space: &'a str,
+}
+
+impl Format<'_> {
+    fn write_container<T>(
+        &self,
+        out: &mut String,
+        depth: usize,
+        (open, close): (char, char),
+        items: &[T],
+        mut write_item: impl FnMut(&mut String, &T),
+    ) {
+        out.push(open);
+        if items.is_empty() {
+            out.push(close);
+            return;
+        }
+
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(self.newline);
+            out.push_str(&self.indent.repeat(depth + 1));
+            write_item(out, item);
+        }
+
+        out.push_str(self.newline);
+        out.push_str(&self.indent.repeat(depth));
+        out.push(close);
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    src: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn error<T>(&self, what: &str) -> Result<T, String> {
+        Err(format!("{what} at offset {}", self.pos))
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) == Some(&b) {
+            true => {
+                self.pos += 1;
+                Ok(())
+            }
+            false => self.error(&format!("expected '{}'", b as char)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+
+        match self.bytes.get(self.pos) {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b'[') => self.nested(|p| p.array()),
+            Some(b'{') => self.nested(|p| p.object()),
+            _ => self.error("expected value"),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        match self.src[self.pos..].starts_with(word) {
+            true => {
+                self.pos += word.len();
+                Ok(value)
+            }
+            false => self.error("expected value"),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Json, String>,
+    ) -> Result<Json, String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return self.error("nesting too deep");
+        }
+
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        if self.bytes[self.pos] == b'-' {
+            self.pos += 1;
+        }
+
+        let leading_zero = self.bytes.get(self.pos) == Some(&b'0');
+        match self.digits() {
+            0 => return self.error("expected digit"),
+            n if n > 1 && leading_zero => return self.error("invalid number"),
+            _ => {}
+        }
+
+        let mut float = false;
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            float = true;
+            if self.digits() == 0 {
+                return self.error("expected digit");
+            }
+        }
+
+        if matches!(self.bytes.get(self.pos), Some(b'e' | b'E')) {
+            self.pos += 1;
+            float = true;
+            if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                return self.error("expected digit");
+            }
+        }
+
+        let s = &self.src[start..self.pos];
+        if !float {
+            if let Ok(n) = s.parse() {
+                return Ok(Json::Int(n));
+            }
+        }
+
+        match s.parse::<f64>() {
+            Ok(f) if f.is_finite() => Ok(Json::Float(f)),
+            _ => self.error("number out of range"),
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let code = self
+            .src
+            .get(self.pos..self.pos + 4)
+            .and_then(|s| u32::from_str_radix(s, 16).ok());
+
+        match code {
+            Some(code) => {
+                self.pos += 4;
+                Ok(code)
+            }
+            None => self.error("invalid unicode escape"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut s = String::new();
+
+        loop {
+            let start = self.pos;
+            while self
+                .bytes
+                .get(self.pos)
+                .is_some_and(|b| !matches!(b, b'"' | b'\\' | 0..=0x1F))
+            {
+                self.pos += 1;
+            }
+            s.push_str(&self.src[start..self.pos]);
+
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.bytes.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            s.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        _ => return self.error("invalid escape"),
+                    };
+                    self.pos += 1;
+                    s.push(escaped);
+                }
+                Some(_) => return self.error("control character in string"),
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    /// Decodes the digits of a `\u` escape, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).map_or_else(|| self.error("invalid unicode escape"), Ok);
+        }
+
+        if !self.src[self.pos..].starts_with("\\u") {
+            return self.error("unpaired surrogate");
+        }
+        self.pos += 2;
+
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return self.error("unpaired surrogate");
+        }
+
+        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        char::from_u32(code).map_or_else(|| self.error("invalid unicode escape"), Ok)
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return self.error("expected ',' or ']'"),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut members: Vec<(String, Json)> = Vec::new();
+
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.bytes.get(self.pos) != Some(&b'"') {
+                return self.error("expected key");
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            let value = self.value()?;
+
+            // A repeated key keeps the last value.
+            match members.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => *v = value,
+                None => members.push((key, value)),
+            }
+
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return self.error("expected ',' or '}'"),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, i64),
+}
+
+/// The location of a value in a document: object keys and array indexes from the root.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Step {
+    Key(String),
+    Index(usize),
+}
+
+pub type Location = Vec<Step>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    segments: Vec<Segment>,
+    legacy: bool,
+}
+
+impl Path {
+    pub fn root() -> Self {
+        Path {
+            segments: Vec::new(),
+            legacy: true,
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Path, String> {
+        let (legacy, rest) = match s.strip_prefix('$') {
+            Some(rest) => (false, rest.to_owned()),
+            None if s == "." => (true, String::new()),
+            None if s.starts_with(['.', '[']) => (true, s.to_owned()),
+            None => (true, format!(".{s}")),
+        };
+
+        let error = || format!("invalid JSON path '{s}'");
+        let mut segments = Vec::new();
+        let mut chars = rest.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let segment = match c {
+                '.' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    Segment::Wildcard
+                }
+                '.' => {
+                    let mut key = String::new();
+                    while let Some(c) = chars.next_if(|c| !matches!(c, '.' | '[')) {
+                        key.push(c);
+                    }
+                    if key.is_empty() {
+                        return Err(error());
+                    }
+                    Segment::Key(key)
+                }
+                '[' => {
+                    let mut inner = String::new();
+                    let mut quote = None;
+                    let mut quoted = false;
+                    loop {
+                        match (chars.next().ok_or_else(error)?, quote) {
+                            ('\\', Some(_)) => inner.push(chars.next().ok_or_else(error)?),
+                            (c, Some(q)) if c == q => quote = None,
+                            (c @ ('\'' | '"'), None) if !quoted && inner.is_empty() => {
+                                quote = Some(c);
+                                quoted = true;
+                            }
+                            (']', None) => break,
+                            (c, _) => inner.push(c),
+                        }
+                    }
+
+                    match quoted {
+                        true => Segment::Key(inner),
+                        false => parse_bracket(inner.trim()).ok_or_else(error)?,
+                    }
+                }
+                _ => return Err(error()),
+            };
+            segments.push(segment);
+        }
+
+        Ok(Path { segments, legacy })
+    }
+
+    /// Whether the path uses the legacy syntax, and so refers to a single value.
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Splits the path into the path of the parent and the key of the last segment, if it is
+    /// a key, so that missing members can be created.
+    pub fn parent(&self) -> Option<(Path, &str)> {
+        match self.segments.last()? {
+            Segment::Key(key) => {
+                let parent = Path {
+                    segments: self.segments[..self.segments.len() - 1].to_vec(),
+                    legacy: self.legacy,
+                };
+                Some((parent, key))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the locations of all the values the path matches, in document order.
+    pub fn select(&self, root: &Json) -> Vec<Location> {
+        let mut matches = vec![(Vec::new(), root)];
+
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for (location, value) in matches {
+                let child = |step: Step| {
+                    let mut location = location.clone();
+                    location.push(step);
+                    location
+                };
+
+                match (segment, value) {
+                    (Segment::Key(key), Json::Object(_)) => {
+                        if let Some(v) = value.get(key) {
+                            next.push((child(Step::Key(key.clone())), v));
+                        }
+                    }
+                    (Segment::Wildcard, Json::Object(members)) => {
+                        for (k, v) in members {
+                            next.push((child(Step::Key(k.clone())), v));
+                        }
+                    }
+                    (Segment::Wildcard, Json::Array(items)) => {
+                        for (i, v) in items.iter().enumerate() {
+                            next.push((child(Step::Index(i)), v));
+                        }
+                    }
+                    (Segment::Index(index), Json::Array(items)) => {
+                        let len = items.len() as i64;
+                        let i = if *index < 0 { index + len } else { *index };
+                        if (0..len).contains(&i) {
+                            next.push((child(Step::Index(i as usize)), &items[i as usize]));
+                        }
+                    }
+                    (Segment::Slice(start, end, step), Json::Array(items)) => {
+                        let len = items.len() as i64;
+                        let bound = |i: i64| if i < 0 { (i + len).max(0) } else { i.min(len) };
+                        let start = start.map_or(0, bound);
+                        let end = end.map_or(len, bound);
+                        for i in (start..end).step_by(*step as usize) {
+                            next.push((child(Step::Index(i as usize)), &items[i as usize]));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            matches = next;
+        }
+
+        matches.into_iter().map(|(location, _)| location).collect()
+    }
+}
+
+fn parse_bracket(inner: &str) -> Option<Segment> {
+    if inner == "*" {
+        return Some(Segment::Wildcard);
+    }
+
+    if !inner.contains(':') {
+        return inner.parse().ok().map(Segment::Index);
+    }
+
+    let parts: Vec<&str> = inner.split(':').map(str::trim).collect();
+    let bound = |s: &str| match s {
+        "" => Some(None),
+        s => s.parse().ok().map(Some),
+    };
+
+    let (start, end, step) = match parts.as_slice() {
+        [start, end] => (bound(start)?, bound(end)?, 1),
+        [start, end, step] => (bound(start)?, bound(end)?, bound(step)?.unwrap_or(1)),
+        _ => return None,
+    };
+
+    // Only forward slices are supported.
+    (step > 0).then_some(Segment::Slice(start, end, step))
+}
+
+pub fn get<'a>(root: &'a Json, location: &[Step]) -> &'a Json {
+    location
+        .iter()
+        .fold(root, |value, step| match (step, value) {
+            (Step::Key(key), _) => value.get(key).expect("location points to a member"),
+            (Step::Index(i), Json::Array(items)) => &items[*i],
+            _ => unreachable!("location points to an array item"),
+        })
+}
+
+pub fn get_mut<'a>(root: &'a mut Json, location: &[Step]) -> &'a mut Json {
+    location.iter().fold(root, |value, step| match step {
+        Step::Key(key) => value.get_mut(key).expect("location points to a member"),
+        Step::Index(i) => match value {
+            Json::Array(items) => &mut items[*i],
+            _ => unreachable!("location points to an array item"),
+        },
+    })
+}
+
+/// Removes the values at `locations`, which must not include the root. Later locations are
+/// removed first so that array indexes stay valid.
+pub fn delete(root: &mut Json, mut locations: Vec<Location>) -> usize {
+    locations.sort();
+    locations.dedup();
+
+    for location in locations.iter().rev() {
+        let (last, parent) = location.split_last().expect("the root can't be deleted");
+        match (get_mut(root, parent), last) {
+            (Json::Object(members), Step::Key(key)) => members.retain(|(k, _)| k != key),
+            (Json::Array(items), Step::Index(i)) => {
+                items.remove(*i);
+            }
+            _ => unreachable!("location matches its parent"),
+        }
+    }
+
+    locations.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{delete, get, get_mut, Format, Json, Path, Step};
+
+    #[test]
+    fn test_parse_and_serialize() {
+        let doc =
+            r#" {"a": [1, -2.5, 1e3, true, null], "b": {"c": "x\"\u00e9\ud83d\ude00"}, "a": 7} "#;
+        let json = Json::parse(doc).unwrap();
+
+        assert_eq!(Some(&Json::Int(7)), json.get("a"));
+        assert_eq!(r#"{"a":7,"b":{"c":"x\"é😀"}}"#, json.to_compact());
+
+        let json = Json::parse("[1.0,{\"k\":[]}]").unwrap();
+        let format = Format {
+            indent: "  ",
+            newline: "\n",
+            space: " ",
+        };
+        assert_eq!(
+            "[\n  1.0,\n  {\n    \"k\": []\n  }\n]",
+            json.to_formatted(&format)
+        );
+
+        for invalid in ["", "[1,]", "{\"a\" 1}", "01", "\"\\x\"", "[1] x", "nul"] {
+            assert!(Json::parse(invalid).is_err(), "{invalid} should not parse");
+        }
+        assert!(Json::parse(&"[".repeat(200)).is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let json = Json::parse(r#"{"a":{"b":[10,20,30,40]},"c":{"b":5},"d e":1}"#).unwrap();
+        let values = |path: &str| -> Vec<String> {
+            let path = Path::parse(path).unwrap();
+            path.select(&json)
+                .iter()
+                .map(|l| get(&json, l).to_compact())
+                .collect()
+        };
+
+        assert_eq!(vec![json.to_compact()], values("$"));
+        assert_eq!(vec!["[10,20,30,40]"], values("$.a.b"));
+        assert_eq!(vec!["[10,20,30,40]", "5"], values("$.*.b"));
+        assert_eq!(vec!["40"], values("$.a.b[-1]"));
+        assert_eq!(vec!["20", "40"], values("$.a.b[1::2]"));
+        assert_eq!(vec!["10", "20"], values("$['a'][\"b\"][:2]"));
+        assert_eq!(vec!["1"], values("$['d e']"));
+        assert_eq!(vec!["5"], values(".c.b"));
+        assert_eq!(vec!["30"], values("a.b[2]"));
+        assert!(values("$.x").is_empty());
+
+        assert!(Path::parse(".").unwrap().is_root());
+        assert!(Path::parse("$.").is_err());
+        assert!(Path::parse("$[1").is_err());
+        assert!(Path::parse("$[::-1]").is_err());
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let mut json = Json::parse(r#"{"a":[1,2,3],"b":{"c":1}}"#).unwrap();
+
+        *get_mut(&mut json, &[Step::Key(String::from("b"))]) = Json::Bool(false);
+        let path = Path::parse("$.a[0:2]").unwrap();
+        let locations = path.select(&json);
+        assert_eq!(2, delete(&mut json, locations));
+
+        assert_eq!(r#"{"a":[3],"b":false}"#, json.to_compact());
+    }
+}
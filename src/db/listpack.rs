@@ -0,0 +1,375 @@
+use std::{borrow::Cow, fmt};
+
+/// Total bytes (u32) and number of elements (u16), both little endian.
+const HEADER_SIZE: usize = 6;
+const EOF: u8 = 0xFF;
+/// Stored in the header once the element count no longer fits and must be computed.
+const UNKNOWN_LEN: u16 = u16::MAX;
+
+/// An element of a listpack. Strings that are canonical integers are stored as integers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry<'a> {
+    Int(i64),
+    Str(&'a str),
+}
+
+impl Entry<'_> {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Entry::Int(i) => Some(*i),
+            Entry::Str(s) => s.parse().ok().filter(|i: &i64| i.to_string() == *s),
+        }
+    }
+}
+
+impl<'a> From<Entry<'a>> for Cow<'a, str> {
+    fn from(entry: Entry<'a>) -> Self {
+        match entry {
+            Entry::Int(i) => Cow::Owned(i.to_string()),
+            Entry::Str(s) => Cow::Borrowed(s),
+        }
+    }
+}
+
+impl fmt::Display for Entry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Int(i) => write!(f, "{i}"),
+            Entry::Str(s) => f.write_str(s),
+        }
+    }
+}
+
+/// A sequence of strings and integers packed into a single allocation, in the same layout as
+/// Redis listpacks. Every element is followed by its own length so the list can be walked in
+/// both directions. Elements are addressed by their byte offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listpack {
+    buf: Vec<u8>,
+}
+
+impl Default for Listpack {
+    fn default() -> Self {
+        Listpack::new()
+    }
+}
+
+fn encode(entry: Entry) -> Vec<u8> {
+    let mut buf = Vec::new();
+
+    match entry.as_int() {
+        Some(i @ 0..=127) => buf.push(i as u8),
+        Some(i @ -4096..=4095) => {
+            let v = (i as u16) & 0x1FFF;
+            buf.extend([0xC0 | (v >> 8) as u8, v as u8]);
+        }
+        Some(i) if i16::try_from(i).is_ok() => {
+            buf.push(0xF1);
+            buf.extend(&(i as i16).to_le_bytes());
+        }
+        Some(i) if (-(1 << 23)..(1 << 23)).contains(&i) => {
+            buf.push(0xF2);
+            buf.extend(&(i as i32).to_le_bytes()[..3]);
+        }
+        Some(i) if i32::try_from(i).is_ok() => {
+            buf.push(0xF3);
+            buf.extend(&(i as i32).to_le_bytes());
+        }
+        Some(i) => {
+            buf.push(0xF4);
+            buf.extend(&i.to_le_bytes());
+        }
+        None => {
+            let s = match entry {
+                Entry::Str(s) => s.as_bytes(),
+                Entry::Int(_) => unreachable!("integers are always encoded as such"),
+            };
+
+            match s.len() {
+                len @ 0..=63 => buf.push(0x80 | len as u8),
+                len @ 64..=4095 => buf.extend([0xE0 | (len >> 8) as u8, len as u8]),
+                len => {
+                    buf.push(0xF0);
+                    buf.extend(&(len as u32).to_le_bytes());
+                }
+            }
+            buf.extend(s);
+        }
+    }
+
+    // The back length, readable from right to left: the rightmost byte holds the lowest
+    // seven bits and every byte but the leftmost one has its high bit set.
+    let len = buf.len();
+    let mut backlen = vec![(len & 127) as u8];
+    let mut rest = len >> 7;
+    while rest > 0 {
+        backlen[0] |= 128;
+        backlen.insert(0, (rest & 127) as u8);
+        rest >>= 7;
+    }
+    buf.extend(backlen);
+
+    buf
+}
+
+impl Listpack {
+    pub fn new() -> Self {
+        let mut lp = Listpack {
+            buf: vec![0; HEADER_SIZE],
+        };
+        lp.buf.push(EOF);
+        lp.set_header(0);
+        lp
+    }
+
+    /// The encoded listpack.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Size of the encoded listpack in bytes.
+    pub fn bytes(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn len(&self) -> usize {
+        match u16::from_le_bytes([self.buf[4], self.buf[5]]) {
+            UNKNOWN_LEN => std::iter::successors(self.first(), |&off| self.next(off)).count(),
+            len => len as usize,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.first().is_none()
+    }
+
+    fn set_header(&mut self, len: usize) {
+        let total = self.buf.len() as u32;
+        let len = u16::try_from(len)
+            .ok()
+            .filter(|len| *len != UNKNOWN_LEN)
+            .unwrap_or(UNKNOWN_LEN);
+
+        self.buf[..4].copy_from_slice(&total.to_le_bytes());
+        self.buf[4..6].copy_from_slice(&len.to_le_bytes());
+    }
+
+    /// Size of the encoding and data of the element at `off`, without its back length.
+    fn data_len(&self, off: usize) -> (usize, Entry<'_>) {
+        let b = &self.buf[off..];
+        let int = |n: usize| {
+            let mut bytes = [0; 8];
+            bytes[..n].copy_from_slice(&b[1..=n]);
+            // Sign extend from `n` bytes.
+            let shift = 64 - 8 * n as u32;
+            (
+                n + 1,
+                Entry::Int(i64::from_le_bytes(bytes) << shift >> shift),
+            )
+        };
+        let str = |start: usize, len: usize| {
+            let s = std::str::from_utf8(&b[start..start + len]).expect("listpack holds utf-8");
+            (start + len, Entry::Str(s))
+        };
+
+        match b[0] {
+            v if v & 0x80 == 0 => (1, Entry::Int(v as i64)),
+            v if v & 0xC0 == 0x80 => str(1, (v & 0x3F) as usize),
+            v if v & 0xE0 == 0xC0 => {
+                let v = (((v & 0x1F) as i64) << 8) | b[1] as i64;
+                (2, Entry::Int(if v >= 4096 { v - 8192 } else { v }))
+            }
+            v if v & 0xF0 == 0xE0 => str(2, (((v & 0x0F) as usize) << 8) | b[1] as usize),
+            0xF0 => str(5, u32::from_le_bytes([b[1], b[2], b[3], b[4]]) as usize),
+            0xF1 => int(2),
+            0xF2 => int(3),
+            0xF3 => int(4),
+            0xF4 => int(8),
+            v => unreachable!("invalid listpack encoding {v:#x}"),
+        }
+    }
+
+    fn entry_len(&self, off: usize) -> usize {
+        let (len, _) = self.data_len(off);
+        let backlen = match len {
+            0..=127 => 1,
+            128..=16383 => 2,
+            16384..=2097151 => 3,
+            2097152..=268435455 => 4,
+            _ => 5,
+        };
+        len + backlen
+    }
+
+    pub fn first(&self) -> Option<usize> {
+        (self.buf[HEADER_SIZE] != EOF).then_some(HEADER_SIZE)
+    }
+
+    pub fn last(&self) -> Option<usize> {
+        self.prev(self.buf.len() - 1)
+    }
+
+    pub fn next(&self, off: usize) -> Option<usize> {
+        let next = off + self.entry_len(off);
+        (self.buf[next] != EOF).then_some(next)
+    }
+
+    pub fn prev(&self, off: usize) -> Option<usize> {
+        if off <= HEADER_SIZE {
+            return None;
+        }
+
+        let mut p = off - 1;
+        let mut len = 0;
+        let mut shift = 0;
+        loop {
+            len |= ((self.buf[p] & 127) as usize) << shift;
+            if self.buf[p] & 128 == 0 {
+                break;
+            }
+            shift += 7;
+            p -= 1;
+        }
+
+        Some(p - len)
+    }
+
+    pub fn get(&self, off: usize) -> Entry<'_> {
+        self.data_len(off).1
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entry<'_>> {
+        std::iter::successors(self.first(), |&off| self.next(off)).map(|off| self.get(off))
+    }
+
+    /// Returns the offset of the first element equal to `entry`, comparing every `skip + 1`
+    /// elements from the first, such as only the fields of a listpack of field-value pairs.
+    pub fn find(&self, entry: Entry, skip: usize) -> Option<usize> {
+        let entry = entry.as_int().map_or(entry, Entry::Int);
+        let mut off = self.first();
+
+        while let Some(o) = off {
+            if self.get(o) == entry {
+                return Some(o);
+            }
+            off = (0..=skip).try_fold(o, |o, _| self.next(o));
+        }
+
+        None
+    }
+
+    pub fn push(&mut self, entry: Entry) {
+        let len = self.len();
+        let end = self.buf.len() - 1;
+        self.buf.splice(end..end, encode(entry));
+        self.set_header(len + 1);
+    }
+
+    /// Inserts `entry` before the element at `off`.
+    pub fn insert(&mut self, off: usize, entry: Entry) {
+        let len = self.len();
+        self.buf.splice(off..off, encode(entry));
+        self.set_header(len + 1);
+    }
+
+    /// Replaces the element at `off`. Elements after it move if the encoded sizes differ.
+    pub fn replace(&mut self, off: usize, entry: Entry) {
+        let len = self.len();
+        let end = off + self.entry_len(off);
+        self.buf.splice(off..end, encode(entry));
+        self.set_header(len);
+    }
+
+    /// Removes the element at `off`, so that `off` now points to the element that followed.
+    pub fn remove(&mut self, off: usize) {
+        let len = self.len();
+        let end = off + self.entry_len(off);
+        self.buf.drain(off..end);
+        self.set_header(len - 1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Entry, Listpack};
+
+    #[test]
+    fn test_round_trip_encodings() {
+        let long = "x".repeat(5000);
+        let entries = [
+            Entry::Int(7),
+            Entry::Int(-100),
+            Entry::Int(30000),
+            Entry::Int(-5_000_000),
+            Entry::Int(1 << 30),
+            Entry::Int(i64::MIN),
+            Entry::Str("hello"),
+            Entry::Str(&long[..100]),
+            Entry::Str(&long),
+        ];
+
+        let mut lp = Listpack::new();
+        for entry in entries {
+            lp.push(entry);
+        }
+
+        assert_eq!(entries.len(), lp.len());
+        assert_eq!(entries.to_vec(), lp.iter().collect::<Vec<_>>());
+
+        // Walking backwards visits the same elements.
+        let mut backwards = Vec::new();
+        let mut off = lp.last();
+        while let Some(o) = off {
+            backwards.push(lp.get(o));
+            off = lp.prev(o);
+        }
+        backwards.reverse();
+        assert_eq!(entries.to_vec(), backwards);
+    }
+
+    #[test]
+    fn test_strings_are_stored_as_integers() {
+        let mut lp = Listpack::new();
+        lp.push(Entry::Str("42"));
+        lp.push(Entry::Str("042"));
+
+        assert_eq!(
+            vec![Entry::Int(42), Entry::Str("042")],
+            lp.iter().collect::<Vec<_>>()
+        );
+        // Header, two elements and the terminator.
+        assert_eq!(6 + 2 + 5 + 1, lp.bytes());
+    }
+
+    #[test]
+    fn test_replace_and_remove() {
+        let mut lp = Listpack::new();
+        for s in ["a", "b", "c"] {
+            lp.push(Entry::Str(s));
+        }
+
+        let second = lp.next(lp.first().unwrap()).unwrap();
+        lp.replace(second, Entry::Str("bigger"));
+        lp.insert(second, Entry::Int(1));
+        lp.remove(lp.first().unwrap());
+
+        assert_eq!(
+            vec![Entry::Int(1), Entry::Str("bigger"), Entry::Str("c")],
+            lp.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(3, lp.len());
+    }
+
+    #[test]
+    fn test_find_with_skip() {
+        let mut lp = Listpack::new();
+        for s in ["a", "b", "b", "7"] {
+            lp.push(Entry::Str(s));
+        }
+
+        let third = lp.next(lp.next(lp.first().unwrap()).unwrap());
+        assert_eq!(third, lp.find(Entry::Str("b"), 1));
+        assert_eq!(lp.last(), lp.find(Entry::Str("7"), 0));
+        assert_eq!(None, lp.find(Entry::Str("7"), 1));
+    }
+}
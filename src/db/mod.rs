@@ -0,0 +1,853 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ops::Bound,
+    time::SystemTime,
+};
+
+use crate::{error::RedisError, frame::Frame, module::ModuleValue, util::time::is_expired};
+
+pub mod bitmap;
+pub mod bloom;
+pub mod countmin;
+pub mod cuckoo;
+pub mod dict;
+pub mod encoding;
+pub mod expr;
+pub mod geo;
+pub mod hash;
+pub mod hyperloglog;
+pub mod json;
+pub mod listpack;
+pub mod notify;
+pub mod rax;
+pub mod search;
+pub mod set;
+pub mod skiplist;
+pub mod stream;
+pub mod string;
+pub mod timeseries;
+pub mod vectorset;
+pub mod zset;
+
+use bloom::Bloom;
+use countmin::CountMin;
+use cuckoo::Cuckoo;
+use encoding::Thresholds;
+use hash::Hash;
+use json::Json;
+use notify::Notification;
+use search::Index;
+use set::Set;
+use stream::Stream;
+use string::Str;
+use timeseries::TimeSeries;
+use vectorset::VectorSet;
+use zset::SortedSet;
+
+pub trait Database {
+    /// Reads the string at `key`, reporting a miss like `read_value`.
+    fn get(&mut self, key: &str) -> Result<Option<Vec<u8>>, RedisError>;
+    fn set(&mut self, key: &str, value: &[u8], exp: Option<SystemTime>);
+
+    /// When the value at `key` expires, if it is live and has an expiration.
+    fn expire_time(&self, key: &str) -> Option<SystemTime>;
+
+    /// Returns the live value stored at `key`, lazily removing it if it has expired.
+    fn get_value(&mut self, key: &str) -> Option<&mut Value>;
+    /// Like `get_value`, for commands that only read `key`, where a missing key is reported
+    /// as a `keymiss` event.
+    fn read_value(&mut self, key: &str) -> Option<&mut Value>;
+    fn get_or_insert_with(&mut self, key: &str, default: &dyn Fn() -> Data) -> &mut Value;
+    fn insert(&mut self, key: &str, value: Value);
+    fn remove(&mut self, key: &str) -> Option<Value>;
+    /// Every live key with its value, in no particular order.
+    fn iter(&self) -> Box<dyn Iterator<Item = (&str, &Value)> + '_>;
+
+    /// Marks `key` as modified by a write command.
+    fn touch(&mut self, key: &str);
+    /// Number of modifications since startup, used to decide whether a command wrote anything.
+    fn dirty(&self) -> u64;
+
+    /// Registers `key` as a hash with expiring fields so the active expiry cycle visits it.
+    fn track_field_expiry(&mut self, key: &str);
+    /// Deletes a sample of the expired keys and hash fields that nobody has accessed.
+    fn active_expire(&mut self);
+
+    /// Queues a frame for replicas that is sent ahead of the command being executed, such as
+    /// the deletion of an expired field.
+    fn propagate(&mut self, frame: Frame);
+    /// Replaces what is sent to replicas for the command being executed. Calling it several
+    /// times sends several frames.
+    fn rewrite(&mut self, frame: Frame);
+    fn drain_propagated(&mut self) -> Vec<Frame>;
+    fn drain_rewritten(&mut self) -> Option<Vec<Frame>>;
+
+    /// Records a keyspace event on `key`, published once the command is done if its class
+    /// is enabled with `notify-keyspace-events`.
+    fn notify(&mut self, class: u32, event: &'static str, key: &str);
+    fn drain_notifications(&mut self) -> Vec<Notification>;
+
+    /// Records that a client is blocked waiting on `key`, so writes to it mark it as ready.
+    fn block(&mut self, key: &str);
+    fn unblock(&mut self, key: &str);
+    /// Returns the keys with blocked clients that were written since the last call, in the
+    /// order they were written.
+    fn drain_ready(&mut self) -> Vec<String>;
+
+    /// Starts tracking changes to `key` for a client watching it, returning its version.
+    fn watch(&mut self, key: &str) -> u64;
+    fn unwatch(&mut self, key: &str);
+    /// Version of a watched `key`, which changes whenever the key is written, deleted or
+    /// expires.
+    fn version(&mut self, key: &str) -> u64;
+
+    /// Deletes every key.
+    fn flush(&mut self);
+
+    /// Adds a search index and indexes the existing hashes it covers. Returns `false` if an
+    /// index with the same name exists.
+    fn create_index(&mut self, name: &str, index: Index) -> bool;
+    fn drop_index(&mut self, name: &str) -> bool;
+    fn index(&self, name: &str) -> Option<&Index>;
+
+    /// Sizes past which hashes and sets leave their compact encoding, set with CONFIG SET.
+    fn thresholds(&self) -> Thresholds;
+    fn thresholds_mut(&mut self) -> &mut Thresholds;
+    /// The classes of keyspace events to publish, set with `notify-keyspace-events`.
+    fn notify_flags(&self) -> u32;
+    fn set_notify_flags(&mut self, flags: u32);
+}
+
+/// Upper bound on the keys, and on the hashes, visited by one run of the active expiry cycle.
+const ACTIVE_EXPIRE_KEYS_PER_CYCLE: usize = 20;
+
+/// Every type other than strings is boxed, so that each key only takes the size of a string
+/// in the keyspace.
+#[derive(Debug)]
+pub enum Data {
+    String(Str),
+    Hash(Box<Hash>),
+    Set(Box<Set>),
+    SortedSet(Box<SortedSet>),
+    Stream(Box<Stream>),
+    Json(Box<Json>),
+    Bloom(Box<Bloom>),
+    Cuckoo(Box<Cuckoo>),
+    CountMin(Box<CountMin>),
+    TimeSeries(Box<TimeSeries>),
+    VectorSet(Box<VectorSet>),
+    /// A value of a data type registered by a module.
+    Module(Box<dyn ModuleValue>),
+}
+
+#[derive(Debug)]
+pub struct Value {
+    data: Data,
+}
+
+impl Value {
+    pub fn new(value: &[u8]) -> Self {
+        Value::from(Data::String(Str::new(value)))
+    }
+
+    pub fn data(&self) -> &Data {
+        &self.data
+    }
+
+    /// How the value is stored, as reported by OBJECT ENCODING. Module types are opaque to
+    /// Redis, which reports them as raw.
+    pub fn encoding(&self) -> &'static str {
+        match &self.data {
+            Data::String(s) => s.encoding(),
+            Data::Hash(hash) => hash.encoding(),
+            Data::Set(set) => set.encoding(),
+            Data::SortedSet(_) => "skiplist",
+            Data::Stream(_) => "stream",
+            Data::Json(_)
+            | Data::Bloom(_)
+            | Data::Cuckoo(_)
+            | Data::CountMin(_)
+            | Data::TimeSeries(_)
+            | Data::VectorSet(_)
+            | Data::Module(_) => "raw",
+        }
+    }
+
+    /// Estimate of the memory taken by the value, including what it allocates. Strings that
+    /// are integers or short enough to be embedded take no more than the value itself.
+    pub fn memory_usage(&self) -> usize {
+        let heap = match &self.data {
+            Data::String(s) => s.heap_size(),
+            Data::Hash(hash) => size_of::<Hash>() + hash.heap_size(),
+            Data::Set(set) => size_of::<Set>() + set.heap_size(),
+            Data::SortedSet(zset) => size_of::<SortedSet>() + zset.heap_size(),
+            Data::Stream(stream) => {
+                let blocks: usize = stream.blocks().map(|(_, lp)| lp.bytes()).sum();
+                size_of::<Stream>() + blocks
+            }
+            // Documents are estimated from their serialized size.
+            Data::Json(json) => size_of::<Json>() + json.to_compact().len(),
+            Data::Bloom(bloom) => size_of::<Bloom>() + bloom.size(),
+            Data::Cuckoo(cuckoo) => {
+                let data: usize = cuckoo.filters().iter().map(|f| f.data().len()).sum();
+                size_of::<Cuckoo>() + data
+            }
+            Data::CountMin(cms) => size_of::<CountMin>() + size_of_val(cms.counters()),
+            Data::TimeSeries(series) => {
+                let chunks: usize = series.chunks().iter().map(|c| c.size()).sum();
+                size_of::<TimeSeries>() + chunks
+            }
+            Data::VectorSet(set) => size_of::<VectorSet>() + set.heap_size(),
+            Data::Module(value) => size_of_val(value.as_ref()) + value.heap_size(),
+        };
+
+        size_of::<Value>() + heap
+    }
+
+    /// Whether the value is a collection without any element left. Such keys are deleted, as
+    /// Redis never stores empty collections.
+    pub fn is_empty(&self) -> bool {
+        match &self.data {
+            Data::String(_) => false,
+            Data::Hash(hash) => hash.is_empty(),
+            Data::Set(set) => set.is_empty(),
+            Data::SortedSet(zset) => zset.is_empty(),
+            // Streams keep their last ID and groups, so they outlive their entries.
+            Data::Stream(_) => false,
+            Data::Json(_) => false,
+            // Filters and sketches keep their dimensions once created.
+            Data::Bloom(_) | Data::Cuckoo(_) | Data::CountMin(_) => false,
+            // Series keep their rules and labels without samples.
+            Data::TimeSeries(_) => false,
+            Data::VectorSet(set) => set.is_empty(),
+            // Modules delete their keys themselves.
+            Data::Module(_) => false,
+        }
+    }
+
+    /// The bytes of a string, which switches to the raw encoding to be changed in place.
+    pub fn as_string_mut(&mut self) -> Result<&mut Vec<u8>, RedisError> {
+        match &mut self.data {
+            Data::String(s) => Ok(s.make_raw()),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_hash_mut(&mut self) -> Result<&mut Hash, RedisError> {
+        match &mut self.data {
+            Data::Hash(hash) => Ok(hash),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_set_mut(&mut self) -> Result<&mut Set, RedisError> {
+        match &mut self.data {
+            Data::Set(set) => Ok(set),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_sorted_set_mut(&mut self) -> Result<&mut SortedSet, RedisError> {
+        match &mut self.data {
+            Data::SortedSet(zset) => Ok(zset),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_json_mut(&mut self) -> Result<&mut Json, RedisError> {
+        match &mut self.data {
+            Data::Json(json) => Ok(json),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_stream_mut(&mut self) -> Result<&mut Stream, RedisError> {
+        match &mut self.data {
+            Data::Stream(stream) => Ok(stream),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_bloom_mut(&mut self) -> Result<&mut Bloom, RedisError> {
+        match &mut self.data {
+            Data::Bloom(bloom) => Ok(bloom),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_cuckoo_mut(&mut self) -> Result<&mut Cuckoo, RedisError> {
+        match &mut self.data {
+            Data::Cuckoo(cuckoo) => Ok(cuckoo),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_count_min_mut(&mut self) -> Result<&mut CountMin, RedisError> {
+        match &mut self.data {
+            Data::CountMin(cms) => Ok(cms),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_timeseries_mut(&mut self) -> Result<&mut TimeSeries, RedisError> {
+        match &mut self.data {
+            Data::TimeSeries(series) => Ok(series),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn as_vector_set_mut(&mut self) -> Result<&mut VectorSet, RedisError> {
+        match &mut self.data {
+            Data::VectorSet(set) => Ok(set),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    /// The value as the module type `T`, failing for values of any other type.
+    pub fn as_module_mut<T: ModuleValue>(&mut self) -> Result<&mut T, RedisError> {
+        match &mut self.data {
+            Data::Module(value) => {
+                let value: &mut dyn Any = value.as_mut();
+                value.downcast_mut().ok_or(RedisError::WrongType)
+            }
+            _ => Err(RedisError::WrongType),
+        }
+    }
+}
+
+impl From<Data> for Value {
+    fn from(data: Data) -> Self {
+        Value { data }
+    }
+}
+
+#[derive(Debug)]
+pub struct KeyValueDb {
+    data: HashMap<String, Value>,
+    /// Expiration times of the keys that have one, kept apart so that persistent keys don't
+    /// pay for them. They are ordered so the active expiry cycle can resume where it stopped.
+    expires: BTreeMap<String, SystemTime>,
+    key_cursor: Option<String>,
+    /// Hashes that have at least one field with an expiration, kept ordered for the same
+    /// reason.
+    volatile_hashes: BTreeSet<String>,
+    hash_cursor: Option<String>,
+    dirty: u64,
+    propagated: Vec<Frame>,
+    rewritten: Option<Vec<Frame>>,
+    notifications: Vec<Notification>,
+    /// Number of clients blocked on each key.
+    blocked: HashMap<String, usize>,
+    ready: Vec<String>,
+    /// Number of clients watching each key, along with the version of the key. Versions are
+    /// only kept for watched keys.
+    watched: HashMap<String, (usize, u64)>,
+    /// Search indexes by name, kept up to date whenever a key is written or removed.
+    indexes: HashMap<String, Index>,
+    thresholds: Thresholds,
+    notify_flags: u32,
+}
+
+impl KeyValueDb {
+    pub fn new() -> Self {
+        KeyValueDb {
+            data: HashMap::new(),
+            expires: BTreeMap::new(),
+            key_cursor: None,
+            volatile_hashes: BTreeSet::new(),
+            hash_cursor: None,
+            dirty: 0,
+            propagated: Vec::new(),
+            rewritten: None,
+            notifications: Vec::new(),
+            blocked: HashMap::new(),
+            ready: Vec::new(),
+            watched: HashMap::new(),
+            indexes: HashMap::new(),
+            thresholds: Thresholds::default(),
+            notify_flags: 0,
+        }
+    }
+
+    fn is_expired(&self, key: &str) -> bool {
+        self.expires.get(key).is_some_and(|at| is_expired(*at))
+    }
+
+    /// Deletes `key` if it has expired, and the expired fields of the hash stored there.
+    /// Returns whether the key was deleted. Deletions are propagated as `DEL`, as replicas
+    /// don't expire keys on their own.
+    fn expire_if_needed(&mut self, key: &str) -> bool {
+        if self.is_expired(key) {
+            self.data.remove(key);
+            self.expires.remove(key);
+            self.reindex(key);
+            self.signal_modified(key);
+            self.notify(notify::EXPIRED, "expired", key);
+            self.propagate(Frame::Arrays(vec![String::from("DEL"), key.to_owned()]));
+            return true;
+        }
+
+        if self.volatile_hashes.contains(key) {
+            self.expire_fields(key);
+        }
+
+        false
+    }
+
+    /// Invalidates the clients watching `key`.
+    fn signal_modified(&mut self, key: &str) {
+        if let Some((_, version)) = self.watched.get_mut(key) {
+            *version += 1;
+        }
+    }
+
+    /// Updates the search indexes with the value now stored at `key`.
+    fn reindex(&mut self, key: &str) {
+        if self.indexes.is_empty() {
+            return;
+        }
+
+        let hash = match self.data.get(key) {
+            Some(Value {
+                data: Data::Hash(hash),
+            }) if !self.is_expired(key) => Some(hash.as_ref()),
+            _ => None,
+        };
+        for index in self.indexes.values_mut() {
+            index.update(key, hash);
+        }
+    }
+
+    /// Deletes the expired fields of the hash at `key`, and the key itself once no field is
+    /// left. Deletions are propagated as `HDEL` so replicas stay in sync with the master clock.
+    fn expire_fields(&mut self, key: &str) {
+        let Some(Value {
+            data: Data::Hash(hash),
+        }) = self.data.get_mut(key)
+        else {
+            self.volatile_hashes.remove(key);
+            return;
+        };
+
+        let expired = hash.remove_expired(SystemTime::now());
+        let is_empty = hash.is_empty();
+
+        if !hash.has_volatile_fields() {
+            self.volatile_hashes.remove(key);
+        }
+
+        if !expired.is_empty() {
+            self.notify(notify::HASH, "hexpired", key);
+            if is_empty {
+                self.data.remove(key);
+                self.expires.remove(key);
+                self.notify(notify::GENERIC, "del", key);
+            }
+            self.reindex(key);
+            self.signal_modified(key);
+
+            let frame = [vec![String::from("HDEL"), key.to_owned()], expired].concat();
+            self.propagate(Frame::Arrays(frame));
+        }
+    }
+}
+
+impl Default for KeyValueDb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Database for KeyValueDb {
+    fn get(&mut self, key: &str) -> Result<Option<Vec<u8>>, RedisError> {
+        match self.read_value(key).map(|value| &value.data) {
+            Some(Data::String(s)) => Ok(Some(s.to_bytes())),
+            Some(_) => Err(RedisError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &[u8], exp: Option<SystemTime>) {
+        self.insert(key, Value::new(value));
+        match exp {
+            Some(at) => self.expires.insert(key.to_owned(), at),
+            None => self.expires.remove(key),
+        };
+        self.touch(key);
+    }
+
+    fn expire_time(&self, key: &str) -> Option<SystemTime> {
+        self.expires.get(key).copied().filter(|at| !is_expired(*at))
+    }
+
+    fn get_value(&mut self, key: &str) -> Option<&mut Value> {
+        self.expire_if_needed(key);
+        self.data.get_mut(key)
+    }
+
+    fn read_value(&mut self, key: &str) -> Option<&mut Value> {
+        self.expire_if_needed(key);
+
+        if !self.data.contains_key(key) {
+            self.notify(notify::KEY_MISS, "keymiss", key);
+        }
+
+        self.data.get_mut(key)
+    }
+
+    fn get_or_insert_with(&mut self, key: &str, default: &dyn Fn() -> Data) -> &mut Value {
+        self.expire_if_needed(key);
+
+        if !self.data.contains_key(key) {
+            self.data.insert(key.to_owned(), Value::from(default()));
+            self.notify(notify::NEW, "new", key);
+        }
+
+        self.data.get_mut(key).expect("value was just inserted")
+    }
+
+    /// Stores `value` at `key`, replacing any previous value along with its expiration.
+    fn insert(&mut self, key: &str, value: Value) {
+        self.expire_if_needed(key);
+
+        if self.data.insert(key.to_owned(), value).is_none() {
+            self.notify(notify::NEW, "new", key);
+        }
+        self.expires.remove(key);
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        let value = self.data.remove(key);
+        self.expires.remove(key);
+        self.reindex(key);
+        if value.is_some() {
+            self.signal_modified(key);
+        }
+        value
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&str, &Value)> + '_> {
+        Box::new(
+            self.data
+                .iter()
+                .filter(|(key, _)| !self.is_expired(key))
+                .map(|(key, value)| (key.as_str(), value)),
+        )
+    }
+
+    fn touch(&mut self, key: &str) {
+        self.dirty += 1;
+        self.reindex(key);
+        self.signal_modified(key);
+
+        if self.blocked.contains_key(key) && !self.ready.iter().any(|k| k == key) {
+            self.ready.push(key.to_owned());
+        }
+    }
+
+    fn dirty(&self) -> u64 {
+        self.dirty
+    }
+
+    fn track_field_expiry(&mut self, key: &str) {
+        self.volatile_hashes.insert(key.to_owned());
+    }
+
+    fn active_expire(&mut self) {
+        let keys: Vec<String> = self
+            .expires
+            .range((resume(&self.key_cursor), Bound::Unbounded))
+            .take(ACTIVE_EXPIRE_KEYS_PER_CYCLE)
+            .map(|(key, _)| key.clone())
+            .collect();
+        advance(&mut self.key_cursor, &keys);
+
+        for key in keys {
+            self.expire_if_needed(&key);
+        }
+
+        let hashes: Vec<String> = self
+            .volatile_hashes
+            .range((resume(&self.hash_cursor), Bound::Unbounded))
+            .take(ACTIVE_EXPIRE_KEYS_PER_CYCLE)
+            .cloned()
+            .collect();
+        advance(&mut self.hash_cursor, &hashes);
+
+        for key in hashes {
+            self.expire_fields(&key);
+        }
+    }
+
+    fn propagate(&mut self, frame: Frame) {
+        self.propagated.push(frame);
+    }
+
+    fn rewrite(&mut self, frame: Frame) {
+        self.rewritten.get_or_insert_with(Vec::new).push(frame);
+    }
+
+    fn drain_propagated(&mut self) -> Vec<Frame> {
+        std::mem::take(&mut self.propagated)
+    }
+
+    fn drain_rewritten(&mut self) -> Option<Vec<Frame>> {
+        self.rewritten.take()
+    }
+
+    fn notify(&mut self, class: u32, event: &'static str, key: &str) {
+        if notify::is_enabled(self.notify_flags, class) {
+            self.notifications.push(Notification {
+                class,
+                event,
+                key: key.to_owned(),
+            });
+        }
+    }
+
+    fn drain_notifications(&mut self) -> Vec<Notification> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    fn block(&mut self, key: &str) {
+        *self.blocked.entry(key.to_owned()).or_default() += 1;
+    }
+
+    fn unblock(&mut self, key: &str) {
+        if let Some(count) = self.blocked.get_mut(key) {
+            *count -= 1;
+            if *count == 0 {
+                self.blocked.remove(key);
+            }
+        }
+    }
+
+    fn drain_ready(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.ready)
+    }
+
+    fn watch(&mut self, key: &str) -> u64 {
+        // A key that has already expired must not count as modified once it is removed.
+        self.expire_if_needed(key);
+
+        let (watchers, version) = self.watched.entry(key.to_owned()).or_default();
+        *watchers += 1;
+        *version
+    }
+
+    fn unwatch(&mut self, key: &str) {
+        if let Some((watchers, _)) = self.watched.get_mut(key) {
+            *watchers -= 1;
+            if *watchers == 0 {
+                self.watched.remove(key);
+            }
+        }
+    }
+
+    fn version(&mut self, key: &str) -> u64 {
+        // Expiring the key now catches expirations that nothing has noticed yet.
+        self.expire_if_needed(key);
+
+        self.watched.get(key).map_or(0, |(_, version)| *version)
+    }
+
+    fn flush(&mut self) {
+        let keys: Vec<String> = self.data.keys().cloned().collect();
+
+        self.data.clear();
+        self.expires.clear();
+        self.volatile_hashes.clear();
+        self.key_cursor = None;
+        self.hash_cursor = None;
+        self.dirty += 1;
+
+        for key in keys {
+            self.reindex(&key);
+            self.signal_modified(&key);
+        }
+    }
+
+    fn create_index(&mut self, name: &str, mut index: Index) -> bool {
+        if self.indexes.contains_key(name) {
+            return false;
+        }
+
+        for (key, value) in &self.data {
+            if let Data::Hash(hash) = &value.data {
+                if !self.is_expired(key) {
+                    index.update(key, Some(hash));
+                }
+            }
+        }
+
+        self.indexes.insert(name.to_owned(), index);
+        self.dirty += 1;
+        true
+    }
+
+    fn drop_index(&mut self, name: &str) -> bool {
+        let dropped = self.indexes.remove(name).is_some();
+        if dropped {
+            self.dirty += 1;
+        }
+        dropped
+    }
+
+    fn index(&self, name: &str) -> Option<&Index> {
+        self.indexes.get(name)
+    }
+
+    fn thresholds(&self) -> Thresholds {
+        self.thresholds
+    }
+
+    fn thresholds_mut(&mut self) -> &mut Thresholds {
+        &mut self.thresholds
+    }
+
+    fn notify_flags(&self) -> u32 {
+        self.notify_flags
+    }
+
+    fn set_notify_flags(&mut self, flags: u32) {
+        self.notify_flags = flags;
+    }
+}
+
+/// Where a run of the active expiry cycle starts, right after the last key of the previous run.
+fn resume(cursor: &Option<String>) -> Bound<String> {
+    match cursor {
+        Some(cursor) => Bound::Excluded(cursor.clone()),
+        None => Bound::Unbounded,
+    }
+}
+
+/// Moves the cursor past the keys just visited, starting over from the first key once the
+/// end is reached.
+fn advance(cursor: &mut Option<String>, keys: &[String]) {
+    *cursor = if keys.len() < ACTIVE_EXPIRE_KEYS_PER_CYCLE {
+        None
+    } else {
+        keys.last().cloned()
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use super::{notify, Database, KeyValueDb, Value};
+    use crate::frame::Frame;
+
+    #[test]
+    fn test_expiry_is_kept_apart() {
+        let now = SystemTime::now();
+        let mut db = KeyValueDb::new();
+        db.set("old", b"1", Some(now - Duration::from_secs(1)));
+        db.set("new", b"2", Some(now + Duration::from_secs(60)));
+
+        assert!(db.get_value("old").is_none());
+        assert_eq!(1, db.iter().count());
+        assert!(db.expire_time("new").is_some());
+
+        // Replacing a value drops its expiration.
+        db.insert("new", Value::new(b"3"));
+        assert_eq!(None, db.expire_time("new"));
+        assert_eq!(Some(b"3".to_vec()), db.get("new").unwrap());
+    }
+
+    #[test]
+    fn test_expiry_propagates_del() {
+        let past = SystemTime::now() - Duration::from_secs(1);
+        let del = |key: &str| Frame::Arrays(vec![String::from("DEL"), key.to_owned()]);
+        let mut db = KeyValueDb::new();
+        db.set("lazy", b"1", Some(past));
+        db.set("active", b"2", Some(past));
+        db.set("kept", b"3", None);
+
+        assert!(db.get_value("lazy").is_none());
+        assert_eq!(vec![del("lazy")], db.drain_propagated());
+
+        db.active_expire();
+        assert_eq!(vec![del("active")], db.drain_propagated());
+        assert_eq!(1, db.iter().count());
+    }
+
+    #[test]
+    fn test_watched_versions() {
+        let mut db = KeyValueDb::new();
+        db.set("a", b"1", None);
+        db.set(
+            "b",
+            b"2",
+            Some(SystemTime::now() + Duration::from_millis(20)),
+        );
+
+        let a = db.watch("a");
+        let b = db.watch("b");
+        db.watch("c");
+
+        // Reads leave the versions alone.
+        db.get("a").unwrap();
+        assert_eq!(a, db.version("a"));
+
+        db.set("a", b"3", None);
+        assert_ne!(a, db.version("a"));
+
+        // Expiring counts as a change, even if nothing accessed the key.
+        std::thread::sleep(Duration::from_millis(30));
+        assert_ne!(b, db.version("b"));
+
+        db.set("d", b"4", None);
+        let d = db.watch("d");
+        let c = db.version("c");
+        db.flush();
+        assert_ne!(d, db.version("d"));
+        assert_eq!(c, db.version("c"));
+        assert_eq!(0, db.iter().count());
+
+        // Versions are dropped with the last watcher.
+        db.unwatch("a");
+        assert!(!db.watched.contains_key("a"));
+    }
+
+    #[test]
+    fn test_notifications() {
+        let now = SystemTime::now();
+        let mut db = KeyValueDb::new();
+        db.set_notify_flags(notify::parse("Exmn").unwrap());
+        db.set("a", b"1", Some(now - Duration::from_secs(1)));
+        db.drain_notifications();
+
+        // Reading a key that expired reports both the expiry and the miss.
+        assert!(db.read_value("a").is_none());
+        // Looking a key up to write it is not a miss.
+        assert!(db.get_value("c").is_none());
+        db.set("b", b"2", None);
+        // Classes that are not enabled are dropped.
+        db.notify(notify::STRING, "set", "b");
+
+        let events: Vec<_> = db
+            .drain_notifications()
+            .into_iter()
+            .map(|n| (n.event, n.key))
+            .collect();
+        assert_eq!(
+            vec![
+                ("expired", String::from("a")),
+                ("keymiss", String::from("a")),
+                ("new", String::from("b")),
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn test_value_size() {
+        // Only as large as a string, whatever the type.
+        assert_eq!(32, size_of::<Value>());
+        assert_eq!(32, Value::new(b"12345").memory_usage());
+        assert_eq!(32 + 64, Value::new(&[b'x'; 64]).memory_usage());
+    }
+}
@@ -0,0 +1,130 @@
+//! Keyspace notifications, published for the classes of events enabled with
+//! `notify-keyspace-events`.
+//!
+//! Like in Redis, every event is published on `__keyspace@0__:<key>` with the event as the
+//! message when `K` is set, and on `__keyevent@0__:<event>` with the key as the message when
+//! `E` is set. Nothing is published unless one of them is set along with a class.
+
+pub const KEYSPACE: u32 = 1 << 0;
+pub const KEYEVENT: u32 = 1 << 1;
+pub const GENERIC: u32 = 1 << 2;
+pub const STRING: u32 = 1 << 3;
+pub const LIST: u32 = 1 << 4;
+pub const SET: u32 = 1 << 5;
+pub const HASH: u32 = 1 << 6;
+pub const ZSET: u32 = 1 << 7;
+pub const EXPIRED: u32 = 1 << 8;
+/// Never published, as there is no memory limit to evict keys under. It can still be set,
+/// so that configurations written for Redis keep working.
+pub const EVICTED: u32 = 1 << 9;
+pub const STREAM: u32 = 1 << 10;
+pub const KEY_MISS: u32 = 1 << 11;
+pub const MODULE: u32 = 1 << 12;
+pub const NEW: u32 = 1 << 13;
+/// Every class but key misses and new keys, which have to be asked for explicitly.
+pub const ALL: u32 =
+    GENERIC | STRING | LIST | SET | HASH | ZSET | EXPIRED | EVICTED | STREAM | MODULE;
+
+/// The class flags in the order Redis lists them.
+const CLASSES: [(char, u32); 10] = [
+    ('g', GENERIC),
+    ('$', STRING),
+    ('l', LIST),
+    ('s', SET),
+    ('h', HASH),
+    ('z', ZSET),
+    ('x', EXPIRED),
+    ('e', EVICTED),
+    ('t', STREAM),
+    ('d', MODULE),
+];
+
+/// Whether events of `class` are published at all with `flags`.
+pub fn is_enabled(flags: u32, class: u32) -> bool {
+    flags & class != 0 && flags & (KEYSPACE | KEYEVENT) != 0
+}
+
+/// Parses flags such as `KEA`, returning `None` on an unknown character.
+pub fn parse(s: &str) -> Option<u32> {
+    s.chars().try_fold(0, |flags, c| {
+        let flag = match c {
+            'A' => ALL,
+            'K' => KEYSPACE,
+            'E' => KEYEVENT,
+            'm' => KEY_MISS,
+            'n' => NEW,
+            c => CLASSES.iter().find(|(name, _)| *name == c)?.1,
+        };
+        Some(flags | flag)
+    })
+}
+
+pub fn format(flags: u32) -> String {
+    let mut s = match flags & ALL == ALL {
+        true => String::from("A"),
+        false => CLASSES
+            .iter()
+            .filter(|(_, flag)| flags & flag != 0)
+            .map(|(name, _)| *name)
+            .collect(),
+    };
+
+    for (name, flag) in [
+        ('K', KEYSPACE),
+        ('E', KEYEVENT),
+        ('m', KEY_MISS),
+        ('n', NEW),
+    ] {
+        if flags & flag != 0 {
+            s.push(name);
+        }
+    }
+
+    s
+}
+
+/// An event that happened to a key while running a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub class: u32,
+    pub event: &'static str,
+    pub key: String,
+}
+
+impl Notification {
+    /// The channels to publish the event on with their message, given the enabled flags.
+    pub fn messages(&self, flags: u32) -> Vec<(String, String)> {
+        let mut messages = Vec::new();
+
+        if flags & KEYSPACE != 0 {
+            let channel = format!("__keyspace@0__:{}", self.key);
+            messages.push((channel, self.event.to_owned()));
+        }
+        if flags & KEYEVENT != 0 {
+            let channel = format!("__keyevent@0__:{}", self.event);
+            messages.push((channel, self.key.clone()));
+        }
+
+        messages
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format, parse, ALL, EXPIRED, GENERIC, KEYEVENT, KEYSPACE, KEY_MISS};
+
+    #[test]
+    fn test_flags() {
+        assert_eq!(Some(0), parse(""));
+        assert_eq!(Some(KEYSPACE | GENERIC | EXPIRED), parse("Kgx"));
+        assert_eq!(Some(KEYSPACE | KEYEVENT | ALL), parse("KEA"));
+        assert_eq!(None, parse("KEq"));
+
+        assert_eq!("AKE", format(parse("KEA").unwrap()));
+        assert_eq!("gxKm", format(parse("mxgK").unwrap()));
+        assert_eq!(Some(KEY_MISS), parse("m"));
+        // Listing every class is the same as `A`.
+        assert_eq!("AE", format(parse("Eg$lshzxetd").unwrap()));
+        assert_eq!("", format(0));
+    }
+}
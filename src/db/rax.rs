@@ -0,0 +1,330 @@
+use std::cmp::Ordering;
+
+#[derive(Debug)]
+struct Node<V> {
+    /// Bytes between the parent and this node. Only the root has an empty prefix.
+    prefix: Vec<u8>,
+    value: Option<V>,
+    /// Children sorted by the first byte of their prefix.
+    children: Vec<Node<V>>,
+}
+
+impl<V> Node<V> {
+    fn new(prefix: Vec<u8>, value: Option<V>) -> Self {
+        Node {
+            prefix,
+            value,
+            children: Vec::new(),
+        }
+    }
+
+    fn child_index(&self, byte: u8) -> Result<usize, usize> {
+        self.children
+            .binary_search_by(|child| child.prefix[0].cmp(&byte))
+    }
+
+    fn count_nodes(&self) -> usize {
+        1 + self.children.iter().map(Node::count_nodes).sum::<usize>()
+    }
+
+    fn first(&self, path: &mut Vec<u8>) -> Option<&V> {
+        path.extend_from_slice(&self.prefix);
+
+        match &self.value {
+            Some(value) => Some(value),
+            None => self.children.first()?.first(path),
+        }
+    }
+
+    fn last(&self, path: &mut Vec<u8>) -> Option<&V> {
+        path.extend_from_slice(&self.prefix);
+
+        match self.children.last() {
+            Some(child) => child.last(path),
+            None => self.value.as_ref(),
+        }
+    }
+
+    /// Finds the smallest key in this subtree that is greater than or equal to `key`, where
+    /// `key` is relative to the parent of this node.
+    fn seek_ge(&self, path: &mut Vec<u8>, key: &[u8]) -> Option<&V> {
+        let common = common_prefix(&self.prefix, key);
+
+        if common < self.prefix.len() {
+            // Either every key below is greater than `key`, or every key is smaller.
+            return match key.get(common).map(|b| self.prefix[common].cmp(b)) {
+                None | Some(Ordering::Greater) => self.first(path),
+                _ => None,
+            };
+        }
+
+        path.extend_from_slice(&self.prefix);
+        let rest = &key[common..];
+
+        if rest.is_empty() {
+            return match &self.value {
+                Some(value) => Some(value),
+                None => self.children.first()?.first(path),
+            };
+        }
+
+        let start = match self.child_index(rest[0]) {
+            Ok(i) => {
+                let len = path.len();
+                if let Some(value) = self.children[i].seek_ge(path, rest) {
+                    return Some(value);
+                }
+                path.truncate(len);
+                i + 1
+            }
+            Err(i) => i,
+        };
+
+        self.children.get(start)?.first(path)
+    }
+
+    /// Finds the greatest key in this subtree that is less than or equal to `key`.
+    fn seek_le(&self, path: &mut Vec<u8>, key: &[u8]) -> Option<&V> {
+        let common = common_prefix(&self.prefix, key);
+
+        if common < self.prefix.len() {
+            return match key.get(common).map(|b| self.prefix[common].cmp(b)) {
+                Some(Ordering::Less) => self.last(path),
+                _ => None,
+            };
+        }
+
+        path.extend_from_slice(&self.prefix);
+        let rest = &key[common..];
+
+        if rest.is_empty() {
+            return self.value.as_ref();
+        }
+
+        let end = match self.child_index(rest[0]) {
+            Ok(i) => {
+                let len = path.len();
+                if let Some(value) = self.children[i].seek_le(path, rest) {
+                    return Some(value);
+                }
+                path.truncate(len);
+                i
+            }
+            Err(i) => i,
+        };
+
+        match end.checked_sub(1) {
+            Some(i) => self.children[i].last(path),
+            None => self.value.as_ref(),
+        }
+    }
+
+    fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        if key.is_empty() {
+            return self.value.replace(value);
+        }
+
+        match self.child_index(key[0]) {
+            Ok(i) => {
+                let child = &mut self.children[i];
+                let common = common_prefix(&child.prefix, key);
+
+                if common < child.prefix.len() {
+                    // Split the child so that the shared bytes get a node of their own.
+                    let suffix = child.prefix.split_off(common);
+                    let old = Node {
+                        prefix: suffix,
+                        value: child.value.take(),
+                        children: std::mem::take(&mut child.children),
+                    };
+                    child.children.push(old);
+                }
+
+                child.insert(&key[common..], value)
+            }
+            Err(i) => {
+                self.children
+                    .insert(i, Node::new(key.to_vec(), Some(value)));
+                None
+            }
+        }
+    }
+
+    fn get_mut(&mut self, key: &[u8]) -> Option<&mut V> {
+        if key.is_empty() {
+            return self.value.as_mut();
+        }
+
+        let i = self.child_index(key[0]).ok()?;
+        let child = &mut self.children[i];
+
+        let rest = key.strip_prefix(child.prefix.as_slice())?;
+        child.get_mut(rest)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<V> {
+        if key.is_empty() {
+            return self.value.take();
+        }
+
+        let i = self.child_index(key[0]).ok()?;
+        let child = &mut self.children[i];
+
+        let rest = key.strip_prefix(child.prefix.as_slice())?;
+        let value = child.remove(rest)?;
+
+        // Keep the tree compressed: drop empty leaves and merge single-child nodes.
+        if child.value.is_none() {
+            match child.children.len() {
+                0 => {
+                    self.children.remove(i);
+                }
+                1 => {
+                    let grandchild = child.children.pop().expect("child has one child");
+                    child.prefix.extend_from_slice(&grandchild.prefix);
+                    child.value = grandchild.value;
+                    child.children = grandchild.children;
+                }
+                _ => {}
+            }
+        }
+
+        Some(value)
+    }
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// A radix tree mapping byte strings to values, kept in lexicographical key order. Runs of
+/// bytes shared by a single branch are stored once in a node prefix.
+#[derive(Debug)]
+pub struct Rax<V> {
+    root: Node<V>,
+    len: usize,
+}
+
+impl<V> Default for Rax<V> {
+    fn default() -> Self {
+        Rax::new()
+    }
+}
+
+impl<V> Rax<V> {
+    pub fn new() -> Self {
+        Rax {
+            root: Node::new(Vec::new(), None),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of nodes in the tree, including the root.
+    pub fn node_count(&self) -> usize {
+        self.root.count_nodes()
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        let old = self.root.insert(key, value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut V> {
+        self.root.get_mut(key)
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let value = self.root.remove(key);
+        if value.is_some() {
+            self.len -= 1;
+        }
+        value
+    }
+
+    pub fn first(&self) -> Option<(Vec<u8>, &V)> {
+        let mut path = Vec::new();
+        self.root.first(&mut path).map(|value| (path, value))
+    }
+
+    pub fn last(&self) -> Option<(Vec<u8>, &V)> {
+        let mut path = Vec::new();
+        self.root.last(&mut path).map(|value| (path, value))
+    }
+
+    /// Returns the first entry whose key is greater than or equal to `key`.
+    pub fn seek_ge(&self, key: &[u8]) -> Option<(Vec<u8>, &V)> {
+        let mut path = Vec::new();
+        self.root.seek_ge(&mut path, key).map(|value| (path, value))
+    }
+
+    /// Returns the last entry whose key is less than or equal to `key`.
+    pub fn seek_le(&self, key: &[u8]) -> Option<(Vec<u8>, &V)> {
+        let mut path = Vec::new();
+        self.root.seek_le(&mut path, key).map(|value| (path, value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Rax;
+
+    #[test]
+    fn test_insert_and_seek() {
+        let mut rax = Rax::new();
+        for key in ["romane", "romanus", "romulus", "rubens", "ruber", "rubicon"] {
+            rax.insert(key.as_bytes(), key.to_owned());
+        }
+
+        assert_eq!(6, rax.len());
+        assert_eq!("romane", rax.first().unwrap().1);
+        assert_eq!("rubicon", rax.last().unwrap().1);
+
+        assert_eq!("romulus", rax.seek_ge(b"romb").unwrap().1);
+        assert_eq!("ruber", rax.seek_ge(b"ruber").unwrap().1);
+        assert!(rax.seek_ge(b"s").is_none());
+
+        assert_eq!("romanus", rax.seek_le(b"romb").unwrap().1);
+        assert_eq!("romulus", rax.seek_le(b"rube").unwrap().1);
+        assert!(rax.seek_le(b"r").is_none());
+    }
+
+    #[test]
+    fn test_remove_merges_nodes() {
+        let mut rax = Rax::new();
+        rax.insert(b"test", 1);
+        rax.insert(b"team", 2);
+        rax.insert(b"toast", 3);
+
+        assert_eq!(Some(2), rax.remove(b"team"));
+        assert_eq!(None, rax.remove(b"te"));
+        assert_eq!(2, rax.len());
+        // The root, "t", "est" and "oast".
+        assert_eq!(4, rax.node_count());
+
+        assert_eq!(Some(&mut 1), rax.get_mut(b"test"));
+        assert_eq!((b"toast".to_vec(), &3), rax.last().unwrap());
+    }
+
+    #[test]
+    fn test_fixed_width_keys() {
+        let mut rax = Rax::new();
+        for i in (0u64..1000).step_by(10) {
+            rax.insert(&i.to_be_bytes(), i);
+        }
+
+        assert_eq!(&40, rax.seek_le(&45u64.to_be_bytes()).unwrap().1);
+        assert_eq!(&50, rax.seek_ge(&45u64.to_be_bytes()).unwrap().1);
+        assert_eq!(&990, rax.seek_le(&u64::MAX.to_be_bytes()).unwrap().1);
+    }
+}
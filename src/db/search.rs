@@ -0,0 +1,578 @@
+//! Secondary indexes over hashes. An index covers the hashes whose key starts with one of its
+//! prefixes, and keeps an inverted index of each field of its schema: the words of text
+//! fields, the values of tag fields and the numbers of numeric fields, each pointing to the
+//! keys that contain them.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ops::Bound,
+};
+
+use super::hash::Hash;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    /// Words, matched case-insensitively by term or prefix.
+    Text,
+    /// A list of exact values.
+    Tag {
+        separator: char,
+        case_sensitive: bool,
+    },
+    Numeric,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// The field of the hash.
+    pub name: String,
+    /// The name used in queries, which defaults to the name of the field.
+    pub alias: String,
+    pub kind: FieldType,
+}
+
+/// The number held by the value of a numeric field, or `None` if it holds none, which
+/// leaves the document out of the field.
+pub fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|n| !n.is_nan())
+}
+
+/// Maps a float to an integer with the same order, so numbers can be kept in a `BTreeMap`.
+fn ordered(f: f64) -> u64 {
+    let bits = f.to_bits();
+    match bits >> 63 {
+        1 => !bits,
+        _ => bits | 1 << 63,
+    }
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+}
+
+fn tags(value: &str, separator: char, case_sensitive: bool) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(separator)
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(move |t| match case_sensitive {
+            true => t.to_owned(),
+            false => t.to_lowercase(),
+        })
+}
+
+/// Keys by term for text and tag fields, or by number for numeric fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Postings {
+    terms: BTreeMap<String, BTreeSet<String>>,
+    numbers: BTreeMap<u64, BTreeSet<String>>,
+}
+
+impl Postings {
+    fn update(&mut self, field: &Field, key: &str, value: &str, add: bool) {
+        let change = |keys: &mut BTreeSet<String>| match add {
+            true => keys.insert(key.to_owned()),
+            false => keys.remove(key),
+        };
+
+        match field.kind {
+            FieldType::Text => {
+                for word in words(value) {
+                    change(self.terms.entry(word).or_default());
+                }
+            }
+            FieldType::Tag {
+                separator,
+                case_sensitive,
+            } => {
+                for tag in tags(value, separator, case_sensitive) {
+                    change(self.terms.entry(tag).or_default());
+                }
+            }
+            FieldType::Numeric => {
+                if let Some(n) = parse_number(value) {
+                    change(self.numbers.entry(ordered(n)).or_default());
+                }
+            }
+        }
+
+        self.terms.retain(|_, keys| !keys.is_empty());
+        self.numbers.retain(|_, keys| !keys.is_empty());
+    }
+}
+
+/// A parsed query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// `*`, every indexed document.
+    All,
+    /// Clauses separated by spaces, which must all match.
+    And(Vec<Query>),
+    /// `-clause`
+    Not(Box<Query>),
+    /// `@field:{value | value}`
+    Tag { field: String, values: Vec<String> },
+    /// `@field:[min max]`, where `(` makes a bound exclusive.
+    Numeric {
+        field: String,
+        min: Bound<f64>,
+        max: Bound<f64>,
+    },
+    /// `word`, `prefix*`, or the same preceded by `@field:`.
+    Text {
+        field: Option<String>,
+        term: String,
+        prefix: bool,
+    },
+}
+
+impl Query {
+    pub fn parse(s: &str) -> Result<Query, String> {
+        if s.trim() == "*" {
+            return Ok(Query::All);
+        }
+
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        let mut clauses = Vec::new();
+        while let Some(clause) = parser.clause()? {
+            clauses.push(clause);
+        }
+
+        match clauses.len() {
+            0 => Err(String::from("Syntax error: empty query")),
+            1 => Ok(clauses.remove(0)),
+            _ => Ok(Query::And(clauses)),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self) -> String {
+        format!("Syntax error at offset {}", self.pos)
+    }
+
+    /// Reads until `end` or whitespace if `end` is `None`, unescaping backslashes.
+    fn until(&mut self, end: Option<char>) -> Result<String, String> {
+        let mut s = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' => {
+                    self.pos += 1;
+                    s.push(self.peek().ok_or_else(|| self.error())?);
+                }
+                c if Some(c) == end => return Ok(s),
+                c if end.is_none() && c.is_whitespace() => return Ok(s),
+                c => s.push(c),
+            }
+            self.pos += 1;
+        }
+
+        match end {
+            Some(_) => Err(self.error()),
+            None => Ok(s),
+        }
+    }
+
+    fn clause(&mut self) -> Result<Option<Query>, String> {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+        let Some(c) = self.peek() else {
+            return Ok(None);
+        };
+
+        if c == '-' {
+            self.pos += 1;
+            let clause = self.clause()?.ok_or_else(|| self.error())?;
+            return Ok(Some(Query::Not(Box::new(clause))));
+        }
+
+        let field = match c {
+            '@' => {
+                self.pos += 1;
+                let field = self.until(Some(':'))?;
+                self.pos += 1;
+                Some(field)
+            }
+            _ => None,
+        };
+
+        match (self.peek(), field) {
+            (Some('{'), Some(field)) => {
+                self.pos += 1;
+                let values = self.tag_values()?;
+                Ok(Some(Query::Tag { field, values }))
+            }
+            (Some('['), Some(field)) => {
+                self.pos += 1;
+                let range = self.until(Some(']'))?;
+                self.pos += 1;
+                let (min, max) = match range.split_whitespace().collect::<Vec<_>>()[..] {
+                    [min, max] => (parse_bound(min)?, parse_bound(max)?),
+                    _ => return Err(self.error()),
+                };
+                Ok(Some(Query::Numeric { field, min, max }))
+            }
+            (_, field) => {
+                let term = self.until(None)?;
+                let (term, prefix) = match term.strip_suffix('*') {
+                    Some(prefix) => (prefix, true),
+                    None => (term.as_str(), false),
+                };
+                if term.is_empty() {
+                    return Err(self.error());
+                }
+                Ok(Some(Query::Text {
+                    field,
+                    term: term.to_lowercase(),
+                    prefix,
+                }))
+            }
+        }
+    }
+
+    /// Reads the values of a tag clause up to the closing brace, separated by `|`.
+    fn tag_values(&mut self) -> Result<Vec<String>, String> {
+        let mut values = vec![String::new()];
+        loop {
+            let c = self.peek().ok_or_else(|| self.error())?;
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    let escaped = self.peek().ok_or_else(|| self.error())?;
+                    values.last_mut().expect("at least one value").push(escaped);
+                    self.pos += 1;
+                }
+                '|' => values.push(String::new()),
+                '}' => break,
+                c => values.last_mut().expect("at least one value").push(c),
+            }
+        }
+
+        let values: Vec<_> = values
+            .iter()
+            .map(|v| v.trim().to_owned())
+            .filter(|v| !v.is_empty())
+            .collect();
+        match values.is_empty() {
+            true => Err(self.error()),
+            false => Ok(values),
+        }
+    }
+}
+
+fn parse_bound(s: &str) -> Result<Bound<f64>, String> {
+    let (s, exclusive) = match s.strip_prefix('(') {
+        Some(s) => (s, true),
+        None => (s, false),
+    };
+    let n = match s.to_lowercase().as_str() {
+        "-inf" => f64::NEG_INFINITY,
+        "inf" | "+inf" => f64::INFINITY,
+        _ => s
+            .parse::<f64>()
+            .map_err(|_| format!("Expected a number but got '{s}'"))?,
+    };
+
+    Ok(match exclusive {
+        true => Bound::Excluded(n),
+        false => Bound::Included(n),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Index {
+    pub prefixes: Vec<String>,
+    pub fields: Vec<Field>,
+    postings: Vec<Postings>,
+    /// The indexed values of each document, to remove them when it changes and to sort on.
+    docs: HashMap<String, Vec<Option<String>>>,
+}
+
+impl Index {
+    /// Creates an index over the hashes whose key starts with one of `prefixes`, or every
+    /// hash if there is none.
+    pub fn new(prefixes: Vec<String>, fields: Vec<Field>) -> Self {
+        Index {
+            prefixes,
+            postings: vec![Postings::default(); fields.len()],
+            fields,
+            docs: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    pub fn covers(&self, key: &str) -> bool {
+        self.prefixes.is_empty() || self.prefixes.iter().any(|p| key.starts_with(p.as_str()))
+    }
+
+    pub fn field(&self, alias: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.alias == alias)
+    }
+
+    /// The indexed value of a field of a document.
+    pub fn value(&self, key: &str, alias: &str) -> Option<&str> {
+        let i = self.fields.iter().position(|f| f.alias == alias)?;
+        self.docs.get(key)?[i].as_deref()
+    }
+
+    /// Indexes the hash now stored at `key`, or removes the document if `hash` is `None`.
+    pub fn update(&mut self, key: &str, hash: Option<&Hash>) {
+        if let Some(values) = self.docs.remove(key) {
+            for ((field, postings), value) in self.fields.iter().zip(&mut self.postings).zip(values)
+            {
+                if let Some(value) = value {
+                    postings.update(field, key, &value, false);
+                }
+            }
+        }
+
+        let Some(hash) = hash.filter(|_| self.covers(key)) else {
+            return;
+        };
+
+        let values: Vec<_> = self
+            .fields
+            .iter()
+            .map(|field| hash.get(&field.name).map(Cow::into_owned))
+            .collect();
+        for ((field, postings), value) in self.fields.iter().zip(&mut self.postings).zip(&values) {
+            if let Some(value) = value {
+                postings.update(field, key, value, true);
+            }
+        }
+        self.docs.insert(key.to_owned(), values);
+    }
+
+    fn postings(&self, alias: &str) -> Result<(&Field, &Postings), String> {
+        let i = self
+            .fields
+            .iter()
+            .position(|f| f.alias == alias)
+            .ok_or_else(|| format!("Unknown field '{alias}'"))?;
+        Ok((&self.fields[i], &self.postings[i]))
+    }
+
+    /// Returns the keys of the documents matching `query`, in key order.
+    pub fn search(&self, query: &Query) -> Result<BTreeSet<String>, String> {
+        match query {
+            Query::All => Ok(self.docs.keys().cloned().collect()),
+            Query::And(clauses) => {
+                let mut keys: Option<BTreeSet<String>> = None;
+                for clause in clauses {
+                    let matched = self.search(clause)?;
+                    keys = Some(match keys {
+                        Some(keys) => keys.intersection(&matched).cloned().collect(),
+                        None => matched,
+                    });
+                }
+                Ok(keys.unwrap_or_default())
+            }
+            Query::Not(clause) => {
+                let excluded = self.search(clause)?;
+                Ok(self
+                    .docs
+                    .keys()
+                    .filter(|key| !excluded.contains(*key))
+                    .cloned()
+                    .collect())
+            }
+            Query::Tag { field, values } => {
+                let (field, postings) = self.postings(field)?;
+                let FieldType::Tag { case_sensitive, .. } = field.kind else {
+                    return Err(format!("Field '{}' is not a tag field", field.alias));
+                };
+
+                Ok(values
+                    .iter()
+                    .filter_map(|value| match case_sensitive {
+                        true => postings.terms.get(value),
+                        false => postings.terms.get(&value.to_lowercase()),
+                    })
+                    .flatten()
+                    .cloned()
+                    .collect())
+            }
+            Query::Numeric { field, min, max } => {
+                let (field, postings) = self.postings(field)?;
+                if field.kind != FieldType::Numeric {
+                    return Err(format!("Field '{}' is not a numeric field", field.alias));
+                }
+
+                let bound = |b: &Bound<f64>| match *b {
+                    Bound::Included(n) => Bound::Included(ordered(n)),
+                    Bound::Excluded(n) => Bound::Excluded(ordered(n)),
+                    Bound::Unbounded => Bound::Unbounded,
+                };
+                let (min, max) = (bound(min), bound(max));
+                if matches!((min, max), (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) if a > b)
+                {
+                    return Ok(BTreeSet::new());
+                }
+
+                Ok(postings
+                    .numbers
+                    .range((min, max))
+                    .flat_map(|(_, keys)| keys)
+                    .cloned()
+                    .collect())
+            }
+            Query::Text {
+                field,
+                term,
+                prefix,
+            } => {
+                let fields: Vec<_> = match field {
+                    Some(alias) => {
+                        let (field, postings) = self.postings(alias)?;
+                        if field.kind != FieldType::Text {
+                            return Err(format!("Field '{alias}' is not a text field"));
+                        }
+                        vec![postings]
+                    }
+                    None => self
+                        .fields
+                        .iter()
+                        .zip(&self.postings)
+                        .filter(|(f, _)| f.kind == FieldType::Text)
+                        .map(|(_, p)| p)
+                        .collect(),
+                };
+
+                let mut keys = BTreeSet::new();
+                for postings in fields {
+                    match prefix {
+                        true => postings
+                            .terms
+                            .range(term.clone()..)
+                            .take_while(|(t, _)| t.starts_with(term.as_str()))
+                            .for_each(|(_, k)| keys.extend(k.iter().cloned())),
+                        false => {
+                            keys.extend(postings.terms.get(term).into_iter().flatten().cloned())
+                        }
+                    }
+                }
+                Ok(keys)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Bound;
+
+    use super::{Field, FieldType, Index, Query};
+    use crate::db::{encoding::Thresholds, hash::Hash};
+
+    fn user(email: &str, age: &str, bio: &str) -> Hash {
+        let thresholds = Thresholds::default();
+        let mut hash = Hash::new();
+        hash.insert("email", email, &thresholds);
+        hash.insert("age", age, &thresholds);
+        hash.insert("bio", bio, &thresholds);
+        hash
+    }
+
+    fn index() -> Index {
+        let field = |name: &str, kind| Field {
+            name: name.to_owned(),
+            alias: name.to_owned(),
+            kind,
+        };
+        let tag = FieldType::Tag {
+            separator: ',',
+            case_sensitive: false,
+        };
+
+        let mut index = Index::new(
+            vec![String::from("user:")],
+            vec![
+                field("email", tag),
+                field("age", FieldType::Numeric),
+                field("bio", FieldType::Text),
+            ],
+        );
+        index.update("user:1", Some(&user("a@x.com", "30", "Rust developer")));
+        index.update("user:2", Some(&user("b@x.com", "45", "Retired developer")));
+        index.update("other:3", Some(&user("c@x.com", "20", "Ignored")));
+        index
+    }
+
+    fn search(index: &Index, query: &str) -> Vec<String> {
+        let query = Query::parse(query).unwrap();
+        index.search(&query).unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Ok(Query::And(vec![
+                Query::Tag {
+                    field: String::from("email"),
+                    values: vec![String::from("a@x.com"), String::from("b c")],
+                },
+                Query::Numeric {
+                    field: String::from("age"),
+                    min: Bound::Excluded(10.0),
+                    max: Bound::Included(f64::INFINITY),
+                },
+                Query::Not(Box::new(Query::Text {
+                    field: None,
+                    term: String::from("dev"),
+                    prefix: true,
+                })),
+            ])),
+            Query::parse(r"@email:{a\@x\.com | b c} @age:[(10 +inf] -Dev*")
+        );
+        assert!(Query::parse("@age:[1]").is_err());
+        assert!(Query::parse("@email:{a").is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let mut index = index();
+        assert_eq!(2, index.len());
+
+        assert_eq!(vec!["user:2"], search(&index, "@email:{B@X.COM}"));
+        assert_eq!(vec!["user:2"], search(&index, "@age:[(30 100]"));
+        assert_eq!(vec!["user:1", "user:2"], search(&index, "@bio:dev*"));
+        assert_eq!(vec!["user:1"], search(&index, "develop* -retired"));
+        assert!(search(&index, "@age:[50 40]").is_empty());
+
+        // Updating a document replaces its old values.
+        index.update("user:2", Some(&user("b@x.com", "25", "Rust fan")));
+        assert_eq!(
+            vec!["user:1", "user:2"],
+            search(&index, "@age:[20 30] rust")
+        );
+        index.update("user:1", None);
+        assert_eq!(vec!["user:2"], search(&index, "*"));
+        assert_eq!(Some("25"), index.value("user:2", "age"));
+
+        let query = Query::parse("@bio:{rust}").unwrap();
+        assert!(index.search(&query).is_err());
+    }
+}
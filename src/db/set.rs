@@ -0,0 +1,302 @@
+use crate::{
+    db::{
+        dict::Dict,
+        encoding::Thresholds,
+        listpack::{Entry, Listpack},
+    },
+    util::rand::random_range,
+};
+
+#[derive(Debug)]
+pub enum Set {
+    /// Sorted members of a set made only of integers.
+    IntSet(Vec<i64>),
+    /// Members of a small set, in insertion order.
+    Listpack(Listpack),
+    Hash(Dict<()>),
+}
+
+impl Default for Set {
+    fn default() -> Self {
+        Set::IntSet(Vec::new())
+    }
+}
+
+/// Parses `member` as an integer only if it is in canonical form, so that converting it back
+/// to a string yields the original member.
+fn as_int(member: &str) -> Option<i64> {
+    member
+        .parse::<i64>()
+        .ok()
+        .filter(|i| i.to_string() == member)
+}
+
+/// Whether a set of `len` members, the longest being `max_len` bytes, fits in a listpack.
+fn fits_listpack(len: usize, max_len: usize, thresholds: &Thresholds) -> bool {
+    len <= thresholds.set_max_listpack_entries && max_len <= thresholds.set_max_listpack_value
+}
+
+impl Set {
+    pub fn new() -> Self {
+        Set::default()
+    }
+
+    pub fn from_members(
+        members: impl IntoIterator<Item = String>,
+        thresholds: &Thresholds,
+    ) -> Self {
+        let mut set = Set::new();
+        for member in members {
+            set.insert(&member, thresholds);
+        }
+        set
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Set::IntSet(ints) => ints.len(),
+            Set::Listpack(lp) => lp.len(),
+            Set::Hash(members) => members.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        match self {
+            Set::IntSet(ints) => as_int(member).is_some_and(|i| ints.binary_search(&i).is_ok()),
+            Set::Listpack(lp) => lp.find(Entry::Str(member), 0).is_some(),
+            Set::Hash(members) => members.contains_key(member),
+        }
+    }
+
+    /// Adds `member`, returning `true` if it wasn't already present.
+    pub fn insert(&mut self, member: &str, thresholds: &Thresholds) -> bool {
+        if let Set::IntSet(ints) = self {
+            match as_int(member) {
+                Some(i) => {
+                    let Err(pos) = ints.binary_search(&i) else {
+                        return false;
+                    };
+
+                    ints.insert(pos, i);
+                    if ints.len() > thresholds.set_max_intset_entries {
+                        self.convert(thresholds);
+                    }
+
+                    return true;
+                }
+                None => {
+                    let max_len = ints.iter().map(|i| i.to_string().len()).max();
+                    let max_len = max_len.unwrap_or(0).max(member.len());
+                    match fits_listpack(ints.len() + 1, max_len, thresholds) {
+                        true => self.convert_to_listpack(),
+                        false => self.convert_to_hash(),
+                    }
+                }
+            }
+        }
+
+        if let Set::Listpack(lp) = self {
+            if member.len() > thresholds.set_max_listpack_value {
+                self.convert_to_hash();
+            } else {
+                if lp.find(Entry::Str(member), 0).is_some() {
+                    return false;
+                }
+
+                lp.push(Entry::Str(member));
+                if lp.len() > thresholds.set_max_listpack_entries {
+                    self.convert_to_hash();
+                }
+
+                return true;
+            }
+        }
+
+        match self {
+            Set::Hash(members) => members.insert(member.to_owned(), ()).is_none(),
+            _ => unreachable!("set was converted to a hash"),
+        }
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self {
+            Set::IntSet(ints) => match as_int(member).map(|i| ints.binary_search(&i)) {
+                Some(Ok(pos)) => {
+                    ints.remove(pos);
+                    true
+                }
+                _ => false,
+            },
+            Set::Listpack(lp) => match lp.find(Entry::Str(member), 0) {
+                Some(off) => {
+                    lp.remove(off);
+                    true
+                }
+                None => false,
+            },
+            Set::Hash(members) => members.remove(member).is_some(),
+        }
+    }
+
+    pub fn members(&self) -> Vec<String> {
+        match self {
+            Set::IntSet(ints) => ints.iter().map(|i| i.to_string()).collect(),
+            Set::Listpack(lp) => lp.iter().map(|entry| entry.to_string()).collect(),
+            Set::Hash(members) => members.keys().map(String::from).collect(),
+        }
+    }
+
+    /// Returns about `count` members from `cursor` on, and the cursor to resume from, which
+    /// is zero once the scan is complete. Small sets are returned whole.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<String>) {
+        match self {
+            Set::Hash(members) => {
+                let (cursor, batch) = members.scan(cursor, count);
+                (
+                    cursor,
+                    batch.into_iter().map(|(m, _)| m.to_owned()).collect(),
+                )
+            }
+            _ => (0, self.members()),
+        }
+    }
+
+    pub fn random_member(&self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let index = random_range(self.len());
+
+        match self {
+            Set::IntSet(ints) => Some(ints[index].to_string()),
+            Set::Listpack(lp) => lp.iter().nth(index).map(|entry| entry.to_string()),
+            Set::Hash(members) => members.keys().nth(index).map(String::from),
+        }
+    }
+
+    /// Estimate of the bytes allocated for the members, ignoring the spare capacity of tables.
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Set::IntSet(ints) => ints.capacity() * size_of::<i64>(),
+            Set::Listpack(lp) => lp.bytes(),
+            Set::Hash(members) => members.keys().map(|m| size_of::<String>() + m.len()).sum(),
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Set::IntSet(_) => "intset",
+            Set::Listpack(_) => "listpack",
+            Set::Hash(_) => "hashtable",
+        }
+    }
+
+    /// Converts an intset that grew too large to a listpack if it is small enough for one,
+    /// which only happens when intsets are configured to be smaller than listpacks.
+    fn convert(&mut self, thresholds: &Thresholds) {
+        let max_len = self.members().iter().map(|m| m.len()).max().unwrap_or(0);
+        match fits_listpack(self.len(), max_len, thresholds) {
+            true => self.convert_to_listpack(),
+            false => self.convert_to_hash(),
+        }
+    }
+
+    fn convert_to_listpack(&mut self) {
+        if let Set::IntSet(ints) = self {
+            let mut lp = Listpack::new();
+            for i in ints.iter() {
+                lp.push(Entry::Int(*i));
+            }
+            *self = Set::Listpack(lp);
+        }
+    }
+
+    fn convert_to_hash(&mut self) {
+        if !matches!(self, Set::Hash(_)) {
+            *self = Set::Hash(self.members().into_iter().map(|m| (m, ())).collect());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Set;
+    use crate::db::encoding::Thresholds;
+
+    #[test]
+    fn test_intset_stays_sorted() {
+        let thresholds = Thresholds::default();
+        let mut set = Set::new();
+        set.insert("3", &thresholds);
+        set.insert("-1", &thresholds);
+        set.insert("2", &thresholds);
+
+        assert_eq!("intset", set.encoding());
+        assert_eq!(vec!["-1", "2", "3"], set.members());
+        assert!(set.contains("2"));
+        assert!(!set.contains("02"));
+    }
+
+    #[test]
+    fn test_convert_on_non_integer() {
+        let thresholds = Thresholds::default();
+        let mut set = Set::new();
+        set.insert("1", &thresholds);
+        set.insert("a", &thresholds);
+
+        assert_eq!("listpack", set.encoding());
+        assert!(set.contains("1"));
+        assert!(set.contains("a"));
+        assert!(!set.insert("1", &thresholds));
+        assert!(set.remove("1"));
+        assert_eq!(vec!["a"], set.members());
+    }
+
+    #[test]
+    fn test_convert_on_size() {
+        let thresholds = Thresholds::default();
+        let members = (0..=thresholds.set_max_intset_entries).map(|i| i.to_string());
+        let set = Set::from_members(members, &thresholds);
+
+        assert_eq!("hashtable", set.encoding());
+        assert_eq!(thresholds.set_max_intset_entries + 1, set.len());
+    }
+
+    #[test]
+    fn test_listpack_to_hash() {
+        let thresholds = Thresholds::default();
+        let members = (0..thresholds.set_max_listpack_entries).map(|i| format!("m{i}"));
+        let mut set = Set::from_members(members, &thresholds);
+        assert_eq!("listpack", set.encoding());
+
+        set.insert("last", &thresholds);
+        assert_eq!("hashtable", set.encoding());
+        assert!(set.contains("m0"));
+
+        let mut set = Set::new();
+        set.insert(
+            &"x".repeat(thresholds.set_max_listpack_value + 1),
+            &thresholds,
+        );
+        assert_eq!("hashtable", set.encoding());
+    }
+
+    #[test]
+    fn test_custom_thresholds() {
+        let thresholds = Thresholds {
+            set_max_intset_entries: 2,
+            ..Thresholds::default()
+        };
+        let mut set = Set::from_members(["1", "2"].map(String::from), &thresholds);
+        assert_eq!("intset", set.encoding());
+
+        // An intset past its limit becomes a listpack if it fits in one.
+        set.insert("3", &thresholds);
+        assert_eq!("listpack", set.encoding());
+    }
+}
@@ -0,0 +1,559 @@
+use crate::util::rand::random_u64;
+
+const MAX_LEVEL: usize = 32;
+/// Index of the header node, which holds no element.
+const HEAD: usize = 0;
+
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    forward: Option<usize>,
+    /// Number of elements skipped by following `forward`, used to compute ranks.
+    span: usize,
+}
+
+#[derive(Debug)]
+struct Node {
+    member: String,
+    score: f64,
+    backward: Option<usize>,
+    levels: Vec<Level>,
+}
+
+impl Node {
+    fn is_before(&self, score: f64, member: &str) -> bool {
+        self.score < score || (self.score == score && self.member.as_str() < member)
+    }
+}
+
+/// A score range such as `(1 5]`, used by `BYSCORE` queries.
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreRange {
+    pub min: f64,
+    pub max: f64,
+    pub min_exclusive: bool,
+    pub max_exclusive: bool,
+}
+
+impl ScoreRange {
+    pub fn above_min(&self, score: f64) -> bool {
+        if self.min_exclusive {
+            score > self.min
+        } else {
+            score >= self.min
+        }
+    }
+
+    pub fn below_max(&self, score: f64) -> bool {
+        if self.max_exclusive {
+            score < self.max
+        } else {
+            score <= self.max
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min > self.max || (self.min == self.max && (self.min_exclusive || self.max_exclusive))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexBound {
+    NegInf,
+    PosInf,
+    Inclusive(String),
+    Exclusive(String),
+}
+
+/// A lexicographical range such as `[a (c`, used by `BYLEX` queries.
+#[derive(Debug, Clone)]
+pub struct LexRange {
+    pub min: LexBound,
+    pub max: LexBound,
+}
+
+impl LexRange {
+    pub fn above_min(&self, member: &str) -> bool {
+        match &self.min {
+            LexBound::NegInf => true,
+            LexBound::PosInf => false,
+            LexBound::Inclusive(min) => member >= min.as_str(),
+            LexBound::Exclusive(min) => member > min.as_str(),
+        }
+    }
+
+    pub fn below_max(&self, member: &str) -> bool {
+        match &self.max {
+            LexBound::NegInf => false,
+            LexBound::PosInf => true,
+            LexBound::Inclusive(max) => member <= max.as_str(),
+            LexBound::Exclusive(max) => member < max.as_str(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match (&self.min, &self.max) {
+            (LexBound::PosInf, _) | (_, LexBound::NegInf) => true,
+            (LexBound::NegInf, _) | (_, LexBound::PosInf) => false,
+            (LexBound::Inclusive(min), LexBound::Inclusive(max)) => min > max,
+            (LexBound::Inclusive(min), LexBound::Exclusive(max))
+            | (LexBound::Exclusive(min), LexBound::Inclusive(max))
+            | (LexBound::Exclusive(min), LexBound::Exclusive(max)) => min >= max,
+        }
+    }
+}
+
+/// Skiplist ordered by `(score, member)`, as used by Redis sorted sets. Every link records how
+/// many elements it skips, so ranks are found in O(log n). Nodes live in an arena and refer to
+/// each other by index.
+#[derive(Debug)]
+pub struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    tail: Option<usize>,
+    len: usize,
+    level: usize,
+}
+
+impl SkipList {
+    /// Bytes allocated for the nodes, including the free ones waiting to be reused.
+    pub fn heap_size(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| {
+                size_of::<Node>()
+                    + node.member.capacity()
+                    + node.levels.capacity() * size_of::<Level>()
+            })
+            .sum()
+    }
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        SkipList::new()
+    }
+}
+
+fn random_level() -> usize {
+    // Each additional level is kept with a probability of 1/4.
+    let mut level = 1;
+    while level < MAX_LEVEL && random_u64().trailing_zeros() >= 2 {
+        level += 1;
+    }
+    level
+}
+
+impl SkipList {
+    pub fn new() -> Self {
+        let head = Node {
+            member: String::new(),
+            score: 0.0,
+            backward: None,
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0,
+                };
+                MAX_LEVEL
+            ],
+        };
+
+        SkipList {
+            nodes: vec![head],
+            free: Vec::new(),
+            tail: None,
+            len: 0,
+            level: 1,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn member(&self, node: usize) -> &str {
+        &self.nodes[node].member
+    }
+
+    pub fn score(&self, node: usize) -> f64 {
+        self.nodes[node].score
+    }
+
+    pub fn first(&self) -> Option<usize> {
+        self.nodes[HEAD].levels[0].forward
+    }
+
+    pub fn last(&self) -> Option<usize> {
+        self.tail
+    }
+
+    pub fn next(&self, node: usize) -> Option<usize> {
+        self.nodes[node].levels[0].forward
+    }
+
+    pub fn prev(&self, node: usize) -> Option<usize> {
+        self.nodes[node].backward
+    }
+
+    fn forward(&self, node: usize, level: usize) -> Option<usize> {
+        self.nodes[node].levels[level].forward
+    }
+
+    fn span(&self, node: usize, level: usize) -> usize {
+        self.nodes[node].levels[level].span
+    }
+
+    fn alloc(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Inserts an element. The caller guarantees that `member` is not already present.
+    pub fn insert(&mut self, score: f64, member: &str) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut x = HEAD;
+
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.forward(x, i) {
+                if !self.nodes[next].is_before(score, member) {
+                    break;
+                }
+                rank[i] += self.span(x, i);
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = self.alloc(Node {
+            member: member.to_owned(),
+            score,
+            backward: None,
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0,
+                };
+                level
+            ],
+        });
+
+        for i in 0..level {
+            let prev = update[i];
+            self.nodes[node].levels[i].forward = self.forward(prev, i);
+            self.nodes[prev].levels[i].forward = Some(node);
+            self.nodes[node].levels[i].span = self.span(prev, i) - (rank[0] - rank[i]);
+            self.nodes[prev].levels[i].span = rank[0] - rank[i] + 1;
+        }
+
+        for (i, prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[*prev].levels[i].span += 1;
+        }
+
+        self.nodes[node].backward = if update[0] == HEAD {
+            None
+        } else {
+            Some(update[0])
+        };
+
+        match self.forward(node, 0) {
+            Some(next) => self.nodes[next].backward = Some(node),
+            None => self.tail = Some(node),
+        }
+
+        self.len += 1;
+    }
+
+    /// Removes the element with the given score and member, returning `true` if it was found.
+    pub fn remove(&mut self, score: f64, member: &str) -> bool {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut x = HEAD;
+
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !self.nodes[next].is_before(score, member) {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        match self.forward(x, 0) {
+            Some(node) if self.nodes[node].score == score && self.nodes[node].member == member => {
+                self.unlink(node, &update);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn unlink(&mut self, node: usize, update: &[usize; MAX_LEVEL]) {
+        for (i, prev) in update.iter().enumerate().take(self.level) {
+            if self.forward(*prev, i) == Some(node) {
+                self.nodes[*prev].levels[i].span += self.span(node, i);
+                self.nodes[*prev].levels[i].span -= 1;
+                self.nodes[*prev].levels[i].forward = self.forward(node, i);
+            } else {
+                self.nodes[*prev].levels[i].span -= 1;
+            }
+        }
+
+        match self.forward(node, 0) {
+            Some(next) => self.nodes[next].backward = self.nodes[node].backward,
+            None => self.tail = self.nodes[node].backward,
+        }
+
+        while self.level > 1 && self.forward(HEAD, self.level - 1).is_none() {
+            self.level -= 1;
+        }
+
+        self.nodes[node].member = String::new();
+        self.nodes[node].levels = Vec::new();
+        self.free.push(node);
+        self.len -= 1;
+    }
+
+    /// Returns the 0-based rank of the element, or `None` if it isn't in the list.
+    pub fn rank(&self, score: f64, member: &str) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEAD;
+
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                let n = &self.nodes[next];
+                if !(n.is_before(score, member) || (n.score == score && n.member == member)) {
+                    break;
+                }
+                rank += self.span(x, i);
+                x = next;
+            }
+
+            if x != HEAD && self.nodes[x].score == score && self.nodes[x].member == member {
+                return Some(rank - 1);
+            }
+        }
+
+        None
+    }
+
+    /// Returns the node at the 0-based `rank`.
+    pub fn by_rank(&self, rank: usize) -> Option<usize> {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEAD;
+
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if traversed + self.span(x, i) > target {
+                    break;
+                }
+                traversed += self.span(x, i);
+                x = next;
+            }
+
+            if traversed == target {
+                return Some(x);
+            }
+        }
+
+        None
+    }
+
+    pub fn first_in_score_range(&self, range: &ScoreRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if range.above_min(self.nodes[next].score) {
+                    break;
+                }
+                x = next;
+            }
+        }
+
+        self.forward(x, 0)
+            .filter(|node| range.below_max(self.nodes[*node].score))
+    }
+
+    pub fn last_in_score_range(&self, range: &ScoreRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !range.below_max(self.nodes[next].score) {
+                    break;
+                }
+                x = next;
+            }
+        }
+
+        Some(x).filter(|node| *node != HEAD && range.above_min(self.nodes[*node].score))
+    }
+
+    pub fn first_in_lex_range(&self, range: &LexRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if range.above_min(&self.nodes[next].member) {
+                    break;
+                }
+                x = next;
+            }
+        }
+
+        self.forward(x, 0)
+            .filter(|node| range.below_max(&self.nodes[*node].member))
+    }
+
+    pub fn last_in_lex_range(&self, range: &LexRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !range.below_max(&self.nodes[next].member) {
+                    break;
+                }
+                x = next;
+            }
+        }
+
+        Some(x).filter(|node| *node != HEAD && range.above_min(&self.nodes[*node].member))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LexBound, LexRange, ScoreRange, SkipList};
+
+    fn list() -> SkipList {
+        let mut list = SkipList::new();
+        for i in 0..100 {
+            list.insert(i as f64, &format!("m{i:03}"));
+        }
+        list
+    }
+
+    #[test]
+    fn test_rank() {
+        let list = list();
+
+        assert_eq!(100, list.len());
+        assert_eq!(Some(0), list.rank(0.0, "m000"));
+        assert_eq!(Some(42), list.rank(42.0, "m042"));
+        assert_eq!(None, list.rank(42.0, "m043"));
+
+        let node = list.by_rank(57).unwrap();
+        assert_eq!("m057", list.member(node));
+    }
+
+    #[test]
+    fn test_remove_keeps_ranks() {
+        let mut list = list();
+
+        for i in (0..100).step_by(2) {
+            assert!(list.remove(i as f64, &format!("m{i:03}")));
+        }
+
+        assert!(!list.remove(0.0, "m000"));
+        assert_eq!(50, list.len());
+        assert_eq!(Some(10), list.rank(21.0, "m021"));
+        assert_eq!("m099", list.member(list.last().unwrap()));
+
+        list.insert(0.5, "a");
+        assert_eq!(Some(0), list.rank(0.5, "a"));
+        assert_eq!(Some(1), list.rank(1.0, "m001"));
+    }
+
+    #[test]
+    fn test_equal_scores_order_by_member() {
+        let mut list = SkipList::new();
+        list.insert(1.0, "b");
+        list.insert(1.0, "a");
+        list.insert(1.0, "c");
+
+        let first = list.first().unwrap();
+        assert_eq!("a", list.member(first));
+        assert_eq!("b", list.member(list.next(first).unwrap()));
+    }
+
+    #[test]
+    fn test_score_range() {
+        let list = list();
+        let range = ScoreRange {
+            min: 10.0,
+            max: 20.0,
+            min_exclusive: true,
+            max_exclusive: false,
+        };
+
+        assert_eq!(
+            "m011",
+            list.member(list.first_in_score_range(&range).unwrap())
+        );
+        assert_eq!(
+            "m020",
+            list.member(list.last_in_score_range(&range).unwrap())
+        );
+
+        let empty = ScoreRange {
+            min: 200.0,
+            max: 300.0,
+            min_exclusive: false,
+            max_exclusive: false,
+        };
+        assert_eq!(None, list.first_in_score_range(&empty));
+    }
+
+    #[test]
+    fn test_lex_range() {
+        let mut list = SkipList::new();
+        for member in ["a", "b", "c", "d"] {
+            list.insert(0.0, member);
+        }
+
+        let range = LexRange {
+            min: LexBound::Exclusive(String::from("a")),
+            max: LexBound::Inclusive(String::from("c")),
+        };
+
+        assert_eq!("b", list.member(list.first_in_lex_range(&range).unwrap()));
+        assert_eq!("c", list.member(list.last_in_lex_range(&range).unwrap()));
+    }
+}
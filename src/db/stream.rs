@@ -0,0 +1,859 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::db::{
+    listpack::{Entry, Listpack},
+    rax::Rax,
+};
+
+/// Blocks are closed once they hold this many entries, deleted ones included...
+const STREAM_NODE_MAX_ENTRIES: usize = 100;
+/// ...or once they grow past this many bytes.
+const STREAM_NODE_MAX_BYTES: usize = 4096;
+
+const FLAG_DELETED: i64 = 1;
+/// The entry has the same fields as the master entry, so only its values are stored.
+const FLAG_SAMEFIELDS: i64 = 2;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        StreamId { ms, seq }
+    }
+
+    /// Parses `ms-seq`, or a bare `ms` with `default_seq` as the sequence.
+    pub fn parse(s: &str, default_seq: u64) -> Option<Self> {
+        match s.split_once('-') {
+            Some((ms, seq)) => Some(StreamId::new(ms.parse().ok()?, seq.parse().ok()?)),
+            None => Some(StreamId::new(s.parse().ok()?, default_seq)),
+        }
+    }
+
+    pub fn next(&self) -> Option<Self> {
+        match (self.seq.checked_add(1), self.ms.checked_add(1)) {
+            (Some(seq), _) => Some(StreamId::new(self.ms, seq)),
+            (None, Some(ms)) => Some(StreamId::new(ms, 0)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn prev(&self) -> Option<Self> {
+        match (self.seq.checked_sub(1), self.ms.checked_sub(1)) {
+            (Some(seq), _) => Some(StreamId::new(self.ms, seq)),
+            (None, Some(ms)) => Some(StreamId::new(ms, u64::MAX)),
+            (None, None) => None,
+        }
+    }
+
+    /// Big-endian key, so that the radix tree orders blocks by ID.
+    fn to_key(self) -> [u8; 16] {
+        let mut key = [0; 16];
+        key[..8].copy_from_slice(&self.ms.to_be_bytes());
+        key[8..].copy_from_slice(&self.seq.to_be_bytes());
+        key
+    }
+
+    fn from_key(key: &[u8]) -> Self {
+        let ms = u64::from_be_bytes(key[..8].try_into().expect("stream keys are 16 bytes"));
+        let seq = u64::from_be_bytes(key[8..].try_into().expect("stream keys are 16 bytes"));
+        StreamId::new(ms, seq)
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+pub type Fields = Vec<(String, String)>;
+pub type StreamEntry = (StreamId, Fields);
+
+#[derive(Debug, Clone, Copy)]
+pub enum TrimStrategy {
+    MaxLen(usize),
+    MinId(StreamId),
+}
+
+/// An entry decoded from a block.
+struct BlockEntry {
+    id: StreamId,
+    /// Offset of the entry flags in the block.
+    offset: usize,
+    deleted: bool,
+    fields: Fields,
+}
+
+/// A block holds consecutive entries packed in a listpack, laid out like Redis stream nodes:
+///
+/// ```text
+/// count deleted num-fields field_1 ... field_N 0
+/// flags ms-diff seq-diff [num-fields field_1 value_1 ... | value_1 ...] lp-count
+/// ...
+/// ```
+///
+/// The first entry added to a block is its master entry: the IDs of the other entries are
+/// stored relative to it, and entries with the same fields as the master only store values.
+/// Deleted entries are only flagged until the whole block is freed.
+fn new_block(fields: &Fields) -> Listpack {
+    let mut lp = Listpack::new();
+    lp.push(Entry::Int(0));
+    lp.push(Entry::Int(0));
+    lp.push(Entry::Int(fields.len() as i64));
+    for (field, _) in fields {
+        lp.push(Entry::Str(field));
+    }
+    lp.push(Entry::Int(0));
+    lp
+}
+
+fn header_offsets(lp: &Listpack) -> (usize, usize) {
+    let count = lp.first().expect("blocks have a header");
+    let deleted = lp.next(count).expect("blocks have a header");
+    (count, deleted)
+}
+
+fn block_counts(lp: &Listpack) -> (usize, usize) {
+    let (count, deleted) = header_offsets(lp);
+    let int = |off| lp.get(off).as_int().unwrap_or_default() as usize;
+    (int(count), int(deleted))
+}
+
+fn set_block_counts(lp: &mut Listpack, count: usize, deleted: usize) {
+    let (count_off, _) = header_offsets(lp);
+    lp.replace(count_off, Entry::Int(count as i64));
+    let (_, deleted_off) = header_offsets(lp);
+    lp.replace(deleted_off, Entry::Int(deleted as i64));
+}
+
+fn master_fields(lp: &Listpack) -> (Vec<String>, Option<usize>) {
+    let (_, deleted) = header_offsets(lp);
+    let mut off = lp.next(deleted);
+    let num = off.map_or(0, |o| lp.get(o).as_int().unwrap_or_default());
+
+    let mut fields = Vec::new();
+    for _ in 0..num {
+        off = off.and_then(|o| lp.next(o));
+        fields.extend(off.map(|o| lp.get(o).to_string()));
+    }
+
+    // Skip the terminator of the master entry.
+    let first_entry = off.and_then(|o| lp.next(o)).and_then(|o| lp.next(o));
+    (fields, first_entry)
+}
+
+fn decode_block(lp: &Listpack, master: StreamId) -> Vec<BlockEntry> {
+    let (master_fields, mut off) = master_fields(lp);
+    let mut entries = Vec::new();
+
+    let read = |off: &mut Option<usize>| {
+        let o = off.expect("stream entry is truncated");
+        *off = lp.next(o);
+        lp.get(o)
+    };
+
+    while let Some(offset) = off {
+        let flags = read(&mut off).as_int().unwrap_or_default();
+        let ms = read(&mut off).as_int().unwrap_or_default();
+        let seq = read(&mut off).as_int().unwrap_or_default();
+        let id = StreamId::new(
+            master.ms.wrapping_add(ms as u64),
+            master.seq.wrapping_add(seq as u64),
+        );
+
+        let fields = if flags & FLAG_SAMEFIELDS != 0 {
+            master_fields
+                .iter()
+                .map(|field| (field.clone(), read(&mut off).to_string()))
+                .collect()
+        } else {
+            let num = read(&mut off).as_int().unwrap_or_default();
+            (0..num)
+                .map(|_| {
+                    let field = read(&mut off).to_string();
+                    (field, read(&mut off).to_string())
+                })
+                .collect()
+        };
+
+        // lp-count, only needed to walk a block backwards.
+        read(&mut off);
+
+        entries.push(BlockEntry {
+            id,
+            offset,
+            deleted: flags & FLAG_DELETED != 0,
+            fields,
+        });
+    }
+
+    entries
+}
+
+fn append_to_block(lp: &mut Listpack, master: StreamId, id: StreamId, fields: &Fields) {
+    let (master_fields, _) = master_fields(lp);
+    let same_fields = master_fields.len() == fields.len()
+        && master_fields.iter().zip(fields).all(|(m, (f, _))| m == f);
+
+    let flags = if same_fields { FLAG_SAMEFIELDS } else { 0 };
+    lp.push(Entry::Int(flags));
+    lp.push(Entry::Int(id.ms.wrapping_sub(master.ms) as i64));
+    lp.push(Entry::Int(id.seq.wrapping_sub(master.seq) as i64));
+
+    let lp_count = if same_fields {
+        for (_, value) in fields {
+            lp.push(Entry::Str(value));
+        }
+        fields.len()
+    } else {
+        lp.push(Entry::Int(fields.len() as i64));
+        for (field, value) in fields {
+            lp.push(Entry::Str(field));
+            lp.push(Entry::Str(value));
+        }
+        fields.len() * 2 + 1
+    };
+    lp.push(Entry::Int(lp_count as i64 + 3));
+
+    let (count, deleted) = block_counts(lp);
+    set_block_counts(lp, count + 1, deleted);
+}
+
+/// An append-only log of entries ordered by ID, stored as a radix tree of blocks keyed by the
+/// ID of their master entry.
+#[derive(Debug, Default)]
+pub struct Stream {
+    blocks: Rax<Listpack>,
+    len: usize,
+    last_id: StreamId,
+    max_deleted_id: StreamId,
+    entries_added: u64,
+    groups: BTreeMap<String, ConsumerGroup>,
+}
+
+impl Stream {
+    pub fn new() -> Self {
+        Stream::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    pub fn max_deleted_id(&self) -> StreamId {
+        self.max_deleted_id
+    }
+
+    pub fn entries_added(&self) -> u64 {
+        self.entries_added
+    }
+
+    /// Number of blocks in the radix tree.
+    pub fn radix_tree_keys(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn radix_tree_nodes(&self) -> usize {
+        self.blocks.node_count()
+    }
+
+    /// Overrides the stream metadata, as XSETID does. The caller validates the values.
+    pub fn set_last_id(&mut self, id: StreamId, entries_added: u64, max_deleted_id: StreamId) {
+        self.last_id = id;
+        self.entries_added = entries_added;
+        self.max_deleted_id = max_deleted_id;
+    }
+
+    /// Appends an entry. The caller guarantees that `id` is greater than the last ID.
+    pub fn append(&mut self, id: StreamId, fields: &Fields) {
+        let last = self.blocks.last().map(|(key, lp)| {
+            let (count, deleted) = block_counts(lp);
+            let full =
+                count + deleted >= STREAM_NODE_MAX_ENTRIES || lp.bytes() >= STREAM_NODE_MAX_BYTES;
+            (StreamId::from_key(&key), full)
+        });
+
+        let master = match last {
+            Some((master, false)) => master,
+            _ => {
+                self.blocks.insert(&id.to_key(), new_block(fields));
+                id
+            }
+        };
+
+        let lp = self
+            .blocks
+            .get_mut(&master.to_key())
+            .expect("block was just found");
+        append_to_block(lp, master, id, fields);
+
+        self.len += 1;
+        self.last_id = id;
+        self.entries_added += 1;
+    }
+
+    /// Returns up to `count` live entries between `start` and `end`, both inclusive, walking
+    /// backwards from `end` if `reverse`.
+    pub fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        reverse: bool,
+        count: Option<usize>,
+    ) -> Vec<StreamEntry> {
+        let mut result = Vec::new();
+        if start > end {
+            return result;
+        }
+
+        let full = |result: &Vec<StreamEntry>| count.is_some_and(|count| result.len() >= count);
+
+        if reverse {
+            let mut block = self.blocks.seek_le(&end.to_key());
+            while let Some((key, lp)) = block {
+                let master = StreamId::from_key(&key);
+                for entry in decode_block(lp, master).into_iter().rev() {
+                    if entry.id < start || full(&result) {
+                        return result;
+                    }
+                    if !entry.deleted && entry.id <= end {
+                        result.push((entry.id, entry.fields));
+                    }
+                }
+
+                block = master
+                    .prev()
+                    .and_then(|prev| self.blocks.seek_le(&prev.to_key()));
+            }
+        } else {
+            let mut block = self
+                .blocks
+                .seek_le(&start.to_key())
+                .or_else(|| self.blocks.first());
+            while let Some((key, lp)) = block {
+                let master = StreamId::from_key(&key);
+                for entry in decode_block(lp, master) {
+                    if entry.id > end || full(&result) {
+                        return result;
+                    }
+                    if !entry.deleted && entry.id >= start {
+                        result.push((entry.id, entry.fields));
+                    }
+                }
+
+                block = master
+                    .next()
+                    .and_then(|next| self.blocks.seek_ge(&next.to_key()));
+            }
+        }
+
+        result
+    }
+
+    pub fn first_entry(&self) -> Option<StreamEntry> {
+        self.range(StreamId::MIN, StreamId::MAX, false, Some(1))
+            .pop()
+    }
+
+    pub fn last_entry(&self) -> Option<StreamEntry> {
+        self.range(StreamId::MIN, StreamId::MAX, true, Some(1))
+            .pop()
+    }
+
+    /// ID of the first live entry, or 0-0 if the stream is empty.
+    pub fn first_id(&self) -> StreamId {
+        self.first_entry().map_or(StreamId::MIN, |(id, _)| id)
+    }
+
+    /// Deletes the entry with `id`, returning `false` if there is none.
+    pub fn remove(&mut self, id: StreamId) -> bool {
+        let Some((key, lp)) = self.blocks.seek_le(&id.to_key()) else {
+            return false;
+        };
+
+        let master = StreamId::from_key(&key);
+        let Some(entry) = decode_block(lp, master)
+            .into_iter()
+            .find(|entry| entry.id == id && !entry.deleted)
+        else {
+            return false;
+        };
+
+        self.delete_entries(master, &[entry.offset]);
+
+        if id > self.max_deleted_id {
+            self.max_deleted_id = id;
+        }
+
+        true
+    }
+
+    /// Flags the entries at `offsets` of a block as deleted, freeing the block once no live
+    /// entry is left.
+    fn delete_entries(&mut self, master: StreamId, offsets: &[usize]) {
+        let key = master.to_key();
+        let lp = self.blocks.get_mut(&key).expect("block exists");
+
+        // Flags are small integers, so rewriting them leaves every offset in place.
+        for &offset in offsets {
+            let flags = lp.get(offset).as_int().unwrap_or_default();
+            lp.replace(offset, Entry::Int(flags | FLAG_DELETED));
+        }
+
+        let (count, deleted) = block_counts(lp);
+        let count = count - offsets.len();
+        if count == 0 {
+            self.blocks.remove(&key);
+        } else {
+            set_block_counts(lp, count, deleted + offsets.len());
+        }
+
+        self.len -= offsets.len();
+    }
+
+    /// Trims the oldest entries, returning how many were deleted. Approximate trimming only
+    /// frees whole blocks, and stops once `limit` entries were deleted.
+    pub fn trim(&mut self, strategy: TrimStrategy, approx: bool, limit: Option<usize>) -> usize {
+        let mut trimmed = 0;
+
+        while let Some((key, lp)) = self.blocks.first() {
+            let master = StreamId::from_key(&key);
+            let (count, _) = block_counts(lp);
+
+            let remove_block = match strategy {
+                TrimStrategy::MaxLen(max) => self.len - count >= max,
+                TrimStrategy::MinId(min) => decode_block(lp, master)
+                    .last()
+                    .is_some_and(|entry| entry.id < min),
+            };
+
+            if remove_block {
+                if limit.is_some_and(|limit| trimmed + count > limit) {
+                    break;
+                }
+
+                self.blocks.remove(&key);
+                self.len -= count;
+                trimmed += count;
+                continue;
+            }
+
+            if approx {
+                break;
+            }
+
+            // Exact trimming deletes single entries from the first block that is kept.
+            let live = decode_block(lp, master)
+                .into_iter()
+                .filter(|entry| !entry.deleted);
+            let offsets: Vec<usize> = match strategy {
+                TrimStrategy::MaxLen(max) => live
+                    .take(self.len.saturating_sub(max))
+                    .map(|entry| entry.offset)
+                    .collect(),
+                TrimStrategy::MinId(min) => live
+                    .take_while(|entry| entry.id < min)
+                    .map(|entry| entry.offset)
+                    .collect(),
+            };
+
+            if !offsets.is_empty() {
+                trimmed += offsets.len();
+                self.delete_entries(master, &offsets);
+            }
+            break;
+        }
+
+        trimmed
+    }
+
+    /// Blocks in ID order, with the ID of their master entry.
+    pub fn blocks(&self) -> impl Iterator<Item = (StreamId, &Listpack)> + '_ {
+        std::iter::successors(self.blocks.first(), |(key, _)| {
+            StreamId::from_key(key)
+                .next()
+                .and_then(|next| self.blocks.seek_ge(&next.to_key()))
+        })
+        .map(|(key, lp)| (StreamId::from_key(&key), lp))
+    }
+
+    /// Returns the fields of the live entry with `id`.
+    pub fn get(&self, id: StreamId) -> Option<Fields> {
+        self.range(id, id, false, Some(1))
+            .pop()
+            .map(|(_, fields)| fields)
+    }
+
+    pub fn groups(&self) -> &BTreeMap<String, ConsumerGroup> {
+        &self.groups
+    }
+
+    pub fn group(&self, name: &str) -> Option<&ConsumerGroup> {
+        self.groups.get(name)
+    }
+
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut ConsumerGroup> {
+        self.groups.get_mut(name)
+    }
+
+    /// Creates a consumer group, returning `false` if one with the same name exists.
+    pub fn create_group(
+        &mut self,
+        name: &str,
+        last_id: StreamId,
+        entries_read: Option<u64>,
+    ) -> bool {
+        if self.groups.contains_key(name) {
+            return false;
+        }
+
+        self.groups
+            .insert(name.to_owned(), ConsumerGroup::new(last_id, entries_read));
+        true
+    }
+
+    pub fn destroy_group(&mut self, name: &str) -> bool {
+        self.groups.remove(name).is_some()
+    }
+
+    /// Moves the last delivered ID of a group forward to `id`, counting the entry as read.
+    pub fn advance_group(&mut self, name: &str, id: StreamId) {
+        let tombstones = self.has_tombstones(id);
+        let estimate = self.estimate_entries_read(id);
+        let entries_added = self.entries_added;
+
+        let Some(group) = self.groups.get_mut(name) else {
+            return;
+        };
+        if id <= group.last_id {
+            return;
+        }
+
+        group.entries_read = match group.entries_read {
+            Some(read) if !tombstones => Some(read + 1),
+            _ if entries_added > 0 => estimate,
+            read => read,
+        };
+        group.last_id = id;
+    }
+
+    /// Number of entries added to the stream that the group has yet to read, if it can be
+    /// known.
+    pub fn lag(&self, group: &ConsumerGroup) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+
+        let read = match group.entries_read {
+            Some(read) if !self.has_tombstones(group.last_id) => Some(read),
+            _ => self.estimate_entries_read(group.last_id),
+        };
+        read.map(|read| self.entries_added.saturating_sub(read))
+    }
+
+    /// Whether entries were deleted after `start`. A group read counter can't be kept up to
+    /// date across such gaps.
+    fn has_tombstones(&self, start: StreamId) -> bool {
+        self.len > 0 && self.max_deleted_id != StreamId::MIN && self.max_deleted_id >= start
+    }
+
+    /// Estimates how many entries were ever added up to `id`, which is only possible when no
+    /// entry was deleted in the middle of the stream.
+    fn estimate_entries_read(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        if self.len == 0 && id <= self.last_id {
+            return Some(self.entries_added);
+        }
+
+        match id.cmp(&self.last_id) {
+            Ordering::Equal => return Some(self.entries_added),
+            Ordering::Greater => return None,
+            Ordering::Less => {}
+        }
+
+        let first = self.first_id();
+        let trimmed = self.entries_added - self.len as u64;
+        if self.max_deleted_id == StreamId::MIN || self.max_deleted_id < first {
+            match id.cmp(&first) {
+                Ordering::Less => return Some(trimmed),
+                Ordering::Equal => return Some(trimmed + 1),
+                Ordering::Greater => {}
+            }
+        }
+
+        None
+    }
+}
+
+/// An entry delivered to a consumer of a group but not acknowledged yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEntry {
+    pub consumer: String,
+    /// Unix time of the last delivery, in milliseconds.
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Consumer {
+    /// Unix time of the last interaction, in milliseconds.
+    pub seen_time: u64,
+    /// Unix time of the last successful read or claim, in milliseconds.
+    pub active_time: Option<u64>,
+    pending: BTreeSet<StreamId>,
+}
+
+impl Consumer {
+    /// IDs of the entries pending for this consumer.
+    pub fn pending(&self) -> &BTreeSet<StreamId> {
+        &self.pending
+    }
+}
+
+/// A consumer group tracks the last entry delivered to its consumers and the pending entries
+/// list (PEL) of entries they have yet to acknowledge. Every pending entry is also indexed in
+/// the PEL of the consumer that owns it.
+#[derive(Debug, Clone)]
+pub struct ConsumerGroup {
+    pub last_id: StreamId,
+    /// Entries read by the group since the stream was created, if it can be known.
+    pub entries_read: Option<u64>,
+    pending: BTreeMap<StreamId, PendingEntry>,
+    consumers: BTreeMap<String, Consumer>,
+}
+
+impl ConsumerGroup {
+    fn new(last_id: StreamId, entries_read: Option<u64>) -> Self {
+        ConsumerGroup {
+            last_id,
+            entries_read,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        }
+    }
+
+    pub fn pending(&self) -> &BTreeMap<StreamId, PendingEntry> {
+        &self.pending
+    }
+
+    pub fn consumers(&self) -> &BTreeMap<String, Consumer> {
+        &self.consumers
+    }
+
+    /// Creates a consumer unless it exists, returning whether it was created.
+    pub fn create_consumer(&mut self, name: &str, now: u64) -> bool {
+        if self.consumers.contains_key(name) {
+            return false;
+        }
+
+        let consumer = Consumer {
+            seen_time: now,
+            ..Consumer::default()
+        };
+        self.consumers.insert(name.to_owned(), consumer);
+        true
+    }
+
+    /// Records an interaction with a consumer, which must exist. Active consumers also
+    /// read or claimed entries.
+    pub fn touch_consumer(&mut self, name: &str, now: u64, active: bool) {
+        if let Some(consumer) = self.consumers.get_mut(name) {
+            consumer.seen_time = now;
+            if active {
+                consumer.active_time = Some(now);
+            }
+        }
+    }
+
+    /// Deletes a consumer along with its pending entries, returning how many it had.
+    pub fn delete_consumer(&mut self, name: &str) -> Option<usize> {
+        let consumer = self.consumers.remove(name)?;
+        for id in &consumer.pending {
+            self.pending.remove(id);
+        }
+        Some(consumer.pending.len())
+    }
+
+    /// Makes `consumer`, which must exist, the owner of the pending entry `id`, creating the
+    /// entry if needed.
+    pub fn assign(
+        &mut self,
+        id: StreamId,
+        consumer: &str,
+        delivery_time: u64,
+        delivery_count: u64,
+    ) {
+        let entry = PendingEntry {
+            consumer: consumer.to_owned(),
+            delivery_time,
+            delivery_count,
+        };
+
+        if let Some(old) = self.pending.insert(id, entry) {
+            if let Some(owner) = self.consumers.get_mut(&old.consumer) {
+                owner.pending.remove(&id);
+            }
+        }
+
+        self.consumers
+            .get_mut(consumer)
+            .expect("consumer exists")
+            .pending
+            .insert(id);
+    }
+
+    /// Removes `id` from the PEL, returning `false` if it was not pending.
+    pub fn ack(&mut self, id: StreamId) -> bool {
+        let Some(entry) = self.pending.remove(&id) else {
+            return false;
+        };
+
+        if let Some(owner) = self.consumers.get_mut(&entry.consumer) {
+            owner.pending.remove(&id);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Stream, StreamId, TrimStrategy, STREAM_NODE_MAX_ENTRIES};
+
+    fn fields(i: usize) -> Vec<(String, String)> {
+        vec![(String::from("n"), i.to_string())]
+    }
+
+    fn filled(n: usize) -> Stream {
+        let mut stream = Stream::new();
+        for i in 1..=n {
+            stream.append(StreamId::new(i as u64, 0), &fields(i));
+        }
+        stream
+    }
+
+    #[test]
+    fn test_append_and_range() {
+        let mut stream = filled(250);
+        stream.append(
+            StreamId::new(251, 0),
+            &vec![(String::from("other"), String::from("field"))],
+        );
+
+        assert_eq!(251, stream.len());
+        assert_eq!(3, stream.radix_tree_keys());
+
+        let range = stream.range(StreamId::new(99, 0), StreamId::new(102, 0), false, None);
+        let ids: Vec<u64> = range.iter().map(|(id, _)| id.ms).collect();
+        assert_eq!(vec![99, 100, 101, 102], ids);
+        assert_eq!(fields(101), range[2].1);
+
+        let range = stream.range(StreamId::MIN, StreamId::MAX, true, Some(2));
+        assert_eq!(StreamId::new(251, 0), range[0].0);
+        assert_eq!(
+            vec![(String::from("other"), String::from("field"))],
+            range[0].1
+        );
+        assert_eq!(StreamId::new(250, 0), range[1].0);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut stream = filled(3);
+
+        assert!(stream.remove(StreamId::new(2, 0)));
+        assert!(!stream.remove(StreamId::new(2, 0)));
+        assert_eq!(2, stream.len());
+        assert_eq!(StreamId::new(2, 0), stream.max_deleted_id());
+
+        assert!(stream.remove(StreamId::new(1, 0)));
+        assert!(stream.remove(StreamId::new(3, 0)));
+        assert_eq!(0, stream.radix_tree_keys());
+        assert_eq!(StreamId::new(3, 0), stream.last_id());
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut stream = filled(250);
+        assert_eq!(100, stream.trim(TrimStrategy::MaxLen(120), true, None));
+        assert_eq!(150, stream.len());
+
+        assert_eq!(30, stream.trim(TrimStrategy::MaxLen(120), false, None));
+        assert_eq!(120, stream.len());
+        assert_eq!(StreamId::new(131, 0), stream.first_id());
+
+        let min = StreamId::new(200 + STREAM_NODE_MAX_ENTRIES as u64 / 2, 0);
+        assert_eq!(119, stream.trim(TrimStrategy::MinId(min), false, None));
+        assert_eq!(min, stream.first_id());
+    }
+
+    #[test]
+    fn test_consumer_group_pending_entries() {
+        let mut stream = filled(3);
+        assert!(stream.create_group("g", StreamId::MIN, None));
+        assert!(!stream.create_group("g", StreamId::MIN, None));
+
+        let group = stream.group_mut("g").unwrap();
+        assert!(group.create_consumer("alice", 10));
+        assert!(group.create_consumer("bob", 10));
+        group.assign(StreamId::new(1, 0), "alice", 10, 1);
+        group.assign(StreamId::new(2, 0), "alice", 10, 1);
+
+        // Claiming moves the entry from one consumer PEL to the other.
+        group.assign(StreamId::new(2, 0), "bob", 20, 2);
+        assert_eq!(1, group.consumers()["alice"].pending().len());
+        assert_eq!("bob", group.pending()[&StreamId::new(2, 0)].consumer);
+
+        assert!(group.ack(StreamId::new(1, 0)));
+        assert!(!group.ack(StreamId::new(1, 0)));
+        assert!(group.consumers()["alice"].pending().is_empty());
+
+        assert_eq!(Some(1), group.delete_consumer("bob"));
+        assert!(group.pending().is_empty());
+    }
+
+    #[test]
+    fn test_consumer_group_lag() {
+        let mut stream = filled(5);
+        stream.create_group("g", StreamId::MIN, Some(0));
+        assert_eq!(Some(5), stream.lag(stream.group("g").unwrap()));
+
+        stream.advance_group("g", StreamId::new(1, 0));
+        stream.advance_group("g", StreamId::new(2, 0));
+        assert_eq!(Some(2), stream.group("g").unwrap().entries_read);
+        assert_eq!(Some(3), stream.lag(stream.group("g").unwrap()));
+
+        // A deletion ahead of the group makes the counter unreliable.
+        stream.remove(StreamId::new(4, 0));
+        assert_eq!(None, stream.lag(stream.group("g").unwrap()));
+
+        stream.advance_group("g", StreamId::new(3, 0));
+        assert_eq!(None, stream.group("g").unwrap().entries_read);
+        stream.advance_group("g", StreamId::new(5, 0));
+        assert_eq!(Some(5), stream.group("g").unwrap().entries_read);
+        assert_eq!(Some(0), stream.lag(stream.group("g").unwrap()));
+    }
+}
@@ -0,0 +1,118 @@
+//! String values, encoded like in Redis to save memory on small counters and short strings.
+
+/// Longest string stored inline, filling the space that the tag and a `Vec` take anyway.
+pub const EMBSTR_MAX_LEN: usize = 30;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Str {
+    /// A canonical integer, stored in place.
+    Int(i64),
+    /// A short string stored inline, without a heap allocation.
+    Embedded {
+        len: u8,
+        bytes: [u8; EMBSTR_MAX_LEN],
+    },
+    Raw(Vec<u8>),
+}
+
+impl Str {
+    pub fn new(bytes: &[u8]) -> Self {
+        let int = std::str::from_utf8(bytes)
+            .ok()
+            .filter(|s| s.len() <= 20)
+            .and_then(|s| s.parse::<i64>().ok().filter(|i| i.to_string() == s));
+
+        match (int, bytes.len()) {
+            (Some(i), _) => Str::Int(i),
+            (None, len @ 0..=EMBSTR_MAX_LEN) => {
+                let mut inline = [0; EMBSTR_MAX_LEN];
+                inline[..len].copy_from_slice(bytes);
+                Str::Embedded {
+                    len: len as u8,
+                    bytes: inline,
+                }
+            }
+            (None, _) => Str::Raw(bytes.to_vec()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Str::Int(i) => i.to_string().into_bytes(),
+            Str::Embedded { len, bytes } => bytes[..*len as usize].to_vec(),
+            Str::Raw(bytes) => bytes.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Str::Int(i) => i.to_string().len(),
+            Str::Embedded { len, .. } => *len as usize,
+            Str::Raw(bytes) => bytes.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes allocated on the heap.
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Str::Int(_) | Str::Embedded { .. } => 0,
+            Str::Raw(bytes) => bytes.capacity(),
+        }
+    }
+
+    /// The bytes of the string, converting it to the raw encoding so they can be changed in
+    /// place, like Redis does before bit operations.
+    pub fn make_raw(&mut self) -> &mut Vec<u8> {
+        if !matches!(self, Str::Raw(_)) {
+            *self = Str::Raw(self.to_bytes());
+        }
+
+        match self {
+            Str::Raw(bytes) => bytes,
+            _ => unreachable!("string was converted to raw"),
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Str::Int(_) => "int",
+            Str::Embedded { .. } => "embstr",
+            Str::Raw(_) => "raw",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Str, EMBSTR_MAX_LEN};
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(Str::Int(-42), Str::new(b"-42"));
+        assert_eq!("embstr", Str::new(b"042").encoding());
+        assert_eq!("embstr", Str::new(b"99999999999999999999").encoding());
+        assert_eq!("embstr", Str::new(&[b'x'; EMBSTR_MAX_LEN]).encoding());
+        assert_eq!("raw", Str::new(&[b'x'; EMBSTR_MAX_LEN + 1]).encoding());
+
+        for s in ["-42", "042", "", "hello", &"x".repeat(100)] {
+            let value = Str::new(s.as_bytes());
+            assert_eq!(s.as_bytes(), value.to_bytes());
+            assert_eq!(s.len(), value.len());
+        }
+
+        assert_eq!(32, std::mem::size_of::<Str>());
+    }
+
+    #[test]
+    fn test_make_raw() {
+        let mut value = Str::new(b"12");
+        value.make_raw().push(b'3');
+
+        assert_eq!("raw", value.encoding());
+        assert_eq!(b"123".to_vec(), value.to_bytes());
+    }
+}
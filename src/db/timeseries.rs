@@ -0,0 +1,667 @@
+//! Time series: samples of a float value ordered by a millisecond timestamp, stored in
+//! compressed chunks.
+//!
+//! Chunks use the Gorilla encoding: timestamps are stored as the difference between
+//! consecutive deltas, which is zero for regular intervals, and values as the XOR with the
+//! previous value, of which only the meaningful bits are kept.
+
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+/// Default size in bytes after which a chunk is closed and a new one started.
+pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// What to do when a sample is added with the timestamp of an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    Block,
+    First,
+    Last,
+    Min,
+    Max,
+    Sum,
+}
+
+impl DuplicatePolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "block" => Some(DuplicatePolicy::Block),
+            "first" => Some(DuplicatePolicy::First),
+            "last" => Some(DuplicatePolicy::Last),
+            "min" => Some(DuplicatePolicy::Min),
+            "max" => Some(DuplicatePolicy::Max),
+            "sum" => Some(DuplicatePolicy::Sum),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DuplicatePolicy::Block => "block",
+            DuplicatePolicy::First => "first",
+            DuplicatePolicy::Last => "last",
+            DuplicatePolicy::Min => "min",
+            DuplicatePolicy::Max => "max",
+            DuplicatePolicy::Sum => "sum",
+        }
+    }
+
+    /// Returns the value to keep, or `None` if the update is refused.
+    fn resolve(&self, old: f64, new: f64) -> Option<f64> {
+        match self {
+            DuplicatePolicy::Block => None,
+            DuplicatePolicy::First => Some(old),
+            DuplicatePolicy::Last => Some(new),
+            DuplicatePolicy::Min => Some(old.min(new)),
+            DuplicatePolicy::Max => Some(old.max(new)),
+            DuplicatePolicy::Sum => Some(old + new),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    Avg,
+    Sum,
+    Min,
+    Max,
+    Count,
+    First,
+    Last,
+}
+
+impl Aggregation {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "avg" => Some(Aggregation::Avg),
+            "sum" => Some(Aggregation::Sum),
+            "min" => Some(Aggregation::Min),
+            "max" => Some(Aggregation::Max),
+            "count" => Some(Aggregation::Count),
+            "first" => Some(Aggregation::First),
+            "last" => Some(Aggregation::Last),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregation::Avg => "avg",
+            Aggregation::Sum => "sum",
+            Aggregation::Min => "min",
+            Aggregation::Max => "max",
+            Aggregation::Count => "count",
+            Aggregation::First => "first",
+            Aggregation::Last => "last",
+        }
+    }
+
+    /// Aggregates the values of a bucket, which must not be empty.
+    pub fn apply(&self, values: &[f64]) -> f64 {
+        match self {
+            Aggregation::Avg => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregation::Sum => values.iter().sum(),
+            Aggregation::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregation::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregation::Count => values.len() as f64,
+            Aggregation::First => values[0],
+            Aggregation::Last => values[values.len() - 1],
+        }
+    }
+}
+
+/// Returns the start of the bucket of `duration` milliseconds containing `ts`, where buckets
+/// start at `align` and every multiple of `duration` away from it.
+pub fn bucket_start(ts: i64, duration: i64, align: i64) -> i64 {
+    ts - (ts - align).rem_euclid(duration)
+}
+
+/// Aggregates samples ordered by timestamp into buckets, each reported at its start.
+pub fn aggregate(
+    samples: &[(i64, f64)],
+    aggregation: Aggregation,
+    duration: i64,
+    align: i64,
+) -> Vec<(i64, f64)> {
+    let mut buckets = Vec::new();
+    let mut values = Vec::new();
+    let mut current = None;
+
+    for &(ts, value) in samples {
+        let start = bucket_start(ts, duration, align);
+        if let Some(previous) = current.filter(|c| *c != start) {
+            buckets.push((previous, aggregation.apply(&values)));
+            values.clear();
+        }
+        current = Some(start);
+        values.push(value);
+    }
+
+    if let Some(start) = current {
+        buckets.push((start, aggregation.apply(&values)));
+    }
+
+    buckets
+}
+
+/// Combines the samples of several series into one, with a sample for every timestamp that
+/// any of them has.
+pub fn reduce(series: &[Vec<(i64, f64)>], reducer: Aggregation) -> Vec<(i64, f64)> {
+    let mut values: BTreeMap<i64, Vec<f64>> = BTreeMap::new();
+    for &(ts, value) in series.iter().flatten() {
+        values.entry(ts).or_default().push(value);
+    }
+
+    values
+        .into_iter()
+        .map(|(ts, values)| (ts, reducer.apply(&values)))
+        .collect()
+}
+
+/// Writes bits from the most significant one.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if value >> i & 1 == 1 {
+                self.bytes[self.len / 8] |= 0x80 >> (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: u32) -> u64 {
+        (0..bits).fold(0, |value, _| {
+            let bit = self.bytes[self.pos / 8] >> (7 - self.pos % 8) & 1;
+            self.pos += 1;
+            (value << 1) | bit as u64
+        })
+    }
+
+    fn bit(&mut self) -> bool {
+        self.read(1) == 1
+    }
+}
+
+/// Delta of deltas are stored with a prefix of ones telling their width.
+const DOD_WIDTHS: [u32; 4] = [7, 9, 12, 32];
+
+fn fits(value: i64, bits: u32) -> bool {
+    (-(1 << (bits - 1))..(1 << (bits - 1))).contains(&value)
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/// A run of samples in the Gorilla encoding, which can only be appended to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    bits: BitWriter,
+    count: usize,
+    first: i64,
+    last: i64,
+    last_delta: i64,
+    last_value: u64,
+    /// Leading and trailing zeros of the last value XOR that was stored with its bits.
+    window: Option<(u32, u32)>,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Chunk {
+            bits: BitWriter::default(),
+            count: 0,
+            first: 0,
+            last: 0,
+            last_delta: 0,
+            last_value: 0,
+            window: None,
+        }
+    }
+
+    /// Encodes samples ordered by timestamp into chunks of about `size` bytes.
+    fn encode(samples: &[(i64, f64)], size: usize) -> Vec<Chunk> {
+        let mut chunks = vec![Chunk::new()];
+        for &(ts, value) in samples {
+            let chunk = chunks.last_mut().expect("at least one chunk");
+            if chunk.size() >= size {
+                chunks.push(Chunk::new());
+            }
+            chunks
+                .last_mut()
+                .expect("at least one chunk")
+                .append(ts, value);
+        }
+        chunks
+    }
+
+    pub fn first_timestamp(&self) -> i64 {
+        self.first
+    }
+
+    pub fn last_timestamp(&self) -> i64 {
+        self.last
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The encoded samples, whose last byte may be partially used.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bits.bytes
+    }
+
+    pub fn size(&self) -> usize {
+        self.bits.bytes.len()
+    }
+
+    /// Appends a sample, whose timestamp must be after the last one.
+    fn append(&mut self, ts: i64, value: f64) {
+        let value = value.to_bits();
+        if self.count == 0 {
+            self.bits.write(ts as u64, 64);
+            self.bits.write(value, 64);
+            self.first = ts;
+        } else {
+            let delta = ts - self.last;
+            self.write_dod(delta - self.last_delta);
+            self.write_xor(value ^ self.last_value);
+            self.last_delta = delta;
+        }
+
+        self.last = ts;
+        self.last_value = value;
+        self.count += 1;
+    }
+
+    fn write_dod(&mut self, dod: i64) {
+        if dod == 0 {
+            self.bits.write(0, 1);
+            return;
+        }
+
+        for (i, &width) in DOD_WIDTHS.iter().enumerate() {
+            if fits(dod, width) {
+                // `i + 1` ones followed by a zero.
+                self.bits.write((1 << (i + 2)) - 2, i as u32 + 2);
+                self.bits.write(dod as u64 & ((1 << width) - 1), width);
+                return;
+            }
+        }
+
+        self.bits.write(0x1F, 5);
+        self.bits.write(dod as u64, 64);
+    }
+
+    fn write_xor(&mut self, xor: u64) {
+        if xor == 0 {
+            self.bits.write(0, 1);
+            return;
+        }
+
+        let (leading, trailing) = (xor.leading_zeros(), xor.trailing_zeros());
+        match self.window {
+            // The meaningful bits fit in those of the previous value.
+            Some((l, t)) if leading >= l && trailing >= t => {
+                self.bits.write(0b10, 2);
+                self.bits.write(xor >> t, 64 - l - t);
+            }
+            _ => {
+                let len = 64 - leading - trailing;
+                self.bits.write(0b11, 2);
+                self.bits.write(leading as u64, 6);
+                self.bits.write(len as u64 - 1, 6);
+                self.bits.write(xor >> trailing, len);
+                self.window = Some((leading, trailing));
+            }
+        }
+    }
+
+    pub fn samples(&self) -> Vec<(i64, f64)> {
+        let mut reader = BitReader {
+            bytes: &self.bits.bytes,
+            pos: 0,
+        };
+        let mut samples = Vec::with_capacity(self.count);
+        if self.count == 0 {
+            return samples;
+        }
+
+        let mut ts = reader.read(64) as i64;
+        let mut value = reader.read(64);
+        let mut delta = 0;
+        let mut window = (0, 0);
+        samples.push((ts, f64::from_bits(value)));
+
+        for _ in 1..self.count {
+            let ones = (0..5).take_while(|_| reader.bit()).count();
+            let dod = match ones {
+                0 => 0,
+                5 => reader.read(64) as i64,
+                n => {
+                    let width = DOD_WIDTHS[n - 1];
+                    sign_extend(reader.read(width), width)
+                }
+            };
+            delta += dod;
+            ts += delta;
+
+            if reader.bit() {
+                if reader.bit() {
+                    let leading = reader.read(6) as u32;
+                    let len = reader.read(6) as u32 + 1;
+                    window = (leading, 64 - leading - len);
+                }
+                let (l, t) = window;
+                value ^= reader.read(64 - l - t) << t;
+            }
+
+            samples.push((ts, f64::from_bits(value)));
+        }
+
+        samples
+    }
+}
+
+/// A rule that downsamples a series into another one by aggregating buckets of samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub destination: String,
+    pub aggregation: Aggregation,
+    pub duration: i64,
+    pub align: i64,
+    /// Start of the bucket being filled, which is written to the destination once a sample
+    /// arrives in a later bucket.
+    pub open: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A sample with the same timestamp exists and the policy is `BLOCK`.
+    Duplicate,
+    /// The sample is older than the retention period allows.
+    TooOld,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSeries {
+    chunks: Vec<Chunk>,
+    /// How long samples are kept, in milliseconds before the last one, where 0 keeps them
+    /// forever.
+    pub retention: i64,
+    pub chunk_size: usize,
+    pub duplicate_policy: DuplicatePolicy,
+    pub labels: Vec<(String, String)>,
+    pub rules: Vec<Rule>,
+    /// The series this one is a compaction of.
+    pub source: Option<String>,
+}
+
+impl TimeSeries {
+    pub fn new(retention: i64, duplicate_policy: DuplicatePolicy) -> Self {
+        TimeSeries {
+            chunks: Vec::new(),
+            retention,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            duplicate_policy,
+            labels: Vec::new(),
+            rules: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn last_timestamp(&self) -> Option<i64> {
+        self.chunks.last().map(|chunk| chunk.last)
+    }
+
+    pub fn label(&self, name: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Oldest timestamp still within the retention period.
+    fn cutoff(&self) -> Option<i64> {
+        match self.retention {
+            0 => None,
+            retention => self.last_timestamp().map(|last| last - retention),
+        }
+    }
+
+    /// Adds a sample, resolving a duplicate timestamp with `policy` or the policy of the
+    /// series.
+    pub fn add(
+        &mut self,
+        ts: i64,
+        value: f64,
+        policy: Option<DuplicatePolicy>,
+    ) -> Result<(), Error> {
+        if self.cutoff().is_some_and(|cutoff| ts < cutoff) {
+            return Err(Error::TooOld);
+        }
+
+        match self.chunks.last_mut() {
+            Some(chunk) if ts > chunk.last => {
+                if chunk.size() >= self.chunk_size {
+                    self.chunks.push(Chunk::new());
+                }
+            }
+            None => self.chunks.push(Chunk::new()),
+            Some(_) => return self.upsert(ts, value, policy),
+        }
+
+        self.chunks
+            .last_mut()
+            .expect("at least one chunk")
+            .append(ts, value);
+        self.trim();
+        Ok(())
+    }
+
+    /// Inserts or updates a sample that is not after the last one, re-encoding its chunk.
+    fn upsert(
+        &mut self,
+        ts: i64,
+        value: f64,
+        policy: Option<DuplicatePolicy>,
+    ) -> Result<(), Error> {
+        let index = self
+            .chunks
+            .iter()
+            .rposition(|chunk| chunk.first <= ts)
+            .unwrap_or(0);
+        let mut samples = self.chunks[index].samples();
+
+        match samples.binary_search_by_key(&ts, |(t, _)| *t) {
+            Ok(i) => {
+                let policy = policy.unwrap_or(self.duplicate_policy);
+                samples[i].1 = policy
+                    .resolve(samples[i].1, value)
+                    .ok_or(Error::Duplicate)?;
+            }
+            Err(i) => samples.insert(i, (ts, value)),
+        }
+
+        let chunks = Chunk::encode(&samples, self.chunk_size);
+        self.chunks.splice(index..=index, chunks);
+        Ok(())
+    }
+
+    /// Drops the chunks that only hold samples older than the retention period.
+    fn trim(&mut self) {
+        if let Some(cutoff) = self.cutoff() {
+            let expired = self
+                .chunks
+                .iter()
+                .take_while(|chunk| chunk.last < cutoff)
+                .count();
+            self.chunks.drain(..expired);
+        }
+    }
+
+    /// Samples with timestamps in `range`, in order.
+    pub fn range(&self, range: RangeInclusive<i64>) -> Vec<(i64, f64)> {
+        let from = (*range.start()).max(self.cutoff().unwrap_or(i64::MIN));
+        let to = *range.end();
+
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.last >= from && chunk.first <= to)
+            .flat_map(|chunk| chunk.samples())
+            .filter(|(ts, _)| (from..=to).contains(ts))
+            .collect()
+    }
+
+    /// Updates the compaction rules after a sample was added at `ts`, returning the buckets
+    /// to write to their destinations as `(destination, start, value)`.
+    pub fn compact(&mut self, ts: i64) -> Vec<(String, i64, f64)> {
+        let mut writes = Vec::new();
+
+        for i in 0..self.rules.len() {
+            let rule = &self.rules[i];
+            let start = bucket_start(ts, rule.duration, rule.align);
+
+            let closed = match rule.open {
+                // A late sample changes a bucket that was already written.
+                Some(open) if start < open => Some(start),
+                Some(open) if start > open => Some(open),
+                _ => None,
+            };
+
+            if let Some(bucket) = closed {
+                let samples = self.range(bucket..=bucket + rule.duration - 1);
+                if let Some(&(_, value)) =
+                    aggregate(&samples, rule.aggregation, rule.duration, rule.align).first()
+                {
+                    writes.push((rule.destination.clone(), bucket, value));
+                }
+            }
+
+            let rule = &mut self.rules[i];
+            rule.open = Some(rule.open.map_or(start, |open| open.max(start)));
+        }
+
+        writes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{aggregate, reduce, Aggregation, Chunk, DuplicatePolicy, Error, Rule, TimeSeries};
+
+    #[test]
+    fn test_chunk_encoding() {
+        let samples: Vec<_> = (0..1000)
+            .map(|i| (1_000 + i * 10 + (i % 3), (i as f64 / 7.0).sin()))
+            .chain([(100_000, 0.0), (100_001, f64::INFINITY), (1 << 40, -1.5)])
+            .collect();
+
+        let chunks = Chunk::encode(&samples, 4096);
+        let decoded: Vec<_> = chunks.iter().flat_map(|c| c.samples()).collect();
+        assert_eq!(samples, decoded);
+
+        // Regular samples with a constant value take a couple of bits each.
+        let regular: Vec<_> = (0..1000).map(|i| (i * 1000, 42.0)).collect();
+        let chunks = Chunk::encode(&regular, 4096);
+        assert_eq!(1, chunks.len());
+        assert!(chunks[0].size() < 300);
+    }
+
+    #[test]
+    fn test_add_duplicates_and_retention() {
+        let mut ts = TimeSeries::new(0, DuplicatePolicy::Block);
+        ts.add(10, 1.0, None).unwrap();
+        ts.add(30, 3.0, None).unwrap();
+        ts.add(20, 2.0, None).unwrap();
+        assert_eq!(Err(Error::Duplicate), ts.add(20, 5.0, None));
+        ts.add(20, 5.0, Some(DuplicatePolicy::Sum)).unwrap();
+        assert_eq!(vec![(10, 1.0), (20, 7.0), (30, 3.0)], ts.range(0..=100));
+
+        let mut ts = TimeSeries::new(100, DuplicatePolicy::Last);
+        ts.chunk_size = 48;
+        for i in 0..100 {
+            ts.add(i * 10, i as f64, None).unwrap();
+        }
+        assert_eq!(Err(Error::TooOld), ts.add(100, 0.0, None));
+        assert_eq!(11, ts.range(0..=i64::MAX).len());
+        assert!(ts.len() < 100);
+    }
+
+    #[test]
+    fn test_aggregate_and_compact() {
+        let samples = [(1, 1.0), (5, 3.0), (10, 5.0), (19, 7.0), (25, 9.0)];
+        assert_eq!(
+            vec![(0, 2.0), (10, 6.0), (20, 9.0)],
+            aggregate(&samples, Aggregation::Avg, 10, 0)
+        );
+        assert_eq!(
+            vec![(-5, 1.0), (5, 2.0), (15, 1.0), (25, 1.0)],
+            aggregate(&samples, Aggregation::Count, 10, 5)
+        );
+
+        let series = [vec![(1, 1.0), (2, 4.0)], vec![(2, 2.0), (3, 3.0)]];
+        assert_eq!(
+            vec![(1, 1.0), (2, 6.0), (3, 3.0)],
+            reduce(&series, Aggregation::Sum)
+        );
+        assert_eq!(
+            vec![(1, 1.0), (2, 2.0), (3, 3.0)],
+            reduce(&series, Aggregation::Min)
+        );
+
+        let mut ts = TimeSeries::new(0, DuplicatePolicy::Last);
+        ts.rules.push(Rule {
+            destination: String::from("dest"),
+            aggregation: Aggregation::Sum,
+            duration: 10,
+            align: 0,
+            open: None,
+        });
+
+        let mut writes = Vec::new();
+        for (t, v) in samples {
+            ts.add(t, v, None).unwrap();
+            writes.extend(ts.compact(t));
+        }
+        let dest = |start, value| (String::from("dest"), start, value);
+        assert_eq!(vec![dest(0, 4.0), dest(10, 12.0)], writes);
+
+        // A late sample rewrites its bucket.
+        ts.add(2, 1.0, None).unwrap();
+        assert_eq!(vec![dest(0, 5.0)], ts.compact(2));
+    }
+}
@@ -0,0 +1,746 @@
+//! Vector sets: named elements with a vector of fixed dimension and optional JSON
+//! attributes, searched by similarity.
+//!
+//! Small sets are searched exhaustively. Larger ones use an HNSW graph: each element is
+//! linked to its nearest neighbors on layer 0 and on the sparser layers above it, up to a
+//! level drawn from the hash of its name so that replicas build the same layers.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use crate::util::hash::murmurhash64a;
+
+/// Default number of links per element on the layers above 0, which get twice as many.
+pub const DEFAULT_M: usize = 16;
+pub const DEFAULT_EF_CONSTRUCTION: usize = 200;
+/// Sets with at most this many elements are searched exhaustively.
+pub const BRUTE_FORCE_MAX: usize = 1000;
+
+const MAX_LEVEL: usize = 16;
+const LEVEL_SEED: u64 = 0x5eed_1e7e;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Vectors are normalized, and similarity is the cosine of their angle.
+    Cosine,
+    /// Euclidean distance.
+    L2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantization {
+    /// Full 32-bit floats.
+    None,
+    /// 8-bit integers with a scale per vector, for a quarter of the memory.
+    Q8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stored {
+    F32(Vec<f32>),
+    Q8 { values: Vec<i8>, scale: f32 },
+}
+
+impl Stored {
+    fn new(vector: &[f32], quantization: Quantization) -> Self {
+        match quantization {
+            Quantization::None => Stored::F32(vector.to_vec()),
+            Quantization::Q8 => {
+                let max = vector.iter().fold(0f32, |max, x| max.max(x.abs()));
+                let scale = if max == 0.0 { 1.0 } else { max / 127.0 };
+                Stored::Q8 {
+                    values: vector.iter().map(|x| (x / scale).round() as i8).collect(),
+                    scale,
+                }
+            }
+        }
+    }
+
+    fn to_vec(&self) -> Vec<f32> {
+        match self {
+            Stored::F32(v) => v.clone(),
+            Stored::Q8 { values, scale } => values.iter().map(|&x| x as f32 * scale).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    element: String,
+    vector: Stored,
+    /// Norm of the vector before it was normalized for the cosine metric, to return it as it
+    /// was added.
+    norm: f32,
+    attributes: Option<String>,
+    /// Neighbors on each layer, from layer 0 to the level of the node.
+    links: Vec<Vec<usize>>,
+    /// Nodes that have this one among their neighbors on each layer, so that removing it
+    /// only visits them.
+    backlinks: Vec<Vec<usize>>,
+}
+
+/// A node id with its distance to a query, ordered by distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate(f32, usize);
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// Decides whether an element is a search result from its attributes.
+pub type Filter<'a> = &'a dyn Fn(Option<&str>) -> bool;
+
+/// How to search a vector set.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchOptions {
+    pub count: usize,
+    /// Size of the candidate list of the graph search.
+    pub ef: usize,
+    /// Size of the candidate list of the graph search when filtering, which has to look
+    /// further to find enough matching elements.
+    pub filter_ef: usize,
+    /// Scan every element instead of using the graph.
+    pub exact: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorSet {
+    dim: usize,
+    metric: Metric,
+    quantization: Quantization,
+    m: usize,
+    ef_construction: usize,
+    nodes: Vec<Option<Node>>,
+    free: Vec<usize>,
+    ids: HashMap<String, usize>,
+    entry: Option<usize>,
+}
+
+impl VectorSet {
+    pub fn new(dim: usize, metric: Metric, quantization: Quantization) -> Self {
+        VectorSet {
+            dim,
+            metric,
+            quantization,
+            m: DEFAULT_M,
+            ef_construction: DEFAULT_EF_CONSTRUCTION,
+            nodes: Vec::new(),
+            free: Vec::new(),
+            ids: HashMap::new(),
+            entry: None,
+        }
+    }
+
+    pub fn set_graph_options(&mut self, m: usize, ef_construction: usize) {
+        self.m = m.max(2);
+        self.ef_construction = ef_construction.max(1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    pub fn quantization(&self) -> Quantization {
+        self.quantization
+    }
+
+    pub fn m(&self) -> usize {
+        self.m
+    }
+
+    fn node(&self, id: usize) -> &Node {
+        self.nodes[id].as_ref().expect("linked nodes are live")
+    }
+
+    fn node_mut(&mut self, id: usize) -> &mut Node {
+        self.nodes[id].as_mut().expect("linked nodes are live")
+    }
+
+    fn level(&self, id: usize) -> usize {
+        self.node(id).links.len() - 1
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        match layer {
+            0 => self.m * 2,
+            _ => self.m,
+        }
+    }
+
+    /// Prepares a vector to be stored or compared, returning it with its norm.
+    fn prepare(&self, vector: &[f32]) -> (Vec<f32>, f32) {
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        match self.metric {
+            Metric::Cosine if norm > 0.0 => (vector.iter().map(|x| x / norm).collect(), norm),
+            _ => (vector.to_vec(), norm),
+        }
+    }
+
+    /// Distance from a stored vector to a prepared query, where smaller is closer.
+    fn distance(&self, stored: &Stored, query: &[f32]) -> f32 {
+        match (self.metric, stored) {
+            (Metric::Cosine, Stored::F32(v)) => {
+                1.0 - v.iter().zip(query).map(|(a, b)| a * b).sum::<f32>()
+            }
+            (Metric::Cosine, Stored::Q8 { values, scale }) => {
+                1.0 - values
+                    .iter()
+                    .zip(query)
+                    .map(|(&a, b)| a as f32 * b)
+                    .sum::<f32>()
+                    * scale
+            }
+            (Metric::L2, Stored::F32(v)) => v.iter().zip(query).map(|(a, b)| (a - b).powi(2)).sum(),
+            (Metric::L2, Stored::Q8 { values, scale }) => values
+                .iter()
+                .zip(query)
+                .map(|(&a, b)| (a as f32 * scale - b).powi(2))
+                .sum(),
+        }
+    }
+
+    /// Converts a distance to the score reported to clients: a similarity from 0 to 1 for
+    /// the cosine metric, where 1 is the same direction, or the Euclidean distance.
+    pub fn score(&self, distance: f32) -> f64 {
+        match self.metric {
+            Metric::Cosine => (1.0 - distance as f64 / 2.0).clamp(0.0, 1.0),
+            Metric::L2 => (distance as f64).sqrt(),
+        }
+    }
+
+    /// Bytes allocated for the elements, their vectors and the graph.
+    pub fn heap_size(&self) -> usize {
+        let nodes: usize = self
+            .nodes
+            .iter()
+            .flatten()
+            .map(|node| {
+                let vector = match &node.vector {
+                    Stored::F32(v) => v.capacity() * size_of::<f32>(),
+                    Stored::Q8 { values, .. } => values.capacity(),
+                };
+                let links: usize = node
+                    .links
+                    .iter()
+                    .chain(&node.backlinks)
+                    .map(|l| size_of::<Vec<usize>>() + l.capacity() * size_of::<usize>())
+                    .sum();
+
+                node.element.len()
+                    + vector
+                    + node.attributes.as_ref().map_or(0, |a| a.len())
+                    + links
+            })
+            .sum();
+        let ids: usize = self
+            .ids
+            .keys()
+            .map(|e| size_of::<String>() + e.len() + size_of::<usize>())
+            .sum();
+
+        self.nodes.capacity() * size_of::<Option<Node>>() + nodes + ids
+    }
+
+    pub fn contains(&self, element: &str) -> bool {
+        self.ids.contains_key(element)
+    }
+
+    /// The vector of `element` as it was added, up to the loss of quantization.
+    pub fn embedding(&self, element: &str) -> Option<Vec<f32>> {
+        let node = self.node(*self.ids.get(element)?);
+        let vector = node.vector.to_vec();
+        Some(match self.metric {
+            Metric::Cosine => vector.iter().map(|x| x * node.norm).collect(),
+            Metric::L2 => vector,
+        })
+    }
+
+    pub fn attributes(&self, element: &str) -> Option<&str> {
+        self.node(*self.ids.get(element)?).attributes.as_deref()
+    }
+
+    pub fn set_attributes(&mut self, element: &str, attributes: Option<String>) -> bool {
+        match self.ids.get(element).copied() {
+            Some(id) => {
+                self.node_mut(id).attributes = attributes;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Elements with their vector and attributes, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Vec<f32>, Option<&str>)> {
+        self.ids.keys().map(|element| {
+            let vector = self.embedding(element).expect("element exists");
+            (element.as_str(), vector, self.attributes(element))
+        })
+    }
+
+    /// Adds `element`, or replaces its vector if it exists. Attributes are only changed when
+    /// some are given. Returns whether the element is new.
+    pub fn add(&mut self, element: &str, vector: &[f32], attributes: Option<String>) -> bool {
+        let mut attributes = attributes;
+        let existed = match self.ids.get(element).copied() {
+            Some(id) => {
+                attributes = attributes.or(self.node_mut(id).attributes.take());
+                self.remove(element);
+                true
+            }
+            None => false,
+        };
+
+        let (prepared, norm) = self.prepare(vector);
+        let level = self.random_level(element);
+        let node = Node {
+            element: element.to_owned(),
+            vector: Stored::new(&prepared, self.quantization),
+            norm,
+            attributes,
+            links: vec![Vec::new(); level + 1],
+            backlinks: vec![Vec::new(); level + 1],
+        };
+
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = Some(node);
+                id
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        self.ids.insert(element.to_owned(), id);
+
+        // Link with the stored vector, as searches compare against it.
+        let stored = self.node(id).vector.to_vec();
+        self.link(id, &stored, level);
+        !existed
+    }
+
+    /// Draws a level with an exponentially decaying probability, from the hash of `element`.
+    fn random_level(&self, element: &str) -> usize {
+        let hash = murmurhash64a(element.as_bytes(), LEVEL_SEED);
+        let uniform = ((hash >> 11) as f64 / (1u64 << 53) as f64).max(f64::MIN_POSITIVE);
+        let ml = 1.0 / (self.m as f64).ln();
+        ((-uniform.ln() * ml) as usize).min(MAX_LEVEL)
+    }
+
+    fn link(&mut self, id: usize, vector: &[f32], level: usize) {
+        let Some(entry) = self.entry.filter(|&e| e != id) else {
+            self.entry = Some(id);
+            return;
+        };
+
+        let top = self.level(entry);
+        let mut ep = Candidate(self.distance(&self.node(entry).vector, vector), entry);
+        for layer in (level + 1..=top).rev() {
+            ep = self.greedy(vector, ep, layer);
+        }
+
+        for layer in (0..=level.min(top)).rev() {
+            let candidates = self.search_layer(vector, &[ep], self.ef_construction, layer);
+            let neighbors = self.select(&candidates, self.max_links(layer));
+            self.set_links(id, layer, neighbors.clone());
+
+            for n in neighbors {
+                self.add_link(n, id, layer);
+                self.prune(n, layer);
+            }
+            ep = candidates[0];
+        }
+
+        if level > top {
+            self.entry = Some(id);
+        }
+    }
+
+    /// Keeps the closest links of `id` on `layer` if it has too many.
+    fn prune(&mut self, id: usize, layer: usize) {
+        if self.node(id).links[layer].len() <= self.max_links(layer) {
+            return;
+        }
+
+        let vector = self.node(id).vector.to_vec();
+        let mut candidates: Vec<_> = self.node(id).links[layer]
+            .iter()
+            .map(|&n| Candidate(self.distance(&self.node(n).vector, &vector), n))
+            .collect();
+        candidates.sort();
+        let links = self.select(&candidates, self.max_links(layer));
+        self.set_links(id, layer, links);
+    }
+
+    fn add_link(&mut self, from: usize, to: usize, layer: usize) {
+        self.node_mut(from).links[layer].push(to);
+        self.node_mut(to).backlinks[layer].push(from);
+    }
+
+    /// Replaces the neighbors of `id` on `layer`, keeping their backlinks in step.
+    fn set_links(&mut self, id: usize, layer: usize, links: Vec<usize>) {
+        let old = std::mem::replace(&mut self.node_mut(id).links[layer], links);
+
+        for n in old {
+            let backlinks = &mut self.node_mut(n).backlinks[layer];
+            if let Some(i) = backlinks.iter().position(|&b| b == id) {
+                backlinks.swap_remove(i);
+            }
+        }
+        for i in 0..self.node(id).links[layer].len() {
+            let n = self.node(id).links[layer][i];
+            self.node_mut(n).backlinks[layer].push(id);
+        }
+    }
+
+    /// Picks up to `max` neighbors among candidates sorted by distance, preferring those that
+    /// are closer to the query than to the neighbors already picked, so that links point in
+    /// different directions. The closest remaining ones fill the rest.
+    fn select(&self, candidates: &[Candidate], max: usize) -> Vec<usize> {
+        let mut selected: Vec<Candidate> = Vec::new();
+        let mut skipped = Vec::new();
+
+        for &c in candidates {
+            if selected.len() >= max {
+                break;
+            }
+            let vector = self.node(c.1).vector.to_vec();
+            let diverse = selected
+                .iter()
+                .all(|s| self.distance(&self.node(s.1).vector, &vector) > c.0);
+            match diverse {
+                true => selected.push(c),
+                false => skipped.push(c),
+            }
+        }
+
+        let missing = max.saturating_sub(selected.len());
+        selected.extend(skipped.into_iter().take(missing));
+        selected.into_iter().map(|c| c.1).collect()
+    }
+
+    /// Moves to the closest neighbor on `layer` until none is closer.
+    fn greedy(&self, query: &[f32], mut current: Candidate, layer: usize) -> Candidate {
+        loop {
+            let closest = self.node(current.1).links[layer]
+                .iter()
+                .map(|&n| Candidate(self.distance(&self.node(n).vector, query), n))
+                .min();
+            match closest {
+                Some(c) if c < current => current = c,
+                _ => return current,
+            }
+        }
+    }
+
+    /// Returns the `ef` closest nodes found on `layer` from the entry points, sorted by
+    /// distance.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[Candidate],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entries.iter().map(|c| c.1).collect();
+        let mut candidates: BinaryHeap<Reverse<Candidate>> =
+            entries.iter().copied().map(Reverse).collect();
+        let mut results: BinaryHeap<Candidate> = entries.iter().copied().collect();
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            let furthest = results.peek().expect("results are never empty");
+            if current.0 > furthest.0 && results.len() >= ef {
+                break;
+            }
+
+            for &n in &self.node(current.1).links[layer] {
+                if !visited.insert(n) {
+                    continue;
+                }
+
+                let c = Candidate(self.distance(&self.node(n).vector, query), n);
+                let furthest = results.peek().expect("results are never empty");
+                if results.len() < ef || c.0 < furthest.0 {
+                    candidates.push(Reverse(c));
+                    results.push(c);
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        results.into_sorted_vec()
+    }
+
+    /// Removes `element` and reconnects the nodes that linked to it with its neighbors.
+    pub fn remove(&mut self, element: &str) -> bool {
+        let Some(id) = self.ids.remove(element) else {
+            return false;
+        };
+        let node = self.nodes[id].take().expect("element is live");
+        self.free.push(id);
+
+        for (layer, links) in node.links.iter().enumerate() {
+            for &n in links {
+                self.node_mut(n).backlinks[layer].retain(|&b| b != id);
+            }
+        }
+
+        for (layer, backlinks) in node.backlinks.iter().enumerate() {
+            for &n in backlinks {
+                self.node_mut(n).links[layer].retain(|&l| l != id);
+                for &l in &node.links[layer] {
+                    if l != n && !self.node(n).links[layer].contains(&l) {
+                        self.add_link(n, l, layer);
+                    }
+                }
+                self.prune(n, layer);
+            }
+        }
+
+        if self.entry == Some(id) {
+            // A neighbor on the top layer is as high as the entry was. Only when there is
+            // none are all the nodes looked at.
+            let top = node.links.len() - 1;
+            self.entry = node.links[top].first().copied().or_else(|| {
+                (0..self.nodes.len())
+                    .filter(|&n| self.nodes[n].is_some())
+                    .max_by_key(|&n| self.level(n))
+            });
+        }
+
+        true
+    }
+
+    /// Finds the elements closest to `query` among those accepted by `filter`, with their
+    /// distance.
+    pub fn search(
+        &self,
+        query: &[f32],
+        options: SearchOptions,
+        filter: Option<Filter>,
+    ) -> Vec<(&str, f32)> {
+        let (query, _) = self.prepare(query);
+        let accept = |id: usize| match filter {
+            Some(filter) => filter(self.node(id).attributes.as_deref()),
+            None => true,
+        };
+
+        let mut found = match (self.entry, options.exact || self.len() <= BRUTE_FORCE_MAX) {
+            (None, _) => Vec::new(),
+            (Some(_), true) => {
+                let mut all: Vec<_> = self
+                    .ids
+                    .values()
+                    .filter(|&&id| accept(id))
+                    .map(|&id| Candidate(self.distance(&self.node(id).vector, &query), id))
+                    .collect();
+                all.sort();
+                all
+            }
+            (Some(entry), false) => {
+                let mut ep = Candidate(self.distance(&self.node(entry).vector, &query), entry);
+                for layer in (1..=self.level(entry)).rev() {
+                    ep = self.greedy(&query, ep, layer);
+                }
+
+                let ef = match filter {
+                    Some(_) => options.filter_ef.max(options.ef),
+                    None => options.ef,
+                };
+                let mut found = self.search_layer(&query, &[ep], ef.max(options.count), 0);
+                found.retain(|c| accept(c.1));
+                found
+            }
+        };
+
+        found.truncate(options.count);
+        found
+            .into_iter()
+            .map(|c| (self.node(c.1).element.as_str(), c.0))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Metric, Quantization, SearchOptions, VectorSet};
+    use crate::util::hash::murmurhash64a;
+
+    /// Deterministic pseudo-random vectors.
+    fn vector(i: usize, dim: usize) -> Vec<f32> {
+        (0..dim)
+            .map(|d| {
+                let h = murmurhash64a(format!("{i}:{d}").as_bytes(), 0);
+                (h % 2000) as f32 / 1000.0 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_add_remove() {
+        let mut set = VectorSet::new(2, Metric::Cosine, Quantization::None);
+        assert!(set.add("a", &[3.0, 4.0], Some(String::from("{}"))));
+        assert!(set.add("b", &[1.0, 0.0], None));
+        assert!(!set.add("a", &[6.0, 8.0], None));
+        assert_eq!(2, set.len());
+        assert_eq!(Some(vec![6.0, 8.0]), set.embedding("a"));
+        assert_eq!(Some("{}"), set.attributes("a"));
+
+        let options = SearchOptions {
+            count: 10,
+            ef: 10,
+            filter_ef: 10,
+            exact: false,
+        };
+        let found = set.search(&[0.0, 1.0], options, None);
+        assert_eq!("a", found[0].0);
+        assert!((set.score(found[0].1) - 0.9).abs() < 1e-6);
+
+        assert!(set.remove("a"));
+        assert!(!set.remove("a"));
+        assert_eq!(
+            vec!["b"],
+            set.search(&[0.0, 1.0], options, None)
+                .iter()
+                .map(|f| f.0)
+                .collect::<Vec<_>>()
+        );
+        assert!(set.remove("b"));
+        assert!(set.search(&[0.0, 1.0], options, None).is_empty());
+    }
+
+    #[test]
+    fn test_quantization_and_l2() {
+        let mut set = VectorSet::new(3, Metric::L2, Quantization::Q8);
+        set.add("a", &[1.0, 2.0, 3.0], None);
+        set.add("b", &[10.0, 10.0, 10.0], None);
+
+        let a = set.embedding("a").unwrap();
+        assert!(a
+            .iter()
+            .zip([1.0, 2.0, 3.0])
+            .all(|(x, y)| (x - y).abs() < 0.02));
+
+        let options = SearchOptions {
+            count: 1,
+            ef: 10,
+            filter_ef: 10,
+            exact: true,
+        };
+        let found = set.search(&[1.0, 2.0, 4.0], options, None);
+        assert_eq!("a", found[0].0);
+        assert!((set.score(found[0].1) - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_hnsw_recall() {
+        let dim = 16;
+        let mut set = VectorSet::new(dim, Metric::Cosine, Quantization::None);
+        set.set_graph_options(8, 64);
+        for i in 0..2000 {
+            let attributes = (i % 2 == 0).then(|| String::from("{}"));
+            set.add(&i.to_string(), &vector(i, dim), attributes);
+        }
+        // Removing elements must keep the graph connected.
+        for i in (0..2000).step_by(7) {
+            set.remove(&i.to_string());
+        }
+
+        let (mut hits, mut total) = (0, 0);
+        for q in 0..20 {
+            let query = vector(10_000 + q, dim);
+            let exact = SearchOptions {
+                count: 10,
+                ef: 100,
+                filter_ef: 100,
+                exact: true,
+            };
+            let approximate = SearchOptions {
+                exact: false,
+                ..exact
+            };
+
+            let truth: Vec<_> = set
+                .search(&query, exact, None)
+                .iter()
+                .map(|f| f.0)
+                .collect();
+            let found = set.search(&query, approximate, None);
+            hits += found.iter().filter(|f| truth.contains(&f.0)).count();
+            total += truth.len();
+        }
+
+        assert!(hits * 100 / total >= 90, "recall {hits}/{total}");
+
+        let even = |attributes: Option<&str>| attributes.is_some();
+        let options = SearchOptions {
+            count: 5,
+            ef: 100,
+            filter_ef: 500,
+            exact: false,
+        };
+        let found = set.search(&vector(1, dim), options, Some(&even));
+        assert_eq!(5, found.len());
+        assert!(found.iter().all(|f| f.0.parse::<usize>().unwrap() % 2 == 0));
+    }
+
+    #[test]
+    fn test_backlinks_follow_links() {
+        let dim = 8;
+        let mut set = VectorSet::new(dim, Metric::L2, Quantization::None);
+        set.set_graph_options(4, 16);
+        for i in 0..300 {
+            set.add(&i.to_string(), &vector(i, dim), None);
+        }
+        for i in (0..300).step_by(3) {
+            set.remove(&i.to_string());
+        }
+        // Replacing a vector removes and adds the element again.
+        for i in (1..300).step_by(3) {
+            set.add(&i.to_string(), &vector(1000 + i, dim), None);
+        }
+
+        for (id, node) in set.nodes.iter().enumerate() {
+            let Some(node) = node else {
+                continue;
+            };
+            for layer in 0..node.links.len() {
+                for &n in &node.links[layer] {
+                    let backlinks = &set.node(n).backlinks[layer];
+                    assert_eq!(1, backlinks.iter().filter(|&&b| b == id).count());
+                }
+                for &n in &node.backlinks[layer] {
+                    assert!(set.node(n).links[layer].contains(&id));
+                }
+            }
+        }
+        assert_eq!(200, set.len());
+        assert!(set.entry.is_some_and(|entry| set.nodes[entry].is_some()));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::db::{
+    dict::Dict,
+    skiplist::{LexRange, ScoreRange, SkipList},
+};
+
+/// A sorted set: a dictionary from member to score for O(1) lookups, plus a skiplist ordered
+/// by score for ranges and ranks.
+#[derive(Debug, Default)]
+pub struct SortedSet {
+    scores: Dict<f64>,
+    list: SkipList,
+}
+
+impl SortedSet {
+    pub fn new() -> Self {
+        SortedSet::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// Estimate of the bytes allocated for the members, which are held by both the dictionary
+    /// and the skiplist.
+    pub fn heap_size(&self) -> usize {
+        let scores: usize = self
+            .scores
+            .keys()
+            .map(|m| size_of::<String>() + m.len() + size_of::<f64>())
+            .sum();
+
+        scores + self.list.heap_size()
+    }
+
+    pub fn score(&self, member: &str) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Sets the score of `member`, returning `true` if it is a new member.
+    pub fn insert(&mut self, member: &str, score: f64) -> bool {
+        match self.scores.get_mut(member) {
+            Some(current) if *current == score => false,
+            Some(current) => {
+                self.list.remove(*current, member);
+                self.list.insert(score, member);
+                *current = score;
+                false
+            }
+            None => {
+                self.list.insert(score, member);
+                self.scores.insert(member.to_owned(), score);
+                true
+            }
+        }
+    }
+
+    /// Returns about `count` members with their scores from `cursor` on, and the cursor to
+    /// resume from, which is zero once the scan is complete.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&str, f64)>) {
+        let (cursor, batch) = self.scores.scan(cursor, count);
+        (cursor, batch.into_iter().map(|(m, s)| (m, *s)).collect())
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.list.remove(score, member),
+            None => false,
+        }
+    }
+
+    /// Returns the 0-based rank of `member`, counting from the highest score if `reverse`.
+    pub fn rank(&self, member: &str, reverse: bool) -> Option<usize> {
+        let score = self.score(member)?;
+        let rank = self.list.rank(score, member)?;
+
+        Some(if reverse { self.len() - 1 - rank } else { rank })
+    }
+
+    /// Returns the elements between the 0-based ranks `start` and `stop`, both inclusive.
+    pub fn range_by_rank(&self, start: usize, stop: usize, reverse: bool) -> Vec<(String, f64)> {
+        if start > stop || start >= self.len() {
+            return vec![];
+        }
+
+        let stop = stop.min(self.len() - 1);
+        let first = if reverse {
+            self.list.by_rank(self.len() - 1 - start)
+        } else {
+            self.list.by_rank(start)
+        };
+
+        self.walk(first, reverse, 0, Some(stop - start + 1), |_| true)
+    }
+
+    pub fn range_by_score(
+        &self,
+        range: &ScoreRange,
+        reverse: bool,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Vec<(String, f64)> {
+        if reverse {
+            let first = self.list.last_in_score_range(range);
+            self.walk(first, true, offset, limit, |node| {
+                range.above_min(self.list.score(node))
+            })
+        } else {
+            let first = self.list.first_in_score_range(range);
+            self.walk(first, false, offset, limit, |node| {
+                range.below_max(self.list.score(node))
+            })
+        }
+    }
+
+    pub fn range_by_lex(
+        &self,
+        range: &LexRange,
+        reverse: bool,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Vec<(String, f64)> {
+        if reverse {
+            let first = self.list.last_in_lex_range(range);
+            self.walk(first, true, offset, limit, |node| {
+                range.above_min(self.list.member(node))
+            })
+        } else {
+            let first = self.list.first_in_lex_range(range);
+            self.walk(first, false, offset, limit, |node| {
+                range.below_max(self.list.member(node))
+            })
+        }
+    }
+
+    /// Counts the elements within `range` using ranks, without walking the range.
+    pub fn count(&self, range: &ScoreRange) -> usize {
+        let (Some(first), Some(last)) = (
+            self.list.first_in_score_range(range),
+            self.list.last_in_score_range(range),
+        ) else {
+            return 0;
+        };
+
+        let rank = |node| {
+            self.list
+                .rank(self.list.score(node), self.list.member(node))
+                .unwrap_or_default()
+        };
+
+        rank(last) - rank(first) + 1
+    }
+
+    /// Removes and returns up to `count` elements with the lowest scores, or the highest ones
+    /// if `max`.
+    pub fn pop(&mut self, count: usize, max: bool) -> Vec<(String, f64)> {
+        let first = if max {
+            self.list.last()
+        } else {
+            self.list.first()
+        };
+
+        let popped = self.walk(first, max, 0, Some(count), |_| true);
+        for (member, _) in popped.iter() {
+            self.remove(member);
+        }
+
+        popped
+    }
+
+    /// Iterates over the elements from the lowest to the highest score.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        let mut node = self.list.first();
+
+        std::iter::from_fn(move || {
+            let current = node?;
+            node = self.list.next(current);
+            Some((self.list.member(current), self.list.score(current)))
+        })
+    }
+
+    fn walk<F>(
+        &self,
+        first: Option<usize>,
+        reverse: bool,
+        offset: usize,
+        limit: Option<usize>,
+        in_range: F,
+    ) -> Vec<(String, f64)>
+    where
+        F: Fn(usize) -> bool,
+    {
+        let mut result = Vec::new();
+        let mut node = first;
+        let mut skipped = 0;
+
+        while let Some(current) = node {
+            if !in_range(current) || limit.is_some_and(|limit| result.len() >= limit) {
+                break;
+            }
+
+            if skipped < offset {
+                skipped += 1;
+            } else {
+                result.push((
+                    self.list.member(current).to_owned(),
+                    self.list.score(current),
+                ));
+            }
+
+            node = if reverse {
+                self.list.prev(current)
+            } else {
+                self.list.next(current)
+            };
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SortedSet;
+    use crate::db::skiplist::ScoreRange;
+
+    #[test]
+    fn test_insert_updates_score() {
+        let mut zset = SortedSet::new();
+        assert!(zset.insert("a", 1.0));
+        assert!(zset.insert("b", 2.0));
+        assert!(!zset.insert("a", 3.0));
+
+        assert_eq!(Some(3.0), zset.score("a"));
+        assert_eq!(Some(1), zset.rank("a", false));
+        assert_eq!(Some(0), zset.rank("a", true));
+    }
+
+    #[test]
+    fn test_count_and_pop() {
+        let mut zset = SortedSet::new();
+        for i in 0..10 {
+            zset.insert(&i.to_string(), i as f64);
+        }
+
+        let range = ScoreRange {
+            min: 2.0,
+            max: 5.0,
+            min_exclusive: false,
+            max_exclusive: true,
+        };
+        assert_eq!(3, zset.count(&range));
+
+        let popped = zset.pop(2, true);
+        assert_eq!(
+            vec![(String::from("9"), 9.0), (String::from("8"), 8.0)],
+            popped
+        );
+        assert_eq!(8, zset.len());
+    }
+
+    #[test]
+    fn test_range_by_rank() {
+        let mut zset = SortedSet::new();
+        for (member, score) in [("a", 1.0), ("b", 2.0), ("c", 3.0)] {
+            zset.insert(member, score);
+        }
+
+        let members: Vec<String> = zset
+            .range_by_rank(1, 10, true)
+            .into_iter()
+            .map(|(m, _)| m)
+            .collect();
+
+        assert_eq!(vec!["b", "a"], members);
+    }
+}
@@ -1,47 +1,93 @@
-use std::io::{BufRead, Cursor, Read};
+use std::io::{self, BufRead, Cursor, Read};
 
 use anyhow::Error;
 
+use crate::error::RedisError;
+
+/// The longest bulk string a client may send, as with `proto-max-bulk-len` in Redis.
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Frame {
     SimpleString(String),
     BulkString(String),
+    /// A bulk string holding arbitrary bytes, such as a bitmap.
+    BulkBinary(Vec<u8>),
+    /// A bulk payload without the trailing CRLF, as used to transfer RDB files.
     BulkBytes(Vec<u8>),
     Arrays(Vec<String>),
+    Array(Vec<Frame>),
+    Integer(i64),
     Null,
-    Error,
+    NullArray,
+    Error(String),
 }
 
 impl Frame {
     pub fn parse(buf: &[u8]) -> Result<Self, Error> {
+        Frame::read(&mut Cursor::new(buf))
+    }
+
+    /// Parses the frame at the start of `buf`, returning it with the number of bytes it
+    /// takes, or `None` if it hasn't fully arrived yet.
+    pub fn parse_partial(buf: &[u8]) -> Result<Option<(Self, usize)>, Error> {
         let mut cursor = Cursor::new(buf);
 
-        match read_byte(&mut cursor)? {
+        match Frame::read(&mut cursor) {
+            Ok(frame) => Ok(Some((frame, cursor.position() as usize))),
+            Err(err) if is_incomplete(&err) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn read(cursor: &mut Cursor<&[u8]>) -> Result<Self, Error> {
+        match read_byte(cursor)? {
             b'+' => {
-                let line = read_line(&mut cursor)?;
+                let line = read_line(cursor)?;
 
                 Ok(Frame::SimpleString(line))
             }
             b'$' => {
-                let bulk_string = read_bulk_string(&mut cursor)?;
+                let bulk_string = read_bulk_string(cursor)?;
 
                 Ok(Frame::BulkString(bulk_string))
             }
             b'*' => {
-                let count = read_line(&mut cursor)?.parse::<usize>().unwrap_or(0);
+                let count = read_line(cursor)?.parse::<usize>().unwrap_or(0);
 
-                let mut array = Vec::with_capacity(count);
+                // The count is only trusted as far as the elements actually arrive.
+                let mut array = Vec::with_capacity(count.min(1024));
 
                 for _ in 0..count {
-                    if let Ok(b'$') = read_byte(&mut cursor) {
-                        let bulk_string = read_bulk_string(&mut cursor)?;
-                        let _ = read_line(&mut cursor)?; // consume \r\n
-
-                        array.push(bulk_string);
+                    if read_byte(cursor)? != b'$' {
+                        return Err(Error::msg("Unable to parse frame"));
                     }
+
+                    let bulk = read_bulk_bytes(cursor)?;
+                    let _ = read_line(cursor)?; // consume \r\n
+
+                    array.push(bulk);
                 }
 
-                Ok(Frame::Arrays(array))
+                // Arguments that are not UTF-8, such as HyperLogLogs or vector blobs, are kept
+                // as they are.
+                match array.iter().all(|bulk| std::str::from_utf8(bulk).is_ok()) {
+                    true => Ok(Frame::Arrays(
+                        array
+                            .into_iter()
+                            .map(|bulk| String::from_utf8(bulk).expect("checked above"))
+                            .collect(),
+                    )),
+                    false => Ok(Frame::Array(
+                        array
+                            .into_iter()
+                            .map(|bulk| match String::from_utf8(bulk) {
+                                Ok(s) => Frame::BulkString(s),
+                                Err(err) => Frame::BulkBinary(err.into_bytes()),
+                            })
+                            .collect(),
+                    )),
+                }
             }
             _ => Err(Error::msg("Unable to parse frame")),
         }
@@ -58,11 +104,93 @@ impl Frame {
                 result.push(s.clone());
             }
             Frame::Arrays(a) => result = a.to_vec(),
+            Frame::Array(a) => {
+                for frame in a {
+                    match frame {
+                        Frame::BulkString(s) => result.push(s.clone()),
+                        Frame::BulkBinary(b) => result.push(String::from_utf8_lossy(b).to_string()),
+                        _ => {}
+                    }
+                }
+            }
             _ => {}
         }
 
         result
     }
+
+    /// Like `to_vec`, keeping the exact bytes of arguments that are not UTF-8.
+    pub fn to_raw_vec(&self) -> Vec<Vec<u8>> {
+        match self {
+            Frame::Array(a) => a
+                .iter()
+                .filter_map(|frame| match frame {
+                    Frame::BulkString(s) => Some(s.as_bytes().to_vec()),
+                    Frame::BulkBinary(b) => Some(b.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => self.to_vec().into_iter().map(String::into_bytes).collect(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        self.encode(&mut buf);
+
+        buf
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Frame::SimpleString(s) => {
+                buf.push(b'+');
+                buf.extend_from_slice(s.as_bytes());
+                buf.extend_from_slice(b"\r\n");
+            }
+            Frame::BulkString(s) => encode_bulk(buf, s.as_bytes()),
+            Frame::BulkBinary(b) => encode_bulk(buf, b),
+            Frame::BulkBytes(b) => {
+                buf.extend_from_slice(format!("${}\r\n", b.len()).as_bytes());
+                buf.extend_from_slice(b);
+            }
+            Frame::Arrays(a) => {
+                buf.extend_from_slice(format!("*{}\r\n", a.len()).as_bytes());
+                for s in a {
+                    encode_bulk(buf, s.as_bytes());
+                }
+            }
+            Frame::Array(a) => {
+                buf.extend_from_slice(format!("*{}\r\n", a.len()).as_bytes());
+                for frame in a {
+                    frame.encode(buf);
+                }
+            }
+            Frame::Integer(i) => {
+                buf.extend_from_slice(format!(":{i}\r\n").as_bytes());
+            }
+            Frame::Null => buf.extend_from_slice(b"$-1\r\n"),
+            Frame::NullArray => buf.extend_from_slice(b"*-1\r\n"),
+            Frame::Error(e) => {
+                buf.push(b'-');
+                buf.extend_from_slice(e.as_bytes());
+                buf.extend_from_slice(b"\r\n");
+            }
+        }
+    }
+}
+
+impl From<RedisError> for Frame {
+    fn from(err: RedisError) -> Self {
+        Frame::Error(err.to_string())
+    }
+}
+
+fn encode_bulk(buf: &mut Vec<u8>, b: &[u8]) {
+    buf.extend_from_slice(format!("${}\r\n", b.len()).as_bytes());
+    buf.extend_from_slice(b);
+    buf.extend_from_slice(b"\r\n");
 }
 
 fn read_bulk_string(cursor: &mut Cursor<&[u8]>) -> Result<String, Error> {
@@ -77,6 +205,25 @@ fn read_bulk_string(cursor: &mut Cursor<&[u8]>) -> Result<String, Error> {
     }
 }
 
+fn read_bulk_bytes(cursor: &mut Cursor<&[u8]>) -> Result<Vec<u8>, Error> {
+    let length = read_line(cursor)?.parse::<usize>().unwrap_or(0);
+    if length > MAX_BULK_LEN {
+        return Err(Error::msg("invalid bulk length"));
+    }
+
+    // Nothing is allocated for a bulk string that hasn't arrived yet.
+    let remaining = cursor.get_ref().len() - cursor.position() as usize;
+    if remaining < length {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    let mut buf = vec![0; length];
+
+    cursor.read_exact(&mut buf)?;
+
+    Ok(buf)
+}
+
 fn read_byte(cursor: &mut Cursor<&[u8]>) -> Result<u8, Error> {
     let mut buf = [0; 1];
 
@@ -97,10 +244,19 @@ fn read_line(cursor: &mut Cursor<&[u8]>) -> Result<String, Error> {
     let mut line = String::new();
 
     cursor.read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
 
     Ok(line.trim_end_matches("\r\n").to_string())
 }
 
+/// Whether parsing failed only because the frame was cut short.
+fn is_incomplete(err: &Error) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|err| err.kind() == io::ErrorKind::UnexpectedEof)
+}
+
 #[cfg(test)]
 mod test {
     use anyhow::Error;
@@ -141,4 +297,58 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_binary_arrays() -> Result<(), Error> {
+        let blob = [0x00, 0x00, 0x80, 0xbf, 0xff, 0xfe, 0x00, 0x41];
+        let mut raw_bulk = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$8\r\n".to_vec();
+        raw_bulk.extend_from_slice(&blob);
+        raw_bulk.extend_from_slice(b"\r\n");
+
+        let frame = Frame::parse(&raw_bulk)?;
+
+        assert_eq!(
+            vec![b"SET".to_vec(), b"k".to_vec(), blob.to_vec()],
+            frame.to_raw_vec()
+        );
+        assert_eq!("k", frame.to_vec()[1]);
+        // Encoding the frame again, as when forwarding it, gives back the same bytes.
+        assert_eq!(raw_bulk, frame.to_bytes());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_partial() -> Result<(), Error> {
+        let raw = b"*1\r\n$5\r\nMULTI\r\n*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n";
+
+        // Pipelined frames are parsed one at a time.
+        let (frame, len) = Frame::parse_partial(raw)?.unwrap();
+        assert_eq!(Frame::Arrays(vec![String::from("MULTI")]), frame);
+        let (frame, rest) = Frame::parse_partial(&raw[len..])?.unwrap();
+        assert_eq!(vec!["SET", "a", "1"], frame.to_vec());
+        assert_eq!(raw.len(), len + rest);
+
+        // A frame cut anywhere hasn't arrived yet.
+        for end in 0..len {
+            assert_eq!(None, Frame::parse_partial(&raw[..end])?);
+        }
+        assert_eq!(None, Frame::parse_partial(b"*1\r\n$1000000\r\nabc")?);
+
+        assert!(Frame::parse_partial(b"*1\r\n:1\r\n").is_err());
+        assert!(Frame::parse_partial(b"*1\r\n$999999999999\r\n").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_bytes_nested_array() {
+        let frame = Frame::Array(vec![
+            Frame::Integer(1),
+            Frame::BulkString(String::from("a")),
+            Frame::Null,
+        ]);
+
+        assert_eq!(b"*3\r\n:1\r\n$1\r\na\r\n$-1\r\n".to_vec(), frame.to_bytes());
+    }
 }
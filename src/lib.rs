@@ -1,8 +1,15 @@
+mod acl;
+mod blocking;
 pub mod cmd;
 pub mod config;
 pub mod connection;
 pub mod db;
+pub mod error;
 pub mod frame;
+pub mod module;
+mod pubsub;
+pub mod rdb;
 pub mod replication;
 pub mod server;
+mod transaction;
 pub mod util;
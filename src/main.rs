@@ -16,6 +16,17 @@ async fn main() -> Result<(), Error> {
     let (sender, _rx) = broadcast::channel(16);
     let sender = Arc::new(sender);
 
+    {
+        let server = Arc::clone(&server);
+        let sender = Arc::clone(&sender);
+
+        tokio::spawn(async move {
+            if let Err(err) = server.active_expire_cycle(sender).await {
+                eprintln!("Active expire cycle stopped: {err}");
+            }
+        });
+    }
+
     if let Ok(stream) = server.connect_to_master().await {
         let conn = Connection::new(stream);
 
@@ -0,0 +1,334 @@
+//! Extension API for commands and data types defined outside the crate, in the spirit of
+//! Redis modules.
+//!
+//! A [`Module`] registers its commands and data types with [`RedisServer::load_module`]
+//! before the server starts accepting connections. Module commands run under the database
+//! lock like the built-in ones, so they are atomic, can be queued in transactions, and are
+//! forwarded to replicas whenever they mark a key as modified with [`Database::touch`].
+//!
+//! [`RedisServer::load_module`]: crate::server::RedisServer::load_module
+
+use std::{any::Any, collections::HashMap, fmt::Debug, sync::Arc};
+
+use crate::{
+    cmd::Command,
+    db::Database,
+    error::RedisError,
+    frame::Frame,
+    rdb::{self, ModuleWriter},
+};
+
+/// The command may modify the dataset.
+pub const WRITE: u32 = 1 << 0;
+/// The command only reads the dataset.
+pub const READONLY: u32 = 1 << 1;
+/// The command administers the server rather than working on keys.
+pub const ADMIN: u32 = 1 << 2;
+/// The command runs in constant or logarithmic time.
+pub const FAST: u32 = 1 << 3;
+/// The command cannot be queued in a transaction.
+pub const NO_MULTI: u32 = 1 << 4;
+
+/// Encoding versions take the 10 low bits of the type ID saved in snapshots.
+const MAX_ENCODING_VERSION: u64 = (1 << 10) - 1;
+
+/// A set of commands and data types loaded into the server together.
+pub trait Module {
+    /// Registers the commands and data types of the module.
+    fn load(&self, registry: &mut Registry) -> Result<(), RedisError>;
+}
+
+/// A command implemented outside the crate.
+pub trait ModuleCommand: Send + Sync {
+    fn name(&self) -> &str;
+    /// The number of arguments including the command name, or minus the minimum number of
+    /// arguments for commands that take a variable number of them, as in COMMAND INFO.
+    fn arity(&self) -> i64;
+    fn flags(&self) -> u32;
+    /// The positions of the keys among the arguments, which ACL rules are checked against.
+    /// They are written to if the command has the `WRITE` flag, and read otherwise.
+    fn keys(&self, args: &[String]) -> Vec<usize>;
+    /// Runs the command with its arguments, the command name being the first one. Changes
+    /// to keys must be marked with [`Database::touch`], which is what gets the command
+    /// forwarded to replicas and fails the transactions watching the keys.
+    fn execute(&self, db: &mut dyn Database, args: &[String]) -> Result<Frame, RedisError>;
+}
+
+/// A data type registered by a module, which identifies its values in snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataType {
+    name: &'static str,
+    encoding_version: u64,
+}
+
+impl DataType {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn encoding_version(&self) -> u64 {
+        self.encoding_version
+    }
+}
+
+/// A value of a module data type, stored in the keyspace as `Data::Module`.
+pub trait ModuleValue: Any + Debug + Send + Sync {
+    /// The type returned by [`Registry::create_data_type`] when the module was loaded.
+    fn data_type(&self) -> DataType;
+    /// Writes the value into a snapshot, as a sequence of fields that the module reads back
+    /// in the same order.
+    fn save(&self, writer: &mut ModuleWriter);
+    /// Estimate of the memory allocated by the value, for MEMORY USAGE.
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+/// The commands and data types of the loaded modules.
+#[derive(Clone, Default)]
+pub struct Registry {
+    commands: HashMap<String, Arc<dyn ModuleCommand>>,
+    data_types: Vec<DataType>,
+}
+
+impl Registry {
+    /// Adds a command, which can't take the name of a built-in or already registered one.
+    pub fn register_command(&mut self, command: Arc<dyn ModuleCommand>) -> Result<(), RedisError> {
+        let name = command.name().to_lowercase();
+        let frame = Frame::Arrays(vec![name.clone()]);
+        let builtin = !matches!(
+            Command::parse(&frame, &Registry::default()),
+            Command::Error(RedisError::UnknownCommand(_))
+        );
+
+        if name.is_empty() || builtin || self.commands.contains_key(&name) {
+            return Err(RedisError::Custom(format!(
+                "command '{name}' already exists"
+            )));
+        }
+        if command.arity() == 0 {
+            return Err(RedisError::Custom(format!(
+                "invalid arity for command '{name}'"
+            )));
+        }
+        if command.flags() & (WRITE | READONLY) == WRITE | READONLY {
+            return Err(RedisError::Custom(format!(
+                "command '{name}' can't be both write and readonly"
+            )));
+        }
+
+        self.commands.insert(name, command);
+        Ok(())
+    }
+
+    /// Adds a data type, named with exactly 9 characters from `A-Z`, `a-z`, `0-9`, `-` and
+    /// `_`. The encoding version lets newer versions of a module read older snapshots.
+    pub fn create_data_type(
+        &mut self,
+        name: &'static str,
+        encoding_version: u64,
+    ) -> Result<DataType, RedisError> {
+        let valid = name.len() == 9
+            && name.bytes().all(|c| rdb::MODULE_ID_CHARSET.contains(&c))
+            && encoding_version <= MAX_ENCODING_VERSION;
+        if !valid {
+            return Err(RedisError::Custom(format!("invalid data type '{name}'")));
+        }
+
+        let taken = rdb::BUILTIN_MODULE_TYPES.contains(&name)
+            || self.data_types.iter().any(|t| t.name == name);
+        if taken {
+            return Err(RedisError::Custom(format!(
+                "data type '{name}' already exists"
+            )));
+        }
+
+        let data_type = DataType {
+            name,
+            encoding_version,
+        };
+        self.data_types.push(data_type);
+        Ok(data_type)
+    }
+
+    pub(crate) fn command(&self, name: &str) -> Option<Arc<dyn ModuleCommand>> {
+        self.commands.get(name).cloned()
+    }
+
+    pub(crate) fn command_names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::{
+        cmd::{Command, Execute},
+        db::{Data, Database, KeyValueDb},
+        error::RedisError,
+        frame::Frame,
+        rdb::{self, ModuleWriter},
+    };
+
+    use super::{DataType, ModuleCommand, ModuleValue, Registry, NO_MULTI, READONLY, WRITE};
+
+    #[derive(Debug)]
+    struct Counter {
+        data_type: DataType,
+        count: u64,
+    }
+
+    impl ModuleValue for Counter {
+        fn data_type(&self) -> DataType {
+            self.data_type
+        }
+
+        fn save(&self, writer: &mut ModuleWriter) {
+            writer.save_unsigned(self.count);
+        }
+    }
+
+    /// `COUNTER.INCR key`
+    struct Incr(DataType);
+
+    impl ModuleCommand for Incr {
+        fn name(&self) -> &str {
+            "counter.incr"
+        }
+
+        fn arity(&self) -> i64 {
+            2
+        }
+
+        fn flags(&self) -> u32 {
+            WRITE | NO_MULTI
+        }
+
+        fn keys(&self, _: &[String]) -> Vec<usize> {
+            vec![1]
+        }
+
+        fn execute(&self, db: &mut dyn Database, args: &[String]) -> Result<Frame, RedisError> {
+            let data_type = self.0;
+            let counter = db
+                .get_or_insert_with(&args[1], &|| {
+                    Data::Module(Box::new(Counter {
+                        data_type,
+                        count: 0,
+                    }))
+                })
+                .as_module_mut::<Counter>()?;
+            counter.count += 1;
+            let count = counter.count;
+            db.touch(&args[1]);
+
+            Ok(Frame::Integer(count as i64))
+        }
+    }
+
+    struct Named(&'static str, i64, u32);
+
+    impl ModuleCommand for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn arity(&self) -> i64 {
+            self.1
+        }
+
+        fn flags(&self) -> u32 {
+            self.2
+        }
+
+        fn keys(&self, _: &[String]) -> Vec<usize> {
+            Vec::new()
+        }
+
+        fn execute(&self, _: &mut dyn Database, _: &[String]) -> Result<Frame, RedisError> {
+            Ok(Frame::Null)
+        }
+    }
+
+    fn parse(registry: &Registry, args: &[&str]) -> Command {
+        let frame = Frame::Arrays(args.iter().map(|s| s.to_string()).collect());
+        Command::parse(&frame, registry)
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = Registry::default();
+
+        assert!(registry.create_data_type("counter", 0).is_err());
+        assert!(registry.create_data_type("ReJSON-RL", 0).is_err());
+        assert!(registry.create_data_type("counter-1", 1024).is_err());
+        assert!(registry.create_data_type("counter-1", 0).is_ok());
+        assert!(registry.create_data_type("counter-1", 1).is_err());
+
+        for (name, arity, flags) in [
+            ("get", 2, READONLY),
+            ("ping", 1, READONLY),
+            ("x.cmd", 0, READONLY),
+            ("x.cmd", 1, WRITE | READONLY),
+        ] {
+            let command = Arc::new(Named(name, arity, flags));
+            assert!(registry.register_command(command).is_err());
+        }
+
+        assert!(registry
+            .register_command(Arc::new(Named("X.Cmd", -2, READONLY)))
+            .is_ok());
+        assert!(registry
+            .register_command(Arc::new(Named("x.cmd", 1, 0)))
+            .is_err());
+
+        // Names are case insensitive and arity is checked when parsing.
+        assert!(matches!(
+            parse(&registry, &["x.CMD", "a"]),
+            Command::Module(_)
+        ));
+        assert!(matches!(
+            parse(&registry, &["x.cmd"]),
+            Command::Error(RedisError::WrongArity(_))
+        ));
+        assert!(matches!(
+            parse(&registry, &["y.cmd"]),
+            Command::Error(RedisError::UnknownCommand(_))
+        ));
+    }
+
+    #[test]
+    fn test_module_value() {
+        let mut registry = Registry::default();
+        let data_type = registry.create_data_type("counter-1", 2).unwrap();
+        registry
+            .register_command(Arc::new(Incr(data_type)))
+            .unwrap();
+
+        let mut db = KeyValueDb::new();
+        let Command::Module(incr) = parse(&registry, &["COUNTER.INCR", "c"]) else {
+            panic!("not a module command");
+        };
+        assert_eq!(Frame::Integer(1), incr.execute(&mut db).unwrap());
+        assert_eq!(Frame::Integer(2), incr.execute(&mut db).unwrap());
+        assert_eq!(2, db.dirty());
+
+        db.set("s", b"v", None);
+        let Command::Module(incr) = parse(&registry, &["counter.incr", "s"]) else {
+            panic!("not a module command");
+        };
+        assert_eq!(Err(RedisError::WrongType), incr.execute(&mut db));
+
+        // The value is saved as a module value: its 64-bit type ID ending with the encoding
+        // version, the count, then the end marker.
+        let snapshot = rdb::dump(&db);
+        let start = snapshot.windows(3).position(|w| w == [7, 1, b'c']).unwrap();
+        let value = &snapshot[start + 3..start + 15];
+        assert_eq!(0x81, value[0]);
+        let id = u64::from_be_bytes(value[1..9].try_into().unwrap());
+        assert_eq!(2, id & 0x3FF);
+        assert_eq!([2, 2, 0], value[9..]);
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::{BTreeSet, HashMap};
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::{frame::Frame, util::glob};
+
+/// Messages a subscriber can fall behind by before it is disconnected, so that publishers
+/// never wait on a slow client.
+const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// Whether a subscription is to a channel or to a glob-style pattern of channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Channel,
+    Pattern,
+}
+
+struct Client {
+    /// The ACL user the client subscribed as.
+    user: String,
+    messages: mpsc::Sender<Frame>,
+    channels: BTreeSet<String>,
+    patterns: BTreeSet<String>,
+}
+
+impl Client {
+    fn subscriptions(&mut self, kind: Kind) -> &mut BTreeSet<String> {
+        match kind {
+            Kind::Channel => &mut self.channels,
+            Kind::Pattern => &mut self.patterns,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
+
+/// The receiving end of a client that subscribed at least once.
+pub(crate) struct Subscriber {
+    pub(crate) id: u64,
+    messages: mpsc::Receiver<Frame>,
+}
+
+impl Subscriber {
+    /// Waits for the next message, returning `None` once the client has been dropped for
+    /// falling behind or losing access to its channels.
+    pub(crate) async fn recv(&mut self) -> Option<Frame> {
+        self.messages.recv().await
+    }
+}
+
+/// Subscriptions of every client to channels and patterns. Messages are delivered through a
+/// bounded queue per client, which publishers never wait on.
+#[derive(Default)]
+pub(crate) struct PubSub {
+    next_id: u64,
+    clients: HashMap<u64, Client>,
+    channels: HashMap<String, BTreeSet<u64>>,
+    patterns: HashMap<String, BTreeSet<u64>>,
+}
+
+impl PubSub {
+    pub(crate) fn register(&mut self) -> Subscriber {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let (sender, messages) = mpsc::channel(SUBSCRIBER_BUFFER);
+        let client = Client {
+            user: String::new(),
+            messages: sender,
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+        };
+        self.clients.insert(id, client);
+
+        Subscriber { id, messages }
+    }
+
+    /// Removes a client along with its subscriptions.
+    pub(crate) fn remove(&mut self, id: u64) {
+        let Some(mut client) = self.clients.remove(&id) else {
+            return;
+        };
+
+        for kind in [Kind::Channel, Kind::Pattern] {
+            for name in std::mem::take(client.subscriptions(kind)) {
+                self.remove_subscriber(kind, &name, id);
+            }
+        }
+    }
+
+    /// Records the user a client subscribes as, which may change between subscriptions.
+    pub(crate) fn set_user(&mut self, id: u64, user: &str) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            user.clone_into(&mut client.user);
+        }
+    }
+
+    /// Disconnects the clients subscribed to a channel or pattern that `allowed` no longer
+    /// lets their user access, as after their ACL user changed. `allowed` is given the
+    /// user, the channel or pattern, and whether it is a pattern.
+    pub(crate) fn disconnect_denied(&mut self, allowed: impl Fn(&str, &str, bool) -> bool) {
+        let denied: Vec<u64> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| {
+                let channels = client.channels.iter().map(|c| (c, false));
+                let patterns = client.patterns.iter().map(|p| (p, true));
+                channels
+                    .chain(patterns)
+                    .any(|(name, is_pattern)| !allowed(&client.user, name, is_pattern))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in denied {
+            self.remove(id);
+        }
+    }
+
+    /// Subscribes a client to `name`, returning its number of subscriptions.
+    pub(crate) fn subscribe(&mut self, id: u64, kind: Kind, name: &str) -> usize {
+        let Some(client) = self.clients.get_mut(&id) else {
+            return 0;
+        };
+
+        let added = client.subscriptions(kind).insert(name.to_owned());
+        let count = client.count();
+
+        if added {
+            self.subscribers(kind)
+                .entry(name.to_owned())
+                .or_default()
+                .insert(id);
+        }
+
+        count
+    }
+
+    /// Unsubscribes a client from `name`, returning its number of subscriptions left.
+    pub(crate) fn unsubscribe(&mut self, id: u64, kind: Kind, name: &str) -> usize {
+        let Some(client) = self.clients.get_mut(&id) else {
+            return 0;
+        };
+
+        let removed = client.subscriptions(kind).remove(name);
+        let count = client.count();
+
+        if removed {
+            self.remove_subscriber(kind, name, id);
+        }
+
+        count
+    }
+
+    /// The channels or patterns a client is subscribed to.
+    pub(crate) fn subscriptions(&mut self, id: u64, kind: Kind) -> Vec<String> {
+        self.clients
+            .get_mut(&id)
+            .map(|client| client.subscriptions(kind).iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn count(&self, id: u64) -> usize {
+        self.clients.get(&id).map_or(0, Client::count)
+    }
+
+    /// Sends `message` to the subscribers of `channel` and of the patterns matching it,
+    /// returning the number of clients that received it. Clients whose queue is full are
+    /// disconnected.
+    pub(crate) fn publish(&mut self, channel: &str, message: &str) -> usize {
+        let mut deliveries = Vec::new();
+
+        if let Some(ids) = self.channels.get(channel) {
+            let frame = Frame::Arrays(vec![
+                String::from("message"),
+                channel.to_owned(),
+                message.to_owned(),
+            ]);
+            deliveries.extend(ids.iter().map(|id| (*id, frame.clone())));
+        }
+
+        for (pattern, ids) in &self.patterns {
+            if glob::matches(pattern, channel) {
+                let frame = Frame::Arrays(vec![
+                    String::from("pmessage"),
+                    pattern.clone(),
+                    channel.to_owned(),
+                    message.to_owned(),
+                ]);
+                deliveries.extend(ids.iter().map(|id| (*id, frame.clone())));
+            }
+        }
+
+        let mut received = 0;
+        let mut lagging = Vec::new();
+
+        for (id, frame) in deliveries {
+            let Some(client) = self.clients.get(&id) else {
+                continue;
+            };
+
+            match client.messages.try_send(frame) {
+                Ok(()) => received += 1,
+                Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => lagging.push(id),
+            }
+        }
+
+        // Dropping the sender closes the queue, which disconnects the client once it has
+        // read what is left in it.
+        for id in lagging {
+            self.remove(id);
+        }
+
+        received
+    }
+
+    /// The channels with at least one subscriber, optionally only those matching `pattern`.
+    pub(crate) fn channels(&self, pattern: Option<&str>) -> Vec<String> {
+        let mut channels: Vec<String> = self
+            .channels
+            .keys()
+            .filter(|channel| pattern.is_none_or(|p| glob::matches(p, channel)))
+            .cloned()
+            .collect();
+        channels.sort();
+        channels
+    }
+
+    pub(crate) fn numsub(&self, channel: &str) -> usize {
+        self.channels.get(channel).map_or(0, BTreeSet::len)
+    }
+
+    /// The number of patterns with at least one subscriber.
+    pub(crate) fn numpat(&self) -> usize {
+        self.patterns.len()
+    }
+
+    fn subscribers(&mut self, kind: Kind) -> &mut HashMap<String, BTreeSet<u64>> {
+        match kind {
+            Kind::Channel => &mut self.channels,
+            Kind::Pattern => &mut self.patterns,
+        }
+    }
+
+    fn remove_subscriber(&mut self, kind: Kind, name: &str, id: u64) {
+        let subscribers = self.subscribers(kind);
+        if let Some(ids) = subscribers.get_mut(name) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                subscribers.remove(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc::error::TryRecvError;
+
+    use super::{Kind, PubSub, SUBSCRIBER_BUFFER};
+    use crate::frame::Frame;
+
+    #[test]
+    fn test_publish() {
+        let mut pubsub = PubSub::default();
+        let mut a = pubsub.register();
+        let mut b = pubsub.register();
+
+        assert_eq!(1, pubsub.subscribe(a.id, Kind::Channel, "news"));
+        assert_eq!(2, pubsub.subscribe(a.id, Kind::Pattern, "n*"));
+        assert_eq!(1, pubsub.subscribe(b.id, Kind::Channel, "news"));
+
+        // A client gets the message once per matching subscription.
+        assert_eq!(3, pubsub.publish("news", "hi"));
+        assert_eq!(1, pubsub.publish("nope", "hi"));
+        assert_eq!(0, pubsub.publish("other", "hi"));
+
+        let message = Frame::Arrays(vec!["message".into(), "news".into(), "hi".into()]);
+        assert_eq!(message, a.messages.try_recv().unwrap());
+        assert_eq!(message, b.messages.try_recv().unwrap());
+
+        assert_eq!(vec!["news"], pubsub.channels(Some("n*")));
+        assert_eq!(2, pubsub.numsub("news"));
+        assert_eq!(1, pubsub.numpat());
+
+        assert_eq!(1, pubsub.unsubscribe(a.id, Kind::Channel, "news"));
+        pubsub.remove(a.id);
+        assert_eq!(1, pubsub.numsub("news"));
+        assert_eq!(0, pubsub.numpat());
+    }
+
+    #[test]
+    fn test_slow_subscriber_is_dropped() {
+        let mut pubsub = PubSub::default();
+        let mut slow = pubsub.register();
+        pubsub.subscribe(slow.id, Kind::Channel, "c");
+
+        for _ in 0..SUBSCRIBER_BUFFER {
+            assert_eq!(1, pubsub.publish("c", "m"));
+        }
+        assert_eq!(0, pubsub.publish("c", "m"));
+        assert_eq!(0, pubsub.numsub("c"));
+
+        // What was queued is still delivered before the queue closes.
+        let mut received = 0;
+        while slow.messages.try_recv().is_ok() {
+            received += 1;
+        }
+        assert_eq!(SUBSCRIBER_BUFFER, received);
+    }
+
+    #[test]
+    fn test_disconnect_denied() {
+        let mut pubsub = PubSub::default();
+        let mut a = pubsub.register();
+        let b = pubsub.register();
+        pubsub.set_user(a.id, "alice");
+        pubsub.set_user(b.id, "bob");
+        pubsub.subscribe(a.id, Kind::Channel, "news");
+        pubsub.subscribe(a.id, Kind::Pattern, "n*");
+        pubsub.subscribe(b.id, Kind::Channel, "news");
+
+        pubsub.disconnect_denied(|user, name, is_pattern| {
+            user == "bob" || (name == "news" && !is_pattern)
+        });
+        assert_eq!(0, pubsub.count(a.id));
+        assert_eq!(1, pubsub.numsub("news"));
+        assert_eq!(0, pubsub.numpat());
+        assert_eq!(
+            Err(TryRecvError::Disconnected),
+            a.messages.try_recv().map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,595 @@
+use std::time::SystemTime;
+
+use crate::{
+    db::{
+        bloom::Bloom,
+        countmin::CountMin,
+        cuckoo::Cuckoo,
+        set::Set,
+        stream::{Stream, StreamId},
+        timeseries::TimeSeries,
+        vectorset::{Metric, Quantization, VectorSet},
+        Data, Database,
+    },
+    util::time::to_unix_millis,
+};
+
+const RDB_VERSION: &[u8] = b"REDIS0012";
+
+const OPCODE_AUX: u8 = 0xFA;
+const OPCODE_RESIZEDB: u8 = 0xFB;
+const OPCODE_EXPIRETIME_MS: u8 = 0xFC;
+const OPCODE_SELECTDB: u8 = 0xFE;
+const OPCODE_EOF: u8 = 0xFF;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_SET: u8 = 2;
+const TYPE_HASH: u8 = 4;
+const TYPE_ZSET_2: u8 = 5;
+const TYPE_MODULE_2: u8 = 7;
+const TYPE_SET_INTSET: u8 = 11;
+const TYPE_HASH_LISTPACK: u8 = 16;
+const TYPE_SET_LISTPACK: u8 = 20;
+const TYPE_STREAM_LISTPACKS_3: u8 = 21;
+const TYPE_HASH_METADATA: u8 = 24;
+
+/// Module values are a sequence of typed fields, each preceded by its opcode.
+const MODULE_OPCODE_EOF: u8 = 0;
+const MODULE_OPCODE_SINT: u8 = 1;
+const MODULE_OPCODE_UINT: u8 = 2;
+const MODULE_OPCODE_DOUBLE: u8 = 4;
+const MODULE_OPCODE_STRING: u8 = 5;
+pub(crate) const MODULE_ID_CHARSET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Module data types are identified by a 9 character name and an encoding version, so that
+/// servers loading the file can find the module that reads them.
+const JSON_MODULE: (&str, u64) = ("ReJSON-RL", 3);
+const BLOOM_MODULE: (&str, u64) = ("MBbloom--", 4);
+const CUCKOO_MODULE: (&str, u64) = ("MBbloomCF", 4);
+const COUNT_MIN_MODULE: (&str, u64) = ("CMSk-TYPE", 0);
+const TIMESERIES_MODULE: (&str, u64) = ("TSDB-TYPE", 6);
+const VECTOR_SET_MODULE: (&str, u64) = ("vectorset", 0);
+/// Names that data types registered by modules can't take.
+pub(crate) const BUILTIN_MODULE_TYPES: [&str; 6] = [
+    JSON_MODULE.0,
+    BLOOM_MODULE.0,
+    CUCKOO_MODULE.0,
+    COUNT_MIN_MODULE.0,
+    TIMESERIES_MODULE.0,
+    VECTOR_SET_MODULE.0,
+];
+
+/// Reflected form of the polynomial of the CRC-64/Jones checksum that ends RDB files.
+const CRC64_POLY: u64 = 0x95AC_9329_AC4B_C9B5;
+
+pub(crate) fn crc64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |crc, &b| {
+        (0..8).fold(crc ^ b as u64, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ CRC64_POLY,
+            _ => crc >> 1,
+        })
+    })
+}
+
+/// Builds RDB payloads, where lengths use the variable size encoding of Redis: 6 bits, 14
+/// bits, or a marker byte followed by a 32 or 64-bit big endian integer.
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn byte(&mut self, b: u8) {
+        self.buf.push(b);
+    }
+
+    fn len(&mut self, n: u64) {
+        match n {
+            0..=0x3F => self.buf.push(n as u8),
+            0x40..=0x3FFF => self.buf.extend([0x40 | (n >> 8) as u8, n as u8]),
+            0x4000..=0xFFFF_FFFF => {
+                self.buf.push(0x80);
+                self.buf.extend((n as u32).to_be_bytes());
+            }
+            _ => {
+                self.buf.push(0x81);
+                self.buf.extend(n.to_be_bytes());
+            }
+        }
+    }
+
+    fn string(&mut self, s: &[u8]) {
+        self.len(s.len() as u64);
+        self.buf.extend(s);
+    }
+
+    fn millis(&mut self, ms: u64) {
+        self.buf.extend(ms.to_le_bytes());
+    }
+
+    fn id(&mut self, id: StreamId) {
+        self.len(id.ms);
+        self.len(id.seq);
+    }
+
+    /// IDs in a PEL are stored raw, in the big endian layout of radix tree keys.
+    fn raw_id(&mut self, id: StreamId) {
+        self.buf.extend(id.ms.to_be_bytes());
+        self.buf.extend(id.seq.to_be_bytes());
+    }
+
+    /// Packs the name of a module type in 6 bits per character, followed by 10 bits of
+    /// encoding version.
+    fn module_id(&mut self, (name, version): (&str, u64)) {
+        let id = name.bytes().fold(0, |id, c| {
+            let index = MODULE_ID_CHARSET.iter().position(|&x| x == c);
+            (id << 6) | index.expect("valid module type name") as u64
+        });
+        self.len((id << 10) | version);
+    }
+
+    fn module_uint(&mut self, n: u64) {
+        self.byte(MODULE_OPCODE_UINT);
+        self.len(n);
+    }
+
+    fn module_double(&mut self, f: f64) {
+        self.byte(MODULE_OPCODE_DOUBLE);
+        self.buf.extend(f.to_le_bytes());
+    }
+
+    fn module_string(&mut self, s: &[u8]) {
+        self.byte(MODULE_OPCODE_STRING);
+        self.string(s);
+    }
+
+    fn aux(&mut self, key: &str, value: &str) {
+        self.byte(OPCODE_AUX);
+        self.string(key.as_bytes());
+        self.string(value.as_bytes());
+    }
+}
+
+/// Saves the fields of a value of a data type registered by a module.
+pub struct ModuleWriter<'a> {
+    rdb: &'a mut Encoder,
+}
+
+impl ModuleWriter<'_> {
+    pub fn save_unsigned(&mut self, n: u64) {
+        self.rdb.module_uint(n);
+    }
+
+    pub fn save_signed(&mut self, n: i64) {
+        self.rdb.byte(MODULE_OPCODE_SINT);
+        self.rdb.len(n as u64);
+    }
+
+    pub fn save_double(&mut self, f: f64) {
+        self.rdb.module_double(f);
+    }
+
+    pub fn save_string(&mut self, s: &[u8]) {
+        self.rdb.module_string(s);
+    }
+}
+
+/// Serializes the dataset as an RDB file, as sent to replicas on a full resynchronization.
+pub fn dump(db: &dyn Database) -> Vec<u8> {
+    let mut rdb = Encoder::default();
+    rdb.buf.extend(RDB_VERSION);
+
+    let now = SystemTime::now();
+    rdb.aux("redis-ver", "7.4.0");
+    rdb.aux("redis-bits", "64");
+    rdb.aux("ctime", &(to_unix_millis(now) / 1000).to_string());
+    rdb.aux("aof-base", "0");
+
+    let entries: Vec<_> = db.iter().collect();
+    let expires = entries
+        .iter()
+        .filter(|(key, _)| db.expire_time(key).is_some())
+        .count();
+
+    rdb.byte(OPCODE_SELECTDB);
+    rdb.len(0);
+    rdb.byte(OPCODE_RESIZEDB);
+    rdb.len(entries.len() as u64);
+    rdb.len(expires as u64);
+
+    for (key, value) in entries {
+        if let Some(at) = db.expire_time(key) {
+            rdb.byte(OPCODE_EXPIRETIME_MS);
+            rdb.millis(to_unix_millis(at));
+        }
+
+        match value.data() {
+            Data::String(s) => {
+                rdb.byte(TYPE_STRING);
+                rdb.string(key.as_bytes());
+                rdb.string(&s.to_bytes());
+            }
+            // Small collections are saved in their compact encoding, as Redis does.
+            Data::Hash(hash) if hash.listpack().is_some() => {
+                rdb.byte(TYPE_HASH_LISTPACK);
+                rdb.string(key.as_bytes());
+                rdb.string(hash.listpack().expect("hash is a listpack").as_bytes());
+            }
+            Data::Hash(hash) => {
+                // Field expirations are stored relative to the earliest one, plus one so that
+                // zero means no expiration.
+                let min_expire = hash
+                    .iter()
+                    .filter_map(|(field, _)| hash.expire_time(&field))
+                    .min()
+                    .map(to_unix_millis);
+
+                rdb.byte(min_expire.map_or(TYPE_HASH, |_| TYPE_HASH_METADATA));
+                rdb.string(key.as_bytes());
+                if let Some(min) = min_expire {
+                    rdb.millis(min);
+                }
+                rdb.len(hash.len() as u64);
+
+                for (field, value) in hash.iter() {
+                    if let Some(min) = min_expire {
+                        let ttl = hash
+                            .expire_time(&field)
+                            .map_or(0, |at| to_unix_millis(at) - min + 1);
+                        rdb.len(ttl);
+                    }
+                    rdb.string(field.as_bytes());
+                    rdb.string(value.as_bytes());
+                }
+            }
+            Data::Set(set) => match set.as_ref() {
+                Set::IntSet(ints) => {
+                    rdb.byte(TYPE_SET_INTSET);
+                    rdb.string(key.as_bytes());
+                    rdb.string(&intset(ints));
+                }
+                Set::Listpack(lp) => {
+                    rdb.byte(TYPE_SET_LISTPACK);
+                    rdb.string(key.as_bytes());
+                    rdb.string(lp.as_bytes());
+                }
+                Set::Hash(_) => {
+                    rdb.byte(TYPE_SET);
+                    rdb.string(key.as_bytes());
+                    let members = set.members();
+                    rdb.len(members.len() as u64);
+                    for member in members {
+                        rdb.string(member.as_bytes());
+                    }
+                }
+            },
+            Data::SortedSet(zset) => {
+                rdb.byte(TYPE_ZSET_2);
+                rdb.string(key.as_bytes());
+                rdb.len(zset.len() as u64);
+                for (member, score) in zset.iter() {
+                    rdb.string(member.as_bytes());
+                    rdb.buf.extend(score.to_le_bytes());
+                }
+            }
+            Data::Stream(stream) => {
+                rdb.byte(TYPE_STREAM_LISTPACKS_3);
+                rdb.string(key.as_bytes());
+                write_stream(&mut rdb, stream);
+            }
+            Data::Json(json) => {
+                // Documents are saved as their serialized text.
+                rdb.byte(TYPE_MODULE_2);
+                rdb.string(key.as_bytes());
+                rdb.module_id(JSON_MODULE);
+                rdb.module_string(json.to_compact().as_bytes());
+                rdb.byte(MODULE_OPCODE_EOF);
+            }
+            Data::Bloom(bloom) => {
+                rdb.byte(TYPE_MODULE_2);
+                rdb.string(key.as_bytes());
+                rdb.module_id(BLOOM_MODULE);
+                write_bloom(&mut rdb, bloom);
+                rdb.byte(MODULE_OPCODE_EOF);
+            }
+            Data::Cuckoo(cuckoo) => {
+                rdb.byte(TYPE_MODULE_2);
+                rdb.string(key.as_bytes());
+                rdb.module_id(CUCKOO_MODULE);
+                write_cuckoo(&mut rdb, cuckoo);
+                rdb.byte(MODULE_OPCODE_EOF);
+            }
+            Data::CountMin(cms) => {
+                rdb.byte(TYPE_MODULE_2);
+                rdb.string(key.as_bytes());
+                rdb.module_id(COUNT_MIN_MODULE);
+                write_count_min(&mut rdb, cms);
+                rdb.byte(MODULE_OPCODE_EOF);
+            }
+            Data::TimeSeries(series) => {
+                rdb.byte(TYPE_MODULE_2);
+                rdb.string(key.as_bytes());
+                rdb.module_id(TIMESERIES_MODULE);
+                write_timeseries(&mut rdb, series);
+                rdb.byte(MODULE_OPCODE_EOF);
+            }
+            Data::VectorSet(set) => {
+                rdb.byte(TYPE_MODULE_2);
+                rdb.string(key.as_bytes());
+                rdb.module_id(VECTOR_SET_MODULE);
+                write_vector_set(&mut rdb, set);
+                rdb.byte(MODULE_OPCODE_EOF);
+            }
+            Data::Module(value) => {
+                let data_type = value.data_type();
+                rdb.byte(TYPE_MODULE_2);
+                rdb.string(key.as_bytes());
+                rdb.module_id((data_type.name(), data_type.encoding_version()));
+                value.save(&mut ModuleWriter { rdb: &mut rdb });
+                rdb.byte(MODULE_OPCODE_EOF);
+            }
+        }
+    }
+
+    rdb.byte(OPCODE_EOF);
+    let checksum = crc64(&rdb.buf);
+    rdb.buf.extend(checksum.to_le_bytes());
+
+    rdb.buf
+}
+
+/// Bloom filters follow the field order of RedisBloom: the chain, then each filter with its
+/// bits.
+fn write_bloom(rdb: &mut Encoder, bloom: &Bloom) {
+    // Option flags of RedisBloom, where 8 marks a filter that does not scale.
+    let (options, expansion) = match bloom.expansion() {
+        Some(expansion) => (0, expansion),
+        None => (8, 0),
+    };
+
+    rdb.module_uint(bloom.items());
+    rdb.module_uint(bloom.layers().len() as u64);
+    rdb.module_uint(options);
+    rdb.module_uint(expansion as u64);
+    for layer in bloom.layers() {
+        rdb.module_uint(layer.capacity());
+        rdb.module_double(layer.error());
+        rdb.module_uint(layer.hashes() as u64);
+        rdb.module_double(layer.bits_per_entry());
+        rdb.module_uint(layer.bits().len() as u64 * 8);
+        // Filters are not rounded to a power of two bits.
+        rdb.module_uint(0);
+        rdb.module_string(layer.bits());
+        rdb.module_uint(layer.items());
+    }
+}
+
+fn write_cuckoo(rdb: &mut Encoder, cuckoo: &Cuckoo) {
+    rdb.module_uint(cuckoo.filters().len() as u64);
+    rdb.module_uint(cuckoo.filters()[0].buckets());
+    rdb.module_uint(cuckoo.items());
+    rdb.module_uint(cuckoo.deletes());
+    rdb.module_uint(cuckoo.bucket_size() as u64);
+    rdb.module_uint(cuckoo.max_iterations() as u64);
+    rdb.module_uint(cuckoo.expansion() as u64);
+    for filter in cuckoo.filters() {
+        rdb.module_uint(filter.buckets());
+        rdb.module_string(filter.data());
+    }
+}
+
+/// Counters are saved as one buffer of 32-bit little endian integers, row after row.
+fn write_count_min(rdb: &mut Encoder, cms: &CountMin) {
+    rdb.module_uint(cms.width() as u64);
+    rdb.module_uint(cms.depth() as u64);
+    rdb.module_uint(cms.count());
+    let counters: Vec<u8> = cms
+        .counters()
+        .iter()
+        .flat_map(|c| c.to_le_bytes())
+        .collect();
+    rdb.module_string(&counters);
+}
+
+/// Series are saved with their settings, labels and rules, followed by their chunks as they
+/// are compressed in memory.
+fn write_timeseries(rdb: &mut Encoder, series: &TimeSeries) {
+    rdb.module_uint(series.retention as u64);
+    rdb.module_uint(series.chunk_size as u64);
+    rdb.module_string(series.duplicate_policy.name().as_bytes());
+    rdb.module_string(series.source.as_deref().unwrap_or_default().as_bytes());
+
+    rdb.module_uint(series.labels.len() as u64);
+    for (name, value) in &series.labels {
+        rdb.module_string(name.as_bytes());
+        rdb.module_string(value.as_bytes());
+    }
+
+    rdb.module_uint(series.rules.len() as u64);
+    for rule in &series.rules {
+        rdb.module_string(rule.destination.as_bytes());
+        rdb.module_uint(rule.duration as u64);
+        rdb.module_string(rule.aggregation.name().as_bytes());
+        rdb.module_uint(rule.align as u64);
+        // The bucket being filled, or -1 before the first sample.
+        rdb.module_uint(rule.open.unwrap_or(-1) as u64);
+    }
+
+    rdb.module_uint(series.chunks().len() as u64);
+    for chunk in series.chunks() {
+        rdb.module_uint(chunk.first_timestamp() as u64);
+        rdb.module_uint(chunk.last_timestamp() as u64);
+        rdb.module_uint(chunk.len() as u64);
+        rdb.module_string(chunk.bytes());
+    }
+}
+
+/// Encodes sorted integers as a Redis intset: the size of each integer and the number of
+/// integers, followed by the integers, all little endian.
+fn intset(ints: &[i64]) -> Vec<u8> {
+    let size = match ints.iter().map(|&i| i.max(-i - 1)).max() {
+        Some(i) if i > i32::MAX as i64 => 8,
+        Some(i) if i > i16::MAX as i64 => 4,
+        _ => 2,
+    };
+
+    let mut buf = Vec::with_capacity(8 + ints.len() * size);
+    buf.extend((size as u32).to_le_bytes());
+    buf.extend((ints.len() as u32).to_le_bytes());
+    for i in ints {
+        buf.extend(&i.to_le_bytes()[..size]);
+    }
+    buf
+}
+
+/// Vector sets are saved as their elements with their vector and attributes. The graph is
+/// not saved, and is rebuilt when the set is loaded.
+fn write_vector_set(rdb: &mut Encoder, set: &VectorSet) {
+    rdb.module_uint(set.dim() as u64);
+    rdb.module_uint(match set.metric() {
+        Metric::Cosine => 0,
+        Metric::L2 => 1,
+    });
+    rdb.module_uint(match set.quantization() {
+        Quantization::None => 0,
+        Quantization::Q8 => 1,
+    });
+    rdb.module_uint(set.m() as u64);
+
+    rdb.module_uint(set.len() as u64);
+    for (element, vector, attributes) in set.iter() {
+        rdb.module_string(element.as_bytes());
+        let vector: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+        rdb.module_string(&vector);
+        rdb.module_string(attributes.unwrap_or_default().as_bytes());
+    }
+}
+
+/// Streams are saved as their blocks, which already use the Redis listpack layout, followed
+/// by the stream metadata and the consumer groups with their PELs.
+fn write_stream(rdb: &mut Encoder, stream: &Stream) {
+    let blocks: Vec<_> = stream.blocks().collect();
+    rdb.len(blocks.len() as u64);
+    for (master, lp) in blocks {
+        let mut key = Encoder::default();
+        key.raw_id(master);
+        rdb.string(&key.buf);
+        rdb.string(lp.as_bytes());
+    }
+
+    rdb.len(stream.len() as u64);
+    rdb.id(stream.last_id());
+    rdb.id(stream.first_id());
+    rdb.id(stream.max_deleted_id());
+    rdb.len(stream.entries_added());
+
+    rdb.len(stream.groups().len() as u64);
+    for (name, group) in stream.groups() {
+        rdb.string(name.as_bytes());
+        rdb.id(group.last_id);
+        // An unknown read counter is saved as -1.
+        rdb.len(group.entries_read.unwrap_or(u64::MAX));
+
+        rdb.len(group.pending().len() as u64);
+        for (id, pending) in group.pending() {
+            rdb.raw_id(*id);
+            rdb.millis(pending.delivery_time);
+            rdb.len(pending.delivery_count);
+        }
+
+        rdb.len(group.consumers().len() as u64);
+        for (name, consumer) in group.consumers() {
+            rdb.string(name.as_bytes());
+            rdb.millis(consumer.seen_time);
+            rdb.millis(consumer.active_time.unwrap_or(u64::MAX));
+            rdb.len(consumer.pending().len() as u64);
+            for id in consumer.pending() {
+                rdb.raw_id(*id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use crate::{
+        db::{
+            stream::{Stream, StreamId},
+            Data, Database, KeyValueDb, Value,
+        },
+        util::hex,
+    };
+
+    use super::{crc64, dump, intset, Encoder};
+
+    #[test]
+    fn test_crc64() {
+        assert_eq!(0xe9c6d914c4b8d9ca, crc64(b"123456789"));
+    }
+
+    #[test]
+    fn test_length_encoding() {
+        let mut rdb = Encoder::default();
+        for n in [10, 700, 70000, 1 << 40] {
+            rdb.len(n);
+        }
+
+        assert_eq!(hex::decode("0a42bc8000011170810000010000000000"), rdb.buf);
+    }
+
+    #[test]
+    fn test_intset() {
+        assert_eq!(hex::decode("0200000002000000ffff0500"), intset(&[-1, 5]));
+        assert_eq!(
+            hex::decode("04000000020000000080ffffffff0000"),
+            intset(&[-32768, 65535])
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let mut db = KeyValueDb::new();
+        let exp = SystemTime::UNIX_EPOCH + Duration::from_millis(u64::MAX >> 20);
+        db.set("k", b"v", Some(exp));
+
+        let rdb = dump(&db);
+        assert!(rdb.starts_with(b"REDIS0012"));
+
+        // The key follows the database selector, and the checksum covers everything before it.
+        let (body, checksum) = rdb.split_at(rdb.len() - 8);
+        let mut key = hex::decode("fe00fb0101fc");
+        key.extend((u64::MAX >> 20).to_le_bytes());
+        key.extend(hex::decode("00016b0176ff"));
+        assert!(body.ends_with(&key));
+        assert_eq!(crc64(body).to_le_bytes(), checksum);
+    }
+
+    #[test]
+    fn test_dump_stream_groups() {
+        let mut stream = Stream::new();
+        stream.append(
+            StreamId::new(1, 0),
+            &vec![(String::from("f"), String::from("v"))],
+        );
+        stream.create_group("readers", StreamId::MIN, None);
+        let group = stream.group_mut("readers").unwrap();
+        group.create_consumer("alice", 5);
+        group.assign(StreamId::new(1, 0), "alice", 7, 1);
+
+        let mut db = KeyValueDb::new();
+        db.insert("s", Value::from(Data::Stream(Box::new(stream))));
+        let rdb = dump(&db);
+
+        // Group name, last ID 0-0, unknown read counter, then the PEL entry.
+        let mut group = hex::decode("07");
+        group.extend(b"readers");
+        group.extend(hex::decode("000081ffffffffffffffff01"));
+        group.extend(hex::decode("00000000000000010000000000000000"));
+        group.extend(7u64.to_le_bytes());
+        group.push(1);
+        assert!(rdb.windows(group.len()).any(|w| w == group));
+    }
+}
@@ -1,20 +1,39 @@
-use std::sync::Arc;
+use std::{
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
 
 use anyhow::Error;
 use tokio::{
     net::{TcpListener, TcpStream},
     sync::{broadcast::Sender, Mutex},
+    time,
 };
 
 use crate::{
-    cmd::{ping::Ping, psync::Psync, replconf::Replconf, Command},
+    acl::{Acl, Denial, Reason, DEFAULT_USER},
+    blocking::BlockedClients,
+    cmd::{
+        acl::{AclCommand, Auth},
+        ping::Ping,
+        psync::Psync,
+        replconf::Replconf,
+        Block, Command, Execute,
+    },
     config::Config,
     connection::Connection,
     db::Database,
+    error::RedisError,
     frame::Frame,
+    module::{Module, Registry},
+    pubsub::{PubSub, Subscriber},
+    rdb,
     replication::Replication,
+    transaction::{Transaction, WatchedKeys},
 };
 
+const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+
 pub struct RedisServer<D>
 where
     D: Database,
@@ -22,6 +41,31 @@ where
     replication: Replication,
     config: Config,
     db: Arc<Mutex<D>>,
+    /// Only locked while holding the database lock.
+    blocked: StdMutex<BlockedClients>,
+    /// Never held across an await, so publishers don't wait on each other for long.
+    pubsub: StdMutex<PubSub>,
+    modules: Registry,
+    /// Never held across an await, like the pub/sub registry.
+    acl: StdMutex<Acl>,
+}
+
+/// A connected client, authenticated as `user` once AUTH succeeds or right away while the
+/// default user needs no password.
+struct Client {
+    user: Option<String>,
+    addr: String,
+}
+
+impl Client {
+    /// Describes the client in the ACL log.
+    fn info(&self) -> String {
+        format!(
+            "addr={} user={}",
+            self.addr,
+            self.user.as_deref().unwrap_or_default()
+        )
+    }
 }
 
 impl<D> RedisServer<D>
@@ -29,14 +73,43 @@ where
     D: Database,
 {
     pub fn new(config: Config, db: Arc<Mutex<D>>) -> Self {
+        let acl = Acl::new(config.requirepass.as_deref());
+
         RedisServer {
             replication: Replication::new(&config),
             config,
             db,
+            blocked: StdMutex::new(BlockedClients::default()),
+            pubsub: StdMutex::new(PubSub::default()),
+            modules: Registry::default(),
+            acl: StdMutex::new(acl),
+        }
+    }
+
+    /// Registers the commands and data types of `module`, which has to happen before the
+    /// server is shared with the connections. Nothing is registered if loading fails.
+    pub fn load_module(&mut self, module: &dyn Module) -> Result<(), RedisError> {
+        let mut modules = self.modules.clone();
+        module.load(&mut modules)?;
+        self.modules = modules;
+
+        let acl = self.acl.get_mut().expect("acl lock poisoned");
+        for name in self.modules.command_names() {
+            acl.add_module_command(name);
         }
+
+        Ok(())
     }
 
+    /// Binds the listening socket, loading the ACL file first so that its rules can name
+    /// the commands of the modules loaded by then.
     pub async fn listen(&self) -> Result<TcpListener, Error> {
+        if let Some(aclfile) = &self.config.aclfile {
+            if let Err(err) = self.acl().load(aclfile) {
+                eprintln!("Failed to load ACL users: {err}");
+            }
+        }
+
         let address = format!("127.0.0.1:{}", self.config.port);
         let listener = TcpListener::bind(address).await?;
 
@@ -48,53 +121,638 @@ where
         mut conn: Connection,
         sender: Arc<Sender<Frame>>,
     ) -> Result<(), Error> {
-        let sender = Arc::clone(&sender);
+        let mut watched = WatchedKeys::default();
+        let mut subscriber = None;
+        let mut client = Client {
+            user: self.acl().is_open().then(|| DEFAULT_USER.to_owned()),
+            addr: conn.peer_addr().map(|a| a.to_string()).unwrap_or_default(),
+        };
+
+        let result = self
+            .serve(
+                &mut conn,
+                sender,
+                &mut client,
+                &mut watched,
+                &mut subscriber,
+            )
+            .await;
+
+        // The client may have dropped while watching keys or subscribed.
+        if !watched.is_empty() {
+            watched.clear(&mut *self.db.lock().await);
+        }
+        if let Some(subscriber) = subscriber {
+            self.pubsub().remove(subscriber.id);
+        }
+
+        result
+    }
+
+    async fn serve(
+        &self,
+        conn: &mut Connection,
+        sender: Arc<Sender<Frame>>,
+        client: &mut Client,
+        watched: &mut WatchedKeys,
+        subscriber: &mut Option<Subscriber>,
+    ) -> Result<(), Error> {
+        let mut multi: Option<Transaction> = None;
 
         loop {
-            let Ok(frame) = conn.read_frame().await else {
+            let frame = tokio::select! {
+                frame = conn.read_frame() => frame,
+                message = next_message(subscriber) => {
+                    let Some(message) = message else {
+                        break Err(Error::msg("Subscriber was disconnected"));
+                    };
+
+                    conn.write_frame(&message).await?;
+                    continue;
+                }
+            };
+
+            let Ok(frame) = frame else {
                 break Err(Error::msg("Unable to read frame"));
             };
 
-            println!("Frame: {frame:?}");
+            let cmd = Command::parse(&frame, &self.modules);
 
-            let cmd = Command::parse(&frame);
+            // Clients of a deleted user are disconnected.
+            let deleted = client
+                .user
+                .as_ref()
+                .is_some_and(|user| self.acl().user(user).is_none());
+            if deleted {
+                break Ok(());
+            }
 
-            println!("Command: {cmd:?}");
+            // QUIT needs no authentication, and is neither queued nor refused to subscribers.
+            if let Command::Quit = cmd {
+                conn.write_frame(&Frame::SimpleString(String::from("OK")))
+                    .await?;
+                break Ok(());
+            }
+
+            // Refused commands fail like unparsable ones, aborting the transaction if queued.
+            let cmd = match self.authorize(client, &cmd, &frame.to_vec(), "toplevel") {
+                Ok(()) => cmd,
+                Err(err) => Command::Error(err),
+            };
+
+            // A subscribed client can only change its subscriptions, ping or quit.
+            let subscribed = subscriber
+                .as_ref()
+                .is_some_and(|subscriber| self.pubsub().count(subscriber.id) > 0);
+            if subscribed {
+                let reply = match &cmd {
+                    Command::Subscription(_) => None,
+                    Command::Ping(ping) => Some(ping.subscribed_reply()),
+                    _ => {
+                        let name = frame.to_vec().first().cloned().unwrap_or_default();
+                        Some(Frame::from(RedisError::Custom(format!(
+                            "Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / \
+                             QUIT are allowed in this context",
+                            name.to_lowercase()
+                        ))))
+                    }
+                };
+
+                if let Some(reply) = reply {
+                    conn.write_frame(&reply).await?;
+                    continue;
+                }
+            }
+
+            if let Some(tx) = multi.as_mut() {
+                if !matches!(
+                    cmd,
+                    Command::Multi | Command::Exec | Command::Discard | Command::Watch(_)
+                ) {
+                    conn.write_frame(&tx.queue(cmd, frame)).await?;
+                    continue;
+                }
+            }
 
             match cmd {
                 Command::Ping(ping) => {
-                    ping.apply(&mut conn).await?;
+                    ping.apply(conn).await?;
                 }
                 Command::Echo(echo) => {
-                    echo.apply(&mut conn).await?;
+                    echo.apply(conn).await?;
                 }
                 Command::Get(get) => {
-                    let db = Arc::clone(&self.db);
-                    get.apply(&mut conn, db).await?;
+                    self.execute(conn, &get, &frame, &sender).await?;
                 }
-                Command::Set(ref set) => {
-                    let db = Arc::clone(&self.db);
-                    set.apply(&mut conn, db).await?;
-
-                    sender.send(frame)?;
+                Command::Set(set) => {
+                    self.execute(conn, &set, &frame, &sender).await?;
                 }
                 Command::Info(info) => {
-                    info.apply(&mut conn, &self.config, &self.replication)
-                        .await?;
+                    info.apply(conn, &self.config, &self.replication).await?;
                 }
                 Command::Replconf(replconf) => {
-                    replconf.apply(&mut conn).await?;
+                    replconf.apply(conn).await?;
                 }
                 Command::Psync(psync) => {
-                    psync.apply(&mut conn, &self.replication).await?;
+                    // Commands forward their writes under the database lock, so the replica
+                    // gets each write either in the snapshot or through the receiver.
+                    let (snapshot, mut receiver) = {
+                        let db = self.db.lock().await;
+                        (rdb::dump(&*db), sender.subscribe())
+                    };
 
-                    let mut receiver = sender.subscribe();
+                    psync.apply(conn, &self.replication, snapshot).await?;
 
                     while let Ok(f) = receiver.recv().await {
                         conn.write_frame(&f).await?;
                     }
                 }
-                _ => {}
+                Command::Hash(hash) => {
+                    self.execute(conn, &hash, &frame, &sender).await?;
+                }
+                Command::Sets(set) => {
+                    self.execute(conn, &set, &frame, &sender).await?;
+                }
+                Command::SortedSet(zset) => {
+                    self.execute(conn, &zset, &frame, &sender).await?;
+                }
+                Command::Stream(stream) => {
+                    self.execute(conn, &stream, &frame, &sender).await?;
+                }
+                Command::ConsumerGroup(group) => {
+                    self.execute(conn, &group, &frame, &sender).await?;
+                }
+                Command::Bitmap(bitmap) => {
+                    self.execute(conn, &bitmap, &frame, &sender).await?;
+                }
+                Command::HyperLogLog(hll) => {
+                    self.execute(conn, &hll, &frame, &sender).await?;
+                }
+                Command::Geo(geo) => {
+                    self.execute(conn, &geo, &frame, &sender).await?;
+                }
+                Command::Json(json) => {
+                    self.execute(conn, &json, &frame, &sender).await?;
+                }
+                Command::Bloom(bloom) => {
+                    self.execute(conn, &bloom, &frame, &sender).await?;
+                }
+                Command::Cuckoo(cuckoo) => {
+                    self.execute(conn, &cuckoo, &frame, &sender).await?;
+                }
+                Command::CountMin(cms) => {
+                    self.execute(conn, &cms, &frame, &sender).await?;
+                }
+                Command::TimeSeries(ts) => {
+                    self.execute(conn, &ts, &frame, &sender).await?;
+                }
+                Command::VectorSet(set) => {
+                    self.execute(conn, &set, &frame, &sender).await?;
+                }
+                Command::Search(search) => {
+                    self.execute(conn, &search, &frame, &sender).await?;
+                }
+                Command::Throttle(throttle) => {
+                    self.execute(conn, &throttle, &frame, &sender).await?;
+                }
+                Command::Object(object) => {
+                    self.execute(conn, &object, &frame, &sender).await?;
+                }
+                Command::Memory(memory) => {
+                    self.execute(conn, &memory, &frame, &sender).await?;
+                }
+                Command::Config(config) => {
+                    let reply = {
+                        let mut db = self.db.lock().await;
+                        config.execute(&mut *db, &mut self.acl())
+                    };
+                    let reply = reply.unwrap_or_else(Frame::from);
+                    conn.write_frame(&reply).await?;
+                }
+                Command::Auth(auth) => {
+                    let reply = self.auth(client, &auth);
+                    conn.write_frame(&reply).await?;
+                }
+                Command::Acl(cmd) => {
+                    let reply = self.acl_command(client, &cmd);
+                    conn.write_frame(&reply).await?;
+                }
+                Command::Flushdb(flushdb) => {
+                    self.execute(conn, &flushdb, &frame, &sender).await?;
+                }
+                Command::Del(del) => {
+                    self.execute(conn, &del, &frame, &sender).await?;
+                }
+                Command::Module(call) => {
+                    self.execute(conn, &call, &frame, &sender).await?;
+                }
+                Command::Subscription(subscription) => {
+                    let replies = {
+                        let mut pubsub = self.pubsub();
+                        let id = subscriber.get_or_insert_with(|| pubsub.register()).id;
+                        pubsub.set_user(id, client.user.as_deref().unwrap_or_default());
+                        subscription.apply(&mut pubsub, id)
+                    };
+
+                    for reply in replies {
+                        conn.write_frame(&reply).await?;
+                    }
+                }
+                Command::PubSub(cmd) => {
+                    let reply = cmd.execute(&mut self.pubsub());
+                    conn.write_frame(&reply).await?;
+                }
+                Command::Blocking(cmd) => {
+                    self.block(conn, cmd, &sender).await?;
+                }
+                Command::Multi => {
+                    let reply = match multi {
+                        Some(_) => Frame::from(RedisError::Custom(String::from(
+                            "MULTI calls can not be nested",
+                        ))),
+                        None => {
+                            multi = Some(Transaction::default());
+                            Frame::SimpleString(String::from("OK"))
+                        }
+                    };
+
+                    conn.write_frame(&reply).await?;
+                }
+                Command::Exec => match multi.take() {
+                    Some(tx) => self.exec(conn, tx, client, watched, &sender).await?,
+                    None => {
+                        let err = RedisError::Custom(String::from("EXEC without MULTI"));
+                        conn.write_frame(&Frame::from(err)).await?;
+                    }
+                },
+                Command::Discard => {
+                    let reply = match multi.take() {
+                        Some(_) => {
+                            watched.clear(&mut *self.db.lock().await);
+                            Frame::SimpleString(String::from("OK"))
+                        }
+                        None => {
+                            Frame::from(RedisError::Custom(String::from("DISCARD without MULTI")))
+                        }
+                    };
+
+                    conn.write_frame(&reply).await?;
+                }
+                Command::Watch(keys) => {
+                    let reply = match multi {
+                        Some(_) => Frame::from(RedisError::Custom(String::from(
+                            "WATCH inside MULTI is not allowed",
+                        ))),
+                        None => {
+                            let mut db = self.db.lock().await;
+                            for key in &keys {
+                                watched.watch(&mut *db, key);
+                            }
+                            self.notify_keyspace_events(&mut db);
+                            Frame::SimpleString(String::from("OK"))
+                        }
+                    };
+
+                    conn.write_frame(&reply).await?;
+                }
+                Command::Unwatch => {
+                    watched.clear(&mut *self.db.lock().await);
+                    conn.write_frame(&Frame::SimpleString(String::from("OK")))
+                        .await?;
+                }
+                Command::Quit => unreachable!("QUIT is handled before anything else"),
+                Command::Error(err) => {
+                    conn.write_frame(&Frame::from(err)).await?;
+                }
+            }
+        }
+    }
+
+    /// Runs a command under the database lock, replies to the client and forwards any
+    /// modification to replicas.
+    async fn execute<C>(
+        &self,
+        conn: &mut Connection,
+        cmd: &C,
+        frame: &Frame,
+        sender: &Sender<Frame>,
+    ) -> Result<(), Error>
+    where
+        C: Execute,
+    {
+        let reply = {
+            let mut db = self.db.lock().await;
+            let dirty = db.dirty();
+
+            let reply = cmd.execute(&mut *db).unwrap_or_else(Frame::from);
+
+            let mut propagated = drain_writes(&mut *db, Some(frame), dirty);
+            propagated.extend(self.serve_blocked(&mut *db));
+            self.notify_keyspace_events(&mut db);
+
+            for f in propagated {
+                sender.send(f)?;
+            }
+
+            reply
+        };
+
+        conn.write_frame(&reply).await
+    }
+
+    /// Runs a blocking command, waiting for one of its keys to become ready if none can serve
+    /// it right away. The client stops waiting if its connection drops.
+    async fn block(
+        &self,
+        conn: &mut Connection,
+        cmd: Arc<dyn Block>,
+        sender: &Sender<Frame>,
+    ) -> Result<(), Error> {
+        let served = {
+            let mut db = self.db.lock().await;
+
+            let dirty = db.dirty();
+            let served = cmd.execute(&mut *db);
+
+            let mut propagated = drain_writes(&mut *db, None, dirty);
+            propagated.extend(self.serve_blocked(&mut *db));
+            self.notify_keyspace_events(&mut db);
+
+            let served = match served {
+                Ok(Some(reply)) => Ok(reply),
+                Err(err) => Ok(Frame::from(err)),
+                Ok(None) => {
+                    let cmd = cmd.resolve(&mut *db).unwrap_or_else(|| Arc::clone(&cmd));
+                    Err(self.blocked_clients().block(&mut *db, cmd))
+                }
+            };
+
+            for f in propagated {
+                sender.send(f)?;
+            }
+
+            served
+        };
+
+        let (id, mut rx) = match served {
+            Ok(reply) => return conn.write_frame(&reply).await,
+            Err(blocked) => blocked,
+        };
+
+        let timeout = cmd.timeout();
+        let wait = async {
+            match timeout {
+                None => (&mut rx).await.ok(),
+                Some(timeout) => time::timeout(timeout, &mut rx).await.ok()?.ok(),
+            }
+        };
+
+        let (reply, closed) = tokio::select! {
+            reply = wait => (reply, false),
+            _ = conn.closed() => (None, true),
+        };
+
+        let reply = match reply {
+            Some(reply) => reply,
+            None => {
+                let mut db = self.db.lock().await;
+
+                // The client may have been served right as it stopped waiting.
+                if self.blocked_clients().unblock(&mut *db, id) {
+                    Frame::NullArray
+                } else {
+                    rx.await.unwrap_or(Frame::NullArray)
+                }
+            }
+        };
+
+        if closed {
+            return Ok(());
+        }
+
+        conn.write_frame(&reply).await
+    }
+
+    /// Runs the commands of a transaction under a single acquisition of the database lock,
+    /// replying with all their replies at once. Replicas get the writes wrapped in MULTI and
+    /// EXEC so they apply them as a unit too. Nothing runs if a watched key has changed.
+    async fn exec(
+        &self,
+        conn: &mut Connection,
+        tx: Transaction,
+        client: &Client,
+        watched: &mut WatchedKeys,
+        sender: &Sender<Frame>,
+    ) -> Result<(), Error> {
+        let reply = {
+            let mut db = self.db.lock().await;
+
+            let modified = watched.is_modified(&mut *db);
+            watched.clear(&mut *db);
+            // Checking the watched keys may have expired some.
+            self.notify_keyspace_events(&mut db);
+
+            match tx.commands() {
+                Ok(_) if modified => Frame::NullArray,
+                Ok(commands) => self.run_transaction(&mut db, commands, client, sender)?,
+                Err(err) => Frame::from(err),
+            }
+        };
+
+        conn.write_frame(&reply).await
+    }
+
+    /// Runs the commands queued by a transaction, returning the array of their replies.
+    /// Permissions are checked again as they may have changed since the commands were queued.
+    fn run_transaction(
+        &self,
+        db: &mut D,
+        commands: Vec<(Command, Frame)>,
+        client: &Client,
+        sender: &Sender<Frame>,
+    ) -> Result<Frame, Error> {
+        let mut writes = Vec::new();
+        let mut replies = Vec::with_capacity(commands.len());
+
+        for (cmd, frame) in &commands {
+            if let Err(err) = self.authorize(client, cmd, &frame.to_vec(), "multi") {
+                replies.push(Frame::from(err));
+                continue;
+            }
+
+            let dirty = db.dirty();
+
+            // Blocking commands never block inside a transaction, and like outside of
+            // one they only propagate what they rewrite themselves as.
+            let (reply, frame) = match cmd {
+                Command::Info(info) => (Ok(info.reply(&self.replication)), Some(frame)),
+                // Watched keys are released by EXEC itself.
+                Command::Unwatch => (Ok(Frame::SimpleString(String::from("OK"))), None),
+                Command::PubSub(cmd) => (Ok(cmd.execute(&mut self.pubsub())), None),
+                Command::Config(cmd) => (cmd.execute(db, &mut self.acl()), None),
+                Command::Acl(cmd) => (Ok(self.acl_command(client, cmd)), None),
+                Command::Blocking(cmd) => (
+                    cmd.execute(db)
+                        .map(|reply| reply.unwrap_or(Frame::NullArray)),
+                    None,
+                ),
+                cmd => {
+                    let cmd = cmd.as_execute().expect("only runnable commands are queued");
+                    (cmd.execute(db), Some(frame))
+                }
+            };
+
+            replies.push(reply.unwrap_or_else(Frame::from));
+            writes.extend(drain_writes(db, frame, dirty));
+            self.notify_keyspace_events(db);
+        }
+
+        let mut propagated = Vec::new();
+        if !writes.is_empty() {
+            propagated.push(Frame::Arrays(vec![String::from("MULTI")]));
+            propagated.extend(writes);
+            propagated.push(Frame::Arrays(vec![String::from("EXEC")]));
+        }
+
+        propagated.extend(self.serve_blocked(db));
+        self.notify_keyspace_events(db);
+
+        for f in propagated {
+            sender.send(f)?;
+        }
+
+        Ok(Frame::Array(replies))
+    }
+
+    fn blocked_clients(&self) -> std::sync::MutexGuard<'_, BlockedClients> {
+        self.blocked.lock().expect("blocked clients lock poisoned")
+    }
+
+    fn pubsub(&self) -> std::sync::MutexGuard<'_, PubSub> {
+        self.pubsub.lock().expect("pubsub lock poisoned")
+    }
+
+    fn acl(&self) -> std::sync::MutexGuard<'_, Acl> {
+        self.acl.lock().expect("acl lock poisoned")
+    }
+
+    /// Checks that the user of the client may run `cmd` with `args`, logging the refusal
+    /// otherwise. `context` tells whether the command runs on its own or inside EXEC.
+    fn authorize(
+        &self,
+        client: &Client,
+        cmd: &Command,
+        args: &[String],
+        context: &'static str,
+    ) -> Result<(), RedisError> {
+        let Some(username) = &client.user else {
+            return match cmd {
+                Command::Auth(_) => Ok(()),
+                _ => Err(RedisError::NoAuth),
+            };
+        };
+        let mut acl = self.acl();
+        // A command missing from the table can't be checked, so only AUTH and commands
+        // that failed to parse run without an access.
+        let Some(access) = cmd.access(args) else {
+            return match cmd {
+                Command::Auth(_) | Command::Error(_) => Ok(()),
+                _ => {
+                    let name = args.first().map(|s| s.to_lowercase()).unwrap_or_default();
+                    let denial = Denial::new(Reason::Command, &name);
+                    let err = denial.error(username);
+                    acl.log(denial, context, username, &client.info());
+                    Err(err)
+                }
+            };
+        };
+
+        acl.check(username, &access).map_err(|denial| {
+            let err = denial.error(username);
+            acl.log(denial, context, username, &client.info());
+            err
+        })
+    }
+
+    /// Authenticates the client as the user of AUTH, keeping its current user on failure.
+    fn auth(&self, client: &mut Client, auth: &Auth) -> Frame {
+        let mut acl = self.acl();
+        if auth.username.is_none() && acl.requirepass().is_empty() && acl.is_open() {
+            return Frame::from(RedisError::Custom(String::from(
+                "AUTH <password> called without any password configured for the default user. \
+                 Are you sure your configuration is correct?",
+            )));
+        }
+
+        let username = auth.username.as_deref().unwrap_or(DEFAULT_USER);
+        if !acl.authenticate(username, &auth.password) {
+            let denial = Denial::new(Reason::Auth, "AUTH");
+            acl.log(denial, "toplevel", username, &client.info());
+            return Frame::from(RedisError::WrongPass);
+        }
+
+        client.user = Some(username.to_owned());
+        Frame::SimpleString(String::from("OK"))
+    }
+
+    /// Runs an ACL subcommand, then disconnects the subscribers whose user changed in a way
+    /// that refuses them one of their channels, or was deleted.
+    fn acl_command(&self, client: &Client, cmd: &AclCommand) -> Frame {
+        let username = client.user.as_deref().unwrap_or_default();
+        let mut acl = self.acl();
+        let reply = cmd
+            .execute(&mut acl, username, self.config.aclfile.as_deref())
+            .unwrap_or_else(Frame::from);
+
+        if matches!(
+            cmd,
+            AclCommand::SetUser(..) | AclCommand::DelUser(_) | AclCommand::Load
+        ) {
+            self.pubsub()
+                .disconnect_denied(|user, channel, is_pattern| {
+                    acl.allows_channel(user, channel, is_pattern)
+                });
+        }
+
+        reply
+    }
+
+    /// Publishes the keyspace events of what just ran.
+    fn notify_keyspace_events(&self, db: &mut D) {
+        let notifications = db.drain_notifications();
+        if notifications.is_empty() {
+            return;
+        }
+
+        let flags = db.notify_flags();
+        let mut pubsub = self.pubsub();
+
+        for notification in notifications {
+            for (channel, message) in notification.messages(flags) {
+                pubsub.publish(&channel, &message);
+            }
+        }
+    }
+
+    /// Serves clients blocked on keys that the last command wrote to.
+    fn serve_blocked(&self, db: &mut D) -> Vec<Frame> {
+        self.blocked_clients().serve(db)
+    }
+
+    /// Periodically deletes expired data that no client has touched, forwarding the deletions
+    /// to replicas.
+    pub async fn active_expire_cycle(&self, sender: Arc<Sender<Frame>>) -> Result<(), Error> {
+        let mut interval = time::interval(ACTIVE_EXPIRE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let mut db = self.db.lock().await;
+            db.active_expire();
+            self.notify_keyspace_events(&mut db);
+
+            for f in db.drain_propagated() {
+                sender.send(f)?;
             }
         }
     }
@@ -134,3 +792,1063 @@ where
         Ok(())
     }
 }
+
+/// Collects the writes of the command that just ran, to forward to replicas. Unless the
+/// command rewrote itself, its own `frame` stands for its writes when the database changed
+/// since `dirty`.
+fn drain_writes(db: &mut dyn Database, frame: Option<&Frame>, dirty: u64) -> Vec<Frame> {
+    let mut propagated = db.drain_propagated();
+    match (db.drain_rewritten(), frame) {
+        (Some(frames), _) => propagated.extend(frames),
+        (None, Some(frame)) if db.dirty() > dirty => propagated.push(frame.clone()),
+        (None, _) => {}
+    }
+
+    propagated
+}
+
+/// Waits for the next message to a subscribed client, or forever if it never subscribed.
+async fn next_message(subscriber: &mut Option<Subscriber>) -> Option<Frame> {
+    match subscriber {
+        Some(subscriber) => subscriber.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        sync::{broadcast, Mutex},
+        time,
+    };
+
+    use super::RedisServer;
+    use crate::{config::Config, connection::Connection, db::KeyValueDb, frame::Frame};
+
+    /// How long a reply that is expected may take to arrive.
+    const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Starts a server on a free port, returning its address.
+    async fn start() -> SocketAddr {
+        start_server().await.0
+    }
+
+    /// Starts a server on a free port, returning its address and the server itself so that
+    /// tests can look at its state.
+    async fn start_server() -> (SocketAddr, Arc<RedisServer<KeyValueDb>>) {
+        let config = Config {
+            port: String::from("0"),
+            replicaof: None,
+            requirepass: None,
+            aclfile: None,
+        };
+        let db = Arc::new(Mutex::new(KeyValueDb::new()));
+        let server = Arc::new(RedisServer::new(config, db));
+        let listener = server.listen().await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (sender, receiver) = broadcast::channel(16);
+        let sender = Arc::new(sender);
+
+        tokio::spawn({
+            let server = Arc::clone(&server);
+            let sender = Arc::clone(&sender);
+            async move { server.active_expire_cycle(sender).await }
+        });
+
+        tokio::spawn({
+            let server = Arc::clone(&server);
+            async move {
+                // Replicas would subscribe to the sender, which fails without any receiver.
+                let _receiver = receiver;
+
+                while let Ok((stream, _)) = listener.accept().await {
+                    let server = Arc::clone(&server);
+                    let sender = Arc::clone(&sender);
+                    tokio::spawn(async move {
+                        server
+                            .handle_connection(Connection::new(stream), sender)
+                            .await
+                    });
+                }
+            }
+        });
+
+        (addr, server)
+    }
+
+    /// A client that sends one command at a time and parses the replies it gets back.
+    struct TestClient {
+        stream: TcpStream,
+        buffer: Vec<u8>,
+    }
+
+    impl TestClient {
+        async fn connect(addr: SocketAddr) -> Self {
+            TestClient {
+                stream: TcpStream::connect(addr).await.unwrap(),
+                buffer: Vec::new(),
+            }
+        }
+
+        async fn send(&mut self, args: &[&str]) {
+            self.pipeline(&[args]).await;
+        }
+
+        /// Sends a command whose arguments may not be UTF-8 and waits for its reply.
+        async fn call_raw(&mut self, args: &[&[u8]]) -> Frame {
+            let frame = Frame::Array(
+                args.iter()
+                    .map(|arg| Frame::BulkBinary(arg.to_vec()))
+                    .collect(),
+            );
+            self.stream.write_all(&frame.to_bytes()).await.unwrap();
+            self.read().await
+        }
+
+        /// Sends several commands in a single write.
+        async fn pipeline(&mut self, commands: &[&[&str]]) {
+            let bytes: Vec<u8> = commands
+                .iter()
+                .flat_map(|args| {
+                    Frame::Arrays(args.iter().map(|arg| arg.to_string()).collect()).to_bytes()
+                })
+                .collect();
+            self.stream.write_all(&bytes).await.unwrap();
+        }
+
+        /// Waits up to `timeout` for the next reply.
+        async fn read_within(&mut self, timeout: Duration) -> Option<Frame> {
+            let read = async {
+                loop {
+                    if let Some((frame, len)) = parse_reply(&self.buffer) {
+                        self.buffer.drain(..len);
+                        return frame;
+                    }
+
+                    let mut chunk = [0; 4096];
+                    let n = self.stream.read(&mut chunk).await.unwrap();
+                    assert!(n > 0, "server closed the connection");
+                    self.buffer.extend_from_slice(&chunk[..n]);
+                }
+            };
+
+            time::timeout(timeout, read).await.ok()
+        }
+
+        async fn read(&mut self) -> Frame {
+            self.read_within(REPLY_TIMEOUT)
+                .await
+                .expect("no reply from the server")
+        }
+
+        async fn call(&mut self, args: &[&str]) -> Frame {
+            self.send(args).await;
+            self.read().await
+        }
+
+        /// Checks that the server closed the connection without sending anything more.
+        async fn assert_closed(&mut self) {
+            assert!(self.buffer.is_empty());
+
+            let mut chunk = [0; 4096];
+            let n = time::timeout(REPLY_TIMEOUT, self.stream.read(&mut chunk))
+                .await
+                .expect("the server kept the connection open")
+                .unwrap_or(0);
+            assert_eq!(0, n);
+        }
+    }
+
+    /// Parses the reply at the start of `buf`, returning it with its length, or `None` if it
+    /// hasn't fully arrived.
+    fn parse_reply(buf: &[u8]) -> Option<(Frame, usize)> {
+        let end = buf.windows(2).position(|w| w == b"\r\n")?;
+        let line = std::str::from_utf8(&buf[1..end]).unwrap();
+        let mut len = end + 2;
+
+        let frame = match buf[0] {
+            b'+' => Frame::SimpleString(line.to_owned()),
+            b'-' => Frame::Error(line.to_owned()),
+            b':' => Frame::Integer(line.parse().unwrap()),
+            b'$' if line == "-1" => Frame::Null,
+            b'$' => {
+                let size: usize = line.parse().unwrap();
+                let data = buf.get(len..len + size)?;
+                buf.get(len + size..len + size + 2)?;
+                len += size + 2;
+                match String::from_utf8(data.to_vec()) {
+                    Ok(s) => Frame::BulkString(s),
+                    Err(err) => Frame::BulkBinary(err.into_bytes()),
+                }
+            }
+            b'*' if line == "-1" => Frame::NullArray,
+            b'*' => {
+                let count: usize = line.parse().unwrap();
+                let mut items = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let (item, item_len) = parse_reply(&buf[len..])?;
+                    items.push(item);
+                    len += item_len;
+                }
+                Frame::Array(items)
+            }
+            other => panic!("unexpected reply type {}", other as char),
+        };
+
+        Some((frame, len))
+    }
+
+    fn ok() -> Frame {
+        Frame::SimpleString(String::from("OK"))
+    }
+
+    fn queued() -> Frame {
+        Frame::SimpleString(String::from("QUEUED"))
+    }
+
+    fn bulk(s: &str) -> Frame {
+        Frame::BulkString(s.to_owned())
+    }
+
+    fn error(msg: &str) -> Frame {
+        Frame::Error(msg.to_owned())
+    }
+
+    fn array(items: &[&str]) -> Frame {
+        Frame::Array(items.iter().map(|item| bulk(item)).collect())
+    }
+
+    /// Checks that `client` gets no reply for a while, as when it is blocked.
+    async fn assert_blocked(client: &mut TestClient) {
+        let reply = client.read_within(Duration::from_millis(100)).await;
+        assert_eq!(None, reply);
+    }
+
+    #[tokio::test]
+    async fn test_exec_runs_queued_commands() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["MULTI"]).await);
+        assert_eq!(queued(), client.call(&["SET", "a", "1"]).await);
+        assert_eq!(queued(), client.call(&["HSET", "h", "f", "v"]).await);
+        assert_eq!(queued(), client.call(&["GET", "a"]).await);
+
+        // Nothing runs before EXEC.
+        assert_eq!(Frame::Null, other.call(&["GET", "a"]).await);
+
+        assert_eq!(
+            Frame::Array(vec![ok(), Frame::Integer(1), bulk("1")]),
+            client.call(&["EXEC"]).await
+        );
+        assert_eq!(bulk("v"), other.call(&["HGET", "h", "f"]).await);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_transaction() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        client
+            .pipeline(&[
+                &["MULTI"],
+                &["SET", "a", "1"],
+                &["SET", "a", "2"],
+                &["EXEC"],
+                &["GET", "a"],
+            ])
+            .await;
+
+        assert_eq!(ok(), client.read().await);
+        assert_eq!(queued(), client.read().await);
+        assert_eq!(queued(), client.read().await);
+        assert_eq!(Frame::Array(vec![ok(), ok()]), client.read().await);
+        assert_eq!(bulk("2"), client.read().await);
+    }
+
+    #[tokio::test]
+    async fn test_large_frames() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let value = "v".repeat(64 * 1024);
+
+        assert_eq!(ok(), client.call(&["SET", "k", &value]).await);
+        assert_eq!(bulk(&value), client.call(&["GET", "k"]).await);
+    }
+
+    #[tokio::test]
+    async fn test_vadd_large_fp32_blob() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        // 768 dimensions take 3KB, more than a single read of the connection buffer.
+        let vector: Vec<f32> = (0..768).map(|i| (i as f32 - 384.0) / 64.0).collect();
+        let blob: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+        assert_eq!(
+            Frame::Integer(1),
+            client
+                .call_raw(&[b"VADD", b"v", b"FP32", &blob, b"a", b"NOQUANT", b"METRIC", b"L2"])
+                .await
+        );
+
+        let embedding = match client.call(&["VEMB", "v", "a"]).await {
+            Frame::Array(embedding) => embedding,
+            reply => panic!("unexpected {reply:?}"),
+        };
+        let embedding: Vec<f32> = embedding
+            .iter()
+            .map(|x| match x {
+                Frame::BulkString(x) => x.parse().unwrap(),
+                x => panic!("unexpected {x:?}"),
+            })
+            .collect();
+        assert_eq!(vector, embedding);
+    }
+
+    #[tokio::test]
+    async fn test_hll_get_set_round_trip() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        // Enough elements to switch to the dense encoding, which isn't valid UTF-8.
+        for batch in 0..10 {
+            let elements: Vec<String> = (0..500).map(|i| format!("e{batch}-{i}")).collect();
+            let args: Vec<&str> = ["PFADD", "h"]
+                .into_iter()
+                .chain(elements.iter().map(String::as_str))
+                .collect();
+            client.call(&args).await;
+        }
+
+        let dense = match client.call(&["GET", "h"]).await {
+            Frame::BulkBinary(dense) => dense,
+            reply => panic!("unexpected {reply:?}"),
+        };
+        assert_eq!(b"HYLL", &dense[..4]);
+        assert_eq!(0, dense[4], "the HyperLogLog should be dense");
+
+        assert_eq!(ok(), client.call_raw(&[b"SET", b"copy", &dense]).await);
+        assert_eq!(
+            Frame::BulkBinary(dense),
+            client.call(&["GET", "copy"]).await
+        );
+
+        let count = client.call(&["PFCOUNT", "h"]).await;
+        assert!(matches!(count, Frame::Integer(n) if (4800..5200).contains(&n)));
+        assert_eq!(count, client.call(&["PFCOUNT", "copy"]).await);
+        assert_eq!(
+            Frame::Integer(1),
+            client.call(&["PFADD", "copy", "new"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queue_error_aborts_exec() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["MULTI"]).await);
+        assert_eq!(queued(), client.call(&["SET", "a", "1"]).await);
+        assert_eq!(
+            error("ERR wrong number of arguments for 'get' command"),
+            client.call(&["GET"]).await
+        );
+        assert_eq!(
+            error("EXECABORT Transaction discarded because of previous errors."),
+            client.call(&["EXEC"]).await
+        );
+
+        // The transaction is over, and none of it ran.
+        assert_eq!(Frame::Null, client.call(&["GET", "a"]).await);
+        assert_eq!(
+            error("ERR EXEC without MULTI"),
+            client.call(&["EXEC"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discard() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["MULTI"]).await);
+        assert_eq!(queued(), client.call(&["SET", "a", "1"]).await);
+        assert_eq!(ok(), client.call(&["DISCARD"]).await);
+
+        assert_eq!(Frame::Null, client.call(&["GET", "a"]).await);
+        assert_eq!(
+            error("ERR DISCARD without MULTI"),
+            client.call(&["DISCARD"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nested_multi() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["MULTI"]).await);
+        assert_eq!(
+            error("ERR MULTI calls can not be nested"),
+            client.call(&["MULTI"]).await
+        );
+
+        // The transaction carries on.
+        assert_eq!(queued(), client.call(&["SET", "a", "1"]).await);
+        assert_eq!(Frame::Array(vec![ok()]), client.call(&["EXEC"]).await);
+    }
+
+    #[tokio::test]
+    async fn test_exec_without_multi() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(
+            error("ERR EXEC without MULTI"),
+            client.call(&["EXEC"]).await
+        );
+    }
+
+    /// Runs a transaction setting `key`, returning the reply to EXEC.
+    async fn exec_set(client: &mut TestClient, key: &str) -> Frame {
+        assert_eq!(ok(), client.call(&["MULTI"]).await);
+        assert_eq!(queued(), client.call(&["SET", key, "tx"]).await);
+        client.call(&["EXEC"]).await
+    }
+
+    #[tokio::test]
+    async fn test_watch_unchanged_key() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["WATCH", "a"]).await);
+        assert_eq!(Frame::Array(vec![ok()]), exec_set(&mut client, "a").await);
+    }
+
+    #[tokio::test]
+    async fn test_watch_key_changed_by_another_client() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["WATCH", "a"]).await);
+        assert_eq!(ok(), other.call(&["SET", "a", "other"]).await);
+
+        assert_eq!(Frame::NullArray, exec_set(&mut client, "a").await);
+        assert_eq!(bulk("other"), client.call(&["GET", "a"]).await);
+
+        // EXEC releases the watched keys.
+        assert_eq!(Frame::Array(vec![ok()]), exec_set(&mut client, "a").await);
+    }
+
+    #[tokio::test]
+    async fn test_watch_key_that_expires() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["SET", "a", "1", "PX", "50"]).await);
+        assert_eq!(ok(), client.call(&["WATCH", "a"]).await);
+        time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(Frame::NullArray, exec_set(&mut client, "a").await);
+    }
+
+    #[tokio::test]
+    async fn test_watch_key_that_is_flushed() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["SET", "a", "1"]).await);
+        assert_eq!(ok(), client.call(&["WATCH", "a"]).await);
+        assert_eq!(ok(), other.call(&["FLUSHDB"]).await);
+
+        assert_eq!(Frame::NullArray, exec_set(&mut client, "a").await);
+    }
+
+    #[tokio::test]
+    async fn test_unwatch() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["WATCH", "a"]).await);
+        assert_eq!(ok(), other.call(&["SET", "a", "other"]).await);
+        assert_eq!(ok(), client.call(&["UNWATCH"]).await);
+
+        assert_eq!(Frame::Array(vec![ok()]), exec_set(&mut client, "a").await);
+    }
+
+    #[tokio::test]
+    async fn test_discard_unwatches() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["WATCH", "a"]).await);
+        assert_eq!(ok(), other.call(&["SET", "a", "other"]).await);
+        assert_eq!(ok(), client.call(&["MULTI"]).await);
+        assert_eq!(ok(), client.call(&["DISCARD"]).await);
+
+        assert_eq!(Frame::Array(vec![ok()]), exec_set(&mut client, "a").await);
+    }
+
+    #[tokio::test]
+    async fn test_bzpopmin_woken_by_zadd() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        client.send(&["BZPOPMIN", "z", "0"]).await;
+        assert_blocked(&mut client).await;
+
+        assert_eq!(
+            Frame::Integer(2),
+            other.call(&["ZADD", "z", "2", "b", "1", "a"]).await
+        );
+        assert_eq!(array(&["z", "a", "1"]), client.read().await);
+        assert_eq!(Frame::Integer(1), other.call(&["ZCARD", "z"]).await);
+    }
+
+    #[tokio::test]
+    async fn test_bzmpop_woken_by_any_key() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        client.send(&["BZMPOP", "0", "2", "z1", "z2", "MIN"]).await;
+        assert_blocked(&mut client).await;
+
+        assert_eq!(
+            Frame::Integer(1),
+            other.call(&["ZADD", "z2", "1", "a"]).await
+        );
+        assert_eq!(
+            Frame::Array(vec![bulk("z2"), Frame::Array(vec![array(&["a", "1"])])]),
+            client.read().await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocking_pop_timeout() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(
+            Frame::NullArray,
+            client.call(&["BZPOPMIN", "z", "0.05"]).await
+        );
+        assert_eq!(
+            Frame::NullArray,
+            client.call(&["BZMPOP", "0.05", "1", "z", "MAX"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocked_clients_served_in_order() {
+        let addr = start().await;
+        let mut first = TestClient::connect(addr).await;
+        let mut second = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        first.send(&["BZPOPMIN", "z", "0"]).await;
+        assert_blocked(&mut first).await;
+        second.send(&["BZPOPMIN", "z", "0"]).await;
+        assert_blocked(&mut second).await;
+
+        // One element only serves the client that blocked first.
+        assert_eq!(
+            Frame::Integer(1),
+            other.call(&["ZADD", "z", "1", "a"]).await
+        );
+        assert_eq!(array(&["z", "a", "1"]), first.read().await);
+        assert_blocked(&mut second).await;
+
+        assert_eq!(
+            Frame::Integer(1),
+            other.call(&["ZADD", "z", "2", "b"]).await
+        );
+        assert_eq!(array(&["z", "b", "2"]), second.read().await);
+    }
+
+    #[tokio::test]
+    async fn test_disconnected_blocked_client_is_dropped() {
+        let addr = start().await;
+        let mut gone = TestClient::connect(addr).await;
+        let mut waiting = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        gone.send(&["BZPOPMIN", "z", "0"]).await;
+        assert_blocked(&mut gone).await;
+        waiting.send(&["BZPOPMIN", "z", "0"]).await;
+        assert_blocked(&mut waiting).await;
+        drop(gone);
+        time::sleep(Duration::from_millis(50)).await;
+
+        // The element goes to the client still waiting instead of being lost.
+        assert_eq!(
+            Frame::Integer(1),
+            other.call(&["ZADD", "z", "1", "a"]).await
+        );
+        assert_eq!(array(&["z", "a", "1"]), waiting.read().await);
+
+        // With nobody left waiting, elements stay in the set.
+        assert_eq!(
+            Frame::Integer(1),
+            other.call(&["ZADD", "z", "2", "b"]).await
+        );
+        assert_eq!(Frame::Integer(1), other.call(&["ZCARD", "z"]).await);
+    }
+
+    fn xread_reply(key: &str, id: &str, field: &str, value: &str) -> Frame {
+        Frame::Array(vec![Frame::Array(vec![
+            bulk(key),
+            Frame::Array(vec![Frame::Array(vec![bulk(id), array(&[field, value])])]),
+        ])])
+    }
+
+    #[tokio::test]
+    async fn test_xread_block_woken_by_xadd() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        assert_eq!(
+            bulk("1-1"),
+            other.call(&["XADD", "s", "1-1", "f", "old"]).await
+        );
+        client
+            .send(&["XREAD", "BLOCK", "0", "STREAMS", "s", "$"])
+            .await;
+        assert_blocked(&mut client).await;
+
+        // Only the entry added while blocked is returned.
+        assert_eq!(
+            bulk("1-2"),
+            other.call(&["XADD", "s", "1-2", "f", "new"]).await
+        );
+        assert_eq!(xread_reply("s", "1-2", "f", "new"), client.read().await);
+    }
+
+    #[tokio::test]
+    async fn test_xread_block_wakes_every_reader() {
+        let addr = start().await;
+        let mut first = TestClient::connect(addr).await;
+        let mut second = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        first
+            .send(&["XREAD", "BLOCK", "0", "STREAMS", "s", "$"])
+            .await;
+        assert_blocked(&mut first).await;
+        second
+            .send(&["XREAD", "BLOCK", "0", "STREAMS", "s", "0"])
+            .await;
+        assert_blocked(&mut second).await;
+
+        // Reading does not consume entries, so one entry serves both clients.
+        assert_eq!(
+            bulk("1-1"),
+            other.call(&["XADD", "s", "1-1", "f", "v"]).await
+        );
+        assert_eq!(xread_reply("s", "1-1", "f", "v"), first.read().await);
+        assert_eq!(xread_reply("s", "1-1", "f", "v"), second.read().await);
+    }
+
+    #[tokio::test]
+    async fn test_xread_block_timeout() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(
+            Frame::NullArray,
+            client
+                .call(&["XREAD", "BLOCK", "50", "STREAMS", "s", "$"])
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disconnected_xread_client_is_dropped() {
+        let (addr, server) = start_server().await;
+        let mut gone = TestClient::connect(addr).await;
+        let mut other = TestClient::connect(addr).await;
+
+        gone.send(&["XREAD", "BLOCK", "0", "STREAMS", "s", "$"])
+            .await;
+        assert_blocked(&mut gone).await;
+        assert!(!server.blocked_clients().is_empty());
+        drop(gone);
+        time::sleep(Duration::from_millis(50)).await;
+
+        // The client stops waiting as soon as it disconnects.
+        assert!(server.blocked_clients().is_empty());
+        assert_eq!(
+            bulk("1-1"),
+            other.call(&["XADD", "s", "1-1", "f", "v"]).await
+        );
+    }
+
+    fn subscription(kind: &str, name: &str, count: i64) -> Frame {
+        Frame::Array(vec![bulk(kind), bulk(name), Frame::Integer(count)])
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(
+            Frame::SimpleString(String::from("PONG")),
+            client.call(&["PING"]).await
+        );
+        assert_eq!(bulk("hello"), client.call(&["PING", "hello"]).await);
+    }
+
+    #[tokio::test]
+    async fn test_subscribed_mode_allows_only_some_commands() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(
+            subscription("subscribe", "news", 1),
+            client.call(&["SUBSCRIBE", "news"]).await
+        );
+        assert_eq!(array(&["pong", ""]), client.call(&["PING"]).await);
+        assert_eq!(
+            array(&["pong", "hello"]),
+            client.call(&["PING", "hello"]).await
+        );
+        assert_eq!(
+            error(
+                "ERR Can't execute 'get': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT are \
+                 allowed in this context"
+            ),
+            client.call(&["GET", "k"]).await
+        );
+        assert_eq!(
+            subscription("psubscribe", "news.*", 2),
+            client.call(&["PSUBSCRIBE", "news.*"]).await
+        );
+        assert_eq!(
+            subscription("unsubscribe", "news", 1),
+            client.call(&["UNSUBSCRIBE", "news"]).await
+        );
+        assert_eq!(
+            subscription("punsubscribe", "news.*", 0),
+            client.call(&["PUNSUBSCRIBE", "news.*"]).await
+        );
+
+        // Without subscriptions left, the client is back to normal.
+        assert_eq!(
+            Frame::SimpleString(String::from("PONG")),
+            client.call(&["PING"]).await
+        );
+        assert_eq!(Frame::Null, client.call(&["GET", "k"]).await);
+    }
+
+    #[tokio::test]
+    async fn test_pattern_messages() {
+        let addr = start().await;
+        let mut subscriber = TestClient::connect(addr).await;
+        let mut publisher = TestClient::connect(addr).await;
+
+        assert_eq!(
+            subscription("psubscribe", "news.*", 1),
+            subscriber.call(&["PSUBSCRIBE", "news.*"]).await
+        );
+
+        assert_eq!(
+            Frame::Integer(1),
+            publisher.call(&["PUBLISH", "news.tech", "hello"]).await
+        );
+        assert_eq!(
+            array(&["pmessage", "news.*", "news.tech", "hello"]),
+            subscriber.read().await
+        );
+
+        assert_eq!(
+            Frame::Integer(0),
+            publisher.call(&["PUBLISH", "weather", "rain"]).await
+        );
+        assert_blocked(&mut subscriber).await;
+    }
+
+    #[tokio::test]
+    async fn test_quit() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(ok(), client.call(&["QUIT"]).await);
+        client.assert_closed().await;
+    }
+
+    #[tokio::test]
+    async fn test_quit_when_subscribed() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+
+        assert_eq!(
+            subscription("subscribe", "news", 1),
+            client.call(&["SUBSCRIBE", "news"]).await
+        );
+        assert_eq!(ok(), client.call(&["QUIT"]).await);
+        client.assert_closed().await;
+    }
+
+    #[tokio::test]
+    async fn test_quit_without_authentication() {
+        let addr = start().await;
+        let mut admin = TestClient::connect(addr).await;
+        assert_eq!(
+            ok(),
+            admin
+                .call(&["CONFIG", "SET", "requirepass", "secret"])
+                .await
+        );
+
+        let mut client = TestClient::connect(addr).await;
+        assert_eq!(
+            error("NOAUTH Authentication required."),
+            client.call(&["GET", "k"]).await
+        );
+        assert_eq!(ok(), client.call(&["QUIT"]).await);
+        client.assert_closed().await;
+    }
+
+    #[tokio::test]
+    async fn test_revoked_subscribers_are_disconnected() {
+        let addr = start().await;
+        let mut admin = TestClient::connect(addr).await;
+        for (user, rules) in [
+            ("alice", ["&news.*", "&weather"]),
+            ("bob", ["&news.*", "&sport"]),
+        ] {
+            let mut args = vec!["ACL", "SETUSER", user, "on", ">pw", "+@all"];
+            args.extend(rules);
+            assert_eq!(ok(), admin.call(&args).await);
+        }
+
+        let mut subscribers = Vec::new();
+        for (user, kind, name) in [
+            ("alice", "psubscribe", "news.*"),
+            ("alice", "subscribe", "weather"),
+            ("bob", "subscribe", "sport"),
+        ] {
+            let mut client = TestClient::connect(addr).await;
+            assert_eq!(ok(), client.call(&["AUTH", user, "pw"]).await);
+            assert_eq!(
+                subscription(kind, name, 1),
+                client.call(&[&kind.to_uppercase(), name]).await
+            );
+            subscribers.push(client);
+        }
+
+        // Only the subscription to a channel that is no longer allowed is dropped.
+        assert_eq!(
+            ok(),
+            admin
+                .call(&["ACL", "SETUSER", "alice", "resetchannels", "&news.*"])
+                .await
+        );
+        subscribers[1].assert_closed().await;
+        assert_eq!(
+            Frame::Integer(1),
+            admin.call(&["PUBLISH", "news.tech", "hi"]).await
+        );
+        assert_eq!(
+            array(&["pmessage", "news.*", "news.tech", "hi"]),
+            subscribers[0].read().await
+        );
+
+        // A pattern has to stay allowed as it is.
+        assert_eq!(
+            ok(),
+            admin
+                .call(&["ACL", "SETUSER", "alice", "resetchannels", "&news.tech"])
+                .await
+        );
+        subscribers[0].assert_closed().await;
+
+        assert_eq!(
+            Frame::Integer(1),
+            admin.call(&["ACL", "DELUSER", "bob"]).await
+        );
+        subscribers[2].assert_closed().await;
+        assert_eq!(
+            Frame::Integer(0),
+            admin.call(&["PUBLISH", "sport", "goal"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_keyevent() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut subscriber = TestClient::connect(addr).await;
+
+        // Evicted events can be enabled, though none are ever published.
+        assert_eq!(
+            ok(),
+            client
+                .call(&["CONFIG", "SET", "notify-keyspace-events", "Exe"])
+                .await
+        );
+        assert_eq!(
+            array(&["notify-keyspace-events", "xeE"]),
+            client
+                .call(&["CONFIG", "GET", "notify-keyspace-events"])
+                .await
+        );
+
+        assert_eq!(
+            subscription("subscribe", "__keyevent@0__:expired", 1),
+            subscriber
+                .call(&["SUBSCRIBE", "__keyevent@0__:expired"])
+                .await
+        );
+        assert_eq!(ok(), client.call(&["SET", "k", "v", "PX", "50"]).await);
+
+        // The key expires without being accessed again.
+        assert_eq!(
+            array(&["message", "__keyevent@0__:expired", "k"]),
+            subscriber.read().await
+        );
+        assert_blocked(&mut subscriber).await;
+        assert_eq!(Frame::Null, client.call(&["GET", "k"]).await);
+    }
+
+    #[tokio::test]
+    async fn test_pubsub_introspection() {
+        let addr = start().await;
+        let mut client = TestClient::connect(addr).await;
+        let mut subscribers = vec![
+            TestClient::connect(addr).await,
+            TestClient::connect(addr).await,
+        ];
+
+        for (i, kind, name, count) in [
+            (0, "SUBSCRIBE", "news.tech", 1),
+            (0, "SUBSCRIBE", "news.sport", 2),
+            (0, "SUBSCRIBE", "weather", 3),
+            (0, "PSUBSCRIBE", "news.*", 4),
+            (1, "SUBSCRIBE", "news.tech", 1),
+            (1, "PSUBSCRIBE", "news.*", 2),
+            (1, "PSUBSCRIBE", "w*", 3),
+        ] {
+            assert_eq!(
+                subscription(&kind.to_lowercase(), name, count),
+                subscribers[i].call(&[kind, name]).await
+            );
+        }
+
+        // Patterns are counted apart from channels, once however many clients use them.
+        assert_eq!(
+            array(&["news.sport", "news.tech", "weather"]),
+            client.call(&["PUBSUB", "CHANNELS"]).await
+        );
+        assert_eq!(
+            array(&["news.sport", "news.tech"]),
+            client.call(&["PUBSUB", "CHANNELS", "news.*"]).await
+        );
+        assert_eq!(
+            Frame::Array(Vec::new()),
+            client.call(&["PUBSUB", "CHANNELS", "w"]).await
+        );
+        assert_eq!(
+            Frame::Array(vec![
+                bulk("news.tech"),
+                Frame::Integer(2),
+                bulk("weather"),
+                Frame::Integer(1),
+                bulk("news.*"),
+                Frame::Integer(0),
+            ]),
+            client
+                .call(&["PUBSUB", "NUMSUB", "news.tech", "weather", "news.*"])
+                .await
+        );
+        assert_eq!(
+            Frame::Array(Vec::new()),
+            client.call(&["PUBSUB", "NUMSUB"]).await
+        );
+        assert_eq!(Frame::Integer(2), client.call(&["PUBSUB", "NUMPAT"]).await);
+
+        // Subscriptions go away with the clients that made them.
+        assert_eq!(
+            subscription("punsubscribe", "w*", 2),
+            subscribers[1].call(&["PUNSUBSCRIBE", "w*"]).await
+        );
+        assert_eq!(Frame::Integer(1), client.call(&["PUBSUB", "NUMPAT"]).await);
+        drop(subscribers.remove(0));
+        time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            array(&["news.tech"]),
+            client.call(&["PUBSUB", "CHANNELS", "*"]).await
+        );
+        assert_eq!(
+            Frame::Array(vec![bulk("news.tech"), Frame::Integer(1)]),
+            client.call(&["PUBSUB", "NUMSUB", "news.tech"]).await
+        );
+        assert_eq!(Frame::Integer(1), client.call(&["PUBSUB", "NUMPAT"]).await);
+
+        assert_eq!(
+            error("ERR wrong number of arguments for 'pubsub|channels' command"),
+            client.call(&["PUBSUB", "CHANNELS", "a", "b"]).await
+        );
+        assert_eq!(
+            error("ERR wrong number of arguments for 'pubsub|numpat' command"),
+            client.call(&["PUBSUB", "NUMPAT", "a"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_is_disconnected() {
+        let addr = start().await;
+        let mut subscriber = TestClient::connect(addr).await;
+        let mut publisher = TestClient::connect(addr).await;
+        assert_eq!(
+            subscription("subscribe", "c", 1),
+            subscriber.call(&["SUBSCRIBE", "c"]).await
+        );
+
+        // The subscriber reads nothing, so once the socket buffers are full its queue fills
+        // up and the next message drops it.
+        let message = "m".repeat(16 * 1024);
+        let mut delivered = 0;
+        while publisher.call(&["PUBLISH", "c", &message]).await == Frame::Integer(1) {
+            delivered += 1;
+            assert!(delivered < 100_000, "the subscriber was never dropped");
+        }
+        assert_eq!(
+            Frame::Array(vec![bulk("c"), Frame::Integer(0)]),
+            publisher.call(&["PUBSUB", "NUMSUB", "c"]).await
+        );
+        assert_eq!(
+            Frame::Integer(0),
+            publisher.call(&["PUBLISH", "c", "late"]).await
+        );
+
+        // What was queued still arrives before the connection closes.
+        let frame_len = format!("*3\r\n$7\r\nmessage\r\n$1\r\nc\r\n$16384\r\n{message}\r\n").len();
+        let mut received = subscriber.buffer.len();
+        let mut chunk = [0; 64 * 1024];
+        loop {
+            let n = time::timeout(REPLY_TIMEOUT, subscriber.stream.read(&mut chunk))
+                .await
+                .expect("the server kept the connection open")
+                .unwrap_or(0);
+            if n == 0 {
+                break;
+            }
+            received += n;
+        }
+        assert_eq!(delivered * frame_len, received);
+    }
+}
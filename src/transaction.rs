@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use crate::{cmd::Command, db::Database, error::RedisError, frame::Frame, module};
+
+/// The commands a client has queued since MULTI, run by EXEC as a single unit.
+#[derive(Debug, Default)]
+pub(crate) struct Transaction {
+    queued: Vec<(Command, Frame)>,
+    /// Set once a command fails to queue, in which case EXEC discards the whole transaction.
+    aborted: bool,
+}
+
+impl Transaction {
+    /// Queues `cmd` along with the frame it was parsed from, returning the reply to send back.
+    pub(crate) fn queue(&mut self, cmd: Command, frame: Frame) -> Frame {
+        match cmd {
+            Command::Error(err) => {
+                self.aborted = true;
+                Frame::from(err)
+            }
+            Command::Replconf(_)
+            | Command::Psync(_)
+            | Command::Subscription(_)
+            | Command::Auth(_) => self.reject(),
+            Command::Module(call) if call.flags() & module::NO_MULTI != 0 => self.reject(),
+            cmd => {
+                self.queued.push((cmd, frame));
+                Frame::SimpleString(String::from("QUEUED"))
+            }
+        }
+    }
+
+    fn reject(&mut self) -> Frame {
+        self.aborted = true;
+        Frame::from(RedisError::Custom(String::from(
+            "Command not allowed inside a transaction",
+        )))
+    }
+
+    /// Returns the queued commands in order, or an error if one of them failed to queue.
+    pub(crate) fn commands(self) -> Result<Vec<(Command, Frame)>, RedisError> {
+        if self.aborted {
+            return Err(RedisError::ExecAbort);
+        }
+
+        Ok(self.queued)
+    }
+}
+
+/// The keys a client watches, each with the version it had when the client started watching
+/// it. EXEC fails if any of them has changed since.
+#[derive(Debug, Default)]
+pub(crate) struct WatchedKeys {
+    keys: HashMap<String, u64>,
+}
+
+impl WatchedKeys {
+    /// Watches `key`, keeping the original version if it is already watched.
+    pub(crate) fn watch(&mut self, db: &mut dyn Database, key: &str) {
+        if !self.keys.contains_key(key) {
+            self.keys.insert(key.to_owned(), db.watch(key));
+        }
+    }
+
+    pub(crate) fn is_modified(&self, db: &mut dyn Database) -> bool {
+        self.keys
+            .iter()
+            .any(|(key, version)| db.version(key) != *version)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self, db: &mut dyn Database) {
+        for key in self.keys.keys() {
+            db.unwatch(key);
+        }
+        self.keys.clear();
+    }
+}
@@ -0,0 +1,136 @@
+//! The generic cell rate algorithm, which rate limits with a single timestamp per limiter.
+//!
+//! Each request moves the theoretical arrival time (TAT) forward by the emission interval,
+//! `period / count`, times its quantity. A request is allowed as long as the new TAT is no
+//! further than the burst tolerance, `emission interval * (max_burst + 1)`, from now. Times
+//! are nanoseconds since the Unix epoch.
+
+/// The rate of a limiter: `count` requests per `period`, with bursts of up to `max_burst`
+/// requests on top of the first one.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Rate {
+    pub(crate) max_burst: i64,
+    pub(crate) count: i64,
+    pub(crate) period: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Decision {
+    pub(crate) limited: bool,
+    /// Maximum number of requests allowed at once.
+    pub(crate) limit: i64,
+    pub(crate) remaining: i64,
+    /// How long to wait before the request would be allowed, or `None` if it was allowed or
+    /// never will be because it asks for more than the limit.
+    pub(crate) retry_after: Option<i64>,
+    /// How long until the limiter is back to its full limit.
+    pub(crate) reset_after: i64,
+    /// The TAT to store when the request was allowed.
+    pub(crate) tat: Option<i64>,
+}
+
+/// Decides whether `quantity` requests are allowed at `now`, given the stored TAT if any.
+pub(crate) fn throttle(rate: Rate, quantity: i64, tat: Option<i64>, now: i64) -> Decision {
+    let (now, quantity) = (now as i128, quantity as i128);
+    let emission = (rate.period as i128 / rate.count as i128).max(1);
+    let tolerance = emission * (rate.max_burst as i128 + 1);
+    let increment = emission * quantity;
+
+    let tat = tat.map_or(now, |tat| tat as i128);
+    let new_tat = tat.max(now) + increment;
+    let diff = now - (new_tat - tolerance);
+
+    let (limited, retry_after, ttl, stored) = match diff < 0 {
+        true => {
+            let retry_after = (increment <= tolerance).then_some(-diff);
+            (true, retry_after, tat - now, None)
+        }
+        false => (false, None, new_tat - now, Some(new_tat)),
+    };
+
+    let next = tolerance - ttl;
+    let remaining = match next > -emission {
+        true => next / emission,
+        false => 0,
+    };
+
+    let clamp = |n: i128| n.clamp(0, i64::MAX as i128) as i64;
+    Decision {
+        limited,
+        limit: rate.max_burst.saturating_add(1),
+        remaining: clamp(remaining),
+        retry_after: retry_after.map(clamp),
+        reset_after: clamp(ttl),
+        tat: stored.map(clamp),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{throttle, Decision, Rate};
+
+    const SECOND: i64 = 1_000_000_000;
+
+    #[test]
+    fn test_throttle_burst() {
+        // One request every 10 seconds, with one more in a burst.
+        let rate = Rate {
+            max_burst: 1,
+            count: 1,
+            period: 10 * SECOND,
+        };
+        let now = 1_700_000_000 * SECOND;
+
+        let first = throttle(rate, 1, None, now);
+        assert_eq!(
+            Decision {
+                limited: false,
+                limit: 2,
+                remaining: 1,
+                retry_after: None,
+                reset_after: 10 * SECOND,
+                tat: Some(now + 10 * SECOND),
+            },
+            first
+        );
+
+        let second = throttle(rate, 1, first.tat, now);
+        assert!(!second.limited);
+        assert_eq!(0, second.remaining);
+        assert_eq!(20 * SECOND, second.reset_after);
+
+        let third = throttle(rate, 1, second.tat, now);
+        assert!(third.limited);
+        assert_eq!(Some(10 * SECOND), third.retry_after);
+        assert_eq!(20 * SECOND, third.reset_after);
+        assert_eq!(None, third.tat);
+
+        // One request is allowed again after one emission interval.
+        let later = throttle(rate, 1, second.tat, now + 10 * SECOND);
+        assert!(!later.limited);
+        assert_eq!(0, later.remaining);
+    }
+
+    #[test]
+    fn test_throttle_quantity() {
+        let rate = Rate {
+            max_burst: 4,
+            count: 5,
+            period: SECOND,
+        };
+
+        let decision = throttle(rate, 3, None, 0);
+        assert!(!decision.limited);
+        assert_eq!(2, decision.remaining);
+
+        let decision = throttle(rate, 3, decision.tat, 0);
+        assert!(decision.limited);
+        assert_eq!(Some(SECOND / 5), decision.retry_after);
+
+        // Asking for more than the limit can never succeed.
+        let decision = throttle(rate, 6, None, 0);
+        assert!(decision.limited);
+        assert_eq!(None, decision.retry_after);
+        assert_eq!(5, decision.remaining);
+    }
+}
@@ -0,0 +1,117 @@
+/// Matches `s` against a Redis-style glob pattern supporting `*`, `?`, `[...]` and `\` escapes.
+pub(crate) fn matches(pattern: &str, s: &str) -> bool {
+    match_bytes(pattern.as_bytes(), s.as_bytes(), false)
+}
+
+fn match_bytes(mut p: &[u8], mut s: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+
+    while let Some(&c) = p.first() {
+        match c {
+            b'*' => {
+                while p.len() > 1 && p[1] == b'*' {
+                    p = &p[1..];
+                }
+
+                if p.len() == 1 {
+                    return true;
+                }
+
+                return (0..=s.len()).any(|i| match_bytes(&p[1..], &s[i..], nocase));
+            }
+            b'?' => {
+                if s.is_empty() {
+                    return false;
+                }
+                s = &s[1..];
+            }
+            b'[' => {
+                let Some(&ch) = s.first() else {
+                    return false;
+                };
+
+                p = &p[1..];
+                let negate = p.first() == Some(&b'^');
+                if negate {
+                    p = &p[1..];
+                }
+
+                let mut found = false;
+                loop {
+                    match p {
+                        [] => break,
+                        [b']', ..] => break,
+                        [b'\\', e, rest @ ..] => {
+                            found |= eq(*e, ch);
+                            p = rest;
+                        }
+                        [lo, b'-', hi, rest @ ..] if *hi != b']' => {
+                            let (lo, hi) = if lo <= hi { (*lo, *hi) } else { (*hi, *lo) };
+                            let (c, lo, hi) = if nocase {
+                                (
+                                    ch.to_ascii_lowercase(),
+                                    lo.to_ascii_lowercase(),
+                                    hi.to_ascii_lowercase(),
+                                )
+                            } else {
+                                (ch, lo, hi)
+                            };
+                            found |= c >= lo && c <= hi;
+                            p = rest;
+                        }
+                        [e, rest @ ..] => {
+                            found |= eq(*e, ch);
+                            p = rest;
+                        }
+                    }
+                }
+
+                if found == negate {
+                    return false;
+                }
+                s = &s[1..];
+            }
+            b'\\' if p.len() > 1 => {
+                p = &p[1..];
+                if !matches!(s.first(), Some(&ch) if eq(p[0], ch)) {
+                    return false;
+                }
+                s = &s[1..];
+            }
+            _ => {
+                if !matches!(s.first(), Some(&ch) if eq(c, ch)) {
+                    return false;
+                }
+                s = &s[1..];
+            }
+        }
+
+        p = p.get(1..).unwrap_or_default();
+    }
+
+    s.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::matches;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*", "anything"));
+        assert!(matches("h?llo", "hello"));
+        assert!(matches("h*llo", "heeeello"));
+        assert!(matches("h[ae]llo", "hallo"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(matches("h[a-b]llo", "hbllo"));
+        assert!(matches("news.\\*", "news.*"));
+        assert!(!matches("news.\\*", "news.a"));
+        assert!(!matches("user:*:name", "user:1:email"));
+    }
+}
@@ -1,10 +1,14 @@
-pub(crate) fn decode(hex: &str) -> Vec<u8> {
+pub fn decode(hex: &str) -> Vec<u8> {
     hex.as_bytes()
         .chunks_exact(2)
         .map(|pair| val(pair[0]) << 4 | val(pair[1]))
         .collect()
 }
 
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
 fn val(c: u8) -> u8 {
     match c {
         b'A'..=b'F' => c - b'A' + 10,
@@ -16,7 +20,7 @@ fn val(c: u8) -> u8 {
 
 #[cfg(test)]
 mod test {
-    use super::decode;
+    use super::{decode, encode};
 
     #[test]
     fn test_decode() {
@@ -24,5 +28,6 @@ mod test {
         let s = String::from("Hello World");
 
         assert_eq!(s, String::from_utf8(decode(hex).to_vec()).unwrap());
+        assert_eq!(hex, encode(s.as_bytes()));
     }
 }
@@ -1,2 +1,8 @@
+pub mod gcra;
+pub mod glob;
+pub mod hash;
 pub mod hex;
+pub mod num;
+pub mod rand;
+pub mod sha256;
 pub mod time;
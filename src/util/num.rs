@@ -0,0 +1,72 @@
+use crate::error::RedisError;
+
+pub(crate) fn parse_int(s: &str) -> Result<i64, RedisError> {
+    s.parse::<i64>().map_err(|_| RedisError::NotInteger)
+}
+
+/// Parses a float the way Redis does: `inf`, `+inf` and `-inf` are accepted, NaN is not.
+pub(crate) fn parse_float(s: &str) -> Result<f64, RedisError> {
+    let f = match s.to_lowercase().as_str() {
+        "inf" | "+inf" => f64::INFINITY,
+        "-inf" => f64::NEG_INFINITY,
+        _ => s.parse::<f64>().map_err(|_| RedisError::NotFloat)?,
+    };
+
+    if f.is_nan() || s.trim() != s || s.is_empty() {
+        return Err(RedisError::NotFloat);
+    }
+
+    Ok(f)
+}
+
+/// Formats a float like `%.17g` does, but with the shortest digits that read back as the same
+/// float, as Redis does. Exponents below -4 or from 17 on use scientific notation.
+pub(crate) fn format_float(f: f64) -> String {
+    if f == f64::INFINITY {
+        return String::from("inf");
+    } else if f == f64::NEG_INFINITY {
+        return String::from("-inf");
+    }
+
+    let scientific = format!("{f:e}");
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("floats are formatted with an exponent");
+    let exponent: i32 = exponent.parse().expect("exponents are integers");
+
+    if (-4..17).contains(&exponent) {
+        format!("{f}")
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{mantissa}e{sign}{:02}", exponent.abs())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_float, parse_float};
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(Ok(1.5), parse_float("1.5"));
+        assert_eq!(Ok(f64::NEG_INFINITY), parse_float("-inf"));
+        assert!(parse_float("nan").is_err());
+        assert!(parse_float("abc").is_err());
+    }
+
+    #[test]
+    fn test_format_float() {
+        assert_eq!("10.5", format_float(10.5));
+        assert_eq!("3", format_float(3.0));
+        assert_eq!("inf", format_float(f64::INFINITY));
+        assert_eq!("0.1", format_float(0.1));
+        assert_eq!("0.0001", format_float(0.0001));
+        assert_eq!("1.5e-05", format_float(0.000015));
+        assert_eq!("12345678901234568", format_float(12345678901234568.0));
+        assert_eq!("1e+17", format_float(1e17));
+        assert_eq!("1e+300", format_float(1e300));
+        assert_eq!("-2.5e+100", format_float(-2.5e100));
+        assert_eq!("1.7976931348623157e+308", format_float(f64::MAX));
+        assert_eq!("5e-324", format_float(f64::MIN_POSITIVE * f64::EPSILON));
+    }
+}
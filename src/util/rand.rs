@@ -0,0 +1,81 @@
+use std::{
+    cell::Cell,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+thread_local! {
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+
+    // The state of xorshift must never be zero.
+    nanos | 1
+}
+
+/// Returns a pseudo-random number using xorshift64*. Not suitable for cryptography.
+pub(crate) fn random_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    })
+}
+
+/// Returns a pseudo-random number in `0..n`. `n` must be greater than zero.
+pub(crate) fn random_range(n: usize) -> usize {
+    (random_u64() % n as u64) as usize
+}
+
+/// Picks `count` random items. A positive count returns distinct items, at most all of them,
+/// while a negative count returns exactly `-count` items that may repeat.
+pub(crate) fn sample<T: Clone>(mut items: Vec<T>, count: i64) -> Vec<T> {
+    if items.is_empty() {
+        return items;
+    }
+
+    if count < 0 {
+        return (0..count.unsigned_abs())
+            .map(|_| items[random_range(items.len())].clone())
+            .collect();
+    }
+
+    let n = (count as usize).min(items.len());
+    for i in 0..n {
+        let j = i + random_range(items.len() - i);
+        items.swap(i, j);
+    }
+    items.truncate(n);
+
+    items
+}
+
+#[cfg(test)]
+mod test {
+    use super::{random_range, sample};
+
+    #[test]
+    fn test_random_range() {
+        for _ in 0..1000 {
+            assert!(random_range(7) < 7);
+        }
+    }
+
+    #[test]
+    fn test_sample() {
+        let items: Vec<i32> = (0..10).collect();
+
+        let mut distinct = sample(items.clone(), 20);
+        distinct.sort();
+        assert_eq!(items, distinct);
+
+        assert_eq!(25, sample(items, -25).len());
+    }
+}
@@ -1,4 +1,4 @@
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub(crate) fn current_time_with_seconds(seconds: u64) -> SystemTime {
     SystemTime::now() + Duration::from_secs(seconds)
@@ -12,11 +12,28 @@ pub(crate) fn is_expired(expiry_time: SystemTime) -> bool {
     SystemTime::now().duration_since(expiry_time).is_ok()
 }
 
+pub(crate) fn to_unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn from_unix_millis(milliseconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(milliseconds)
+}
+
+/// Milliseconds left until `time`, or zero if it has already passed.
+pub(crate) fn millis_until(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::now())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod test {
     use std::time::SystemTime;
 
-    use super::is_expired;
+    use super::{from_unix_millis, is_expired, to_unix_millis};
 
     use super::current_time_with_milliseconds;
 
@@ -44,4 +61,11 @@ mod test {
 
         assert!(!is_expired(t))
     }
+
+    #[test]
+    fn test_unix_millis_round_trip() {
+        let t = from_unix_millis(1_700_000_000_123);
+
+        assert_eq!(1_700_000_000_123, to_unix_millis(t));
+    }
 }